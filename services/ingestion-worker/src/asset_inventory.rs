@@ -0,0 +1,183 @@
+//! Asset Inventory
+//!
+//! Lists the non-code files architects ask about (configs, schemas, large
+//! images) without reading them, for the graph's Asset nodes. The inventory
+//! is opt-in through the `inventory_assets` job option, since most graphs
+//! don't need a node per config file, and capped at `MAX_ASSETS`.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+use crate::path_filter::PathFilter;
+
+/// Job option turning the inventory on
+pub const INVENTORY_OPTION: &str = "inventory_assets";
+
+/// Assets past this many (in path order) are left out
+pub const MAX_ASSETS: usize = 5_000;
+
+/// Smaller images are icons and sprites nobody asks about
+pub const IMAGE_MIN_BYTES: u64 = 100 * 1024;
+
+/// Config, schema and data extensions listed whatever their size
+const ASSET_EXTENSIONS: &[&str] = &["json", "yaml", "yml", "toml", "proto", "sql", "graphql", "gql"];
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "bmp", "tiff", "svg", "ico"];
+
+/// Directories skipped like in the source walk
+const IGNORED_DIRS: &[&str] = &["node_modules", "target", "dist", "build", "venv", "__pycache__"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetFile {
+    /// Relative to the repository root
+    pub path: String,
+    /// Lowercase, without the dot; `env.example` for `.env.example` files
+    pub extension: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AssetInventory {
+    pub assets: Vec<AssetFile>,
+    /// Assets found before the cap was applied
+    pub total_found: usize,
+}
+
+impl AssetInventory {
+    pub fn truncated(&self) -> bool {
+        self.total_found > self.assets.len()
+    }
+
+    /// Listed assets per extension
+    pub fn counts_by_extension(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for asset in &self.assets {
+            *counts.entry(asset.extension.as_str()).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+/// Whether the job asked for an asset inventory
+pub fn enabled(options: Option<&std::collections::HashMap<String, String>>) -> bool {
+    options.and_then(|opts| opts.get(INVENTORY_OPTION)).is_some_and(|value| value == "true")
+}
+
+/// Extension `path` is listed under, or `None` when it isn't an asset
+pub fn asset_extension(path: &str, size_bytes: u64) -> Option<String> {
+    let file_name = path.rsplit('/').next().unwrap_or(path).to_lowercase();
+    if file_name == ".env.example" || file_name.ends_with(".env.example") {
+        return Some("env.example".to_string());
+    }
+    let (_, extension) = file_name.rsplit_once('.')?;
+    if ASSET_EXTENSIONS.contains(&extension) || (IMAGE_EXTENSIONS.contains(&extension) && size_bytes >= IMAGE_MIN_BYTES) {
+        Some(extension.to_string())
+    } else {
+        None
+    }
+}
+
+/// Walk the repository for assets within `filter`, keeping the first
+/// `max_assets` in path order. Symlinks are not followed.
+pub fn collect_assets(repo_path: &Path, filter: &PathFilter, max_assets: usize) -> Result<AssetInventory> {
+    let mut assets = Vec::new();
+    collect(repo_path, repo_path, filter, &mut assets)?;
+    assets.sort_by(|a: &AssetFile, b| a.path.cmp(&b.path));
+
+    let total_found = assets.len();
+    if total_found > max_assets {
+        warn!("⚠️  Listing {} of {} assets", max_assets, total_found);
+        assets.truncate(max_assets);
+    }
+    Ok(AssetInventory { assets, total_found })
+}
+
+fn collect(root: &Path, dir: &Path, filter: &PathFilter, assets: &mut Vec<AssetFile>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read directory {:?}", dir))? {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() {
+            if !name.starts_with('.') && !IGNORED_DIRS.contains(&name.as_str()) {
+                if let Err(e) = collect(root, &path, filter, assets) {
+                    warn!("⚠️  Skipping directory {:?}: {}", path, e);
+                }
+            }
+        } else if file_type.is_file() {
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+            let size_bytes = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            if let Some(extension) = asset_extension(&relative, size_bytes) {
+                if filter.matches(&relative) {
+                    assets.push(AssetFile { path: relative, extension, size_bytes });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_extensions_and_image_threshold() {
+        for (path, size, expected) in [
+            ("config/app.yaml", 10, Some("yaml")),
+            ("api/schema.GraphQL", 10, Some("graphql")),
+            ("proto/billing.proto", 10, Some("proto")),
+            ("db/seed.sql", 10, Some("sql")),
+            ("Cargo.toml", 10, Some("toml")),
+            (".env.example", 10, Some("env.example")),
+            ("deploy/prod.env.example", 10, Some("env.example")),
+            ("web/hero.PNG", IMAGE_MIN_BYTES, Some("png")),
+            ("web/icon.png", IMAGE_MIN_BYTES - 1, None),
+            ("src/main.rs", 10, None),
+            (".env", 10, None),
+            ("Makefile", 10, None),
+        ] {
+            assert_eq!(asset_extension(path, size).as_deref(), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_collect_assets_filters_and_caps() {
+        let root = std::env::temp_dir().join(format!("assets-{}", uuid::Uuid::new_v4()));
+        for (path, size) in [
+            ("config/app.yaml", 8),
+            ("config/db.json", 8),
+            ("node_modules/pkg/package.json", 8),
+            ("src/main.py", 8),
+            ("static/banner.jpg", IMAGE_MIN_BYTES as usize),
+            ("static/favicon.ico", 64),
+            ("vendor/openapi.yaml", 8),
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, vec![b'x'; size]).unwrap();
+        }
+        let filter = PathFilter { include: vec![], exclude: vec!["vendor/**".to_string()] };
+
+        let inventory = collect_assets(&root, &filter, MAX_ASSETS).unwrap();
+        let paths: Vec<&str> = inventory.assets.iter().map(|a| a.path.as_str()).collect();
+        assert_eq!(paths, vec!["config/app.yaml", "config/db.json", "static/banner.jpg"]);
+        assert_eq!(inventory.assets[2].size_bytes, IMAGE_MIN_BYTES);
+        assert_eq!(inventory.counts_by_extension(), BTreeMap::from([("jpg", 1), ("json", 1), ("yaml", 1)]));
+        assert!(!inventory.truncated());
+
+        let capped = collect_assets(&root, &filter, 2).unwrap();
+        fs::remove_dir_all(&root).ok();
+        assert_eq!(capped.assets.len(), 2);
+        assert_eq!(capped.total_found, 3);
+        assert!(capped.truncated());
+    }
+}
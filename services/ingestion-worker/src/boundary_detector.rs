@@ -5,7 +5,9 @@
 //! - Logical Boundaries: Package/namespace groupings, directory structure
 //! - Architectural Boundaries: Presentation, Business Logic, Data Access layers
 
+use crate::metrics::BoundaryMetrics;
 use crate::parsers::ParsedFile;
+use crate::path_filter::wildcard_match;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -50,6 +52,18 @@ impl ArchitecturalLayer {
             ArchitecturalLayer::Unknown => "unknown",
         }
     }
+
+    /// Layer named by `as_str`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "presentation" => Some(ArchitecturalLayer::Presentation),
+            "business_logic" => Some(ArchitecturalLayer::BusinessLogic),
+            "data_access" => Some(ArchitecturalLayer::DataAccess),
+            "infrastructure" => Some(ArchitecturalLayer::Infrastructure),
+            "unknown" => Some(ArchitecturalLayer::Unknown),
+            _ => None,
+        }
+    }
 }
 
 /// Detected boundary in the codebase
@@ -62,10 +76,12 @@ pub struct Boundary {
     pub layer: Option<ArchitecturalLayer>,
     pub file_count: usize,
     pub files: Vec<String>,
+    /// Cohesion/coupling scores, filled in once file dependencies are resolved
+    pub metrics: Option<BoundaryMetrics>,
 }
 
 /// Module boundary detection results
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct BoundaryDetectionResult {
     pub boundaries: Vec<Boundary>,
     pub file_to_boundary: HashMap<String, String>, // file_path -> boundary_id
@@ -86,7 +102,7 @@ impl BoundaryDetector {
         let mut file_to_boundary = HashMap::new();
 
         // 1. Detect physical boundaries (workspaces)
-        let physical = Self::detect_physical_boundaries(repo_path)?;
+        let physical = Self::detect_physical_boundaries(repo_path, parsed_files)?;
         boundaries.extend(physical);
 
         // 2. Detect logical boundaries (directory structure)
@@ -97,6 +113,10 @@ impl BoundaryDetector {
         let architectural = Self::detect_architectural_boundaries(parsed_files)?;
         boundaries.extend(architectural);
 
+        // Keep ids, membership and file counts identical between runs,
+        // whatever order the files were parsed in
+        Self::sort_boundaries(&mut boundaries);
+
         // Build file-to-boundary mapping
         for boundary in &boundaries {
             for file in &boundary.files {
@@ -112,71 +132,292 @@ impl BoundaryDetector {
         })
     }
 
+    /// Order boundaries by type, then id, with each one's files sorted
+    fn sort_boundaries(boundaries: &mut [Boundary]) {
+        for boundary in boundaries.iter_mut() {
+            boundary.files.sort();
+            boundary.files.dedup();
+            boundary.file_count = boundary.files.len();
+        }
+        let type_rank = |t: &BoundaryType| match t {
+            BoundaryType::Physical => 0,
+            BoundaryType::Logical => 1,
+            BoundaryType::Architectural => 2,
+        };
+        boundaries.sort_by(|a, b| {
+            type_rank(&a.boundary_type)
+                .cmp(&type_rank(&b.boundary_type))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+    }
+
     /// Detect physical boundaries (monorepo workspaces, multi-repo)
-    fn detect_physical_boundaries(repo_path: &Path) -> Result<Vec<Boundary>> {
-        let mut boundaries = Vec::new();
+    ///
+    /// Every npm/yarn workspace, Cargo workspace member and `go.work` module
+    /// becomes a boundary of its own, with globs such as `packages/*`
+    /// expanded against the checkout. Each file belongs to the most specific
+    /// workspace containing it.
+    fn detect_physical_boundaries(repo_path: &Path, parsed_files: &[ParsedFile]) -> Result<Vec<Boundary>> {
+        let mut members: Vec<(String, String)> = Vec::new();
+        let mut add_member = |dir: String, name: String| {
+            if !members.iter().any(|(existing, _)| *existing == dir) {
+                members.push((dir, name));
+            }
+        };
+
+        // package.json workspaces, as an array or yarn's { "packages": [...] }
+        if let Ok(content) = std::fs::read_to_string(repo_path.join("package.json")) {
+            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&content) {
+                let workspaces = json.get("workspaces").and_then(|w| w.as_array().or_else(|| w.get("packages")?.as_array()));
+                let patterns: Vec<String> = workspaces
+                    .map(|w| w.iter().filter_map(|p| p.as_str().map(str::to_string)).collect())
+                    .unwrap_or_default();
+                for dir in Self::expand_workspace_patterns(repo_path, &patterns, &[]) {
+                    let name = Self::package_json_name(&repo_path.join(&dir)).unwrap_or_else(|| dir.clone());
+                    add_member(dir, name);
+                }
+            }
+        }
+
+        // Cargo.toml [workspace] members
+        if let Ok(content) = std::fs::read_to_string(repo_path.join("Cargo.toml")) {
+            let crates = Self::toml_string_array(&content, "workspace", "members");
+            let exclude = Self::toml_string_array(&content, "workspace", "exclude");
+            for dir in Self::expand_workspace_patterns(repo_path, &crates, &exclude) {
+                let name = std::fs::read_to_string(repo_path.join(&dir).join("Cargo.toml"))
+                    .ok()
+                    .and_then(|manifest| Self::toml_string(&manifest, "package", "name"))
+                    .unwrap_or_else(|| dir.clone());
+                add_member(dir, name);
+            }
+        }
+
+        // go.work use directives
+        if let Ok(content) = std::fs::read_to_string(repo_path.join("go.work")) {
+            let uses = Self::go_work_uses(&content);
+            for dir in Self::expand_workspace_patterns(repo_path, &uses, &[]) {
+                let name = std::fs::read_to_string(repo_path.join(&dir).join("go.mod"))
+                    .ok()
+                    .and_then(|go_mod| {
+                        go_mod
+                            .lines()
+                            .find_map(|line| line.trim().strip_prefix("module ").map(|m| m.trim().to_string()))
+                    })
+                    .unwrap_or_else(|| dir.clone());
+                add_member(dir, name);
+            }
+        }
+
+        let mut boundaries: Vec<Boundary> = members
+            .into_iter()
+            .map(|(dir, name)| Boundary {
+                id: format!("physical_workspace_{}", dir.replace('/', "_")),
+                name,
+                boundary_type: BoundaryType::Physical,
+                path: dir,
+                layer: None,
+                file_count: 0,
+                files: Vec::new(),
+                metrics: None,
+            })
+            .collect();
 
-        // Check for package.json workspaces
-        if let Ok(package_json_path) = repo_path.join("package.json").canonicalize() {
-            if package_json_path.exists() {
-                if let Ok(content) = std::fs::read_to_string(&package_json_path) {
-                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(&content) {
-                        if let Some(workspaces) = json.get("workspaces").and_then(|w| w.as_array()) {
-                            for (idx, workspace) in workspaces.iter().enumerate() {
-                                if let Some(workspace_path) = workspace.as_str() {
-                                    boundaries.push(Boundary {
-                                        id: format!("physical_workspace_{}", idx),
-                                        name: format!("Workspace: {}", workspace_path),
-                                        boundary_type: BoundaryType::Physical,
-                                        path: workspace_path.to_string(),
-                                        layer: None,
-                                        file_count: 0,
-                                        files: Vec::new(),
-                                    });
-                                }
-                            }
-                        }
+        for file in parsed_files {
+            let path = file.path.replace('\\', "/");
+            let path = path.trim_start_matches("./");
+            let owner = boundaries
+                .iter_mut()
+                .filter(|b| b.path == "." || path.starts_with(&format!("{}/", b.path)))
+                .max_by_key(|b| if b.path == "." { 0 } else { b.path.len() });
+            if let Some(boundary) = owner {
+                boundary.files.push(file.path.clone());
+                boundary.file_count += 1;
+            }
+        }
+
+        debug!("Detected {} physical boundaries", boundaries.len());
+        Ok(boundaries)
+    }
+
+    /// Repo-relative directories matched by workspace patterns, minus the
+    /// excluded ones. npm-style `!pattern` entries exclude as well. The repo
+    /// root itself is ".".
+    fn expand_workspace_patterns(repo_path: &Path, patterns: &[String], exclude: &[String]) -> Vec<String> {
+        let mut excluded: Vec<String> = Vec::new();
+        let mut dirs: Vec<String> = Vec::new();
+        for pattern in exclude {
+            excluded.extend(Self::expand_workspace_glob(repo_path, pattern));
+        }
+        for pattern in patterns {
+            match pattern.strip_prefix('!') {
+                Some(negated) => excluded.extend(Self::expand_workspace_glob(repo_path, negated)),
+                None => dirs.extend(Self::expand_workspace_glob(repo_path, pattern)),
+            }
+        }
+        dirs.retain(|dir| !excluded.contains(dir));
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// Directories matching one glob; segments may use `*`, `?` and `**`
+    fn expand_workspace_glob(repo_path: &Path, pattern: &str) -> Vec<String> {
+        let pattern = pattern.trim().trim_start_matches("./").trim_end_matches('/');
+        let mut dirs = vec![String::new()];
+
+        for segment in pattern.split('/').filter(|s| !s.is_empty() && *s != ".") {
+            let mut next = Vec::new();
+            for dir in &dirs {
+                if segment == "**" {
+                    next.push(dir.clone());
+                    Self::collect_subdirs(repo_path, dir, true, &mut next);
+                } else if segment.contains(['*', '?']) {
+                    let mut children = Vec::new();
+                    Self::collect_subdirs(repo_path, dir, false, &mut children);
+                    next.extend(children.into_iter().filter(|child| {
+                        let name = child.rsplit('/').next().unwrap_or(child);
+                        wildcard_match(segment, name)
+                    }));
+                } else {
+                    let joined = Self::join_relative(dir, segment);
+                    if repo_path.join(&joined).is_dir() {
+                        next.push(joined);
                     }
                 }
             }
+            dirs = next;
         }
 
-        // Check for Cargo.toml workspace
-        if let Ok(cargo_toml_path) = repo_path.join("Cargo.toml").canonicalize() {
-            if cargo_toml_path.exists() {
-                if let Ok(content) = std::fs::read_to_string(&cargo_toml_path) {
-                    if content.contains("[workspace]") {
-                        boundaries.push(Boundary {
-                            id: "physical_cargo_workspace".to_string(),
-                            name: "Cargo Workspace".to_string(),
-                            boundary_type: BoundaryType::Physical,
-                            path: ".".to_string(),
-                            layer: None,
-                            file_count: 0,
-                            files: Vec::new(),
-                        });
+        dirs.into_iter()
+            .map(|dir| if dir.is_empty() { ".".to_string() } else { dir })
+            .collect()
+    }
+
+    /// Subdirectories of `dir`, skipping hidden, dependency and build output
+    /// directories and symlinks
+    fn collect_subdirs(repo_path: &Path, dir: &str, recursive: bool, out: &mut Vec<String>) {
+        let Ok(entries) = std::fs::read_dir(repo_path.join(dir)) else {
+            return;
+        };
+        let mut children: Vec<String> = entries
+            .flatten()
+            .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| !name.starts_with('.') && name != "node_modules" && name != "target")
+            .collect();
+        children.sort();
+
+        for name in children {
+            let child = Self::join_relative(dir, &name);
+            out.push(child.clone());
+            if recursive {
+                Self::collect_subdirs(repo_path, &child, true, out);
+            }
+        }
+    }
+
+    fn join_relative(dir: &str, name: &str) -> String {
+        if dir.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", dir, name)
+        }
+    }
+
+    fn package_json_name(dir: &Path) -> Option<String> {
+        let content = std::fs::read_to_string(dir.join("package.json")).ok()?;
+        let json = serde_json::from_str::<serde_json::Value>(&content).ok()?;
+        json.get("name")?.as_str().map(str::to_string)
+    }
+
+    /// Lines of a TOML table, up to the next table header
+    fn toml_table<'a>(content: &'a str, table: &str) -> Vec<&'a str> {
+        let header = format!("[{}]", table);
+        content
+            .lines()
+            .skip_while(|line| line.trim() != header)
+            .skip(1)
+            .take_while(|line| !line.trim_start().starts_with('['))
+            .collect()
+    }
+
+    /// `key = "value"` in a TOML table
+    fn toml_string(content: &str, table: &str, key: &str) -> Option<String> {
+        Self::toml_table(content, table).into_iter().find_map(|line| {
+            let (k, v) = line.split_once('=')?;
+            if k.trim() != key {
+                return None;
+            }
+            Some(v.trim().trim_matches('"').to_string())
+        })
+    }
+
+    /// `key = ["a", "b"]` in a TOML table; the array may span several lines
+    fn toml_string_array(content: &str, table: &str, key: &str) -> Vec<String> {
+        let header = format!("[{}]", table);
+        let Some(start) = content.lines().position(|line| line.trim() == header) else {
+            return Vec::new();
+        };
+        let body: Vec<&str> = content.lines().skip(start + 1).collect();
+
+        let mut values = Vec::new();
+        let mut in_array = false;
+        for line in body {
+            let line = line.split('#').next().unwrap_or_default();
+            let rest = if in_array {
+                line
+            } else {
+                if line.trim_start().starts_with('[') {
+                    break;
+                }
+                match line.split_once('=') {
+                    Some((k, v)) if k.trim() == key => {
+                        in_array = true;
+                        v.trim_start().trim_start_matches('[')
                     }
+                    _ => continue,
                 }
+            };
+            let (items, closed) = match rest.split_once(']') {
+                Some((items, _)) => (items, true),
+                None => (rest, false),
+            };
+            values.extend(
+                items
+                    .split(',')
+                    .map(|item| item.trim().trim_matches('"').trim_matches('\''))
+                    .filter(|item| !item.is_empty())
+                    .map(str::to_string),
+            );
+            if closed {
+                break;
             }
         }
+        values
+    }
 
-        // Check for go.work
-        if let Ok(go_work_path) = repo_path.join("go.work").canonicalize() {
-            if go_work_path.exists() {
-                boundaries.push(Boundary {
-                    id: "physical_go_workspace".to_string(),
-                    name: "Go Workspace".to_string(),
-                    boundary_type: BoundaryType::Physical,
-                    path: ".".to_string(),
-                    layer: None,
-                    file_count: 0,
-                    files: Vec::new(),
-                });
+    /// Directories listed by `use` in a go.work file, single or in a block
+    fn go_work_uses(content: &str) -> Vec<String> {
+        let mut uses = Vec::new();
+        let mut in_block = false;
+        for line in content.lines() {
+            let line = line.split("//").next().unwrap_or_default().trim();
+            if in_block {
+                if line == ")" {
+                    in_block = false;
+                } else if !line.is_empty() {
+                    uses.push(line.trim_matches('"').to_string());
+                }
+            } else if let Some(rest) = line.strip_prefix("use") {
+                let rest = rest.trim();
+                if rest == "(" {
+                    in_block = true;
+                } else if !rest.is_empty() && line.starts_with("use ") {
+                    uses.push(rest.trim_matches('"').to_string());
+                }
             }
         }
-
-        debug!("Detected {} physical boundaries", boundaries.len());
-        Ok(boundaries)
+        uses
     }
 
     /// Detect logical boundaries (directory-based modules)
@@ -206,6 +447,7 @@ impl BoundaryDetector {
                     layer: None,
                     file_count: files.len(),
                     files,
+                    metrics: None,
                 });
             }
         }
@@ -238,6 +480,7 @@ impl BoundaryDetector {
                 layer: Some(layer),
                 file_count: files.len(),
                 files,
+                metrics: None,
             })
             .collect();
 
@@ -258,7 +501,7 @@ impl BoundaryDetector {
     }
 
     /// Classify a file into an architectural layer
-    fn classify_architectural_layer(path: &str) -> ArchitecturalLayer {
+    pub fn classify_architectural_layer(path: &str) -> ArchitecturalLayer {
         let path_lower = path.to_lowercase();
 
         // Presentation layer indicators
@@ -310,3 +553,168 @@ impl BoundaryDetector {
         ArchitecturalLayer::Unknown
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn parsed(path: &str) -> ParsedFile {
+        ParsedFile {
+            path: path.to_string(),
+            language: "typescript".to_string(),
+            functions: vec![],
+            classes: vec![],
+            interfaces: vec![],
+            imports: vec![],
+            data_tables: vec![],
+            service_calls: vec![],
+            sql_queries: vec![],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: false,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: 0,
+        }
+    }
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn physical(root: &Path, files: &[&str]) -> Vec<(String, String, Vec<String>)> {
+        let parsed_files: Vec<ParsedFile> = files.iter().map(|f| parsed(f)).collect();
+        let mut boundaries: Vec<(String, String, Vec<String>)> =
+            BoundaryDetector::detect_physical_boundaries(root, &parsed_files)
+                .unwrap()
+                .into_iter()
+                .map(|b| {
+                    assert_eq!(b.file_count, b.files.len());
+                    (b.path, b.name, b.files)
+                })
+                .collect();
+        boundaries.sort();
+        boundaries
+    }
+
+    fn monorepo(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("boundary-{}-{}", name, uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_npm_workspace_globs() {
+        let root = monorepo("npm");
+        write(&root, "package.json", r#"{"name": "root", "workspaces": ["packages/*", "apps/web", "!packages/legacy"]}"#);
+        write(&root, "packages/ui/package.json", r#"{"name": "@acme/ui"}"#);
+        write(&root, "packages/core/package.json", r#"{"name": "@acme/core"}"#);
+        write(&root, "packages/legacy/package.json", r#"{"name": "@acme/legacy"}"#);
+        write(&root, "packages/node_modules/dep/package.json", "{}");
+        write(&root, "apps/web/package.json", r#"{"name": "web"}"#);
+
+        let boundaries = physical(
+            &root,
+            &["packages/ui/src/Button.tsx", "packages/core/index.ts", "packages/legacy/a.ts", "apps/web/main.ts", "scripts/x.ts"],
+        );
+        fs::remove_dir_all(&root).ok();
+
+        assert_eq!(
+            boundaries,
+            vec![
+                ("apps/web".to_string(), "web".to_string(), vec!["apps/web/main.ts".to_string()]),
+                ("packages/core".to_string(), "@acme/core".to_string(), vec!["packages/core/index.ts".to_string()]),
+                ("packages/ui".to_string(), "@acme/ui".to_string(), vec!["packages/ui/src/Button.tsx".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cargo_workspace_members_prefer_most_specific_path() {
+        let root = monorepo("cargo");
+        write(
+            &root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\n    \"crates/*\",\n    \"crates/api/plugins/**\", # nested\n]\nexclude = [\"crates/scratch\"]\n\n[workspace.dependencies]\nserde = \"1\"\n",
+        );
+        write(&root, "crates/api/Cargo.toml", "[package]\nname = \"api\"\nversion = \"0.1.0\"\n");
+        write(&root, "crates/api/plugins/auth/Cargo.toml", "[package]\nname = \"api-auth\"\n");
+        write(&root, "crates/scratch/Cargo.toml", "[package]\nname = \"scratch\"\n");
+
+        let boundaries = physical(&root, &["crates/api/src/lib.rs", "crates/api/plugins/auth/src/lib.rs", "crates/scratch/main.rs"]);
+        fs::remove_dir_all(&root).ok();
+
+        let names: Vec<(&str, &str, usize)> =
+            boundaries.iter().map(|(path, name, files)| (path.as_str(), name.as_str(), files.len())).collect();
+        assert_eq!(
+            names,
+            vec![
+                ("crates/api", "api", 1),
+                ("crates/api/plugins", "crates/api/plugins", 0),
+                ("crates/api/plugins/auth", "api-auth", 1),
+            ]
+        );
+        assert_eq!(boundaries[0].2, vec!["crates/api/src/lib.rs".to_string()]);
+    }
+
+    #[test]
+    fn test_go_work_uses() {
+        let root = monorepo("go");
+        write(&root, "go.work", "go 1.21\n\nuse (\n\t.\n\t./services/billing // payments\n)\nuse ./tools\n");
+        write(&root, "go.mod", "module example.com/platform\n");
+        write(&root, "services/billing/go.mod", "module example.com/billing\n\ngo 1.21\n");
+        write(&root, "tools/go.mod", "module example.com/tools\n");
+
+        let boundaries = physical(&root, &["cmd/main.go", "services/billing/invoice.go", "tools/gen.go"]);
+        fs::remove_dir_all(&root).ok();
+
+        assert_eq!(
+            boundaries,
+            vec![
+                (".".to_string(), "example.com/platform".to_string(), vec!["cmd/main.go".to_string()]),
+                ("services/billing".to_string(), "example.com/billing".to_string(), vec!["services/billing/invoice.go".to_string()]),
+                ("tools".to_string(), "example.com/tools".to_string(), vec!["tools/gen.go".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detection_order_does_not_depend_on_file_order() {
+        let root = std::env::temp_dir().join(format!("boundary-order-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        let paths = [
+            "web/components/Nav.tsx",
+            "api/services/billing.ts",
+            "web/pages/index.tsx",
+            "api/db/models.ts",
+            "api/services/auth.ts",
+            "web/utils/format.ts",
+            "api/config.ts",
+        ];
+        let forward: Vec<ParsedFile> = paths.iter().map(|p| parsed(p)).collect();
+        let reversed: Vec<ParsedFile> = paths.iter().rev().map(|p| parsed(p)).collect();
+
+        let summarize = |result: BoundaryDetectionResult| -> Vec<(String, usize, Vec<String>)> {
+            result.boundaries.into_iter().map(|b| (b.id, b.file_count, b.files)).collect()
+        };
+        let first = summarize(BoundaryDetector::detect_boundaries(&forward, &root).unwrap());
+        let second = summarize(BoundaryDetector::detect_boundaries(&reversed, &root).unwrap());
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(first, second);
+        let ids: Vec<&str> = first.iter().map(|(id, _, _)| id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "logical_api",
+                "logical_web",
+                "architectural_business_logic",
+                "architectural_infrastructure",
+                "architectural_presentation",
+            ]
+        );
+        assert_eq!(first[1].2, vec!["web/components/Nav.tsx", "web/pages/index.tsx", "web/utils/format.ts"]);
+    }
+}
@@ -0,0 +1,213 @@
+//! Local Analysis
+//!
+//! `ingestion-worker analyze` runs the analysis pipeline on a checkout on
+//! disk and writes the result to a file, without Redis, Neo4j or the API:
+//!
+//! ```sh
+//! ingestion-worker analyze --path ./repo --output graph.json [--format json|graphml|dot] [--no-git]
+//! ```
+//!
+//! The JSON output holds the graph patch of every parsed file and the result
+//! summary a job would report; GraphML and DOT hold the dependency graph.
+//! Parse limits, concurrency, the git commit limit and the cohesion
+//! threshold come from the worker's environment variables. The exit code is
+//! 0 on success, 1 when the analysis failed and 2 when the output was
+//! written but some files failed to parse.
+
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+use tracing::{error, info, warn};
+
+use crate::graph_export::{self, ExportFormat};
+use crate::job_warnings::JobWarnings;
+use crate::layering::LayerRules;
+use crate::parsed_source;
+use crate::path_filter::PathFilter;
+use crate::phases::{Phase, Phases};
+use crate::pipeline::{self, PipelineSettings};
+use crate::stage_timings::{self, StageTimings};
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FAILED: i32 = 1;
+pub const EXIT_PARSE_ERRORS: i32 = 2;
+
+const USAGE: &str =
+    "Usage: ingestion-worker analyze --path <dir> --output <file> [--format json|graphml|dot] [--no-git]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Graph patch and result summary
+    Json,
+    Graph(ExportFormat),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalyzeArgs {
+    pub path: PathBuf,
+    pub output: PathBuf,
+    pub format: OutputFormat,
+    /// Skip the git history phase and don't read HEAD
+    pub no_git: bool,
+}
+
+impl AnalyzeArgs {
+    /// Arguments after `analyze`; the path defaults to the current directory
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut path = PathBuf::from(".");
+        let mut output = None;
+        let mut format = OutputFormat::Json;
+        let mut no_git = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().with_context(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--path" => path = PathBuf::from(value()?),
+                "--output" => output = Some(PathBuf::from(value()?)),
+                "--format" => {
+                    let raw = value()?;
+                    format = match raw.trim().to_ascii_lowercase().as_str() {
+                        "json" => OutputFormat::Json,
+                        other => match ExportFormat::parse(other) {
+                            Some(export) => OutputFormat::Graph(export),
+                            None => bail!("Unknown format {:?} (expected json, graphml or dot)", raw),
+                        },
+                    };
+                }
+                "--no-git" => no_git = true,
+                other => bail!("Unknown argument {:?}", other),
+            }
+        }
+
+        let output = output.context("--output is required")?;
+        Ok(Self { path, output, format, no_git })
+    }
+}
+
+/// Run the `analyze` command and return the process exit code
+pub async fn run(args: &[String]) -> i32 {
+    let args = match AnalyzeArgs::parse(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{:#}\n{}", e, USAGE);
+            return EXIT_FAILED;
+        }
+    };
+    match analyze(&args).await {
+        Ok(0) => EXIT_OK,
+        Ok(parse_errors) => {
+            warn!("⚠️  {} files failed to parse, see parse_errors in the summary", parse_errors);
+            EXIT_PARSE_ERRORS
+        }
+        Err(e) => {
+            error!("❌ Analysis failed: {:?}", e);
+            EXIT_FAILED
+        }
+    }
+}
+
+/// Analyze the checkout and write the output, returning the number of files
+/// that failed to parse
+async fn analyze(args: &AnalyzeArgs) -> Result<usize> {
+    let started = std::time::Instant::now();
+    let config = crate::Config::from_env()?;
+    let repo_path = args
+        .path
+        .canonicalize()
+        .with_context(|| format!("Cannot read repository at {:?}", args.path))?;
+    if !repo_path.is_dir() {
+        bail!("{:?} is not a directory", args.path);
+    }
+    info!("🔍 Analyzing {:?}", repo_path);
+
+    let phases = if args.no_git { Phases::default().without(Phase::Git) } else { Phases::default() };
+    let analyzed_commit = if args.no_git { None } else { crate::checkout_analyzed_commit(&repo_path, None, crate::CommitCheckout::Refused)? };
+    if let Some(sha) = &analyzed_commit {
+        info!("📌 Analyzing commit {}", sha);
+    }
+
+    let mut timings = StageTimings::default();
+    let (path, concurrency, limits) = (repo_path.clone(), config.parse_concurrency, config.file_limits());
+    let stream_threshold = config.stream_file_threshold;
+    let (mut parsed_files, parse_stats) = stage_timings::timed(
+        &mut timings.parse_ms,
+        crate::run_blocking(move || {
+            let (filter, chunk_files) = (PathFilter::default(), parsed_source::CHUNK_FILES);
+            crate::parse_repository_source(&path, concurrency, &limits, &filter, stream_threshold, chunk_files)
+        }),
+    )
+    .await?;
+    info!("📄 Parsed {} files", parsed_files.len());
+    parse_stats.log();
+    let warnings = JobWarnings::default();
+    warnings.record_parse_stats(&parse_stats, limits.max_file_size_bytes);
+
+    let settings = PipelineSettings {
+        phases,
+        path_filter: PathFilter::default(),
+        layer_rules: LayerRules::default(),
+        incremental: false,
+        git_max_commits: config.git_max_commits,
+        git_since: None,
+        git_max_walk: None,
+        cohesion_threshold: config.cohesion_threshold,
+        inventory_assets: false,
+    };
+    let analysis = pipeline::analyze(&repo_path, &mut parsed_files, &settings, &mut timings, &warnings).await?;
+
+    let output = match args.format {
+        OutputFormat::Json => {
+            let mut summary = pipeline::analysis_summary(
+                parsed_files.outlines(),
+                &parse_stats,
+                &analysis,
+                &settings,
+                analyzed_commit.as_deref(),
+            )?;
+            timings.total_ms = stage_timings::elapsed_ms(started);
+            summary["timings"] = serde_json::to_value(&timings)?;
+            warnings.add_to_summary(&mut summary);
+            let outlines = parsed_files.outlines();
+            let files: Vec<String> = outlines.iter().map(|file| file.path.clone()).collect();
+            let patch = crate::build_graph_patch(outlines, &analysis.dep_graph, &analysis.packages, &files, &[], None);
+            let output = serde_json::json!({ "graph_patch": patch, "summary": summary });
+            serde_json::to_string_pretty(&output).context("Failed to serialize analysis")?
+        }
+        OutputFormat::Graph(format) => graph_export::export(&analysis.dep_graph, format),
+    };
+    std::fs::write(&args.output, output).with_context(|| format!("Failed to write {:?}", args.output))?;
+    info!("💾 Wrote {:?} in {} ms", args.output, stage_timings::elapsed_ms(started));
+
+    Ok(parse_stats.parse_errors.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(raw: &[&str]) -> Result<AnalyzeArgs> {
+        AnalyzeArgs::parse(&raw.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed = args(&["--path", "./repo", "--output", "out.graphml", "--format", "GraphML", "--no-git"]).unwrap();
+        assert_eq!(
+            parsed,
+            AnalyzeArgs {
+                path: PathBuf::from("./repo"),
+                output: PathBuf::from("out.graphml"),
+                format: OutputFormat::Graph(ExportFormat::GraphMl),
+                no_git: true,
+            }
+        );
+
+        let defaults = args(&["--output", "graph.json"]).unwrap();
+        assert_eq!((defaults.path, defaults.format, defaults.no_git), (PathBuf::from("."), OutputFormat::Json, false));
+
+        assert!(args(&["--path", "."]).unwrap_err().to_string().contains("--output"));
+        assert!(args(&["--output"]).unwrap_err().to_string().contains("needs a value"));
+        assert!(args(&["--output", "x", "--format", "csv"]).unwrap_err().to_string().contains("\"csv\""));
+        assert!(args(&["--output", "x", "--verbose"]).unwrap_err().to_string().contains("\"--verbose\""));
+    }
+}
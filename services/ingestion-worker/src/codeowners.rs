@@ -0,0 +1,262 @@
+//! Code Owners
+//!
+//! Reads the repository's GitHub CODEOWNERS file and answers who owns a
+//! path. GitHub looks for the file in `.github/`, the repository root and
+//! `docs/`, using the first one it finds; so does this. Each line is a
+//! pattern followed by owners (`@org/team`, `@user` or an email), and the
+//! last rule matching a path decides its owners. A rule without owners
+//! leaves the paths it matches unowned.
+//!
+//! Patterns follow GitHub's rules: a leading `/` or a `/` in the middle
+//! anchors the pattern at the root, otherwise it matches at any depth; a
+//! pattern matching a directory covers everything below it, except that a
+//! trailing `/*` only covers the directory's own files; `*` and `?` match
+//! within one path segment and `**` any number of segments.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::boundary_detector::BoundaryDetectionResult;
+use crate::path_filter::wildcard_match;
+
+/// Where GitHub looks for the file, in the order it looks
+pub const CODEOWNERS_LOCATIONS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnerRule {
+    pub pattern: String,
+    pub owners: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodeOwners {
+    /// Repo-relative path of the file the rules came from
+    pub source: String,
+    pub rules: Vec<OwnerRule>,
+}
+
+impl CodeOwners {
+    /// The repository's CODEOWNERS file, or None when it has none
+    pub fn load(repo_path: &Path) -> Result<Option<Self>> {
+        for location in CODEOWNERS_LOCATIONS {
+            let path = repo_path.join(location);
+            if !path.is_file() {
+                continue;
+            }
+            let content =
+                std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            return Ok(Some(Self::parse(location, &content)));
+        }
+        Ok(None)
+    }
+
+    pub fn parse(source: &str, content: &str) -> Self {
+        let rules = content
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+                let mut fields = line.split_whitespace();
+                let pattern = fields.next()?.replace("\\#", "#");
+                let owners = fields.take_while(|field| !field.starts_with('#')).map(str::to_string).collect();
+                Some(OwnerRule { pattern, owners })
+            })
+            .collect();
+        Self { source: source.to_string(), rules }
+    }
+
+    /// Owners of a repo-relative path; empty when no rule gives it any
+    pub fn owners(&self, path: &str) -> &[String] {
+        let path = path.replace('\\', "/");
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| pattern_matches(&rule.pattern, &path))
+            .map(|rule| rule.owners.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+/// Whether a CODEOWNERS pattern covers `path`
+pub fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let directory_only = pattern.ends_with('/');
+    let anchored = pattern.starts_with('/') || pattern.trim_end_matches('/').contains('/');
+    let trimmed = pattern.trim_matches('/');
+    if trimmed.is_empty() {
+        return false;
+    }
+    let mut segments: Vec<&str> = trimmed.split('/').collect();
+    if !anchored {
+        segments.insert(0, "**");
+    }
+
+    let path_segments: Vec<&str> = path.trim_start_matches("./").split('/').filter(|s| !s.is_empty()).collect();
+    if path_segments.is_empty() {
+        return false;
+    }
+    // `docs/*` stops at the directory's own files
+    let directories = if segments.len() > 1 && segments.last() == Some(&"*") { 0 } else { path_segments.len() - 1 };
+    (!directory_only && segments_match(&segments, &path_segments))
+        || (1..=directories).any(|len| segments_match(&segments, &path_segments[..len]))
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| segments_match(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path_rest)) => wildcard_match(segment, name) && segments_match(rest, path_rest),
+            None => false,
+        },
+    }
+}
+
+/// The owner of most of a boundary's files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundaryOwner {
+    pub boundary_id: String,
+    pub owner: String,
+    /// Files of the boundary the owner owns
+    pub file_count: usize,
+}
+
+/// Each boundary's owner: whoever owns the most of its files, the first by
+/// name on a tie. Boundaries with no owned files are left out.
+pub fn boundary_owners(boundary_result: &BoundaryDetectionResult, codeowners: &CodeOwners) -> Vec<BoundaryOwner> {
+    let mut owners = Vec::new();
+    for boundary in &boundary_result.boundaries {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for file in &boundary.files {
+            for owner in codeowners.owners(file) {
+                *counts.entry(owner.as_str()).or_default() += 1;
+            }
+        }
+        // max_by_key keeps the last maximum, so walk the names backwards
+        if let Some((owner, file_count)) = counts.into_iter().rev().max_by_key(|(_, count)| *count) {
+            owners.push(BoundaryOwner { boundary_id: boundary.id.clone(), owner: owner.to_string(), file_count });
+        }
+    }
+    owners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary_detector::{Boundary, BoundaryType};
+
+    const CODEOWNERS: &str = "\
+# Default owners
+*                   @acme/platform
+
+*.ts                @acme/frontend
+/src/**             @acme/core
+/src/web/*.ts       @acme/frontend  # inline comment
+docs/*              docs@acme.dev
+apps/               @acme/apps
+/build/logs/        @acme/ops
+/src/vendor/
+";
+
+    fn owners(path: &str) -> Vec<String> {
+        CodeOwners::parse("CODEOWNERS", CODEOWNERS).owners(path).to_vec()
+    }
+
+    #[test]
+    fn test_last_matching_rule_wins() {
+        assert_eq!(owners("README.md"), vec!["@acme/platform"]);
+        // `*.ts` matches at any depth, but the later `/src/**` overrides it
+        assert_eq!(owners("web/index.ts"), vec!["@acme/frontend"]);
+        assert_eq!(owners("src/lib/util.ts"), vec!["@acme/core"]);
+        assert_eq!(owners("src/main.rs"), vec!["@acme/core"]);
+        // ...and a later, narrower rule overrides `/src/**` again
+        assert_eq!(owners("src/web/app.ts"), vec!["@acme/frontend"]);
+        assert_eq!(owners("src/web/pages/home.ts"), vec!["@acme/core"]);
+        // A rule without owners unowns what it matches
+        assert!(owners("src/vendor/lib.js").is_empty());
+    }
+
+    #[test]
+    fn test_pattern_forms() {
+        // `docs/*` is anchored and covers the directory's own files only
+        assert_eq!(owners("docs/intro.md"), vec!["docs@acme.dev"]);
+        assert_eq!(owners("guide/docs/intro.md"), vec!["@acme/platform"]);
+        assert_eq!(owners("docs/api/intro.md"), vec!["@acme/platform"]);
+        // A directory pattern covers everything below it, wherever it is
+        assert_eq!(owners("apps/web/main.go"), vec!["@acme/apps"]);
+        assert_eq!(owners("services/apps/main.go"), vec!["@acme/apps"]);
+        assert_eq!(owners("apps"), vec!["@acme/platform"]);
+        // An anchored directory only at the root
+        assert_eq!(owners("build/logs/today.log"), vec!["@acme/ops"]);
+        assert_eq!(owners("tools/build/logs/today.log"), vec!["@acme/platform"]);
+
+        assert!(pattern_matches("/src/**", "src/a/b/c.go"));
+        assert!(!pattern_matches("/src/**", "lib/src/c.go"));
+        assert!(pattern_matches("src", "lib/src/c.go"));
+        assert!(!pattern_matches("/src", "lib/src/c.go"));
+        assert!(pattern_matches("**/logs", "deep/nested/logs/out.txt"));
+        assert!(pattern_matches("*.ts", "a/b/c.ts"));
+        assert!(!pattern_matches("*.ts", "a/b/c.tsx"));
+        assert!(!pattern_matches("/", "a.ts"));
+    }
+
+    #[test]
+    fn test_parse_skips_comments_and_reads_escaped_hash() {
+        let codeowners = CodeOwners::parse(".github/CODEOWNERS", "# header\n\n\\#notes.md @acme/docs\n");
+        assert_eq!(
+            codeowners.rules,
+            vec![OwnerRule { pattern: "#notes.md".to_string(), owners: vec!["@acme/docs".to_string()] }]
+        );
+        assert_eq!(codeowners.owners("#notes.md"), ["@acme/docs".to_string()]);
+    }
+
+    #[test]
+    fn test_load_uses_first_location() {
+        let root = std::env::temp_dir().join(format!("codeowners-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        assert_eq!(CodeOwners::load(&root).unwrap(), None);
+
+        std::fs::write(root.join("docs/CODEOWNERS"), "* @docs\n").unwrap();
+        std::fs::write(root.join("CODEOWNERS"), "* @root\n").unwrap();
+        let codeowners = CodeOwners::load(&root).unwrap().unwrap();
+        assert_eq!((codeowners.source.as_str(), codeowners.owners("a.rs")), ("CODEOWNERS", &["@root".to_string()][..]));
+
+        std::fs::create_dir_all(root.join(".github")).unwrap();
+        std::fs::write(root.join(".github/CODEOWNERS"), "* @github\n").unwrap();
+        assert_eq!(CodeOwners::load(&root).unwrap().unwrap().source, ".github/CODEOWNERS");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_boundary_owner_is_owner_of_most_files() {
+        let codeowners = CodeOwners::parse(
+            "CODEOWNERS",
+            "/billing/ @acme/billing\n/billing/ui/ @acme/frontend @acme/billing\n/shared/ @b-team\n/shared/x/ @a-team\n",
+        );
+        let boundary = |id: &str, files: &[&str]| Boundary {
+            id: id.to_string(),
+            name: id.to_string(),
+            boundary_type: BoundaryType::Logical,
+            path: id.to_string(),
+            layer: None,
+            file_count: files.len(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+            metrics: None,
+        };
+        let result = BoundaryDetectionResult {
+            boundaries: vec![
+                boundary("billing", &["billing/api.ts", "billing/ui/page.tsx", "billing/ui/form.tsx"]),
+                boundary("shared", &["shared/x/a.ts", "shared/b.ts"]),
+                boundary("scripts", &["scripts/seed.ts"]),
+            ],
+            ..Default::default()
+        };
+
+        let owners = boundary_owners(&result, &codeowners);
+        let owners: Vec<(&str, &str, usize)> =
+            owners.iter().map(|o| (o.boundary_id.as_str(), o.owner.as_str(), o.file_count)).collect();
+        assert_eq!(owners, vec![("billing", "@acme/billing", 3), ("shared", "@a-team", 1)]);
+    }
+}
@@ -0,0 +1,294 @@
+//! Corpus Benchmark
+//!
+//! Runs the analysis pipeline over the vendored fixture repositories in
+//! `tests/corpus/` and compares extraction quality against the checked-in
+//! `tests/corpus/baseline.json`. It is an ignored test so it never slows down
+//! the normal suite:
+//!
+//! ```sh
+//! cargo test corpus_benchmark -- --ignored --nocapture
+//! UPDATE_CORPUS_BASELINE=1 cargo test corpus_benchmark -- --ignored
+//! ```
+//!
+//! The report is written to `target/corpus-report.json`, or to the path in
+//! `CORPUS_REPORT`. `CORPUS_TOLERANCE` overrides the baseline's tolerance.
+
+use crate::communication_detector::CommunicationDetector;
+use crate::graph_builder::{DependencyGraph, EdgeType, SymbolTable};
+use crate::import_resolver::ImportResolver;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const DEFAULT_TOLERANCE: f64 = 0.05;
+
+/// Quality metrics where a higher value is a regression
+const LOWER_IS_BETTER: &[&str] = &["imports_unresolved_relative"];
+
+/// Reported but not compared: better resolution moves imports out of these
+const INFORMATIONAL: &[&str] = &["imports_external"];
+
+/// Extraction metrics for one corpus repository
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorpusReport {
+    /// Metric name -> value, compared against the baseline
+    pub quality: BTreeMap<String, f64>,
+    /// Stage name -> milliseconds, reported but never compared
+    pub timings_ms: BTreeMap<String, u128>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorpusBaseline {
+    pub tolerance: f64,
+    pub repos: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+/// Run the pipeline over one repository and collect its metrics
+pub fn analyze_corpus_repo(repo_path: &PathBuf) -> Result<CorpusReport> {
+    let mut timings_ms = BTreeMap::new();
+
+    let started = Instant::now();
+    let (parsed_files, _) = crate::parse_repository(repo_path, 1, &crate::FileLimits::default(), &crate::PathFilter::default())?;
+    timings_ms.insert("parse".to_string(), started.elapsed().as_millis());
+
+    let started = Instant::now();
+    let symbol_table = SymbolTable::from_parsed_files(&parsed_files);
+    let import_resolver = ImportResolver::from_repo(repo_path);
+    let dep_graph = DependencyGraph::from_parsed_files_with_resolver(&parsed_files, &symbol_table, &import_resolver);
+    timings_ms.insert("graph".to_string(), started.elapsed().as_millis());
+
+    let started = Instant::now();
+    let communication = CommunicationDetector::detect(
+        repo_path,
+        &parsed_files,
+        &crate::PathFilter::default(),
+        &crate::job_warnings::JobWarnings::default(),
+    )?;
+    timings_ms.insert("communication".to_string(), started.elapsed().as_millis());
+
+    let stats = dep_graph.stats();
+    // `calls` has one entry per call site, while a CALLS edge covers every
+    // call from one function to another, so count distinct callees
+    let distinct_callees = |calls: &[String]| calls.iter().collect::<HashSet<_>>().len();
+    let calls_total: usize = parsed_files
+        .iter()
+        .map(|f| {
+            f.functions.iter().map(|func| distinct_callees(&func.calls)).sum::<usize>()
+                + f.classes
+                    .iter()
+                    .flat_map(|c| c.methods.iter())
+                    .map(|m| distinct_callees(&m.calls))
+                    .sum::<usize>()
+        })
+        .sum();
+
+    // An import is resolved when it produced a DEPENDS_ON edge from its file
+    let resolved_imports: HashSet<(String, String)> = dep_graph
+        .edges_of_type(EdgeType::DependsOn)
+        .into_iter()
+        .filter_map(|edge| {
+            let source = edge.from.file_path()?.to_string();
+            Some((source, edge.properties.get("import_path")?.clone()))
+        })
+        .collect();
+    let mut imports_total = 0usize;
+    let mut imports_resolved = 0usize;
+    let mut imports_external = 0usize;
+    let mut imports_unresolved_relative = 0usize;
+    for file in &parsed_files {
+        for import in &file.imports {
+            imports_total += 1;
+            if resolved_imports.contains(&(file.path.clone(), import.clone())) {
+                imports_resolved += 1;
+            } else if import.starts_with('.') {
+                imports_unresolved_relative += 1;
+            } else {
+                imports_external += 1;
+            }
+        }
+    }
+
+    let mut quality = BTreeMap::new();
+    let mut record = |name: &str, value: f64| {
+        quality.insert(name.to_string(), value);
+    };
+    record("files", parsed_files.len() as f64);
+    record("functions", stats.functions as f64);
+    record("classes", stats.classes as f64);
+    record("calls_total", calls_total as f64);
+    record("calls_resolved", stats.calls_edges as f64);
+    record("call_resolution_rate", ratio(stats.calls_edges, calls_total));
+    record("imports_total", imports_total as f64);
+    record("imports_resolved", imports_resolved as f64);
+    record("imports_external", imports_external as f64);
+    record("imports_unresolved_relative", imports_unresolved_relative as f64);
+    record("import_resolution_rate", ratio(imports_resolved, imports_total));
+    record("endpoints", communication.endpoints.len() as f64);
+    record("routes", communication.routes.len() as f64);
+    record("rpc_services", communication.rpc_services.len() as f64);
+    record("queues", communication.queues.len() as f64);
+    record(
+        "sql_queries",
+        parsed_files.iter().map(|f| f.sql_queries.len()).sum::<usize>() as f64,
+    );
+
+    Ok(CorpusReport { quality, timings_ms })
+}
+
+/// Describe every metric that moved in the wrong direction by more than
+/// `tolerance` (relative to the baseline value). Metrics missing from the
+/// baseline are new and never fail.
+pub fn find_regressions(
+    baseline: &BTreeMap<String, BTreeMap<String, f64>>,
+    current: &BTreeMap<String, CorpusReport>,
+    tolerance: f64,
+) -> Vec<String> {
+    let mut regressions = Vec::new();
+
+    for (repo, expected_metrics) in baseline {
+        let Some(report) = current.get(repo) else {
+            regressions.push(format!("{}: repository missing from corpus", repo));
+            continue;
+        };
+
+        for (metric, expected) in expected_metrics {
+            if INFORMATIONAL.contains(&metric.as_str()) {
+                continue;
+            }
+            let actual = report.quality.get(metric).copied().unwrap_or(0.0);
+            let allowed = expected.abs() * tolerance;
+            let regressed = if LOWER_IS_BETTER.contains(&metric.as_str()) {
+                actual > expected + allowed
+            } else {
+                actual < expected - allowed
+            };
+            if regressed {
+                regressions.push(format!("{}: {} {} -> {}", repo, metric, expected, actual));
+            }
+        }
+    }
+
+    regressions
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        1.0
+    } else {
+        // Rounded so the baseline file stays readable
+        (numerator as f64 / denominator as f64 * 10_000.0).round() / 10_000.0
+    }
+}
+
+fn corpus_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("corpus")
+}
+
+fn run_corpus() -> Result<BTreeMap<String, CorpusReport>> {
+    let mut reports = BTreeMap::new();
+    for entry in fs::read_dir(corpus_dir()).context("Failed to read corpus directory")? {
+        let path = entry.context("Failed to read corpus entry")?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let report = analyze_corpus_repo(&path).with_context(|| format!("Failed to analyze corpus repo {}", name))?;
+        reports.insert(name, report);
+    }
+    Ok(reports)
+}
+
+#[test]
+#[ignore = "corpus benchmark; run with --ignored"]
+fn corpus_benchmark() {
+    let reports = run_corpus().expect("Corpus run failed");
+
+    let report_path = std::env::var("CORPUS_REPORT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| Path::new(env!("CARGO_MANIFEST_DIR")).join("target").join("corpus-report.json"));
+    if let Some(parent) = report_path.parent() {
+        fs::create_dir_all(parent).expect("Failed to create report directory");
+    }
+    fs::write(&report_path, serde_json::to_string_pretty(&reports).unwrap()).expect("Failed to write report");
+    println!("Corpus report written to {:?}", report_path);
+
+    let baseline_path = corpus_dir().join("baseline.json");
+    let existing: Option<CorpusBaseline> = fs::read_to_string(&baseline_path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok());
+
+    if std::env::var("UPDATE_CORPUS_BASELINE").is_ok_and(|v| v == "1") {
+        let baseline = CorpusBaseline {
+            tolerance: existing.map(|b| b.tolerance).unwrap_or(DEFAULT_TOLERANCE),
+            repos: reports.iter().map(|(name, r)| (name.clone(), r.quality.clone())).collect(),
+        };
+        fs::write(&baseline_path, serde_json::to_string_pretty(&baseline).unwrap() + "\n")
+            .expect("Failed to write baseline");
+        println!("Baseline updated at {:?}", baseline_path);
+        return;
+    }
+
+    let baseline = existing.expect("Missing or invalid tests/corpus/baseline.json; run with UPDATE_CORPUS_BASELINE=1");
+    let tolerance = std::env::var("CORPUS_TOLERANCE")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .unwrap_or(baseline.tolerance);
+
+    let regressions = find_regressions(&baseline.repos, &reports, tolerance);
+    assert!(regressions.is_empty(), "Corpus metrics regressed:\n{}", regressions.join("\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(metrics: &[(&str, f64)]) -> CorpusReport {
+        CorpusReport {
+            quality: metrics.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
+            timings_ms: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_find_regressions_respects_direction_and_tolerance() {
+        let mut baseline = BTreeMap::new();
+        baseline.insert(
+            "repo".to_string(),
+            [
+                ("functions".to_string(), 100.0),
+                ("call_resolution_rate".to_string(), 0.5),
+                ("imports_unresolved_relative".to_string(), 10.0),
+                ("imports_external".to_string(), 8.0),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        let mut current = BTreeMap::new();
+        current.insert(
+            "repo".to_string(),
+            report(&[
+                ("functions", 96.0),                   // within 5%
+                ("call_resolution_rate", 0.4),         // dropped
+                ("imports_unresolved_relative", 12.0), // rose
+                ("imports_external", 2.0),             // informational
+                ("endpoints", 3.0),                    // not in baseline
+            ]),
+        );
+
+        let regressions = find_regressions(&baseline, &current, 0.05);
+        assert_eq!(
+            regressions,
+            vec![
+                "repo: call_resolution_rate 0.5 -> 0.4",
+                "repo: imports_unresolved_relative 10 -> 12",
+            ]
+        );
+
+        current.clear();
+        assert_eq!(find_regressions(&baseline, &current, 0.05), vec!["repo: repository missing from corpus"]);
+    }
+}
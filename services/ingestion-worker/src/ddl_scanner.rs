@@ -0,0 +1,534 @@
+//! DDL Scanner
+//!
+//! Builds authoritative table definitions from schema and migration files:
+//! every `.sql` file plus Rails migrations under `db/migrate/`. Files are
+//! applied in path order, which is migration order for timestamped names, so
+//! `ALTER TABLE` and `DROP TABLE` act on the tables earlier files created.
+//! Table names found by regex in source files are then pointed at these
+//! definitions, see `canonicalize_table_references`.
+
+use crate::orm_detector::OrmModel;
+use crate::parsers::ParsedFile;
+use crate::path_filter::PathFilter;
+use anyhow::{Context, Result};
+use regex::Regex;
+use sqlparser::ast::{AlterColumnOperation, AlterTableOperation, ObjectName, ObjectType, Statement};
+use sqlparser::dialect::{Dialect, GenericDialect, MySqlDialect, PostgreSqlDialect};
+use sqlparser::parser::Parser as SqlParser;
+use std::fs;
+use std::path::Path;
+use tracing::debug;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DdlColumn {
+    pub name: String,
+    pub data_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DdlTable {
+    /// Name as written, without quotes; schema-qualified if it was
+    pub name: String,
+    pub columns: Vec<DdlColumn>,
+    /// Files that create or alter the table, in the order they were applied
+    pub files: Vec<String>,
+}
+
+/// Markers after which golang-migrate, goose and dbmate files hold the down
+/// migration
+const DOWN_MARKERS: &[&str] = &["-- +migrate Down", "-- +goose Down", "-- migrate:down"];
+
+/// Scan the repository's schema and migration files
+pub fn scan_migrations(repo_path: &Path, filter: &PathFilter) -> Result<Vec<DdlTable>> {
+    let mut files = Vec::new();
+    collect_ddl_files(repo_path, repo_path, filter, &mut files)?;
+    files.sort();
+
+    let mut sources = Vec::new();
+    for relative in files {
+        let Ok(content) = fs::read_to_string(repo_path.join(&relative)) else {
+            continue;
+        };
+        sources.push((relative, content));
+    }
+
+    let tables = analyze_ddl(&sources);
+    debug!("Found {} tables in DDL", tables.len());
+    Ok(tables)
+}
+
+fn collect_ddl_files(root: &Path, dir: &Path, filter: &PathFilter, results: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(dir).context("Failed to read directory")? {
+        let entry = entry.context("Failed to read directory entry")?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let file_type = entry.file_type().context("Failed to read file type")?;
+
+        if file_type.is_dir() {
+            if !name.starts_with('.') && !matches!(name.as_str(), "node_modules" | "target" | "dist" | "build" | "vendor") {
+                collect_ddl_files(root, &path, filter, results)?;
+            }
+            continue;
+        }
+        if !file_type.is_file() {
+            continue;
+        }
+
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+        if is_ddl_file(&relative) && filter.matches(&relative) {
+            results.push(relative);
+        }
+    }
+    Ok(())
+}
+
+/// `.sql` files other than separate down migrations, and Rails migrations
+fn is_ddl_file(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    if lower.ends_with(".sql") {
+        return !lower.ends_with(".down.sql") && !lower.ends_with("_down.sql");
+    }
+    lower.ends_with(".rb") && (lower.starts_with("db/migrate/") || lower.contains("/db/migrate/"))
+}
+
+/// Apply the DDL in `sources` (path, content), in order
+pub fn analyze_ddl(sources: &[(String, String)]) -> Vec<DdlTable> {
+    let mut tables: Vec<DdlTable> = Vec::new();
+
+    for (path, content) in sources {
+        let changes = if path.to_lowercase().ends_with(".rb") {
+            rails_changes(content)
+        } else {
+            sql_changes(up_migration(content))
+        };
+        for change in changes {
+            apply(&mut tables, change, path);
+        }
+    }
+
+    tables.sort_by(|a, b| a.name.cmp(&b.name));
+    tables
+}
+
+/// One schema change, in terms simple enough for both SQL and Rails
+#[derive(Debug)]
+enum Change {
+    Create { table: String, columns: Vec<DdlColumn> },
+    AddColumn { table: String, column: DdlColumn },
+    DropColumn { table: String, column: String },
+    RenameColumn { table: String, from: String, to: String },
+    SetType { table: String, column: String, data_type: String },
+    RenameTable { from: String, to: String },
+    Drop { table: String },
+}
+
+fn apply(tables: &mut Vec<DdlTable>, change: Change, path: &str) {
+    let position = |tables: &[DdlTable], name: &str| tables.iter().position(|t| same_table(&t.name, name));
+    let touch = |table: &mut DdlTable| {
+        if !table.files.iter().any(|f| f == path) {
+            table.files.push(path.to_string());
+        }
+    };
+
+    match change {
+        Change::Create { table, columns } => {
+            if let Some(i) = position(tables, &table) {
+                tables.remove(i);
+            }
+            tables.push(DdlTable { name: table, columns, files: vec![path.to_string()] });
+        }
+        Change::Drop { table } => {
+            if let Some(i) = position(tables, &table) {
+                tables.remove(i);
+            }
+        }
+        Change::RenameTable { from, to } => {
+            if let Some(i) = position(tables, &from) {
+                tables[i].name = to;
+                touch(&mut tables[i]);
+            }
+        }
+        Change::AddColumn { table, column } => {
+            if let Some(i) = position(tables, &table) {
+                if !tables[i].columns.iter().any(|c| c.name.eq_ignore_ascii_case(&column.name)) {
+                    tables[i].columns.push(column);
+                }
+                touch(&mut tables[i]);
+            }
+        }
+        Change::DropColumn { table, column } => {
+            if let Some(i) = position(tables, &table) {
+                tables[i].columns.retain(|c| !c.name.eq_ignore_ascii_case(&column));
+                touch(&mut tables[i]);
+            }
+        }
+        Change::RenameColumn { table, from, to } => {
+            if let Some(i) = position(tables, &table) {
+                if let Some(column) = tables[i].columns.iter_mut().find(|c| c.name.eq_ignore_ascii_case(&from)) {
+                    column.name = to;
+                }
+                touch(&mut tables[i]);
+            }
+        }
+        Change::SetType { table, column, data_type } => {
+            if let Some(i) = position(tables, &table) {
+                if let Some(column) = tables[i].columns.iter_mut().find(|c| c.name.eq_ignore_ascii_case(&column)) {
+                    column.data_type = data_type;
+                }
+                touch(&mut tables[i]);
+            }
+        }
+    }
+}
+
+/// The part of a migration file before its down section
+fn up_migration(content: &str) -> &str {
+    DOWN_MARKERS
+        .iter()
+        .filter_map(|marker| content.find(marker))
+        .min()
+        .map_or(content, |end| &content[..end])
+}
+
+/// Schema changes in SQL text. The whole text is tried first; if no dialect
+/// accepts it, statements are parsed one by one and unparseable ones (stored
+/// procedures, vendor syntax) are skipped.
+fn sql_changes(content: &str) -> Vec<Change> {
+    let statements = parse_statements(content).unwrap_or_else(|| {
+        content
+            .split(';')
+            .filter(|s| !s.trim().is_empty())
+            .filter_map(parse_statements)
+            .flatten()
+            .collect()
+    });
+    statements.iter().flat_map(statement_changes).collect()
+}
+
+/// Postgres first, unless backtick quoting gives MySQL away: the Postgres
+/// dialect reads MySQL's inline `KEY idx (col)` as a column named KEY
+fn parse_statements(sql: &str) -> Option<Vec<Statement>> {
+    let dialects: [&dyn Dialect; 3] = if sql.contains('`') {
+        [&MySqlDialect {}, &PostgreSqlDialect {}, &GenericDialect {}]
+    } else {
+        [&PostgreSqlDialect {}, &MySqlDialect {}, &GenericDialect {}]
+    };
+    dialects.into_iter().find_map(|dialect| SqlParser::parse_sql(dialect, sql).ok())
+}
+
+fn statement_changes(statement: &Statement) -> Vec<Change> {
+    match statement {
+        Statement::CreateTable { name, columns, .. } => vec![Change::Create {
+            table: object_name(name),
+            columns: columns
+                .iter()
+                .map(|c| DdlColumn { name: c.name.value.clone(), data_type: c.data_type.to_string() })
+                .collect(),
+        }],
+        Statement::AlterTable { name, operations, .. } => {
+            let table = object_name(name);
+            operations.iter().flat_map(|op| alter_changes(&table, op)).collect()
+        }
+        Statement::Drop { object_type: ObjectType::Table, names, .. } => {
+            names.iter().map(|name| Change::Drop { table: object_name(name) }).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn alter_changes(table: &str, operation: &AlterTableOperation) -> Vec<Change> {
+    let table = table.to_string();
+    match operation {
+        AlterTableOperation::AddColumn { column_def, .. } => vec![Change::AddColumn {
+            table,
+            column: DdlColumn { name: column_def.name.value.clone(), data_type: column_def.data_type.to_string() },
+        }],
+        AlterTableOperation::DropColumn { column_name, .. } => {
+            vec![Change::DropColumn { table, column: column_name.value.clone() }]
+        }
+        AlterTableOperation::RenameColumn { old_column_name, new_column_name } => vec![Change::RenameColumn {
+            table,
+            from: old_column_name.value.clone(),
+            to: new_column_name.value.clone(),
+        }],
+        // MySQL's CHANGE renames and retypes in one go
+        AlterTableOperation::ChangeColumn { old_name, new_name, data_type, .. } => vec![
+            Change::RenameColumn { table: table.clone(), from: old_name.value.clone(), to: new_name.value.clone() },
+            Change::SetType { table, column: new_name.value.clone(), data_type: data_type.to_string() },
+        ],
+        AlterTableOperation::AlterColumn { column_name, op: AlterColumnOperation::SetDataType { data_type, .. } } => {
+            vec![Change::SetType { table, column: column_name.value.clone(), data_type: data_type.to_string() }]
+        }
+        AlterTableOperation::RenameTable { table_name } => {
+            vec![Change::RenameTable { from: table, to: object_name(table_name) }]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Unquoted, dot-joined name
+fn object_name(name: &ObjectName) -> String {
+    name.0.iter().map(|ident| ident.value.as_str()).collect::<Vec<_>>().join(".")
+}
+
+/// Schema changes in a Rails migration: `create_table` blocks with
+/// `t.<type> :column` lines, `add_column`, `remove_column`, `rename_column`
+/// and `drop_table`
+fn rails_changes(content: &str) -> Vec<Change> {
+    let create_re = Regex::new(r#"^\s*create_table\s*\(?\s*[:"']([A-Za-z0-9_]+)"#).unwrap();
+    let column_re = Regex::new(r#"^\s*t\.([a-z_]+)\s*\(?\s*[:"']([A-Za-z0-9_]+)"#).unwrap();
+    let add_re = Regex::new(r#"^\s*add_column\s*\(?\s*[:"']([A-Za-z0-9_]+)["']?\s*,\s*[:"']([A-Za-z0-9_]+)["']?\s*,\s*:([a-z_]+)"#).unwrap();
+    let remove_re = Regex::new(r#"^\s*remove_column\s*\(?\s*[:"']([A-Za-z0-9_]+)["']?\s*,\s*[:"']([A-Za-z0-9_]+)"#).unwrap();
+    let rename_re = Regex::new(r#"^\s*rename_column\s*\(?\s*[:"']([A-Za-z0-9_]+)["']?\s*,\s*[:"']([A-Za-z0-9_]+)["']?\s*,\s*[:"']([A-Za-z0-9_]+)"#).unwrap();
+    let drop_re = Regex::new(r#"^\s*drop_table\s*\(?\s*[:"']([A-Za-z0-9_]+)"#).unwrap();
+
+    let mut changes = Vec::new();
+    let mut current: Option<(String, Vec<DdlColumn>)> = None;
+    for line in content.lines() {
+        if let Some((_, columns)) = current.as_mut() {
+            if let Some(cap) = column_re.captures(line) {
+                match &cap[1] {
+                    "index" | "check_constraint" | "foreign_key" => {}
+                    "references" | "belongs_to" => {
+                        columns.push(DdlColumn { name: format!("{}_id", &cap[2]), data_type: "bigint".to_string() })
+                    }
+                    data_type => columns.push(DdlColumn { name: cap[2].to_string(), data_type: data_type.to_string() }),
+                }
+                continue;
+            }
+            if line.trim_start().starts_with("t.timestamps") {
+                for name in ["created_at", "updated_at"] {
+                    columns.push(DdlColumn { name: name.to_string(), data_type: "datetime".to_string() });
+                }
+                continue;
+            }
+            if line.trim() == "end" {
+                let (table, mut columns) = current.take().unwrap();
+                // Rails adds the primary key itself
+                columns.insert(0, DdlColumn { name: "id".to_string(), data_type: "bigint".to_string() });
+                changes.push(Change::Create { table, columns });
+            }
+            continue;
+        }
+
+        if let Some(cap) = create_re.captures(line) {
+            current = Some((cap[1].to_string(), Vec::new()));
+        } else if let Some(cap) = add_re.captures(line) {
+            changes.push(Change::AddColumn {
+                table: cap[1].to_string(),
+                column: DdlColumn { name: cap[2].to_string(), data_type: cap[3].to_string() },
+            });
+        } else if let Some(cap) = remove_re.captures(line) {
+            changes.push(Change::DropColumn { table: cap[1].to_string(), column: cap[2].to_string() });
+        } else if let Some(cap) = rename_re.captures(line) {
+            changes.push(Change::RenameColumn { table: cap[1].to_string(), from: cap[2].to_string(), to: cap[3].to_string() });
+        } else if let Some(cap) = drop_re.captures(line) {
+            changes.push(Change::Drop { table: cap[1].to_string() });
+        }
+    }
+    changes
+}
+
+/// Whether two table references name the same table: equal ignoring case,
+/// quotes and a schema prefix on either side
+fn same_table(a: &str, b: &str) -> bool {
+    let unquote = |name: &str| name.replace(['"', '`', '[', ']'], "").to_lowercase();
+    let (a, b) = (unquote(a), unquote(b));
+    let last = |name: &str| name.rsplit('.').next().unwrap_or_default().to_string();
+    a == b || ((a.contains('.') || b.contains('.')) && last(&a) == last(&b))
+}
+
+/// The DDL table `reference` refers to, if exactly one matches
+pub fn canonical_table_name<'a>(reference: &str, tables: &'a [DdlTable]) -> Option<&'a str> {
+    if let Some(table) = tables.iter().find(|t| t.name == reference) {
+        return Some(&table.name);
+    }
+    let mut matches = tables.iter().filter(|t| same_table(&t.name, reference));
+    match (matches.next(), matches.next()) {
+        (Some(table), None) => Some(&table.name),
+        _ => None,
+    }
+}
+
+/// Rewrite regex-found tables and query reads/writes that refer to a DDL
+/// table to its name, so they MERGE onto the DDL's Table node instead of
+/// creating lookalikes such as `public.users` or `Users`. Each file is
+/// rewritten on its own.
+pub fn canonicalize_table_references(parsed_files: &mut [ParsedFile], tables: &[DdlTable]) {
+    if tables.is_empty() {
+        return;
+    }
+    let canonicalize = |names: &mut Vec<String>| {
+        for name in names.iter_mut() {
+            if let Some(canonical) = canonical_table_name(name, tables) {
+                *name = canonical.to_string();
+            }
+        }
+        let mut seen = std::collections::HashSet::new();
+        names.retain(|name| seen.insert(name.clone()));
+    };
+
+    for file in parsed_files.iter_mut() {
+        canonicalize(&mut file.data_tables);
+        for query in &mut file.sql_queries {
+            canonicalize(&mut query.reads);
+            canonicalize(&mut query.writes);
+        }
+    }
+}
+
+/// Rewrite ORM tables that refer to a DDL table to its name, see
+/// `canonicalize_table_references`
+pub fn canonicalize_orm_tables(orm_models: &mut [OrmModel], tables: &[DdlTable]) {
+    for model in orm_models.iter_mut() {
+        if let Some(canonical) = canonical_table_name(&model.table, tables) {
+            model.table = canonical.to_string();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(table: &DdlTable) -> Vec<(&str, &str)> {
+        table.columns.iter().map(|c| (c.name.as_str(), c.data_type.as_str())).collect()
+    }
+
+    #[test]
+    fn test_postgres_ddl_with_alter_table() {
+        let sources = vec![
+            (
+                "migrations/0001_init.sql".to_string(),
+                r#"
+-- +goose Up
+CREATE TABLE IF NOT EXISTS public.users (
+    id SERIAL PRIMARY KEY,
+    email VARCHAR(255) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ DEFAULT now()
+);
+CREATE TABLE orders (
+    id BIGSERIAL PRIMARY KEY,
+    user_id INTEGER REFERENCES public.users(id) ON DELETE CASCADE,
+    total NUMERIC(10, 2)
+);
+CREATE OR REPLACE FUNCTION touch() RETURNS trigger AS $$ BEGIN RETURN NEW; END; $$ LANGUAGE plpgsql;
+-- +goose Down
+DROP TABLE orders;
+"#
+                .to_string(),
+            ),
+            (
+                "migrations/0002_profile.sql".to_string(),
+                "ALTER TABLE users ADD COLUMN display_name TEXT;\nALTER TABLE orders DROP COLUMN total;\n".to_string(),
+            ),
+        ];
+
+        let tables = analyze_ddl(&sources);
+        let names: Vec<&str> = tables.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["orders", "public.users"]);
+
+        assert_eq!(columns(&tables[0]), vec![("id", "BIGSERIAL"), ("user_id", "INTEGER")]);
+        assert_eq!(
+            columns(&tables[1]),
+            vec![
+                ("id", "SERIAL"),
+                ("email", "VARCHAR(255)"),
+                ("created_at", "TIMESTAMPTZ"),
+                ("display_name", "TEXT"),
+            ]
+        );
+        assert_eq!(tables[1].files, vec!["migrations/0001_init.sql", "migrations/0002_profile.sql"]);
+    }
+
+    #[test]
+    fn test_mysql_ddl() {
+        let sources = vec![(
+            "db/schema.sql".to_string(),
+            r#"
+CREATE TABLE `products` (
+  `id` INT UNSIGNED NOT NULL AUTO_INCREMENT,
+  `sku` VARCHAR(64) NOT NULL,
+  `price` DECIMAL(10,2) DEFAULT NULL,
+  PRIMARY KEY (`id`),
+  KEY `idx_sku` (`sku`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+ALTER TABLE `products` ADD COLUMN `stock` INT NOT NULL DEFAULT 0;
+ALTER TABLE products RENAME COLUMN sku TO code;
+"#
+            .to_string(),
+        )];
+
+        let tables = analyze_ddl(&sources);
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].name, "products");
+        let names: Vec<&str> = tables[0].columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["id", "code", "price", "stock"]);
+    }
+
+    #[test]
+    fn test_rails_migrations() {
+        let sources = vec![
+            (
+                "db/migrate/20240101000000_create_accounts.rb".to_string(),
+                r#"
+class CreateAccounts < ActiveRecord::Migration[7.1]
+  def change
+    create_table :accounts do |t|
+      t.string :name, null: false
+      t.references :owner, foreign_key: true
+      t.timestamps
+    end
+  end
+end
+"#
+                .to_string(),
+            ),
+            (
+                "db/migrate/20240201000000_add_plan_to_accounts.rb".to_string(),
+                "class AddPlan < ActiveRecord::Migration[7.1]\n  def change\n    add_column :accounts, :plan, :string\n  end\nend\n"
+                    .to_string(),
+            ),
+        ];
+
+        let tables = analyze_ddl(&sources);
+        assert_eq!(tables.len(), 1);
+        assert_eq!(
+            columns(&tables[0]),
+            vec![
+                ("id", "bigint"),
+                ("name", "string"),
+                ("owner_id", "bigint"),
+                ("created_at", "datetime"),
+                ("updated_at", "datetime"),
+                ("plan", "string"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ddl_file_selection() {
+        assert!(is_ddl_file("migrations/0001_init.sql"));
+        assert!(is_ddl_file("schema.SQL"));
+        assert!(!is_ddl_file("migrations/0001_init.down.sql"));
+        assert!(is_ddl_file("db/migrate/20240101_create_users.rb"));
+        assert!(is_ddl_file("apps/api/db/migrate/20240101_create_users.rb"));
+        assert!(!is_ddl_file("app/models/user.rb"));
+    }
+
+    #[test]
+    fn test_references_canonicalize_to_ddl_tables() {
+        let table = |name: &str| DdlTable { name: name.to_string(), columns: vec![], files: vec![] };
+        let tables = vec![table("users"), table("billing.invoices"), table("audit.events"), table("public.events")];
+
+        assert_eq!(canonical_table_name("users", &tables), Some("users"));
+        assert_eq!(canonical_table_name("Users", &tables), Some("users"));
+        assert_eq!(canonical_table_name("public.users", &tables), Some("users"));
+        assert_eq!(canonical_table_name("\"users\"", &tables), Some("users"));
+        assert_eq!(canonical_table_name("invoices", &tables), Some("billing.invoices"));
+        // Ambiguous without the schema
+        assert_eq!(canonical_table_name("events", &tables), None);
+        assert_eq!(canonical_table_name("sessions", &tables), None);
+    }
+}
@@ -1,8 +1,434 @@
+use crate::parsers::ParsedFile;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LibraryDependency {
     pub name: String,
     pub version: Option<String>,
     pub source_file: String,
+    /// OSV advisory ids, `None` unless the library was checked, see `osv_client`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vulnerability_ids: Option<Vec<String>>,
+    /// Imports of the library, `None` unless every file was parsed, see
+    /// `apply_library_usage`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<LibraryUsage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryUsage {
+    /// Files importing the library
+    pub used_by_count: usize,
+    /// Declared but never imported
+    pub unused: bool,
+    /// Never imported, but usually used some other way (a CLI tool, type
+    /// declarations, a plugin), so not being imported says nothing
+    pub usage_unknown: bool,
+}
+
+/// Python distributions imported under an unrelated name, as
+/// `(import, distribution)`
+const PYTHON_IMPORT_NAMES: &[(&str, &str)] = &[
+    ("bs4", "beautifulsoup4"),
+    ("cv2", "opencv-python"),
+    ("dateutil", "python-dateutil"),
+    ("google.protobuf", "protobuf"),
+    ("jwt", "pyjwt"),
+    ("pil", "pillow"),
+    ("sklearn", "scikit-learn"),
+    ("yaml", "pyyaml"),
+];
+
+/// Libraries run as tools or loaded by configuration rather than imported
+const USAGE_UNKNOWN_NAMES: &[&str] = &[
+    "typescript", "ts-node", "tsx", "nodemon", "concurrently", "rimraf", "cross-env", "husky",
+    "lint-staged", "prettier", "eslint", "jest", "vitest", "mocha", "webpack", "webpack-cli",
+    "vite", "rollup", "esbuild", "tailwindcss", "postcss", "autoprefixer", "pytest", "pytest-cov",
+    "black", "ruff", "flake8", "isort", "mypy", "pylint", "coverage", "tox", "pre-commit",
+    "gunicorn", "uvicorn", "setuptools", "wheel",
+];
+
+/// Name prefixes of type declaration and plugin packages
+const USAGE_UNKNOWN_PREFIXES: &[&str] =
+    &["@types/", "@typescript-eslint/", "@babel/", "eslint-", "prettier-", "types-", "pytest-", "flake8-"];
+
+fn usage_unknown(name: &str) -> bool {
+    let name = name.to_lowercase();
+    USAGE_UNKNOWN_NAMES.contains(&name.as_str()) || USAGE_UNKNOWN_PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+/// Lookup key of a library name: lowercase, with `_` and `.` read as `-`,
+/// so `typing_extensions` imports `typing-extensions` and `tokio_postgres`
+/// is the `tokio-postgres` crate
+fn name_key(name: &str) -> String {
+    name.to_lowercase().replace(['_', '.'], "-")
+}
+
+/// Finds the declared library an import refers to
+pub struct LibraryMatcher<'a> {
+    /// Maven `groupId:artifactId` names
+    maven: Vec<&'a str>,
+    /// Other names by `name_key`, `python-` prefix dropped as well
+    by_key: HashMap<String, &'a str>,
+    /// Names that are import path prefixes: Go modules, npm packages
+    paths: Vec<&'a str>,
+}
+
+impl<'a> LibraryMatcher<'a> {
+    pub fn new(dependencies: &'a [LibraryDependency]) -> Self {
+        let mut matcher = LibraryMatcher { maven: Vec::new(), by_key: HashMap::new(), paths: Vec::new() };
+        for dep in dependencies {
+            let name = dep.name.as_str();
+            if name.contains(':') {
+                matcher.maven.push(name);
+                continue;
+            }
+            matcher.by_key.insert(name_key(name), name);
+            if let Some(short) = name_key(name).strip_prefix("python-") {
+                matcher.by_key.entry(short.to_string()).or_insert(name);
+            }
+            if name.contains('/') {
+                matcher.paths.push(name);
+            }
+        }
+        for (import, distribution) in PYTHON_IMPORT_NAMES {
+            if let Some(name) = matcher.by_key.get(*distribution).copied() {
+                matcher.by_key.entry(name_key(import)).or_insert(name);
+            }
+        }
+        matcher
+    }
+
+    /// The declared library `import` comes from. Tried in turn: a Maven
+    /// library for a dotted JVM import, the whole import, the longest
+    /// declared name it extends by path (`github.com/gin-gonic/gin/render`,
+    /// `@scope/pkg/sub`), then its first segment (`lodash/fp`, `serde::de`,
+    /// `dateutil.parser`).
+    pub fn library_for_import(&self, import: &str) -> Option<&'a str> {
+        let trimmed = import.trim().trim_matches('"').trim_matches('`');
+        if trimmed.is_empty() || trimmed.starts_with('.') || trimmed.starts_with('/') {
+            return None;
+        }
+
+        if trimmed.contains('.') && !trimmed.contains('/') && !trimmed.contains("::") {
+            if let Some(library) = maven_library_for_import(trimmed, &self.maven) {
+                return Some(library);
+            }
+        }
+        if let Some(library) = self.by_key.get(&name_key(trimmed)) {
+            return Some(*library);
+        }
+        let extended = self
+            .paths
+            .iter()
+            .filter(|name| trimmed.strip_prefix(**name).is_some_and(|rest| rest.starts_with('/')))
+            .max_by_key(|name| name.len());
+        if let Some(library) = extended {
+            return Some(*library);
+        }
+        let first = trimmed.split(['/', ':', '.']).next().unwrap_or(trimmed);
+        self.by_key.get(&name_key(first)).copied()
+    }
+}
+
+/// The Maven library a package import most likely comes from. A library
+/// matches when its groupId is a prefix of the import, or when they share
+/// at least two leading segments and the import names part of the artifactId
+/// (`com.fasterxml.jackson.databind` for `com.fasterxml.jackson.core:jackson-databind`).
+/// The most specific match wins.
+fn maven_library_for_import<'a>(import_path: &str, maven_libraries: &[&'a str]) -> Option<&'a str> {
+    let segments: Vec<&str> = import_path.trim_end_matches(".*").split('.').collect();
+
+    maven_libraries
+        .iter()
+        .filter_map(|library| {
+            let (group, artifact) = library.split_once(':')?;
+            let group: Vec<&str> = group.split('.').collect();
+            let shared = group.iter().zip(&segments).take_while(|(g, s)| g == s).count();
+            let named = artifact
+                .split(['-', '_', '.'])
+                .filter(|token| segments.contains(token))
+                .count();
+            let matches = shared == group.len() || (shared >= 2 && named > 0);
+            matches.then_some(((shared, named), *library))
+        })
+        .max_by(|(a, lib_a), (b, lib_b)| a.cmp(b).then_with(|| lib_b.cmp(lib_a)))
+        .map(|(_, library)| library)
+}
+
+/// Count the files importing each declared library. Only meaningful when
+/// `parsed_files` is the whole repository.
+pub fn apply_library_usage(dependencies: &mut [LibraryDependency], parsed_files: &[ParsedFile]) {
+    let mut users: HashMap<String, HashSet<&str>> = HashMap::new();
+    {
+        let matcher = LibraryMatcher::new(dependencies);
+        for file in parsed_files {
+            for import in &file.imports {
+                if let Some(library) = matcher.library_for_import(import) {
+                    users.entry(library.to_string()).or_default().insert(&file.path);
+                }
+            }
+        }
+    }
+
+    for dep in dependencies {
+        let used_by_count = users.get(&dep.name).map_or(0, HashSet::len);
+        let unknown = used_by_count == 0 && usage_unknown(&dep.name);
+        dep.usage = Some(LibraryUsage {
+            used_by_count,
+            unused: used_by_count == 0 && !unknown,
+            usage_unknown: unknown,
+        });
+    }
+}
+
+/// A library as stored, for the dependency diff
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LibraryVersion {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+/// A library whose declared version changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VersionChange {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// How a run's library dependencies differ from the stored ones, by name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LibraryDiff {
+    pub added: Vec<LibraryVersion>,
+    pub removed: Vec<LibraryVersion>,
+    /// Every version change, downgrades included: specs like git refs, `*`
+    /// or `workspace:*` can't be ordered, so versions are only compared
+    pub upgraded: Vec<VersionChange>,
+}
+
+/// Compare `current` with the `stored` name-to-version map, versions being
+/// empty when unknown. Versions are compared as trimmed strings; an unknown
+/// version on either side is no change, as storage keeps the known one.
+/// A library declared in several manifests takes its last known version,
+/// like its Library node.
+pub fn diff_libraries(stored: &HashMap<String, String>, current: &[LibraryDependency]) -> LibraryDiff {
+    let mut versions: BTreeMap<&str, Option<&str>> = BTreeMap::new();
+    for dep in current {
+        let version = dep.version.as_deref().map(str::trim).filter(|v| !v.is_empty());
+        let entry = versions.entry(dep.name.as_str()).or_default();
+        if version.is_some() {
+            *entry = version;
+        }
+    }
+
+    let mut diff = LibraryDiff::default();
+    for (&name, &version) in &versions {
+        match stored.get(name).map(|v| v.trim()) {
+            None => diff.added.push(LibraryVersion { name: name.to_string(), version: version.map(str::to_string) }),
+            Some(old) => {
+                if let Some(new) = version.filter(|new| !old.is_empty() && new != &old) {
+                    diff.upgraded.push(VersionChange {
+                        name: name.to_string(),
+                        from: old.to_string(),
+                        to: new.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    let mut removed: Vec<(&String, &String)> =
+        stored.iter().filter(|(name, _)| !versions.contains_key(name.as_str())).collect();
+    removed.sort();
+    diff.removed = removed
+        .into_iter()
+        .map(|(name, version)| LibraryVersion {
+            name: name.clone(),
+            version: Some(version.trim().to_string()).filter(|v| !v.is_empty()),
+        })
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(name: &str) -> LibraryDependency {
+        LibraryDependency {
+            name: name.to_string(),
+            version: None,
+            source_file: "package.json".to_string(),
+            vulnerability_ids: None,
+            usage: None,
+        }
+    }
+
+    fn file(path: &str, imports: &[&str]) -> ParsedFile {
+        ParsedFile {
+            imports: imports.iter().map(|i| i.to_string()).collect(),
+            ..ParsedFile::failed(path.to_string(), "typescript", "")
+        }
+    }
+
+    #[test]
+    fn test_java_imports_match_maven_libraries() {
+        let deps: Vec<LibraryDependency> = [
+            "com.fasterxml.jackson.core:jackson-core",
+            "com.fasterxml.jackson.core:jackson-databind",
+            "org.springframework.boot:spring-boot-starter-web",
+            "com.google.guava:guava",
+        ]
+        .into_iter()
+        .map(dependency)
+        .collect();
+        let matcher = LibraryMatcher::new(&deps);
+
+        assert_eq!(
+            matcher.library_for_import("com.fasterxml.jackson.databind.ObjectMapper"),
+            Some("com.fasterxml.jackson.core:jackson-databind")
+        );
+        assert_eq!(
+            matcher.library_for_import("com.fasterxml.jackson.core.JsonParser"),
+            Some("com.fasterxml.jackson.core:jackson-core")
+        );
+        assert_eq!(
+            matcher.library_for_import("org.springframework.boot.SpringApplication"),
+            Some("org.springframework.boot:spring-boot-starter-web")
+        );
+        assert_eq!(
+            matcher.library_for_import("org.springframework.boot.autoconfigure.*"),
+            Some("org.springframework.boot:spring-boot-starter-web")
+        );
+        // Guava's packages don't share its groupId beyond `com.google`
+        assert_eq!(matcher.library_for_import("com.google.common.collect.ImmutableList"), None);
+    }
+
+    #[test]
+    fn test_imports_match_npm_python_go_and_cargo_names() {
+        let deps: Vec<LibraryDependency> = [
+            "@scope/pkg",
+            "lodash",
+            "typing-extensions",
+            "python-dateutil",
+            "Flask_Cors",
+            "beautifulsoup4",
+            "github.com/gin-gonic/gin",
+            "tokio-postgres",
+        ]
+        .into_iter()
+        .map(dependency)
+        .collect();
+        let matcher = LibraryMatcher::new(&deps);
+
+        for (import, library) in [
+            ("@scope/pkg", Some("@scope/pkg")),
+            ("@scope/pkg/sub", Some("@scope/pkg")),
+            ("@scope/other", None),
+            ("lodash/fp", Some("lodash")),
+            ("typing_extensions", Some("typing-extensions")),
+            ("dateutil.parser", Some("python-dateutil")),
+            ("flask_cors", Some("Flask_Cors")),
+            ("bs4", Some("beautifulsoup4")),
+            ("github.com/gin-gonic/gin", Some("github.com/gin-gonic/gin")),
+            ("github.com/gin-gonic/gin/render", Some("github.com/gin-gonic/gin")),
+            ("github.com/gin-gonic/ginx", None),
+            ("tokio_postgres::Client", Some("tokio-postgres")),
+            ("./lodash", None),
+            ("react", None),
+        ] {
+            assert_eq!(matcher.library_for_import(import), library, "{}", import);
+        }
+    }
+
+    #[test]
+    fn test_library_usage_counts_importing_files() {
+        let mut deps: Vec<LibraryDependency> =
+            ["react", "left-pad", "@types/node", "typescript", "eslint-plugin-react", "jest"].into_iter().map(dependency).collect();
+        let files = vec![
+            file("src/app.tsx", &["react", "react/jsx-runtime"]),
+            file("src/index.tsx", &["react", "./app"]),
+            file("src/app.test.tsx", &["jest"]),
+        ];
+        apply_library_usage(&mut deps, &files);
+
+        let usage: Vec<(&str, usize, bool, bool)> = deps
+            .iter()
+            .map(|dep| {
+                let usage = dep.usage.as_ref().unwrap();
+                (dep.name.as_str(), usage.used_by_count, usage.unused, usage.usage_unknown)
+            })
+            .collect();
+        assert_eq!(
+            usage,
+            vec![
+                ("react", 2, false, false),
+                ("left-pad", 0, true, false),
+                ("@types/node", 0, false, true),
+                ("typescript", 0, false, true),
+                ("eslint-plugin-react", 0, false, true),
+                // Imported, so its usage is known after all
+                ("jest", 1, false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_libraries_against_stored_versions() {
+        let versioned = |name: &str, version: Option<&str>| LibraryDependency {
+            version: version.map(str::to_string),
+            ..dependency(name)
+        };
+        let stored: HashMap<String, String> = [
+            ("react", "^17.0.2"),
+            ("left-pad", "1.3.0"),
+            ("ui-kit", "workspace:*"),
+            ("forked", "git+https://github.com/acme/forked.git#v1"),
+            ("anything", "*"),
+            ("unpinned", ""),
+            ("lodash", "4.17.21"),
+        ]
+        .into_iter()
+        .map(|(name, version)| (name.to_string(), version.to_string()))
+        .collect();
+        let current = vec![
+            versioned("react", Some("^18.2.0")),
+            versioned("ui-kit", Some("workspace:*")),
+            versioned("forked", Some("git+https://github.com/acme/forked.git#v2")),
+            versioned("anything", Some("^2.0.0")),
+            versioned("unpinned", Some("3.1.0")),
+            // An unknown version says nothing about the stored one
+            versioned("lodash", None),
+            versioned("zod", Some(" 3.22.4 ")),
+            versioned("zod", None),
+        ];
+
+        let diff = diff_libraries(&stored, &current);
+        assert_eq!(
+            diff.added,
+            vec![LibraryVersion { name: "zod".to_string(), version: Some("3.22.4".to_string()) }]
+        );
+        assert_eq!(
+            diff.removed,
+            vec![LibraryVersion { name: "left-pad".to_string(), version: Some("1.3.0".to_string()) }]
+        );
+        let change = |name: &str, from: &str, to: &str| VersionChange {
+            name: name.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+        };
+        assert_eq!(
+            diff.upgraded,
+            vec![
+                change("anything", "*", "^2.0.0"),
+                change("forked", "git+https://github.com/acme/forked.git#v1", "git+https://github.com/acme/forked.git#v2"),
+                change("react", "^17.0.2", "^18.2.0"),
+            ]
+        );
+
+        assert!(diff_libraries(&stored, &[]).added.is_empty());
+        assert_eq!(diff_libraries(&stored, &[]).removed.len(), stored.len());
+        assert_eq!(diff_libraries(&HashMap::new(), &[]), LibraryDiff::default());
+    }
 }
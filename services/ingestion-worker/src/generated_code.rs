@@ -0,0 +1,141 @@
+//! Generated Code Classification
+//!
+//! Decides whether a source file was produced by a tool rather than written
+//! by hand, so protobuf stubs, OpenAPI clients and bundles don't skew
+//! function counts and hotspots. A file is generated when its name says so
+//! (`*_pb2.py`, `*.pb.go`, `*.generated.ts`, `*.min.js`, ...), when its
+//! header carries a generator's marker ("Code generated by protoc",
+//! "DO NOT EDIT", `@generated`, OpenAPI Generator's notice), when it points
+//! at a source map, or when its lines average over 500 characters, as
+//! minified code does.
+
+use regex::Regex;
+
+/// Job option that leaves generated files out of the parse entirely
+pub const SKIP_GENERATED_OPTION: &str = "skip_generated";
+
+/// File name endings of generated code
+const GENERATED_SUFFIXES: &[&str] = &[
+    "_pb2.py",
+    "_pb2_grpc.py",
+    "_pb2.pyi",
+    ".pb.go",
+    ".pb.gw.go",
+    "_pb.js",
+    "_pb.d.ts",
+    "_grpc_pb.js",
+    ".pb.cc",
+    ".pb.h",
+    ".min.js",
+    ".min.mjs",
+    ".bundle.js",
+];
+
+/// Infixes like `api.generated.ts` or `Client.g.cs`
+const GENERATED_INFIXES: &[&str] = &[".generated.", ".gen.", ".g."];
+
+/// Directories whose files are all generated
+const GENERATED_DIRS: &[&str] = &["__generated__", ".openapi-generator"];
+
+/// Lines of the header searched for a generator's marker
+const HEADER_LINES: usize = 20;
+
+/// Average line length above which a file is taken as minified
+const MINIFIED_LINE_LENGTH: usize = 500;
+
+/// Generator markers looked for in the header
+const HEADER_MARKER: &str = r"(?i)\bcode generated by\b|\bDO NOT EDIT\b|@generated\b|generated by the protocol buffer compiler|auto[- ]?generated by (?:openapi|swagger)|openapi-generator\.tech|swagger-codegen";
+
+/// `//# sourceMappingURL=` left by bundlers and compilers
+const SOURCE_MAP_POINTER: &str = r"(?m)^\s*//[#@]\s*sourceMappingURL=";
+
+/// Whether the file at repo-relative `path` with `content` is generated
+pub fn is_generated(path: &str, content: &str) -> bool {
+    is_generated_path(path) || has_generator_header(content) || has_source_map(content) || is_minified(content)
+}
+
+fn is_generated_path(path: &str) -> bool {
+    let mut segments: Vec<&str> = path.split(['/', '\\']).collect();
+    let file_name = segments.pop().unwrap_or_default().to_lowercase();
+    segments.iter().any(|dir| GENERATED_DIRS.contains(dir))
+        || GENERATED_SUFFIXES.iter().any(|suffix| file_name.ends_with(suffix))
+        || GENERATED_INFIXES.iter().any(|infix| file_name.contains(infix))
+}
+
+fn has_generator_header(content: &str) -> bool {
+    let marker = Regex::new(HEADER_MARKER).unwrap();
+    content.lines().take(HEADER_LINES).any(|line| marker.is_match(line))
+}
+
+/// Only the end of the file is searched, where bundlers put the pointer
+fn has_source_map(content: &str) -> bool {
+    let tail_start = content.len().saturating_sub(1024);
+    let tail_start = (tail_start..content.len()).find(|&i| content.is_char_boundary(i)).unwrap_or(content.len());
+    Regex::new(SOURCE_MAP_POINTER).unwrap().is_match(&content[tail_start..])
+}
+
+fn is_minified(content: &str) -> bool {
+    let lines = content.lines().filter(|line| !line.trim().is_empty()).count();
+    lines > 0 && content.len() / lines > MINIFIED_LINE_LENGTH
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HANDWRITTEN: &str = "def total(items):\n    return sum(items)\n";
+
+    #[test]
+    fn test_generated_file_names() {
+        for path in [
+            "protos/user_pb2.py",
+            "protos/user_pb2_grpc.py",
+            "api/user.pb.go",
+            "web/src/api.generated.ts",
+            "web/static/vendor.min.js",
+            "web/src/__generated__/schema.ts",
+        ] {
+            assert!(is_generated(path, HANDWRITTEN), "{}", path);
+        }
+        for path in ["src/pb2.py", "web/src/generator.ts", "web/admin.js", "cmd/gen/main.go"] {
+            assert!(!is_generated(path, HANDWRITTEN), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_generator_headers() {
+        let protoc = "// Code generated by protoc-gen-go. DO NOT EDIT.\n// source: user.proto\n\npackage users\n";
+        assert!(is_generated("users/user.go", protoc));
+        let python = "# -*- coding: utf-8 -*-\n# Generated by the protocol buffer compiler.  DO NOT EDIT!\n";
+        assert!(is_generated("users/user.py", python));
+        let marker = "/**\n * @generated SignedSource<<abc>>\n */\nexport const schema = {};\n";
+        assert!(is_generated("web/schema.ts", marker));
+        let openapi = "/**\n * Pet Store\n * NOTE: This class is auto generated by OpenAPI Generator (https://openapi-generator.tech).\n */\nexport class PetApi {}\n";
+        assert!(is_generated("client/api.ts", openapi));
+
+        // A marker far below the header is code, not a header
+        let late = format!("{}# DO NOT EDIT below\n", "x = 1\n".repeat(HEADER_LINES));
+        assert!(!is_generated("settings.py", &late));
+    }
+
+    #[test]
+    fn test_source_map_pointer() {
+        let bundle = "\"use strict\";\nexports.run = function () { return 1; };\n//# sourceMappingURL=index.js.map\n";
+        assert!(is_generated("dist/index.js", bundle));
+    }
+
+    #[test]
+    fn test_minified_by_line_length() {
+        let minified = format!("!function(){{{}}}();\n", "var a=1;".repeat(100));
+        assert!(is_generated("web/app.js", &minified));
+        let long_but_normal = format!("{}\n", "const x = 1;\n".repeat(200));
+        assert!(!is_generated("web/app.js", &long_but_normal));
+    }
+
+    #[test]
+    fn test_mentioning_generated_is_not_generated() {
+        let content = "\"\"\"Reports generated nightly are uploaded here.\"\"\"\n\n\
+                       def upload(report):\n    # the generated PDF is kept for 30 days\n    return store(report)\n";
+        assert!(!is_generated("reports/upload.py", content));
+    }
+}
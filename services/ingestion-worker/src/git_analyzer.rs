@@ -3,14 +3,21 @@
 //! Extracts commit history and contribution metrics for files in a repository.
 
 use anyhow::{Context, Result};
-use git2::{Repository, Oid};
+use git2::{build::CheckoutBuilder, Delta, DiffFindOptions, ErrorCode, Oid, Repository, Sort};
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use chrono::{DateTime, Utc, TimeZone};
 use tracing::{info, warn, debug};
 use serde::Serialize;
 
-const DEFAULT_MAX_COMMITS: usize = 1000;
+pub const DEFAULT_MAX_COMMITS: usize = 1000;
+
+/// Commits touching more files than this (bulk renames, formatting sweeps)
+/// are ignored for co-change coupling
+const MAX_COCHANGE_COMMIT_FILES: usize = 50;
+
+/// Minimum number of shared commits for a co-change pair to be reported
+pub const DEFAULT_COCHANGE_MIN_SUPPORT: usize = 2;
 
 /// File contribution metrics extracted from git history
 #[derive(Debug, Clone)]
@@ -35,6 +42,9 @@ pub struct CommitRecord {
     pub authored_at: DateTime<Utc>,
     pub changed_files: Vec<String>,
     pub files_changed_count: usize,
+    /// Merge commits change no files of their own: their diff against the
+    /// first parent repeats the merged branch, whose commits are walked too
+    pub is_merge: bool,
 }
 
 /// Individual contributor information
@@ -48,17 +58,111 @@ pub struct ContributorInfo {
 }
 
 /// Repository-wide contribution summary
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct RepoContributions {
     pub files: HashMap<String, FileContribution>,
     pub total_commits: usize,
     pub total_contributors: usize,
+    /// Every author in the analyzed history, most commits first. Line
+    /// counts only cover code files.
+    pub contributors: Vec<ContributorInfo>,
     pub commits: Vec<CommitRecord>,
+    /// History was truncated by a shallow clone
+    pub shallow: bool,
+    /// Files that change together, strongest first
+    pub cochange: Vec<CoChangePair>,
+}
+
+/// Two files changed together in `count` commits. `confidence` is `count`
+/// divided by the change count of the less frequently changed file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CoChangePair {
+    pub file_a: String,
+    pub file_b: String,
+    pub count: usize,
+    pub confidence: f64,
+}
+
+impl FileContribution {
+    /// Fold in the history of the file under a previous path
+    fn absorb(&mut self, older: &FileContribution) {
+        self.commit_count += older.commit_count;
+        self.last_modified = self.last_modified.max(older.last_modified);
+
+        for contributor in &older.contributors {
+            match self.contributors.iter_mut().find(|c| c.email == contributor.email) {
+                Some(existing) => {
+                    existing.commit_count += contributor.commit_count;
+                    existing.lines_added += contributor.lines_added;
+                    existing.lines_deleted += contributor.lines_deleted;
+                }
+                None => self.contributors.push(contributor.clone()),
+            }
+        }
+        self.contributors.sort_by_key(|c| std::cmp::Reverse(c.commit_count));
+        if let Some(primary) = self.contributors.first() {
+            self.primary_author = primary.email.clone();
+        }
+
+        self.lines_added_total += older.lines_added_total;
+        self.lines_deleted_total += older.lines_deleted_total;
+        self.lines_changed_total = self.lines_added_total + self.lines_deleted_total;
+    }
+}
+
+impl RepoContributions {
+    /// Copy with each renamed file's history under its old path merged into
+    /// its new path, so metrics survive `git mv`
+    pub fn with_renames(&self, renames: &[(String, String)]) -> RepoContributions {
+        let mut merged = self.clone();
+        for (from, to) in renames {
+            let Some(older) = merged.files.remove(from) else {
+                continue;
+            };
+            match merged.files.get_mut(to) {
+                Some(newer) => newer.absorb(&older),
+                None => {
+                    merged.files.insert(to.clone(), FileContribution { file_path: to.clone(), ..older });
+                }
+            }
+        }
+        merged
+    }
+}
+
+/// Files that differ between two commits
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CommitRangeDiff {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub deleted: Vec<String>,
+    /// (old path, new path)
+    pub renamed: Vec<(String, String)>,
+}
+
+impl CommitRangeDiff {
+    /// Paths to parse at the head commit: added, modified and rename targets
+    pub fn changed_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = self.added.iter().chain(self.modified.iter()).cloned().collect();
+        files.extend(self.renamed.iter().map(|(_, new_path)| new_path.clone()));
+        files
+    }
+
+    /// Paths whose nodes no longer exist at the head commit: deletions and rename sources
+    pub fn removed_files(&self) -> Vec<String> {
+        let mut files = self.deleted.clone();
+        files.extend(self.renamed.iter().map(|(old_path, _)| old_path.clone()));
+        files
+    }
 }
 
 /// Analyzes git history for a repository
 pub struct GitAnalyzer {
     repo: Repository,
+    /// Commits authored before this are not walked
+    since: Option<DateTime<Utc>>,
+    /// Commits walked at most, newest first; None walks all of them
+    max_walk: Option<usize>,
 }
 
 impl GitAnalyzer {
@@ -68,7 +172,21 @@ impl GitAnalyzer {
             .context(format!("Failed to open git repository at {:?}", repo_path))?;
         
         info!("📂 Opened git repository at {:?}", repo_path);
-        Ok(Self { repo })
+        Ok(Self { repo, since: None, max_walk: None })
+    }
+
+    /// Stop the history walk at commits authored before `since`
+    pub fn with_since(mut self, since: Option<DateTime<Utc>>) -> Self {
+        self.since = since;
+        self
+    }
+
+    /// Walk at most `max_walk` commits, newest first. Unlike the record
+    /// limit of `analyze_contributions_with_limit`, this also bounds the
+    /// file and contributor metrics.
+    pub fn with_max_walk(mut self, max_walk: Option<usize>) -> Self {
+        self.max_walk = max_walk;
+        self
     }
 
     /// Extract contribution metrics for all files in the repository
@@ -80,8 +198,13 @@ impl GitAnalyzer {
     pub fn analyze_contributions_with_limit(&self, max_commits: usize) -> Result<RepoContributions> {
         info!("🔍 Analyzing git commit history...");
 
+        if !self.has_commits()? {
+            info!("Repository has no commits, no history to analyze");
+            return Ok(RepoContributions { shallow: self.repo.is_shallow(), ..Default::default() });
+        }
+
         let mut file_stats: HashMap<String, FileStats> = HashMap::new();
-        let mut all_contributors: HashSet<String> = HashSet::new();
+        let mut all_contributors: HashMap<String, ContributorInfo> = HashMap::new();
         let mut total_commits = 0;
         let mut commits: Vec<CommitRecord> = Vec::new();
 
@@ -91,9 +214,38 @@ impl GitAnalyzer {
 
         revwalk.push_head()
             .context("Failed to push HEAD")?;
+        // Newest first across branches, so the `since` cutoff can end the walk
+        revwalk.set_sorting(Sort::TIME)
+            .context("Failed to sort revwalk")?;
+
+        // The oldest commits of a shallow clone have no parents locally, so
+        // their diff would count the entire tree; skip them.
+        let shallow_boundary = self.shallow_boundary();
 
         for oid in revwalk {
-            let oid = oid.context("Failed to get commit OID")?;
+            let oid = match oid {
+                Ok(oid) => oid,
+                Err(e) if self.repo.is_shallow() => {
+                    warn!("⚠️  Stopping at shallow history boundary: {}", e);
+                    break;
+                }
+                Err(e) => return Err(e).context("Failed to get commit OID"),
+            };
+            if shallow_boundary.contains(&oid) {
+                debug!("Skipping shallow boundary commit {}", oid);
+                continue;
+            }
+            if self.max_walk.is_some_and(|max_walk| total_commits >= max_walk) {
+                info!("Stopping history walk after {} commits", total_commits);
+                break;
+            }
+            if let Some(since) = self.since {
+                let authored = self.repo.find_commit(oid).map(|c| c.time().seconds());
+                if authored.is_ok_and(|seconds| seconds < since.timestamp()) {
+                    info!("Stopping history walk at commits older than {}", since.to_rfc3339());
+                    break;
+                }
+            }
 
             match self.process_commit(oid, &mut file_stats, &mut all_contributors) {
                 Ok(record) => {
@@ -118,26 +270,63 @@ impl GitAnalyzer {
         info!("✅ Analyzed {} commits from {} contributors",
               total_commits, all_contributors.len());
 
+        // Lines are counted per file; add them up per author
+        for stats in file_stats.values() {
+            for (email, author_stats) in &stats.contributors {
+                if let Some(contributor) = all_contributors.get_mut(email) {
+                    contributor.lines_added += author_stats.lines_added;
+                    contributor.lines_deleted += author_stats.lines_deleted;
+                }
+            }
+        }
+        let mut contributors: Vec<ContributorInfo> = all_contributors.into_values().collect();
+        contributors.sort_by(|a, b| b.commit_count.cmp(&a.commit_count).then_with(|| a.email.cmp(&b.email)));
+
         // Convert internal stats to FileContribution
         let files = file_stats
             .into_iter()
             .map(|(path, stats)| (path.clone(), stats.to_contribution(path)))
             .collect();
 
+        let cochange = compute_cochange_coupling(&commits, DEFAULT_COCHANGE_MIN_SUPPORT);
+
         Ok(RepoContributions {
             files,
             total_commits,
-            total_contributors: all_contributors.len(),
+            total_contributors: contributors.len(),
+            contributors,
             commits,
+            shallow: self.repo.is_shallow(),
+            cochange,
         })
     }
 
+    /// Whether HEAD points to a commit; false for a fresh repository, or a
+    /// mirror whose HEAD names a branch without commits
+    fn has_commits(&self) -> Result<bool> {
+        match self.repo.head() {
+            Ok(_) => Ok(true),
+            Err(e) if matches!(e.code(), ErrorCode::UnbornBranch | ErrorCode::NotFound) => Ok(false),
+            Err(e) => Err(e).context("Failed to get HEAD"),
+        }
+    }
+
+    /// Commits at the edge of a shallow clone, listed in `.git/shallow`
+    fn shallow_boundary(&self) -> HashSet<Oid> {
+        if !self.repo.is_shallow() {
+            return HashSet::new();
+        }
+        std::fs::read_to_string(self.repo.path().join("shallow"))
+            .map(|content| content.lines().filter_map(|line| Oid::from_str(line.trim()).ok()).collect())
+            .unwrap_or_default()
+    }
+
     /// Process a single commit and update file statistics
     fn process_commit(
         &self,
         oid: Oid,
         file_stats: &mut HashMap<String, FileStats>,
-        all_contributors: &mut HashSet<String>,
+        all_contributors: &mut HashMap<String, ContributorInfo>,
     ) -> Result<CommitRecord> {
         let commit = self.repo.find_commit(oid)
             .context("Failed to find commit")?;
@@ -150,7 +339,30 @@ impl GitAnalyzer {
             .unwrap_or_else(Utc::now);
         let message = commit.message().unwrap_or("").trim().to_string();
 
-        all_contributors.insert(author_email.clone());
+        all_contributors
+            .entry(author_email.clone())
+            .or_insert_with(|| ContributorInfo {
+                email: author_email.clone(),
+                name: author_name.clone(),
+                commit_count: 0,
+                lines_added: 0,
+                lines_deleted: 0,
+            })
+            .commit_count += 1;
+
+        let is_merge = commit.parent_count() > 1;
+        if is_merge {
+            return Ok(CommitRecord {
+                sha: oid.to_string(),
+                author_name,
+                author_email,
+                message,
+                authored_at: commit_time,
+                changed_files: Vec::new(),
+                files_changed_count: 0,
+                is_merge,
+            });
+        }
 
         // Get parent commit for diff
         let parent = if commit.parent_count() > 0 {
@@ -238,11 +450,110 @@ impl GitAnalyzer {
             authored_at: commit_time,
             files_changed_count: files_changed.len(),
             changed_files: files_changed,
+            is_merge,
         })
     }
 
+    /// Compute the files changed between two commits, with rename detection
+    pub fn diff_commits(&self, base_sha: &str, head_sha: &str) -> Result<CommitRangeDiff> {
+        let base_tree = self.repo.revparse_single(base_sha)
+            .context(format!("Failed to find base commit {}", base_sha))?
+            .peel_to_tree()
+            .context("Failed to get base tree")?;
+        let head_tree = self.repo.revparse_single(head_sha)
+            .context(format!("Failed to find head commit {}", head_sha))?
+            .peel_to_tree()
+            .context("Failed to get head tree")?;
+
+        let mut diff = self.repo.diff_tree_to_tree(Some(&base_tree), Some(&head_tree), None)
+            .context("Failed to create diff")?;
+        let mut find_options = DiffFindOptions::new();
+        find_options.renames(true);
+        diff.find_similar(Some(&mut find_options))
+            .context("Failed to detect renames")?;
+
+        let mut range = CommitRangeDiff::default();
+        for delta in diff.deltas() {
+            let old_path = delta.old_file().path().map(|p| p.to_string_lossy().replace('\\', "/"));
+            let new_path = delta.new_file().path().map(|p| p.to_string_lossy().replace('\\', "/"));
+
+            match (delta.status(), old_path, new_path) {
+                (Delta::Added | Delta::Copied, _, Some(new_path)) => range.added.push(new_path),
+                (Delta::Modified | Delta::Typechange, _, Some(new_path)) => range.modified.push(new_path),
+                (Delta::Deleted, Some(old_path), _) => range.deleted.push(old_path),
+                (Delta::Renamed, Some(old_path), Some(new_path)) => range.renamed.push((old_path, new_path)),
+                _ => {}
+            }
+        }
+
+        debug!("Diff {}..{}: {:?}", base_sha, head_sha, range);
+        Ok(range)
+    }
+
+    /// Renames between `base_sha` and HEAD whose old path is in `removed` and
+    /// new path is in `changed`
+    pub fn detect_renames(&self, base_sha: &str, removed: &[String], changed: &[String]) -> Result<Vec<(String, String)>> {
+        let diff = self.diff_commits(base_sha, "HEAD")?;
+        Ok(diff
+            .renamed
+            .into_iter()
+            .filter(|(from, to)| removed.contains(from) && changed.contains(to))
+            .collect())
+    }
+
+    /// Check out a commit as a detached HEAD. With `discard_changes` working
+    /// tree changes are overwritten, otherwise a checkout that would overwrite
+    /// them fails. History walks then start from it.
+    pub fn checkout_commit(&self, sha: &str, discard_changes: bool) -> Result<()> {
+        let object = match self.repo.revparse_single(sha) {
+            Ok(object) => object,
+            Err(e) if self.repo.is_shallow() => anyhow::bail!(
+                "Commit {} is not in the shallow clone ({}); raise the clone_depth job option, or set it to 0 to fetch the full history",
+                sha,
+                e.message()
+            ),
+            Err(e) => return Err(e).context(format!("Failed to find commit {}", sha)),
+        };
+        let commit = object.peel_to_commit().context("Failed to resolve commit")?;
+
+        let mut checkout = CheckoutBuilder::new();
+        if discard_changes {
+            checkout.force();
+        } else {
+            checkout.safe();
+        }
+        self.repo.checkout_tree(commit.as_object(), Some(&mut checkout))
+            .context("Failed to checkout commit tree")?;
+        self.repo.set_head_detached(commit.id())
+            .context("Failed to set HEAD detached")?;
+
+        info!("🔀 Checked out commit {}", commit.id());
+        Ok(())
+    }
+
+    /// Read a file's content at a commit; None if it doesn't exist there or isn't UTF-8
+    pub fn file_content_at(&self, sha: &str, file_path: &str) -> Result<Option<String>> {
+        let tree = self.repo.revparse_single(sha)
+            .context(format!("Failed to find commit {}", sha))?
+            .peel_to_tree()
+            .context("Failed to get commit tree")?;
+
+        let entry = match tree.get_path(Path::new(file_path)) {
+            Ok(entry) => entry,
+            Err(_) => return Ok(None),
+        };
+        let object = entry.to_object(&self.repo).context("Failed to load tree entry")?;
+        Ok(object
+            .as_blob()
+            .and_then(|blob| std::str::from_utf8(blob.content()).ok())
+            .map(|content| content.to_string()))
+    }
+
     /// Get the latest commit for a specific file
     pub fn get_file_last_commit(&self, file_path: &str) -> Result<Option<DateTime<Utc>>> {
+        if !self.has_commits()? {
+            return Ok(None);
+        }
         let mut revwalk = self.repo.revwalk()
             .context("Failed to create revwalk")?;
         
@@ -371,13 +682,59 @@ impl FileStats {
 fn is_code_file(path: &str) -> bool {
     let code_extensions = [
         ".rs", ".go", ".py", ".js", ".ts", ".tsx", ".jsx",
-        ".java", ".c", ".cpp", ".h", ".hpp", ".cs",
-        ".rb", ".php", ".swift", ".kt", ".scala",
+        ".java", ".c", ".cc", ".cpp", ".cxx", ".h", ".hh", ".hpp", ".cs",
+        ".rb", ".rake", ".php", ".swift", ".kt", ".kts", ".scala",
     ];
 
     code_extensions.iter().any(|ext| path.ends_with(ext))
 }
 
+/// Count how often each pair of files changed in the same commit. Pairs seen
+/// in fewer than `min_support` commits are dropped. Sorted by count, then
+/// confidence, then path.
+pub fn compute_cochange_coupling(commits: &[CommitRecord], min_support: usize) -> Vec<CoChangePair> {
+    let mut file_changes: HashMap<&str, usize> = HashMap::new();
+    let mut pair_counts: HashMap<(&str, &str), usize> = HashMap::new();
+
+    for commit in commits {
+        let mut files: Vec<&str> = commit.changed_files.iter().map(|f| f.as_str()).collect();
+        files.sort_unstable();
+        files.dedup();
+        if files.len() > MAX_COCHANGE_COMMIT_FILES {
+            continue;
+        }
+
+        for (i, file_a) in files.iter().enumerate() {
+            *file_changes.entry(file_a).or_insert(0) += 1;
+            for file_b in &files[i + 1..] {
+                *pair_counts.entry((file_a, file_b)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut pairs: Vec<CoChangePair> = pair_counts
+        .into_iter()
+        .filter(|(_, count)| *count >= min_support.max(1))
+        .map(|((file_a, file_b), count)| {
+            let changes = file_changes[file_a].min(file_changes[file_b]);
+            CoChangePair {
+                file_a: file_a.to_string(),
+                file_b: file_b.to_string(),
+                count,
+                confidence: count as f64 / changes as f64,
+            }
+        })
+        .collect();
+
+    pairs.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(b.confidence.total_cmp(&a.confidence))
+            .then_with(|| (&a.file_a, &a.file_b).cmp(&(&b.file_a, &b.file_b)))
+    });
+    pairs
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -391,4 +748,313 @@ mod tests {
         assert!(!is_code_file("package.json"));
         assert!(!is_code_file(".gitignore"));
     }
+
+    fn commit_all(repo: &Repository, message: &str) -> Oid {
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None).unwrap();
+        index.update_all(["*"].iter(), None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+        let parents: Vec<git2::Commit> = repo.head().ok().and_then(|h| h.peel_to_commit().ok()).into_iter().collect();
+        let parent_refs: Vec<&git2::Commit> = parents.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parent_refs).unwrap()
+    }
+
+    #[test]
+    fn test_diff_commits_classifies_changes() {
+        let dir = std::env::temp_dir().join(format!("git-diff-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let repo = Repository::init(&dir).unwrap();
+
+        let helpers = "def normalize(value):\n    return value.strip().lower()\n\n\ndef slugify(value):\n    return normalize(value).replace(' ', '-')\n";
+        std::fs::write(dir.join("src/app.py"), "import os\n").unwrap();
+        std::fs::write(dir.join("src/legacy.py"), "def old():\n    pass\n").unwrap();
+        std::fs::write(dir.join("src/utils.py"), helpers).unwrap();
+        let base = commit_all(&repo, "base");
+
+        std::fs::write(dir.join("src/app.py"), "import os\nimport json\n").unwrap();
+        std::fs::write(dir.join("src/new.py"), "class Report:\n    title = \"weekly\"\n").unwrap();
+        std::fs::remove_file(dir.join("src/legacy.py")).unwrap();
+        std::fs::rename(dir.join("src/utils.py"), dir.join("src/text_utils.py")).unwrap();
+        let head = commit_all(&repo, "head");
+
+        let analyzer = GitAnalyzer::new(&dir).unwrap();
+        let diff = analyzer.diff_commits(&base.to_string(), &head.to_string()).unwrap();
+
+        assert_eq!(diff.added, vec!["src/new.py"]);
+        assert_eq!(diff.modified, vec!["src/app.py"]);
+        assert_eq!(diff.deleted, vec!["src/legacy.py"]);
+        assert_eq!(diff.renamed, vec![("src/utils.py".to_string(), "src/text_utils.py".to_string())]);
+        assert_eq!(diff.changed_files(), vec!["src/new.py", "src/app.py", "src/text_utils.py"]);
+        assert_eq!(diff.removed_files(), vec!["src/legacy.py", "src/utils.py"]);
+
+        assert_eq!(
+            analyzer.file_content_at(&base.to_string(), "src/app.py").unwrap().as_deref(),
+            Some("import os\n")
+        );
+        assert_eq!(analyzer.file_content_at(&base.to_string(), "src/new.py").unwrap(), None);
+
+        analyzer.checkout_commit(&base.to_string(), true).unwrap();
+        assert!(dir.join("src/legacy.py").exists());
+        assert!(!dir.join("src/new.py").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_contributions_stop_at_shallow_boundary() {
+        let dir = std::env::temp_dir().join(format!("git-shallow-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let repo = Repository::init(&dir).unwrap();
+
+        let mut oids = Vec::new();
+        for i in 0..4 {
+            std::fs::write(dir.join(format!("mod_{}.py", i)), format!("def f{}():\n    pass\n", i)).unwrap();
+            oids.push(commit_all(&repo, &format!("commit {}", i)));
+        }
+
+        // Mark commit 2 as the edge of a depth-2 shallow clone
+        std::fs::write(repo.path().join("shallow"), format!("{}\n", oids[2])).unwrap();
+
+        let contributions = GitAnalyzer::new(&dir).unwrap().analyze_contributions().unwrap();
+        assert!(contributions.shallow);
+        assert_eq!(contributions.total_commits, 1);
+        assert_eq!(contributions.commits[0].sha, oids[3].to_string());
+        assert!(contributions.files.contains_key("mod_3.py"));
+        assert!(!contributions.files.contains_key("mod_2.py"));
+        assert!(!contributions.files.contains_key("mod_0.py"));
+
+        assert_eq!(contributions.total_contributors, 1);
+        let author = &contributions.contributors[0];
+        assert_eq!((author.email.as_str(), author.name.as_str()), ("test@example.com", "Test"));
+        assert_eq!((author.commit_count, author.lines_added, author.lines_deleted), (1, 2, 0));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Commit the index on `parents` with a fixed commit time, without
+    /// moving HEAD
+    fn commit_at(repo: &Repository, parents: &[Oid], seconds: i64) -> Oid {
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::new("Test", "test@example.com", &git2::Time::new(seconds, 0)).unwrap();
+        let parents: Vec<git2::Commit> = parents.iter().map(|oid| repo.find_commit(*oid).unwrap()).collect();
+        let parent_refs: Vec<&git2::Commit> = parents.iter().collect();
+        repo.commit(None, &signature, &signature, "commit", &tree, &parent_refs).unwrap()
+    }
+
+    #[test]
+    fn test_repository_without_commits_has_empty_history() {
+        let dir = std::env::temp_dir().join(format!("git-empty-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        // A mirror whose HEAD names a branch nobody committed to
+        repo.set_head("refs/heads/develop").unwrap();
+        std::fs::write(dir.join("app.py"), "import os\n").unwrap();
+
+        let analyzer = GitAnalyzer::new(&dir).unwrap();
+        let contributions = analyzer.analyze_contributions().unwrap();
+        assert_eq!((contributions.total_commits, contributions.total_contributors), (0, 0));
+        assert!(contributions.files.is_empty() && contributions.commits.is_empty());
+        assert_eq!(analyzer.get_file_last_commit("app.py").unwrap(), None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_merge_commits_are_not_double_counted() {
+        let dir = std::env::temp_dir().join(format!("git-merge-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        let day = 86_400;
+
+        std::fs::write(dir.join("app.py"), "import os\n").unwrap();
+        let base = commit_at(&repo, &[], 10 * day);
+        // A feature branch adds three lines, main adds a file meanwhile
+        std::fs::write(dir.join("feature.py"), "a = 1\nb = 2\nc = 3\n").unwrap();
+        let feature = commit_at(&repo, &[base], 11 * day);
+        std::fs::remove_file(dir.join("feature.py")).unwrap();
+        std::fs::write(dir.join("main.py"), "x = 1\n").unwrap();
+        let main = commit_at(&repo, &[base], 12 * day);
+        std::fs::write(dir.join("feature.py"), "a = 1\nb = 2\nc = 3\n").unwrap();
+        let merge = commit_at(&repo, &[main, feature], 13 * day);
+        repo.reference("refs/heads/main", merge, true, "merge").unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+
+        let contributions = GitAnalyzer::new(&dir).unwrap().analyze_contributions().unwrap();
+        assert_eq!(contributions.total_commits, 4);
+        let feature_file = &contributions.files["feature.py"];
+        assert_eq!((feature_file.commit_count, feature_file.lines_added_total), (1, 3));
+        assert_eq!(contributions.contributors[0].lines_added, 5);
+        let merge_record = &contributions.commits[0];
+        assert_eq!(merge_record.sha, merge.to_string());
+        assert!(merge_record.is_merge && merge_record.changed_files.is_empty());
+        assert!(!contributions.commits[1].is_merge);
+
+        // The cutoff stops the walk before the base commit
+        let since = Utc.timestamp_opt(11 * day, 0).single();
+        let recent = GitAnalyzer::new(&dir).unwrap().with_since(since).analyze_contributions().unwrap();
+        let shas: Vec<&str> = recent.commits.iter().map(|c| c.sha.as_str()).collect();
+        assert_eq!(shas, vec![merge.to_string(), main.to_string(), feature.to_string()]);
+        assert!(!recent.files.contains_key("app.py"));
+
+        // The walk limit bounds the metrics, not just the records
+        let capped = GitAnalyzer::new(&dir).unwrap().with_max_walk(Some(2)).analyze_contributions().unwrap();
+        assert_eq!(capped.total_commits, 2);
+        assert_eq!(capped.commits.len(), 2);
+        assert!(!capped.files.contains_key("feature.py"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn record(changed_files: &[&str]) -> CommitRecord {
+        CommitRecord {
+            sha: "0".repeat(40),
+            author_name: "Test".to_string(),
+            author_email: "test@example.com".to_string(),
+            message: String::new(),
+            authored_at: Utc::now(),
+            changed_files: changed_files.iter().map(|f| f.to_string()).collect(),
+            files_changed_count: changed_files.len(),
+            is_merge: false,
+        }
+    }
+
+    #[test]
+    fn test_cochange_coupling_counts_pairs() {
+        let commits = vec![
+            record(&["api.py", "models.py"]),
+            record(&["models.py", "api.py", "views.py"]),
+            record(&["api.py"]),
+            record(&["models.py", "views.py"]),
+            record(&["api.py", "models.py"]),
+        ];
+
+        let pairs = compute_cochange_coupling(&commits, 2);
+        assert_eq!(
+            pairs,
+            vec![
+                CoChangePair {
+                    file_a: "api.py".to_string(),
+                    file_b: "models.py".to_string(),
+                    count: 3,
+                    confidence: 0.75, // api.py and models.py each changed 4 times
+                },
+                CoChangePair {
+                    file_a: "models.py".to_string(),
+                    file_b: "views.py".to_string(),
+                    count: 2,
+                    confidence: 1.0, // every views.py change
+                },
+            ]
+        );
+
+        // api.py/views.py only share one commit
+        assert_eq!(compute_cochange_coupling(&commits, 1).len(), 3);
+    }
+
+    #[test]
+    fn test_cochange_coupling_ignores_large_commits() {
+        let sweep: Vec<String> = (0..=MAX_COCHANGE_COMMIT_FILES).map(|i| format!("f{}.py", i)).collect();
+        let sweep: Vec<&str> = sweep.iter().map(|f| f.as_str()).collect();
+        let commits = vec![
+            record(&sweep),
+            record(&sweep),
+            record(&["f0.py", "f1.py"]),
+        ];
+
+        let pairs = compute_cochange_coupling(&commits, 1);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].count, 1);
+        // The sweeps don't count as changes either
+        assert_eq!(pairs[0].confidence, 1.0);
+    }
+
+    fn contribution(path: &str, contributors: &[(&str, usize)], day: u32) -> FileContribution {
+        let contributors: Vec<ContributorInfo> = contributors
+            .iter()
+            .map(|(email, commits)| ContributorInfo {
+                email: email.to_string(),
+                name: email.to_string(),
+                commit_count: *commits,
+                lines_added: commits * 10,
+                lines_deleted: *commits,
+            })
+            .collect();
+        FileContribution {
+            file_path: path.to_string(),
+            commit_count: contributors.iter().map(|c| c.commit_count).sum(),
+            last_modified: Utc.with_ymd_and_hms(2024, 3, day, 0, 0, 0).unwrap(),
+            primary_author: contributors[0].email.clone(),
+            lines_added_total: contributors.iter().map(|c| c.lines_added).sum(),
+            lines_deleted_total: contributors.iter().map(|c| c.lines_deleted).sum(),
+            lines_changed_total: contributors.iter().map(|c| c.lines_added + c.lines_deleted).sum(),
+            contributors,
+        }
+    }
+
+    #[test]
+    fn test_with_renames_merges_history() {
+        let mut files = HashMap::new();
+        files.insert("src/a.ts".to_string(), contribution("src/a.ts", &[("ann@x.io", 5), ("bob@x.io", 1)], 1));
+        files.insert("src/b.ts".to_string(), contribution("src/b.ts", &[("bob@x.io", 5)], 9));
+        files.insert("src/old.ts".to_string(), contribution("src/old.ts", &[("cy@x.io", 2)], 2));
+        let contributions = RepoContributions {
+            files,
+            total_commits: 13,
+            total_contributors: 3,
+            contributors: vec![],
+            commits: vec![],
+            shallow: false,
+            cochange: vec![],
+        };
+
+        let merged = contributions.with_renames(&[
+            ("src/a.ts".to_string(), "src/b.ts".to_string()),
+            ("src/old.ts".to_string(), "src/new.ts".to_string()),
+        ]);
+
+        assert!(!merged.files.contains_key("src/a.ts"));
+        let b = &merged.files["src/b.ts"];
+        assert_eq!(b.commit_count, 11);
+        assert_eq!(b.last_modified, Utc.with_ymd_and_hms(2024, 3, 9, 0, 0, 0).unwrap());
+        assert_eq!(b.primary_author, "bob@x.io"); // 6 commits beats ann's 5
+        assert_eq!(b.contributors.len(), 2);
+        assert_eq!(b.lines_changed_total, 121);
+
+        // A rename with no history at the new path just moves the entry
+        let moved = &merged.files["src/new.ts"];
+        assert_eq!(moved.file_path, "src/new.ts");
+        assert_eq!(moved.commit_count, 2);
+    }
+
+    #[test]
+    fn test_detect_renames_against_base() {
+        let dir = std::env::temp_dir().join(format!("git-renames-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let repo = Repository::init(&dir).unwrap();
+
+        let body = "export function format(value: string) {\n  return value.trim().toLowerCase();\n}\n";
+        std::fs::write(dir.join("src/a.ts"), body).unwrap();
+        std::fs::write(dir.join("src/gone.ts"), "export const x = 1;\n").unwrap();
+        let base = commit_all(&repo, "base");
+
+        std::fs::rename(dir.join("src/a.ts"), dir.join("src/b.ts")).unwrap();
+        std::fs::remove_file(dir.join("src/gone.ts")).unwrap();
+        commit_all(&repo, "move");
+
+        let analyzer = GitAnalyzer::new(&dir).unwrap();
+        let renames = analyzer
+            .detect_renames(
+                &base.to_string(),
+                &["src/a.ts".to_string(), "src/gone.ts".to_string()],
+                &["src/b.ts".to_string()],
+            )
+            .unwrap();
+        assert_eq!(renames, vec![("src/a.ts".to_string(), "src/b.ts".to_string())]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -3,8 +3,11 @@
 //! Builds an in-memory graph from parsed code with symbol resolution
 //! and cross-file dependency tracking.
 
-use crate::parsers::{FunctionInfo, ParsedFile};
-use std::collections::{HashMap, HashSet};
+use crate::import_resolver::ImportResolver;
+use crate::parsers::exports::Barrels;
+use crate::parsers::{FunctionInfo, InterfaceInfo, MethodSignature, ParsedFile};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
 
 // ============================================================================
 // Node and Edge Types
@@ -17,8 +20,10 @@ pub enum NodeId {
     File(String),
     /// A class/struct (file_path, class_name)
     Class(String, String),
-    /// A function/method (file_path, func_name)
+    /// A top-level function (file_path, func_name)
     Function(String, String),
+    /// A method (file_path, class_name, method_name)
+    Method(String, String, String),
     /// An external module (import path)
     Module(String),
 }
@@ -29,6 +34,7 @@ impl NodeId {
             NodeId::File(p) => Some(p),
             NodeId::Class(p, _) => Some(p),
             NodeId::Function(p, _) => Some(p),
+            NodeId::Method(p, _, _) => Some(p),
             NodeId::Module(_) => None,
         }
     }
@@ -38,15 +44,27 @@ impl NodeId {
             NodeId::File(p) => p,
             NodeId::Class(_, n) => n,
             NodeId::Function(_, n) => n,
+            NodeId::Method(_, _, n) => n,
             NodeId::Module(m) => m,
         }
     }
 
+    /// Stored id: the path for files, `file::name` for classes and
+    /// functions, `file::Class::method` for methods
+    pub fn qualified_id(&self) -> String {
+        match self {
+            NodeId::File(p) => p.clone(),
+            NodeId::Class(p, n) | NodeId::Function(p, n) => format!("{}::{}", p, n),
+            NodeId::Method(p, c, n) => format!("{}::{}::{}", p, c, n),
+            NodeId::Module(m) => m.clone(),
+        }
+    }
+
     pub fn node_type(&self) -> &'static str {
         match self {
             NodeId::File(_) => "File",
             NodeId::Class(_, _) => "Class",
-            NodeId::Function(_, _) => "Function",
+            NodeId::Function(_, _) | NodeId::Method(_, _, _) => "Function",
             NodeId::Module(_) => "Module",
         }
     }
@@ -65,6 +83,10 @@ pub enum EdgeType {
     Inherits,
     /// Class contains a method
     Contains,
+    /// File depends on another file through a resolved import
+    DependsOn,
+    /// File renders a JSX component defined in another file
+    UsesComponent,
 }
 
 impl EdgeType {
@@ -75,6 +97,8 @@ impl EdgeType {
             EdgeType::Imports => "IMPORTS",
             EdgeType::Inherits => "INHERITS",
             EdgeType::Contains => "CONTAINS",
+            EdgeType::DependsOn => "DEPENDS_ON",
+            EdgeType::UsesComponent => "USES_COMPONENT",
         }
     }
 }
@@ -88,6 +112,27 @@ pub struct Edge {
     pub properties: HashMap<String, String>,
 }
 
+impl Edge {
+    pub fn id(&self) -> String {
+        edge_id(self.edge_type.as_str(), &self.from.qualified_id(), &self.to.qualified_id())
+    }
+}
+
+/// Id of an edge: `type:source->target`, with the type lowercased and the
+/// stored ids of its ends. Graph patches and the `id` property of stored
+/// relationships both use it, so an edge picked in the UI can be looked up
+/// in Neo4j.
+pub fn edge_id(edge_type: &str, source: &str, target: &str) -> String {
+    format!("{}:{}->{}", edge_type.to_lowercase(), source, target)
+}
+
+/// `edge_id` as a Cypher expression, for relationships merged by queries
+/// that find both ends in the stored graph. `source` and `target` are
+/// Cypher string expressions.
+pub fn edge_id_cypher(edge_type: &str, source: &str, target: &str) -> String {
+    format!("'{}:' + {} + '->' + {}", edge_type.to_lowercase(), source, target)
+}
+
 // ============================================================================
 // Symbol Table
 // ============================================================================
@@ -97,10 +142,22 @@ pub struct Edge {
 pub struct SymbolEntry {
     pub file_path: String,
     pub name: String,
+    /// Class a method belongs to; None for functions and classes
+    pub class_name: Option<String>,
     pub start_line: usize,
     pub end_line: usize,
 }
 
+impl SymbolEntry {
+    /// Graph node of a function or method entry
+    pub fn function_node(&self) -> NodeId {
+        match &self.class_name {
+            Some(class) => NodeId::Method(self.file_path.clone(), class.clone(), self.name.clone()),
+            None => NodeId::Function(self.file_path.clone(), self.name.clone()),
+        }
+    }
+}
+
 /// Index of all symbols in the codebase for lookup
 #[derive(Debug, Default)]
 pub struct SymbolTable {
@@ -126,7 +183,13 @@ impl SymbolTable {
     /// Build a symbol table from parsed files
     pub fn from_parsed_files(parsed_files: &[ParsedFile]) -> Self {
         let mut table = SymbolTable::default();
+        table.add_files(parsed_files);
+        table
+    }
 
+    /// Index the symbols of more files, for tables built a chunk of files
+    /// at a time
+    pub fn add_files(&mut self, parsed_files: &[ParsedFile]) {
         for file in parsed_files {
             let mut file_symbols = FileSymbols::default();
 
@@ -135,10 +198,20 @@ impl SymbolTable {
                 let entry = SymbolEntry {
                     file_path: file.path.clone(),
                     name: func.name.clone(),
+                    class_name: None,
                     start_line: func.start_line,
                     end_line: func.end_line,
                 };
-                table
+                // Rust functions of nested modules are named `foo::bar::baz`;
+                // calls name them by the last segment
+                if let Some((_, simple_name)) = func.name.rsplit_once("::") {
+                    self
+                        .functions
+                        .entry(simple_name.to_string())
+                        .or_default()
+                        .push(entry.clone());
+                }
+                self
                     .functions
                     .entry(func.name.clone())
                     .or_default()
@@ -151,10 +224,11 @@ impl SymbolTable {
                 let entry = SymbolEntry {
                     file_path: file.path.clone(),
                     name: class.name.clone(),
+                    class_name: None,
                     start_line: class.start_line,
                     end_line: class.end_line,
                 };
-                table
+                self
                     .classes
                     .entry(class.name.clone())
                     .or_default()
@@ -167,16 +241,17 @@ impl SymbolTable {
                     let method_entry = SymbolEntry {
                         file_path: file.path.clone(),
                         name: method.name.clone(),
+                        class_name: Some(class.name.clone()),
                         start_line: method.start_line,
                         end_line: method.end_line,
                     };
-                    table
+                    self
                         .functions
                         .entry(qualified_name)
                         .or_default()
                         .push(method_entry.clone());
                     // Also index by simple name for cross-file resolution
-                    table
+                    self
                         .functions
                         .entry(method.name.clone())
                         .or_default()
@@ -190,25 +265,41 @@ impl SymbolTable {
             // Build exports list
             let mut exports = file_symbols.functions.clone();
             exports.extend(file_symbols.classes.clone());
-            table.file_exports.insert(file.path.clone(), exports);
-            table.files.insert(file.path.clone(), file_symbols);
+            self.file_exports.insert(file.path.clone(), exports);
+            self.files.insert(file.path.clone(), file_symbols);
         }
+    }
 
-        table
+    /// Resolve a function call made in `current_file`, which depends on the
+    /// `imported_files`. Among the definitions of `name`, one in the same
+    /// file wins, then one in an imported file, then one in the same
+    /// directory, then the first found. The flag is true when the winning
+    /// tier still held more than one candidate.
+    pub fn resolve_function_with_imports(
+        &self,
+        name: &str,
+        current_file: &str,
+        imported_files: &HashSet<&str>,
+    ) -> Option<(&SymbolEntry, bool)> {
+        let entries = self.functions.get(name)?;
+        let directory = parent_dir(current_file);
+        let tiers: [&dyn Fn(&SymbolEntry) -> bool; 4] = [
+            &|e| e.file_path == current_file,
+            &|e| imported_files.contains(e.file_path.as_str()),
+            &|e| parent_dir(&e.file_path) == directory,
+            &|_| true,
+        ];
+        tiers.iter().find_map(|in_tier| {
+            let mut candidates = entries.iter().filter(|e| in_tier(e));
+            let first = candidates.next()?;
+            Some((first, candidates.next().is_some()))
+        })
     }
 
-    /// Resolve a function call to its definition(s)
-    pub fn resolve_function(&self, name: &str, current_file: &str) -> Option<&SymbolEntry> {
-        if let Some(entries) = self.functions.get(name) {
-            // Prefer definition in the same file
-            if let Some(same_file) = entries.iter().find(|e| e.file_path == current_file) {
-                return Some(same_file);
-            }
-            // Otherwise return the first one found
-            entries.first()
-        } else {
-            None
-        }
+    /// The top-level function `name` defined in `file_path`
+    pub fn resolve_function_in(&self, name: &str, file_path: &str) -> Option<&SymbolEntry> {
+        let entries = self.functions.get(name)?;
+        entries.iter().find(|e| e.file_path == file_path && e.class_name.is_none())
     }
 
     /// Resolve a class reference to its definition
@@ -236,21 +327,49 @@ pub struct DependencyGraph {
 }
 
 impl DependencyGraph {
-    /// Build a dependency graph from parsed files
+    /// Build a dependency graph from parsed files, with no repository
+    /// settings; jobs go through `from_parsed_files_with_resolver`
+    #[cfg(test)]
     pub fn from_parsed_files(parsed_files: &[ParsedFile], symbol_table: &SymbolTable) -> Self {
+        Self::from_parsed_files_with_resolver(parsed_files, symbol_table, &ImportResolver::default())
+    }
+
+    /// Build a dependency graph, resolving imports with repository-specific
+    /// settings such as tsconfig path aliases
+    pub fn from_parsed_files_with_resolver(
+        parsed_files: &[ParsedFile],
+        symbol_table: &SymbolTable,
+        import_resolver: &ImportResolver,
+    ) -> Self {
+        let mut dependencies = FileDependencies::resolve(parsed_files, import_resolver);
+        dependencies.follow_barrels(parsed_files);
         let mut graph = DependencyGraph::default();
+        graph.add_files(parsed_files, &dependencies, symbol_table);
+        graph.link_files(parsed_files, dependencies, symbol_table);
+        graph
+    }
 
+    /// Add the nodes and edges within each of `parsed_files`: its symbols,
+    /// their calls and inheritance, and its imports. Graphs of streamed
+    /// files are built a chunk of files at a time.
+    pub fn add_files(&mut self, parsed_files: &[ParsedFile], dependencies: &FileDependencies, symbol_table: &SymbolTable) {
         for file in parsed_files {
             let file_node = NodeId::File(file.path.clone());
-            graph.nodes.insert(file_node.clone());
+            let imported_files: HashSet<&str> = dependencies
+                .imported
+                .get(&file.path)
+                .map(|targets| targets.iter().map(String::as_str).collect())
+                .unwrap_or_default();
+            let through_barrels = dependencies.through_barrels.get(&file.path);
+            self.nodes.insert(file_node.clone());
 
             // Process top-level functions
             for func in &file.functions {
                 let func_node = NodeId::Function(file.path.clone(), func.name.clone());
-                graph.nodes.insert(func_node.clone());
+                self.nodes.insert(func_node.clone());
 
                 // File DEFINES Function
-                graph.edges.push(Edge {
+                self.edges.push(Edge {
                     from: file_node.clone(),
                     to: func_node.clone(),
                     edge_type: EdgeType::Defines,
@@ -258,16 +377,16 @@ impl DependencyGraph {
                 });
 
                 // Process function calls
-                graph.add_call_edges(&func_node, func, &file.path, symbol_table);
+                self.add_call_edges(&func_node, func, &file.path, &imported_files, through_barrels, symbol_table);
             }
 
             // Process classes
             for class in &file.classes {
                 let class_node = NodeId::Class(file.path.clone(), class.name.clone());
-                graph.nodes.insert(class_node.clone());
+                self.nodes.insert(class_node.clone());
 
                 // File DEFINES Class
-                graph.edges.push(Edge {
+                self.edges.push(Edge {
                     from: file_node.clone(),
                     to: class_node.clone(),
                     edge_type: EdgeType::Defines,
@@ -279,10 +398,10 @@ impl DependencyGraph {
                     if let Some(parent_entry) = symbol_table.resolve_class(&inheritance.name, &file.path) {
                         let parent_node =
                             NodeId::Class(parent_entry.file_path.clone(), inheritance.name.clone());
-                        graph.nodes.insert(parent_node.clone());
+                        self.nodes.insert(parent_node.clone());
                         let mut properties = HashMap::new();
                         properties.insert("kind".to_string(), inheritance.kind.clone());
-                        graph.edges.push(Edge {
+                        self.edges.push(Edge {
                             from: class_node.clone(),
                             to: parent_node,
                             edge_type: EdgeType::Inherits,
@@ -291,10 +410,10 @@ impl DependencyGraph {
                     } else {
                         // External parent class - create a module node
                         let parent_node = NodeId::Module(inheritance.name.clone());
-                        graph.nodes.insert(parent_node.clone());
+                        self.nodes.insert(parent_node.clone());
                         let mut properties = HashMap::new();
                         properties.insert("kind".to_string(), inheritance.kind.clone());
-                        graph.edges.push(Edge {
+                        self.edges.push(Edge {
                             from: class_node.clone(),
                             to: parent_node,
                             edge_type: EdgeType::Inherits,
@@ -305,11 +424,12 @@ impl DependencyGraph {
 
                 // Process methods
                 for method in &class.methods {
-                    let method_node = NodeId::Function(file.path.clone(), method.name.clone());
-                    graph.nodes.insert(method_node.clone());
+                    let method_node =
+                        NodeId::Method(file.path.clone(), class.name.clone(), method.name.clone());
+                    self.nodes.insert(method_node.clone());
 
                     // Class CONTAINS Method
-                    graph.edges.push(Edge {
+                    self.edges.push(Edge {
                         from: class_node.clone(),
                         to: method_node.clone(),
                         edge_type: EdgeType::Contains,
@@ -317,16 +437,16 @@ impl DependencyGraph {
                     });
 
                     // Process method calls
-                    graph.add_call_edges(&method_node, method, &file.path, symbol_table);
+                    self.add_call_edges(&method_node, method, &file.path, &imported_files, through_barrels, symbol_table);
                 }
             }
 
             // Process imports
             for import in &file.imports {
                 let module_node = NodeId::Module(import.clone());
-                graph.nodes.insert(module_node.clone());
+                self.nodes.insert(module_node.clone());
 
-                graph.edges.push(Edge {
+                self.edges.push(Edge {
                     from: file_node.clone(),
                     to: module_node,
                     edge_type: EdgeType::Imports,
@@ -334,32 +454,140 @@ impl DependencyGraph {
                 });
             }
         }
+    }
 
-        graph
+    /// Add the edges between files once every file's own nodes are in:
+    /// implicit interface implementations, DEPENDS_ON and USES_COMPONENT.
+    /// These don't read call lists or SQL queries, so outlines will do.
+    pub fn link_files(&mut self, parsed_files: &[ParsedFile], dependencies: FileDependencies, symbol_table: &SymbolTable) {
+        self.add_implicit_interface_edges(parsed_files);
+
+        let component_edges = resolve_rendered_components(parsed_files, symbol_table, &dependencies);
+        for (source, target, import_path) in dependencies.dependencies {
+            let mut properties = HashMap::new();
+            properties.insert("import_path".to_string(), import_path);
+            self.edges.push(Edge {
+                from: NodeId::File(source),
+                to: NodeId::File(target),
+                edge_type: EdgeType::DependsOn,
+                properties,
+            });
+        }
+
+        for (source, target, components) in component_edges {
+            let mut properties = HashMap::new();
+            properties.insert("components".to_string(), components.join(","));
+            self.edges.push(Edge {
+                from: NodeId::File(source),
+                to: NodeId::File(target),
+                edge_type: EdgeType::UsesComponent,
+                properties,
+            });
+        }
     }
 
-    /// Add CALLS edges from a function to its callees
+    /// Add CALLS edges from a function to its callees. A name imported
+    /// through a barrel resolves to the function it re-exports. An edge
+    /// whose callee was picked among several candidates gets `resolution:
+    /// ambiguous`.
     fn add_call_edges(
         &mut self,
         caller_node: &NodeId,
         func: &FunctionInfo,
         current_file: &str,
+        imported_files: &HashSet<&str>,
+        through_barrels: Option<&HashMap<String, (String, String)>>,
         symbol_table: &SymbolTable,
     ) {
-        for call in &func.calls {
-            if let Some(callee_entry) = symbol_table.resolve_function(call, current_file) {
-                let callee_node =
-                    NodeId::Function(callee_entry.file_path.clone(), callee_entry.name.clone());
-                self.nodes.insert(callee_node.clone());
-                self.edges.push(Edge {
-                    from: caller_node.clone(),
-                    to: callee_node,
-                    edge_type: EdgeType::Calls,
-                    properties: HashMap::new(),
-                });
+        // One edge per callee; names that resolve to the same function add
+        // up, and one unambiguous name settles it
+        let mut callees: Vec<(NodeId, usize, bool)> = Vec::new();
+        for (call, count) in func.call_counts() {
+            let reexported = through_barrels
+                .and_then(|names| names.get(call))
+                .and_then(|(file, name)| symbol_table.resolve_function_in(name, file))
+                .map(|entry| (entry, false));
+            if let Some((callee_entry, ambiguous)) =
+                reexported.or_else(|| symbol_table.resolve_function_with_imports(call, current_file, imported_files))
+            {
+                let callee_node = callee_entry.function_node();
+                match callees.iter_mut().find(|(node, _, _)| *node == callee_node) {
+                    Some((_, total, all_ambiguous)) => {
+                        *total += count;
+                        *all_ambiguous &= ambiguous;
+                    }
+                    None => callees.push((callee_node, count, ambiguous)),
+                }
             }
             // If unresolved, we skip - it's likely an external/built-in function
         }
+
+        for (callee_node, count, ambiguous) in callees {
+            self.nodes.insert(callee_node.clone());
+            let mut properties = HashMap::new();
+            properties.insert("count".to_string(), count.to_string());
+            if ambiguous {
+                properties.insert("resolution".to_string(), "ambiguous".to_string());
+            }
+            self.edges.push(Edge {
+                from: caller_node.clone(),
+                to: callee_node,
+                edge_type: EdgeType::Calls,
+                properties,
+            });
+        }
+    }
+
+    /// Go types implement interfaces implicitly. Add an INHERITS edge (kind
+    /// `interface`) from every Go type whose methods cover an interface's
+    /// method set, matching names and parameter counts only.
+    fn add_implicit_interface_edges(&mut self, parsed_files: &[ParsedFile]) {
+        let mut declared: HashMap<&str, &InterfaceInfo> = HashMap::new();
+        for interface in parsed_files.iter().flat_map(|file| &file.interfaces) {
+            declared.entry(interface.name.as_str()).or_insert(interface);
+        }
+
+        let mut method_sets = Vec::new();
+        for file in parsed_files {
+            for interface in &file.interfaces {
+                match interface_method_set(interface, &declared, &mut Vec::new()) {
+                    // Every type satisfies an empty interface
+                    Some(methods) if !methods.is_empty() => {
+                        method_sets.push((NodeId::Class(file.path.clone(), interface.name.clone()), methods))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if method_sets.is_empty() {
+            return;
+        }
+
+        for file in parsed_files.iter().filter(|file| file.language == "go") {
+            for class in file.classes.iter().filter(|class| !class.methods.is_empty()) {
+                let methods: HashSet<MethodSignature> = class
+                    .methods
+                    .iter()
+                    .map(|method| MethodSignature {
+                        name: method.name.clone(),
+                        arity: method.params.len(),
+                    })
+                    .collect();
+                for (interface_node, required) in &method_sets {
+                    if !required.is_subset(&methods) {
+                        continue;
+                    }
+                    let mut properties = HashMap::new();
+                    properties.insert("kind".to_string(), "interface".to_string());
+                    self.edges.push(Edge {
+                        from: NodeId::Class(file.path.clone(), class.name.clone()),
+                        to: interface_node.clone(),
+                        edge_type: EdgeType::Inherits,
+                        properties,
+                    });
+                }
+            }
+        }
     }
 
     /// Get all edges of a specific type
@@ -367,6 +595,100 @@ impl DependencyGraph {
         self.edges.iter().filter(|e| e.edge_type == edge_type).collect()
     }
 
+    /// Groups of files that import each other, directly or through other
+    /// files: the strongly connected components (Tarjan) of the file-level
+    /// IMPORTS/DEPENDS_ON edges that span more than one file. Files within a
+    /// group are sorted by path, and larger groups come first.
+    pub fn find_cycles(&self) -> Vec<Vec<String>> {
+        let mut adjacency: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for edge in &self.edges {
+            if !matches!(edge.edge_type, EdgeType::Imports | EdgeType::DependsOn) {
+                continue;
+            }
+            if let (NodeId::File(from), NodeId::File(to)) = (&edge.from, &edge.to) {
+                adjacency.entry(from).or_default().insert(to);
+                adjacency.entry(to).or_default();
+            }
+        }
+
+        let files: Vec<&str> = adjacency.keys().copied().collect();
+        let position: HashMap<&str, usize> = files.iter().enumerate().map(|(i, f)| (*f, i)).collect();
+        let successors: Vec<Vec<usize>> = adjacency
+            .values()
+            .map(|targets| targets.iter().map(|t| position[t]).collect())
+            .collect();
+
+        let mut tarjan = Tarjan::new(files.len());
+        for root in 0..files.len() {
+            if tarjan.index[root].is_none() {
+                tarjan.run(root, &successors);
+            }
+        }
+
+        let mut cycles: Vec<Vec<String>> = tarjan
+            .components
+            .into_iter()
+            .filter(|component| component.len() > 1)
+            .map(|component| {
+                let mut paths: Vec<String> = component.into_iter().map(|i| files[i].to_string()).collect();
+                paths.sort();
+                paths
+            })
+            .collect();
+        cycles.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        cycles
+    }
+
+    /// Add a file-level DEPENDS_ON edge, such as one read back from Neo4j
+    pub fn add_file_dependency(&mut self, from: &str, to: &str) {
+        let (from, to) = (NodeId::File(from.to_string()), NodeId::File(to.to_string()));
+        self.nodes.insert(from.clone());
+        self.nodes.insert(to.clone());
+        self.edges.push(Edge { from, to, edge_type: EdgeType::DependsOn, properties: HashMap::new() });
+    }
+
+    /// Files that depend on the `changed` ones, directly or through other
+    /// files, up to `max_depth` hops: a breadth-first walk of the file-level
+    /// IMPORTS/DEPENDS_ON edges in reverse. Each file is reached once, at its
+    /// shortest distance, so cycles end the walk. The changed files
+    /// themselves are left out.
+    pub fn reverse_reachability(&self, changed: &[String], max_depth: usize) -> Reachability {
+        let mut dependents: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+        for edge in &self.edges {
+            if !matches!(edge.edge_type, EdgeType::Imports | EdgeType::DependsOn) {
+                continue;
+            }
+            if let (NodeId::File(from), NodeId::File(to)) = (&edge.from, &edge.to) {
+                dependents.entry(to).or_default().insert(from);
+            }
+        }
+
+        let mut seen: HashSet<&str> = changed.iter().map(String::as_str).collect();
+        let mut frontier: Vec<&str> = seen.iter().copied().collect();
+        let mut impacted = Vec::new();
+        let mut max_depth_reached = false;
+        let mut distance = 0;
+        while !frontier.is_empty() {
+            let mut next: BTreeSet<&str> = BTreeSet::new();
+            for file in &frontier {
+                let unseen = dependents.get(file).into_iter().flatten().filter(|f| !seen.contains(*f));
+                next.extend(unseen);
+            }
+            if next.is_empty() {
+                break;
+            }
+            if distance == max_depth {
+                max_depth_reached = true;
+                break;
+            }
+            distance += 1;
+            seen.extend(&next);
+            impacted.extend(next.iter().map(|path| ImpactedFile { path: path.to_string(), distance }));
+            frontier = next.into_iter().collect();
+        }
+        Reachability { impacted, max_depth_reached }
+    }
+
     /// Get statistics about the graph
     pub fn stats(&self) -> GraphStats {
         let mut stats = GraphStats::default();
@@ -374,7 +696,7 @@ impl DependencyGraph {
             match node {
                 NodeId::File(_) => stats.files += 1,
                 NodeId::Class(_, _) => stats.classes += 1,
-                NodeId::Function(_, _) => stats.functions += 1,
+                NodeId::Function(_, _) | NodeId::Method(_, _, _) => stats.functions += 1,
                 NodeId::Module(_) => stats.modules += 1,
             }
         }
@@ -385,12 +707,110 @@ impl DependencyGraph {
                 EdgeType::Imports => stats.imports_edges += 1,
                 EdgeType::Inherits => stats.inherits_edges += 1,
                 EdgeType::Contains => stats.contains_edges += 1,
+                EdgeType::DependsOn => stats.depends_on_edges += 1,
+                EdgeType::UsesComponent => stats.uses_component_edges += 1,
             }
         }
         stats
     }
 }
 
+/// Tarjan's strongly connected components, iterative so long import chains
+/// can't overflow the stack
+struct Tarjan {
+    index: Vec<Option<usize>>,
+    lowlink: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl Tarjan {
+    fn new(size: usize) -> Self {
+        Self {
+            index: vec![None; size],
+            lowlink: vec![0; size],
+            on_stack: vec![false; size],
+            stack: Vec::new(),
+            next_index: 0,
+            components: Vec::new(),
+        }
+    }
+
+    fn visit(&mut self, node: usize) {
+        self.index[node] = Some(self.next_index);
+        self.lowlink[node] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(node);
+        self.on_stack[node] = true;
+    }
+
+    fn run(&mut self, root: usize, successors: &[Vec<usize>]) {
+        // (node, position of the next successor to look at)
+        let mut frames = vec![(root, 0)];
+        self.visit(root);
+
+        while let Some(&(node, next)) = frames.last() {
+            if let Some(&successor) = successors[node].get(next) {
+                frames.last_mut().expect("frame exists").1 += 1;
+                match self.index[successor] {
+                    None => {
+                        self.visit(successor);
+                        frames.push((successor, 0));
+                    }
+                    Some(index) if self.on_stack[successor] => {
+                        self.lowlink[node] = self.lowlink[node].min(index);
+                    }
+                    Some(_) => {}
+                }
+                continue;
+            }
+
+            frames.pop();
+            if let Some(&(parent, _)) = frames.last() {
+                self.lowlink[parent] = self.lowlink[parent].min(self.lowlink[node]);
+            }
+            if Some(self.lowlink[node]) == self.index[node] {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+        }
+    }
+}
+
+/// Hops followed from changed files when listing the files they impact
+pub const DEFAULT_IMPACT_DEPTH: usize = 5;
+
+/// A file depending on a changed file, `distance` hops away
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImpactedFile {
+    pub path: String,
+    pub distance: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reachability {
+    /// By distance, then path
+    pub impacted: Vec<ImpactedFile>,
+    /// Whether the walk stopped at the depth cap with dependents left
+    pub max_depth_reached: bool,
+}
+
+impl Reachability {
+    /// The files depending on a changed file directly
+    pub fn direct(&self) -> Vec<&str> {
+        self.impacted.iter().filter(|f| f.distance == 1).map(|f| f.path.as_str()).collect()
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct GraphStats {
     pub files: usize,
@@ -402,6 +822,366 @@ pub struct GraphStats {
     pub imports_edges: usize,
     pub inherits_edges: usize,
     pub contains_edges: usize,
+    pub depends_on_edges: usize,
+    pub uses_component_edges: usize,
+}
+
+/// Methods of an interface including those of the interfaces it embeds.
+/// None if an embed isn't declared in the parsed files (a qualified
+/// `io.Reader`, a type set, a file outside an incremental run) or embeds
+/// itself, since the full method set is then unknown.
+fn interface_method_set(
+    interface: &InterfaceInfo,
+    declared: &HashMap<&str, &InterfaceInfo>,
+    visiting: &mut Vec<String>,
+) -> Option<HashSet<MethodSignature>> {
+    if visiting.contains(&interface.name) {
+        return None;
+    }
+    visiting.push(interface.name.clone());
+    let mut methods: HashSet<MethodSignature> = interface.methods.iter().cloned().collect();
+    for embed in &interface.embeds {
+        methods.extend(interface_method_set(declared.get(embed.as_str())?, declared, visiting)?);
+    }
+    visiting.pop();
+    Some(methods)
+}
+
+/// File-to-file dependencies from resolved imports, which also pick among
+/// same-named callees
+#[derive(Debug, Default)]
+pub struct FileDependencies {
+    /// `(source_file, target_file, import_path)`, see `resolve_file_dependencies`
+    dependencies: Vec<(String, String, String)>,
+    /// Files each file imports
+    imported: HashMap<String, HashSet<String>>,
+    barrels: Barrels,
+    /// Barrel files each file imports, as `(barrel, import_path)`
+    barrel_imports: HashMap<String, Vec<(String, String)>>,
+    /// Names each file uses through a barrel, mapped to the file defining
+    /// them and their name there, see `follow_barrels`
+    through_barrels: HashMap<String, HashMap<String, (String, String)>>,
+}
+
+impl FileDependencies {
+    /// Resolve the imports of `parsed_files`, which needn't hold call lists
+    pub fn resolve(parsed_files: &[ParsedFile], import_resolver: &ImportResolver) -> Self {
+        let dependencies = resolve_file_dependencies(parsed_files, import_resolver);
+        let mut imported: HashMap<String, HashSet<String>> = HashMap::new();
+        for (source, target, _) in &dependencies {
+            imported.entry(source.clone()).or_default().insert(target.clone());
+        }
+
+        let barrels = Barrels::from_files(parsed_files, import_resolver);
+        let file_paths: HashSet<&str> = parsed_files.iter().map(|file| file.path.as_str()).collect();
+        let mut barrel_imports: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        for file in parsed_files.iter().filter(|file| matches!(file.language.as_str(), "typescript" | "javascript")) {
+            for import in &file.imports {
+                match import_resolver.resolve_ts_module(import, &file.path, &file_paths) {
+                    Some(barrel) if barrel != file.path && barrels.is_barrel(&barrel) => {
+                        barrel_imports.entry(file.path.clone()).or_default().push((barrel, import.clone()));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Self { dependencies, imported, barrels, barrel_imports, through_barrels: HashMap::new() }
+    }
+
+    /// Move the dependencies of `parsed_files` on barrel files to the files
+    /// defining the names they call, render or inherit from through them.
+    /// A barrel keeps its edge when the file uses something it defines
+    /// itself, or nothing it re-exports. Needs the files' call lists, so
+    /// streamed files are followed a chunk at a time, before `add_files`.
+    pub fn follow_barrels(&mut self, parsed_files: &[ParsedFile]) {
+        let mut dropped: HashSet<(String, String)> = HashSet::new();
+        for file in parsed_files {
+            let Some(barrel_imports) = self.barrel_imports.get(&file.path) else {
+                continue;
+            };
+            let used = used_names(file);
+            let mut names: HashMap<String, (String, String)> = HashMap::new();
+            let mut added: Vec<(String, String)> = Vec::new();
+            for (barrel, import) in barrel_imports {
+                let mut kept = false;
+                let mut moved = false;
+                for name in &used {
+                    match self.barrels.resolve(barrel, name) {
+                        Some((defining, _)) if defining == *barrel => kept = true,
+                        Some((defining, original)) => {
+                            moved = true;
+                            if defining != file.path {
+                                added.push((defining.clone(), import.clone()));
+                            }
+                            names.entry(name.to_string()).or_insert((defining, original));
+                        }
+                        None => {}
+                    }
+                }
+                if moved && !kept {
+                    dropped.insert((file.path.clone(), barrel.clone()));
+                }
+            }
+
+            let imported = self.imported.entry(file.path.clone()).or_default();
+            for (barrel, _) in barrel_imports {
+                if dropped.contains(&(file.path.clone(), barrel.clone())) {
+                    imported.remove(barrel);
+                }
+            }
+            for (target, import) in added {
+                if imported.insert(target.clone()) {
+                    self.dependencies.push((file.path.clone(), target, import));
+                }
+            }
+            if !names.is_empty() {
+                self.through_barrels.insert(file.path.clone(), names);
+            }
+        }
+
+        if !dropped.is_empty() {
+            self.dependencies.retain(|(source, target, _)| !dropped.contains(&(source.clone(), target.clone())));
+        }
+    }
+}
+
+/// Names a file calls, renders or inherits from, in name order
+fn used_names(file: &ParsedFile) -> BTreeSet<&str> {
+    let methods = file.classes.iter().flat_map(|class| &class.methods);
+    let calls = file.functions.iter().chain(methods).flat_map(|function| &function.calls);
+    let inherited = file.classes.iter().flat_map(|class| &class.inheritances).map(|inheritance| &inheritance.name);
+    calls.chain(&file.rendered_components).chain(inherited).map(String::as_str).collect()
+}
+
+/// Resolve imports to files within the parsed set. Language-aware resolution
+/// runs first; anything it can't place is matched by module name against file
+/// stems and parent directory names.
+///
+/// Returns `(source_file, target_file, import_path)` triples, one per distinct
+/// file pair; self-imports are dropped.
+fn resolve_file_dependencies(
+    parsed_files: &[ParsedFile],
+    import_resolver: &ImportResolver,
+) -> Vec<(String, String, String)> {
+    // Build a map of module names to file paths for resolution
+    let mut module_to_files: HashMap<String, Vec<String>> = HashMap::new();
+
+    for file in parsed_files {
+        let file_path = Path::new(&file.path);
+
+        // Extract potential module names from file path
+        // e.g., "src/utils/helper.ts" -> ["utils/helper", "helper"]
+        if let Some(file_stem) = file_path.file_stem() {
+            let stem_str = file_stem.to_string_lossy().to_string();
+            module_to_files.entry(stem_str.clone()).or_default().push(file.path.clone());
+
+            // Also add parent directory as potential module name
+            if let Some(parent) = file_path.parent() {
+                if let Some(parent_str) = parent.file_name() {
+                    let parent_name = parent_str.to_string_lossy().to_string();
+                    module_to_files.entry(parent_name).or_default().push(file.path.clone());
+                }
+            }
+        }
+    }
+
+    let file_paths: HashSet<&str> = parsed_files.iter().map(|f| f.path.as_str()).collect();
+
+    let mut dependencies = Vec::new();
+    let mut seen_pairs = HashSet::new();
+
+    for file in parsed_files {
+        for import in &file.imports {
+            // Language-aware resolution gives a single exact target
+            if let Some(target_file) = import_resolver.resolve(&file.language, import, &file.path, &file_paths) {
+                if target_file != file.path && seen_pairs.insert((file.path.clone(), target_file.clone())) {
+                    dependencies.push((file.path.clone(), target_file, import.clone()));
+                }
+                continue;
+            }
+
+            // Try to resolve import to a file
+            let mut resolved_files = Vec::new();
+
+            // Try exact match
+            if let Some(files) = module_to_files.get(import) {
+                resolved_files.extend(files.iter().cloned());
+            }
+
+            // Try extracting last part of import path (e.g., "./utils/helper" -> "helper",
+            // "./fileA.js" -> "fileA.js" then also "fileA" after extension strip)
+            if let Some(last_part) = import.split('/').next_back() {
+                let clean_part = last_part.trim_start_matches("./").trim_start_matches("../");
+                // Strip file extension so "fileA.js" matches the stem key "fileA"
+                let stem = clean_part.rfind('.').map(|p| &clean_part[..p]).unwrap_or(clean_part);
+                for key in &[clean_part, stem] {
+                    if let Some(files) = module_to_files.get(*key) {
+                        resolved_files.extend(files.iter().cloned());
+                    }
+                }
+            }
+
+            // Try partial matches for relative imports
+            if import.starts_with("./") || import.starts_with("../") {
+                let import_parts: Vec<&str> = import.split('/').filter(|p| !p.is_empty() && *p != ".." && *p != ".").collect();
+                if let Some(last_part) = import_parts.last() {
+                    let stem = last_part.rfind('.').map(|p| &last_part[..p]).unwrap_or(last_part);
+                    for key in &[*last_part, stem] {
+                        if let Some(files) = module_to_files.get(*key) {
+                            resolved_files.extend(files.iter().cloned());
+                        }
+                    }
+                }
+            }
+
+            // Keep the first import that links a pair (excluding self-imports)
+            for target_file in resolved_files {
+                if target_file != file.path && seen_pairs.insert((file.path.clone(), target_file.clone())) {
+                    dependencies.push((file.path.clone(), target_file, import.clone()));
+                }
+            }
+        }
+    }
+
+    dependencies
+}
+
+/// Files defining the components each file renders, as `(source_file,
+/// target_file, components)`. A component imported through a barrel is
+/// defined where the barrel's re-exports lead. Other names are looked up
+/// among the functions and classes of the symbol table; a definition in a
+/// file the source imports wins, otherwise the name must be defined exactly
+/// once. Components defined in the rendering file itself add no edge.
+fn resolve_rendered_components(
+    parsed_files: &[ParsedFile],
+    symbol_table: &SymbolTable,
+    dependencies: &FileDependencies,
+) -> Vec<(String, String, Vec<String>)> {
+    let mut edges = Vec::new();
+    for file in parsed_files {
+        let imports = dependencies.imported.get(&file.path);
+        let through_barrels = dependencies.through_barrels.get(&file.path);
+        let mut targets: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for component in &file.rendered_components {
+            if let Some((defining, _)) = through_barrels.and_then(|names| names.get(component)) {
+                if *defining != file.path {
+                    targets.entry(defining.as_str()).or_default().push(component.clone());
+                }
+                continue;
+            }
+            let definitions: Vec<&str> = symbol_table
+                .functions
+                .get(component)
+                .into_iter()
+                .flatten()
+                .filter(|entry| entry.class_name.is_none())
+                .chain(symbol_table.classes.get(component).into_iter().flatten())
+                .map(|entry| entry.file_path.as_str())
+                .collect();
+            if definitions.contains(&file.path.as_str()) {
+                continue;
+            }
+            let target = match definitions.iter().find(|path| imports.is_some_and(|i| i.contains(**path))) {
+                Some(path) => *path,
+                None if definitions.len() == 1 => definitions[0],
+                None => continue,
+            };
+            targets.entry(target).or_default().push(component.clone());
+        }
+        for (target, components) in targets {
+            edges.push((file.path.clone(), target.to_string(), components));
+        }
+    }
+    edges
+}
+
+// ============================================================================
+// Package Hierarchy
+// ============================================================================
+
+/// A directory that is a Rust module or a Python package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+    /// Directory path, relative to the repository root
+    pub path: String,
+    /// Last segment of the path
+    pub name: String,
+    /// Path of the nearest enclosing package
+    pub parent: Option<String>,
+    /// Files the package contains directly, sorted
+    pub files: Vec<String>,
+}
+
+/// Whether directory `dir` is a package, judged by the files that would make
+/// it one: `__init__.py` for Python; for Rust, the `mod.rs` or sibling
+/// `dir.rs` a `mod` declaration resolves to, or a `lib.rs`/`main.rs` crate
+/// root. The repository root itself is never a package.
+fn is_package_dir(dir: &str, exists: &impl Fn(&str) -> bool) -> bool {
+    !dir.is_empty()
+        && (["__init__.py", "mod.rs", "lib.rs", "main.rs"]
+            .iter()
+            .any(|marker| exists(&format!("{}/{}", dir, marker)))
+            || exists(&format!("{}.rs", dir)))
+}
+
+/// Directory holding `path`, `""` at the repository root
+pub fn parent_dir(path: &str) -> &str {
+    path.rfind('/').map_or("", |i| &path[..i])
+}
+
+/// Packages holding `files` and every package above them. `exists` tells
+/// whether a file or directory exists in the repository, so an incremental
+/// run that only sees some files still finds the markers of their packages. A Rust module's
+/// `dir.rs` belongs to the `dir` package, not to the directory it sits in.
+pub fn build_package_hierarchy<'a>(
+    files: impl IntoIterator<Item = &'a str>,
+    exists: impl Fn(&str) -> bool,
+) -> Vec<Package> {
+    let files: BTreeSet<&str> = files.into_iter().collect();
+    let is_package = |dir: &str| is_package_dir(dir, &|path: &str| files.contains(path) || exists(path));
+    let is_dir = |dir: &str| {
+        let prefix = format!("{}/", dir);
+        exists(dir) || files.range(prefix.as_str()..).next().is_some_and(|f| f.starts_with(&prefix))
+    };
+
+    // Package directory -> its files
+    let mut packages: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for file in &files {
+        let mut dir = parent_dir(file);
+        let module_dir = file.strip_suffix(".rs").filter(|stem| is_dir(stem) && is_package(stem));
+        if let Some(stem) = module_dir {
+            packages.entry(stem.to_string()).or_default().push(file.to_string());
+        } else if is_package(dir) {
+            packages.entry(dir.to_string()).or_default().push(file.to_string());
+        }
+
+        // Ancestors are listed even without files of their own, so the
+        // chain up to the top-level package is complete
+        while !dir.is_empty() {
+            if is_package(dir) {
+                packages.entry(dir.to_string()).or_default();
+            }
+            dir = parent_dir(dir);
+        }
+    }
+
+    let dirs: BTreeSet<String> = packages.keys().cloned().collect();
+    packages
+        .into_iter()
+        .map(|(path, files)| {
+            let mut ancestor = parent_dir(&path);
+            while !ancestor.is_empty() && !dirs.contains(ancestor) {
+                ancestor = parent_dir(ancestor);
+            }
+            Package {
+                name: path.rsplit('/').next().unwrap_or(&path).to_string(),
+                parent: (!ancestor.is_empty()).then(|| ancestor.to_string()),
+                path,
+                files,
+            }
+        })
+        .collect()
 }
 
 // ============================================================================
@@ -421,12 +1201,17 @@ mod tests {
             calls: calls.into_iter().map(String::from).collect(),
             start_line: 1,
             end_line: 10,
+            complexity: 1,
+            body_hash: String::new(),
+            decorators: Vec::new(),
+            visibility: None,
         }
     }
 
     fn make_class(name: &str, parents: Vec<&str>, methods: Vec<FunctionInfo>) -> ClassInfo {
         ClassInfo {
             name: name.to_string(),
+            kind: None,
             inheritances: parents
                 .into_iter()
                 .map(|p| InheritanceInfo {
@@ -437,9 +1222,49 @@ mod tests {
             methods,
             start_line: 1,
             end_line: 50,
+            body_hash: String::new(),
+            decorators: Vec::new(),
+            visibility: None,
         }
     }
 
+    #[test]
+    fn test_edge_ids_match_patch_scheme() {
+        let file = || NodeId::File("src/shop/cart.py".to_string());
+        let class = || NodeId::Class("src/shop/cart.py".to_string(), "Cart".to_string());
+        let method = || NodeId::Method("src/shop/cart.py".to_string(), "Cart".to_string(), "total".to_string());
+        let function = || NodeId::Function("src/shop/price.py".to_string(), "price".to_string());
+        let edge = |from: NodeId, to: NodeId, edge_type: EdgeType| Edge { from, to, edge_type, properties: HashMap::new() };
+
+        let cases = [
+            (edge(file(), class(), EdgeType::Defines), "defines:src/shop/cart.py->src/shop/cart.py::Cart"),
+            (edge(method(), function(), EdgeType::Calls), "calls:src/shop/cart.py::Cart::total->src/shop/price.py::price"),
+            (edge(file(), NodeId::Module("decimal".to_string()), EdgeType::Imports), "imports:src/shop/cart.py->decimal"),
+            (
+                edge(class(), NodeId::Class("src/shop/base.py".to_string(), "Base".to_string()), EdgeType::Inherits),
+                "inherits:src/shop/cart.py::Cart->src/shop/base.py::Base",
+            ),
+            (edge(class(), method(), EdgeType::Contains), "contains:src/shop/cart.py::Cart->src/shop/cart.py::Cart::total"),
+            (
+                edge(file(), NodeId::File("src/shop/price.py".to_string()), EdgeType::DependsOn),
+                "depends_on:src/shop/cart.py->src/shop/price.py",
+            ),
+            (
+                edge(NodeId::File("web/App.tsx".to_string()), NodeId::File("web/Button.tsx".to_string()), EdgeType::UsesComponent),
+                "uses_component:web/App.tsx->web/Button.tsx",
+            ),
+        ];
+        for (edge, expected) in cases {
+            assert_eq!(edge.id(), expected);
+        }
+
+        assert_eq!(edge_id("CALLS_SERVICE", "api/client.py", "https::billing:8443"), "calls_service:api/client.py->https::billing:8443");
+        assert_eq!(
+            edge_id_cypher("RESOLVES_TO", "e.method + '::' + e.url", "r.path"),
+            "'resolves_to:' + e.method + '::' + e.url + '->' + r.path"
+        );
+    }
+
     #[test]
     fn test_symbol_table_construction() {
         let files = vec![
@@ -448,18 +1273,36 @@ mod tests {
                 language: "rust".to_string(),
                 functions: vec![make_func("foo", vec!["bar"])],
                 classes: vec![],
+                interfaces: vec![],
                 imports: vec![],
                 data_tables: vec![],
                 service_calls: vec![],
+                sql_queries: vec![],
+                env_vars: vec![],
+                is_test: false,
+                generated: false,
+                parse_failed: false,
+                rendered_components: vec![],
+                reexports: vec![],
+                line_count: 0,
             },
             ParsedFile {
                 path: "file_b.rs".to_string(),
                 language: "rust".to_string(),
                 functions: vec![make_func("bar", vec![])],
                 classes: vec![],
+                interfaces: vec![],
                 imports: vec![],
                 data_tables: vec![],
                 service_calls: vec![],
+                sql_queries: vec![],
+                env_vars: vec![],
+                is_test: false,
+                generated: false,
+                parse_failed: false,
+                rendered_components: vec![],
+                reexports: vec![],
+                line_count: 0,
             },
         ];
 
@@ -478,18 +1321,36 @@ mod tests {
                 language: "rust".to_string(),
                 functions: vec![make_func("main", vec!["helper"])],
                 classes: vec![],
+                interfaces: vec![],
                 imports: vec![],
                 data_tables: vec![],
                 service_calls: vec![],
+                sql_queries: vec![],
+                env_vars: vec![],
+                is_test: false,
+                generated: false,
+                parse_failed: false,
+                rendered_components: vec![],
+                reexports: vec![],
+                line_count: 0,
             },
             ParsedFile {
                 path: "callee.rs".to_string(),
                 language: "rust".to_string(),
                 functions: vec![make_func("helper", vec![])],
                 classes: vec![],
+                interfaces: vec![],
                 imports: vec![],
                 data_tables: vec![],
                 service_calls: vec![],
+                sql_queries: vec![],
+                env_vars: vec![],
+                is_test: false,
+                generated: false,
+                parse_failed: false,
+                rendered_components: vec![],
+                reexports: vec![],
+                line_count: 0,
             },
         ];
 
@@ -502,6 +1363,94 @@ mod tests {
         assert!(matches!(&calls[0].to, NodeId::Function(f, n) if f == "callee.rs" && n == "helper"));
     }
 
+    #[test]
+    fn test_duplicate_function_names_resolve_through_imports() {
+        let make_file = |path: &str, imports: Vec<&str>, functions: Vec<FunctionInfo>| ParsedFile {
+            path: path.to_string(),
+            language: "python".to_string(),
+            functions,
+            classes: vec![],
+            interfaces: vec![],
+            imports: imports.into_iter().map(String::from).collect(),
+            data_tables: vec![],
+            service_calls: vec![],
+            sql_queries: vec![],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: false,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: 0,
+        };
+        let files = vec![
+            make_file("apps/legacy/setup.py", vec![], vec![make_func("init", vec![])]),
+            make_file("apps/core/db.py", vec![], vec![make_func("init", vec![])]),
+            make_file("apps/api/server.py", vec!["apps.core.db"], vec![make_func("serve", vec!["init"])]),
+            make_file("apps/legacy/cli.py", vec![], vec![make_func("run", vec!["init"])]),
+            make_file("scripts/seed.py", vec![], vec![make_func("seed", vec!["init"])]),
+        ];
+
+        let table = SymbolTable::from_parsed_files(&files);
+        let graph = DependencyGraph::from_parsed_files(&files, &table);
+
+        let mut calls: Vec<(&str, &str, Option<&str>)> = graph
+            .edges_of_type(EdgeType::Calls)
+            .into_iter()
+            .map(|e| {
+                (e.from.file_path().unwrap(), e.to.file_path().unwrap(), e.properties.get("resolution").map(String::as_str))
+            })
+            .collect();
+        calls.sort();
+        assert_eq!(
+            calls,
+            vec![
+                // The imported module beats the first definition found
+                ("apps/api/server.py", "apps/core/db.py", None),
+                // Then a definition in the same directory
+                ("apps/legacy/cli.py", "apps/legacy/setup.py", None),
+                // Otherwise the first one, flagged
+                ("scripts/seed.py", "apps/legacy/setup.py", Some("ambiguous")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_call_edges_carry_call_counts() {
+        let files = vec![ParsedFile {
+            path: "jobs.py".to_string(),
+            language: "python".to_string(),
+            functions: vec![
+                make_func("run", vec!["fetch", "print", "fetch", "store", "fetch"]),
+                make_func("fetch", vec![]),
+                make_func("store", vec![]),
+            ],
+            classes: vec![],
+            interfaces: vec![],
+            imports: vec![],
+            data_tables: vec![],
+            service_calls: vec![],
+            sql_queries: vec![],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: false,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: 0,
+        }];
+
+        let table = SymbolTable::from_parsed_files(&files);
+        let graph = DependencyGraph::from_parsed_files(&files, &table);
+
+        let counts: Vec<(&str, &str)> = graph
+            .edges_of_type(EdgeType::Calls)
+            .into_iter()
+            .map(|e| (e.to.name(), e.properties["count"].as_str()))
+            .collect();
+        assert_eq!(counts, vec![("fetch", "3"), ("store", "1")]);
+    }
+
     #[test]
     fn test_inheritance_edges() {
         let files = vec![ParsedFile {
@@ -512,9 +1461,18 @@ mod tests {
                 make_class("Animal", vec![], vec![]),
                 make_class("Dog", vec!["Animal"], vec![make_func("bark", vec![])]),
             ],
+            interfaces: vec![],
             imports: vec![],
             data_tables: vec![],
             service_calls: vec![],
+            sql_queries: vec![],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: false,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: 0,
         }];
 
         let table = SymbolTable::from_parsed_files(&files);
@@ -525,4 +1483,580 @@ mod tests {
         assert!(matches!(&inherits[0].from, NodeId::Class(_, n) if n == "Dog"));
         assert!(matches!(&inherits[0].to, NodeId::Class(_, n) if n == "Animal"));
     }
+
+    #[test]
+    fn test_methods_and_functions_get_distinct_ids() {
+        let files = vec![ParsedFile {
+            path: "apps/api.py".to_string(),
+            language: "python".to_string(),
+            functions: vec![make_func("save", vec![]), make_func("main", vec!["save"])],
+            classes: vec![make_class(
+                "User",
+                vec![],
+                vec![make_func("save", vec![]), make_func("persist", vec!["User.save"])],
+            )],
+            interfaces: vec![],
+            imports: vec![],
+            data_tables: vec![],
+            service_calls: vec![],
+            sql_queries: vec![],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: false,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: 0,
+        }];
+
+        let table = SymbolTable::from_parsed_files(&files);
+        let graph = DependencyGraph::from_parsed_files(&files, &table);
+
+        let ids: HashSet<String> = graph.nodes.iter().map(NodeId::qualified_id).collect();
+        assert_eq!(ids.len(), graph.nodes.len());
+        assert!(ids.contains("apps/api.py::save"));
+        assert!(ids.contains("apps/api.py::User::save"));
+        assert_eq!(graph.stats().functions, 4);
+
+        let contains = graph.edges_of_type(EdgeType::Contains);
+        assert!(contains.iter().all(|e| matches!(&e.to, NodeId::Method(_, c, _) if c == "User")));
+        let defines: Vec<String> = graph
+            .edges_of_type(EdgeType::Defines)
+            .iter()
+            .map(|e| e.to.qualified_id())
+            .collect();
+        assert!(defines.contains(&"apps/api.py::save".to_string()));
+        assert!(!defines.contains(&"apps/api.py::User::save".to_string()));
+
+        let calls: Vec<(String, String)> = graph
+            .edges_of_type(EdgeType::Calls)
+            .iter()
+            .map(|e| (e.from.qualified_id(), e.to.qualified_id()))
+            .collect();
+        assert!(calls.contains(&("apps/api.py::main".to_string(), "apps/api.py::save".to_string())));
+        assert!(calls.contains(&("apps/api.py::User::persist".to_string(), "apps/api.py::User::save".to_string())));
+    }
+
+    #[test]
+    fn test_cpp_fixture_inheritance_edges() {
+        use crate::parsers::cpp_parser::CppParser;
+        use crate::parsers::LanguageParser;
+        use std::path::PathBuf;
+
+        let parser = CppParser::new().unwrap();
+        let header = parser
+            .parse_file(
+                &PathBuf::from("include/shape.hpp"),
+                "class Shape {\npublic:\n    virtual double area() const = 0;\n};\n",
+            )
+            .unwrap();
+        let source = parser
+            .parse_file(
+                &PathBuf::from("src/square.cpp"),
+                "#include \"shape.hpp\"\n\nclass Square : public Shape {\n    double side;\npublic:\n    double area() const override { return side * side; }\n};\n",
+            )
+            .unwrap();
+        let files = vec![header, source];
+
+        let table = SymbolTable::from_parsed_files(&files);
+        let graph = DependencyGraph::from_parsed_files(&files, &table);
+
+        let inherits = graph.edges_of_type(EdgeType::Inherits);
+        assert_eq!(inherits.len(), 1);
+        assert!(matches!(&inherits[0].from, NodeId::Class(f, n) if f == "src/square.cpp" && n == "Square"));
+        assert!(matches!(&inherits[0].to, NodeId::Class(f, n) if f == "include/shape.hpp" && n == "Shape"));
+        assert_eq!(graph.edges_of_type(EdgeType::Contains).len(), 2);
+
+        let deps = graph.edges_of_type(EdgeType::DependsOn);
+        assert!(deps.iter().any(|d| matches!(&d.to, NodeId::File(f) if f == "include/shape.hpp")));
+    }
+
+    #[test]
+    fn test_go_fixture_implicit_interface_edges() {
+        use crate::parsers::go_parser::GoParser;
+        use crate::parsers::LanguageParser;
+        use std::path::PathBuf;
+
+        let parser = GoParser::new().unwrap();
+        let interfaces = parser
+            .parse_file(
+                &PathBuf::from("store/store.go"),
+                r#"package store
+
+type Reader interface {
+    Get(key string) (string, error)
+}
+
+type Store interface {
+    Reader
+    Put(key, value string) error
+}
+
+type Flusher interface {
+    Flush(ctx context.Context, force bool) error
+}
+
+type Any interface{}
+
+type Remote interface {
+    io.Closer
+    Get(string) (string, error)
+}
+"#,
+            )
+            .unwrap();
+        let memory = parser
+            .parse_file(
+                &PathBuf::from("store/memory.go"),
+                r#"package store
+
+type MemoryStore struct {
+    items map[string]string
+}
+
+func (m *MemoryStore) Get(key string) (string, error) {
+    return m.items[key], nil
+}
+
+func (m *MemoryStore) Put(key, value string) error {
+    m.items[key] = value
+    return nil
+}
+
+func (m *MemoryStore) Flush(ctx context.Context) error {
+    return nil
+}
+"#,
+            )
+            .unwrap();
+        let files = vec![interfaces, memory];
+
+        let table = SymbolTable::from_parsed_files(&files);
+        let graph = DependencyGraph::from_parsed_files(&files, &table);
+
+        let mut inherits: Vec<(String, String, String)> = graph
+            .edges_of_type(EdgeType::Inherits)
+            .iter()
+            .map(|e| (e.from.qualified_id(), e.to.qualified_id(), e.properties["kind"].clone()))
+            .collect();
+        inherits.sort();
+        let edge = |from: &str, to: &str| (from.to_string(), to.to_string(), "interface".to_string());
+        assert_eq!(
+            inherits,
+            vec![
+                edge("store/memory.go::MemoryStore", "store/store.go::Reader"),
+                edge("store/memory.go::MemoryStore", "store/store.go::Store"),
+                edge("store/store.go::Remote", "io.Closer"),
+                edge("store/store.go::Store", "store/store.go::Reader"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_depends_on_edges() {
+        let make_file = |path: &str, imports: Vec<&str>| ParsedFile {
+            path: path.to_string(),
+            language: "typescript".to_string(),
+            functions: vec![],
+            classes: vec![],
+            interfaces: vec![],
+            imports: imports.into_iter().map(String::from).collect(),
+            data_tables: vec![],
+            service_calls: vec![],
+            sql_queries: vec![],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: false,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: 0,
+        };
+        let files = vec![
+            make_file("src/app.ts", vec!["./utils/helper", "./utils/helper.ts", "react"]),
+            make_file("src/utils/helper.ts", vec!["./helper"]),
+        ];
+
+        let table = SymbolTable::from_parsed_files(&files);
+        let graph = DependencyGraph::from_parsed_files(&files, &table);
+
+        // Duplicate imports of the same file collapse; self-imports and externals are dropped
+        let deps = graph.edges_of_type(EdgeType::DependsOn);
+        assert_eq!(deps.len(), 1);
+        assert!(matches!(&deps[0].from, NodeId::File(f) if f == "src/app.ts"));
+        assert!(matches!(&deps[0].to, NodeId::File(f) if f == "src/utils/helper.ts"));
+        assert_eq!(deps[0].properties.get("import_path").map(String::as_str), Some("./utils/helper"));
+        assert_eq!(graph.stats().depends_on_edges, 1);
+    }
+
+    #[test]
+    fn test_python_imports_resolve_to_module_files() {
+        let make_file = |path: &str, imports: Vec<&str>| ParsedFile {
+            path: path.to_string(),
+            language: "python".to_string(),
+            functions: vec![],
+            classes: vec![],
+            interfaces: vec![],
+            imports: imports.into_iter().map(String::from).collect(),
+            data_tables: vec![],
+            service_calls: vec![],
+            sql_queries: vec![],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: false,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: 0,
+        };
+        let files = vec![
+            make_file("apps/__init__.py", vec![]),
+            make_file("apps/api/__init__.py", vec![]),
+            make_file("apps/api/admin.py", vec![".views", "..core.utils", "apps.core"]),
+            make_file("apps/api/views.py", vec!["apps.api.admin", "mypkg.tool", "os"]),
+            make_file("apps/api/handlers.py", vec!["."]),
+            make_file("apps/core/__init__.py", vec![]),
+            make_file("apps/core/utils.py", vec![]),
+            make_file("scripts/utils.py", vec![]),
+            make_file("src/mypkg/tool.py", vec![]),
+        ];
+
+        let table = SymbolTable::from_parsed_files(&files);
+        let graph = DependencyGraph::from_parsed_files(&files, &table);
+
+        let mut deps: Vec<(String, String, String)> = graph
+            .edges_of_type(EdgeType::DependsOn)
+            .into_iter()
+            .map(|e| {
+                (
+                    e.from.file_path().unwrap().to_string(),
+                    e.to.file_path().unwrap().to_string(),
+                    e.properties["import_path"].clone(),
+                )
+            })
+            .collect();
+        deps.sort();
+
+        let expected: Vec<(String, String, String)> = vec![
+            ("apps/api/admin.py", "apps/api/views.py", ".views"),
+            ("apps/api/admin.py", "apps/core/__init__.py", "apps.core"),
+            ("apps/api/admin.py", "apps/core/utils.py", "..core.utils"),
+            ("apps/api/handlers.py", "apps/api/__init__.py", "."),
+            ("apps/api/views.py", "apps/api/admin.py", "apps.api.admin"),
+            ("apps/api/views.py", "src/mypkg/tool.py", "mypkg.tool"),
+        ]
+        .into_iter()
+        .map(|(a, b, c)| (a.to_string(), b.to_string(), c.to_string()))
+        .collect();
+        assert_eq!(deps, expected);
+    }
+
+    #[test]
+    fn test_tsconfig_alias_imports_resolve() {
+        let make_file = |path: &str, imports: Vec<&str>| ParsedFile {
+            path: path.to_string(),
+            language: "typescript".to_string(),
+            functions: vec![],
+            classes: vec![],
+            interfaces: vec![],
+            imports: imports.into_iter().map(String::from).collect(),
+            data_tables: vec![],
+            service_calls: vec![],
+            sql_queries: vec![],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: false,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: 0,
+        };
+        let files = vec![
+            make_file("src/pages/Home.tsx", vec!["@app/components/Button", "@app/missing", "react"]),
+            make_file("src/components/Button.tsx", vec![]),
+            make_file("lib/Button.ts", vec![]),
+        ];
+        let mut resolver = ImportResolver::default();
+        resolver.add_ts_config("", &serde_json::json!({"paths": {"@app/*": ["src/*"]}}));
+
+        let table = SymbolTable::from_parsed_files(&files);
+        let graph = DependencyGraph::from_parsed_files_with_resolver(&files, &table, &resolver);
+
+        // The alias picks the one real target instead of every "Button" stem
+        let deps = graph.edges_of_type(EdgeType::DependsOn);
+        assert_eq!(deps.len(), 1);
+        assert!(matches!(&deps[0].to, NodeId::File(f) if f == "src/components/Button.tsx"));
+        assert_eq!(deps[0].properties["import_path"], "@app/components/Button");
+
+        // Unresolvable aliases stay Module nodes
+        assert!(graph.nodes.contains(&NodeId::Module("@app/missing".to_string())));
+    }
+
+    #[test]
+    fn test_tsx_fixture_uses_component_edges() {
+        use crate::parsers::typescript::TypeScriptParser;
+        use crate::parsers::LanguageParser;
+        use std::path::PathBuf;
+
+        let parser = TypeScriptParser::new().unwrap();
+        let parse = |path: &str, content: &str| parser.parse_file(&PathBuf::from(path), content).unwrap();
+        let files = vec![
+            parse(
+                "src/App.tsx",
+                r#"
+import { Button } from "./components/Button";
+import { Header } from "./components/Header";
+
+function Footer() {
+    return <footer>(c)</footer>;
+}
+
+export default function App() {
+    return (
+        <main>
+            <Header />
+            <Button label="Save" />
+            <Footer />
+            <Tooltip />
+        </main>
+    );
+}
+"#,
+            ),
+            parse(
+                "src/components/Button.tsx",
+                "export const Button = ({ label }: Props) => <button>{label}</button>;\n",
+            ),
+            parse("src/legacy/Button.tsx", "export function Button() { return <input type=\"button\" />; }\n"),
+            parse(
+                "src/components/Header.tsx",
+                "export class Header extends React.Component { render() { return <h1>Title</h1>; } }\n",
+            ),
+        ];
+
+        let table = SymbolTable::from_parsed_files(&files);
+        let graph = DependencyGraph::from_parsed_files(&files, &table);
+
+        let mut uses: Vec<(String, String, String)> = graph
+            .edges_of_type(EdgeType::UsesComponent)
+            .iter()
+            .map(|e| (e.from.qualified_id(), e.to.qualified_id(), e.properties["components"].clone()))
+            .collect();
+        uses.sort();
+        // The imported Button wins over the legacy one; Footer is local and
+        // Tooltip is defined nowhere
+        assert_eq!(
+            uses,
+            vec![
+                ("src/App.tsx".to_string(), "src/components/Button.tsx".to_string(), "Button".to_string()),
+                ("src/App.tsx".to_string(), "src/components/Header.tsx".to_string(), "Header".to_string()),
+            ]
+        );
+        assert_eq!(graph.stats().uses_component_edges, 2);
+    }
+
+    #[test]
+    fn test_barrel_reexports_resolve_to_defining_files() {
+        use crate::parsers::typescript::TypeScriptParser;
+        use crate::parsers::LanguageParser;
+        use std::path::PathBuf;
+
+        let parser = TypeScriptParser::new().unwrap();
+        let parse = |path: &str, content: &str| parser.parse_file(&PathBuf::from(path), content).unwrap();
+        // Two levels of barrels, the inner one re-exporting the outer one back
+        let files = vec![
+            parse(
+                "src/app.tsx",
+                r#"
+import { PrimaryButton, Icon, Banner } from "./components";
+
+export function main() {
+    PrimaryButton();
+    Icon();
+    missing();
+    return <Banner />;
+}
+"#,
+            ),
+            parse(
+                "src/components/index.ts",
+                "export * from './forms';\nexport { Icon } from './icons/icon';\nexport { Banner } from './banner';\n",
+            ),
+            parse("src/components/forms/index.ts", "export { Button as PrimaryButton } from './button';\nexport * from '../index';\n"),
+            parse("src/components/forms/button.ts", "export function Button() { return 1; }\n"),
+            parse("src/components/icons/icon.ts", "export function Icon() { return 2; }\n"),
+            parse("src/components/banner.tsx", "export function Banner() { return <div />; }\n"),
+            // Same-named functions the barrels don't lead to
+            parse("src/legacy/icon.ts", "export function Icon() { return 3; }\n"),
+        ];
+
+        let table = SymbolTable::from_parsed_files(&files);
+        let graph = DependencyGraph::from_parsed_files(&files, &table);
+        let targets = |edge_type: EdgeType| {
+            let mut targets: Vec<String> = graph
+                .edges_of_type(edge_type)
+                .iter()
+                .filter(|e| e.from.file_path() == Some("src/app.tsx"))
+                .map(|e| e.to.qualified_id())
+                .collect();
+            targets.sort();
+            targets
+        };
+
+        assert_eq!(
+            targets(EdgeType::Calls),
+            vec!["src/components/forms/button.ts::Button", "src/components/icons/icon.ts::Icon"]
+        );
+        assert!(graph.edges_of_type(EdgeType::Calls).iter().all(|e| !e.properties.contains_key("resolution")));
+        assert_eq!(
+            targets(EdgeType::DependsOn),
+            vec!["src/components/banner.tsx", "src/components/forms/button.ts", "src/components/icons/icon.ts"]
+        );
+        assert_eq!(targets(EdgeType::UsesComponent), vec!["src/components/banner.tsx"]);
+    }
+
+    fn graph_with_file_deps(deps: &[(&str, &str)]) -> DependencyGraph {
+        let mut graph = DependencyGraph::default();
+        for (from, to) in deps {
+            graph.nodes.insert(NodeId::File(from.to_string()));
+            graph.nodes.insert(NodeId::File(to.to_string()));
+            graph.edges.push(Edge {
+                from: NodeId::File(from.to_string()),
+                to: NodeId::File(to.to_string()),
+                edge_type: EdgeType::DependsOn,
+                properties: HashMap::new(),
+            });
+        }
+        graph
+    }
+
+    #[test]
+    fn test_find_cycles_two_files() {
+        let graph = graph_with_file_deps(&[("b.ts", "a.ts"), ("a.ts", "b.ts"), ("c.ts", "a.ts")]);
+        assert_eq!(graph.find_cycles(), vec![vec!["a.ts".to_string(), "b.ts".to_string()]]);
+    }
+
+    #[test]
+    fn test_find_cycles_three_files() {
+        let graph = graph_with_file_deps(&[
+            ("x.py", "y.py"),
+            ("y.py", "z.py"),
+            ("z.py", "x.py"),
+            ("p.py", "q.py"),
+            ("q.py", "p.py"),
+            ("z.py", "p.py"),
+        ]);
+        assert_eq!(
+            graph.find_cycles(),
+            vec![
+                vec!["x.py".to_string(), "y.py".to_string(), "z.py".to_string()],
+                vec!["p.py".to_string(), "q.py".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reverse_reachability_walks_dependents() {
+        // api.ts -> service.ts -> db.ts, page.ts -> api.ts, with a cycle
+        // between service.ts and cache.ts
+        let graph = graph_with_file_deps(&[
+            ("api.ts", "service.ts"),
+            ("service.ts", "db.ts"),
+            ("page.ts", "api.ts"),
+            ("cache.ts", "service.ts"),
+            ("service.ts", "cache.ts"),
+            ("db.ts", "config.ts"),
+        ]);
+        let reach = graph.reverse_reachability(&["db.ts".to_string()], DEFAULT_IMPACT_DEPTH);
+        let found: Vec<(&str, usize)> = reach.impacted.iter().map(|f| (f.path.as_str(), f.distance)).collect();
+        assert_eq!(found, vec![("service.ts", 1), ("api.ts", 2), ("cache.ts", 2), ("page.ts", 3)]);
+        assert_eq!(reach.direct(), vec!["service.ts"]);
+        assert!(!reach.max_depth_reached);
+
+        // A changed file inside the cycle isn't listed as impacted by itself
+        let reach = graph.reverse_reachability(&["cache.ts".to_string()], DEFAULT_IMPACT_DEPTH);
+        let found: Vec<(&str, usize)> = reach.impacted.iter().map(|f| (f.path.as_str(), f.distance)).collect();
+        assert_eq!(found, vec![("service.ts", 1), ("api.ts", 2), ("page.ts", 3)]);
+
+        // Nothing depends on page.ts
+        assert_eq!(graph.reverse_reachability(&["page.ts".to_string()], 3), Reachability::default());
+    }
+
+    #[test]
+    fn test_reverse_reachability_depth_cap() {
+        let graph = graph_with_file_deps(&[("b.rs", "a.rs"), ("c.rs", "b.rs"), ("d.rs", "c.rs")]);
+        let reach = graph.reverse_reachability(&["a.rs".to_string()], 2);
+        assert_eq!(reach.impacted.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(), vec!["b.rs", "c.rs"]);
+        assert!(reach.max_depth_reached);
+
+        // Reaching the cap with nothing left isn't cutting the walk short
+        let reach = graph.reverse_reachability(&["a.rs".to_string()], 3);
+        assert_eq!(reach.impacted.len(), 3);
+        assert!(!reach.max_depth_reached);
+        assert!(graph.reverse_reachability(&["a.rs".to_string()], 0).max_depth_reached);
+    }
+
+    #[test]
+    fn test_find_cycles_acyclic_graph() {
+        let graph = graph_with_file_deps(&[("a.rs", "b.rs"), ("b.rs", "c.rs"), ("a.rs", "c.rs")]);
+        assert!(graph.find_cycles().is_empty());
+        assert!(DependencyGraph::default().find_cycles().is_empty());
+    }
+
+    /// (path, parent, files) of each package
+    fn package_summary(packages: &[Package]) -> Vec<(&str, Option<&str>, Vec<&str>)> {
+        packages
+            .iter()
+            .map(|p| (p.path.as_str(), p.parent.as_deref(), p.files.iter().map(String::as_str).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_package_hierarchy_from_paths() {
+        let paths = [
+            "src/main.rs",
+            "src/auth.rs",
+            "src/auth/token.rs",
+            "src/auth/session/mod.rs",
+            "src/util.rs",
+            "apps/api/__init__.py",
+            "apps/api/views.py",
+            "apps/api/v1/__init__.py",
+            "apps/api/v1/routes.py",
+            "apps/scripts/seed.py",
+            "web/src/index.ts",
+        ];
+        let packages = build_package_hierarchy(paths, |_| false);
+
+        assert_eq!(
+            package_summary(&packages),
+            vec![
+                ("apps/api", None, vec!["apps/api/__init__.py", "apps/api/views.py"]),
+                ("apps/api/v1", Some("apps/api"), vec!["apps/api/v1/__init__.py", "apps/api/v1/routes.py"]),
+                ("src", None, vec!["src/main.rs", "src/util.rs"]),
+                // auth.rs is the module's own file; util.rs has no directory
+                ("src/auth", Some("src"), vec!["src/auth.rs", "src/auth/token.rs"]),
+                ("src/auth/session", Some("src/auth"), vec!["src/auth/session/mod.rs"]),
+            ]
+        );
+        assert_eq!(packages[3].name, "auth");
+    }
+
+    #[test]
+    fn test_package_hierarchy_checks_markers_outside_the_run() {
+        // An incremental run that only changed a leaf file still finds its
+        // packages through the checkout
+        let repo = ["pkg/__init__.py", "pkg/core/__init__.py", "src/lib.rs", "src/net/mod.rs", "src/net"];
+        let packages = build_package_hierarchy(["pkg/core/models.py", "src/net/http.rs"], |path| repo.contains(&path));
+
+        assert_eq!(
+            package_summary(&packages),
+            vec![
+                ("pkg", None, vec![]),
+                ("pkg/core", Some("pkg"), vec!["pkg/core/models.py"]),
+                ("src", None, vec![]),
+                ("src/net", Some("src"), vec!["src/net/http.rs"]),
+            ]
+        );
+    }
 }
@@ -0,0 +1,287 @@
+//! Graph Export
+//!
+//! Serializes a `DependencyGraph` as GraphML (Gephi, yEd) or DOT (Graphviz)
+//! so it can be explored without querying Neo4j. Node ids are the same
+//! qualified ids used in Neo4j and graph patches.
+
+use crate::graph_builder::{DependencyGraph, NodeId};
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    GraphMl,
+    Dot,
+}
+
+impl ExportFormat {
+    /// Value of the `export_format` job option
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "graphml" => Some(ExportFormat::GraphMl),
+            "dot" => Some(ExportFormat::Dot),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::GraphMl => "graphml",
+            ExportFormat::Dot => "dot",
+        }
+    }
+
+    /// Name of the uploaded artifact
+    pub fn artifact_name(&self) -> String {
+        format!("graph.{}", self.as_str())
+    }
+}
+
+pub fn export(graph: &DependencyGraph, format: ExportFormat) -> String {
+    match format {
+        ExportFormat::GraphMl => to_graphml(graph),
+        ExportFormat::Dot => to_dot(graph),
+    }
+}
+
+/// Nodes ordered by id, so exports of the same graph are identical
+fn sorted_nodes(graph: &DependencyGraph) -> Vec<(String, &NodeId)> {
+    let mut nodes: Vec<(String, &NodeId)> = graph.nodes.iter().map(|node| (node.qualified_id(), node)).collect();
+    nodes.sort_by(|a, b| a.0.cmp(&b.0));
+    nodes
+}
+
+fn xml_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn dot_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+pub fn to_graphml(graph: &DependencyGraph) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    out.push_str("  <key id=\"type\" for=\"node\" attr.name=\"type\" attr.type=\"string\"/>\n");
+    out.push_str("  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n");
+    out.push_str("  <key id=\"file\" for=\"node\" attr.name=\"file\" attr.type=\"string\"/>\n");
+    out.push_str("  <key id=\"edge_type\" for=\"edge\" attr.name=\"type\" attr.type=\"string\"/>\n");
+    out.push_str("  <graph id=\"dependencies\" edgedefault=\"directed\">\n");
+
+    for (id, node) in sorted_nodes(graph) {
+        let _ = writeln!(out, "    <node id=\"{}\">", xml_escape(&id));
+        let _ = writeln!(out, "      <data key=\"type\">{}</data>", node.node_type());
+        let _ = writeln!(out, "      <data key=\"label\">{}</data>", xml_escape(node.name()));
+        if let Some(file) = node.file_path() {
+            let _ = writeln!(out, "      <data key=\"file\">{}</data>", xml_escape(file));
+        }
+        out.push_str("    </node>\n");
+    }
+
+    for (i, edge) in graph.edges.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "    <edge id=\"e{}\" source=\"{}\" target=\"{}\">",
+            i,
+            xml_escape(&edge.from.qualified_id()),
+            xml_escape(&edge.to.qualified_id())
+        );
+        let _ = writeln!(out, "      <data key=\"edge_type\">{}</data>", edge.edge_type.as_str());
+        out.push_str("    </edge>\n");
+    }
+
+    out.push_str("  </graph>\n");
+    out.push_str("</graphml>\n");
+    out
+}
+
+pub fn to_dot(graph: &DependencyGraph) -> String {
+    let mut out = String::from("digraph dependencies {\n");
+
+    for (id, node) in sorted_nodes(graph) {
+        let _ = write!(
+            out,
+            "  \"{}\" [label=\"{}\", type=\"{}\"",
+            dot_escape(&id),
+            dot_escape(node.name()),
+            node.node_type()
+        );
+        if let Some(file) = node.file_path() {
+            let _ = write!(out, ", file=\"{}\"", dot_escape(file));
+        }
+        out.push_str("];\n");
+    }
+
+    for edge in &graph.edges {
+        let _ = writeln!(
+            out,
+            "  \"{}\" -> \"{}\" [type=\"{}\"];",
+            dot_escape(&edge.from.qualified_id()),
+            dot_escape(&edge.to.qualified_id()),
+            edge.edge_type.as_str()
+        );
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_builder::{Edge, EdgeType};
+    use regex::Regex;
+    use std::collections::{BTreeSet, HashMap};
+
+    fn sample_graph() -> DependencyGraph {
+        let file = NodeId::File("src/a&b.ts".to_string());
+        let class = NodeId::Class("src/a&b.ts".to_string(), "Box<T>".to_string());
+        let method = NodeId::Method("src/a&b.ts".to_string(), "Box<T>".to_string(), "say\"hi\"".to_string());
+        let module = NodeId::Module("C:\\libs\\it's".to_string());
+
+        let mut graph = DependencyGraph::default();
+        for node in [&file, &class, &method, &module] {
+            graph.nodes.insert(node.clone());
+        }
+        for (from, to, edge_type) in [
+            (&file, &class, EdgeType::Defines),
+            (&class, &method, EdgeType::Contains),
+            (&file, &module, EdgeType::Imports),
+        ] {
+            graph.edges.push(Edge {
+                from: from.clone(),
+                to: to.clone(),
+                edge_type,
+                properties: HashMap::new(),
+            });
+        }
+        graph
+    }
+
+    fn expected_ids(graph: &DependencyGraph) -> BTreeSet<String> {
+        graph.nodes.iter().map(NodeId::qualified_id).collect()
+    }
+
+    fn xml_unescape(value: &str) -> String {
+        value
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&")
+    }
+
+    /// Well-formedness check: every tag is closed in order and no raw markup
+    /// characters are left in text or attribute values
+    fn assert_balanced_xml(xml: &str) {
+        let entity = r"&(?:amp|lt|gt|quot|apos);";
+        let tag = Regex::new(&format!(
+            r#"<(/?)([A-Za-z_][\w.:-]*)((?:\s+[\w.:-]+="(?:[^"<&]|{})*")*)\s*(/?)>"#,
+            entity
+        ))
+        .unwrap();
+        let entity = Regex::new(entity).unwrap();
+        let body = xml.trim_start_matches(|c| c != '\n').trim();
+        let mut stack: Vec<String> = Vec::new();
+        let mut last = 0;
+        for cap in tag.captures_iter(body) {
+            let whole = cap.get(0).unwrap();
+            let text = entity.replace_all(&body[last..whole.start()], "");
+            assert!(!text.contains(['<', '>', '&']), "raw markup in text: {:?}", text);
+            last = whole.end();
+            let name = cap[2].to_string();
+            if &cap[1] == "/" {
+                assert_eq!(stack.pop().as_deref(), Some(name.as_str()), "mismatched </{}>", name);
+            } else if &cap[4] != "/" {
+                stack.push(name);
+            }
+        }
+        assert!(body[last..].trim().is_empty(), "trailing content: {:?}", &body[last..]);
+        assert!(stack.is_empty(), "unclosed tags: {:?}", stack);
+    }
+
+    #[test]
+    fn test_graphml_round_trip() {
+        let graph = sample_graph();
+        let xml = to_graphml(&graph);
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<graphml"));
+        assert_balanced_xml(&xml);
+
+        let node_re = Regex::new(r#"<node id="([^"]*)">"#).unwrap();
+        let ids: BTreeSet<String> = node_re.captures_iter(&xml).map(|c| xml_unescape(&c[1])).collect();
+        assert_eq!(ids, expected_ids(&graph));
+
+        let edge_re = Regex::new(r#"<edge id="e\d+" source="([^"]*)" target="([^"]*)">\s*<data key="edge_type">(\w+)</data>"#).unwrap();
+        let edges: Vec<(String, String, String)> = edge_re
+            .captures_iter(&xml)
+            .map(|c| (xml_unescape(&c[1]), xml_unescape(&c[2]), c[3].to_string()))
+            .collect();
+        assert_eq!(edges.len(), 3);
+        assert!(edges.contains(&(
+            "src/a&b.ts::Box<T>".to_string(),
+            "src/a&b.ts::Box<T>::say\"hi\"".to_string(),
+            "CONTAINS".to_string()
+        )));
+        assert!(xml.contains("<data key=\"label\">say&quot;hi&quot;</data>"));
+    }
+
+    #[test]
+    fn test_dot_round_trip() {
+        let graph = sample_graph();
+        let dot = to_dot(&graph);
+        assert!(dot.starts_with("digraph dependencies {\n") && dot.ends_with("}\n"));
+
+        let quoted = r#""((?:[^"\\]|\\.)*)""#;
+        let node_re = Regex::new(&format!(r#"^  {q} \[label={q}, type="(\w+)"(?:, file={q})?\];$"#, q = quoted)).unwrap();
+        let edge_re = Regex::new(&format!(r#"^  {q} -> {q} \[type="(\w+)"\];$"#, q = quoted)).unwrap();
+        let unescape = |s: &str| s.replace("\\\"", "\"").replace("\\\\", "\\");
+
+        let mut ids = BTreeSet::new();
+        let mut edges = Vec::new();
+        for line in dot.lines().skip(1).take_while(|line| *line != "}") {
+            if let Some(c) = node_re.captures(line) {
+                ids.insert(unescape(&c[1]));
+            } else if let Some(c) = edge_re.captures(line) {
+                edges.push((unescape(&c[1]), unescape(&c[2]), c[3].to_string()));
+            } else {
+                panic!("unparseable DOT line: {}", line);
+            }
+        }
+        assert_eq!(ids, expected_ids(&graph));
+        assert_eq!(edges.len(), 3);
+        assert!(edges.contains(&(
+            "src/a&b.ts".to_string(),
+            "C:\\libs\\it's".to_string(),
+            "IMPORTS".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_export_format_option() {
+        assert_eq!(ExportFormat::parse("GraphML"), Some(ExportFormat::GraphMl));
+        assert_eq!(ExportFormat::parse("dot"), Some(ExportFormat::Dot));
+        assert_eq!(ExportFormat::parse("svg"), None);
+        assert_eq!(ExportFormat::Dot.artifact_name(), "graph.dot");
+    }
+}
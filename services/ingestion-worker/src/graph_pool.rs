@@ -0,0 +1,187 @@
+//! Graph Pool
+//!
+//! One Neo4j handle per database. The worker connects to its configured
+//! database (`NEO4J_DATABASE`, or the server default) at startup; jobs whose
+//! `neo4j_database` option names another database get a handle connected on
+//! first use and cached for later jobs. A handle whose server went away is
+//! replaced with `reconnect`.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::future::Future;
+use tokio::sync::Mutex;
+
+/// Job option naming the database a job's graph is stored in
+pub const DATABASE_OPTION: &str = "neo4j_database";
+
+/// Opens a handle for a database; `None` is the server's default database.
+/// Any per-database setup, such as the schema, belongs here too.
+pub trait GraphConnector: Send + Sync {
+    type Graph: Clone + Send + Sync;
+
+    fn connect(&self, database: Option<&str>) -> impl Future<Output = Result<Self::Graph>> + Send;
+}
+
+pub struct GraphPool<C: GraphConnector> {
+    connector: C,
+    default_database: Option<String>,
+    default_graph: Mutex<C::Graph>,
+    /// Handles for databases other than the default, by name
+    graphs: Mutex<HashMap<String, C::Graph>>,
+}
+
+impl<C: GraphConnector> GraphPool<C> {
+    /// Connect to the default database up front, so a misconfigured worker
+    /// fails at startup rather than on its first job
+    pub async fn connect(connector: C, default_database: Option<String>) -> Result<Self> {
+        let default_graph = connector.connect(default_database.as_deref()).await?;
+        Ok(Self {
+            connector,
+            default_database,
+            default_graph: Mutex::new(default_graph),
+            graphs: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The handle for `database`, connecting on first use. A failed connect
+    /// isn't cached, so the next job asking for the database tries again.
+    pub async fn get(&self, database: Option<&str>) -> Result<C::Graph> {
+        let Some(database) = self.non_default(database) else {
+            return Ok(self.default_graph.lock().await.clone());
+        };
+
+        // Held across the connect so concurrent jobs don't open the same
+        // database twice
+        let mut graphs = self.graphs.lock().await;
+        if let Some(graph) = graphs.get(database) {
+            return Ok(graph.clone());
+        }
+        let graph = self.connector.connect(Some(database)).await?;
+        graphs.insert(database.to_string(), graph.clone());
+        Ok(graph)
+    }
+
+    /// Replace the handle for `database` with a fresh connection, for when
+    /// the server restarted under it. On failure the old handle stays.
+    pub async fn reconnect(&self, database: Option<&str>) -> Result<C::Graph> {
+        match self.non_default(database) {
+            Some(database) => {
+                let mut graphs = self.graphs.lock().await;
+                let graph = self.connector.connect(Some(database)).await?;
+                graphs.insert(database.to_string(), graph.clone());
+                Ok(graph)
+            }
+            None => {
+                let mut default_graph = self.default_graph.lock().await;
+                *default_graph = self.connector.connect(self.default_database.as_deref()).await?;
+                Ok(default_graph.clone())
+            }
+        }
+    }
+
+    /// The handle for a job, per its `neo4j_database` option
+    pub async fn for_job(&self, options: Option<&HashMap<String, String>>) -> Result<C::Graph> {
+        self.get(job_database(options)).await
+    }
+
+    /// A fresh handle for a job's database, see `reconnect`
+    pub async fn reconnect_for_job(&self, options: Option<&HashMap<String, String>>) -> Result<C::Graph> {
+        self.reconnect(job_database(options)).await
+    }
+
+    /// `database` unless it is blank or names the default database
+    fn non_default<'a>(&self, database: Option<&'a str>) -> Option<&'a str> {
+        database
+            .map(str::trim)
+            .filter(|db| !db.is_empty() && Some(*db) != self.default_database.as_deref())
+    }
+}
+
+fn job_database(options: Option<&HashMap<String, String>>) -> Option<&str> {
+    options.and_then(|o| o.get(DATABASE_OPTION)).map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+
+    /// "Connects" by recording the database; names starting with "down"
+    /// fail
+    #[derive(Default)]
+    struct FakeConnector {
+        connects: StdMutex<Vec<Option<String>>>,
+    }
+
+    impl GraphConnector for FakeConnector {
+        type Graph = String;
+
+        async fn connect(&self, database: Option<&str>) -> Result<String> {
+            self.connects.lock().unwrap().push(database.map(str::to_string));
+            match database {
+                Some(db) if db.starts_with("down") => anyhow::bail!("database {} unavailable", db),
+                Some(db) => Ok(format!("graph:{}", db)),
+                None => Ok("graph:default".to_string()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool_caches_handles_per_database() {
+        let pool = GraphPool::connect(FakeConnector::default(), None).await.unwrap();
+        assert_eq!(pool.get(None).await.unwrap(), "graph:default");
+        assert_eq!(pool.get(Some(" ")).await.unwrap(), "graph:default");
+        assert_eq!(pool.get(Some("tenant-a")).await.unwrap(), "graph:tenant-a");
+        assert_eq!(pool.get(Some("tenant-a")).await.unwrap(), "graph:tenant-a");
+        assert_eq!(pool.get(Some("tenant-b")).await.unwrap(), "graph:tenant-b");
+
+        let options = HashMap::from([(DATABASE_OPTION.to_string(), "tenant-b".to_string())]);
+        assert_eq!(pool.for_job(Some(&options)).await.unwrap(), "graph:tenant-b");
+        assert_eq!(pool.for_job(None).await.unwrap(), "graph:default");
+
+        assert_eq!(
+            *pool.connector.connects.lock().unwrap(),
+            vec![None, Some("tenant-a".to_string()), Some("tenant-b".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pool_uses_configured_default_and_retries_failures() {
+        let pool = GraphPool::connect(FakeConnector::default(), Some("tenants".to_string())).await.unwrap();
+        assert_eq!(pool.get(None).await.unwrap(), "graph:tenants");
+        // Naming the default database reuses its handle
+        assert_eq!(pool.get(Some("tenants")).await.unwrap(), "graph:tenants");
+
+        assert!(pool.get(Some("down-1")).await.is_err());
+        assert!(pool.get(Some("down-1")).await.is_err());
+        assert_eq!(
+            *pool.connector.connects.lock().unwrap(),
+            vec![Some("tenants".to_string()), Some("down-1".to_string()), Some("down-1".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_replaces_cached_handles() {
+        let pool = GraphPool::connect(FakeConnector::default(), None).await.unwrap();
+        pool.get(Some("tenant-a")).await.unwrap();
+
+        let options = HashMap::from([(DATABASE_OPTION.to_string(), "tenant-a".to_string())]);
+        assert_eq!(pool.reconnect_for_job(Some(&options)).await.unwrap(), "graph:tenant-a");
+        assert_eq!(pool.reconnect_for_job(None).await.unwrap(), "graph:default");
+        // Later lookups use the new handles without connecting again
+        pool.get(Some("tenant-a")).await.unwrap();
+        pool.get(None).await.unwrap();
+        assert!(pool.reconnect(Some("down-2")).await.is_err());
+
+        assert_eq!(
+            *pool.connector.connects.lock().unwrap(),
+            vec![
+                None,
+                Some("tenant-a".to_string()),
+                Some("tenant-a".to_string()),
+                None,
+                Some("down-2".to_string())
+            ]
+        );
+    }
+}
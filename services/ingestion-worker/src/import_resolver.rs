@@ -0,0 +1,501 @@
+//! Import Resolution
+//!
+//! Language-aware mapping of import strings to files in the repository. The
+//! graph builder tries this before its generic stem matching:
+//! - Python: dotted module paths and relative imports
+//! - TypeScript/JavaScript: `paths` aliases and `baseUrl` from every
+//!   `tsconfig.json` / `jsconfig.json`, scoped to the config's directory
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Extensions tried, in order, for an extensionless TS/JS module path
+const TS_EXTENSIONS: &[&str] = &[".ts", ".tsx", ".d.ts", ".js", ".jsx", ".mjs"];
+const TS_INDEX_FILES: &[&str] = &["/index.ts", "/index.tsx", "/index.js", "/index.jsx"];
+
+/// Maximum depth of tsconfig `extends` chains
+const MAX_EXTENDS_DEPTH: usize = 5;
+
+#[derive(Debug, Default)]
+pub struct ImportResolver {
+    ts_configs: Vec<TsConfigScope>,
+}
+
+/// Module resolution settings of one tsconfig/jsconfig
+#[derive(Debug, Clone, Default)]
+struct TsConfigScope {
+    /// Directory of the config file relative to the repo root ("" for the root)
+    dir: String,
+    /// `baseUrl` relative to the repo root, if set
+    base_url: Option<String>,
+    /// `paths` entries: pattern -> target patterns, relative to the repo root
+    aliases: Vec<(String, Vec<String>)>,
+}
+
+impl ImportResolver {
+    /// Load every tsconfig.json/jsconfig.json in the repository. Configs that
+    /// can't be read or parsed are skipped with a warning.
+    pub fn from_repo(repo_path: &Path) -> Self {
+        let mut config_files = Vec::new();
+        if let Err(e) = collect_ts_configs(repo_path, &mut config_files) {
+            warn!("⚠️  Failed to scan for tsconfig files: {}", e);
+        }
+        config_files.sort();
+
+        let mut resolver = ImportResolver::default();
+        for config_file in config_files {
+            match load_ts_config(&config_file, repo_path, 0) {
+                Ok(config) => resolver.add_scope(&config.dir, config.base_url, config.paths.as_ref(), &config.paths_dir),
+                Err(e) => warn!("⚠️  Skipping {:?}: {:#}", config_file, e),
+            }
+        }
+
+        if !resolver.ts_configs.is_empty() {
+            info!("🧭 Loaded {} tsconfig/jsconfig path configurations", resolver.ts_configs.len());
+        }
+        resolver
+    }
+
+    /// Register the `compilerOptions` of a config located in `config_dir`;
+    /// jobs load them from disk through `from_repo`
+    #[cfg(test)]
+    pub fn add_ts_config(&mut self, config_dir: &str, compiler_options: &serde_json::Value) {
+        let base_url = compiler_options
+            .get("baseUrl")
+            .and_then(|v| v.as_str())
+            .and_then(|url| normalize_path(&join_path(config_dir, url)));
+        self.add_scope(config_dir, base_url, compiler_options.get("paths"), config_dir);
+    }
+
+    /// `paths` targets are relative to `base_url` when set, otherwise to
+    /// `paths_dir` (the directory of the config that declared them)
+    fn add_scope(&mut self, dir: &str, base_url: Option<String>, paths: Option<&serde_json::Value>, paths_dir: &str) {
+        let paths_base = base_url.clone().unwrap_or_else(|| paths_dir.to_string());
+        let mut aliases: Vec<(String, Vec<String>)> = paths
+            .and_then(|v| v.as_object())
+            .map(|paths| {
+                paths
+                    .iter()
+                    .map(|(pattern, targets)| {
+                        let targets = targets
+                            .as_array()
+                            .map(|t| {
+                                t.iter()
+                                    .filter_map(|v| v.as_str())
+                                    .filter_map(|target| normalize_path(&join_path(&paths_base, target)))
+                                    .collect()
+                            })
+                            .unwrap_or_default();
+                        (pattern.clone(), targets)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Like tsc, prefer the pattern with the longest prefix before `*`
+        aliases.sort_by_key(|(pattern, _)| std::cmp::Reverse(pattern.split('*').next().unwrap_or("").len()));
+
+        if base_url.is_none() && aliases.is_empty() {
+            return;
+        }
+        self.ts_configs.push(TsConfigScope {
+            dir: dir.to_string(),
+            base_url,
+            aliases,
+        });
+    }
+
+    /// Resolve an import to a single file among `file_paths`, or None to let
+    /// the caller fall back to its generic matching.
+    pub fn resolve(&self, language: &str, import: &str, importer: &str, file_paths: &HashSet<&str>) -> Option<String> {
+        match language {
+            "python" => resolve_python_import(import, importer, file_paths),
+            "typescript" | "javascript" => self.resolve_ts_import(import, importer, file_paths),
+            _ => None,
+        }
+    }
+
+    /// Resolve a TS/JS module specifier to a file among `file_paths`,
+    /// including relative ones (`./button`), which `resolve` leaves to the
+    /// caller
+    pub fn resolve_ts_module(&self, import: &str, importer: &str, file_paths: &HashSet<&str>) -> Option<String> {
+        if import.starts_with("./") || import.starts_with("../") {
+            let dir = importer.rsplit_once('/').map_or("", |(dir, _)| dir);
+            return find_ts_module(&join_path(dir, import), file_paths);
+        }
+        self.resolve_ts_import(import, importer, file_paths)
+    }
+
+    fn resolve_ts_import(&self, import: &str, importer: &str, file_paths: &HashSet<&str>) -> Option<String> {
+        if import.starts_with('.') || import.starts_with('/') {
+            return None;
+        }
+
+        // The innermost config whose directory contains the importer applies
+        let scope = self
+            .ts_configs
+            .iter()
+            .filter(|scope| scope.dir.is_empty() || importer.starts_with(&format!("{}/", scope.dir)))
+            .max_by_key(|scope| scope.dir.len())?;
+
+        for (pattern, targets) in &scope.aliases {
+            let Some(wildcard) = match_alias(pattern, import) else {
+                continue;
+            };
+            for target in targets {
+                let module_path = target.replacen('*', wildcard, 1);
+                if let Some(found) = find_ts_module(&module_path, file_paths) {
+                    return Some(found);
+                }
+            }
+        }
+
+        let base_url = scope.base_url.as_ref()?;
+        find_ts_module(&join_path(base_url, import), file_paths)
+    }
+}
+
+/// Match an import against a `paths` pattern, returning the text matched by `*`
+fn match_alias<'a>(pattern: &str, import: &'a str) -> Option<&'a str> {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => {
+            if import.len() >= prefix.len() + suffix.len() && import.starts_with(prefix) && import.ends_with(suffix) {
+                Some(&import[prefix.len()..import.len() - suffix.len()])
+            } else {
+                None
+            }
+        }
+        None => (pattern == import).then_some(""),
+    }
+}
+
+/// Find the file a TS/JS module path refers to, trying extensions and index files
+fn find_ts_module(module_path: &str, file_paths: &HashSet<&str>) -> Option<String> {
+    let module_path = normalize_path(module_path)?;
+    if file_paths.contains(module_path.as_str()) {
+        return Some(module_path);
+    }
+    TS_EXTENSIONS
+        .iter()
+        .chain(TS_INDEX_FILES.iter())
+        .map(|suffix| format!("{}{}", module_path, suffix))
+        .find(|candidate| file_paths.contains(candidate.as_str()))
+}
+
+/// Resolve a Python module path to a parsed file.
+///
+/// Relative imports (`.models`, `..core.db`) are resolved against the
+/// importing file's package. Absolute dotted paths (`apps.api.admin`) are
+/// tried from the repo root first, then as a unique suffix to allow `src/`
+/// style layouts. Modules map to `a/b.py` or `a/b/__init__.py`.
+fn resolve_python_import(import: &str, importer: &str, file_paths: &HashSet<&str>) -> Option<String> {
+    let module = import.trim();
+    let dots = module.len() - module.trim_start_matches('.').len();
+    let parts: Vec<&str> = module[dots..].split('.').filter(|p| !p.is_empty()).collect();
+
+    let module_paths = |base: &[&str]| -> Vec<String> {
+        let joined: Vec<&str> = base.iter().chain(parts.iter()).copied().collect();
+        let stem = joined.join("/");
+        if parts.is_empty() {
+            vec![format!("{}/__init__.py", stem).trim_start_matches('/').to_string()]
+        } else {
+            vec![format!("{}.py", stem), format!("{}/__init__.py", stem)]
+        }
+    };
+
+    if dots > 0 {
+        // One dot is the importer's own package, each extra dot goes up a level
+        let mut package: Vec<&str> = importer.split('/').collect();
+        package.pop();
+        for _ in 1..dots {
+            package.pop()?;
+        }
+        return module_paths(&package)
+            .into_iter()
+            .find(|candidate| file_paths.contains(candidate.as_str()));
+    }
+
+    if parts.is_empty() {
+        return None;
+    }
+
+    let candidates = module_paths(&[]);
+    if let Some(found) = candidates.iter().find(|c| file_paths.contains(c.as_str())) {
+        return Some(found.clone());
+    }
+
+    for candidate in &candidates {
+        let suffix = format!("/{}", candidate);
+        let mut matches = file_paths.iter().filter(|path| path.ends_with(&suffix));
+        if let (Some(only), None) = (matches.next(), matches.next()) {
+            return Some(only.to_string());
+        }
+    }
+
+    None
+}
+
+fn join_path(base: &str, path: &str) -> String {
+    if base.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}/{}", base, path)
+    }
+}
+
+/// Collapse `.` and `..` segments; None if the path escapes the repo root
+fn normalize_path(path: &str) -> Option<String> {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Some(segments.join("/"))
+}
+
+fn collect_ts_configs(current_dir: &Path, results: &mut Vec<std::path::PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(current_dir).context("Failed to read directory")? {
+        let entry = entry.context("Failed to read directory entry")?;
+        let path = entry.path();
+
+        if let Some(name) = path.file_name() {
+            let name_str = name.to_string_lossy();
+            if name_str.starts_with('.')
+                || name_str == "node_modules"
+                || name_str == "target"
+                || name_str == "dist"
+                || name_str == "build"
+                || name_str == "venv"
+                || name_str == "__pycache__" {
+                continue;
+            }
+
+            if path.is_dir() {
+                collect_ts_configs(&path, results)?;
+            } else if name_str == "tsconfig.json" || name_str == "jsconfig.json" {
+                results.push(path);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Module resolution settings of a config after following `extends`, with
+/// every directory relative to the repo root
+#[derive(Debug, Default)]
+struct LoadedTsConfig {
+    dir: String,
+    base_url: Option<String>,
+    paths: Option<serde_json::Value>,
+    paths_dir: String,
+}
+
+/// Load a config and the chain it `extends`. As in tsc, an inherited
+/// `baseUrl` or `paths` stays relative to the config that declared it.
+fn load_ts_config(config_file: &Path, repo_path: &Path, depth: usize) -> Result<LoadedTsConfig> {
+    let raw = fs::read_to_string(config_file).context("Failed to read config")?;
+    let config: serde_json::Value = serde_json::from_str(&strip_jsonc(&raw)).context("Failed to parse config")?;
+
+    let config_dir = config_file.parent().unwrap_or(Path::new(""));
+    let dir = config_dir
+        .strip_prefix(repo_path)
+        .ok()
+        .and_then(|relative| normalize_path(&relative.to_string_lossy().replace('\\', "/")))
+        .context("Config is outside the repository")?;
+
+    let mut loaded = LoadedTsConfig::default();
+    if let Some(parent) = config.get("extends").and_then(|v| v.as_str()) {
+        if depth < MAX_EXTENDS_DEPTH && parent.starts_with('.') {
+            let parent_file = if parent.ends_with(".json") {
+                config_dir.join(parent)
+            } else {
+                config_dir.join(format!("{}.json", parent))
+            };
+            match load_ts_config(&parent_file, repo_path, depth + 1) {
+                Ok(parent_config) => loaded = parent_config,
+                Err(e) => warn!("⚠️  Ignoring extends {:?}: {:#}", parent_file, e),
+            }
+        }
+    }
+
+    let options = config.get("compilerOptions");
+    if let Some(url) = options.and_then(|o| o.get("baseUrl")).and_then(|v| v.as_str()) {
+        loaded.base_url = normalize_path(&join_path(&dir, url));
+    }
+    if let Some(paths) = options.and_then(|o| o.get("paths")) {
+        loaded.paths = Some(paths.clone());
+        loaded.paths_dir = dir.clone();
+    }
+    loaded.dir = dir;
+
+    Ok(loaded)
+}
+
+/// Remove comments and trailing commas, which tsconfig files allow
+fn strip_jsonc(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            if c == '\\' {
+                if let Some(escaped) = chars.next() {
+                    out.push(escaped);
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                while chars.peek().is_some_and(|&n| n != '\n') {
+                    chars.next();
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for n in chars.by_ref() {
+                    if previous == '*' && n == '/' {
+                        break;
+                    }
+                    previous = n;
+                }
+            }
+            (']' | '}', _) => {
+                // Drop a trailing comma before the closing bracket
+                let trimmed_len = out.trim_end().len();
+                if out[..trimmed_len].ends_with(',') {
+                    out.truncate(trimmed_len - 1);
+                }
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn files(paths: &[&'static str]) -> HashSet<&'static str> {
+        paths.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_wildcard_and_exact_aliases() {
+        let mut resolver = ImportResolver::default();
+        resolver.add_ts_config(
+            "web",
+            &json!({
+                "baseUrl": ".",
+                "paths": {
+                    "@app/*": ["src/*"],
+                    "@app/ui/*": ["src/components/*", "src/legacy/*"],
+                    "@config": ["src/config/index.ts"]
+                }
+            }),
+        );
+        let paths = files(&[
+            "web/src/main.ts",
+            "web/src/services/api.ts",
+            "web/src/components/Button.tsx",
+            "web/src/legacy/Modal/index.ts",
+            "web/src/config/index.ts",
+            "web/src/utils/format.ts",
+        ]);
+        let resolve = |import: &str| resolver.resolve("typescript", import, "web/src/main.ts", &paths);
+
+        assert_eq!(resolve("@app/services/api").as_deref(), Some("web/src/services/api.ts"));
+        // The more specific pattern wins, and falls through its target list
+        assert_eq!(resolve("@app/ui/Button").as_deref(), Some("web/src/components/Button.tsx"));
+        assert_eq!(resolve("@app/ui/Modal").as_deref(), Some("web/src/legacy/Modal/index.ts"));
+        assert_eq!(resolve("@config").as_deref(), Some("web/src/config/index.ts"));
+        // baseUrl makes bare paths resolvable too
+        assert_eq!(resolve("src/utils/format").as_deref(), Some("web/src/utils/format.ts"));
+        assert_eq!(resolve("@app/missing"), None);
+        assert_eq!(resolve("react"), None);
+        assert_eq!(resolve("./relative"), None);
+
+        // Files outside the config's directory are not affected by it
+        assert_eq!(resolver.resolve("typescript", "@config", "server/index.ts", &paths), None);
+    }
+
+    #[test]
+    fn test_relative_ts_modules() {
+        let files: HashSet<&str> = ["src/ui/button.tsx", "src/ui/index.ts", "src/app.ts"].into_iter().collect();
+        let resolver = ImportResolver::default();
+        assert_eq!(resolver.resolve_ts_module("./button", "src/ui/index.ts", &files).as_deref(), Some("src/ui/button.tsx"));
+        assert_eq!(resolver.resolve_ts_module("./ui", "src/app.ts", &files).as_deref(), Some("src/ui/index.ts"));
+        assert_eq!(resolver.resolve_ts_module("../app", "src/ui/index.ts", &files).as_deref(), Some("src/app.ts"));
+        assert_eq!(resolver.resolve_ts_module("../../../app", "src/ui/index.ts", &files), None);
+        assert_eq!(resolver.resolve_ts_module("react", "src/app.ts", &files), None);
+    }
+
+    #[test]
+    fn test_paths_without_base_url_are_relative_to_config() {
+        let mut resolver = ImportResolver::default();
+        resolver.add_ts_config("", &json!({"paths": {"@/*": ["./src/*"]}}));
+        let paths = files(&["src/components/Button.tsx", "src/index.js"]);
+
+        assert_eq!(
+            resolver.resolve("javascript", "@/components/Button", "src/index.js", &paths).as_deref(),
+            Some("src/components/Button.tsx")
+        );
+        assert_eq!(resolver.resolve("javascript", "components/Button", "src/index.js", &paths), None);
+    }
+
+    #[test]
+    fn test_from_repo_reads_jsonc_and_extends() {
+        let dir = std::env::temp_dir().join(format!("tsconfig-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("app/src/lib")).unwrap();
+        fs::write(
+            dir.join("tsconfig.base.json"),
+            "{\n  // shared settings\n  \"compilerOptions\": {\n    \"baseUrl\": \".\",\n    \"paths\": {\"@lib/*\": [\"app/src/lib/*\"],},\n  },\n}\n",
+        )
+        .unwrap();
+        fs::write(dir.join("tsconfig.json"), "{ not json").unwrap();
+        fs::write(
+            dir.join("app/tsconfig.json"),
+            "{ \"extends\": \"../tsconfig.base\", /* no overrides */ \"compilerOptions\": { \"strict\": true } }",
+        )
+        .unwrap();
+
+        let resolver = ImportResolver::from_repo(&dir);
+        let _ = fs::remove_dir_all(&dir);
+
+        let paths = files(&["app/src/lib/math.ts", "app/src/main.ts"]);
+        assert_eq!(
+            resolver.resolve("typescript", "@lib/math", "app/src/main.ts", &paths).as_deref(),
+            Some("app/src/lib/math.ts")
+        );
+    }
+
+    #[test]
+    fn test_strip_jsonc_keeps_strings_intact() {
+        let stripped = strip_jsonc("{\"a\": \"http://x/*y*/\", // note\n \"b\": [1, 2,], }");
+        let value: serde_json::Value = serde_json::from_str(&stripped).unwrap();
+        assert_eq!(value, json!({"a": "http://x/*y*/", "b": [1, 2]}));
+    }
+}
@@ -0,0 +1,747 @@
+//! Infrastructure Scanner
+//!
+//! Deployment topology next to the code. Each Dockerfile becomes a container
+//! image, and the Deployments, StatefulSets, DaemonSets, Services and
+//! Ingresses in Kubernetes manifests become workloads and services.
+//! Manifests are YAML files under a `k8s`, `kubernetes`, `manifests` or
+//! `deploy` directory. A file may hold several documents; documents that
+//! don't parse or are of other kinds are skipped. Helm charts (directories
+//! with a `Chart.yaml`) are skipped as a whole, since their templates aren't
+//! YAML until rendered.
+//!
+//! A workload runs a repository image when one of its containers' images has
+//! the image's name as its repository name (`ghcr.io/acme/api:1.4` runs
+//! `api`). A Service selects the workloads in its namespace whose pod labels
+//! match its selector. A compose service builds the `Dockerfile` in its
+//! build context.
+
+use anyhow::{Context, Result};
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::communication_detector::{scalar, ComposeService};
+use crate::graph_builder::parent_dir;
+use crate::path_filter::PathFilter;
+use crate::repo_paths::normalize_repo_path;
+
+/// Directories whose YAML files are read as Kubernetes manifests
+const MANIFEST_DIRS: &[&str] = &["k8s", "kubernetes", "manifests", "deploy"];
+
+const IGNORED_DIRS: &[&str] = &["node_modules", "target", "dist", "build", "vendor", "venv", "__pycache__"];
+
+/// Manifest kinds stored as K8sDeployment nodes
+const WORKLOAD_KINDS: &[&str] = &["Deployment", "StatefulSet", "DaemonSet"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContainerImage {
+    /// Repo-relative path of the Dockerfile, which identifies the image
+    pub dockerfile: String,
+    /// `api` for `Dockerfile.api`, `api.Dockerfile` or `api/Dockerfile`;
+    /// `root` for the Dockerfile at the repository root
+    pub name: String,
+    /// Image of the last stage's `FROM`, followed back through stages built
+    /// on earlier ones; None without a `FROM`
+    pub base_image: Option<String>,
+    /// `EXPOSE` ports as written (`8080`, `53/udp`)
+    pub exposed_ports: Vec<String>,
+    /// Sources of `COPY` and `ADD`, relative to the build context; copies
+    /// from other stages are left out
+    pub copy_sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct K8sDeployment {
+    pub file: String,
+    /// Deployment | StatefulSet | DaemonSet
+    pub kind: String,
+    pub name: String,
+    pub namespace: String,
+    pub replicas: Option<i64>,
+    /// Images of the pod's containers and init containers, as written
+    pub images: Vec<String>,
+    /// Labels of the pod template, which Services select on
+    pub pod_labels: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct K8sService {
+    pub file: String,
+    pub name: String,
+    pub namespace: String,
+    /// ClusterIP unless the manifest sets another
+    pub service_type: String,
+    /// `port` or `port:targetPort`
+    pub ports: Vec<String>,
+    pub selector: BTreeMap<String, String>,
+    /// Hosts of the Ingress rules routing to the service; `*` for a rule or
+    /// default backend without a host
+    pub ingress_hosts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InfraAnalysis {
+    pub container_images: Vec<ContainerImage>,
+    pub k8s_deployments: Vec<K8sDeployment>,
+    pub k8s_services: Vec<K8sService>,
+}
+
+impl InfraAnalysis {
+    /// Each workload with the repository images its containers run
+    pub fn runs_image(&self) -> Vec<(&K8sDeployment, &ContainerImage)> {
+        let mut pairs = Vec::new();
+        for deployment in &self.k8s_deployments {
+            for image in &self.container_images {
+                if deployment.images.iter().any(|reference| image_repository_name(reference) == image.name) {
+                    pairs.push((deployment, image));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Each Service with the workloads of its namespace it selects. A
+    /// Service without a selector selects nothing.
+    pub fn selects(&self) -> Vec<(&K8sService, &K8sDeployment)> {
+        let mut pairs = Vec::new();
+        for service in self.k8s_services.iter().filter(|service| !service.selector.is_empty()) {
+            for deployment in &self.k8s_deployments {
+                let matches = service.selector.iter().all(|(key, value)| deployment.pod_labels.get(key) == Some(value));
+                if deployment.namespace == service.namespace && matches {
+                    pairs.push((service, deployment));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Each compose service with the image it builds: the `Dockerfile` in
+    /// its build context
+    pub fn compose_builds<'a>(
+        &'a self,
+        compose_services: &'a [ComposeService],
+    ) -> Vec<(&'a ComposeService, &'a ContainerImage)> {
+        let mut pairs = Vec::new();
+        for service in compose_services {
+            let Some(context) = service.build_context.as_deref().and_then(build_context_dir) else {
+                continue;
+            };
+            let dockerfile = if context.is_empty() { "Dockerfile".to_string() } else { format!("{}/Dockerfile", context) };
+            if let Some(image) = self.container_images.iter().find(|image| image.dockerfile == dockerfile) {
+                pairs.push((service, image));
+            }
+        }
+        pairs
+    }
+}
+
+/// The repository's Dockerfiles and Kubernetes manifests
+pub fn scan_infrastructure(repo_path: &Path, filter: &PathFilter) -> Result<InfraAnalysis> {
+    let mut dockerfiles = Vec::new();
+    let mut manifests = Vec::new();
+    collect_infra_files(repo_path, repo_path, filter, &mut dockerfiles, &mut manifests)?;
+    dockerfiles.sort();
+    manifests.sort();
+
+    let mut analysis = InfraAnalysis::default();
+    for path in dockerfiles {
+        let Ok(content) = fs::read_to_string(repo_path.join(&path)) else {
+            continue;
+        };
+        analysis.container_images.push(parse_dockerfile(&path, &content));
+    }
+    let mut sources = Vec::new();
+    for path in manifests {
+        let Ok(content) = fs::read_to_string(repo_path.join(&path)) else {
+            continue;
+        };
+        sources.push((path, content));
+    }
+    let (deployments, services) = parse_manifests(&sources);
+    analysis.k8s_deployments = deployments;
+    analysis.k8s_services = services;
+    Ok(analysis)
+}
+
+fn collect_infra_files(
+    root: &Path,
+    dir: &Path,
+    filter: &PathFilter,
+    dockerfiles: &mut Vec<String>,
+    manifests: &mut Vec<String>,
+) -> Result<()> {
+    if dir.join("Chart.yaml").is_file() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir).context("Failed to read directory")? {
+        let entry = entry.context("Failed to read directory entry")?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let file_type = entry.file_type().context("Failed to read file type")?;
+
+        if file_type.is_dir() {
+            if !name.starts_with('.') && !IGNORED_DIRS.contains(&name.as_str()) {
+                collect_infra_files(root, &path, filter, dockerfiles, manifests)?;
+            }
+            continue;
+        }
+        if !file_type.is_file() {
+            continue;
+        }
+
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+        if !filter.matches(&relative) {
+            continue;
+        }
+        if is_dockerfile(&name) {
+            dockerfiles.push(relative);
+        } else if is_manifest(&relative) {
+            manifests.push(relative);
+        }
+    }
+    Ok(())
+}
+
+fn is_dockerfile(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    !lower.ends_with(".dockerignore")
+        && (lower == "dockerfile" || lower.starts_with("dockerfile.") || lower.ends_with(".dockerfile"))
+}
+
+fn is_manifest(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    let directories = parent_dir(&lower);
+    (lower.ends_with(".yaml") || lower.ends_with(".yml"))
+        && directories.split('/').any(|dir| MANIFEST_DIRS.contains(&dir))
+}
+
+/// Name of the image a Dockerfile builds, see `ContainerImage::name`
+fn image_name(dockerfile: &str) -> String {
+    let file = dockerfile.rsplit('/').next().unwrap_or(dockerfile);
+    let lower = file.to_lowercase();
+    if lower.starts_with("dockerfile.") {
+        return file["dockerfile.".len()..].to_string();
+    }
+    if lower.ends_with(".dockerfile") {
+        return file[..file.len() - ".dockerfile".len()].to_string();
+    }
+    match parent_dir(dockerfile).rsplit('/').next() {
+        Some(dir) if !dir.is_empty() => dir.to_string(),
+        _ => "root".to_string(),
+    }
+}
+
+/// Repository name of an image reference: `api` for
+/// `ghcr.io/acme/api:1.4` or `localhost:5000/api@sha256:...`
+pub fn image_repository_name(reference: &str) -> &str {
+    let reference = reference.split('@').next().unwrap_or(reference);
+    let last = reference.rsplit('/').next().unwrap_or(reference);
+    last.split(':').next().unwrap_or(last)
+}
+
+/// Directory of a compose build context, `""` for the repository root;
+/// None for contexts outside the repository or remote ones
+fn build_context_dir(context: &str) -> Option<String> {
+    if context.split(['/', '\\']).all(|segment| segment.is_empty() || segment == ".") {
+        return Some(String::new());
+    }
+    if context.contains("://") {
+        return None;
+    }
+    normalize_repo_path(context).ok()
+}
+
+pub fn parse_dockerfile(path: &str, content: &str) -> ContainerImage {
+    // (image, stage name)
+    let mut stages: Vec<(String, Option<String>)> = Vec::new();
+    let mut exposed_ports: Vec<String> = Vec::new();
+    let mut copy_sources: Vec<String> = Vec::new();
+
+    for instruction in instructions(content) {
+        let (keyword, args) = instruction.split_once(char::is_whitespace).unwrap_or((&instruction, ""));
+        match keyword.to_uppercase().as_str() {
+            "FROM" => {
+                let words: Vec<&str> = args.split_whitespace().filter(|word| !word.starts_with("--")).collect();
+                if let Some(image) = words.first() {
+                    let stage = match words.as_slice() {
+                        [_, as_keyword, name, ..] if as_keyword.eq_ignore_ascii_case("as") => Some(name.to_string()),
+                        _ => None,
+                    };
+                    stages.push((image.to_string(), stage));
+                }
+            }
+            "EXPOSE" => {
+                for port in args.split_whitespace() {
+                    if !exposed_ports.iter().any(|p| p == port) {
+                        exposed_ports.push(port.to_string());
+                    }
+                }
+            }
+            "COPY" | "ADD" => {
+                for source in copy_args(args) {
+                    if !copy_sources.contains(&source) {
+                        copy_sources.push(source);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // `FROM build` ships what an earlier stage built on
+    let mut base_image = stages.last().map(|(image, _)| image.clone());
+    let mut remaining = stages.len().saturating_sub(1);
+    while let Some(image) = base_image.clone() {
+        let earlier = stages[..remaining]
+            .iter()
+            .rposition(|(_, stage)| stage.as_deref().is_some_and(|stage| stage.eq_ignore_ascii_case(&image)));
+        let Some(index) = earlier else {
+            break;
+        };
+        base_image = Some(stages[index].0.clone());
+        remaining = index;
+    }
+
+    ContainerImage {
+        dockerfile: path.to_string(),
+        name: image_name(path),
+        base_image,
+        exposed_ports,
+        copy_sources,
+    }
+}
+
+/// Instructions with continuation lines joined, comments and blank lines
+/// dropped
+fn instructions(content: &str) -> Vec<String> {
+    let mut instructions = Vec::new();
+    let mut current = String::new();
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') || (trimmed.is_empty() && current.is_empty()) {
+            continue;
+        }
+        match trimmed.strip_suffix('\\') {
+            Some(part) => {
+                current.push_str(part.trim_end());
+                current.push(' ');
+            }
+            None => {
+                current.push_str(trimmed);
+                instructions.push(std::mem::take(&mut current).trim().to_string());
+            }
+        }
+    }
+    if !current.trim().is_empty() {
+        instructions.push(current.trim().to_string());
+    }
+    instructions
+}
+
+/// Sources of a `COPY` or `ADD`: every argument but the destination, in
+/// either the shell or the JSON form. None for copies between stages.
+fn copy_args(args: &str) -> Vec<String> {
+    let words: Vec<String> = if args.trim_start().starts_with('[') {
+        serde_json::from_str(args.trim()).unwrap_or_default()
+    } else {
+        args.split_whitespace().map(str::to_string).collect()
+    };
+    if words.iter().any(|word| word.starts_with("--from")) {
+        return Vec::new();
+    }
+    let words: Vec<String> = words.into_iter().filter(|word| !word.starts_with("--")).collect();
+    match words.split_last() {
+        Some((_, sources)) => sources.to_vec(),
+        None => Vec::new(),
+    }
+}
+
+/// Workloads and Services of the manifests in `sources` (path, content),
+/// with the hosts of the Ingresses routing to each Service
+pub fn parse_manifests(sources: &[(String, String)]) -> (Vec<K8sDeployment>, Vec<K8sService>) {
+    let mut deployments = Vec::new();
+    let mut services = Vec::new();
+    // (namespace, service, host)
+    let mut ingress_routes: Vec<(String, String, String)> = Vec::new();
+
+    for (path, content) in sources {
+        let mut documents: Vec<Value> =
+            yaml_documents(content).iter().filter_map(|document| serde_yaml::from_str(document).ok()).collect();
+        // `kind: List` wraps its documents in `items`
+        while let Some(document) = documents.pop() {
+            let kind = document.get("kind").and_then(Value::as_str).unwrap_or_default();
+            let name = document.get("metadata").and_then(|m| m.get("name")).and_then(scalar);
+            let namespace = document
+                .get("metadata")
+                .and_then(|m| m.get("namespace"))
+                .and_then(scalar)
+                .unwrap_or_else(|| "default".to_string());
+            let spec = document.get("spec");
+            match (kind, name) {
+                ("List", _) => {
+                    if let Some(Value::Sequence(items)) = document.get("items") {
+                        documents.extend(items.iter().cloned());
+                    }
+                }
+                (kind, Some(name)) if WORKLOAD_KINDS.contains(&kind) => {
+                    deployments.push(workload(path, kind, name, namespace, spec));
+                }
+                ("Service", Some(name)) => services.push(service(path, name, namespace, spec)),
+                ("Ingress", Some(_)) => {
+                    for (service, host) in ingress_backends(spec) {
+                        ingress_routes.push((namespace.clone(), service, host));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for (namespace, name, host) in ingress_routes {
+        let target = services.iter_mut().find(|service| service.namespace == namespace && service.name == name);
+        if let Some(service) = target {
+            if !service.ingress_hosts.contains(&host) {
+                service.ingress_hosts.push(host);
+            }
+        }
+    }
+    deployments.sort_by(|a, b| (&a.namespace, &a.name).cmp(&(&b.namespace, &b.name)));
+    services.sort_by(|a, b| (&a.namespace, &a.name).cmp(&(&b.namespace, &b.name)));
+    (deployments, services)
+}
+
+/// The documents of a YAML stream, split at `---` lines so one that
+/// doesn't parse leaves the others readable. serde_yaml's own multi-document
+/// reader stops at the first error.
+fn yaml_documents(content: &str) -> Vec<String> {
+    let mut documents = vec![String::new()];
+    for line in content.lines() {
+        match line.strip_prefix("---") {
+            Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => {
+                documents.push(format!("{}\n", rest.trim_start()));
+            }
+            _ if line == "..." => documents.push(String::new()),
+            _ => {
+                let document = documents.last_mut().expect("starts with one document");
+                document.push_str(line);
+                document.push('\n');
+            }
+        }
+    }
+    documents.retain(|document| !document.trim().is_empty());
+    documents
+}
+
+fn string_map(value: Option<&Value>) -> BTreeMap<String, String> {
+    match value {
+        Some(Value::Mapping(map)) => {
+            map.iter().filter_map(|(key, value)| Some((scalar(key)?, scalar(value)?))).collect()
+        }
+        _ => BTreeMap::new(),
+    }
+}
+
+fn workload(path: &str, kind: &str, name: String, namespace: String, spec: Option<&Value>) -> K8sDeployment {
+    let template = spec.and_then(|spec| spec.get("template"));
+    let pod = template.and_then(|template| template.get("spec"));
+    let mut images = Vec::new();
+    for key in ["initContainers", "containers"] {
+        if let Some(Value::Sequence(containers)) = pod.and_then(|pod| pod.get(key)) {
+            images.extend(containers.iter().filter_map(|container| container.get("image").and_then(scalar)));
+        }
+    }
+    K8sDeployment {
+        file: path.to_string(),
+        kind: kind.to_string(),
+        name,
+        namespace,
+        replicas: spec.and_then(|spec| spec.get("replicas")).and_then(Value::as_i64),
+        images,
+        pod_labels: string_map(template.and_then(|t| t.get("metadata")).and_then(|m| m.get("labels"))),
+    }
+}
+
+fn service(path: &str, name: String, namespace: String, spec: Option<&Value>) -> K8sService {
+    let ports = match spec.and_then(|spec| spec.get("ports")) {
+        Some(Value::Sequence(ports)) => ports
+            .iter()
+            .filter_map(|port| {
+                let number = port.get("port").and_then(scalar)?;
+                Some(match port.get("targetPort").and_then(scalar) {
+                    Some(target) => format!("{}:{}", number, target),
+                    None => number,
+                })
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    K8sService {
+        file: path.to_string(),
+        name,
+        namespace,
+        service_type: spec
+            .and_then(|spec| spec.get("type"))
+            .and_then(scalar)
+            .unwrap_or_else(|| "ClusterIP".to_string()),
+        ports,
+        selector: string_map(spec.and_then(|spec| spec.get("selector"))),
+        ingress_hosts: Vec::new(),
+    }
+}
+
+/// (service, host) pairs of an Ingress spec, in the `networking.k8s.io/v1`
+/// form (`backend.service.name`) or the older one (`backend.serviceName`)
+fn ingress_backends(spec: Option<&Value>) -> Vec<(String, String)> {
+    let backend_service = |backend: &Value| {
+        backend
+            .get("service")
+            .and_then(|service| service.get("name"))
+            .or_else(|| backend.get("serviceName"))
+            .and_then(scalar)
+    };
+    let Some(spec) = spec else {
+        return Vec::new();
+    };
+
+    let mut backends = Vec::new();
+    if let Some(service) = spec.get("defaultBackend").or_else(|| spec.get("backend")).and_then(backend_service) {
+        backends.push((service, "*".to_string()));
+    }
+    if let Some(Value::Sequence(rules)) = spec.get("rules") {
+        for rule in rules {
+            let host = rule.get("host").and_then(scalar).unwrap_or_else(|| "*".to_string());
+            let Some(Value::Sequence(paths)) = rule.get("http").and_then(|http| http.get("paths")) else {
+                continue;
+            };
+            for path in paths {
+                if let Some(service) = path.get("backend").and_then(backend_service) {
+                    backends.push((service, host.clone()));
+                }
+            }
+        }
+    }
+    backends
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCKERFILE: &str = r#"# syntax=docker/dockerfile:1
+FROM --platform=$BUILDPLATFORM golang:1.22 AS build
+WORKDIR /src
+COPY go.mod go.sum ./
+COPY cmd/ cmd/
+COPY internal \
+     internal/
+RUN go build -o /out/api ./cmd/api
+
+FROM build AS test
+RUN go test ./...
+
+FROM gcr.io/distroless/base-debian12
+COPY --from=build /out/api /api
+ADD ["configs/prod.yaml", "/etc/api/"]
+EXPOSE 8080 9090/udp
+EXPOSE 8080
+ENTRYPOINT ["/api"]
+"#;
+
+    const MANIFESTS: &str = r#"
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: api
+  namespace: shop
+spec:
+  replicas: 3
+  selector:
+    matchLabels:
+      app: api
+  template:
+    metadata:
+      labels:
+        app: api
+        tier: backend
+    spec:
+      initContainers:
+        - name: migrate
+          image: ghcr.io/acme/migrations:2.0
+      containers:
+        - name: api
+          image: ghcr.io/acme/api:1.4.2
+        - name: proxy
+          image: envoyproxy/envoy@sha256:abc123
+---
+this: [is not: valid yaml
+---
+apiVersion: v1
+kind: Service
+metadata:
+  name: api
+  namespace: shop
+spec:
+  selector:
+    app: api
+  ports:
+    - port: 80
+      targetPort: 8080
+    - port: 9090
+---
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: api-config
+"#;
+
+    #[test]
+    fn test_parse_dockerfile() {
+        let image = parse_dockerfile("services/api/Dockerfile", DOCKERFILE);
+        assert_eq!(image.name, "api");
+        assert_eq!(image.base_image.as_deref(), Some("gcr.io/distroless/base-debian12"));
+        assert_eq!(image.exposed_ports, vec!["8080", "9090/udp"]);
+        assert_eq!(image.copy_sources, vec!["go.mod", "go.sum", "cmd/", "internal", "configs/prod.yaml"]);
+
+        // The shipped stage builds on an earlier one
+        let staged = "FROM node:20 AS deps\nRUN npm ci\nFROM deps AS build\nRUN npm run build\nFROM build\n";
+        assert_eq!(parse_dockerfile("Dockerfile", staged).base_image.as_deref(), Some("node:20"));
+        assert_eq!(parse_dockerfile("Dockerfile", "# nothing yet\n").base_image, None);
+    }
+
+    #[test]
+    fn test_image_names() {
+        assert_eq!(image_name("services/api/Dockerfile"), "api");
+        assert_eq!(image_name("docker/Dockerfile.worker"), "worker");
+        assert_eq!(image_name("docker/web.Dockerfile"), "web");
+        assert_eq!(image_name("Dockerfile"), "root");
+
+        assert_eq!(image_repository_name("ghcr.io/acme/api:1.4.2"), "api");
+        assert_eq!(image_repository_name("localhost:5000/api"), "api");
+        assert_eq!(image_repository_name("envoyproxy/envoy@sha256:abc123"), "envoy");
+        assert_eq!(image_repository_name("redis"), "redis");
+
+        assert!(is_dockerfile("Dockerfile") && is_dockerfile("Dockerfile.dev") && is_dockerfile("api.dockerfile"));
+        assert!(!is_dockerfile("Dockerfile.dockerignore") && !is_dockerfile("dockerfile_test.go"));
+    }
+
+    #[test]
+    fn test_deployment_and_service_pair() {
+        let ingress = r#"
+apiVersion: networking.k8s.io/v1
+kind: Ingress
+metadata:
+  name: shop
+  namespace: shop
+spec:
+  rules:
+    - host: shop.example.com
+      http:
+        paths:
+          - path: /api
+            pathType: Prefix
+            backend:
+              service:
+                name: api
+                port:
+                  number: 80
+"#;
+        let sources = vec![
+            ("deploy/k8s/api.yaml".to_string(), MANIFESTS.to_string()),
+            ("deploy/k8s/ingress.yaml".to_string(), ingress.to_string()),
+        ];
+        let (deployments, services) = parse_manifests(&sources);
+
+        assert_eq!(deployments.len(), 1);
+        let api = &deployments[0];
+        assert_eq!((api.kind.as_str(), api.name.as_str(), api.namespace.as_str()), ("Deployment", "api", "shop"));
+        assert_eq!(api.replicas, Some(3));
+        assert_eq!(
+            api.images,
+            vec!["ghcr.io/acme/migrations:2.0", "ghcr.io/acme/api:1.4.2", "envoyproxy/envoy@sha256:abc123"]
+        );
+        assert_eq!(api.pod_labels.get("tier").map(String::as_str), Some("backend"));
+
+        assert_eq!(services.len(), 1);
+        let service = &services[0];
+        assert_eq!(service.service_type, "ClusterIP");
+        assert_eq!(service.ports, vec!["80:8080", "9090"]);
+        assert_eq!(service.ingress_hosts, vec!["shop.example.com"]);
+
+        let analysis = InfraAnalysis {
+            container_images: vec![
+                parse_dockerfile("services/api/Dockerfile", DOCKERFILE),
+                parse_dockerfile("services/web/Dockerfile", "FROM nginx:1.27\n"),
+            ],
+            k8s_deployments: deployments,
+            k8s_services: services,
+        };
+        let runs: Vec<(&str, &str)> =
+            analysis.runs_image().iter().map(|(d, i)| (d.name.as_str(), i.dockerfile.as_str())).collect();
+        assert_eq!(runs, vec![("api", "services/api/Dockerfile")]);
+        let selects: Vec<(&str, &str)> =
+            analysis.selects().iter().map(|(s, d)| (s.name.as_str(), d.name.as_str())).collect();
+        assert_eq!(selects, vec![("api", "api")]);
+
+        // Selectors stay within their namespace and need every label
+        let mut elsewhere = analysis.clone();
+        elsewhere.k8s_services[0].namespace = "default".to_string();
+        assert!(elsewhere.selects().is_empty());
+        elsewhere.k8s_services[0].namespace = "shop".to_string();
+        elsewhere.k8s_services[0].selector.insert("tier".to_string(), "frontend".to_string());
+        assert!(elsewhere.selects().is_empty());
+    }
+
+    #[test]
+    fn test_compose_builds_match_dockerfile_directory() {
+        let analysis = InfraAnalysis {
+            container_images: vec![
+                parse_dockerfile("Dockerfile", "FROM python:3.12\n"),
+                parse_dockerfile("services/api/Dockerfile", "FROM golang:1.22\n"),
+                parse_dockerfile("services/api/Dockerfile.worker", "FROM golang:1.22\n"),
+            ],
+            ..Default::default()
+        };
+        let compose = |name: &str, context: Option<&str>| ComposeService {
+            name: name.to_string(),
+            build_context: context.map(str::to_string),
+            ..Default::default()
+        };
+        let services = vec![
+            compose("app", Some(".")),
+            compose("api", Some("./services/api/")),
+            compose("db", None),
+            compose("remote", Some("https://github.com/acme/tool.git")),
+            compose("outside", Some("../other")),
+        ];
+
+        let builds: Vec<(&str, &str)> =
+            analysis.compose_builds(&services).iter().map(|(s, i)| (s.name.as_str(), i.dockerfile.as_str())).collect();
+        assert_eq!(builds, vec![("app", "Dockerfile"), ("api", "services/api/Dockerfile")]);
+    }
+
+    #[test]
+    fn test_scan_skips_helm_charts_and_unrelated_yaml() {
+        let root = std::env::temp_dir().join(format!("infra-scan-{}", uuid::Uuid::new_v4()));
+        for dir in ["services/api", "k8s", "charts/api/templates", ".github/workflows", "config"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(root.join("services/api/Dockerfile"), DOCKERFILE).unwrap();
+        fs::write(root.join("k8s/api.yaml"), MANIFESTS).unwrap();
+        fs::write(root.join("charts/api/Chart.yaml"), "name: api\n").unwrap();
+        fs::write(root.join("charts/api/templates/deployment.yaml"), MANIFESTS).unwrap();
+        // Manifests outside the manifest directories aren't read
+        fs::write(root.join("config/api.yaml"), MANIFESTS).unwrap();
+
+        let analysis = scan_infrastructure(&root, &PathFilter::default()).unwrap();
+        let images: Vec<&str> = analysis.container_images.iter().map(|i| i.dockerfile.as_str()).collect();
+        assert_eq!(images, vec!["services/api/Dockerfile"]);
+        let files: Vec<&str> = analysis.k8s_deployments.iter().map(|d| d.file.as_str()).collect();
+        assert_eq!(files, vec!["k8s/api.yaml"]);
+        assert_eq!(analysis.k8s_services.len(), 1);
+
+        let filter = PathFilter { exclude: vec!["k8s".to_string()], ..Default::default() };
+        assert!(scan_infrastructure(&root, &filter).unwrap().k8s_deployments.is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
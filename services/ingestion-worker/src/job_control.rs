@@ -0,0 +1,209 @@
+//! Job Control
+//!
+//! Pause, resume and cancel for running jobs. The API writes `pause`,
+//! `resume` or `cancel` to `job_control:{job_id}` in Redis; a poller copies
+//! the value into the job's `CancellationToken`, which the pipeline checks
+//! between stages and the storage layer between Neo4j batches.
+
+use crate::worker_state::WorkerStage;
+use anyhow::Result;
+use redis::AsyncCommands;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+const RUNNING: u8 = 0;
+const PAUSED: u8 = 1;
+const CANCELLED: u8 = 2;
+
+/// How often a paused checkpoint looks at the token again
+const PAUSE_POLL: Duration = Duration::from_millis(250);
+
+pub fn control_key(job_id: &str) -> String {
+    format!("job_control:{}", job_id)
+}
+
+/// Shared run/pause/cancel flag. Clones share the same state; cancelling is
+/// final, so a later `resume` or `pause` doesn't undo it.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<AtomicU8>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.state.store(CANCELLED, Ordering::SeqCst);
+    }
+
+    pub fn pause(&self) {
+        let _ = self.state.compare_exchange(RUNNING, PAUSED, Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        let _ = self.state.compare_exchange(PAUSED, RUNNING, Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == CANCELLED
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == PAUSED
+    }
+
+    /// Wait while paused, then fail with `JobCancelled` if the job was
+    /// cancelled. `stage` is reported as the stage the job reached.
+    pub async fn checkpoint(&self, stage: WorkerStage) -> Result<()> {
+        if self.is_paused() {
+            info!("⏸️  Paused during {}", stage.as_str());
+        }
+        loop {
+            match self.state.load(Ordering::SeqCst) {
+                CANCELLED => return Err(JobCancelled { stage }.into()),
+                PAUSED => tokio::time::sleep(PAUSE_POLL).await,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Apply a value read from the control key; unknown values are ignored
+    pub fn apply(&self, command: &str) -> bool {
+        match command.trim().to_ascii_lowercase().as_str() {
+            "cancel" => self.cancel(),
+            "pause" => self.pause(),
+            "resume" => self.resume(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Error returned once a cancelled job hits a checkpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobCancelled {
+    pub stage: WorkerStage,
+}
+
+impl std::fmt::Display for JobCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Job cancelled during {}", self.stage.as_str())
+    }
+}
+
+impl std::error::Error for JobCancelled {}
+
+/// Poll the job's control key every `interval` and apply it to `token`
+/// until the returned task is aborted or the job is cancelled
+pub fn spawn_control_poller(
+    redis_client: redis::Client,
+    job_id: String,
+    token: CancellationToken,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let key = control_key(&job_id);
+        let mut last: Option<String> = None;
+        loop {
+            tokio::time::sleep(interval).await;
+            let command: Option<String> = match redis_client.get_async_connection().await {
+                Ok(mut conn) => match conn.get(&key).await {
+                    Ok(value) => value,
+                    Err(e) => {
+                        warn!("⚠️  Failed to read {}: {:?}", key, e);
+                        continue;
+                    }
+                },
+                Err(e) => {
+                    warn!("⚠️  Failed to get Redis connection for {}: {:?}", key, e);
+                    continue;
+                }
+            };
+
+            if let Some(command) = command.filter(|c| last.as_ref() != Some(c)) {
+                if token.apply(&command) {
+                    info!("🎛️  Job {} received {}", job_id, command.trim());
+                } else {
+                    warn!("⚠️  Ignoring unknown command {:?} in {}", command, key);
+                }
+                last = Some(command);
+            }
+            if token.is_cancelled() {
+                break;
+            }
+        }
+    })
+}
+
+/// Delete the control key once the job is over, so a re-queued job with the
+/// same id doesn't pick up a stale command
+pub async fn clear_control(redis_client: &redis::Client, job_id: &str) {
+    let key = control_key(job_id);
+    let result: Result<()> = async {
+        let mut conn = redis_client.get_async_connection().await?;
+        conn.del::<_, ()>(&key).await?;
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        warn!("⚠️  Failed to clear {}: {:?}", key, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_checkpoint_fails_once_cancelled() {
+        let token = CancellationToken::new();
+        assert!(token.checkpoint(WorkerStage::Cloning).await.is_ok());
+
+        token.clone().cancel();
+        let err = token.checkpoint(WorkerStage::Parsing).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<JobCancelled>(),
+            Some(&JobCancelled { stage: WorkerStage::Parsing })
+        );
+        assert_eq!(err.to_string(), "Job cancelled during parsing");
+
+        // Cancelling is final
+        token.resume();
+        assert!(token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_paused_checkpoint_waits_for_resume() {
+        let token = CancellationToken::new();
+        assert!(token.apply("pause"));
+        assert!(token.is_paused());
+
+        let waiting = tokio::spawn({
+            let token = token.clone();
+            async move { token.checkpoint(WorkerStage::Analyzing).await.is_ok() }
+        });
+        tokio::time::sleep(PAUSE_POLL * 2).await;
+        assert!(!waiting.is_finished());
+
+        assert!(token.apply(" RESUME\n"));
+        assert!(waiting.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_while_paused_stops_the_job() {
+        let token = CancellationToken::new();
+        token.pause();
+        let waiting = tokio::spawn({
+            let token = token.clone();
+            async move { token.checkpoint(WorkerStage::Storing).await }
+        });
+        assert!(token.apply("cancel"));
+        let err = waiting.await.unwrap().unwrap_err();
+        assert_eq!(err.downcast_ref::<JobCancelled>().map(|c| c.stage), Some(WorkerStage::Storing));
+        assert!(!token.apply("restart"));
+    }
+}
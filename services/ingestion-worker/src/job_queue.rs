@@ -0,0 +1,513 @@
+//! Job Queue
+//!
+//! Reliable consumption of the job queues. Claiming a job moves it with
+//! `RPOPLPUSH` into the worker's own processing list for that queue,
+//! `analysis_processing:{worker_id}` for `analysis_queue` and
+//! `analysis_processing:{worker_id}@{queue}` for the others, and it is only
+//! removed from there once its final status reached the API. A worker that
+//! dies mid-job leaves the entry behind; the next worker to start finds the
+//! list, sees the owner's heartbeat has expired and puts the job back on the
+//! queue it came from with its `attempts` count bumped.
+//!
+//! Queues are polled in priority order, `analysis_queue:high`,
+//! `analysis_queue` and `analysis_queue:low` unless configured otherwise.
+//! So a steady stream of higher-priority jobs can't starve the last queue,
+//! it is polled first after a run of claims from the others, see
+//! `QueueSelector`.
+//!
+//! Jobs are claimed over one long-lived connection. When Redis restarts the
+//! connection breaks for good, so a claim failing with a connection error
+//! drops it and the next claim opens a new one.
+
+use crate::worker_state::heartbeat_key;
+use anyhow::{Context, Result};
+use redis::AsyncCommands;
+use tracing::{info, warn};
+
+pub const QUEUE_KEY: &str = "analysis_queue";
+const PROCESSING_PREFIX: &str = "analysis_processing:";
+
+/// Queues polled when `QUEUE_NAMES` is unset, highest priority first
+pub const DEFAULT_QUEUES: &[&str] = &["analysis_queue:high", QUEUE_KEY, "analysis_queue:low"];
+
+/// Claims from the other queues after which the last one is polled first
+pub const DEFAULT_QUEUE_FAIRNESS: usize = 10;
+
+/// Processing lists listed per SCAN call while looking for stale jobs
+const SCAN_COUNT: usize = 100;
+
+/// Moves the head of a processing list (KEYS[1]) to the tail of its queue
+/// (KEYS[2]) as ARGV[2], but only while the head is still ARGV[1], so a
+/// worker recovering the same list at the same time can't re-queue it too.
+/// The bumped entry is built in Rust, since cjson would rewrite the job's
+/// JSON (empty arrays come back as objects).
+const REQUEUE_HEAD: &str = r#"
+if redis.call('LINDEX', KEYS[1], 0) == ARGV[1] then
+    redis.call('LPOP', KEYS[1])
+    return redis.call('RPUSH', KEYS[2], ARGV[2])
+end
+return 0
+"#;
+
+/// Separates the queue from the worker id in a processing list's key, so
+/// worker ids can't contain it
+pub const QUEUE_SEPARATOR: char = '@';
+
+/// This worker's processing list for jobs claimed from `queue`
+pub fn processing_key(worker_id: &str, queue: &str) -> String {
+    if queue == QUEUE_KEY {
+        format!("{}{}", PROCESSING_PREFIX, worker_id)
+    } else {
+        format!("{}{}{}{}", PROCESSING_PREFIX, worker_id, QUEUE_SEPARATOR, queue)
+    }
+}
+
+/// The worker and queue of a processing list's key
+fn parse_processing_key(key: &str) -> (&str, &str) {
+    let rest = key.trim_start_matches(PROCESSING_PREFIX);
+    rest.split_once(QUEUE_SEPARATOR).unwrap_or((rest, QUEUE_KEY))
+}
+
+/// The order queues are polled in. Claims from any queue but the last
+/// count towards `fairness`; once that many were made in a row, the last
+/// queue is polled first for one claim. A fairness of 0 never reorders.
+#[derive(Debug, Clone)]
+pub struct QueueSelector {
+    queues: Vec<String>,
+    fairness: usize,
+    /// Claims in a row from the queues ahead of the last one
+    streak: usize,
+}
+
+impl QueueSelector {
+    pub fn new(queues: Vec<String>, fairness: usize) -> Self {
+        Self { queues, fairness, streak: 0 }
+    }
+
+    fn favors_last(&self) -> bool {
+        self.fairness > 0 && self.streak >= self.fairness && self.queues.len() > 1
+    }
+
+    /// Queues to poll for the next claim, in order
+    pub fn order(&self) -> Vec<&str> {
+        let mut order: Vec<&str> = self.queues.iter().map(String::as_str).collect();
+        if self.favors_last() {
+            order.rotate_right(1);
+        }
+        order
+    }
+
+    /// Count the outcome of a claim: the queue it came from, or None when
+    /// every queue was empty. The streak ends whenever the last queue was
+    /// polled first, even if it was empty, since nothing waited there.
+    pub fn record(&mut self, claimed: Option<&str>) {
+        let last = self.queues.last().map(String::as_str);
+        if claimed.is_none() || claimed == last || self.favors_last() {
+            self.streak = 0;
+        } else {
+            self.streak += 1;
+        }
+    }
+}
+
+/// A job moved into this worker's processing list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClaimedJob {
+    pub queue: String,
+    /// The raw JSON, which `ack_job` needs verbatim
+    pub entry: String,
+}
+
+/// Move the next job, from the first queue in the selector's order that has
+/// one, into this worker's processing list for that queue
+pub async fn claim_job<C>(conn: &mut C, worker_id: &str, selector: &mut QueueSelector) -> Result<Option<ClaimedJob>>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let mut claimed = None;
+    for queue in selector.order() {
+        let entry: Option<String> = conn
+            .rpoplpush(queue, processing_key(worker_id, queue))
+            .await
+            .with_context(|| format!("Failed to claim job from Redis queue {}", queue))?;
+        if let Some(entry) = entry {
+            claimed = Some(ClaimedJob { queue: queue.to_string(), entry });
+            break;
+        }
+    }
+    selector.record(claimed.as_ref().map(|job| job.queue.as_str()));
+    Ok(claimed)
+}
+
+/// Drop a finished job from this worker's processing list for `queue`
+pub async fn ack_job<C>(conn: &mut C, worker_id: &str, queue: &str, entry: &str) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    conn.lrem::<_, _, ()>(processing_key(worker_id, queue), 1, entry)
+        .await
+        .context("Failed to remove job from processing list")
+}
+
+/// Every processing list, found with SCAN so Redis isn't blocked the way
+/// KEYS would block it. SCAN may return a key more than once.
+async fn processing_keys<C>(conn: &mut C) -> Result<Vec<String>>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let mut keys = Vec::new();
+    let mut cursor = 0u64;
+    loop {
+        let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(format!("{}*", PROCESSING_PREFIX))
+            .arg("COUNT")
+            .arg(SCAN_COUNT)
+            .query_async(conn)
+            .await
+            .context("Failed to list processing lists")?;
+        keys.extend(batch);
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    keys.sort();
+    keys.dedup();
+    Ok(keys)
+}
+
+/// Put the jobs of workers whose heartbeat has expired back on the queues
+/// they were claimed from.
+/// `worker_id`'s own list is always recovered, since a starting worker has
+/// nothing in flight. Each entry is popped, bumped and pushed in one script
+/// that only runs while it is still the list's head, so two workers
+/// starting together never re-queue the same job twice and a crash never
+/// loses one in between. Returns how many jobs were re-queued.
+pub async fn requeue_stale_jobs<C>(conn: &mut C, worker_id: &str) -> Result<usize>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let script = redis::Script::new(REQUEUE_HEAD);
+    let mut requeued = 0;
+    for key in processing_keys(conn).await? {
+        let (owner, queue) = parse_processing_key(&key);
+        if owner != worker_id {
+            let alive: bool = conn
+                .exists(heartbeat_key(owner))
+                .await
+                .context("Failed to read worker heartbeat")?;
+            if alive {
+                continue;
+            }
+        }
+
+        // Newest entries are at the head; taking them first and pushing onto
+        // the popping end leaves the oldest job to run first
+        loop {
+            let head: Option<String> = conn
+                .lindex(&key, 0)
+                .await
+                .context("Failed to read processing list")?;
+            let Some(entry) = head else { break };
+            let pushed: i64 = script
+                .key(&key)
+                .key(queue)
+                .arg(&entry)
+                .arg(bump_attempts(&entry))
+                .invoke_async(conn)
+                .await
+                .context("Failed to re-queue job")?;
+            // 0 when another worker took the entry first
+            if pushed > 0 {
+                requeued += 1;
+            }
+        }
+    }
+    Ok(requeued)
+}
+
+/// Opens the connection jobs are claimed over
+pub trait Connector {
+    type Connection: redis::aio::ConnectionLike + Send;
+
+    async fn connect(&self) -> Result<Self::Connection>;
+}
+
+/// The worker's queue connection, reopened after Redis drops it
+pub struct QueueConnection<K: Connector> {
+    connector: K,
+    conn: Option<K::Connection>,
+}
+
+impl<K: Connector> QueueConnection<K> {
+    pub fn new(connector: K, conn: K::Connection) -> Self {
+        Self { connector, conn: Some(conn) }
+    }
+
+    /// The open connection, or a new one if the last one was lost
+    pub async fn connection(&mut self) -> Result<&mut K::Connection> {
+        if self.conn.is_none() {
+            info!("🔄 Reconnecting to Redis...");
+            let conn = self.connector.connect().await.context("Failed to reconnect to Redis")?;
+            info!("✅ Reconnected to Redis");
+            self.conn = Some(conn);
+        }
+        Ok(self.conn.as_mut().expect("connection was just opened"))
+    }
+
+    /// Pass `result` through, dropping the connection if it failed because
+    /// the connection is gone
+    pub fn drop_if_broken<T>(&mut self, result: Result<T>) -> Result<T> {
+        if let Err(e) = &result {
+            if self.conn.is_some() && is_connection_error(e) {
+                warn!("⚠️  Lost the Redis connection, reconnecting on the next poll");
+                self.conn = None;
+            }
+        }
+        result
+    }
+}
+
+/// Whether `error` means the connection itself failed (dropped, refused,
+/// timed out or broken at the socket), rather than a command being rejected
+pub fn is_connection_error(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| match cause.downcast_ref::<redis::RedisError>() {
+        Some(e) => e.is_connection_dropped() || e.is_connection_refusal() || e.is_io_error() || e.is_timeout(),
+        None => cause.is::<std::io::Error>(),
+    })
+}
+
+/// The job JSON with `attempts` incremented; anything that isn't a JSON
+/// object goes back unchanged
+fn bump_attempts(entry: &str) -> String {
+    let Ok(serde_json::Value::Object(mut job)) = serde_json::from_str::<serde_json::Value>(entry) else {
+        return entry.to_string();
+    };
+    let attempts = job.get("attempts").and_then(|a| a.as_u64()).unwrap_or(0);
+    job.insert("attempts".to_string(), serde_json::json!(attempts + 1));
+    serde_json::Value::Object(job).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis::{Arg, Cmd, Pipeline, RedisFuture, Value};
+    use std::collections::VecDeque;
+
+    /// Records every command and answers with the scripted replies in order
+    #[derive(Default)]
+    struct ScriptedConnection {
+        commands: Vec<Vec<String>>,
+        replies: VecDeque<Value>,
+    }
+
+    impl ScriptedConnection {
+        fn new(replies: Vec<Value>) -> Self {
+            Self { commands: Vec::new(), replies: replies.into() }
+        }
+    }
+
+    impl redis::aio::ConnectionLike for ScriptedConnection {
+        fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+            let args = cmd
+                .args_iter()
+                .filter_map(|arg| match arg {
+                    Arg::Simple(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+                    Arg::Cursor => None,
+                })
+                .collect();
+            self.commands.push(args);
+            let reply = self.replies.pop_front().unwrap_or(Value::Nil);
+            Box::pin(async move { Ok(reply) })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _cmd: &'a Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> RedisFuture<'a, Vec<Value>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    #[test]
+    fn test_connection_errors_are_told_from_command_errors() {
+        let io = |kind| anyhow::Error::from(redis::RedisError::from(std::io::Error::new(kind, "socket")));
+        assert!(is_connection_error(&io(std::io::ErrorKind::BrokenPipe)));
+        assert!(is_connection_error(&io(std::io::ErrorKind::ConnectionRefused)));
+        assert!(is_connection_error(&io(std::io::ErrorKind::TimedOut)));
+        // Wrapped in context, as `claim_job` returns it
+        assert!(is_connection_error(&io(std::io::ErrorKind::ConnectionReset).context("Failed to claim job")));
+
+        let wrong_type = redis::RedisError::from((redis::ErrorKind::TypeError, "WRONGTYPE"));
+        assert!(!is_connection_error(&anyhow::Error::from(wrong_type)));
+        let readonly = redis::RedisError::from((redis::ErrorKind::ReadOnly, "READONLY"));
+        assert!(!is_connection_error(&anyhow::Error::from(readonly)));
+        assert!(!is_connection_error(&anyhow::anyhow!("Failed to deserialize job")));
+    }
+
+    fn data(s: &str) -> Value {
+        Value::Data(s.as_bytes().to_vec())
+    }
+
+    fn default_selector(fairness: usize) -> QueueSelector {
+        QueueSelector::new(DEFAULT_QUEUES.iter().map(|q| q.to_string()).collect(), fairness)
+    }
+
+    #[tokio::test]
+    async fn test_claim_and_ack_use_processing_list() {
+        let entry = r#"{"job_id":"job-1"}"#;
+        // Nothing on the high priority queue
+        let mut conn = ScriptedConnection::new(vec![Value::Nil, data(entry), Value::Int(1)]);
+        let mut selector = default_selector(DEFAULT_QUEUE_FAIRNESS);
+
+        let claimed = claim_job(&mut conn, "w1", &mut selector).await.unwrap().unwrap();
+        assert_eq!(claimed, ClaimedJob { queue: "analysis_queue".to_string(), entry: entry.to_string() });
+        ack_job(&mut conn, "w1", &claimed.queue, entry).await.unwrap();
+        assert_eq!(
+            conn.commands,
+            vec![
+                vec!["RPOPLPUSH", "analysis_queue:high", "analysis_processing:w1@analysis_queue:high"],
+                vec!["RPOPLPUSH", "analysis_queue", "analysis_processing:w1"],
+                vec!["LREM", "analysis_processing:w1", "1", entry],
+            ]
+        );
+
+        let mut conn = ScriptedConnection::new(vec![]);
+        assert_eq!(claim_job(&mut conn, "w1", &mut selector).await.unwrap(), None);
+        assert_eq!(conn.commands.len(), 3);
+    }
+
+    #[test]
+    fn test_selector_polls_last_queue_after_a_streak() {
+        let mut selector = default_selector(2);
+        let normal = ["analysis_queue:high", "analysis_queue", "analysis_queue:low"];
+        let favoring_low = ["analysis_queue:low", "analysis_queue:high", "analysis_queue"];
+
+        selector.record(Some("analysis_queue:high"));
+        assert_eq!(selector.order(), normal);
+        selector.record(Some("analysis_queue"));
+        assert_eq!(selector.order(), favoring_low);
+        // The low queue was polled first, whatever it held
+        selector.record(Some("analysis_queue:high"));
+        assert_eq!(selector.order(), normal);
+
+        // A claim from the last queue, or an empty poll, ends the streak
+        selector.record(Some("analysis_queue:high"));
+        selector.record(Some("analysis_queue:low"));
+        selector.record(Some("analysis_queue:high"));
+        assert_eq!(selector.order(), normal);
+        selector.record(None);
+        selector.record(Some("analysis_queue:high"));
+        assert_eq!(selector.order(), normal);
+
+        let mut unfair = default_selector(0);
+        (0..5).for_each(|_| unfair.record(Some("analysis_queue:high")));
+        assert_eq!(unfair.order(), normal);
+        let mut single = QueueSelector::new(vec![QUEUE_KEY.to_string()], 1);
+        single.record(Some(QUEUE_KEY));
+        assert_eq!(single.order(), [QUEUE_KEY]);
+    }
+
+    #[tokio::test]
+    async fn test_claims_let_low_priority_through_under_load() {
+        // The high queue always has a job; low has one
+        let high = |id: &str| data(&format!(r#"{{"job_id":"{}"}}"#, id));
+        let mut conn = ScriptedConnection::new(vec![
+            high("h1"),
+            high("h2"),
+            data(r#"{"job_id":"l1"}"#),
+            high("h3"),
+        ]);
+        let mut selector = default_selector(2);
+
+        let mut queues = Vec::new();
+        for _ in 0..4 {
+            queues.push(claim_job(&mut conn, "w1", &mut selector).await.unwrap().unwrap().queue);
+        }
+        assert_eq!(queues, ["analysis_queue:high", "analysis_queue:high", "analysis_queue:low", "analysis_queue:high"]);
+        assert_eq!(conn.commands[2], ["RPOPLPUSH", "analysis_queue:low", "analysis_processing:w1@analysis_queue:low"]);
+    }
+
+    #[tokio::test]
+    async fn test_requeue_skips_live_workers() {
+        let keys = |cursor: &str, keys: &[&str]| {
+            Value::Bulk(vec![data(cursor), Value::Bulk(keys.iter().map(|k| data(k)).collect())])
+        };
+        let mut conn = ScriptedConnection::new(vec![
+            // Two SCAN pages, the second repeating a key
+            keys("7", &["analysis_processing:dead", "analysis_processing:dead@analysis_queue:high"]),
+            keys("0", &["analysis_processing:alive", "analysis_processing:me", "analysis_processing:dead"]),
+            // alive: heartbeat present
+            Value::Int(1),
+            // dead: no heartbeat, one entry
+            Value::Int(0),
+            data(r#"{"job_id":"job-1","attempts":1}"#),
+            Value::Int(1),
+            Value::Nil,
+            // dead's high priority list: one entry, back on its own queue
+            Value::Int(0),
+            data(r#"{"job_id":"job-3"}"#),
+            Value::Int(1),
+            Value::Nil,
+            // me: recovered without a heartbeat check; the first head was
+            // taken by another worker before the script ran
+            data(r#"{"job_id":"job-4"}"#),
+            Value::Int(0),
+            data(r#"{"job_id":"job-2"}"#),
+            Value::Int(2),
+            Value::Nil,
+        ]);
+
+        assert_eq!(requeue_stale_jobs(&mut conn, "me").await.unwrap(), 3);
+        let hash = redis::Script::new(REQUEUE_HEAD).get_hash().to_string();
+        let requeue = |key: &str, queue: &str, entry: &str, bumped: &str| {
+            vec!["EVALSHA".to_string(), hash.clone(), "2".to_string(), key.to_string(), queue.to_string(), entry.to_string(), bumped.to_string()]
+        };
+        let command = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            conn.commands,
+            vec![
+                command(&["SCAN", "0", "MATCH", "analysis_processing:*", "COUNT", "100"]),
+                command(&["SCAN", "7", "MATCH", "analysis_processing:*", "COUNT", "100"]),
+                command(&["EXISTS", "worker:alive:heartbeat"]),
+                command(&["EXISTS", "worker:dead:heartbeat"]),
+                command(&["LINDEX", "analysis_processing:dead", "0"]),
+                requeue(
+                    "analysis_processing:dead",
+                    "analysis_queue",
+                    r#"{"job_id":"job-1","attempts":1}"#,
+                    r#"{"attempts":2,"job_id":"job-1"}"#
+                ),
+                command(&["LINDEX", "analysis_processing:dead", "0"]),
+                command(&["EXISTS", "worker:dead:heartbeat"]),
+                command(&["LINDEX", "analysis_processing:dead@analysis_queue:high", "0"]),
+                requeue(
+                    "analysis_processing:dead@analysis_queue:high",
+                    "analysis_queue:high",
+                    r#"{"job_id":"job-3"}"#,
+                    r#"{"attempts":1,"job_id":"job-3"}"#
+                ),
+                command(&["LINDEX", "analysis_processing:dead@analysis_queue:high", "0"]),
+                command(&["LINDEX", "analysis_processing:me", "0"]),
+                requeue("analysis_processing:me", "analysis_queue", r#"{"job_id":"job-4"}"#, r#"{"attempts":1,"job_id":"job-4"}"#),
+                command(&["LINDEX", "analysis_processing:me", "0"]),
+                requeue("analysis_processing:me", "analysis_queue", r#"{"job_id":"job-2"}"#, r#"{"attempts":1,"job_id":"job-2"}"#),
+                command(&["LINDEX", "analysis_processing:me", "0"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bump_attempts_leaves_non_objects_alone() {
+        assert_eq!(bump_attempts("not json"), "not json");
+        assert_eq!(bump_attempts("[1]"), "[1]");
+    }
+}
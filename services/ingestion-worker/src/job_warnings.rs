@@ -0,0 +1,137 @@
+//! Job Warnings
+//!
+//! Problems a job works around instead of failing on: a checkout analyzed
+//! without its history, a compose file or API spec that doesn't parse,
+//! files left out of the parse, a branch that fell back to main or master.
+//! Each is logged where it happens and recorded in the job's `JobWarnings`,
+//! which the result summary lists as `warnings`, on failed jobs too, so a
+//! graph missing data says why.
+
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+use crate::parse_limits::ParseStats;
+
+/// Warnings kept per job; later ones are only counted
+pub const MAX_WARNINGS: usize = 100;
+
+/// Characters kept of a warning's message
+pub const MAX_MESSAGE_CHARS: usize = 500;
+
+pub const GIT_OPEN_FAILED: &str = "git_open_failed";
+pub const GIT_HISTORY_FAILED: &str = "git_history_failed";
+pub const COMPOSE_PARSE_FAILED: &str = "compose_parse_failed";
+pub const API_SPEC_PARSE_FAILED: &str = "api_spec_parse_failed";
+pub const PARSE_FAILED: &str = "parse_failed";
+pub const LARGE_FILES_SKIPPED: &str = "large_files_skipped";
+pub const FILES_TRUNCATED: &str = "files_truncated";
+pub const BRANCH_FALLBACK: &str = "branch_fallback";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Warning {
+    pub code: &'static str,
+    pub message: String,
+    /// The file or branch it concerns
+    pub context: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Recorded {
+    warnings: Vec<Warning>,
+    dropped: usize,
+}
+
+/// The warnings of one job, shared by every stage that records them
+#[derive(Debug, Clone, Default)]
+pub struct JobWarnings {
+    recorded: Arc<Mutex<Recorded>>,
+}
+
+impl JobWarnings {
+    pub fn record(&self, code: &'static str, message: impl Into<String>, context: Option<String>) {
+        let mut message = message.into();
+        if let Some((cut, _)) = message.char_indices().nth(MAX_MESSAGE_CHARS) {
+            message.truncate(cut);
+            message.push('…');
+        }
+        let mut recorded = self.recorded.lock().unwrap();
+        if recorded.warnings.len() < MAX_WARNINGS {
+            recorded.warnings.push(Warning { code, message, context });
+        } else {
+            recorded.dropped += 1;
+        }
+    }
+
+    /// Files that failed to parse, one warning each, and one for the
+    /// oversized files skipped and one for a truncated file list
+    pub fn record_parse_stats(&self, stats: &ParseStats, max_file_size_bytes: u64) {
+        for error in &stats.parse_errors {
+            self.record(PARSE_FAILED, error.error.clone(), Some(error.path.clone()));
+        }
+        if stats.skipped_large_files > 0 {
+            let message = format!(
+                "{} files larger than {} bytes were skipped",
+                stats.skipped_large_files, max_file_size_bytes
+            );
+            self.record(LARGE_FILES_SKIPPED, message, None);
+        }
+        if stats.truncated {
+            self.record(FILES_TRUNCATED, "Only the first files in path order were parsed", None);
+        }
+    }
+
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.recorded.lock().unwrap().warnings.clone()
+    }
+
+    /// `warnings`, and `warnings_dropped` past `MAX_WARNINGS`, for the
+    /// result summary
+    pub fn add_to_summary(&self, summary: &mut serde_json::Value) {
+        summary["warnings"] = serde_json::json!(self.warnings());
+        summary["warnings_dropped"] = serde_json::json!(self.recorded.lock().unwrap().dropped);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_limits::ParseError;
+
+    #[test]
+    fn test_warnings_are_capped() {
+        let warnings = JobWarnings::default();
+        let shared = warnings.clone();
+        for i in 0..MAX_WARNINGS + 3 {
+            shared.record(GIT_OPEN_FAILED, "é".repeat(MAX_MESSAGE_CHARS + 1 + i), None);
+        }
+
+        let recorded = warnings.warnings();
+        assert_eq!(recorded.len(), MAX_WARNINGS);
+        assert_eq!(recorded[0].message.chars().count(), MAX_MESSAGE_CHARS + 1);
+        assert!(recorded[0].message.ends_with('…'));
+
+        let mut summary = serde_json::json!({});
+        warnings.add_to_summary(&mut summary);
+        assert_eq!(summary["warnings"].as_array().unwrap().len(), MAX_WARNINGS);
+        assert_eq!(summary["warnings"][0]["code"], "git_open_failed");
+        assert_eq!(summary["warnings_dropped"], 3);
+    }
+
+    #[test]
+    fn test_parse_stats_become_warnings() {
+        let stats = ParseStats {
+            skipped_large_files: 2,
+            truncated: true,
+            parse_errors: vec![ParseError { path: "src/a.py".to_string(), error: "parser panicked".to_string() }],
+            ..ParseStats::default()
+        };
+        let warnings = JobWarnings::default();
+        warnings.record_parse_stats(&stats, 1024);
+
+        let recorded = warnings.warnings();
+        let codes: Vec<&str> = recorded.iter().map(|warning| warning.code).collect();
+        assert_eq!(codes, vec![PARSE_FAILED, LARGE_FILES_SKIPPED, FILES_TRUNCATED]);
+        assert_eq!(recorded[0].context.as_deref(), Some("src/a.py"));
+        assert_eq!(recorded[1].message, "2 files larger than 1024 bytes were skipped");
+    }
+}
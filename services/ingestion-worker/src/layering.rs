@@ -0,0 +1,211 @@
+//! Layer Rules
+//!
+//! Checks file dependencies against the direction architectural layers may
+//! depend in: Presentation on BusinessLogic, BusinessLogic on DataAccess,
+//! and every layer on Infrastructure. The matrix can be replaced per layer
+//! through the `layer_rules` job option, e.g.
+//! `{"presentation": ["business_logic", "data_access", "infrastructure"]}`.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::boundary_detector::{ArchitecturalLayer, BoundaryDetector};
+use crate::graph_builder::{DependencyGraph, EdgeType, NodeId};
+
+/// Job option overriding the allowed targets of some layers
+pub const LAYER_RULES_OPTION: &str = "layer_rules";
+
+/// Violations listed in the job summary
+pub const SUMMARY_EXAMPLES: usize = 20;
+
+/// Layers each layer may depend on, besides itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerRules {
+    allowed: HashMap<ArchitecturalLayer, HashSet<ArchitecturalLayer>>,
+}
+
+impl Default for LayerRules {
+    fn default() -> Self {
+        use ArchitecturalLayer::*;
+        let allowed = [
+            (Presentation, vec![BusinessLogic, Infrastructure]),
+            (BusinessLogic, vec![DataAccess, Infrastructure]),
+            (DataAccess, vec![Infrastructure]),
+            (Infrastructure, vec![]),
+        ]
+        .into_iter()
+        .map(|(layer, targets)| (layer, targets.into_iter().collect()))
+        .collect();
+        Self { allowed }
+    }
+}
+
+impl LayerRules {
+    /// The default rules, with the layers named in the `layer_rules` option
+    /// (a JSON object of layer to allowed target layers) replaced
+    pub fn from_options(options: &Option<HashMap<String, String>>) -> Result<Self> {
+        let mut rules = Self::default();
+        let Some(raw) = options.as_ref().and_then(|opts| opts.get(LAYER_RULES_OPTION)) else {
+            return Ok(rules);
+        };
+        let overrides: HashMap<String, Vec<String>> = serde_json::from_str(raw).with_context(|| {
+            format!("Invalid {} option, expected a JSON object of layer to allowed layers", LAYER_RULES_OPTION)
+        })?;
+        for (layer, targets) in overrides {
+            let layer = parse_layer(&layer)?;
+            let targets = targets.iter().map(|target| parse_layer(target)).collect::<Result<_>>()?;
+            rules.allowed.insert(layer, targets);
+        }
+        Ok(rules)
+    }
+
+    /// Whether a file in `from` may depend on a file in `to`. Files of an
+    /// unknown layer are never in violation.
+    pub fn allows(&self, from: &ArchitecturalLayer, to: &ArchitecturalLayer) -> bool {
+        from == to
+            || *from == ArchitecturalLayer::Unknown
+            || *to == ArchitecturalLayer::Unknown
+            || self.allowed.get(from).is_some_and(|targets| targets.contains(to))
+    }
+}
+
+fn parse_layer(name: &str) -> Result<ArchitecturalLayer> {
+    match ArchitecturalLayer::from_name(name) {
+        Some(ArchitecturalLayer::Unknown) | None => bail!(
+            "Invalid {} option, unknown layer {:?} (expected presentation, business_logic, data_access or infrastructure)",
+            LAYER_RULES_OPTION,
+            name
+        ),
+        Some(layer) => Ok(layer),
+    }
+}
+
+/// A file dependency going against the layer rules
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LayerViolation {
+    pub from_file: String,
+    pub to_file: String,
+    pub from_layer: &'static str,
+    pub to_layer: &'static str,
+}
+
+/// Violations among the graph's file-to-file DEPENDS_ON and IMPORTS edges,
+/// with each file placed in the layer its path suggests
+pub fn layer_violations(dep_graph: &DependencyGraph, rules: &LayerRules) -> Vec<LayerViolation> {
+    let file_dependencies: Vec<(&str, &str)> = dep_graph
+        .edges
+        .iter()
+        .filter(|e| matches!(e.edge_type, EdgeType::DependsOn | EdgeType::Imports))
+        .filter_map(|e| match (&e.from, &e.to) {
+            (NodeId::File(from), NodeId::File(to)) => Some((from.as_str(), to.as_str())),
+            _ => None,
+        })
+        .collect();
+    let file_layers: HashMap<String, ArchitecturalLayer> = file_dependencies
+        .iter()
+        .flat_map(|(from, to)| [*from, *to])
+        .map(|path| (path.to_string(), BoundaryDetector::classify_architectural_layer(path)))
+        .collect();
+    find_layer_violations(&file_layers, &file_dependencies, rules)
+}
+
+/// The file dependencies `rules` don't allow, once per file pair, sorted by
+/// source and target file. Files without a layer are left out.
+pub fn find_layer_violations(
+    file_layers: &HashMap<String, ArchitecturalLayer>,
+    file_dependencies: &[(&str, &str)],
+    rules: &LayerRules,
+) -> Vec<LayerViolation> {
+    let mut pairs = BTreeSet::new();
+    for (from, to) in file_dependencies {
+        let (Some(from_layer), Some(to_layer)) = (file_layers.get(*from), file_layers.get(*to)) else {
+            continue;
+        };
+        if !rules.allows(from_layer, to_layer) {
+            pairs.insert((*from, *to, from_layer.as_str(), to_layer.as_str()));
+        }
+    }
+    pairs
+        .into_iter()
+        .map(|(from_file, to_file, from_layer, to_layer)| LayerViolation {
+            from_file: from_file.to_string(),
+            to_file: to_file.to_string(),
+            from_layer,
+            to_layer,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ArchitecturalLayer::*;
+
+    fn layers() -> HashMap<String, ArchitecturalLayer> {
+        [
+            ("web/page.tsx", Presentation),
+            ("core/billing.ts", BusinessLogic),
+            ("db/invoices.ts", DataAccess),
+            ("lib/log.ts", Infrastructure),
+            ("scripts/seed.ts", Unknown),
+        ]
+        .into_iter()
+        .map(|(path, layer)| (path.to_string(), layer))
+        .collect()
+    }
+
+    #[test]
+    fn test_default_rules_flag_upward_and_skipping_dependencies() {
+        let deps = vec![
+            ("web/page.tsx", "core/billing.ts"),  // presentation -> business
+            ("core/billing.ts", "db/invoices.ts"), // business -> data
+            ("db/invoices.ts", "lib/log.ts"),      // data -> infrastructure
+            ("web/page.tsx", "lib/log.ts"),        // presentation -> infrastructure
+            ("db/invoices.ts", "web/page.tsx"),    // data -> presentation
+            ("db/invoices.ts", "web/page.tsx"),    // same pair again
+            ("web/page.tsx", "db/invoices.ts"),    // presentation skips business
+            ("lib/log.ts", "core/billing.ts"),     // infrastructure -> business
+            ("scripts/seed.ts", "web/page.tsx"),   // unknown layer
+            ("web/page.tsx", "web/other.tsx"),     // no layer
+        ];
+
+        let violations = find_layer_violations(&layers(), &deps, &LayerRules::default());
+        let found: Vec<(&str, &str, &str, &str)> = violations
+            .iter()
+            .map(|v| (v.from_file.as_str(), v.to_file.as_str(), v.from_layer, v.to_layer))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("db/invoices.ts", "web/page.tsx", "data_access", "presentation"),
+                ("lib/log.ts", "core/billing.ts", "infrastructure", "business_logic"),
+                ("web/page.tsx", "db/invoices.ts", "presentation", "data_access"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layer_rules_option_overrides_named_layers() {
+        let options = |raw: &str| Some(HashMap::from([(LAYER_RULES_OPTION.to_string(), raw.to_string())]));
+
+        let rules = LayerRules::from_options(&options(
+            r#"{"presentation": ["business_logic", "data_access", "infrastructure"]}"#,
+        ))
+        .unwrap();
+        assert!(rules.allows(&Presentation, &DataAccess));
+        // Layers left out keep their defaults
+        assert!(!rules.allows(&DataAccess, &Presentation));
+        assert!(rules.allows(&BusinessLogic, &DataAccess));
+
+        let rules = LayerRules::from_options(&options(r#"{"business_logic": []}"#)).unwrap();
+        assert!(!rules.allows(&BusinessLogic, &DataAccess));
+        assert!(rules.allows(&BusinessLogic, &BusinessLogic));
+
+        assert_eq!(LayerRules::from_options(&None).unwrap(), LayerRules::default());
+        assert!(LayerRules::from_options(&options(r#"["presentation"]"#)).is_err());
+        let err = LayerRules::from_options(&options(r#"{"ui": ["data_access"]}"#)).unwrap_err();
+        assert!(err.to_string().contains("\"ui\""), "{}", err);
+        assert!(LayerRules::from_options(&options(r#"{"presentation": ["unknown"]}"#)).is_err());
+    }
+}
@@ -1,21 +1,48 @@
 mod graph_builder;
+mod graph_export;
+mod graph_pool;
 mod neo4j_storage;
 mod parsers;
 mod git_analyzer;
 mod boundary_detector;
 mod dependency_metadata;
 mod communication_detector;
+mod orm_detector;
+mod metrics;
+mod import_resolver;
+mod pr_preview;
+mod parse_limits;
+mod parsed_source;
+mod patch_store;
+mod path_filter;
+mod repo_paths;
+mod stage_timings;
+mod worker_state;
+mod job_control;
+mod job_warnings;
+mod job_queue;
+mod osv_client;
+mod url_utils;
+mod ddl_scanner;
+mod infra_scanner;
+mod asset_inventory;
+mod codeowners;
+mod cli;
+mod generated_code;
+mod git_auth;
+mod layering;
+mod node_metadata;
+mod phases;
+mod pipeline;
+mod progress_updater;
+mod summary;
+mod worker_metrics;
+#[cfg(test)]
+mod corpus_benchmark;
 
 use anyhow::{Context, Result};
-use parsers::{
-    javascript::JavaScriptParser, 
-    typescript::TypeScriptParser, 
-    rust_parser::RustParser,
-    go_parser::GoParser,
-    python_parser::PythonParser,
-    LanguageParser, 
-    ParsedFile
-};
+use parsers::{ParsedFile, ParserSet};
+use rayon::prelude::*;
 use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
@@ -24,6 +51,15 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use tracing::{error, info, warn};
 use dependency_metadata::LibraryDependency;
+use job_control::{CancellationToken, JobCancelled};
+use job_warnings::JobWarnings;
+use parse_limits::{FileLimits, ParseError, ParseStats};
+use parsed_source::ParsedFileSource;
+use path_filter::PathFilter;
+use phases::{Phase, Phases};
+use progress_updater::ProgressUpdater;
+use stage_timings::{timed, StageTimings};
+use worker_state::{SharedWorkerState, WorkerStage, WorkerState};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct AnalysisJob {
@@ -34,9 +70,23 @@ struct AnalysisJob {
     status: String,
     options: Option<HashMap<String, String>>,
     created_at: String,
+    /// Times the job was re-queued after its worker died
+    #[serde(default)]
+    attempts: u32,
+    /// Priority the job was queued with, only logged; the queue it was
+    /// claimed from decides when it runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<String>,
+    /// The queue the job was claimed from
+    #[serde(skip)]
+    queue: String,
+    /// The JSON the job was claimed as, needed to remove it from the
+    /// processing list
+    #[serde(skip)]
+    queue_entry: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct JobUpdatePayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status: Option<String>,
@@ -54,6 +104,12 @@ struct GraphPatch {
     removed_files: Vec<String>,
     nodes: Vec<PatchNode>,
     edges: Vec<PatchEdge>,
+    /// Ids stored before an incremental run that it didn't produce again
+    removed_nodes: Vec<String>,
+    removed_edges: Vec<String>,
+    /// Files depending on the changed ones, for the UI to highlight
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    impacted_files: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -73,6 +129,12 @@ struct PatchNode {
     line_number: Option<usize>,
     #[serde(rename = "endLineNumber")]
     end_line_number: Option<usize>,
+    /// Decorators of a class or function, see `FunctionInfo::decorators`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    decorators: Vec<String>,
+    /// Visibility of a class or function, see `FunctionInfo::visibility`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    visibility: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -88,6 +150,7 @@ struct PatchEdge {
 pub struct ApiClient {
     client: reqwest::Client,
     base_url: String,
+    worker_id: Option<String>,
 }
 
 impl ApiClient {
@@ -95,14 +158,26 @@ impl ApiClient {
         Self {
             client: reqwest::Client::new(),
             base_url,
+            worker_id: None,
         }
     }
 
+    /// Tag every job update with the id of this worker
+    pub fn with_worker_id(mut self, worker_id: String) -> Self {
+        self.worker_id = Some(worker_id);
+        self
+    }
+
     pub async fn update_job(&self, job_id: &str, payload: JobUpdatePayload) -> Result<()> {
         let url = format!("{}/api/v1/jobs/{}", self.base_url, job_id);
+
+        let mut body = serde_json::to_value(&payload).context("Failed to serialize job update")?;
+        if let Some(worker_id) = &self.worker_id {
+            body["worker_id"] = serde_json::json!(worker_id);
+        }
         
         let response = self.client.patch(&url)
-            .json(&payload)
+            .json(&body)
             .send()
             .await
             .context("Failed to send update request")?;
@@ -118,6 +193,28 @@ impl ApiClient {
         
         Ok(())
     }
+
+    /// Attach a file produced by the job, such as a graph export
+    pub async fn upload_artifact(&self, job_id: &str, name: &str, bytes: Vec<u8>) -> Result<()> {
+        let url = format!("{}/api/v1/jobs/{}/artifacts/{}", self.base_url, job_id, name);
+        let size = bytes.len();
+
+        let response = self.client.put(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .body(bytes)
+            .send()
+            .await
+            .context("Failed to send artifact upload request")?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            error!("Failed to upload artifact {}: {}", name, error_text);
+            return Err(anyhow::anyhow!("API Error: {}", error_text));
+        }
+
+        info!("📎 Uploaded artifact {} for job {} ({} bytes)", name, job_id, size);
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -126,20 +223,61 @@ struct Config {
     neo4j_uri: String,
     neo4j_user: String,
     neo4j_password: String,
+    /// Database jobs store into unless they name another; `None` is the
+    /// server default
+    neo4j_database: Option<String>,
     api_gateway_url: String,
     git_max_commits: usize,
     neo4j_batch_size: usize,
+    cohesion_threshold: f64,
+    parse_concurrency: usize,
+    clone_depth: u32,
+    ssh: git_auth::SshSettings,
+    /// Directory `local_path` and `file://` jobs must point inside; such
+    /// jobs are refused when unset
+    local_workspace_root: Option<PathBuf>,
+    worker_id: String,
+    /// Queues polled for jobs, highest priority first
+    queue_names: Vec<String>,
+    /// Claims from the other queues after which the last one is polled
+    /// first; 0 turns that off
+    queue_fairness: usize,
+    heartbeat_interval_secs: u64,
+    max_concurrent_jobs: usize,
+    max_file_size_bytes: u64,
+    max_files: usize,
+    /// Files above which a full run streams its parse results to disk
+    stream_file_threshold: usize,
+    graph_patch_max_bytes: usize,
+    graph_patch_ttl_secs: u64,
+    neo4j_max_chunk_bytes: usize,
+    job_control_poll_secs: u64,
+    progress_update_interval_ms: u64,
+    osv_api_url: String,
+    osv_concurrency: usize,
+    /// Port `/metrics` is served on; no server when unset
+    metrics_port: Option<u16>,
 }
 
 impl Config {
     fn from_env() -> Result<Self> {
         dotenv::dotenv().ok();
 
+        let worker_id = env::var("WORKER_ID")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .unwrap_or_else(|| format!("ingestion-worker-{}", &Uuid::new_v4().simple().to_string()[..8]));
+        // The separator would make its processing lists unreadable
+        if worker_id.contains(job_queue::QUEUE_SEPARATOR) {
+            anyhow::bail!("WORKER_ID must not contain {:?}", job_queue::QUEUE_SEPARATOR);
+        }
+
         Ok(Config {
             redis_url: env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string()),
             neo4j_uri: env::var("NEO4J_URI").unwrap_or_else(|_| "bolt://localhost:7687".to_string()),
             neo4j_user: env::var("NEO4J_USER").unwrap_or_else(|_| "neo4j".to_string()),
             neo4j_password: env::var("NEO4J_PASSWORD").unwrap_or_else(|_| "password".to_string()),
+            neo4j_database: env::var("NEO4J_DATABASE").ok().filter(|value| !value.is_empty()),
             api_gateway_url: env::var("API_GATEWAY_URL").unwrap_or_else(|_| "http://localhost:8080".to_string()),
             git_max_commits: env::var("GIT_MAX_COMMITS")
                 .ok()
@@ -149,8 +287,116 @@ impl Config {
                 .ok()
                 .and_then(|value| value.parse::<usize>().ok())
                 .unwrap_or(100),
+            cohesion_threshold: env::var("COHESION_THRESHOLD")
+                .ok()
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or(metrics::DEFAULT_COHESION_THRESHOLD),
+            parse_concurrency: env::var("PARSE_CONCURRENCY")
+                .ok()
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or_else(num_cpus::get),
+            clone_depth: env::var("CLONE_DEPTH")
+                .ok()
+                .and_then(|value| value.parse::<u32>().ok())
+                .unwrap_or(0),
+            ssh: git_auth::SshSettings {
+                default_key_path: env::var("GIT_SSH_KEY_PATH").ok().filter(|value| !value.is_empty()).map(PathBuf::from),
+                strict_host_key: env::var("GIT_SSH_STRICT_HOST_KEY").map_or(true, |value| value != "false"),
+            },
+            worker_id,
+            local_workspace_root: env::var("LOCAL_WORKSPACE_ROOT").ok().filter(|value| !value.is_empty()).map(PathBuf::from),
+            queue_names: queue_names(env::var("QUEUE_NAMES").ok().as_deref()),
+            queue_fairness: env::var("QUEUE_FAIRNESS")
+                .ok()
+                .and_then(|value| value.parse::<usize>().ok())
+                .unwrap_or(job_queue::DEFAULT_QUEUE_FAIRNESS),
+            heartbeat_interval_secs: env::var("HEARTBEAT_INTERVAL_SECS")
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(10),
+            max_concurrent_jobs: env::var("MAX_CONCURRENT_JOBS")
+                .ok()
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(1),
+            max_file_size_bytes: env::var("MAX_FILE_SIZE_BYTES")
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(parse_limits::DEFAULT_MAX_FILE_SIZE_BYTES),
+            max_files: env::var("MAX_FILES")
+                .ok()
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(parse_limits::DEFAULT_MAX_FILES),
+            stream_file_threshold: env::var("STREAM_FILE_THRESHOLD")
+                .ok()
+                .and_then(|value| value.parse::<usize>().ok())
+                .unwrap_or(parsed_source::DEFAULT_STREAM_FILE_THRESHOLD),
+            graph_patch_max_bytes: env::var("GRAPH_PATCH_MAX_BYTES")
+                .ok()
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(patch_store::DEFAULT_GRAPH_PATCH_MAX_BYTES),
+            graph_patch_ttl_secs: env::var("GRAPH_PATCH_TTL_SECS")
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(patch_store::DEFAULT_GRAPH_PATCH_TTL_SECS),
+            neo4j_max_chunk_bytes: env::var("NEO4J_MAX_CHUNK_BYTES")
+                .ok()
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(neo4j_storage::DEFAULT_MAX_CHUNK_BYTES),
+            job_control_poll_secs: env::var("JOB_CONTROL_POLL_SECS")
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(2),
+            progress_update_interval_ms: env::var("PROGRESS_UPDATE_INTERVAL_MS")
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(progress_updater::DEFAULT_INTERVAL_MS),
+            osv_api_url: env::var("OSV_API_URL").unwrap_or_else(|_| osv_client::DEFAULT_OSV_API_URL.to_string()),
+            osv_concurrency: env::var("OSV_CONCURRENCY")
+                .ok()
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(osv_client::DEFAULT_OSV_CONCURRENCY),
+            metrics_port: env::var("METRICS_PORT")
+                .ok()
+                .filter(|value| !value.is_empty())
+                .map(|value| value.parse::<u16>())
+                .transpose()
+                .context("METRICS_PORT must be a port number")?,
         })
     }
+
+    fn file_limits(&self) -> FileLimits {
+        FileLimits {
+            max_file_size_bytes: self.max_file_size_bytes,
+            max_files: self.max_files,
+            skip_generated: false,
+        }
+    }
+}
+
+/// The comma-separated `QUEUE_NAMES`, in order and without repeats; the
+/// default queues when it names none
+fn queue_names(raw: Option<&str>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in raw.unwrap_or_default().split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        if !names.iter().any(|existing| existing == name) {
+            names.push(name.to_string());
+        }
+    }
+    if names.is_empty() {
+        names = job_queue::DEFAULT_QUEUES.iter().map(|name| name.to_string()).collect();
+    }
+    names
 }
 
 /// Connect to Redis with exponential backoff retry logic
@@ -203,16 +449,24 @@ async fn connect_neo4j_with_retry(
     uri: &str,
     user: &str,
     password: &str,
+    database: Option<&str>,
     max_retries: u32,
 ) -> Result<neo4rs::Graph> {
     use tokio::time::{sleep, Duration};
 
+    let database_name = database.unwrap_or("default database");
     for attempt in 1..=max_retries {
-        info!("🔄 Attempting to connect to Neo4j at {}... (attempt {}/{})", uri, attempt, max_retries);
-        
-        match neo4rs::Graph::new(uri, user, password).await {
+        info!("🔄 Attempting to connect to Neo4j at {} ({})... (attempt {}/{})", uri, database_name, attempt, max_retries);
+
+        let mut builder = neo4rs::ConfigBuilder::default().uri(uri).user(user).password(password);
+        if let Some(database) = database {
+            builder = builder.db(database);
+        }
+        let config = builder.build().context("Invalid Neo4j configuration")?;
+
+        match neo4rs::Graph::connect(config).await {
             Ok(graph) => {
-                info!("✅ Successfully connected to Neo4j");
+                info!("✅ Successfully connected to Neo4j ({})", database_name);
                 return Ok(graph);
             }
             Err(e) => {
@@ -232,6 +486,25 @@ async fn connect_neo4j_with_retry(
     Err(anyhow::anyhow!("Failed to connect to Neo4j"))
 }
 
+/// Connects to a Neo4j database and makes sure its schema exists
+struct Neo4jConnector {
+    uri: String,
+    user: String,
+    password: String,
+}
+
+impl graph_pool::GraphConnector for Neo4jConnector {
+    type Graph = neo4rs::Graph;
+
+    async fn connect(&self, database: Option<&str>) -> Result<neo4rs::Graph> {
+        let graph = connect_neo4j_with_retry(&self.uri, &self.user, &self.password, database, 4).await?;
+        neo4j_storage::ensure_schema(&graph).await;
+        Ok(graph)
+    }
+}
+
+type Neo4jPool = graph_pool::GraphPool<Neo4jConnector>;
+
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -243,11 +516,18 @@ async fn main() -> Result<()> {
         )
         .init();
 
+    // A local analysis needs neither Redis nor Neo4j
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).is_some_and(|arg| arg == "analyze") {
+        std::process::exit(cli::run(&args[2..]).await);
+    }
+
     info!("🚀 Ingestion Worker starting...");
 
     // Load configuration
     let config = Config::from_env()?;
-    let api_client = ApiClient::new(config.api_gateway_url.clone());
+    let api_client = ApiClient::new(config.api_gateway_url.clone()).with_worker_id(config.worker_id.clone());
+    info!("🪪 Worker id: {}", config.worker_id);
 
     // Connect to Redis with retry
     let redis_client = connect_redis_with_retry(&config.redis_url, 4).await?;
@@ -258,30 +538,40 @@ async fn main() -> Result<()> {
 
     info!("✅ Connected to Redis");
 
-    // Connect to Neo4j with retry
-    let neo4j_graph = connect_neo4j_with_retry(
-        &config.neo4j_uri,
-        &config.neo4j_user,
-        &config.neo4j_password,
-        4,
-    )
-    .await?;
+    // Jobs left in the processing lists of dead workers (or of this worker's
+    // previous run) would otherwise stay PROCESSING forever
+    match job_queue::requeue_stale_jobs(&mut redis_conn, &config.worker_id).await {
+        Ok(0) => {}
+        Ok(count) => info!("♻️  Re-queued {} jobs from stale processing lists", count),
+        Err(e) => warn!("⚠️  Failed to re-queue stale jobs: {:?}", e),
+    }
+
+    let worker_state = WorkerState::shared(config.worker_id.clone());
+    spawn_heartbeat(redis_client.clone(), worker_state.clone(), config.heartbeat_interval_secs);
+
+    // Connect to Neo4j with retry; other databases are connected when a job
+    // first asks for them
+    let connector = Neo4jConnector {
+        uri: config.neo4j_uri.clone(),
+        user: config.neo4j_user.clone(),
+        password: config.neo4j_password.clone(),
+    };
+    let neo4j_pool = Arc::new(Neo4jPool::connect(connector, config.neo4j_database.clone()).await?);
 
     info!("✅ Connected to Neo4j");
 
     // Setup shutdown signal handler
     use std::sync::Arc;
-    use std::sync::atomic::{AtomicBool, Ordering};
     use tokio::signal;
     
-    let shutdown = Arc::new(AtomicBool::new(false));
+    let shutdown = CancellationToken::new();
     let shutdown_clone = shutdown.clone();
     
     tokio::spawn(async move {
         match signal::ctrl_c().await {
             Ok(()) => {
-                info!("🛑 Shutdown signal received, finishing current job...");
-                shutdown_clone.store(true, Ordering::SeqCst);
+                info!("🛑 Shutdown signal received, finishing in-flight jobs...");
+                shutdown_clone.cancel();
             }
             Err(err) => {
                 error!("Failed to listen for shutdown signal: {}", err);
@@ -289,20 +579,59 @@ async fn main() -> Result<()> {
         }
     });
 
+    // Metrics are served until the in-flight jobs are done
+    let (stop_metrics, metrics_stopped) = tokio::sync::oneshot::channel::<()>();
+    let metrics_server = match config.metrics_port {
+        Some(port) => {
+            let listener = std::net::TcpListener::bind(("0.0.0.0", port))
+                .with_context(|| format!("Failed to bind metrics port {}", port))?;
+            info!("📈 Serving metrics on :{}/metrics", port);
+            Some(tokio::spawn(worker_metrics::serve(listener, async {
+                let _ = metrics_stopped.await;
+            })))
+        }
+        None => None,
+    };
+
     // Main worker loop
-    info!("👂 Listening for jobs on analysis_queue...");
-    while !shutdown.load(Ordering::SeqCst) {
-        match process_job(&mut redis_conn, &neo4j_graph, &api_client, config.git_max_commits, config.neo4j_batch_size).await {
-            Ok(processed) => {
-                if !processed {
-                    // No job available, sleep briefly
-                    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-                }
-            }
-            Err(e) => {
-                error!("Error processing job: {:?}", e);
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+    info!(
+        "👂 Listening for jobs on {} (up to {} concurrent)...",
+        config.queue_names.join(", "),
+        config.max_concurrent_jobs
+    );
+    let max_concurrent_jobs = config.max_concurrent_jobs;
+    let queue = job_queue::QueueConnection::new(RedisConnector { url: config.redis_url.clone() }, redis_conn);
+    let queue = Arc::new(tokio::sync::Mutex::new(queue));
+    let selector = job_queue::QueueSelector::new(config.queue_names.clone(), config.queue_fairness);
+    let selector = Arc::new(tokio::sync::Mutex::new(selector));
+    let config = Arc::new(config);
+    run_job_loop(
+        max_concurrent_jobs,
+        &shutdown,
+        || {
+            let (queue, selector, worker_id) = (queue.clone(), selector.clone(), config.worker_id.clone());
+            async move {
+                let mut queue = queue.lock().await;
+                pop_job_reconnecting(&mut queue, &mut *selector.lock().await, &worker_id).await
             }
+        },
+        |job| {
+            let neo4j_pool = neo4j_pool.clone();
+            let redis_client = redis_client.clone();
+            let api_client = api_client.clone();
+            let config = config.clone();
+            let worker_state = worker_state.clone();
+            async move { process_job(job, &neo4j_pool, &redis_client, &api_client, &config, &worker_state).await }
+        },
+    )
+    .await;
+
+    let _ = stop_metrics.send(());
+    if let Some(server) = metrics_server {
+        match server.await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Metrics server failed: {:?}", e),
+            Err(e) => error!("Metrics server task failed: {:?}", e),
         }
     }
 
@@ -314,6 +643,64 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Publish the worker state to Redis every `interval_secs`. The key expires
+/// after three missed beats, so a dead worker disappears on its own.
+fn spawn_heartbeat(redis_client: redis::Client, state: SharedWorkerState, interval_secs: u64) {
+    tokio::spawn(async move {
+        let key = worker_state::heartbeat_key(state.lock().unwrap_or_else(|p| p.into_inner()).worker_id());
+        let ttl = interval_secs.saturating_mul(3).max(1);
+        let mut conn: Option<redis::aio::Connection> = None;
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(interval_secs));
+
+        loop {
+            interval.tick().await;
+            let beat = state.lock().unwrap_or_else(|p| p.into_inner()).heartbeat();
+            let payload = match serde_json::to_string(&beat) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    warn!("⚠️  Failed to serialize heartbeat: {}", e);
+                    continue;
+                }
+            };
+
+            if conn.is_none() {
+                match redis_client.get_async_connection().await {
+                    Ok(c) => conn = Some(c),
+                    Err(e) => {
+                        warn!("⚠️  Heartbeat could not connect to Redis: {}", e);
+                        continue;
+                    }
+                }
+            }
+            if let Some(c) = conn.as_mut() {
+                let result: redis::RedisResult<()> = c.set_ex(&key, payload, ttl).await;
+                if let Err(e) = result {
+                    warn!("⚠️  Failed to write heartbeat: {}", e);
+                    conn = None;
+                }
+            }
+        }
+    });
+}
+
+/// Attempts `connect_redis_with_retry` makes each time the job queue
+/// reconnects; the job loop keeps polling after it gives up
+const REDIS_RECONNECT_ATTEMPTS: u32 = 4;
+
+/// Opens job queue connections, backing off while Redis is unreachable
+struct RedisConnector {
+    url: String,
+}
+
+impl job_queue::Connector for RedisConnector {
+    type Connection = redis::aio::Connection;
+
+    async fn connect(&self) -> Result<Self::Connection> {
+        let client = connect_redis_with_retry(&self.url, REDIS_RECONNECT_ATTEMPTS).await?;
+        client.get_async_connection().await.context("Failed to get Redis async connection")
+    }
+}
+
 /// Clean up temporary repository clones
 async fn cleanup_temp_files() {
     use tokio::fs;
@@ -340,87 +727,250 @@ async fn cleanup_temp_files() {
     }
 }
 
-async fn process_job(
-    redis_conn: &mut redis::aio::Connection,
-    neo4j_graph: &neo4rs::Graph,
-    api_client: &ApiClient,
-    git_max_commits: usize,
-    neo4j_batch_size: usize,
-) -> Result<bool> {
-    // Use RPOP instead of BRPOP for compatibility with Redis 3.x (Windows)
-    // which doesn't support float timeouts sent by the redis crate
-    let result: Option<String> = redis_conn
-        .rpop("analysis_queue", None)
-        .await
-        .context("Failed to pop from Redis queue")?;
+/// Run jobs from `pop_job` with at most `max_concurrent` in flight until
+/// `shutdown` is set, then wait for the in-flight jobs to finish
+async fn run_job_loop<P, PF, R, RF>(
+    max_concurrent: usize,
+    shutdown: &CancellationToken,
+    mut pop_job: P,
+    run_job: R,
+) where
+    P: FnMut() -> PF,
+    PF: std::future::Future<Output = Result<Option<AnalysisJob>>>,
+    R: Fn(AnalysisJob) -> RF,
+    RF: std::future::Future<Output = ()> + Send + 'static,
+{
+    use tokio::time::{sleep, Duration};
+
+    let mut in_flight = tokio::task::JoinSet::new();
 
-    if let Some(job_json) = result {
-        // Deserialize job
-        let job: AnalysisJob = serde_json::from_str(&job_json)
-            .context("Failed to deserialize job")?;
+    while !shutdown.is_cancelled() {
+        let mut idle_wait = Duration::from_secs(2);
+        while in_flight.len() < max_concurrent {
+            match pop_job().await {
+                Ok(Some(job)) => {
+                    in_flight.spawn(run_job(job));
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    error!("Error fetching job: {:?}", e);
+                    idle_wait = Duration::from_secs(5);
+                    break;
+                }
+            }
+        }
 
-        info!("📝 Processing job: {} for repo: {}", job.job_id, job.repo_url);
+        if in_flight.len() >= max_concurrent {
+            // All slots busy: wait for one to free up
+            if let Some(Err(e)) = in_flight.join_next().await {
+                error!("Job task failed: {:?}", e);
+            }
+            continue;
+        }
 
-        // Update status to PROCESSING (0%)
-        let payload = JobUpdatePayload {
-            status: Some("PROCESSING".to_string()),
-            progress: Some(0),
-            result_summary: None,
-            error: None,
-        };
-        
-        if let Err(e) = api_client.update_job(&job.job_id, payload).await {
-            error!("Failed to update job status to PROCESSING: {:?}", e);
-        }
-
-        // Process the job
-        match analyze_repository(&job, neo4j_graph, api_client, git_max_commits, neo4j_batch_size).await {
-            Ok(summary) => {
-                info!("✅ Successfully processed job: {}", job.job_id);
-                // Update status to COMPLETED
-                let payload = JobUpdatePayload {
-                    status: Some("COMPLETED".to_string()),
-                    progress: Some(100),
-                    result_summary: Some(summary),
-                    error: None,
-                };
-                if let Err(e) = api_client.update_job(&job.job_id, payload).await {
-                    error!("Failed to update job status to COMPLETED: {:?}", e);
+        // Queue empty: poll again after a short sleep, or as soon as a job finishes
+        tokio::select! {
+            Some(result) = in_flight.join_next() => {
+                if let Err(e) = result {
+                    error!("Job task failed: {:?}", e);
                 }
             }
-            Err(e) => {
-                error!("❌ Failed to process job {}: {:?}", job.job_id, e);
-                // Update status to FAILED
-                let error_msg = format!("{:?}", e);
-                let payload = JobUpdatePayload {
-                    status: Some("FAILED".to_string()),
-                    progress: None,
-                    result_summary: None,
-                    error: Some(error_msg),
-                };
-                if let Err(e) = api_client.update_job(&job.job_id, payload).await {
-                    error!("Failed to update job status to FAILED: {:?}", e);
+            _ = sleep(idle_wait) => {}
+        }
+    }
+
+    if !in_flight.is_empty() {
+        info!("⏳ Waiting for {} in-flight jobs to finish...", in_flight.len());
+    }
+    while let Some(result) = in_flight.join_next().await {
+        if let Err(e) = result {
+            error!("Job task failed: {:?}", e);
+        }
+    }
+}
+
+/// `pop_job` over the queue connection, reopening it first if Redis dropped it
+async fn pop_job_reconnecting<K: job_queue::Connector>(
+    queue: &mut job_queue::QueueConnection<K>,
+    selector: &mut job_queue::QueueSelector,
+    worker_id: &str,
+) -> Result<Option<AnalysisJob>> {
+    let result = match queue.connection().await {
+        Ok(conn) => pop_job(conn, selector, worker_id).await,
+        Err(e) => Err(e),
+    };
+    queue.drop_if_broken(result)
+}
+
+async fn pop_job<C>(
+    redis_conn: &mut C,
+    selector: &mut job_queue::QueueSelector,
+    worker_id: &str,
+) -> Result<Option<AnalysisJob>>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    // Use RPOPLPUSH instead of BRPOPLPUSH for compatibility with Redis 3.x
+    // (Windows) which doesn't support float timeouts sent by the redis crate
+    let Some(claimed) = job_queue::claim_job(redis_conn, worker_id, selector).await? else {
+        return Ok(None);
+    };
+    let job_queue::ClaimedJob { queue, entry } = claimed;
+
+    match serde_json::from_str::<AnalysisJob>(&entry) {
+        Ok(mut job) => {
+            job.queue = queue;
+            job.queue_entry = entry;
+            // A re-queued job's age includes its earlier attempts
+            if job.attempts == 0 {
+                if let Some(wait) = queue_wait(&job.created_at, chrono::Utc::now()) {
+                    worker_metrics::metrics().queue_wait(wait);
                 }
             }
+            Ok(Some(job))
+        }
+        Err(e) => {
+            // Retrying can't fix it, so don't leave it to be re-queued
+            job_queue::ack_job(redis_conn, worker_id, &queue, &entry).await?;
+            Err(e).context("Failed to deserialize job")
         }
+    }
+}
 
-        Ok(true)
-    } else {
-        // No job available
-        Ok(false)
+/// Time since the job's RFC 3339 `created_at`; None when it doesn't parse
+fn queue_wait(created_at: &str, now: chrono::DateTime<chrono::Utc>) -> Option<std::time::Duration> {
+    let created = chrono::DateTime::parse_from_rfc3339(created_at).ok()?;
+    (now - created.with_timezone(&chrono::Utc)).to_std().ok()
+}
+
+/// Remove a job whose final status reached the API from the processing list
+async fn acknowledge_job(redis_client: &redis::Client, worker_id: &str, job: &AnalysisJob) {
+    let result: Result<()> = async {
+        let mut conn = redis_client.get_async_connection().await?;
+        job_queue::ack_job(&mut conn, worker_id, &job.queue, &job.queue_entry).await
+    }
+    .await;
+    if let Err(e) = result {
+        warn!("⚠️  Failed to acknowledge job {}: {:?}", job.job_id, e);
+    }
+}
+
+async fn process_job(
+    job: AnalysisJob,
+    neo4j_pool: &Neo4jPool,
+    redis_client: &redis::Client,
+    api_client: &ApiClient,
+    config: &Config,
+    worker_state: &SharedWorkerState,
+) {
+    info!(
+        "📝 Processing job: {} for repo: {} (from {}, priority {})",
+        job.job_id,
+        job.repo_url,
+        job.queue,
+        job.priority.as_deref().unwrap_or("normal")
+    );
+    let started = std::time::Instant::now();
+    if job.attempts > 0 {
+        warn!("♻️  Job {} was re-queued {} times after a worker died", job.job_id, job.attempts);
+    }
+    worker_state::update(worker_state, |s| s.start_job(&job.job_id));
+
+    // Update status to PROCESSING (0%)
+    let progress = ProgressUpdater::new(
+        api_client.clone(),
+        &job.job_id,
+        std::time::Duration::from_millis(config.progress_update_interval_ms),
+    );
+    let payload = JobUpdatePayload {
+        status: Some("PROCESSING".to_string()),
+        progress: Some(0),
+        result_summary: None,
+        error: None,
+    };
+    
+    if let Err(e) = progress.update(payload).await {
+        error!("Failed to update job status to PROCESSING: {:?}", e);
+    }
+
+    // Process the job, watching job_control:{job_id} for pause/resume/cancel
+    let cancellation = CancellationToken::new();
+    let control_poller = job_control::spawn_control_poller(
+        redis_client.clone(),
+        job.job_id.clone(),
+        cancellation.clone(),
+        std::time::Duration::from_secs(config.job_control_poll_secs),
+    );
+    let warnings = JobWarnings::default();
+    let outcome =
+        analyze_repository(&job, neo4j_pool, redis_client, &progress, config, worker_state, &cancellation, &warnings)
+            .await;
+    control_poller.abort();
+    job_control::clear_control(redis_client, &job.job_id).await;
+    worker_state::update(worker_state, |s| s.finish_job(&job.job_id));
+    let payload = match outcome {
+        Ok(summary) => {
+            info!("✅ Successfully processed job: {}", job.job_id);
+            JobUpdatePayload {
+                status: Some("COMPLETED".to_string()),
+                progress: Some(100),
+                result_summary: Some(summary),
+                error: None,
+            }
+        }
+        Err(e) if e.downcast_ref::<JobCancelled>().is_some() => {
+            let stage = e.downcast_ref::<JobCancelled>().map(|c| c.stage).unwrap_or(WorkerStage::Idle);
+            info!("🚫 Job {} cancelled during {}", job.job_id, stage.as_str());
+            let mut result_summary = serde_json::json!({ "cancelled_stage": stage });
+            warnings.add_to_summary(&mut result_summary);
+            JobUpdatePayload {
+                status: Some("CANCELLED".to_string()),
+                progress: None,
+                result_summary: Some(result_summary),
+                error: Some(e.to_string()),
+            }
+        }
+        Err(e) => {
+            error!("❌ Failed to process job {}: {:?}", job.job_id, e);
+            summary::clear_repo_summary(redis_client, &job.repo_id, &job.job_id).await;
+            let mut result_summary = serde_json::json!({});
+            warnings.add_to_summary(&mut result_summary);
+            JobUpdatePayload {
+                status: Some("FAILED".to_string()),
+                progress: None,
+                result_summary: Some(result_summary),
+                error: Some(format!("{:?}", e)),
+            }
+        }
+    };
+
+    // Until the API has the final status the job stays in the processing
+    // list, so it is re-queued if this worker dies first
+    let status = payload.status.clone().unwrap_or_default();
+    worker_metrics::metrics().job_finished(&status, started.elapsed());
+    match progress.update(payload).await {
+        Ok(()) => acknowledge_job(redis_client, &config.worker_id, &job).await,
+        Err(e) => error!("Failed to update job status to {}: {:?}", status, e),
     }
 }
 
-use git2::{Cred, FetchOptions, RemoteCallbacks};
+use git2::{CertificateCheckStatus, Cred, FetchOptions, RemoteCallbacks};
 use std::ops::Deref;
 use uuid::Uuid;
 
 struct TempRepo {
     path: PathBuf,
+    /// False for a local workspace, which is left in place afterwards
+    owned: bool,
+    /// Branch checked out because the job's branch wasn't found
+    fallback_branch: Option<String>,
 }
 
 impl Drop for TempRepo {
     fn drop(&mut self) {
+        if !self.owned {
+            return;
+        }
         info!("🧹 Cleaning up temporary repository: {:?}", self.path);
         if let Err(e) = fs::remove_dir_all(&self.path) {
             error!("❌ Failed to cleanup temporary directory {:?}: {:?}", self.path, e);
@@ -441,249 +991,645 @@ impl Deref for TempRepo {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn analyze_repository(
     job: &AnalysisJob, 
-    neo4j_graph: &neo4rs::Graph,
-    api_client: &ApiClient,
-    git_max_commits: usize,
-    neo4j_batch_size: usize,
+    neo4j_pool: &Neo4jPool,
+    redis_client: &redis::Client,
+    progress: &ProgressUpdater,
+    config: &Config,
+    worker_state: &SharedWorkerState,
+    cancellation: &CancellationToken,
+    warnings: &JobWarnings,
 ) -> Result<serde_json::Value> {
     info!("🔍 Analyzing repository: {}", job.repo_url);
+    let neo4j_graph = &neo4j_pool
+        .for_job(job.options.as_ref())
+        .await
+        .context("Failed to connect to the job's Neo4j database")?;
+    let started = std::time::Instant::now();
+    let started_at = chrono::Utc::now();
+    let mut timings = StageTimings::default();
+    let path_filter = PathFilter::from_options(&job.options)?;
+    if !path_filter.is_empty() {
+        info!("🎯 Path filter: include {:?}, exclude {:?}", path_filter.include, path_filter.exclude);
+    }
+    let layer_rules = layering::LayerRules::from_options(&job.options)?;
+    let phases = Phases::from_options(&job.options)?;
+    if phases != Phases::default() {
+        info!("🧩 Running phases: {}", phases.names().join(", "));
+    }
+    let progress_plan = phases.progress_plan(CLONE_PROGRESS_END);
+    let node_metadata = node_metadata::NodeMetadata::from_options(job.options.as_ref())?;
+    if !node_metadata.is_empty() {
+        info!("🏷️  Node metadata: {:?}", node_metadata);
+    }
 
-    // Step 1: Clone repository
-    let temp_repo = clone_repository(&job.repo_url, &job.branch, &job.options)?;
-    info!("📦 Repository cloned to: {:?}", temp_repo.path);
-
-    let (changed_files, removed_files) = extract_webhook_changes(&job.options);
+    // Step 1: Clone repository, reporting transfer progress up to 25%
+    let (repo_url, branch, options, clone_depth, ssh) =
+        (job.repo_url.clone(), job.branch.clone(), job.options.clone(), config.clone_depth, config.ssh.clone());
+    let workspace_root = config.local_workspace_root.clone();
+    let clone_progress_tx = spawn_progress_forwarder(progress, worker_state, &job.job_id);
+    let temp_repo = timed(
+        &mut timings.clone_ms,
+        run_blocking(move || match local_repo_path(&repo_url, &options) {
+            Some(path) => open_local_repository(&confine_local_path(&path, workspace_root.as_deref())?, &branch, &options),
+            None => clone_repository(&repo_url, &branch, &options, clone_depth, &ssh, Some(clone_progress_tx)),
+        }),
+    )
+    .await?;
+    info!("📦 Repository ready at: {:?}", temp_repo.path);
+    if let Some(fallback) = &temp_repo.fallback_branch {
+        let message = format!("Branch {} not found, analyzed {} instead", job.branch, fallback);
+        warnings.record(job_warnings::BRANCH_FALLBACK, message, Some(job.branch.clone()));
+    }
+    cancellation.checkpoint(WorkerStage::Cloning).await?;
+
+    // Preview jobs diff base_sha..head_sha themselves instead of trusting the
+    // webhook's file lists, then analyze the head commit
+    let preview_range = pr_preview::extract_preview_range(&job.options);
+    let commit_checkout = CommitCheckout::for_repo(&temp_repo, &job.options);
+    let (mut changed_files, mut removed_files, range_diff) = match &preview_range {
+        Some(range) => {
+            let (repo_path, blocking_range) = (temp_repo.path.clone(), range.clone());
+            let diff = run_blocking(move || {
+                let (base, head) = (&blocking_range.base_sha, &blocking_range.head_sha);
+                let diff = git_analyzer::GitAnalyzer::new(&repo_path)?.diff_commits(base, head)?;
+                checkout_commit(&repo_path, head, commit_checkout)?;
+                Ok(diff)
+            })
+            .await?;
+            info!(
+                "🔎 Preview {}..{}: {} changed, {} removed",
+                range.base_sha,
+                range.head_sha,
+                diff.changed_files().len(),
+                diff.removed_files().len()
+            );
+            (diff.changed_files(), diff.removed_files(), Some(diff))
+        }
+        None => {
+            let (changed_files, removed_files) = extract_webhook_changes(&job.options)?;
+            (changed_files, removed_files, None)
+        }
+    };
+    // A preview analyzes its head commit, checked out above
+    let commit_sha = match &preview_range {
+        Some(_) => None,
+        None => job.options.as_ref().and_then(|opts| opts.get("commit_sha")).cloned(),
+    };
+    let repo_path = temp_repo.path.clone();
+    let analyzed_commit =
+        run_blocking(move || checkout_analyzed_commit(&repo_path, commit_sha.as_deref(), commit_checkout)).await?;
+    if let Some(sha) = &analyzed_commit {
+        info!("📌 Analyzing commit {}", sha);
+    }
+    let renamed_files = match &range_diff {
+        Some(diff) => diff.renamed.clone(),
+        None => {
+            let (repo_path, options) = (temp_repo.path.clone(), job.options.clone());
+            let (removed, changed) = (removed_files.clone(), changed_files.clone());
+            run_blocking(move || resolve_renames(&repo_path, &options, &removed, &changed)).await?
+        }
+    };
+    for (from, to) in &renamed_files {
+        if !removed_files.contains(from) {
+            removed_files.push(from.clone());
+        }
+        if !changed_files.contains(to) {
+            changed_files.push(to.clone());
+        }
+    }
     let incremental_flag = job
         .options
         .as_ref()
         .and_then(|opts| opts.get("incremental"))
         .map(|value| value == "true")
         .unwrap_or(false);
-    let incremental = incremental_flag || !changed_files.is_empty() || !removed_files.is_empty();
+    let incremental = incremental_flag
+        || preview_range.is_some()
+        || !changed_files.is_empty()
+        || !removed_files.is_empty();
 
     // Update progress: 25%
-    if let Err(e) = api_client.update_job(&job.job_id, JobUpdatePayload {
-        status: None,
-        progress: Some(25),
-        result_summary: None,
-        error: None,
-    }).await {
-        error!("Failed to update progress to 25%: {:?}", e);
-    }
+    worker_state::update(worker_state, |s| s.set_stage(&job.job_id, WorkerStage::Parsing, CLONE_PROGRESS_END));
+    progress.progress(CLONE_PROGRESS_END).await;
 
     // Step 2: Parse source files with tree-sitter
-    let parsed_files = if incremental {
-        parse_repository_subset(&temp_repo.path, &changed_files)?
+    let (repo_path, files, concurrency) = (temp_repo.path.clone(), changed_files.clone(), config.parse_concurrency);
+    let skip_generated = job
+        .options
+        .as_ref()
+        .and_then(|opts| opts.get(generated_code::SKIP_GENERATED_OPTION))
+        .is_some_and(|value| value == "true");
+    let limits = FileLimits { skip_generated, ..config.file_limits() };
+    let (filter, stream_threshold) = (path_filter.clone(), config.stream_file_threshold);
+    let (mut parsed_files, parse_stats) = timed(&mut timings.parse_ms, async move {
+        // Incremental runs parse few enough files to keep them in memory
+        if incremental {
+            run_blocking(move || {
+                parse_repository_subset(&repo_path, &files, concurrency, &limits, &filter)
+                    .map(|(parsed_files, stats)| (ParsedFileSource::Memory(parsed_files), stats))
+            })
+            .await
+        } else {
+            run_blocking(move || {
+                let chunk_files = parsed_source::CHUNK_FILES;
+                parse_repository_source(&repo_path, concurrency, &limits, &filter, stream_threshold, chunk_files)
+            })
+            .await
+        }
+    })
+    .await?;
+    info!("📄 Parsed {} files", parsed_files.len());
+    if parsed_files.is_streamed() {
+        info!("💽 Streaming parsed files through a spill file, {} at a time", parsed_source::CHUNK_FILES);
+    }
+    parse_stats.log();
+    warnings.record_parse_stats(&parse_stats, limits.max_file_size_bytes);
+    worker_metrics::metrics().files_parsed(parsed_files.len());
+    cancellation.checkpoint(WorkerStage::Parsing).await?;
+
+    // Update progress: 50% with every phase
+    worker_state::update(worker_state, |s| s.set_stage(&job.job_id, WorkerStage::Analyzing, progress_plan.parsed));
+    progress.progress(progress_plan.parsed).await;
+
+    // Steps 3-5b: Git history, module boundaries and library dependencies
+    let (git_since, git_max_walk) = git_history_limits(&job.options, chrono::Utc::now());
+    let settings = pipeline::PipelineSettings {
+        phases: phases.clone(),
+        path_filter: path_filter.clone(),
+        layer_rules,
+        incremental,
+        git_max_commits: config.git_max_commits,
+        git_since,
+        git_max_walk,
+        cohesion_threshold: config.cohesion_threshold,
+        inventory_assets: asset_inventory::enabled(job.options.as_ref()),
+    };
+    let mut sources =
+        pipeline::analyze_sources(&temp_repo.path, &mut parsed_files, &settings, &mut timings, warnings).await?;
+
+    // Step 5b': Look up known vulnerabilities when the job asks for it
+    let check_vulnerabilities = phases.runs(Phase::Dependencies)
+        && job
+            .options
+            .as_ref()
+            .and_then(|opts| opts.get("check_vulnerabilities"))
+            .is_some_and(|value| value == "true");
+    let libraries_checked = if check_vulnerabilities {
+        let osv = osv_client::OsvClient::new(config.osv_api_url.clone(), config.osv_concurrency)?;
+        let library_dependencies = &mut sources.library_dependencies;
+        let checked = osv.enrich(library_dependencies).await;
+        info!("🛡️  Checked {} of {} libraries for known vulnerabilities", checked, library_dependencies.len());
+        Some(checked)
     } else {
-        parse_repository(&temp_repo.path)?
+        None
     };
-    info!("📄 Parsed {} files", parsed_files.len());
 
-    // Update progress: 50%
-    if let Err(e) = api_client.update_job(&job.job_id, JobUpdatePayload {
-        status: None,
-        progress: Some(50),
-        result_summary: None,
-        error: None,
-    }).await {
-        error!("Failed to update progress to 50%: {:?}", e);
-    }
-
-    // Step 3: Build symbol table for cross-file resolution
-    let symbol_table = graph_builder::SymbolTable::from_parsed_files(&parsed_files);
-    info!("📚 Built symbol table: {} functions, {} classes", 
-          symbol_table.functions.len(), 
-          symbol_table.classes.len());
-
-    // Step 4: Analyze git commit history
-    let git_contributions = match git_analyzer::GitAnalyzer::new(&temp_repo.path) {
-        Ok(analyzer) => {
-            match analyzer.analyze_contributions_with_limit(git_max_commits) {
-                Ok(contributions) => {
-                    info!("📊 Analyzed git history: {} files with {} total commits", 
-                          contributions.files.len(), 
-                          contributions.total_commits);
-                    Some(contributions)
-                }
-                Err(e) => {
-                    warn!("⚠️  Failed to analyze git history: {}. Continuing without git metrics.", e);
-                    None
-                }
+    // Update progress: 60% with every phase
+    worker_state::update(worker_state, |s| s.set_progress(&job.job_id, progress_plan.analyzed));
+    progress.progress(progress_plan.analyzed).await;
+
+    // Steps 5c-6c: Communication, tables, assets, ownership, the dependency
+    // graph and its metrics
+    let analysis =
+        pipeline::build_analysis(&temp_repo.path, &mut parsed_files, sources, &settings, &mut timings, warnings).await?;
+    cancellation.checkpoint(WorkerStage::Analyzing).await?;
+
+    // Update progress: 75% with every phase
+    worker_state::update(worker_state, |s| s.set_stage(&job.job_id, WorkerStage::Storing, progress_plan.storage_start));
+    progress.progress(progress_plan.storage_start).await;
+
+    // Step 7: Store in Neo4j (batch operations with transactions)
+    let progress_tx = spawn_progress_forwarder(progress, worker_state, &job.job_id);
+
+    // Shared by replays, so reported progress doesn't go back
+    let storage_progress: std::sync::Arc<dyn neo4j_storage::ProgressReporter> =
+        std::sync::Arc::new(StorageProgress::new(progress_tx, progress_plan));
+
+    // Read what the changed files held before their nodes are replaced, so
+    // the graph patch can list the ids that disappear
+    let previous_ids = if incremental && preview_range.is_none() {
+        let files: Vec<String> = changed_files.iter().chain(&removed_files).cloned().collect();
+        match neo4j_storage::fetch_existing_ids(neo4j_graph, &job.repo_id, &files).await {
+            Ok(ids) => Some(ids),
+            Err(e) => {
+                warn!("⚠️  Failed to fetch existing ids, graph patch won't list removals: {:?}", e);
+                None
             }
         }
-        Err(e) => {
-            warn!("⚠️  Failed to open git repository: {}. Continuing without git metrics.", e);
-            None
+    } else {
+        None
+    };
+
+    // Read before storage replaces the changed files' edges; the stored
+    // edges into changed and removed files are what the impact follows
+    let impact = if incremental {
+        match neo4j_storage::fetch_file_dependencies(neo4j_graph, &job.repo_id).await {
+            Ok(dependencies) => {
+                let mut stored_graph = graph_builder::DependencyGraph::default();
+                for (source, target) in &dependencies {
+                    stored_graph.add_file_dependency(source, target);
+                }
+                let changed: Vec<String> = changed_files.iter().chain(&removed_files).cloned().collect();
+                Some(stored_graph.reverse_reachability(&changed, graph_builder::DEFAULT_IMPACT_DEPTH))
+            }
+            Err(e) => {
+                warn!("⚠️  Failed to fetch file dependencies, summary won't list impacted files: {:?}", e);
+                None
+            }
         }
+    } else {
+        None
     };
 
-    // Step 5: Detect module boundaries
-    let boundary_result = boundary_detector::BoundaryDetector::detect_boundaries(&parsed_files, &temp_repo.path)?;
-    info!("🗺️  Detected {} module boundaries", boundary_result.boundaries.len());
+    // Read before storage overwrites the stored versions
+    let library_diff = if phases.runs(Phase::Dependencies) {
+        match neo4j_storage::fetch_library_versions(neo4j_graph, &job.repo_id).await {
+            Ok(stored) => {
+                let diff = dependency_metadata::diff_libraries(&stored, &analysis.library_dependencies);
+                info!(
+                    "📦 Libraries since the last run: {} added, {} removed, {} upgraded",
+                    diff.added.len(),
+                    diff.removed.len(),
+                    diff.upgraded.len()
+                );
+                Some(diff)
+            }
+            Err(e) => {
+                warn!("⚠️  Failed to fetch stored libraries, summary won't list library changes: {:?}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
 
-    // Step 5b: Collect library dependencies from manifests
-    let library_dependencies = collect_library_dependencies(&temp_repo.path)?;
-    info!("📦 Detected {} library dependencies", library_dependencies.len());
+    let job_record = neo4j_storage::JobRecord {
+        started_at,
+        files_processed: parsed_files.len(),
+        nodes_created: analysis.dep_graph.nodes.len(),
+        analyzed_commit: analyzed_commit.clone(),
+        library_diff,
+    };
+    let storage_started = std::time::Instant::now();
+    let dry_run = neo4j_storage::dry_run_requested(job.options.as_ref());
+    let storage_mode = neo4j_storage::StorageMode::for_job(incremental, preview_range.is_some(), dry_run);
+    let (partial_errors, storage_replays) = {
+        let store = {
+            let (parsed_files, dep_graph, boundary_result) = (&parsed_files, &analysis.dep_graph, &analysis.boundary_result);
+            let (library_dependencies, communication_analysis) = (&analysis.library_dependencies, &analysis.communication_analysis);
+            let (orm_models, ddl_tables, packages) = (&analysis.orm_models, &analysis.ddl_tables, &analysis.packages);
+            let (changed_files, removed_files, renamed_files) = (&changed_files, &removed_files, &renamed_files);
+            let (job_record, git_contributions) = (&job_record, analysis.git_contributions.as_ref());
+            let boundary_result = phases.runs(Phase::Boundaries).then_some(boundary_result);
+            let communication_analysis = phases.runs(Phase::Communication).then_some(communication_analysis);
+            let assets = analysis.asset_inventory.as_ref().map(|inventory| inventory.assets.as_slice());
+            let infrastructure = analysis.infrastructure.as_ref();
+            let codeowners = analysis.codeowners.as_ref();
+            let layer_violations = &analysis.layer_violations;
+            let storage_progress = &storage_progress;
+            let node_metadata = &node_metadata;
+            move |neo4j_graph: neo4rs::Graph| {
+                let batch_config = neo4j_storage::BatchConfig {
+                    batch_size: config.neo4j_batch_size,
+                    max_commits: config.git_max_commits,
+                    max_chunk_bytes: config.neo4j_max_chunk_bytes,
+                    cancellation: cancellation.clone(),
+                    progress: Some(storage_progress.clone()),
+                    node_metadata: node_metadata.clone(),
+                };
+                async move {
+                    if storage_mode == neo4j_storage::StorageMode::Incremental {
+                        neo4j_storage::store_graph_incremental(
+                            &neo4j_graph,
+                            &job.job_id,
+                            &job.repo_id,
+                            job_record,
+                            parsed_files,
+                            dep_graph,
+                            git_contributions,
+                            boundary_result,
+                            library_dependencies,
+                            communication_analysis,
+                            orm_models,
+                            ddl_tables,
+                            packages,
+                            assets,
+                            infrastructure,
+                            codeowners,
+                            changed_files,
+                            removed_files,
+                            renamed_files,
+                            Some(batch_config),
+                        ).await
+                    } else {
+                        neo4j_storage::store_graph(
+                            &neo4j_graph,
+                            &job.job_id,
+                            &job.repo_id,
+                            job_record,
+                            parsed_files,
+                            dep_graph,
+                            git_contributions,
+                            boundary_result,
+                            library_dependencies,
+                            communication_analysis,
+                            orm_models,
+                            ddl_tables,
+                            packages,
+                            assets,
+                            infrastructure,
+                            codeowners,
+                            layer_violations,
+                            Some(batch_config),
+                        ).await
+                    }
+                }
+            }
+        };
+        let stored = neo4j_storage::store_for_mode(storage_mode, neo4j_graph.clone(), store, || {
+            neo4j_pool.reconnect_for_job(job.options.as_ref())
+        })
+        .await?;
+        match storage_mode {
+            neo4j_storage::StorageMode::Full => info!("💾 Stored graph data in Neo4j (batch mode)"),
+            neo4j_storage::StorageMode::Incremental => info!("💾 Stored incremental graph update in Neo4j"),
+            neo4j_storage::StorageMode::Skipped if dry_run => info!("🧪 Dry run, skipping Neo4j writes"),
+            neo4j_storage::StorageMode::Skipped => info!("👀 Preview job, skipping Neo4j writes"),
+        }
+        stored
+    };
+    if !partial_errors.is_empty() {
+        warn!("⚠️  {} batches could not be stored", partial_errors.len());
+    }
+    timings.storage_ms = stage_timings::elapsed_ms(storage_started);
 
-    // Update progress: 60%
-    if let Err(e) = api_client.update_job(&job.job_id, JobUpdatePayload {
-        status: None,
-        progress: Some(60),
-        result_summary: None,
-        error: None,
-    }).await {
-        error!("Failed to update progress to 60%: {:?}", e);
+    // Only a full run sees the whole repository; dashboards read this
+    // instead of recomputing it from Neo4j, so a dry run leaves it alone
+    if storage_mode == neo4j_storage::StorageMode::Full {
+        let repo_summary = summary::build_repo_summary(
+            &job.repo_id,
+            &job.job_id,
+            chrono::Utc::now(),
+            &analysis.stats,
+            parsed_files.outlines(),
+            phases.runs(Phase::Boundaries).then_some(&analysis.boundary_result),
+            phases.runs(Phase::Dependencies).then_some(analysis.library_dependencies.len()),
+            phases.runs(Phase::Communication).then_some(&analysis.communication_analysis),
+        );
+        let stored = match redis_client.get_async_connection().await {
+            Ok(mut conn) => summary::store_repo_summary(&mut conn, &repo_summary).await,
+            Err(e) => Err(anyhow::Error::new(e).context("Failed to get Redis connection for repo summary")),
+        };
+        if let Err(e) = stored {
+            warn!("⚠️  Repo summary not updated for job {}: {:?}", job.job_id, e);
+        }
     }
 
-    // Step 5c: Detect communication patterns
-    let communication_analysis = communication_detector::CommunicationDetector::detect(&temp_repo.path, &parsed_files)?;
-    info!(
-        "Detected communication artifacts: {} endpoints, {} rpc services, {} queue usages, {} compose services",
-        communication_analysis.endpoints.len(),
-        communication_analysis.rpc_services.len(),
-        communication_analysis.queues.len(),
-        communication_analysis.compose_services.len()
-    );
+    // Update progress: 90% with every phase
+    worker_state::update(worker_state, |s| s.set_progress(&job.job_id, progress_plan.storage_end));
+    progress.progress(progress_plan.storage_end).await;
 
-    // Step 6: Build dependency graph
-    let dep_graph = graph_builder::DependencyGraph::from_parsed_files(&parsed_files, &symbol_table);
-    let stats = dep_graph.stats();
-    info!("🔗 Built dependency graph: {} nodes, {} edges", 
-          dep_graph.nodes.len(), 
-          dep_graph.edges.len());
-
-    // Update progress: 75%
-    if let Err(e) = api_client.update_job(&job.job_id, JobUpdatePayload {
-        status: None,
-        progress: Some(75),
-        result_summary: None,
-        error: None,
-    }).await {
-        error!("Failed to update progress to 75%: {:?}", e);
+    // Create result summary
+    let mut summary = pipeline::analysis_summary(
+        parsed_files.outlines(),
+        &parse_stats,
+        &analysis,
+        &settings,
+        analyzed_commit.as_deref(),
+    )?;
+    add_storage_to_summary(&mut summary, &partial_errors, storage_replays, dry_run)?;
+    warnings.add_to_summary(&mut summary);
+    if let Some(diff) = &job_record.library_diff {
+        summary["library_changes"] = serde_json::to_value(diff)?;
     }
 
-    // Step 7: Store in Neo4j (batch operations with transactions)
-    let (progress_tx, mut progress_rx) = tokio::sync::mpsc::channel::<i32>(100);
-    let worker_api = api_client.clone();
-    let worker_job_id = job.job_id.clone();
-    
+    if let Some(checked) = libraries_checked {
+        let vulnerable: Vec<&Vec<String>> = analysis
+            .library_dependencies
+            .iter()
+            .filter_map(|dep| dep.vulnerability_ids.as_ref())
+            .filter(|ids| !ids.is_empty())
+            .collect();
+        summary["vulnerabilities"] = serde_json::json!({
+            "libraries_checked": checked,
+            "vulnerable_libraries": vulnerable.len(),
+            "total": vulnerable.iter().map(|ids| ids.len()).sum::<usize>(),
+        });
+    }
+
+    // Full runs patch every parsed file so consumers can apply either kind
+    let patch_files = if incremental {
+        changed_files.clone()
+    } else {
+        parsed_files.outlines().iter().map(|file| file.path.clone()).collect()
+    };
+    let mut patch = build_graph_patch(
+        parsed_files.outlines(),
+        &analysis.dep_graph,
+        &analysis.packages,
+        &patch_files,
+        &removed_files,
+        previous_ids.as_ref(),
+    );
+    if let Some(impact) = &impact {
+        summary["impact"] = serde_json::json!({
+            "direct": impact.direct(),
+            "transitive_count": impact.impacted.len(),
+            "max_depth_reached": impact.max_depth_reached,
+        });
+        patch.impacted_files = impact.impacted.iter().map(|file| file.path.clone()).collect();
+    }
+    let payload = serde_json::to_string(&patch).context("Failed to serialize graph patch")?;
+    match patch_store::place_patch(
+        &job.job_id,
+        payload,
+        patch.nodes.len(),
+        patch.edges.len(),
+        config.graph_patch_max_bytes,
+    ) {
+        patch_store::PatchPlacement::Inline(payload) => add_inline_patch(&mut summary, &patch, &payload)?,
+        patch_store::PatchPlacement::Redis { payload, reference } => {
+            // The graph is already stored (or, for a dry run, never will
+            // be), so a lost patch only costs the consumer a full refetch
+            let stored = match redis_client.get_async_connection().await {
+                Ok(mut conn) => {
+                    patch_store::store_graph_patch(&mut conn, &reference.key, &payload, config.graph_patch_ttl_secs).await
+                }
+                Err(e) => Err(anyhow::Error::new(e).context("Failed to get Redis connection for graph patch")),
+            };
+            match stored {
+                Ok(()) => {
+                    info!("🧩 Graph patch ({} bytes) stored in Redis as {}", reference.size_bytes, reference.key);
+                    summary["graph_patch_ref"] = serde_json::to_value(&reference)?;
+                }
+                Err(e) => warn!("⚠️  Dropping graph patch for job {}: {:?}", job.job_id, e),
+            }
+        }
+    }
+
+    if let Some(raw) = job.options.as_ref().and_then(|opts| opts.get("export_format")) {
+        match graph_export::ExportFormat::parse(raw) {
+            Some(format) => match export_graph(progress.api_client(), &job.job_id, &analysis.dep_graph, format).await {
+                Ok(name) => {
+                    summary["graph_export"] = serde_json::json!({ "artifact": name, "format": format.as_str() });
+                }
+                Err(e) => warn!("⚠️  Graph export failed for job {}: {:?}", job.job_id, e),
+            },
+            None => warn!("⚠️  Ignoring unknown export_format {:?}", raw),
+        }
+    }
+
+    if let (Some(range), Some(diff)) = (preview_range, range_diff) {
+        let (repo_path, boundary_result) = (temp_repo.path.clone(), analysis.boundary_result);
+        let impact = run_blocking(move || {
+            let analyzer = git_analyzer::GitAnalyzer::new(&repo_path)?;
+            Ok(pr_preview::compute_impact(&range, &diff, parsed_files.outlines(), &boundary_result, |path| {
+                analyzer.file_content_at(&range.base_sha, path).ok().flatten()
+            }))
+        })
+        .await?;
+        summary["preview"] = serde_json::to_value(&impact)?;
+    }
+
+    timings.total_ms = stage_timings::elapsed_ms(started);
+    timings.log();
+    worker_metrics::metrics().stage_timings(&timings);
+    summary["timings"] = serde_json::to_value(&timings)?;
+    
+    Ok(summary)
+}
+
+/// What storage did, or for a dry run skipped, in the result summary
+fn add_storage_to_summary(
+    summary: &mut serde_json::Value,
+    partial_errors: &[neo4j_storage::PartialError],
+    storage_replays: u32,
+    dry_run: bool,
+) -> Result<()> {
+    summary["partial_errors"] = serde_json::to_value(partial_errors)?;
+    summary["storage_replays"] = serde_json::json!(storage_replays);
+    summary["dry_run"] = serde_json::json!(dry_run);
+    Ok(())
+}
+
+/// A graph patch small enough to travel in the result summary, with the ids
+/// of its nodes and edges
+fn add_inline_patch(summary: &mut serde_json::Value, patch: &GraphPatch, payload: &str) -> Result<()> {
+    summary["graph_patch"] = serde_json::from_str(payload)?;
+    summary["changed_nodes"] = serde_json::to_value(patch.nodes.iter().map(|node| node.id.clone()).collect::<Vec<_>>())?;
+    summary["changed_edges"] = serde_json::to_value(patch.edges.iter().map(|edge| edge.id.clone()).collect::<Vec<_>>())?;
+    Ok(())
+}
+
+/// Write the graph export to a temp file and upload it as a job artifact,
+/// returning the artifact name
+async fn export_graph(
+    api_client: &ApiClient,
+    job_id: &str,
+    dep_graph: &graph_builder::DependencyGraph,
+    format: graph_export::ExportFormat,
+) -> Result<String> {
+    let name = format.artifact_name();
+    let path = env::temp_dir().join(format!("{}-{}", job_id, name));
+    fs::write(&path, graph_export::export(dep_graph, format))
+        .with_context(|| format!("Failed to write graph export to {:?}", path))?;
+
+    let bytes = fs::read(&path).with_context(|| format!("Failed to read graph export {:?}", path));
+    let _ = fs::remove_file(&path);
+    api_client.upload_artifact(job_id, &name, bytes?).await?;
+    Ok(name)
+}
+
+/// Forward progress percentages sent from a pipeline stage to the worker
+/// state and the API. Stops once every sender is dropped.
+fn spawn_progress_forwarder(
+    progress: &ProgressUpdater,
+    worker_state: &SharedWorkerState,
+    job_id: &str,
+) -> tokio::sync::mpsc::Sender<i32> {
+    let (progress_tx, mut progress_rx) = tokio::sync::mpsc::channel::<i32>(100);
+    let progress = progress.clone();
+    let worker_job_id = job_id.to_string();
+    let progress_state = worker_state.clone();
+
     tokio::spawn(async move {
         while let Some(p) = progress_rx.recv().await {
-            let _ = worker_api.update_job(&worker_job_id, JobUpdatePayload {
-                status: None,
-                progress: Some(p),
-                result_summary: None,
-                error: None,
-            }).await;
+            worker_state::update(&progress_state, |s| s.set_progress(&worker_job_id, p));
+            progress.progress(p).await;
         }
     });
 
-    let batch_config = neo4j_storage::BatchConfig { 
-        batch_size: neo4j_batch_size 
-    };
+    progress_tx
+}
 
-    if incremental {
-        neo4j_storage::store_graph_incremental(
-            neo4j_graph,
-            &job.job_id,
-            &job.repo_id,
-            &parsed_files,
-            &dep_graph,
-            git_contributions.as_ref(),
-            &boundary_result,
-            &library_dependencies,
-            &communication_analysis,
-            &changed_files,
-            &removed_files,
-            Some(batch_config),
-            Some(progress_tx.clone()),
-        ).await?;
-        info!("💾 Stored incremental graph update in Neo4j");
-    } else {
-        neo4j_storage::store_graph(
-            neo4j_graph,
-            &job.job_id,
-            &job.repo_id,
-            &parsed_files,
-            &dep_graph,
-            git_contributions.as_ref(),
-            &boundary_result,
-            &library_dependencies,
-            &communication_analysis,
-            Some(batch_config),
-            Some(progress_tx.clone()),
-        ).await?;
-        info!("💾 Stored graph data in Neo4j (batch mode)");
-    }
-
-    // Update progress: 90%
-    if let Err(e) = api_client.update_job(&job.job_id, JobUpdatePayload {
-        status: None,
-        progress: Some(90),
-        result_summary: None,
-        error: None,
-    }).await {
-        error!("Failed to update progress to 90%: {:?}", e);
-    }
+/// Least time between two storage progress updates sent to the API
+const STORAGE_PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3);
 
-    // Create result summary
-    let mut summary = serde_json::json!({
-        "total_files": parsed_files.len(),
-        "total_functions": stats.functions,
-        "total_classes": stats.classes,
-        "dependencies": stats.imports_edges,
-        "complexity_score": 0.0, // Placeholder
-        "languages": {} // Placeholder
-    });
+/// Forwards Neo4j storage progress within the plan's storage band (75–90%
+/// with every phase), at most once every `STORAGE_PROGRESS_INTERVAL`
+struct StorageProgress {
+    tx: tokio::sync::mpsc::Sender<i32>,
+    plan: phases::ProgressPlan,
+    last_sent: std::sync::Mutex<Option<(std::time::Instant, i32)>>,
+}
 
-    if let Some(contributions) = git_contributions.as_ref() {
-        summary["commit_history"] = serde_json::to_value(&contributions.commits)?;
-        summary["commit_history_total"] = serde_json::json!(contributions.total_commits);
-        summary["commit_history_count"] = serde_json::json!(contributions.commits.len());
-        summary["commit_history_truncated"] = serde_json::json!(contributions.commits.len() < contributions.total_commits);
-        summary["commit_history_limit"] = serde_json::json!(git_max_commits);
+impl StorageProgress {
+    fn new(tx: tokio::sync::mpsc::Sender<i32>, plan: phases::ProgressPlan) -> Self {
+        Self { tx, plan, last_sent: std::sync::Mutex::new(None) }
     }
+}
 
-    if incremental {
-        let patch = build_graph_patch(&parsed_files, &dep_graph, &changed_files, &removed_files);
-        summary["graph_patch"] = serde_json::to_value(&patch)?;
-        summary["changed_nodes"] = serde_json::to_value(
-            patch.nodes.iter().map(|node| node.id.clone()).collect::<Vec<_>>()
-        )?;
-        summary["changed_edges"] = serde_json::to_value(
-            patch.edges.iter().map(|edge| edge.id.clone()).collect::<Vec<_>>()
-        )?;
+impl neo4j_storage::ProgressReporter for StorageProgress {
+    fn report(&self, fraction: f64) {
+        let progress = self.plan.storage(fraction);
+        let now = std::time::Instant::now();
+        let mut last_sent = self.last_sent.lock().unwrap();
+        if should_send_progress(*last_sent, now, progress) && self.tx.try_send(progress).is_ok() {
+            *last_sent = Some((now, progress));
+        }
     }
-    
-    Ok(summary)
+}
+
+/// Whether `progress` is worth sending given the last value sent: only when
+/// it went up and the interval has passed
+fn should_send_progress(last_sent: Option<(std::time::Instant, i32)>, now: std::time::Instant, progress: i32) -> bool {
+    match last_sent {
+        Some((at, sent)) => progress > sent && now.duration_since(at) >= STORAGE_PROGRESS_INTERVAL,
+        None => true,
+    }
+}
+
+/// Run blocking git2/tree-sitter work on the blocking pool so concurrent
+/// jobs and the heartbeat keep running
+async fn run_blocking<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await.context("Blocking task panicked")?
 }
 
 fn clone_repository(
     repo_url: &str, 
     branch: &str,
-    options: &Option<HashMap<String, String>>
+    options: &Option<HashMap<String, String>>,
+    default_depth: u32,
+    ssh: &git_auth::SshSettings,
+    progress_tx: Option<tokio::sync::mpsc::Sender<i32>>,
 ) -> Result<TempRepo> {
+    let depth = clone_depth(options, default_depth);
+    if depth > 0 {
+        return shallow_clone_repository(repo_url, branch, options, depth, ssh, progress_tx);
+    }
+
     // Generate unique temporary directory
     let tmp_dir = env::temp_dir().join(format!("archmind-repo-{}", Uuid::new_v4()));
     info!("🚀 Cloning {} (branch: {}) to {:?}", repo_url, branch, tmp_dir);
 
-    // Prepare callbacks for authentication
-    let mut callbacks = RemoteCallbacks::new();
-    
-    // Check for git token in options
-    if let Some(opts) = options {
-        if let Some(token) = opts.get("git_token") {
-            callbacks.credentials(move |_url, _username_from_url, _allowed_types| {
-                Cred::userpass_plaintext("x-access-token", token)
-            });
-        }
-    }
-
-    let mut fetch_options = FetchOptions::new();
-    fetch_options.remote_callbacks(callbacks);
-
+    let fetch_options = fetch_options(options, ssh, progress_tx);
     let mut builder = git2::build::RepoBuilder::new();
     builder.fetch_options(fetch_options);
 
@@ -691,10 +1637,84 @@ fn clone_repository(
     let repo = builder.clone(repo_url, &tmp_dir)
         .context("Failed to clone repository")?;
 
-    // Checkout specific branch if not default
-    let head = repo.head().context("Failed to get HEAD")?;
+    let fallback_branch = checkout_branch(&repo, branch)?;
+
+    Ok(TempRepo { path: tmp_dir, owned: true, fallback_branch })
+}
+
+/// How a job may check out a commit (`commit_sha`, or a preview's
+/// `head_sha`) in its repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CommitCheckout {
+    /// The worker's own clone, whose working tree is overwritten
+    Force,
+    /// A local workspace with `local_checkout`; a checkout that would
+    /// overwrite uncommitted changes fails instead
+    Safe,
+    /// A local workspace without `local_checkout`, whose HEAD never moves
+    Refused,
+}
+
+impl CommitCheckout {
+    fn for_repo(temp_repo: &TempRepo, options: &Option<HashMap<String, String>>) -> Self {
+        if temp_repo.owned {
+            CommitCheckout::Force
+        } else if local_checkout_requested(options) {
+            CommitCheckout::Safe
+        } else {
+            CommitCheckout::Refused
+        }
+    }
+}
+
+fn checkout_commit(path: &Path, sha: &str, checkout: CommitCheckout) -> Result<()> {
+    let discard_changes = match checkout {
+        CommitCheckout::Force => true,
+        CommitCheckout::Safe => false,
+        CommitCheckout::Refused => anyhow::bail!(
+            "Cannot check out commit {} in the local workspace {:?}; set the local_checkout option to \"true\" to allow it",
+            sha,
+            path
+        ),
+    };
+    git_analyzer::GitAnalyzer::new(path)?.checkout_commit(sha, discard_changes)
+}
+
+/// Check out `commit_sha`, when given, as a detached HEAD so the analysis
+/// and the history walk match that exact commit, then return the commit
+/// HEAD is on. None for a local workspace that isn't a git repository or has
+/// no commits.
+fn checkout_analyzed_commit(path: &Path, commit_sha: Option<&str>, checkout: CommitCheckout) -> Result<Option<String>> {
+    let commit_sha = commit_sha.map(str::trim).filter(|sha| !sha.is_empty());
+    let repo = match git2::Repository::open(path) {
+        Ok(repo) => repo,
+        Err(e) => match commit_sha {
+            Some(sha) => anyhow::bail!("Cannot check out commit {}: {:?} is not a git repository ({})", sha, path, e),
+            None => return Ok(None),
+        },
+    };
+    if let Some(sha) = commit_sha {
+        checkout_commit(path, sha, checkout)?;
+    }
+    Ok(repo.head().ok().and_then(|head| head.target()).map(|oid| oid.to_string()))
+}
+
+/// Check out `branch` unless HEAD is already on it, falling back to
+/// master/main when it doesn't exist; returns the fallback checked out. A
+/// repository without commits has nothing to switch to, so its (empty)
+/// working tree is kept as is.
+fn checkout_branch(repo: &git2::Repository, branch: &str) -> Result<Option<String>> {
+    let head = match repo.head() {
+        Ok(head) => head,
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {
+            warn!("⚠️  Repository has no commits yet, not switching to branch {}", branch);
+            return Ok(None);
+        }
+        Err(e) => return Err(e).context("Failed to get HEAD"),
+    };
     let head_name = head.shorthand().unwrap_or("master");
 
+    let mut fallback = None;
     if head_name != branch {
         info!("🔀 Switching to branch: {}", branch);
         
@@ -712,25 +1732,33 @@ fn clone_repository(
         for branch_name in &branches_to_try {
             if let Ok(result) = repo.revparse_ext(branch_name) {
                 info!("✅ Found branch: {}", branch_name);
-                found = Some(result);
+                found = Some((branch_name, result));
                 break;
             }
         }
         
-        let (object, reference) = found
+        let (branch_name, (object, reference)) = found
             .ok_or_else(|| anyhow::anyhow!("No valid branch found. Tried: {:?}", branches_to_try))?;
+        if !branches_to_try[..2].contains(branch_name) {
+            warn!("⚠️  Branch {} not found, analyzing {} instead", branch, branch_name);
+            fallback = Some(branch_name.clone());
+        }
+        // An annotated tag resolves to the tag object, not the commit it tags
+        let commit = object
+            .peel_to_commit()
+            .with_context(|| format!("{} does not point to a commit", branch))?;
 
-        repo.checkout_tree(&object, None)
+        repo.checkout_tree(commit.as_object(), None)
             .context("Failed to checkout branch tree")?;
 
-        match reference {
+        match reference.filter(|gref| gref.is_branch()) {
             Some(gref) => {
                 repo.set_head(gref.name().unwrap())
                     .context("Failed to set HEAD")?;
             }
             None => {
-                // If it's a commit hash or tag without ref
-                repo.set_head_detached(object.id())
+                // A tag, remote branch or commit hash
+                repo.set_head_detached(commit.id())
                      .context("Failed to set HEAD detached")?;
             }
         }
@@ -738,134 +1766,641 @@ fn clone_repository(
         info!("✅ Already on branch: {}", head_name);
     }
 
-    Ok(TempRepo { path: tmp_dir })
+    Ok(fallback)
 }
 
-fn parse_repository(repo_path: &std::path::PathBuf) -> Result<Vec<ParsedFile>> {
-    let mut parsed_files = Vec::new();
-    
-    // Initialize parsers
-    let js_parser = JavaScriptParser::new()?;
-    let ts_parser = TypeScriptParser::new()?;
-    let rust_parser = RustParser::new()?;
-    let go_parser = GoParser::new()?;
-    let py_parser = PythonParser::new()?;
-    
-    // Walk directory tree
-    walk_directory(
-        repo_path,
-        repo_path, // Pass root directory
-        &mut parsed_files, 
-        &js_parser, 
-        &ts_parser,
-        &rust_parser,
-        &go_parser,
-        &py_parser
-    )?;
-    
+/// Directory to analyze in place instead of cloning: the `local_path` job
+/// option, or the path of a `file://` repo URL
+fn local_repo_path(repo_url: &str, options: &Option<HashMap<String, String>>) -> Option<PathBuf> {
+    options
+        .as_ref()
+        .and_then(|opts| opts.get("local_path"))
+        .map(|path| path.trim())
+        .filter(|path| !path.is_empty())
+        .or_else(|| repo_url.strip_prefix("file://"))
+        .map(PathBuf::from)
+}
+
+/// `path` resolved, with symlinks followed, if it lies inside the
+/// `LOCAL_WORKSPACE_ROOT` directory `root`. Jobs can't reach local paths at
+/// all without one.
+fn confine_local_path(path: &Path, root: Option<&Path>) -> Result<PathBuf> {
+    let Some(root) = root else {
+        anyhow::bail!("Local path {:?} refused: set LOCAL_WORKSPACE_ROOT to allow local workspaces", path);
+    };
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("LOCAL_WORKSPACE_ROOT {:?} does not exist", root))?;
+    let resolved = path
+        .canonicalize()
+        .with_context(|| format!("Local path {:?} does not exist or is not a directory", path))?;
+    if !resolved.starts_with(&root) {
+        anyhow::bail!("Local path {:?} is outside LOCAL_WORKSPACE_ROOT {:?}", path, root);
+    }
+    Ok(resolved)
+}
+
+/// Analyze an existing checkout, such as a CI workspace, without copying or
+/// deleting it. The job's branch is only checked out when the
+/// `local_checkout` option is "true" and the path is a git repository.
+fn open_local_repository(
+    path: &Path,
+    branch: &str,
+    options: &Option<HashMap<String, String>>,
+) -> Result<TempRepo> {
+    if !path.is_dir() {
+        anyhow::bail!("Local path {:?} does not exist or is not a directory", path);
+    }
+    info!("📂 Using local workspace {:?}", path);
+
+    let mut fallback_branch = None;
+    if local_checkout_requested(options) && !branch.is_empty() {
+        match git2::Repository::open(path) {
+            Ok(repo) => fallback_branch = checkout_branch(&repo, branch)?,
+            Err(e) => warn!("⚠️  {:?} is not a git repository, analyzing as is: {}", path, e),
+        }
+    }
+
+    Ok(TempRepo { path: path.to_path_buf(), owned: false, fallback_branch })
+}
+
+/// Whether the job may switch a local workspace's branch or commit
+fn local_checkout_requested(options: &Option<HashMap<String, String>>) -> bool {
+    options
+        .as_ref()
+        .and_then(|opts| opts.get("local_checkout"))
+        .is_some_and(|value| value == "true")
+}
+
+/// History cutoff and walk limit from the `git_history_days` and
+/// `git_max_commits` job options; zero or unparsable values mean no limit
+fn git_history_limits(
+    options: &Option<HashMap<String, String>>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> (Option<chrono::DateTime<chrono::Utc>>, Option<usize>) {
+    let positive = |key: &str| {
+        options
+            .as_ref()
+            .and_then(|opts| opts.get(key))
+            .and_then(|value| value.trim().parse::<u32>().ok())
+            .filter(|value| *value > 0)
+    };
+    let since = positive("git_history_days").map(|days| now - chrono::Duration::days(days.into()));
+    (since, positive("git_max_commits").map(|max| max as usize))
+}
+
+/// Clone depth from the `clone_depth` job option, else the configured default.
+/// 0 means full history.
+fn clone_depth(options: &Option<HashMap<String, String>>, default_depth: u32) -> u32 {
+    options
+        .as_ref()
+        .and_then(|opts| opts.get("clone_depth"))
+        .and_then(|value| value.trim().parse::<u32>().ok())
+        .unwrap_or(default_depth)
+}
+
+/// Job progress while a clone has fetched `received` and indexed `indexed`
+/// of `total` objects: downloading and indexing each take half of 0-25%
+fn clone_progress(received: usize, indexed: usize, total: usize) -> i32 {
+    if total == 0 {
+        return 0;
+    }
+    ((received + indexed).min(2 * total) * CLONE_PROGRESS_END as usize / (2 * total)) as i32
+}
+
+/// Job progress once the clone is done and parsing starts
+const CLONE_PROGRESS_END: i32 = 25;
+
+/// Credential requests answered per fetch: a user name, a key or token, and
+/// one retry
+const MAX_CREDENTIAL_REQUESTS: u32 = 3;
+
+/// Fetch options offering the job's credentials, see `git_auth`, and
+/// sending clone progress to `progress_tx` whenever the percentage changes
+fn fetch_options<'a>(
+    options: &'a Option<HashMap<String, String>>,
+    ssh: &'a git_auth::SshSettings,
+    progress_tx: Option<tokio::sync::mpsc::Sender<i32>>,
+) -> FetchOptions<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+
+    if let Some(tx) = progress_tx {
+        let mut last_sent = -1;
+        callbacks.transfer_progress(move |stats| {
+            let progress = clone_progress(stats.received_objects(), stats.indexed_objects(), stats.total_objects());
+            if progress != last_sent {
+                // A full channel only means a skipped update
+                let _ = tx.try_send(progress);
+                last_sent = progress;
+            }
+            true
+        });
+    }
+
+    // libgit2 asks again after a rejected credential; stop instead of
+    // offering the same one forever
+    let mut requests = 0;
+    callbacks.credentials(move |_url, username_from_url, allowed_types| {
+        requests += 1;
+        if requests > MAX_CREDENTIAL_REQUESTS {
+            return Err(git2::Error::from_str("Authentication failed: the remote rejected the configured credentials"));
+        }
+        if allowed_types.contains(git2::CredentialType::USERNAME) {
+            return Cred::username(git_auth::ssh_username(username_from_url));
+        }
+        match git_auth::select_credential(options.as_ref(), ssh, allowed_types) {
+            Some(credential) => credential.to_cred(username_from_url),
+            None => Err(git2::Error::from_str("Authentication required, but the job has no credentials for this remote")),
+        }
+    });
+
+    if !ssh.strict_host_key {
+        callbacks.certificate_check(|cert, _host| {
+            Ok(if cert.as_hostkey().is_some() {
+                CertificateCheckStatus::CertificateOk
+            } else {
+                CertificateCheckStatus::CertificatePassthrough
+            })
+        });
+    }
+
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+    fetch_options
+}
+
+/// Clone only the last `depth` commits of a single branch. Like the full clone,
+/// falls back to main/master when the requested branch doesn't exist.
+fn shallow_clone_repository(
+    repo_url: &str,
+    branch: &str,
+    options: &Option<HashMap<String, String>>,
+    depth: u32,
+    ssh: &git_auth::SshSettings,
+    progress_tx: Option<tokio::sync::mpsc::Sender<i32>>,
+) -> Result<TempRepo> {
+    let mut candidates = vec![branch.to_string()];
+    for fallback in ["main", "master"] {
+        if fallback != branch {
+            candidates.push(fallback.to_string());
+        }
+    }
+
+    let mut last_error = None;
+    for candidate in &candidates {
+        let tmp_dir = env::temp_dir().join(format!("archmind-repo-{}", Uuid::new_v4()));
+        info!("🚀 Shallow cloning {} (branch: {}, depth: {}) to {:?}", repo_url, candidate, depth, tmp_dir);
+
+        let mut fetch_options = fetch_options(options, ssh, progress_tx.clone());
+        fetch_options.depth(depth.min(i32::MAX as u32) as i32);
+
+        // Fetch just this branch instead of every remote head
+        let refspec = format!("+refs/heads/{0}:refs/remotes/origin/{0}", candidate);
+        let mut builder = git2::build::RepoBuilder::new();
+        builder
+            .fetch_options(fetch_options)
+            .branch(candidate)
+            .remote_create(move |repo, name, url| repo.remote_with_fetch(name, url, &refspec));
+
+        match builder.clone(repo_url, &tmp_dir) {
+            Ok(_) => {
+                info!("✅ Shallow clone of branch {} complete", candidate);
+                let fallback_branch = (candidate != branch).then(|| candidate.clone());
+                return Ok(TempRepo { path: tmp_dir, owned: true, fallback_branch });
+            }
+            Err(e) => {
+                warn!("⚠️  Shallow clone of branch {} failed: {}", candidate, e);
+                let _ = fs::remove_dir_all(&tmp_dir);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Err(anyhow::anyhow!(
+        "No valid branch found for shallow clone. Tried: {:?}. Last error: {:?}",
+        candidates,
+        last_error
+    ))
+}
+
+/// Parse every file into memory; jobs and the `analyze` command go through
+/// `parse_repository_source`
+#[cfg(test)]
+fn parse_repository(
+    repo_path: &PathBuf,
+    concurrency: usize,
+    limits: &FileLimits,
+    filter: &PathFilter,
+) -> Result<(Vec<ParsedFile>, ParseStats)> {
+    // Collect candidate paths first so parsing can be spread across workers,
+    // then order by path so the output doesn't depend on scheduling
+    let mut stats = ParseStats::default();
+    let mut paths = Vec::new();
+    collect_source_files(repo_path, repo_path, &mut paths, limits, filter, &mut stats)?;
+    parse_limits::apply_file_limit(&mut paths, limits, &mut stats);
+
+    let mut parsed_files = parse_files_parallel(repo_path, &paths, concurrency, limits, &mut stats)?;
+    parsed_files.sort_by(|a, b| a.path.cmp(&b.path));
+
     info!("📄 Successfully parsed {} files", parsed_files.len());
-    Ok(parsed_files)
+    Ok((parsed_files, stats))
 }
 
-fn parse_repository_subset(repo_path: &PathBuf, files: &[String]) -> Result<Vec<ParsedFile>> {
-    let mut parsed_files = Vec::new();
+/// Parse every supported file under `repo_path`, in path order. With more
+/// than `stream_threshold` candidate files the results go to a spill file,
+/// `chunk_files` at a time, and only their outlines stay in memory.
+fn parse_repository_source(
+    repo_path: &PathBuf,
+    concurrency: usize,
+    limits: &FileLimits,
+    filter: &PathFilter,
+    stream_threshold: usize,
+    chunk_files: usize,
+) -> Result<(ParsedFileSource, ParseStats)> {
+    let mut stats = ParseStats::default();
+    let mut paths = Vec::new();
+    collect_source_files(repo_path, repo_path, &mut paths, limits, filter, &mut stats)?;
+    parse_limits::apply_file_limit(&mut paths, limits, &mut stats);
+    if paths.len() <= stream_threshold {
+        let mut parsed_files = parse_files_parallel(repo_path, &paths, concurrency, limits, &mut stats)?;
+        parsed_files.sort_by(|a, b| a.path.cmp(&b.path));
+        info!("📄 Successfully parsed {} files", parsed_files.len());
+        return Ok((ParsedFileSource::Memory(parsed_files), stats));
+    }
 
-    let js_parser = JavaScriptParser::new()?;
-    let ts_parser = TypeScriptParser::new()?;
-    let rust_parser = RustParser::new()?;
-    let go_parser = GoParser::new()?;
-    let py_parser = PythonParser::new()?;
+    // Chunks come out in path order when the paths go in sorted the way the
+    // parsed files are
+    paths.sort_by_cached_key(|path| repo_relative_path(repo_path, path));
+    let pool = parser_pool(concurrency)?;
+    let mut writer = parsed_source::SpillWriter::create(chunk_files)?;
+    let mut outlines = Vec::new();
+    for chunk in paths.chunks(chunk_files.max(1)) {
+        for file in parse_in_pool(&pool, repo_path, chunk, limits, &mut stats)? {
+            writer.push(&file)?;
+            outlines.push(file.into_outline());
+        }
+    }
+    info!("📄 Successfully parsed {} files", outlines.len());
+    Ok((ParsedFileSource::Spilled { spill: writer.finish()?, outlines }, stats))
+}
 
+fn parse_repository_subset(
+    repo_path: &Path,
+    files: &[String],
+    concurrency: usize,
+    limits: &FileLimits,
+    filter: &PathFilter,
+) -> Result<(Vec<ParsedFile>, ParseStats)> {
+    let mut stats = ParseStats::default();
+    let root = repo_path.canonicalize().unwrap_or_else(|_| repo_path.to_path_buf());
+    let mut candidates = Vec::new();
     for file in files {
-        let normalized = file.replace("\\", "/");
-        let abs_path = repo_path.join(&normalized);
-        if !abs_path.is_file() {
-            continue;
+        match repo_paths::normalize_repo_path(file) {
+            Ok(file) => candidates.push(repo_path.join(file)),
+            Err(e) => stats.skip(file.clone(), format!("{:#}", e)),
         }
+    }
+    let mut paths: Vec<PathBuf> = candidates
+        .into_iter()
+        .filter(|abs_path| {
+            if !filter.matches(&repo_relative_path(repo_path, abs_path)) {
+                stats.excluded_files += 1;
+                return false;
+            }
+            if abs_path.is_symlink() {
+                stats.skip(repo_relative_path(repo_path, abs_path), "symlink");
+                return false;
+            }
+            // A symlinked directory higher up can still lead out of the checkout
+            if abs_path.canonicalize().is_ok_and(|real| !real.starts_with(&root)) {
+                stats.skip(repo_relative_path(repo_path, abs_path), "outside repository");
+                return false;
+            }
+            abs_path.is_file() && !exceeds_size_limit(repo_path, abs_path, limits, &mut stats)
+        })
+        .collect();
+    parse_limits::apply_file_limit(&mut paths, limits, &mut stats);
 
-        let relative_path_buf = PathBuf::from(&normalized);
-        let ext = abs_path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
+    let mut parsed_files = parse_files_parallel(repo_path, &paths, concurrency, limits, &mut stats)?;
+    parsed_files.sort_by(|a, b| a.path.cmp(&b.path));
 
-        let parsed = match ext.as_str() {
-            "js" | "jsx" | "mjs" => match fs::read_to_string(&abs_path) {
-                Ok(content) => js_parser.parse_file(&relative_path_buf, &content).ok(),
-                Err(e) => {
-                    warn!("⚠️  Failed to read file {:?}: {}", abs_path, e);
-                    None
-                }
-            },
-            "ts" | "tsx" => match fs::read_to_string(&abs_path) {
-                Ok(content) => ts_parser.parse_file(&relative_path_buf, &content).ok(),
-                Err(e) => {
-                    warn!("⚠️  Failed to read file {:?}: {}", abs_path, e);
-                    None
-                }
-            },
-            "rs" => match fs::read_to_string(&abs_path) {
-                Ok(content) => rust_parser.parse_file(&relative_path_buf, &content).ok(),
-                Err(e) => {
-                    warn!("⚠️  Failed to read file {:?}: {}", abs_path, e);
-                    None
-                }
-            },
-            "go" => match fs::read_to_string(&abs_path) {
-                Ok(content) => go_parser.parse_file(&relative_path_buf, &content).ok(),
-                Err(e) => {
-                    warn!("⚠️  Failed to read file {:?}: {}", abs_path, e);
-                    None
-                }
-            },
-            "py" => match fs::read_to_string(&abs_path) {
-                Ok(content) => py_parser.parse_file(&relative_path_buf, &content).ok(),
-                Err(e) => {
-                    warn!("⚠️  Failed to read file {:?}: {}", abs_path, e);
-                    None
-                }
-            },
-            _ => None,
-        };
+    info!("📄 Incremental parse: {} files", parsed_files.len());
+    Ok((parsed_files, stats))
+}
+
+/// Parse `paths` on a pool of `concurrency` threads. Each worker owns its own
+/// `ParserSet`, and results keep the order of `paths`. Binary files, and
+/// generated ones when `limits` skips them, are counted in `stats`.
+pub(crate) fn parse_files_parallel(
+    root_dir: &Path,
+    paths: &[PathBuf],
+    concurrency: usize,
+    limits: &FileLimits,
+    stats: &mut ParseStats,
+) -> Result<Vec<ParsedFile>> {
+    let pool = parser_pool(concurrency)?;
+    parse_in_pool(&pool, root_dir, paths, limits, stats)
+}
+
+fn parser_pool(concurrency: usize) -> Result<rayon::ThreadPool> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(concurrency.max(1))
+        .build()
+        .context("Failed to build parser thread pool")
+}
+
+fn parse_in_pool(
+    pool: &rayon::ThreadPool,
+    root_dir: &Path,
+    paths: &[PathBuf],
+    limits: &FileLimits,
+    stats: &mut ParseStats,
+) -> Result<Vec<ParsedFile>> {
+    // A thread without parsers would leave its files out of the graph
+    // unnoticed, so that fails the whole parse
+    let parsed: Vec<SourceFile> = pool.install(|| {
+        paths
+            .par_iter()
+            .map_init(ParserSet::new, |parsers, path| match parsers {
+                Ok(parsers) => Ok(parse_source_file(root_dir, path, parsers, limits.skip_generated)),
+                Err(e) => Err(anyhow::anyhow!("Failed to create the parsers: {:#}", e)),
+            })
+            .collect::<Result<_>>()
+    })?;
+
+    Ok(collect_parsed(parsed, stats))
+}
 
-        if let Some(parsed) = parsed {
-            parsed_files.push(parsed);
+fn collect_parsed(results: Vec<SourceFile>, stats: &mut ParseStats) -> Vec<ParsedFile> {
+    let mut parsed_files = Vec::new();
+    for result in results {
+        match result {
+            SourceFile::Parsed(parsed_file) => parsed_files.push(*parsed_file),
+            SourceFile::Binary(path) => {
+                stats.skipped_binary_files += 1;
+                stats.skip(path, "binary");
+            }
+            SourceFile::Generated(path) => {
+                stats.skipped_generated_files += 1;
+                stats.skip(path, "generated");
+            }
+            SourceFile::Failed(parsed_file, error) => {
+                stats.parse_errors.push(ParseError { path: parsed_file.path.clone(), error });
+                parsed_files.push(*parsed_file);
+            }
+            SourceFile::Unparsed => {}
         }
     }
+    parsed_files
+}
 
-    info!("📄 Incremental parse: {} files", parsed_files.len());
-    Ok(parsed_files)
+/// Lines of test code per line of production code, rounded to two places;
+/// 0 without production code
+fn test_to_code_ratio(parsed_files: &[ParsedFile]) -> f64 {
+    let (test_loc, code_loc) = parsed_files.iter().fold((0, 0), |(test, code), f| {
+        if f.is_test {
+            (test + f.line_count, code)
+        } else {
+            (test, code + f.line_count)
+        }
+    });
+    if code_loc == 0 {
+        return 0.0;
+    }
+    (test_loc as f64 / code_loc as f64 * 100.0).round() / 100.0
+}
+
+/// Per-language file, symbol and line totals, plus each language's share of files.
+/// Lines are each file's full `line_count`.
+fn language_breakdown(parsed_files: &[ParsedFile]) -> serde_json::Value {
+    let mut totals: std::collections::BTreeMap<&str, [usize; 4]> = std::collections::BTreeMap::new();
+
+    for file in parsed_files {
+        let methods: usize = file.classes.iter().map(|c| c.methods.len()).sum();
+
+        let entry = totals.entry(file.language.as_str()).or_default();
+        entry[0] += 1;
+        entry[1] += file.functions.len() + methods;
+        entry[2] += file.classes.len();
+        entry[3] += file.line_count;
+    }
+
+    let total_files = parsed_files.len().max(1) as f64;
+    let languages: serde_json::Map<String, serde_json::Value> = totals
+        .into_iter()
+        .map(|(language, [files, functions, classes, lines])| {
+            let percentage = (files as f64 / total_files * 1000.0).round() / 10.0;
+            (
+                language.to_string(),
+                serde_json::json!({
+                    "files": files,
+                    "functions": functions,
+                    "classes": classes,
+                    "lines": lines,
+                    "percentage": percentage,
+                }),
+            )
+        })
+        .collect();
+
+    serde_json::Value::Object(languages)
+}
+
+/// Lines of code per language
+fn loc_by_language(parsed_files: &[ParsedFile]) -> std::collections::BTreeMap<&str, usize> {
+    let mut totals = std::collections::BTreeMap::new();
+    for file in parsed_files {
+        *totals.entry(file.language.as_str()).or_default() += file.line_count;
+    }
+    totals
+}
+
+/// Average functions (including methods) per file, rounded to two decimals
+fn complexity_score(parsed_files: &[ParsedFile]) -> f64 {
+    let handwritten: Vec<&ParsedFile> = parsed_files.iter().filter(|f| !f.generated).collect();
+    if handwritten.is_empty() {
+        return 0.0;
+    }
+    let functions: usize = handwritten
+        .iter()
+        .map(|f| f.functions.len() + f.classes.iter().map(|c| c.methods.len()).sum::<usize>())
+        .sum();
+    (functions as f64 / handwritten.len() as f64 * 100.0).round() / 100.0
 }
 
-fn extract_webhook_changes(options: &Option<HashMap<String, String>>) -> (Vec<String>, Vec<String>) {
+/// Average and maximum cyclomatic complexity over all functions and methods
+/// outside generated files
+fn function_complexity(parsed_files: &[ParsedFile]) -> serde_json::Value {
+    let complexities: Vec<usize> = parsed_files
+        .iter()
+        .filter(|f| !f.generated)
+        .flat_map(|f| f.functions.iter().chain(f.classes.iter().flat_map(|c| c.methods.iter())))
+        .map(|func| func.complexity)
+        .collect();
+    let average = if complexities.is_empty() {
+        0.0
+    } else {
+        (complexities.iter().sum::<usize>() as f64 / complexities.len() as f64 * 100.0).round() / 100.0
+    };
+
+    serde_json::json!({
+        "average": average,
+        "max": complexities.iter().max().copied().unwrap_or(0),
+    })
+}
+
+/// The `changed_files` and `removed_files` options, normalized. Fails on
+/// an absolute path or one leaving the repository.
+fn extract_webhook_changes(options: &Option<HashMap<String, String>>) -> Result<(Vec<String>, Vec<String>)> {
     let mut changed_files = Vec::new();
     let mut removed_files = Vec::new();
 
     if let Some(opts) = options {
         if let Some(raw) = opts.get("changed_files") {
             if let Ok(files) = serde_json::from_str::<Vec<String>>(raw) {
-                changed_files = files;
+                changed_files = repo_paths::normalize_repo_paths(&files).context("Invalid changed_files option")?;
             }
         }
         if let Some(raw) = opts.get("removed_files") {
             if let Ok(files) = serde_json::from_str::<Vec<String>>(raw) {
-                removed_files = files;
+                removed_files = repo_paths::normalize_repo_paths(&files).context("Invalid removed_files option")?;
             }
         }
     }
 
-    (changed_files, removed_files)
+    Ok((changed_files, removed_files))
+}
+
+/// Renames listed in the `renamed_files` option as `[{"from", "to"}]`,
+/// normalized like `changed_files`
+fn extract_renamed_files(options: &Option<HashMap<String, String>>) -> Result<Vec<(String, String)>> {
+    #[derive(Deserialize)]
+    struct Rename {
+        from: String,
+        to: String,
+    }
+
+    let renames = options
+        .as_ref()
+        .and_then(|opts| opts.get("renamed_files"))
+        .and_then(|raw| serde_json::from_str::<Vec<Rename>>(raw).ok())
+        .unwrap_or_default();
+    renames
+        .into_iter()
+        .map(|r| Ok((repo_paths::normalize_repo_path(&r.from)?, repo_paths::normalize_repo_path(&r.to)?)))
+        .collect::<Result<_>>()
+        .context("Invalid renamed_files option")
+}
+
+/// Renames from the job options, or else detected by diffing `before_sha`
+/// (default `HEAD~1`) against HEAD for files the webhook reported as removed
+fn resolve_renames(
+    repo_path: &Path,
+    options: &Option<HashMap<String, String>>,
+    removed_files: &[String],
+    changed_files: &[String],
+) -> Result<Vec<(String, String)>> {
+    let renames = extract_renamed_files(options)?;
+    if !renames.is_empty() || removed_files.is_empty() {
+        return Ok(renames);
+    }
+
+    let base = options
+        .as_ref()
+        .and_then(|opts| opts.get("before_sha"))
+        .map(String::as_str)
+        .unwrap_or("HEAD~1");
+    let detected = git_analyzer::GitAnalyzer::new(repo_path)
+        .and_then(|analyzer| analyzer.detect_renames(base, removed_files, changed_files));
+    Ok(match detected {
+        Ok(renames) => {
+            if !renames.is_empty() {
+                info!("🔀 Detected {} renamed files since {}", renames.len(), base);
+            }
+            renames
+        }
+        Err(e) => {
+            warn!("⚠️  Failed to detect renames since {}: {}. Treating them as removals.", base, e);
+            Vec::new()
+        }
+    })
 }
 
 fn build_graph_patch(
     parsed_files: &[ParsedFile],
     dep_graph: &graph_builder::DependencyGraph,
+    packages: &[graph_builder::Package],
     changed_files: &[String],
     removed_files: &[String],
+    previous: Option<&neo4j_storage::ExistingIds>,
 ) -> GraphPatch {
     let mut nodes = Vec::new();
     let mut edges = Vec::new();
     let mut module_nodes = HashSet::new();
 
+    // A class or function whose body hash matches the stored one didn't
+    // change; it's left out, along with stored edges touching it
+    let mut unchanged_symbols: HashSet<String> = HashSet::new();
+    let mut is_unchanged = |id: &str, hash: &str| {
+        let unchanged = !hash.is_empty()
+            && previous.and_then(|previous| previous.body_hashes.get(id)).map(String::as_str) == Some(hash);
+        if unchanged {
+            unchanged_symbols.insert(id.to_string());
+        }
+        unchanged
+    };
+
+    // Every directory above a file is a folder node with its parent
+    // directory as parent, so the tree view needs no path splitting. A
+    // package stands in for its directory, and files sit under their
+    // package or directory.
+    let package_dirs: HashSet<&str> = packages.iter().map(|package| package.path.as_str()).collect();
+    let dir_id = |dir: &str| match package_dirs.contains(dir) {
+        true => format!("package:{}", dir),
+        false => dir.to_string(),
+    };
+    let mut file_dirs: HashMap<&str, &str> = parsed_files
+        .iter()
+        .map(|file| (file.path.as_str(), graph_builder::parent_dir(&file.path)))
+        .collect();
+    for package in packages {
+        for file in &package.files {
+            file_dirs.insert(file, &package.path);
+        }
+    }
+
+    let mut directories = std::collections::BTreeSet::new();
+    for start in file_dirs.values().chain(package_dirs.iter()) {
+        let mut dir = *start;
+        while !dir.is_empty() && directories.insert(dir) {
+            dir = graph_builder::parent_dir(dir);
+        }
+    }
+    // A directory sorts after its parent, whose depth is then known
+    let mut dir_depths: HashMap<&str, usize> = HashMap::new();
+    for dir in directories {
+        let parent = graph_builder::parent_dir(dir);
+        let depth = dir_depths.get(parent).map_or(0, |depth| depth + 1);
+        dir_depths.insert(dir, depth);
+        nodes.push(PatchNode {
+            id: dir_id(dir),
+            label: dir.rsplit('/').next().unwrap_or(dir).to_string(),
+            node_type: if package_dirs.contains(dir) { "package" } else { "folder" }.to_string(),
+            parent_id: (!parent.is_empty()).then(|| dir_id(parent)),
+            extension: None,
+            language: None,
+            depth,
+            file_path: None,
+            line_number: None,
+            end_line_number: None,
+            decorators: Vec::new(),
+            visibility: None,
+        });
+    }
+
     for file in parsed_files {
-        let depth = file.path.matches('/').count();
+        let dir = file_dirs.get(file.path.as_str()).copied().unwrap_or_default();
+        let depth = dir_depths.get(dir).map_or(0, |depth| depth + 1);
         let label = file.path.split('/').last().unwrap_or(&file.path).to_string();
         let extension = Path::new(&file.path)
             .extension()
@@ -876,33 +2411,65 @@ fn build_graph_patch(
             id: file.path.clone(),
             label,
             node_type: "file".to_string(),
-            parent_id: None,
+            parent_id: (!dir.is_empty()).then(|| dir_id(dir)),
             extension,
             language: Some(file.language.clone()),
             depth,
             file_path: Some(file.path.clone()),
             line_number: None,
             end_line_number: None,
+            decorators: Vec::new(),
+            visibility: None,
         });
 
         for class in &file.classes {
-            nodes.push(PatchNode {
-                id: format!("{}::{}", file.path, class.name),
-                label: class.name.clone(),
-                node_type: "class".to_string(),
-                parent_id: Some(file.path.clone()),
-                extension: None,
-                language: Some(file.language.clone()),
-                depth: depth + 1,
-                file_path: Some(file.path.clone()),
-                line_number: Some(class.start_line),
-                end_line_number: Some(class.end_line),
-            });
+            let class_id = format!("{}::{}", file.path, class.name);
+            if !is_unchanged(&class_id, &class.body_hash) {
+                nodes.push(PatchNode {
+                    id: class_id.clone(),
+                    label: class.name.clone(),
+                    node_type: "class".to_string(),
+                    parent_id: Some(file.path.clone()),
+                    extension: None,
+                    language: Some(file.language.clone()),
+                    depth: depth + 1,
+                    file_path: Some(file.path.clone()),
+                    line_number: Some(class.start_line),
+                    end_line_number: Some(class.end_line),
+                    decorators: class.decorators.clone(),
+                    visibility: class.visibility.clone(),
+                });
+            }
+
+            for method in &class.methods {
+                let method_id = format!("{}::{}", class_id, method.name);
+                if is_unchanged(&method_id, &method.body_hash) {
+                    continue;
+                }
+                nodes.push(PatchNode {
+                    id: method_id,
+                    label: method.name.clone(),
+                    node_type: "function".to_string(),
+                    parent_id: Some(class_id.clone()),
+                    extension: None,
+                    language: Some(file.language.clone()),
+                    depth: depth + 2,
+                    file_path: Some(file.path.clone()),
+                    line_number: Some(method.start_line),
+                    end_line_number: Some(method.end_line),
+                    decorators: method.decorators.clone(),
+                    visibility: method.visibility.clone(),
+                });
+            }
         }
 
         for func in &file.functions {
+            let func_id = format!("{}::{}", file.path, func.name);
+            if is_unchanged(&func_id, &func.body_hash) {
+                continue;
+            }
             nodes.push(PatchNode {
-                id: format!("{}::{}", file.path, func.name),
+                id: func_id,
                 label: func.name.clone(),
                 node_type: "function".to_string(),
                 parent_id: Some(file.path.clone()),
@@ -912,15 +2479,27 @@ fn build_graph_patch(
                 file_path: Some(file.path.clone()),
                 line_number: Some(func.start_line),
                 end_line_number: Some(func.end_line),
+                decorators: func.decorators.clone(),
+                visibility: func.visibility.clone(),
             });
         }
     }
 
+    let stored_edges: HashSet<&str> = previous
+        .map(|previous| previous.edges.iter().map(String::as_str).collect())
+        .unwrap_or_default();
+    let mut unchanged_edges = Vec::new();
     for edge in &dep_graph.edges {
-        let source = node_id_to_string(&edge.from);
-        let target = node_id_to_string(&edge.to);
+        let source = edge.from.qualified_id();
+        let target = edge.to.qualified_id();
         let edge_type = edge.edge_type.as_str().to_lowercase();
-        let id = format!("{}:{}->{}", edge_type, source, target);
+        let id = edge.id();
+        if stored_edges.contains(id.as_str())
+            && (unchanged_symbols.contains(&source) || unchanged_symbols.contains(&target))
+        {
+            unchanged_edges.push(id);
+            continue;
+        }
 
         if let graph_builder::NodeId::Module(name) = &edge.from {
             module_nodes.insert(name.clone());
@@ -949,31 +2528,54 @@ fn build_graph_patch(
             file_path: None,
             line_number: None,
             end_line_number: None,
+            decorators: Vec::new(),
+            visibility: None,
         });
     }
 
+    let (removed_nodes, removed_edges) = match previous {
+        Some(previous) => (
+            removed_ids(
+                &previous.nodes,
+                nodes.iter().map(|node| node.id.as_str()).chain(unchanged_symbols.iter().map(String::as_str)),
+            ),
+            removed_ids(
+                &previous.edges,
+                edges.iter().map(|edge| edge.id.as_str()).chain(unchanged_edges.iter().map(String::as_str)),
+            ),
+        ),
+        None => (Vec::new(), Vec::new()),
+    };
+
+    // Listed in the form the node ids take, so clients can match them
+    let normalized = |files: &[String]| -> Vec<String> {
+        files.iter().filter_map(|file| repo_paths::normalize_repo_path(file).ok()).collect()
+    };
     GraphPatch {
-        changed_files: changed_files.to_vec(),
-        removed_files: removed_files.to_vec(),
+        changed_files: normalized(changed_files),
+        removed_files: normalized(removed_files),
         nodes,
         edges,
+        removed_nodes,
+        removed_edges,
+        impacted_files: Vec::new(),
     }
 }
 
-fn node_id_to_string(node: &graph_builder::NodeId) -> String {
-    match node {
-        graph_builder::NodeId::File(path) => path.clone(),
-        graph_builder::NodeId::Class(path, name) => format!("{}::{}", path, name),
-        graph_builder::NodeId::Function(path, name) => format!("{}::{}", path, name),
-        graph_builder::NodeId::Module(name) => name.clone(),
-    }
+/// Previous ids missing from the current ones, sorted
+fn removed_ids<'a>(previous: &[String], current: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let current: HashSet<&str> = current.collect();
+    let mut removed: Vec<String> = previous.iter().filter(|id| !current.contains(id.as_str())).cloned().collect();
+    removed.sort();
+    removed.dedup();
+    removed
 }
 
-fn collect_library_dependencies(repo_path: &PathBuf) -> Result<Vec<LibraryDependency>> {
+fn collect_library_dependencies(repo_path: &PathBuf, filter: &PathFilter) -> Result<Vec<LibraryDependency>> {
     use std::collections::HashSet;
 
     let mut manifest_files = Vec::new();
-    collect_manifest_files(repo_path, &mut manifest_files)?;
+    collect_manifest_files(repo_path, repo_path, filter, &mut manifest_files)?;
 
     let mut deps_set: HashSet<(String, Option<String>, String)> = HashSet::new();
 
@@ -987,6 +2589,10 @@ fn collect_library_dependencies(repo_path: &PathBuf) -> Result<Vec<LibraryDepend
             "requirements.txt" => parse_requirements_txt(&file, &source_file)?,
             "Cargo.toml" => parse_cargo_toml(&file, &source_file)?,
             "go.mod" => parse_go_mod(&file, &source_file)?,
+            "pom.xml" => parse_pom_xml(&file, &source_file)?,
+            "build.gradle" | "build.gradle.kts" => parse_gradle_build(&file, &source_file)?,
+            "pyproject.toml" => parse_pyproject_toml(&file, &source_file)?,
+            "Pipfile" => parse_pipfile(&file, &source_file)?,
             _ => Vec::new(),
         };
 
@@ -1001,6 +2607,8 @@ fn collect_library_dependencies(repo_path: &PathBuf) -> Result<Vec<LibraryDepend
             name,
             version,
             source_file,
+            vulnerability_ids: None,
+            usage: None,
         });
     }
 
@@ -1008,7 +2616,25 @@ fn collect_library_dependencies(repo_path: &PathBuf) -> Result<Vec<LibraryDepend
     Ok(dependencies)
 }
 
-fn collect_manifest_files(current_dir: &PathBuf, results: &mut Vec<PathBuf>) -> Result<()> {
+/// Manifest file names `collect_library_dependencies` reads
+const MANIFEST_FILES: &[&str] = &[
+    "package.json",
+    "requirements.txt",
+    "Cargo.toml",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "build.gradle.kts",
+    "pyproject.toml",
+    "Pipfile",
+];
+
+fn collect_manifest_files(
+    root_dir: &Path,
+    current_dir: &PathBuf,
+    filter: &PathFilter,
+    results: &mut Vec<PathBuf>,
+) -> Result<()> {
     if !current_dir.is_dir() {
         return Ok(());
     }
@@ -1031,13 +2657,12 @@ fn collect_manifest_files(current_dir: &PathBuf, results: &mut Vec<PathBuf>) ->
         }
 
         if path.is_dir() {
-            collect_manifest_files(&path, results)?;
+            collect_manifest_files(root_dir, &path, filter, results)?;
         } else if path.is_file() {
             if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                if file_name == "package.json"
-                    || file_name == "requirements.txt"
-                    || file_name == "Cargo.toml"
-                    || file_name == "go.mod" {
+                if MANIFEST_FILES.contains(&file_name)
+                    && filter.matches(&repo_relative_path(root_dir, &path))
+                {
                     results.push(path);
                 }
             }
@@ -1060,6 +2685,8 @@ fn parse_package_json(path: &PathBuf, source_file: &str) -> Result<Vec<LibraryDe
                     name: name.clone(),
                     version,
                     source_file: source_file.to_string(),
+                    vulnerability_ids: None,
+                    usage: None,
                 });
             }
         }
@@ -1090,6 +2717,8 @@ fn parse_requirements_txt(path: &PathBuf, source_file: &str) -> Result<Vec<Libra
                     name,
                     version,
                     source_file: source_file.to_string(),
+                    vulnerability_ids: None,
+                    usage: None,
                 });
             }
         }
@@ -1125,6 +2754,8 @@ fn parse_cargo_toml(path: &PathBuf, source_file: &str) -> Result<Vec<LibraryDepe
                 name: cap.get(1).unwrap().as_str().to_string(),
                 version: Some(cap.get(2).unwrap().as_str().to_string()),
                 source_file: source_file.to_string(),
+                vulnerability_ids: None,
+                usage: None,
             });
             continue;
         }
@@ -1134,6 +2765,8 @@ fn parse_cargo_toml(path: &PathBuf, source_file: &str) -> Result<Vec<LibraryDepe
                 name: cap.get(1).unwrap().as_str().to_string(),
                 version: Some(cap.get(2).unwrap().as_str().to_string()),
                 source_file: source_file.to_string(),
+                vulnerability_ids: None,
+                usage: None,
             });
         }
     }
@@ -1169,6 +2802,8 @@ fn parse_go_mod(path: &PathBuf, source_file: &str) -> Result<Vec<LibraryDependen
                 name: cap.get(1).unwrap().as_str().to_string(),
                 version: Some(cap.get(2).unwrap().as_str().to_string()),
                 source_file: source_file.to_string(),
+                vulnerability_ids: None,
+                usage: None,
             });
             continue;
         }
@@ -1179,6 +2814,8 @@ fn parse_go_mod(path: &PathBuf, source_file: &str) -> Result<Vec<LibraryDependen
                     name: cap.get(1).unwrap().as_str().to_string(),
                     version: Some(cap.get(2).unwrap().as_str().to_string()),
                     source_file: source_file.to_string(),
+                    vulnerability_ids: None,
+                    usage: None,
                 });
             }
         }
@@ -1187,28 +2824,290 @@ fn parse_go_mod(path: &PathBuf, source_file: &str) -> Result<Vec<LibraryDependen
     Ok(deps)
 }
 
-pub(crate) fn walk_directory(
-    root_dir: &PathBuf,
+/// Maven dependencies as `groupId:artifactId`. `${property}` versions are
+/// resolved from the pom's own `<properties>`; others are left unset.
+fn parse_pom_xml(path: &PathBuf, source_file: &str) -> Result<Vec<LibraryDependency>> {
+    use regex::Regex;
+
+    let content = fs::read_to_string(path).context("Failed to read pom.xml")?;
+    let comment_re = Regex::new(r"(?s)<!--.*?-->").context("Failed to build pom.xml comment regex")?;
+    let content = comment_re.replace_all(&content, "");
+    let dependency_re = Regex::new(r"(?s)<dependency>(.*?)</dependency>")
+        .context("Failed to build pom.xml dependency regex")?;
+    let tag = |block: &str, name: &str| -> Option<String> {
+        let re = Regex::new(&format!(r"<{0}>\s*([^<]*?)\s*</{0}>", name)).ok()?;
+        re.captures(block).map(|cap| cap[1].to_string())
+    };
+
+    let properties: HashMap<String, String> = Regex::new(r"(?s)<properties>(.*?)</properties>")
+        .context("Failed to build pom.xml properties regex")?
+        .captures(&content)
+        .map(|cap| {
+            Regex::new(r"<([A-Za-z0-9_.\-]+)>\s*([^<]*?)\s*</[A-Za-z0-9_.\-]+>")
+                .unwrap()
+                .captures_iter(&cap[1])
+                .map(|p| (p[1].to_string(), p[2].to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut deps = Vec::new();
+    for cap in dependency_re.captures_iter(&content) {
+        let block = &cap[1];
+        let (Some(group), Some(artifact)) = (tag(block, "groupId"), tag(block, "artifactId")) else {
+            continue;
+        };
+        let version = tag(block, "version").and_then(|version| match version.strip_prefix("${") {
+            Some(property) => properties.get(property.trim_end_matches('}')).cloned(),
+            None => Some(version),
+        });
+        deps.push(LibraryDependency {
+            name: format!("{}:{}", group, artifact),
+            version,
+            source_file: source_file.to_string(),
+            vulnerability_ids: None,
+            usage: None,
+        });
+    }
+
+    Ok(deps)
+}
+
+/// Gradle dependencies in string notation, `implementation 'g:a:v'` or
+/// `implementation("g:a:v")`, for the usual configurations
+fn parse_gradle_build(path: &PathBuf, source_file: &str) -> Result<Vec<LibraryDependency>> {
+    use regex::Regex;
+
+    const CONFIGURATIONS: &[&str] = &[
+        "implementation",
+        "api",
+        "compile",
+        "compileOnly",
+        "runtimeOnly",
+        "runtime",
+        "testImplementation",
+        "testCompile",
+        "testRuntimeOnly",
+        "annotationProcessor",
+        "kapt",
+        "ksp",
+    ];
+
+    let content = fs::read_to_string(path).context("Failed to read Gradle build file")?;
+    let dependency_re = Regex::new(r#"\b([A-Za-z]+)\s*\(?\s*["']([^:"'\s]+):([^:"'\s]+)(?::([^:"'\s@]+))?[^"']*["']"#)
+        .context("Failed to build Gradle dependency regex")?;
+
+    let mut deps = Vec::new();
+    for line in content.lines() {
+        let line = line.split("//").next().unwrap_or_default();
+        for cap in dependency_re.captures_iter(line) {
+            if !CONFIGURATIONS.contains(&&cap[1]) {
+                continue;
+            }
+            deps.push(LibraryDependency {
+                name: format!("{}:{}", &cap[2], &cap[3]),
+                version: cap.get(4).map(|m| m.as_str().to_string()),
+                source_file: source_file.to_string(),
+                vulnerability_ids: None,
+                usage: None,
+            });
+        }
+    }
+
+    Ok(deps)
+}
+
+/// PEP 621 `[project] dependencies` and Poetry's `[tool.poetry.dependencies]`
+fn parse_pyproject_toml(path: &PathBuf, source_file: &str) -> Result<Vec<LibraryDependency>> {
+    let content = fs::read_to_string(path).context("Failed to read pyproject.toml")?;
+
+    let mut deps: Vec<LibraryDependency> = toml_array_strings(&content, "project", "dependencies")
+        .iter()
+        .filter_map(|requirement| pep508_dependency(requirement, source_file))
+        .collect();
+    deps.extend(toml_dependency_table(&content, "tool.poetry.dependencies", source_file));
+
+    Ok(deps)
+}
+
+/// Pipenv's `[packages]` and `[dev-packages]`
+fn parse_pipfile(path: &PathBuf, source_file: &str) -> Result<Vec<LibraryDependency>> {
+    let content = fs::read_to_string(path).context("Failed to read Pipfile")?;
+
+    let mut deps = toml_dependency_table(&content, "packages", source_file);
+    deps.extend(toml_dependency_table(&content, "dev-packages", source_file));
+    Ok(deps)
+}
+
+/// Name and version spec of a PEP 508 requirement such as
+/// `requests[socks]>=2.31; python_version >= "3.8"`
+fn pep508_dependency(requirement: &str, source_file: &str) -> Option<LibraryDependency> {
+    let requirement = requirement.split(';').next()?.trim();
+    let name_end = requirement
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')))
+        .unwrap_or(requirement.len());
+    let name = &requirement[..name_end];
+    if name.is_empty() {
+        return None;
+    }
+
+    let rest = requirement[name_end..].trim_start();
+    let rest = match rest.strip_prefix('[') {
+        Some(extras) => extras.split_once(']').map(|(_, r)| r).unwrap_or_default(),
+        None => rest,
+    };
+    let version: String = rest.chars().filter(|c| !c.is_whitespace()).collect();
+    let version = version.trim_start_matches('(').trim_end_matches(')').to_string();
+
+    Some(LibraryDependency {
+        name: name.to_string(),
+        version: (!version.is_empty()).then_some(version),
+        source_file: source_file.to_string(),
+        vulnerability_ids: None,
+        usage: None,
+    })
+}
+
+/// Lines of a TOML table, up to the next table header
+fn toml_table_lines<'a>(content: &'a str, table: &str) -> Vec<&'a str> {
+    let header = format!("[{}]", table);
+    content
+        .lines()
+        .skip_while(|line| line.trim() != header)
+        .skip(1)
+        .take_while(|line| !line.trim_start().starts_with('['))
+        .collect()
+}
+
+/// Strings of `key = [...]` in a TOML table; the array may span several lines
+fn toml_array_strings(content: &str, table: &str, key: &str) -> Vec<String> {
+    let header = format!("[{}]", table);
+    let mut lines = content.lines().skip_while(|line| line.trim() != header).skip(1);
+
+    let mut array = String::new();
+    for line in lines.by_ref() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            return Vec::new();
+        }
+        if let Some((k, v)) = trimmed.split_once('=') {
+            if k.trim() == key {
+                array.push_str(v);
+                array.push('\n');
+                array.extend(lines.flat_map(|line| [line, "\n"]));
+                break;
+            }
+        }
+    }
+
+    // Collect quoted strings up to the first `]` outside of one
+    let mut values = Vec::new();
+    let mut quote = None;
+    let mut current = String::new();
+    let mut in_comment = false;
+    for c in array.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => {
+                values.push(std::mem::take(&mut current));
+                quote = None;
+            }
+            (Some(_), c) => current.push(c),
+            (None, '\n') => in_comment = false,
+            (None, _) if in_comment => {}
+            (None, '#') => in_comment = true,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ']') => break,
+            _ => {}
+        }
+    }
+    values
+}
+
+/// `name = "spec"` or `name = { version = "spec", ... }` entries of a TOML
+/// table. `*` means any version, and Poetry's `python` entry is skipped.
+fn toml_dependency_table(content: &str, table: &str, source_file: &str) -> Vec<LibraryDependency> {
+    use regex::Regex;
+
+    let version_re = Regex::new(r#"version\s*=\s*["']([^"']*)["']"#).unwrap();
+    let mut deps = Vec::new();
+
+    for line in toml_table_lines(content, table) {
+        let line = line.split('#').next().unwrap_or_default();
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+        let name = name.trim().trim_matches('"');
+        if name.is_empty() || name == "python" {
+            continue;
+        }
+
+        let value = value.trim();
+        let version = if value.starts_with('{') {
+            version_re.captures(value).map(|cap| cap[1].to_string())
+        } else {
+            Some(value.trim_matches(['"', '\'']).to_string())
+        };
+        deps.push(LibraryDependency {
+            name: name.to_string(),
+            version: version.filter(|v| !v.is_empty() && v != "*"),
+            source_file: source_file.to_string(),
+            vulnerability_ids: None,
+            usage: None,
+        });
+    }
+
+    deps
+}
+
+/// Whether a file is over the size limit, counting it as skipped if so
+fn exceeds_size_limit(root_dir: &Path, path: &Path, limits: &FileLimits, stats: &mut ParseStats) -> bool {
+    let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if size > limits.max_file_size_bytes {
+        warn!("⚠️  Skipping {:?}: {} bytes exceeds the {} byte limit", path, size, limits.max_file_size_bytes);
+        stats.skipped_large_files += 1;
+        stats.skip(repo_relative_path(root_dir, path), format!("larger than {} bytes", limits.max_file_size_bytes));
+        return true;
+    }
+    false
+}
+
+/// Path relative to the repository root, normalized like the paths in job
+/// options so both match the ids stored in Neo4j
+fn repo_relative_path(root_dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root_dir).unwrap_or(path).to_string_lossy().replace("\\", "/");
+    repo_paths::normalize_repo_path(&relative).unwrap_or(relative)
+}
+
+/// Recursively collect source files whose extension has a parser, skipping
+/// files outside the job's path filter and files over the size limit.
+/// Symlinks are never followed, so the walk can't loop or leave the
+/// checkout. Unreadable entries are skipped, not fatal.
+fn collect_source_files(
+    root_dir: &Path,
     current_dir: &PathBuf,
-    parsed_files: &mut Vec<ParsedFile>,
-    js_parser: &JavaScriptParser,
-    ts_parser: &TypeScriptParser,
-    rust_parser: &RustParser,
-    go_parser: &GoParser,
-    py_parser: &PythonParser,
+    results: &mut Vec<PathBuf>,
+    limits: &FileLimits,
+    filter: &PathFilter,
+    stats: &mut ParseStats,
 ) -> Result<()> {
     if !current_dir.is_dir() {
         return Ok(());
     }
-    
+
     for entry in fs::read_dir(current_dir).context("Failed to read directory")? {
-        let entry = entry.context("Failed to read directory entry")?;
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("⚠️  Failed to read entry in {:?}: {}", current_dir, e);
+                continue;
+            }
+        };
         let path = entry.path();
-        
+
         // Skip hidden directories and common ignore patterns
         if let Some(name) = path.file_name() {
             let name_str = name.to_string_lossy();
-            if name_str.starts_with('.') 
+            if name_str.starts_with('.')
                 || name_str == "node_modules"
                 || name_str == "target"
                 || name_str == "dist"
@@ -1218,96 +3117,101 @@ pub(crate) fn walk_directory(
                 continue;
             }
         }
-        
-        if path.is_dir() {
-            // Recursively walk subdirectories
-            walk_directory(
-                root_dir,
-                &path, 
-                parsed_files, 
-                js_parser, 
-                ts_parser,
-                rust_parser,
-                go_parser,
-                py_parser
-            )?;
-        } else if path.is_file() {
-            // Parse files based on extension
-            if let Some(extension) = path.extension() {
-                let ext = extension.to_string_lossy().to_lowercase();
-                
-                // Compute relative path for ID consistency
-                // e.g., "src/main.rs" instead of "C:\Users\...\src\main.rs"
-                let relative_path = path.strip_prefix(root_dir).unwrap_or(&path);
-                // Ensure forward slashes for consistency across OS
-                let path_str = relative_path.to_string_lossy().replace("\\", "/");
-                let relative_path_buf = PathBuf::from(&path_str);
-                
-                let parsed = match ext.as_str() {
-                    "js" | "jsx" | "mjs" => {
-                        match fs::read_to_string(&path) {
-                            Ok(content) => js_parser.parse_file(&relative_path_buf, &content).ok(),
-                            Err(e) => {
-                                warn!("⚠️  Failed to read file {:?}: {}", path, e);
-                                None
-                            }
-                        }
-                    }
-                    "ts" | "tsx" => {
-                        match fs::read_to_string(&path) {
-                            Ok(content) => ts_parser.parse_file(&relative_path_buf, &content).ok(),
-                            Err(e) => {
-                                warn!("⚠️  Failed to read file {:?}: {}", path, e);
-                                None
-                            }
-                        }
-                    }
-                    "rs" => {
-                        match fs::read_to_string(&path) {
-                            Ok(content) => rust_parser.parse_file(&relative_path_buf, &content).ok(),
-                            Err(e) => {
-                                warn!("⚠️  Failed to read file {:?}: {}", path, e);
-                                None
-                            }
-                        }
-                    }
-                    "go" => {
-                        match fs::read_to_string(&path) {
-                            Ok(content) => go_parser.parse_file(&relative_path_buf, &content).ok(),
-                            Err(e) => {
-                                warn!("⚠️  Failed to read file {:?}: {}", path, e);
-                                None
-                            }
-                        }
-                    }
-                    "py" => {
-                        match fs::read_to_string(&path) {
-                            Ok(content) => py_parser.parse_file(&relative_path_buf, &content).ok(),
-                            Err(e) => {
-                                warn!("⚠️  Failed to read file {:?}: {}", path, e);
-                                None
-                            }
-                        }
-                    }
-                    _ => None,
-                };
-                
-                if let Some(mut parsed_file) = parsed {
-                    // Double check path is standardized
-                    parsed_file.path = path_str;
-                    
-                    info!("✓ Parsed: {} ({} functions, {} imports)", 
-                          parsed_file.path, 
-                          parsed_file.functions.len(),
-                          parsed_file.imports.len());
-                    parsed_files.push(parsed_file);
-                }
+
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_symlink() {
+            if path.is_dir() || ParserSet::supports(&file_extension(&path)) {
+                warn!("⚠️  Not following symlink {:?}", path);
+                stats.skip(repo_relative_path(root_dir, &path), "symlink");
+            }
+        } else if file_type.is_dir() {
+            if let Err(e) = collect_source_files(root_dir, &path, results, limits, filter, stats) {
+                warn!("⚠️  Skipping directory {:?}: {}", path, e);
+            }
+        } else if file_type.is_file() && ParserSet::supports(&file_extension(&path)) {
+            if !filter.matches(&repo_relative_path(root_dir, &path)) {
+                stats.excluded_files += 1;
+            } else if !exceeds_size_limit(root_dir, &path, limits, stats) {
+                results.push(path);
             }
         }
     }
-    
+
     Ok(())
 }
 
+fn file_extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// What became of one candidate source file
+enum SourceFile {
+    Parsed(Box<ParsedFile>),
+    /// Not text: a NUL byte near the start. Holds the relative path.
+    Binary(String),
+    /// Generated code the job skips. Holds the relative path.
+    Generated(String),
+    /// The parser failed on it; the file holds no symbols
+    Failed(Box<ParsedFile>, String),
+    /// Unsupported or unreadable
+    Unparsed,
+}
+
+/// Read and parse a single file, marking generated code or skipping it if
+/// `skip_generated`
+fn parse_source_file(root_dir: &Path, path: &PathBuf, parsers: &ParserSet, skip_generated: bool) -> SourceFile {
+    let Some(parser) = parsers.for_extension(&file_extension(path)) else {
+        return SourceFile::Unparsed;
+    };
+
+    // Compute relative path for ID consistency
+    // e.g., "src/main.rs" instead of "C:\Users\...\src\main.rs"
+    let path_str = repo_relative_path(root_dir, path);
+    let relative_path_buf = PathBuf::from(&path_str);
+
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("⚠️  Failed to read file {:?}: {}", path, e);
+            return SourceFile::Unparsed;
+        }
+    };
+    let Some((content, lossy)) = parse_limits::decode_source(bytes) else {
+        warn!("⚠️  Skipping binary file {:?}", path);
+        return SourceFile::Binary(path_str);
+    };
+    if lossy {
+        warn!("⚠️  {:?} is not valid UTF-8, parsing it with the invalid bytes replaced", path);
+    }
+    let generated = generated_code::is_generated(&path_str, &content);
+    if generated && skip_generated {
+        return SourceFile::Generated(path_str);
+    }
+
+    let mut parsed_file = match parsers::parse_isolated(parser, &relative_path_buf, &content) {
+        Ok(parsed_file) => parsed_file,
+        Err(error) => {
+            warn!("⚠️  Failed to parse {}: {}", path_str, error);
+            let language = ParserSet::language(&file_extension(path)).unwrap_or_default();
+            let mut failed = ParsedFile::failed(path_str, language, &content);
+            failed.generated = generated;
+            return SourceFile::Failed(Box::new(failed), error);
+        }
+    };
+    // Double check path is standardized
+    parsed_file.path = path_str;
+    parsed_file.generated = generated;
+
+    info!("✓ Parsed: {} ({} functions, {} imports)",
+          parsed_file.path,
+          parsed_file.functions.len(),
+          parsed_file.imports.len());
+    SourceFile::Parsed(Box::new(parsed_file))
+}
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,645 @@
+//! Modularity Metrics
+//!
+//! Scores detected boundaries from the resolved file-level DEPENDS_ON edges:
+//! - Cohesion: share of a boundary's outgoing dependencies that stay inside it
+//! - Efferent/afferent coupling: distinct boundaries depended on / depending on it
+//! - Instability: Ce / (Ca + Ce), Robert Martin's package metric
+//! - Abstractness: share of a boundary's files that only declare abstract
+//!   types, a proxy for Martin's ratio of abstract classes
+//! - Distance from the main sequence: |A + I - 1|, how far a boundary is
+//!   from balancing abstractness against instability
+//!
+//! Also aggregates file dependencies into weighted boundary-to-boundary
+//! dependencies for the architecture overview, and ranks files as hotspots
+//! by how often they change and how much code they hold.
+
+use crate::boundary_detector::{Boundary, BoundaryDetectionResult, BoundaryType};
+use crate::git_analyzer::RepoContributions;
+use crate::graph_builder::{DependencyGraph, EdgeType, NodeId};
+use crate::parsers::{ClassInfo, FunctionInfo, InterfaceInfo, ParsedFile};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Boundaries with cohesion below this value are flagged unless configured otherwise
+pub const DEFAULT_COHESION_THRESHOLD: f64 = 0.5;
+
+/// Base classes that make a class abstract: Python's `abc.ABC` and
+/// `typing.Protocol`
+const ABSTRACT_BASES: &[&str] = &["ABC", "ABCMeta", "Protocol"];
+
+/// Quality metrics for a single boundary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoundaryMetrics {
+    pub cohesion: f64,
+    pub efferent_coupling: usize,
+    pub afferent_coupling: usize,
+    pub instability: f64,
+    /// Share of files declaring only abstract types, see `is_abstract_file`
+    pub abstractness: f64,
+    /// |abstractness + instability - 1|; 0 is on the main sequence
+    pub main_sequence_distance: f64,
+    /// Letter grade (A-F) derived from cohesion
+    pub health: String,
+    /// Cohesion is below the configured threshold
+    pub low_cohesion: bool,
+}
+
+/// Compute metrics for every boundary that has files and store them on the
+/// boundaries themselves. `abstract_files` holds the paths of the files
+/// `is_abstract_file` accepts.
+pub fn apply_boundary_metrics(
+    boundary_result: &mut BoundaryDetectionResult,
+    dep_graph: &DependencyGraph,
+    abstract_files: &HashSet<String>,
+    cohesion_threshold: f64,
+) {
+    let file_dependencies: Vec<(&str, &str)> = dep_graph
+        .edges
+        .iter()
+        .filter(|e| e.edge_type == EdgeType::DependsOn)
+        .filter_map(|e| match (&e.from, &e.to) {
+            (NodeId::File(from), NodeId::File(to)) => Some((from.as_str(), to.as_str())),
+            _ => None,
+        })
+        .collect();
+
+    let abstract_files: HashSet<&str> = abstract_files.iter().map(String::as_str).collect();
+
+    let metrics =
+        compute_boundary_metrics(&boundary_result.boundaries, &file_dependencies, &abstract_files, cohesion_threshold);
+    for boundary in &mut boundary_result.boundaries {
+        boundary.metrics = metrics.get(&boundary.id).cloned();
+    }
+}
+
+/// Compute metrics keyed by boundary id.
+///
+/// Boundaries are only compared with boundaries of the same type: a file sits
+/// in both a logical (directory) and an architectural (layer) boundary, and
+/// mixing the two would count every file's own layer as an external dependency.
+/// Self-loops are ignored. A boundary with no outgoing dependencies is
+/// perfectly cohesive. `abstract_files` holds the paths of files that only
+/// declare abstract types.
+pub fn compute_boundary_metrics(
+    boundaries: &[Boundary],
+    file_dependencies: &[(&str, &str)],
+    abstract_files: &HashSet<&str>,
+    cohesion_threshold: f64,
+) -> HashMap<String, BoundaryMetrics> {
+    let mut metrics = HashMap::new();
+
+    let mut file_to_boundary: HashMap<(&BoundaryType, &str), &str> = HashMap::new();
+    for boundary in boundaries {
+        for file in &boundary.files {
+            file_to_boundary.insert((&boundary.boundary_type, file.as_str()), boundary.id.as_str());
+        }
+    }
+
+    for boundary in boundaries.iter().filter(|b| !b.files.is_empty()) {
+        let kind = &boundary.boundary_type;
+        let members: HashSet<&str> = boundary.files.iter().map(String::as_str).collect();
+
+        let mut internal = 0usize;
+        let mut outgoing = 0usize;
+        let mut efferent: HashSet<&str> = HashSet::new();
+        let mut afferent: HashSet<&str> = HashSet::new();
+
+        for (from, to) in file_dependencies {
+            if from == to {
+                continue;
+            }
+            let from_inside = members.contains(from);
+            let to_inside = members.contains(to);
+
+            if from_inside {
+                outgoing += 1;
+                if to_inside {
+                    internal += 1;
+                } else if let Some(other) = file_to_boundary.get(&(kind, *to)) {
+                    efferent.insert(other);
+                }
+            } else if to_inside {
+                if let Some(other) = file_to_boundary.get(&(kind, *from)) {
+                    afferent.insert(other);
+                }
+            }
+        }
+
+        let cohesion = if outgoing == 0 {
+            1.0
+        } else {
+            internal as f64 / outgoing as f64
+        };
+        let ce = efferent.len();
+        let ca = afferent.len();
+        let instability = if ca + ce == 0 {
+            0.0
+        } else {
+            ce as f64 / (ca + ce) as f64
+        };
+        let abstract_members = members.iter().filter(|file| abstract_files.contains(*file)).count();
+        let abstractness = abstract_members as f64 / members.len() as f64;
+
+        metrics.insert(
+            boundary.id.clone(),
+            BoundaryMetrics {
+                cohesion,
+                efferent_coupling: ce,
+                afferent_coupling: ca,
+                instability,
+                abstractness,
+                main_sequence_distance: (abstractness + instability - 1.0).abs(),
+                health: health_grade(cohesion).to_string(),
+                low_cohesion: cohesion < cohesion_threshold,
+            },
+        );
+    }
+
+    metrics
+}
+
+/// Whether a file declares abstract types and nothing else: no free
+/// functions, and only traits, interfaces, protocols and abstract classes
+pub fn is_abstract_file(file: &ParsedFile) -> bool {
+    !file.classes.is_empty()
+        && file.functions.is_empty()
+        && file.classes.iter().all(|class| is_abstract_type(class, &file.interfaces))
+}
+
+/// A Rust trait, a Go interface (listed in `interfaces`), a class deriving
+/// from an abstract base, one whose methods are all `@abstractmethod`, or
+/// one whose methods all lack a body and that extends no class. The parsers
+/// keep Java and Kotlin interfaces and Swift protocols as classes, so their
+/// bodiless methods are what gives them away.
+fn is_abstract_type(class: &ClassInfo, interfaces: &[InterfaceInfo]) -> bool {
+    matches!(class.kind.as_deref(), Some("trait" | "interface" | "protocol"))
+        || interfaces.iter().any(|interface| interface.name == class.name)
+        || class.inheritances.iter().any(|parent| ABSTRACT_BASES.contains(&base_name(&parent.name)))
+        || (!class.methods.is_empty() && class.methods.iter().all(is_abstract_method))
+        || (!class.methods.is_empty()
+            && class.methods.iter().all(is_bodiless)
+            && class.inheritances.iter().all(|parent| parent.kind != "class"))
+}
+
+/// `ABC` from `abc.ABC`, `Protocol[T]` or `metaclass=ABCMeta`
+fn base_name(name: &str) -> &str {
+    let name = name.split('[').next().unwrap_or_default();
+    name.rsplit(['.', '=']).next().unwrap_or_default().trim()
+}
+
+fn is_abstract_method(method: &FunctionInfo) -> bool {
+    method.decorators.iter().any(|decorator| decorator == "abstractmethod" || decorator.ends_with(".abstractmethod"))
+}
+
+/// A one-line declaration that calls nothing and doesn't branch, as an
+/// interface method or protocol requirement is
+fn is_bodiless(method: &FunctionInfo) -> bool {
+    method.start_line == method.end_line && method.calls.is_empty() && method.complexity <= 1
+}
+
+/// Boundaries with metrics, least cohesive first
+pub fn least_cohesive_boundaries(boundaries: &[Boundary], limit: usize) -> Vec<&Boundary> {
+    let mut scored: Vec<&Boundary> = boundaries.iter().filter(|b| b.metrics.is_some()).collect();
+    scored.sort_by(|a, b| {
+        let ca = a.metrics.as_ref().map(|m| m.cohesion).unwrap_or(1.0);
+        let cb = b.metrics.as_ref().map(|m| m.cohesion).unwrap_or(1.0);
+        ca.total_cmp(&cb).then_with(|| a.id.cmp(&b.id))
+    });
+    scored.truncate(limit);
+    scored
+}
+
+/// A file that changes often and holds a lot of code
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Hotspot {
+    pub path: String,
+    pub commit_count: usize,
+    /// Functions and methods in the file
+    pub functions: usize,
+    pub score: f64,
+}
+
+/// `log2(1 + commits) * log2(1 + functions)`, rounded to three decimals.
+/// The logarithms keep a single huge or hyperactive file from drowning out
+/// the rest, and the score doesn't depend on other files, so scores written
+/// by incremental runs stay comparable. Without git history (`None`) churn
+/// is left out and the score ranks files by size alone.
+pub fn hotspot_score(commit_count: Option<usize>, functions: usize) -> f64 {
+    let churn = commit_count.map_or(1.0, |commits| (1.0 + commits as f64).log2());
+    let size = (1.0 + functions as f64).log2();
+    (churn * size * 1000.0).round() / 1000.0
+}
+
+/// Commit count of a file, `Some(0)` for files missing from the history and
+/// `None` when there is no history at all
+pub fn file_commit_count(path: &str, contributions: Option<&RepoContributions>) -> Option<usize> {
+    contributions.map(|c| c.files.get(path).map_or(0, |file| file.commit_count))
+}
+
+/// Files with a positive hotspot score, highest first, at most `limit`.
+/// Generated files never count as hotspots.
+pub fn top_hotspots(parsed_files: &[ParsedFile], contributions: Option<&RepoContributions>, limit: usize) -> Vec<Hotspot> {
+    let mut hotspots: Vec<Hotspot> = parsed_files
+        .iter()
+        .filter(|file| !file.generated)
+        .map(|file| {
+            let commit_count = file_commit_count(&file.path, contributions);
+            let functions = file.functions.len() + file.classes.iter().map(|c| c.methods.len()).sum::<usize>();
+            Hotspot {
+                path: file.path.clone(),
+                commit_count: commit_count.unwrap_or(0),
+                functions,
+                score: hotspot_score(commit_count, functions),
+            }
+        })
+        .filter(|hotspot| hotspot.score > 0.0)
+        .collect();
+    hotspots.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.path.cmp(&b.path)));
+    hotspots.truncate(limit);
+    hotspots
+}
+
+/// File dependencies from one boundary into another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BoundaryDependency {
+    pub source: String,
+    pub target: String,
+    /// File-level dependencies between the two
+    pub weight: usize,
+}
+
+/// Boundary dependencies of the graph's file-to-file DEPENDS_ON and IMPORTS
+/// edges, heaviest first
+pub fn boundary_dependencies(
+    boundary_result: &BoundaryDetectionResult,
+    dep_graph: &DependencyGraph,
+) -> Vec<BoundaryDependency> {
+    let file_dependencies: Vec<(&str, &str)> = dep_graph
+        .edges
+        .iter()
+        .filter(|e| matches!(e.edge_type, EdgeType::DependsOn | EdgeType::Imports))
+        .filter_map(|e| match (&e.from, &e.to) {
+            (NodeId::File(from), NodeId::File(to)) => Some((from.as_str(), to.as_str())),
+            _ => None,
+        })
+        .collect();
+    aggregate_boundary_dependencies(&boundary_result.file_to_boundary, &file_dependencies)
+}
+
+/// Count the file dependencies whose ends lie in different boundaries, per
+/// `(source, target)` boundary pair. Files without a boundary are left out.
+/// Sorted by weight, heaviest first, then by source and target.
+pub fn aggregate_boundary_dependencies(
+    file_to_boundary: &HashMap<String, String>,
+    file_dependencies: &[(&str, &str)],
+) -> Vec<BoundaryDependency> {
+    let mut weights: HashMap<(&str, &str), usize> = HashMap::new();
+    for (from, to) in file_dependencies {
+        let (Some(source), Some(target)) = (file_to_boundary.get(*from), file_to_boundary.get(*to)) else {
+            continue;
+        };
+        if source != target {
+            *weights.entry((source.as_str(), target.as_str())).or_default() += 1;
+        }
+    }
+
+    let mut dependencies: Vec<BoundaryDependency> = weights
+        .into_iter()
+        .map(|((source, target), weight)| BoundaryDependency {
+            source: source.to_string(),
+            target: target.to_string(),
+            weight,
+        })
+        .collect();
+    dependencies.sort_by(|a, b| {
+        b.weight
+            .cmp(&a.weight)
+            .then_with(|| a.source.cmp(&b.source))
+            .then_with(|| a.target.cmp(&b.target))
+    });
+    dependencies
+}
+
+fn health_grade(cohesion: f64) -> &'static str {
+    if cohesion >= 0.8 {
+        "A"
+    } else if cohesion >= 0.6 {
+        "B"
+    } else if cohesion >= 0.4 {
+        "C"
+    } else if cohesion >= 0.2 {
+        "D"
+    } else {
+        "F"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boundary(id: &str, files: &[&str]) -> Boundary {
+        Boundary {
+            id: id.to_string(),
+            name: id.to_string(),
+            boundary_type: BoundaryType::Logical,
+            path: id.to_string(),
+            layer: None,
+            file_count: files.len(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+            metrics: None,
+        }
+    }
+
+    #[test]
+    fn test_three_boundary_metrics() {
+        let boundaries = vec![
+            boundary("api", &["api/a.ts", "api/b.ts"]),
+            boundary("core", &["core/c.ts", "core/d.ts"]),
+            boundary("db", &["db/e.ts"]),
+        ];
+        let deps = vec![
+            ("api/a.ts", "api/b.ts"),   // api internal
+            ("api/a.ts", "core/c.ts"),  // api -> core
+            ("api/b.ts", "db/e.ts"),    // api -> db
+            ("api/b.ts", "api/b.ts"),   // self-loop, ignored
+            ("core/c.ts", "core/d.ts"), // core internal
+            ("core/d.ts", "core/c.ts"), // core internal
+            ("core/d.ts", "db/e.ts"),   // core -> db
+        ];
+
+        let metrics = compute_boundary_metrics(&boundaries, &deps, &HashSet::new(), 0.5);
+
+        let api = &metrics["api"];
+        assert!((api.cohesion - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(api.efferent_coupling, 2);
+        assert_eq!(api.afferent_coupling, 0);
+        assert_eq!(api.instability, 1.0);
+        assert_eq!(api.health, "D");
+        assert!(api.low_cohesion);
+
+        let core = &metrics["core"];
+        assert!((core.cohesion - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(core.efferent_coupling, 1);
+        assert_eq!(core.afferent_coupling, 1);
+        assert_eq!(core.instability, 0.5);
+        assert_eq!(core.health, "B");
+        assert!(!core.low_cohesion);
+
+        let db = &metrics["db"];
+        assert_eq!(db.cohesion, 1.0);
+        assert_eq!(db.efferent_coupling, 0);
+        assert_eq!(db.afferent_coupling, 2);
+        assert_eq!(db.instability, 0.0);
+        assert_eq!(db.health, "A");
+    }
+
+    #[test]
+    fn test_abstractness_and_main_sequence_distance() {
+        let boundaries = vec![
+            boundary("api", &["api/a.ts", "api/b.ts"]),
+            boundary("core", &["core/c.ts", "core/d.ts"]),
+            boundary("db", &["db/e.ts"]),
+        ];
+        let deps = vec![("api/a.ts", "core/c.ts"), ("api/b.ts", "db/e.ts"), ("core/d.ts", "db/e.ts")];
+        let abstract_files = HashSet::from(["api/a.ts"]);
+
+        let metrics = compute_boundary_metrics(&boundaries, &deps, &abstract_files, 0.5);
+        let scores = |id: &str| {
+            let m = &metrics[id];
+            (m.instability, m.abstractness, m.main_sequence_distance)
+        };
+        // Half abstract and fully unstable
+        assert_eq!(scores("api"), (1.0, 0.5, 0.5));
+        assert_eq!(scores("core"), (0.5, 0.0, 0.5));
+        // Concrete and only depended on: as far from the main sequence as it gets
+        assert_eq!(scores("db"), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_boundary_types_are_not_mixed() {
+        let mut layer = boundary("architectural_data_access", &["api/a.ts", "db/e.ts"]);
+        layer.boundary_type = BoundaryType::Architectural;
+        let boundaries = vec![boundary("api", &["api/a.ts"]), boundary("db", &["db/e.ts"]), layer];
+
+        let metrics = compute_boundary_metrics(&boundaries, &[("api/a.ts", "db/e.ts")], &HashSet::new(), 0.5);
+
+        assert_eq!(metrics["api"].efferent_coupling, 1);
+        assert_eq!(metrics["db"].afferent_coupling, 1);
+        assert_eq!(metrics["architectural_data_access"].cohesion, 1.0);
+        assert_eq!(metrics["architectural_data_access"].efferent_coupling, 0);
+    }
+
+    #[test]
+    fn test_least_cohesive_ranking_skips_unscored() {
+        let mut boundaries = vec![boundary("a", &["a/1"]), boundary("b", &["b/1"]), boundary("empty", &[])];
+        let metrics = compute_boundary_metrics(&boundaries, &[("a/1", "b/1")], &HashSet::new(), 0.5);
+        for b in &mut boundaries {
+            b.metrics = metrics.get(&b.id).cloned();
+        }
+
+        let ranked: Vec<&str> = least_cohesive_boundaries(&boundaries, 5).iter().map(|b| b.id.as_str()).collect();
+        assert_eq!(ranked, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_boundary_dependencies_aggregate_cross_boundary_files() {
+        let file_to_boundary: HashMap<String, String> = [
+            ("api/a.ts", "api"),
+            ("api/b.ts", "api"),
+            ("core/c.ts", "core"),
+            ("core/d.ts", "core"),
+            ("db/e.ts", "db"),
+        ]
+        .into_iter()
+        .map(|(file, boundary)| (file.to_string(), boundary.to_string()))
+        .collect();
+        let deps = vec![
+            ("api/a.ts", "api/b.ts"),   // inside api
+            ("api/a.ts", "core/c.ts"),  // api -> core
+            ("api/b.ts", "core/c.ts"),  // api -> core
+            ("api/b.ts", "core/d.ts"),  // api -> core
+            ("core/c.ts", "db/e.ts"),   // core -> db
+            ("api/a.ts", "db/e.ts"),    // api -> db
+            ("db/e.ts", "core/d.ts"),   // db -> core
+            ("scripts/x.ts", "db/e.ts"), // no boundary
+        ];
+
+        let dependencies = aggregate_boundary_dependencies(&file_to_boundary, &deps);
+        let weights: Vec<(&str, &str, usize)> =
+            dependencies.iter().map(|d| (d.source.as_str(), d.target.as_str(), d.weight)).collect();
+        assert_eq!(
+            weights,
+            vec![("api", "core", 3), ("api", "db", 1), ("core", "db", 1), ("db", "core", 1)]
+        );
+        assert!(aggregate_boundary_dependencies(&HashMap::new(), &deps).is_empty());
+    }
+
+    #[test]
+    fn test_hotspot_score() {
+        assert_eq!(hotspot_score(Some(3), 7), 6.0);
+        assert_eq!(hotspot_score(Some(0), 50), 0.0);
+        assert_eq!(hotspot_score(Some(12), 0), 0.0);
+        // Without history the score is the size factor alone
+        assert_eq!(hotspot_score(None, 7), 3.0);
+        // Both factors matter, and neither dominates linearly
+        assert!(hotspot_score(Some(10), 10) > hotspot_score(Some(100), 1));
+        assert!(hotspot_score(Some(8), 4) > hotspot_score(Some(4), 4));
+    }
+
+    fn parsed_file(path: &str, functions: usize, methods: usize) -> ParsedFile {
+        use crate::parsers::{ClassInfo, FunctionInfo};
+        let function = |i: usize| FunctionInfo {
+            name: format!("f{}", i),
+            params: vec![],
+            return_type: None,
+            calls: vec![],
+            start_line: i,
+            end_line: i,
+            complexity: 1,
+            body_hash: String::new(),
+            decorators: Vec::new(),
+            visibility: None,
+        };
+        ParsedFile {
+            path: path.to_string(),
+            language: "python".to_string(),
+            functions: (0..functions).map(function).collect(),
+            classes: vec![ClassInfo {
+                name: "C".to_string(),
+                kind: None,
+                inheritances: vec![],
+                methods: (0..methods).map(function).collect(),
+                start_line: 1,
+                end_line: 1,
+                body_hash: String::new(),
+                decorators: Vec::new(),
+                visibility: None,
+            }],
+            interfaces: vec![],
+            imports: vec![],
+            data_tables: vec![],
+            service_calls: vec![],
+            sql_queries: vec![],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: false,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: 10,
+        }
+    }
+
+    #[test]
+    fn test_abstract_files() {
+        use crate::parsers::{InheritanceInfo, InterfaceInfo};
+
+        let method = |lines: usize, decorators: &[&str]| FunctionInfo {
+            name: "m".to_string(),
+            params: vec![],
+            return_type: None,
+            calls: vec![],
+            start_line: 1,
+            end_line: lines,
+            complexity: 1,
+            body_hash: String::new(),
+            decorators: decorators.iter().map(|d| d.to_string()).collect(),
+            visibility: None,
+        };
+        let parent = |name: &str, kind: &str| InheritanceInfo { name: name.to_string(), kind: kind.to_string() };
+        let file = |kind: Option<&str>, inheritances: Vec<InheritanceInfo>, methods: Vec<FunctionInfo>| {
+            let mut file = parsed_file("f", 0, 0);
+            file.classes[0].kind = kind.map(str::to_string);
+            file.classes[0].inheritances = inheritances;
+            file.classes[0].methods = methods;
+            file
+        };
+
+        let rust_trait = file(Some("trait"), vec![], vec![method(3, &[])]);
+        assert!(is_abstract_file(&rust_trait));
+        let mut go_interface = file(None, vec![], vec![]);
+        go_interface.interfaces.push(InterfaceInfo { name: "C".to_string(), methods: vec![], embeds: vec![] });
+        assert!(is_abstract_file(&go_interface));
+        assert!(is_abstract_file(&file(None, vec![parent("abc.ABC", "class")], vec![method(5, &[])])));
+        assert!(is_abstract_file(&file(None, vec![parent("Protocol[T]", "class")], vec![])));
+        assert!(is_abstract_file(&file(None, vec![parent("Base", "class")], vec![method(3, &["abc.abstractmethod"])])));
+        // A Java interface extending another: signatures only
+        assert!(is_abstract_file(&file(None, vec![parent("Named", "interface")], vec![method(1, &[]), method(1, &[])])));
+
+        // One real body makes the type concrete
+        assert!(!is_abstract_file(&file(None, vec![], vec![method(1, &[]), method(4, &[])])));
+        // One-liners in a subclass are overrides, not declarations
+        assert!(!is_abstract_file(&file(None, vec![parent("Base", "class")], vec![method(1, &[])])));
+        // A type without methods or abstract markers is a plain struct or class
+        assert!(!is_abstract_file(&file(None, vec![], vec![])));
+        // Free functions next to the trait, or no types at all
+        let mut with_function = rust_trait.clone();
+        with_function.functions.push(method(3, &[]));
+        assert!(!is_abstract_file(&with_function));
+        let mut no_types = parsed_file("f", 2, 0);
+        no_types.classes.clear();
+        assert!(!is_abstract_file(&no_types));
+    }
+
+    #[test]
+    fn test_top_hotspots() {
+        use crate::git_analyzer::FileContribution;
+
+        let files = vec![
+            parsed_file("big_stable.py", 20, 11),
+            parsed_file("busy.py", 3, 0),
+            parsed_file("tiny_busy.py", 0, 1),
+            parsed_file("new.py", 9, 0),
+            parsed_file("empty.py", 0, 0),
+        ];
+        let mut history = HashMap::new();
+        for (path, commits) in [("big_stable.py", 1), ("busy.py", 15), ("tiny_busy.py", 15), ("empty.py", 40)] {
+            history.insert(
+                path.to_string(),
+                FileContribution {
+                    file_path: path.to_string(),
+                    commit_count: commits,
+                    last_modified: chrono::Utc::now(),
+                    primary_author: String::new(),
+                    contributors: vec![],
+                    lines_added_total: 0,
+                    lines_deleted_total: 0,
+                    lines_changed_total: 0,
+                },
+            );
+        }
+        let contributions = RepoContributions {
+            files: history,
+            total_commits: 40,
+            total_contributors: 1,
+            contributors: vec![],
+            commits: vec![],
+            shallow: false,
+            cochange: vec![],
+        };
+
+        let ranked: Vec<(String, usize, usize, f64)> = top_hotspots(&files, Some(&contributions), 20)
+            .into_iter()
+            .map(|h| (h.path, h.commit_count, h.functions, h.score))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![
+                ("busy.py".to_string(), 15, 3, 8.0),
+                ("big_stable.py".to_string(), 1, 31, 5.0),
+                ("tiny_busy.py".to_string(), 15, 1, 4.0),
+            ]
+        );
+        assert_eq!(top_hotspots(&files, Some(&contributions), 1).len(), 1);
+
+        // Failed history analysis: rank by size, keep every file with code
+        let by_size: Vec<String> = top_hotspots(&files, None, 20).into_iter().map(|h| h.path).collect();
+        assert_eq!(by_size, vec!["big_stable.py", "new.py", "busy.py", "tiny_busy.py"]);
+
+        let mut files = files;
+        files[0].generated = true;
+        let by_size: Vec<String> = top_hotspots(&files, None, 20).into_iter().map(|h| h.path).collect();
+        assert_eq!(by_size, vec!["new.py", "busy.py", "tiny_busy.py"]);
+    }
+}
@@ -3,15 +3,33 @@
 //! Efficient batch storage for dependency graphs using UNWIND queries
 //! and transaction support.
 
-use crate::graph_builder::{DependencyGraph, EdgeType, NodeId};
-use crate::parsers::{FunctionInfo, ParsedFile};
-use crate::git_analyzer::RepoContributions;
+use crate::graph_builder::{edge_id, edge_id_cypher, DependencyGraph, EdgeType, NodeId, Package};
+use crate::parsers::{ClassInfo, FunctionInfo, ParsedFile, SqlQuery};
+use crate::parsed_source::ParsedFileSource;
+use crate::git_analyzer::{CoChangePair, CommitRecord, RepoContributions, DEFAULT_MAX_COMMITS};
 use crate::boundary_detector::BoundaryDetectionResult;
-use crate::dependency_metadata::LibraryDependency;
-use crate::communication_detector::{CommunicationAnalysis, QueueDirection};
+use crate::dependency_metadata::{LibraryDependency, LibraryDiff, LibraryMatcher};
+use crate::communication_detector::{
+    endpoint_path, endpoint_services, normalize_route_path, CommunicationAnalysis, ComposeService, ProtoService, QueueDirection, RpcMethodCall,
+    SocketEventDirection,
+};
+use crate::orm_detector::OrmModel;
+use crate::asset_inventory::AssetFile;
+use crate::ddl_scanner::DdlTable;
+use crate::infra_scanner::{ContainerImage, InfraAnalysis, K8sDeployment, K8sService};
+use crate::layering::LayerViolation;
+use crate::codeowners::{self, CodeOwners};
+use crate::node_metadata::NodeMetadata;
+use crate::repo_paths::normalize_repo_paths;
+use crate::worker_metrics;
+use crate::metrics;
+use crate::job_control::CancellationToken;
+use crate::worker_state::WorkerStage;
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use neo4rs::query;
-use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use tracing::{info, warn};
 
 macro_rules! retry_query {
@@ -62,17 +80,273 @@ macro_rules! retry_query {
 // ============================================================================
 
 const DEFAULT_BATCH_SIZE: usize = 500;
+pub const DEFAULT_MAX_CHUNK_BYTES: usize = 4 * 1024 * 1024;
+
+/// Told how far a storage run has got, as a fraction from 0 to 1, after
+/// each group of inserts. Called from the storage task itself, so it must
+/// return quickly; the fraction never goes down.
+pub trait ProgressReporter: Send + Sync {
+    fn report(&self, fraction: f64);
+}
 
 pub struct BatchConfig {
     pub batch_size: usize,
+    /// Maximum number of Commit nodes stored per job (most recent first)
+    pub max_commits: usize,
+    /// Estimated parameter size above which a chunk is split further
+    pub max_chunk_bytes: usize,
+    /// Checked before every chunk. Each chunk commits on its own, so
+    /// stopping between chunks never leaves a transaction open.
+    pub cancellation: CancellationToken,
+    pub progress: Option<std::sync::Arc<dyn ProgressReporter>>,
+    /// Properties stamped on every node the run writes
+    pub node_metadata: NodeMetadata,
 }
 
 impl Default for BatchConfig {
     fn default() -> Self {
         Self {
             batch_size: DEFAULT_BATCH_SIZE,
+            max_commits: DEFAULT_MAX_COMMITS,
+            max_chunk_bytes: DEFAULT_MAX_CHUNK_BYTES,
+            cancellation: CancellationToken::default(),
+            progress: None,
+            node_metadata: NodeMetadata::default(),
+        }
+    }
+}
+
+/// Insert groups in `execute_batch_operations`, plus recording the job, each
+/// reported as done
+const STORAGE_GROUPS: usize = 13;
+
+/// Job nodes kept per repository; recording a job deletes older ones
+pub const JOB_HISTORY: usize = 20;
+
+/// What the Job node of a storage run records besides its id and mode
+#[derive(Debug, Clone)]
+pub struct JobRecord {
+    pub started_at: DateTime<Utc>,
+    pub files_processed: usize,
+    /// Nodes of the dependency graph
+    pub nodes_created: usize,
+    /// Full SHA of the commit checked out for the run, if it was a git repository
+    pub analyzed_commit: Option<String>,
+    /// Library changes since the previous run, None unless the dependencies
+    /// phase ran and the stored libraries could be read. Removed libraries'
+    /// nodes are deleted.
+    pub library_diff: Option<LibraryDiff>,
+}
+
+/// A chunk that still failed after retries and was skipped
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PartialError {
+    pub label: String,
+    pub rows: usize,
+    pub error: String,
+}
+
+/// Writes UNWIND batches for one storage run. A chunk that fails is logged
+/// and recorded, and the remaining chunks still go in, so one bad batch
+/// doesn't lose the rest of the graph.
+pub struct BatchWriter {
+    config: BatchConfig,
+    errors: std::sync::Mutex<Vec<PartialError>>,
+    groups_done: std::sync::atomic::AtomicUsize,
+}
+
+impl BatchWriter {
+    fn new(config: BatchConfig) -> Self {
+        Self {
+            config,
+            errors: std::sync::Mutex::new(Vec::new()),
+            groups_done: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+
+    /// Report one more of the `STORAGE_GROUPS` insert groups as done
+    fn group_done(&self) {
+        let done = self.groups_done.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+        if let Some(progress) = &self.config.progress {
+            progress.report((done as f64 / STORAGE_GROUPS as f64).min(1.0));
+        }
+    }
+
+    /// Run the query built by `make_query` for each chunk of `rows`. Fails
+    /// only when the job is cancelled, before the next chunk starts.
+    async fn write<T, F>(&self, graph_db: &neo4rs::Graph, label: &str, rows: &[T], make_query: F) -> Result<()>
+    where
+        T: RowSize,
+        F: Fn(&[T]) -> neo4rs::Query,
+    {
+        for chunk in adaptive_chunks(rows, self.config.batch_size, self.config.max_chunk_bytes) {
+            self.config.cancellation.checkpoint(WorkerStage::Storing).await?;
+            let started = std::time::Instant::now();
+            let result = retry_query!(graph_db, { make_query(chunk) });
+            let written = if result.is_ok() { chunk.len() } else { 0 };
+            worker_metrics::metrics().neo4j_batch(label, started.elapsed(), written);
+            if let Err(e) = result {
+                warn!("⚠️  Skipped {} rows of {}: {:?}", chunk.len(), label, e);
+                self.errors
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .push(PartialError {
+                        label: label.to_string(),
+                        rows: chunk.len(),
+                        error: format!("{:#}", e),
+                    });
+            }
+        }
+        Ok(())
+    }
+
+    /// Node rows with the job's `node_metadata` under `metadata`
+    fn stamp<T: Into<neo4rs::BoltType>>(&self, rows: Vec<HashMap<String, T>>) -> Vec<HashMap<String, neo4rs::BoltType>> {
+        self.config.node_metadata.stamp(rows)
+    }
+
+    fn into_errors(self) -> Vec<PartialError> {
+        self.errors.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Rough packstream size of one UNWIND row
+trait RowSize {
+    fn estimated_bytes(&self) -> usize;
+}
+
+impl RowSize for HashMap<String, String> {
+    fn estimated_bytes(&self) -> usize {
+        self.iter().map(|(k, v)| k.len() + v.len() + 2).sum()
+    }
+}
+
+impl RowSize for HashMap<String, i64> {
+    fn estimated_bytes(&self) -> usize {
+        self.keys().map(|k| k.len() + 10).sum()
+    }
+}
+
+impl RowSize for HashMap<String, neo4rs::BoltType> {
+    fn estimated_bytes(&self) -> usize {
+        self.iter().map(|(k, v)| k.len() + 1 + bolt_size(v)).sum()
+    }
+}
+
+fn bolt_size(value: &neo4rs::BoltType) -> usize {
+    match value {
+        neo4rs::BoltType::String(s) => s.value.len() + 1,
+        neo4rs::BoltType::List(list) => 1 + list.value.iter().map(bolt_size).sum::<usize>(),
+        neo4rs::BoltType::Map(map) => {
+            1 + map.value.iter().map(|(k, v)| k.value.len() + 1 + bolt_size(v)).sum::<usize>()
+        }
+        _ => 9,
+    }
+}
+
+/// Split `rows` into chunks of at most `batch_size` rows, halving any chunk
+/// whose estimated size is over `max_bytes`. A single row over budget is
+/// still sent on its own. Row order is kept.
+fn adaptive_chunks<T: RowSize>(rows: &[T], batch_size: usize, max_bytes: usize) -> Vec<&[T]> {
+    let mut chunks = Vec::new();
+    let mut pending: Vec<&[T]> = rows.chunks(batch_size.max(1)).rev().collect();
+    while let Some(chunk) = pending.pop() {
+        let bytes: usize = chunk.iter().map(RowSize::estimated_bytes).sum();
+        if chunk.len() > 1 && bytes > max_bytes {
+            let (head, tail) = chunk.split_at(chunk.len() / 2);
+            pending.push(tail);
+            pending.push(head);
+        } else {
+            chunks.push(chunk);
+        }
+    }
+    chunks
+}
+
+// ============================================================================
+// Schema
+// ============================================================================
+
+/// Properties every MERGE in this module matches on, per label. Single keys
+/// get a uniqueness constraint, composite keys an index.
+const MERGE_KEYS: &[(&str, &[&str])] = &[
+    ("File", &["id"]),
+    ("Class", &["id"]),
+    ("Function", &["id"]),
+    ("Boundary", &["id"]),
+    ("Module", &["name"]),
+    ("Job", &["id", "repo_id"]),
+    ("Repository", &["id"]),
+    ("Library", &["name", "repo_id"]),
+    ("Package", &["path", "repo_id"]),
+    ("Table", &["name", "repo_id"]),
+    ("Query", &["id", "repo_id"]),
+    ("Service", &["name", "protocol", "repo_id"]),
+    ("Endpoint", &["url", "method", "repo_id"]),
+    ("Route", &["path", "method", "repo_id"]),
+    ("Asset", &["path", "repo_id"]),
+    ("ApiOperation", &["path", "method", "repo_id"]),
+    ("RpcService", &["name", "repo_id"]),
+    ("RpcMethod", &["service", "name", "repo_id"]),
+    ("MessageQueue", &["topic", "repo_id"]),
+    ("WebSocketChannel", &["host", "repo_id"]),
+    ("SocketEvent", &["name", "repo_id"]),
+    ("ComposeService", &["name", "repo_id"]),
+    ("ContainerImage", &["path", "repo_id"]),
+    ("K8sDeployment", &["namespace", "name", "repo_id"]),
+    ("K8sService", &["namespace", "name", "repo_id"]),
+    ("EnvVar", &["name", "repo_id"]),
+    ("DependencyCycle", &["id", "repo_id"]),
+    ("LayerViolation", &["from_file", "to_file", "repo_id"]),
+    ("Commit", &["sha", "repo_id"]),
+    ("Contributor", &["email", "repo_id"]),
+    ("Team", &["name", "repo_id"]),
+];
+
+/// Lookups that aren't MERGE keys but run for every edge batch
+const LOOKUP_INDEXES: &[(&str, &[&str])] = &[
+    ("File", &["path", "repo_id"]),
+];
+
+fn schema_statements() -> Vec<String> {
+    let name = |label: &str, props: &[&str]| format!("{}_{}", label.to_lowercase(), props.join("_"));
+    let mut statements = Vec::new();
+    for (label, props) in MERGE_KEYS {
+        if let [prop] = props {
+            statements.push(format!(
+                "CREATE CONSTRAINT {} IF NOT EXISTS FOR (n:{}) REQUIRE n.{} IS UNIQUE",
+                name(label, props),
+                label,
+                prop
+            ));
+        } else {
+            statements.push(composite_index(&name(label, props), label, props));
+        }
+    }
+    for (label, props) in LOOKUP_INDEXES {
+        statements.push(composite_index(&name(label, props), label, props));
+    }
+    statements
+}
+
+fn composite_index(name: &str, label: &str, props: &[&str]) -> String {
+    let properties: Vec<String> = props.iter().map(|p| format!("n.{}", p)).collect();
+    format!("CREATE INDEX {} IF NOT EXISTS FOR (n:{}) ON ({})", name, label, properties.join(", "))
+}
+
+/// Create the constraints and indexes ingestion relies on. Statements the
+/// server rejects (older syntax, duplicates already in the data) are logged
+/// and skipped.
+pub async fn ensure_schema(graph: &neo4rs::Graph) {
+    let statements = schema_statements();
+    let mut failed = 0;
+    for statement in &statements {
+        if let Err(e) = graph.run(query(statement)).await {
+            warn!("⚠️  Schema statement failed, continuing without it: {}: {}", statement, e);
+            failed += 1;
         }
     }
+    info!("🗂️  Ensured Neo4j schema ({} statements, {} failed)", statements.len(), failed);
 }
 
 // ============================================================================
@@ -86,6 +360,10 @@ fn get_qualified_id(file_path: &str, name: &str) -> String {
     format!("{}::{}", file_path, name)
 }
 
+fn get_method_id(file_path: &str, class_name: &str, name: &str) -> String {
+    format!("{}::{}::{}", file_path, class_name, name)
+}
+
 fn file_node_to_map(path: &str, language: &str, job_id: &str, repo_id: &str) -> BoltMap {
     let mut m = HashMap::new();
     m.insert("id".to_string(), path.to_string()); // ID is the relative path
@@ -96,22 +374,34 @@ fn file_node_to_map(path: &str, language: &str, job_id: &str, repo_id: &str) ->
     m
 }
 
-fn class_node_to_map(name: &str, file: &str, start_line: usize, end_line: usize, job_id: &str, repo_id: &str) -> HashMap<String, neo4rs::BoltType> {
+fn class_node_to_map(class: &ClassInfo, file: &str, job_id: &str, repo_id: &str) -> HashMap<String, neo4rs::BoltType> {
     let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
-    let id = get_qualified_id(file, name); // ID is file::name
+    let id = get_qualified_id(file, &class.name); // ID is file::name
     m.insert("id".to_string(), id.into());
-    m.insert("name".to_string(), name.to_string().into());
+    m.insert("name".to_string(), class.name.clone().into());
+    if let Some(kind) = &class.kind {
+        m.insert("kind".to_string(), kind.clone().into());
+    }
     m.insert("file".to_string(), file.to_string().into());
-    m.insert("start_line".to_string(), (start_line as i64).into());
-    m.insert("end_line".to_string(), (end_line as i64).into());
+    m.insert("start_line".to_string(), (class.start_line as i64).into());
+    m.insert("end_line".to_string(), (class.end_line as i64).into());
+    m.insert("body_hash".to_string(), class.body_hash.clone().into());
+    m.insert("decorators".to_string(), class.decorators.clone().into());
+    if let Some(visibility) = &class.visibility {
+        m.insert("visibility".to_string(), visibility.clone().into());
+    }
     m.insert("job_id".to_string(), job_id.to_string().into());
     m.insert("repo_id".to_string(), repo_id.to_string().into());
     m
 }
 
-fn function_node_to_map(func: &FunctionInfo, file: &str, job_id: &str, repo_id: &str) -> HashMap<String, neo4rs::BoltType> {
+fn function_node_to_map(func: &FunctionInfo, class: Option<&str>, file: &str, job_id: &str, repo_id: &str) -> HashMap<String, neo4rs::BoltType> {
     let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
-    let id = get_qualified_id(file, &func.name); // ID is file::name
+    // ID is file::name, or file::Class::name for methods
+    let id = match class {
+        Some(class) => get_method_id(file, class, &func.name),
+        None => get_qualified_id(file, &func.name),
+    };
     m.insert("id".to_string(), id.into());
     m.insert("name".to_string(), func.name.clone().into());
     m.insert("file".to_string(), file.to_string().into());
@@ -119,11 +409,27 @@ fn function_node_to_map(func: &FunctionInfo, file: &str, job_id: &str, repo_id:
     m.insert("end_line".to_string(), (func.end_line as i64).into());
     m.insert("params".to_string(), func.params.clone().into());
     m.insert("return_type".to_string(), func.return_type.clone().unwrap_or_default().into());
+    m.insert("complexity".to_string(), (func.complexity as i64).into());
+    m.insert("body_hash".to_string(), func.body_hash.clone().into());
+    m.insert("decorators".to_string(), func.decorators.clone().into());
+    if let Some(visibility) = &func.visibility {
+        m.insert("visibility".to_string(), visibility.clone().into());
+    }
     m.insert("job_id".to_string(), job_id.to_string().into());
     m.insert("repo_id".to_string(), repo_id.to_string().into());
     m
 }
 
+fn query_node_to_map(query: &SqlQuery, job_id: &str, repo_id: &str) -> BoltMap {
+    let mut m = HashMap::new();
+    m.insert("id".to_string(), query.hash.clone()); // ID is the normalized-text hash
+    m.insert("text".to_string(), query.normalized.clone());
+    m.insert("statement_type".to_string(), query.statement_type.clone());
+    m.insert("job_id".to_string(), job_id.to_string());
+    m.insert("repo_id".to_string(), repo_id.to_string());
+    m
+}
+
 fn module_node_to_map(name: &str, job_id: &str, repo_id: &str) -> BoltMap {
     let mut m = HashMap::new();
     m.insert("name".to_string(), name.to_string());
@@ -132,60 +438,407 @@ fn module_node_to_map(name: &str, job_id: &str, repo_id: &str) -> BoltMap {
     m
 }
 
-async fn delete_file_nodes(graph_db: &neo4rs::Graph, repo_id: &str, files: &[String]) -> Result<()> {
-    if files.is_empty() {
-        return Ok(());
+fn commit_node_to_map(commit: &CommitRecord, repo_id: &str) -> BoltMap {
+    let mut m = HashMap::new();
+    m.insert("sha".to_string(), commit.sha.clone());
+    m.insert("author_name".to_string(), commit.author_name.clone());
+    m.insert("author_email".to_string(), commit.author_email.clone());
+    m.insert("message".to_string(), commit.message.trim().to_string());
+    m.insert("authored_at".to_string(), commit.authored_at.to_rfc3339());
+    m.insert("is_merge".to_string(), commit.is_merge.to_string());
+    m.insert("repo_id".to_string(), repo_id.to_string());
+    m
+}
+
+/// One edge per changed file; edges to files without a File node are
+/// dropped by the MATCH in `batch_insert_touched_edges`
+fn touched_edge_maps(commits: &[CommitRecord], repo_id: &str) -> Vec<BoltMap> {
+    commits
+        .iter()
+        .flat_map(|commit| {
+            commit.changed_files.iter().map(move |file| {
+                let mut m = HashMap::new();
+                m.insert("id".to_string(), edge_id("TOUCHED", &commit.sha, file));
+                m.insert("sha".to_string(), commit.sha.clone());
+                m.insert("file".to_string(), file.clone());
+                m.insert("repo_id".to_string(), repo_id.to_string());
+                m
+            })
+        })
+        .collect()
+}
+
+/// One node per author, from the repo-wide list plus anyone only found in
+/// per-file stats
+fn contributor_node_maps(contributions: &RepoContributions, repo_id: &str) -> Vec<BoltMap> {
+    let mut names: BTreeMap<&str, &str> = BTreeMap::new();
+    let per_file = contributions.files.values().flat_map(|file| &file.contributors);
+    for contributor in contributions.contributors.iter().chain(per_file) {
+        names.entry(contributor.email.as_str()).or_insert(contributor.name.as_str());
     }
 
-    retry_query!(graph_db, {
+    names
+        .into_iter()
+        .map(|(email, name)| {
+            let mut m = HashMap::new();
+            m.insert("email".to_string(), email.to_string());
+            m.insert("name".to_string(), name.to_string());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            m
+        })
+        .collect()
+}
+
+/// One CONTRIBUTED_TO edge per author of each file; edges to files without
+/// a File node are dropped by the MATCH in `batch_insert_authored_edges`
+fn contributed_to_edge_maps(contributions: &RepoContributions, repo_id: &str) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    let mut edges = Vec::new();
+    for (path, file) in &contributions.files {
+        for contributor in &file.contributors {
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("id".to_string(), edge_id("CONTRIBUTED_TO", &contributor.email, path).into());
+            m.insert("email".to_string(), contributor.email.clone().into());
+            m.insert("file".to_string(), path.clone().into());
+            m.insert("commit_count".to_string(), (contributor.commit_count as i64).into());
+            m.insert("lines_added".to_string(), (contributor.lines_added as i64).into());
+            m.insert("lines_deleted".to_string(), (contributor.lines_deleted as i64).into());
+            m.insert("repo_id".to_string(), repo_id.to_string().into());
+            edges.push(m);
+        }
+    }
+    edges
+}
 
+fn cochange_edge_to_map(pair: &CoChangePair, repo_id: &str) -> HashMap<String, neo4rs::BoltType> {
+    let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+    m.insert("id".to_string(), edge_id("CO_CHANGES", &pair.file_a, &pair.file_b).into());
+    m.insert("from".to_string(), pair.file_a.clone().into());
+    m.insert("to".to_string(), pair.file_b.clone().into());
+    m.insert("count".to_string(), (pair.count as i64).into());
+    m.insert("confidence".to_string(), pair.confidence.into());
+    m.insert("repo_id".to_string(), repo_id.to_string().into());
+    m
+}
+
+/// Old and new ids of the classes, functions and methods of each renamed file
+/// that still exist under the new path
+fn renamed_node_ids(parsed_files: &[ParsedFile], renamed_files: &[(String, String)]) -> Vec<BoltMap> {
+    let mut ids = Vec::new();
+    for (from, to) in renamed_files {
+        let Some(file) = parsed_files.iter().find(|f| &f.path == to) else {
+            continue;
+        };
+        let names = file
+            .classes
+            .iter()
+            .map(|c| (None, &c.name))
+            .chain(file.functions.iter().map(|f| (None, &f.name)))
+            .chain(file.classes.iter().flat_map(|c| c.methods.iter().map(move |m| (Some(&c.name), &m.name))));
+        for (class, name) in names {
+            let (old_id, new_id) = match class {
+                Some(class) => (get_method_id(from, class, name), get_method_id(to, class, name)),
+                None => (get_qualified_id(from, name), get_qualified_id(to, name)),
+            };
+            let mut m = HashMap::new();
+            m.insert("old_id".to_string(), old_id);
+            m.insert("new_id".to_string(), new_id);
+            m.insert("file".to_string(), to.clone());
+            ids.push(m);
+        }
+    }
+    ids
+}
+
+fn rename_maps(renamed_files: &[(String, String)]) -> Vec<BoltMap> {
+    renamed_files
+        .iter()
+        .map(|(from, to)| {
+            let mut m = HashMap::new();
+            m.insert("from".to_string(), from.clone());
+            m.insert("to".to_string(), to.clone());
+            m
+        })
+        .collect()
+}
+
+/// Move File nodes and their surviving children to their new paths. Edges
+/// leaving them are dropped since the batch operations recreate them from the
+/// new source; incoming edges are kept.
+async fn rename_file_nodes(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    parsed_files: &[ParsedFile],
+    renamed_files: &[(String, String)],
+) -> Result<()> {
+    if renamed_files.is_empty() {
+        return Ok(());
+    }
 
+    retry_query!(graph_db, {
         query(
-        "UNWIND $paths AS path
-         MATCH (f:File {path: path, repo_id: $repo_id})
-         DETACH DELETE f"
+        "UNWIND $renames AS rename
+         MATCH (f:File {id: rename.from, repo_id: $repo_id})
+         SET f.id = rename.to, f.path = rename.to"
     )
-    .param("paths", files.to_vec())
+    .param("renames", rename_maps(renamed_files))
     .param("repo_id", repo_id)
-
-
     })
-        .context("Failed to delete file nodes")?;
+        .context("Failed to rename file nodes")?;
 
     retry_query!(graph_db, {
+        query(
+        "UNWIND $ids AS node
+         MATCH (n {id: node.old_id, repo_id: $repo_id})
+         WHERE n:Class OR n:Function
+         SET n.id = node.new_id, n.file = node.file"
+    )
+    .param("ids", renamed_node_ids(parsed_files, renamed_files))
+    .param("repo_id", repo_id)
+    })
+        .context("Failed to relink renamed classes and functions")?;
 
+    // Incoming edges are kept, so their ids follow their targets' new ids
+    let moved_ids: Vec<BoltMap> = renamed_files
+        .iter()
+        .map(|(from, to)| HashMap::from([("old_id".to_string(), from.clone()), ("new_id".to_string(), to.clone())]))
+        .chain(renamed_node_ids(parsed_files, renamed_files))
+        .collect();
+    retry_query!(graph_db, {
+        query(
+        "UNWIND $ids AS node
+         MATCH ()-[r {repo_id: $repo_id}]->(n {id: node.new_id, repo_id: $repo_id})
+         WHERE r.id ENDS WITH '->' + node.old_id
+         SET r.id = left(r.id, size(r.id) - size(node.old_id)) + node.new_id"
+    )
+    .param("ids", moved_ids.clone())
+    .param("repo_id", repo_id)
+    })
+        .context("Failed to update ids of edges into renamed files")?;
 
+    let targets: Vec<String> = renamed_files.iter().map(|(_, to)| to.clone()).collect();
+    retry_query!(graph_db, {
         query(
         "UNWIND $paths AS path
-         MATCH (c:Class {file: path, repo_id: $repo_id})
-         DETACH DELETE c"
+         MATCH (n {repo_id: $repo_id})-[r]->()
+         WHERE (n:File AND n.id = path) OR ((n:Class OR n:Function) AND n.file = path)
+         DELETE r"
     )
-    .param("paths", files.to_vec())
+    .param("paths", targets.clone())
     .param("repo_id", repo_id)
+    })
+        .context("Failed to clear outgoing edges of renamed files")?;
+
+    info!("🔀 Renamed {} file nodes", renamed_files.len());
+    Ok(())
+}
 
+/// Ids of every Class and Function node the parsed files produce
+fn symbol_ids(parsed_files: &[ParsedFile]) -> Vec<String> {
+    let mut ids = Vec::new();
+    for file in parsed_files {
+        ids.extend(file.functions.iter().map(|func| get_qualified_id(&file.path, &func.name)));
+        for class in &file.classes {
+            ids.push(get_qualified_id(&file.path, &class.name));
+            ids.extend(class.methods.iter().map(|method| get_method_id(&file.path, &class.name, &method.name)));
+        }
+    }
+    ids
+}
 
-    })
-        .context("Failed to delete class nodes")?;
+/// Relationships leaving a Class or Function node that are derived from its
+/// body, and so are redrawn whenever the symbol is written again
+const SYMBOL_RELATIONSHIPS: &str = "CALLS|CONTAINS|INHERITS|EXECUTES_QUERY|MAPS_TO";
+
+/// Cypher for the Class or Function nodes of `$paths`: the first deletes the
+/// ones not in `$keep`, the second drops the body-derived edges of the rest
+fn symbol_cleanup_cypher(label: &str) -> (String, String) {
+    (
+        format!(
+            "UNWIND $paths AS path
+         MATCH (n:{} {{file: path, repo_id: $repo_id}})
+         WHERE NOT n.id IN $keep
+         DETACH DELETE n",
+            label
+        ),
+        format!(
+            "UNWIND $paths AS path
+         MATCH (n:{} {{file: path, repo_id: $repo_id}})-[r:{}]->()
+         DELETE r",
+            label, SYMBOL_RELATIONSHIPS
+        ),
+    )
+}
+
+/// Delete the nodes of `files` before they are written again. Class and
+/// Function nodes whose id is in `keep` stay, so properties other services
+/// attached to them and edges pointing at them from other files survive;
+/// only their outgoing edges are dropped, to be redrawn from the new parse.
+async fn delete_file_nodes(graph_db: &neo4rs::Graph, repo_id: &str, files: &[String], keep: &[String]) -> Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
+    // File nodes are keyed by normalized path; `./a.rs` or `a\b.rs` would
+    // match nothing
+    let files = &normalize_repo_paths(files).context("Refusing to delete file nodes")?;
 
     retry_query!(graph_db, {
 
 
         query(
         "UNWIND $paths AS path
-         MATCH (fn:Function {file: path, repo_id: $repo_id})
-         DETACH DELETE fn"
+         MATCH (f:File {path: path, repo_id: $repo_id})
+         DETACH DELETE f"
     )
     .param("paths", files.to_vec())
     .param("repo_id", repo_id)
 
 
     })
-        .context("Failed to delete function nodes")?;
+        .context("Failed to delete file nodes")?;
+
+    for label in ["Class", "Function"] {
+        let (stale, outgoing) = symbol_cleanup_cypher(label);
+        retry_query!(graph_db, {
+            query(&stale)
+                .param("paths", files.to_vec())
+                .param("repo_id", repo_id)
+                .param("keep", keep.to_vec())
+        })
+        .with_context(|| format!("Failed to delete {} nodes", label.to_lowercase()))?;
+
+        retry_query!(graph_db, {
+            query(&outgoing)
+                .param("paths", files.to_vec())
+                .param("repo_id", repo_id)
+        })
+        .with_context(|| format!("Failed to clear {} edges", label.to_lowercase()))?;
+    }
 
     Ok(())
 }
 
+/// Node and edge ids in the graph patch format, as stored before a run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExistingIds {
+    pub nodes: Vec<String>,
+    pub edges: Vec<String>,
+    /// Stored `body_hash` of each Class and Function node, by id
+    pub body_hashes: HashMap<String, String>,
+}
+
+/// Relationships that come from the dependency graph and so appear in graph
+/// patches
+const PATCH_RELATIONSHIPS: &str = "DEFINES|CONTAINS|CALLS|IMPORTS|INHERITS|DEPENDS_ON|USES_COMPONENT";
+
+/// Cypher listing the ids (and body hashes) of the File, Class and Function
+/// nodes of `$paths` and of every dependency graph edge touching them. Edge ids follow the
+/// patch's `type:source->target` form; Modules are keyed by name.
+fn existing_ids_cypher() -> (String, String) {
+    let owners = [("File", "path"), ("Class", "file"), ("Function", "file")];
+    let nodes = owners
+        .iter()
+        .map(|(label, key)| {
+            format!(
+                "UNWIND $paths AS path MATCH (n:{} {{{}: path, repo_id: $repo_id}}) RETURN n.id AS id, n.body_hash AS body_hash",
+                label, key
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ");
+    let edges = owners
+        .iter()
+        .map(|(label, key)| {
+            format!(
+                "UNWIND $paths AS path \
+                 MATCH (n:{} {{{}: path, repo_id: $repo_id}})-[r:{}]-(other) \
+                 WHERE NOT other:Library \
+                 WITH r, startNode(r) AS a, endNode(r) AS b \
+                 RETURN toLower(type(r)) + ':' + coalesce(a.id, a.name) + '->' + coalesce(b.id, b.name) AS id",
+                label, key, PATCH_RELATIONSHIPS
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ");
+    (nodes, edges)
+}
+
+/// Ids currently stored for `files`. Call it before an incremental run
+/// deletes their nodes, so the graph patch can list what disappeared.
+pub async fn fetch_existing_ids(graph_db: &neo4rs::Graph, repo_id: &str, files: &[String]) -> Result<ExistingIds> {
+    let mut existing = ExistingIds::default();
+    if files.is_empty() {
+        return Ok(existing);
+    }
+
+    let (nodes_cypher, edges_cypher) = existing_ids_cypher();
+    for (cypher, is_node) in [(nodes_cypher, true), (edges_cypher, false)] {
+        let mut rows = graph_db
+            .execute(query(&cypher).param("paths", files.to_vec()).param("repo_id", repo_id))
+            .await
+            .context("Failed to fetch existing ids")?;
+        while let Some(row) = rows.next().await.context("Failed to read existing ids")? {
+            let Ok(id) = row.get::<String>("id") else { continue };
+            if !is_node {
+                existing.edges.push(id);
+                continue;
+            }
+            if let Ok(hash) = row.get::<String>("body_hash") {
+                existing.body_hashes.insert(id.clone(), hash);
+            }
+            existing.nodes.push(id);
+        }
+    }
+
+    existing.nodes.sort();
+    existing.nodes.dedup();
+    existing.edges.sort();
+    existing.edges.dedup();
+    Ok(existing)
+}
+
+/// The repo's stored file-to-file DEPENDS_ON and TESTS edges, as (source,
+/// target) paths
+pub async fn fetch_file_dependencies(graph_db: &neo4rs::Graph, repo_id: &str) -> Result<Vec<(String, String)>> {
+    let mut rows = graph_db
+        .execute(
+            query(
+                "MATCH (s:File {repo_id: $repo_id})-[:DEPENDS_ON|TESTS]->(t:File {repo_id: $repo_id})
+                 RETURN DISTINCT s.path AS source, t.path AS target",
+            )
+            .param("repo_id", repo_id),
+        )
+        .await
+        .context("Failed to fetch file dependencies")?;
+    let mut dependencies = Vec::new();
+    while let Some(row) = rows.next().await.context("Failed to read file dependencies")? {
+        if let (Ok(source), Ok(target)) = (row.get::<String>("source"), row.get::<String>("target")) {
+            dependencies.push((source, target));
+        }
+    }
+    Ok(dependencies)
+}
 
+/// The repo's stored Library versions by name, empty when unknown. Call it
+/// before storage to diff against, see `dependency_metadata::diff_libraries`.
+pub async fn fetch_library_versions(graph_db: &neo4rs::Graph, repo_id: &str) -> Result<HashMap<String, String>> {
+    let mut rows = graph_db
+        .execute(
+            query(
+                "MATCH (l:Library {repo_id: $repo_id})
+                 RETURN l.name AS name, coalesce(l.version, '') AS version",
+            )
+            .param("repo_id", repo_id),
+        )
+        .await
+        .context("Failed to fetch library versions")?;
+    let mut versions = HashMap::new();
+    while let Some(row) = rows.next().await.context("Failed to read library versions")? {
+        if let (Ok(name), Ok(version)) = (row.get::<String>("name"), row.get::<String>("version")) {
+            versions.insert(name, version);
+        }
+    }
+    Ok(versions)
+}
 
 // ============================================================================
 // Main Storage Function
@@ -196,16 +849,26 @@ pub async fn store_graph(
     graph_db: &neo4rs::Graph,
     job_id: &str,
     repo_id: &str,
-    parsed_files: &[ParsedFile],
+    job: &JobRecord,
+    parsed_files: &ParsedFileSource,
     dep_graph: &DependencyGraph,
     git_contributions: Option<&RepoContributions>,
-    boundary_result: &BoundaryDetectionResult,
+    boundary_result: Option<&BoundaryDetectionResult>,
     library_dependencies: &[LibraryDependency],
-    communication_analysis: &CommunicationAnalysis,
+    communication_analysis: Option<&CommunicationAnalysis>,
+    orm_models: &[OrmModel],
+    ddl_tables: &[DdlTable],
+    packages: &[Package],
+    assets: Option<&[AssetFile]>,
+    infrastructure: Option<&InfraAnalysis>,
+    codeowners: Option<&CodeOwners>,
+    layer_violations: &[LayerViolation],
     config: Option<BatchConfig>,
-    progress_tx: Option<tokio::sync::mpsc::Sender<i32>>,
-) -> Result<()> {
-    let config = config.unwrap_or_default();
+) -> Result<Vec<PartialError>> {
+    let writer = BatchWriter::new(config.unwrap_or_default());
+    if let Some(boundary_result) = boundary_result {
+        reconcile_boundaries(graph_db, repo_id, boundary_result, parsed_files.outlines(), true, &writer).await?;
+    }
     execute_batch_operations(
         graph_db, 
         job_id, 
@@ -216,150 +879,568 @@ pub async fn store_graph(
         boundary_result,
         library_dependencies,
         communication_analysis,
-        &config,
-        progress_tx
-    ).await
+        orm_models,
+        ddl_tables,
+        packages,
+        assets,
+        infrastructure,
+        codeowners,
+        &writer,
+    ).await?;
+
+    // Only a full run sees every import, so only it can redraw the cycles,
+    // the boundary dependency weights and the layer violations
+    replace_dependency_cycles(graph_db, repo_id, &dep_graph.find_cycles(), &writer).await?;
+    if let Some(boundary_result) = boundary_result {
+        let boundary_dependencies = metrics::boundary_dependencies(boundary_result, dep_graph);
+        replace_boundary_dependencies(graph_db, repo_id, &boundary_dependencies, &writer).await?;
+        replace_layer_violations(graph_db, repo_id, layer_violations, &writer).await?;
+        replace_team_ownership(graph_db, repo_id, boundary_result, codeowners, &writer).await?;
+    }
+
+    delete_removed_libraries(graph_db, repo_id, job.library_diff.as_ref()).await?;
+    record_job(graph_db, job_id, repo_id, "full", job, &writer.config.node_metadata).await?;
+    writer.group_done();
+    Ok(writer.into_errors())
 }
 
 async fn execute_batch_operations(
     graph_db: &neo4rs::Graph,
     job_id: &str,
     repo_id: &str,
-    parsed_files: &[ParsedFile],
+    parsed_files: &ParsedFileSource,
     dep_graph: &DependencyGraph,
     git_contributions: Option<&RepoContributions>,
-    boundary_result: &BoundaryDetectionResult,
+    boundary_result: Option<&BoundaryDetectionResult>,
     library_dependencies: &[LibraryDependency],
-    communication_analysis: &CommunicationAnalysis,
-    config: &BatchConfig,
-    progress_tx: Option<tokio::sync::mpsc::Sender<i32>>,
+    communication_analysis: Option<&CommunicationAnalysis>,
+    orm_models: &[OrmModel],
+    ddl_tables: &[DdlTable],
+    packages: &[Package],
+    assets: Option<&[AssetFile]>,
+    infrastructure: Option<&InfraAnalysis>,
+    codeowners: Option<&CodeOwners>,
+    writer: &BatchWriter,
 ) -> Result<()> {
-    let update_prog = |p: i32| {
-        if let Some(tx) = &progress_tx {
-            let _ = tx.try_send(p);
-        }
-    };
-
-    // 1. Create Job node
-    create_job_node(graph_db, job_id, repo_id).await?;
+    // File nodes tell abstract files by their call lists and Query nodes
+    // need the queries, so both read whole files; the rest reads outlines
+    let outlines = parsed_files.outlines();
 
     // 2. Batch insert nodes
-    batch_insert_file_nodes(graph_db, job_id, repo_id, parsed_files, git_contributions, config.batch_size).await?;
-    batch_insert_class_nodes(graph_db, job_id, repo_id, parsed_files, config.batch_size).await?;
-    batch_insert_function_nodes(graph_db, job_id, repo_id, parsed_files, config.batch_size).await?;
-    batch_insert_module_nodes(graph_db, job_id, repo_id, dep_graph, config.batch_size).await?;
+    for chunk in parsed_files.chunks()? {
+        batch_insert_file_nodes(graph_db, job_id, repo_id, &chunk?, git_contributions, codeowners, writer).await?;
+    }
+    batch_insert_class_nodes(graph_db, job_id, repo_id, outlines, writer).await?;
+    batch_insert_function_nodes(graph_db, job_id, repo_id, outlines, writer).await?;
+    batch_insert_module_nodes(graph_db, job_id, repo_id, dep_graph, writer).await?;
+    writer.group_done();
+
+    // 2b. Batch insert the package hierarchy over the files
+    batch_insert_packages(graph_db, repo_id, packages, writer).await?;
+    writer.group_done();
     
     // 3. Batch insert boundaries
-    batch_insert_boundary_nodes(graph_db, job_id, repo_id, boundary_result, config.batch_size).await?;
+    if let Some(boundary_result) = boundary_result {
+        batch_insert_boundary_nodes(graph_db, job_id, repo_id, boundary_result, outlines, writer).await?;
+    }
+    writer.group_done();
 
     // 3b. Batch insert library nodes
-    batch_insert_library_nodes(graph_db, job_id, repo_id, library_dependencies, config.batch_size).await?;
+    batch_insert_library_nodes(graph_db, job_id, repo_id, library_dependencies, writer).await?;
+    writer.group_done();
 
     // 4. Batch insert edges
-    batch_insert_defines_edges(graph_db, repo_id, dep_graph, config.batch_size).await?;
-    batch_insert_contains_edges(graph_db, repo_id, dep_graph, config.batch_size).await?;
-    batch_insert_calls_edges(graph_db, repo_id, dep_graph, config.batch_size).await?;
-    batch_insert_imports_edges(graph_db, repo_id, dep_graph, config.batch_size).await?;
-    batch_insert_inherits_edges(graph_db, repo_id, dep_graph, config.batch_size).await?;
-    batch_insert_belongs_to_edges(graph_db, repo_id, boundary_result, config.batch_size).await?;
+    batch_insert_defines_edges(graph_db, repo_id, dep_graph, writer).await?;
+    batch_insert_contains_edges(graph_db, repo_id, dep_graph, writer).await?;
+    batch_insert_calls_edges(graph_db, repo_id, dep_graph, writer).await?;
+    batch_insert_imports_edges(graph_db, repo_id, dep_graph, writer).await?;
+    batch_insert_inherits_edges(graph_db, repo_id, dep_graph, writer).await?;
+    if let Some(boundary_result) = boundary_result {
+        batch_insert_belongs_to_edges(graph_db, repo_id, boundary_result, writer).await?;
+    }
+    if let Some(assets) = assets {
+        replace_assets(graph_db, repo_id, assets, writer).await?;
+    }
+    writer.group_done();
 
     // 4b. Batch insert library edges
-    batch_insert_library_edges(graph_db, repo_id, parsed_files, library_dependencies, config.batch_size).await?;
-
-    // 4c. Batch insert data dependency edges (tables)
-    batch_insert_table_nodes(graph_db, repo_id, parsed_files, config.batch_size).await?;
-    batch_insert_table_edges(graph_db, repo_id, parsed_files, config.batch_size).await?;
+    batch_insert_library_edges(graph_db, repo_id, outlines, library_dependencies, writer).await?;
+    writer.group_done();
+
+    // 4c. Batch insert data dependency edges (tables), DDL definitions first
+    batch_insert_ddl_tables(graph_db, repo_id, ddl_tables, writer).await?;
+    batch_insert_table_nodes(graph_db, repo_id, outlines, writer).await?;
+    batch_insert_table_edges(graph_db, repo_id, outlines, writer).await?;
+    let mut stored_queries = HashSet::new();
+    for chunk in parsed_files.chunks()? {
+        let chunk = chunk?;
+        batch_insert_query_nodes(graph_db, job_id, repo_id, &chunk, &stored_queries, writer).await?;
+        batch_insert_query_edges(graph_db, repo_id, &chunk, &stored_queries, writer).await?;
+        stored_queries.extend(chunk.iter().flat_map(|file| &file.sql_queries).map(|sql| sql.hash.clone()));
+    }
+    batch_insert_orm_edges(graph_db, job_id, repo_id, orm_models, writer).await?;
+    writer.group_done();
 
     // 4d. Batch insert service communication edges
-    batch_insert_service_nodes(graph_db, repo_id, parsed_files, config.batch_size).await?;
-    batch_insert_service_edges(graph_db, repo_id, parsed_files, config.batch_size).await?;
+    batch_insert_service_nodes(graph_db, repo_id, outlines, writer).await?;
+    batch_insert_service_edges(graph_db, repo_id, outlines, writer).await?;
+    writer.group_done();
 
     // 4e. Batch insert communication nodes and edges
-    batch_insert_endpoint_nodes(graph_db, repo_id, communication_analysis, config.batch_size).await?;
-    batch_insert_endpoint_edges(graph_db, repo_id, communication_analysis, config.batch_size).await?;
-    batch_insert_rpc_nodes(graph_db, repo_id, communication_analysis, config.batch_size).await?;
-    batch_insert_rpc_edges(graph_db, repo_id, communication_analysis, config.batch_size).await?;
-    batch_insert_queue_nodes(graph_db, repo_id, communication_analysis, config.batch_size).await?;
-    batch_insert_queue_edges(graph_db, repo_id, communication_analysis, config.batch_size).await?;
-    batch_insert_compose_service_nodes(graph_db, repo_id, communication_analysis, config.batch_size).await?;
-    batch_insert_endpoint_service_edges(graph_db, repo_id, communication_analysis, config.batch_size).await?;
-    
-    // 5. Create file-to-file dependency edges based on imports
-    batch_insert_file_dependencies(graph_db, repo_id, parsed_files, config.batch_size).await?;
+    if let Some(communication_analysis) = communication_analysis {
+        batch_insert_endpoint_nodes(graph_db, repo_id, communication_analysis, writer).await?;
+        batch_insert_endpoint_edges(graph_db, repo_id, communication_analysis, writer).await?;
+        batch_insert_route_nodes(graph_db, repo_id, communication_analysis, writer).await?;
+        batch_insert_route_edges(graph_db, repo_id, communication_analysis, writer).await?;
+        link_endpoints_to_routes(graph_db, repo_id).await?;
+        replace_api_operations(graph_db, repo_id, communication_analysis, writer).await?;
+        link_api_operations(graph_db, repo_id).await?;
+        batch_insert_rpc_nodes(graph_db, repo_id, communication_analysis, writer).await?;
+        batch_insert_rpc_edges(graph_db, repo_id, communication_analysis, writer).await?;
+        store_rpc_methods(graph_db, repo_id, communication_analysis, writer).await?;
+        batch_insert_queue_nodes(graph_db, repo_id, communication_analysis, writer).await?;
+        batch_insert_queue_edges(graph_db, repo_id, communication_analysis, writer).await?;
+        batch_insert_realtime_nodes(graph_db, repo_id, communication_analysis, writer).await?;
+        batch_insert_realtime_edges(graph_db, repo_id, communication_analysis, writer).await?;
+        mark_websocket_servers(graph_db, repo_id, outlines, communication_analysis, writer).await?;
+        batch_insert_compose_service_nodes(graph_db, repo_id, communication_analysis, writer).await?;
+        batch_insert_compose_dependency_edges(graph_db, repo_id, communication_analysis, writer).await?;
+        batch_insert_endpoint_service_edges(graph_db, repo_id, communication_analysis, writer).await?;
+    }
+    writer.group_done();
+
+    // 4e'. Replace the Dockerfile and Kubernetes nodes, linked to the
+    // compose services that build the images
+    if let Some(infrastructure) = infrastructure {
+        let compose_services = communication_analysis.map(|c| c.compose_services.as_slice()).unwrap_or_default();
+        replace_infrastructure(graph_db, repo_id, infrastructure, compose_services, writer).await?;
+    }
+    writer.group_done();
+
+    // 4f. Batch insert environment variables, read by files and set by
+    // compose services; without communication analysis only the reads
+    let no_communication = CommunicationAnalysis::default();
+    let communication_analysis = communication_analysis.unwrap_or(&no_communication);
+    batch_insert_env_var_nodes(graph_db, repo_id, outlines, communication_analysis, writer).await?;
+    batch_insert_env_var_edges(graph_db, repo_id, outlines, communication_analysis, writer).await?;
+    writer.group_done();
+
+    // 5. Create file-to-file dependency edges based on imports and rendered components
+    batch_insert_file_dependencies(graph_db, repo_id, outlines, dep_graph, writer).await?;
+    batch_insert_uses_component_edges(graph_db, repo_id, dep_graph, writer).await?;
+    writer.group_done();
+
+    // 6. Commit history
+    if let Some(contributions) = git_contributions {
+        let commits = &contributions.commits[..contributions.commits.len().min(writer.config.max_commits)];
+        batch_insert_commit_nodes(graph_db, repo_id, commits, writer).await?;
+        batch_insert_touched_edges(graph_db, repo_id, commits, writer).await?;
+        batch_insert_cochange_edges(graph_db, repo_id, &contributions.cochange, writer).await?;
+        batch_insert_contributor_nodes(graph_db, repo_id, contributions, writer).await?;
+        batch_insert_authored_edges(graph_db, repo_id, contributions, writer).await?;
+    }
+    writer.group_done();
 
     Ok(())
 }
 
+/// Paths whose File nodes an incremental update deletes before inserting:
+/// changed files other than rename targets, which keep their nodes, and
+/// removed files. Orphans can only appear when this is non-empty.
+fn files_to_remove(changed_files: &[String], removed_files: &[String], renamed_files: &[(String, String)]) -> Vec<String> {
+    let rename_targets: HashSet<&String> = renamed_files.iter().map(|(_, to)| to).collect();
+    let mut files = Vec::new();
+    files.extend(changed_files.iter().filter(|f| !rename_targets.contains(f)).cloned());
+    files.extend_from_slice(removed_files);
+    files.sort();
+    files.dedup();
+    files
+}
+
 /// Store an incremental graph update for a subset of files
 pub async fn store_graph_incremental(
     graph_db: &neo4rs::Graph,
     job_id: &str,
     repo_id: &str,
-    parsed_files: &[ParsedFile],
+    job: &JobRecord,
+    parsed_files: &ParsedFileSource,
     dep_graph: &DependencyGraph,
     git_contributions: Option<&RepoContributions>,
-    boundary_result: &BoundaryDetectionResult,
+    boundary_result: Option<&BoundaryDetectionResult>,
     library_dependencies: &[LibraryDependency],
-    communication_analysis: &CommunicationAnalysis,
+    communication_analysis: Option<&CommunicationAnalysis>,
+    orm_models: &[OrmModel],
+    ddl_tables: &[DdlTable],
+    packages: &[Package],
+    assets: Option<&[AssetFile]>,
+    infrastructure: Option<&InfraAnalysis>,
+    codeowners: Option<&CodeOwners>,
     changed_files: &[String],
     removed_files: &[String],
+    renamed_files: &[(String, String)],
     config: Option<BatchConfig>,
-    progress_tx: Option<tokio::sync::mpsc::Sender<i32>>,
-) -> Result<()> {
-    let config = config.unwrap_or_default();
-    let mut files_to_remove = Vec::new();
-    files_to_remove.extend_from_slice(changed_files);
-    files_to_remove.extend_from_slice(removed_files);
-    files_to_remove.sort();
-    files_to_remove.dedup();
+) -> Result<Vec<PartialError>> {
+    let writer = BatchWriter::new(config.unwrap_or_default());
+
+    // Renamed files keep their nodes (and the edges pointing at them); only
+    // symbols missing from the new file stay under the old path and are
+    // deleted with it below
+    rename_file_nodes(graph_db, repo_id, parsed_files.outlines(), renamed_files).await?;
+    let files_to_remove = files_to_remove(changed_files, removed_files, renamed_files);
+    let cleanup_pending = orphan_cleanup_pending(graph_db, repo_id).await?;
+    if !files_to_remove.is_empty() {
+        mark_orphan_cleanup_pending(graph_db, repo_id, &writer.config.node_metadata).await?;
+    }
 
-    delete_file_nodes(graph_db, repo_id, &files_to_remove).await?;
+    delete_file_nodes(graph_db, repo_id, &files_to_remove, &symbol_ids(parsed_files.outlines())).await?;
+    // Only the changed files were assigned to boundaries, so other files'
+    // boundaries can't be judged stale here
+    if let Some(boundary_result) = boundary_result {
+        reconcile_boundaries(graph_db, repo_id, boundary_result, parsed_files.outlines(), false, &writer).await?;
+    }
 
+    let merged_contributions = git_contributions.map(|c| c.with_renames(renamed_files));
     execute_batch_operations(
         graph_db,
         job_id,
         repo_id,
         parsed_files,
         dep_graph,
-        git_contributions,
+        merged_contributions.as_ref(),
         boundary_result,
         library_dependencies,
         communication_analysis,
-        &config,
-        progress_tx
+        orm_models,
+        ddl_tables,
+        packages,
+        assets,
+        infrastructure,
+        codeowners,
+        &writer,
     )
-    .await
+    .await?;
+
+    // Not part of the inserts' transactions, which commit chunk by chunk, so
+    // a cleanup an earlier job never got to is finished here
+    if !files_to_remove.is_empty() || cleanup_pending {
+        delete_orphaned_nodes(graph_db, repo_id).await?;
+    }
+
+    // Manifests are read in full either way, so the diff holds here too
+    delete_removed_libraries(graph_db, repo_id, job.library_diff.as_ref()).await?;
+    record_job(graph_db, job_id, repo_id, "incremental", job, &writer.config.node_metadata).await?;
+    writer.group_done();
+    Ok(writer.into_errors())
 }
-// ============================================================================
-// Job Node
-// ============================================================================
 
-async fn create_job_node(graph_db: &neo4rs::Graph, job_id: &str, repo_id: &str) -> Result<()> {
-    retry_query!(graph_db, {
+/// Storage runs replayed on a fresh connection after the connection to
+/// Neo4j was lost
+pub const MAX_STORAGE_REPLAYS: u32 = 2;
+
+/// Error messages of a lost connection or an unavailable server, as opposed
+/// to a query Neo4j rejected
+const CONNECTION_ERROR_MARKERS: &[&str] = &[
+    "an IO error occurred",
+    "connection error",
+    "broken pipe",
+    "connection reset",
+    "connection refused",
+    "ServiceUnavailable",
+    "Neo.TransientError",
+];
+
+/// Whether `error` means the connection to Neo4j was lost, so the same
+/// writes can be replayed on a new connection
+pub fn is_connection_error(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| match cause.downcast_ref::<neo4rs::Error>() {
+        Some(neo4rs::Error::IOError { .. } | neo4rs::Error::ConnectionError) => true,
+        Some(neo4rs::Error::UnexpectedMessage(message)) => is_connection_message(message),
+        Some(_) => false,
+        None => cause.is::<std::io::Error>(),
+    })
+}
 
-        query(
-        "MERGE (j:Job {id: $id, repo_id: $repo_id})
-         SET j.status = 'COMPLETED', j.timestamp = datetime()"
-    )
-    .param("id", job_id)
-    .param("repo_id", repo_id)
+fn is_connection_message(message: &str) -> bool {
+    let lower = message.to_lowercase();
+    CONNECTION_ERROR_MARKERS.iter().any(|marker| lower.contains(&marker.to_lowercase()))
+}
 
-    }).context("Failed to create job node")?;
-    info!("   Created Job node: {}", job_id);
-    Ok(())
+/// Whether a storage run failed, or skipped chunks, because the connection
+/// was lost
+fn lost_connection(result: &Result<Vec<PartialError>>) -> bool {
+    match result {
+        Err(e) => is_connection_error(e),
+        Ok(errors) => errors.iter().any(|e| is_connection_message(&e.error)),
+    }
 }
 
-// ============================================================================
-// Batch Node Inserts
-// ============================================================================
+/// Run `store` and, when it lost the connection, `reconnect` and run it
+/// again, up to `MAX_STORAGE_REPLAYS` times. Every write is a MERGE or a
+/// delete-and-rewrite, so a replay converges on the same graph; `store`
+/// must derive everything it deletes from its inputs, not from an earlier
+/// attempt. Returns the last run's partial errors and the replay count.
+pub async fn store_with_replays<G, S, SF, R, RF>(graph: G, store: S, reconnect: R) -> Result<(Vec<PartialError>, u32)>
+where
+    S: Fn(G) -> SF,
+    SF: std::future::Future<Output = Result<Vec<PartialError>>>,
+    R: Fn() -> RF,
+    RF: std::future::Future<Output = Result<G>>,
+{
+    let mut result = store(graph).await;
+    let mut replays = 0;
+    while replays < MAX_STORAGE_REPLAYS && lost_connection(&result) {
+        replays += 1;
+        warn!("⚠️  Lost the Neo4j connection while storing, reconnecting for replay {}/{}", replays, MAX_STORAGE_REPLAYS);
+        match reconnect().await {
+            Ok(graph) => result = store(graph).await,
+            Err(e) => {
+                warn!("⚠️  Could not reconnect to Neo4j: {:#}", e);
+                break;
+            }
+        }
+    }
+    if replays > 0 && !lost_connection(&result) {
+        info!("✅ Stored the graph after {} replays", replays);
+    }
+    result.map(|errors| (errors, replays))
+}
 
-async fn batch_insert_file_nodes(
-    graph_db: &neo4rs::Graph,
-    job_id: &str,
+/// Job option that runs every analysis phase but writes nothing to Neo4j
+pub const DRY_RUN_OPTION: &str = "dry_run";
+
+pub fn dry_run_requested(options: Option<&HashMap<String, String>>) -> bool {
+    options.and_then(|opts| opts.get(DRY_RUN_OPTION)).is_some_and(|value| value == "true")
+}
+
+/// How a job's graph reaches Neo4j
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageMode {
+    /// `store_graph`: the whole repository replaces what was stored
+    Full,
+    /// `store_graph_incremental`: only the changed and removed files
+    Incremental,
+    /// Preview and dry-run jobs write nothing
+    Skipped,
+}
+
+impl StorageMode {
+    pub fn for_job(incremental: bool, preview: bool, dry_run: bool) -> Self {
+        if preview || dry_run {
+            StorageMode::Skipped
+        } else if incremental {
+            StorageMode::Incremental
+        } else {
+            StorageMode::Full
+        }
+    }
+}
+
+/// `store_with_replays` unless `mode` skips storage, in which case `store`
+/// is never called and there is nothing to report
+pub async fn store_for_mode<G, S, SF, R, RF>(
+    mode: StorageMode,
+    graph: G,
+    store: S,
+    reconnect: R,
+) -> Result<(Vec<PartialError>, u32)>
+where
+    S: Fn(G) -> SF,
+    SF: std::future::Future<Output = Result<Vec<PartialError>>>,
+    R: Fn() -> RF,
+    RF: std::future::Future<Output = Result<G>>,
+{
+    if mode == StorageMode::Skipped {
+        return Ok((Vec::new(), 0));
+    }
+    store_with_replays(graph, store, reconnect).await
+}
+
+/// Labels of nodes that only exist because some file referenced them
+const FILE_DERIVED_LABELS: &[&str] =
+    &["Module", "Package", "Table", "Service", "Endpoint", "RpcService", "MessageQueue", "WebSocketChannel", "SocketEvent", "EnvVar"];
+
+/// Labels of nodes that only live as long as some node points at them with
+/// the given relationship, whatever other edges they have
+const OWNED_LABELS: &[(&str, &str)] = &[
+    ("Query", "EXECUTES_QUERY"),
+    ("Endpoint", "CALLS_ENDPOINT"),
+    ("Route", "DEFINES_ROUTE"),
+    ("DependencyCycle", "PART_OF_CYCLE"),
+];
+
+/// Cypher removing nodes left behind once the files referencing them are
+/// gone, then clearing the repository's pending cleanup. Edges from removed
+/// files already went with their DETACH DELETE. Owned nodes go first, since
+/// their edges (a Query's READS/WRITES, an Endpoint's RESOLVES_TO) would
+/// otherwise keep them and their targets alive. Each label is matched on its
+/// own so the `repo_id` indexes apply.
+fn orphan_cleanup_cypher() -> String {
+    let owned = OWNED_LABELS.iter().map(|(label, owner_rel)| {
+        format!(
+            "CALL {{
+           MATCH (q:{} {{repo_id: $repo_id}})
+           WHERE NOT ()-[:{}]->(q)
+           DETACH DELETE q
+         }}
+         ",
+            label, owner_rel
+        )
+    });
+    let orphans = FILE_DERIVED_LABELS.iter().map(|label| {
+        format!(
+            "CALL {{
+           MATCH (n:{} {{repo_id: $repo_id}})
+           WHERE NOT (n)--()
+           DELETE n
+         }}
+         ",
+            label
+        )
+    });
+    let mut cypher: String = owned.chain(orphans).collect();
+    cypher.push_str(
+        "CALL {
+           MATCH (r:Repository {id: $repo_id})
+           REMOVE r.orphan_cleanup_pending
+         }",
+    );
+    cypher
+}
+
+fn orphan_cleanup_query(repo_id: &str) -> neo4rs::Query {
+    query(&orphan_cleanup_cypher()).param("repo_id", repo_id)
+}
+
+fn mark_orphan_cleanup_cypher() -> &'static str {
+    "MERGE (r:Repository {id: $repo_id})
+     SET r += $metadata, r.orphan_cleanup_pending = true"
+}
+
+/// Flag the repository before an incremental update deletes File nodes, so
+/// a job failing before `delete_orphaned_nodes` leaves a record that the
+/// next incremental job picks up
+async fn mark_orphan_cleanup_pending(graph_db: &neo4rs::Graph, repo_id: &str, metadata: &NodeMetadata) -> Result<()> {
+    retry_query!(graph_db, {
+        query(mark_orphan_cleanup_cypher()).param("repo_id", repo_id).param("metadata", metadata.to_bolt())
+    }).context("Failed to mark the orphan cleanup as pending")
+}
+
+/// Whether an earlier incremental update removed files but never finished
+/// its orphan cleanup
+async fn orphan_cleanup_pending(graph_db: &neo4rs::Graph, repo_id: &str) -> Result<bool> {
+    let mut rows = graph_db
+        .execute(
+            query("MATCH (r:Repository {id: $repo_id}) RETURN coalesce(r.orphan_cleanup_pending, false) AS pending")
+                .param("repo_id", repo_id),
+        )
+        .await
+        .context("Failed to read the pending orphan cleanup")?;
+    let row = rows.next().await.context("Failed to read the pending orphan cleanup")?;
+    Ok(row.and_then(|row| row.get::<bool>("pending").ok()).unwrap_or(false))
+}
+
+/// Delete orphaned nodes after an incremental update. The cleanup is one
+/// statement, so it applies in full or not at all, but it commits apart
+/// from the inserts before it. It matches every orphan of the repository,
+/// so repeating it is safe: a job failing before it leaves the repository
+/// flagged by `mark_orphan_cleanup_pending`, and the next incremental job
+/// runs it whether or not that job removes files.
+async fn delete_orphaned_nodes(graph_db: &neo4rs::Graph, repo_id: &str) -> Result<()> {
+    retry_query!(graph_db, {
+        orphan_cleanup_query(repo_id)
+    }).context("Failed to delete orphaned nodes")?;
+
+    info!("   Removed orphaned {} nodes", FILE_DERIVED_LABELS.join("/"));
+    Ok(())
+}
+// ============================================================================
+// Job Node
+// ============================================================================
+
+/// Cypher storing a finished job as a Job node linked to its Repository,
+/// then deleting all but the newest `$keep` Job nodes of the repository in
+/// the same transaction. Job nodes stored before they had `started_at` sort
+/// by their `timestamp`.
+fn record_job_cypher() -> &'static str {
+    "MERGE (r:Repository {id: $repo_id})
+     MERGE (j:Job {id: $id, repo_id: $repo_id})
+     SET r += $metadata, j += $metadata
+     SET j.status = 'COMPLETED',
+         j.timestamp = datetime($finished_at),
+         j.started_at = datetime($started_at),
+         j.finished_at = datetime($finished_at),
+         j.mode = $mode,
+         j.files_processed = $files_processed,
+         j.nodes_created = $nodes_created,
+         j.analyzed_commit = $analyzed_commit,
+         j.libraries_added = $libraries_added,
+         j.libraries_removed = $libraries_removed,
+         j.libraries_upgraded = $libraries_upgraded
+     MERGE (j)-[f:FOR_REPO]->(r)
+     SET f.id = $edge_id,
+         f.repo_id = $repo_id
+     WITH DISTINCT r
+     MATCH (old:Job {repo_id: $repo_id})
+     WITH old ORDER BY coalesce(old.started_at, old.timestamp) DESC SKIP $keep
+     DETACH DELETE old"
+}
+
+fn job_node_params(
+    job_id: &str,
+    repo_id: &str,
+    mode: &str,
+    job: &JobRecord,
+    finished_at: DateTime<Utc>,
+) -> HashMap<String, neo4rs::BoltType> {
+    let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+    m.insert("id".to_string(), job_id.to_string().into());
+    m.insert("repo_id".to_string(), repo_id.to_string().into());
+    m.insert("edge_id".to_string(), edge_id("FOR_REPO", job_id, repo_id).into());
+    m.insert("mode".to_string(), mode.to_string().into());
+    m.insert("started_at".to_string(), job.started_at.to_rfc3339().into());
+    m.insert("finished_at".to_string(), finished_at.to_rfc3339().into());
+    m.insert("files_processed".to_string(), (job.files_processed as i64).into());
+    m.insert("nodes_created".to_string(), (job.nodes_created as i64).into());
+    m.insert("analyzed_commit".to_string(), job.analyzed_commit.clone().into());
+    // JSON lists, null when there's no diff
+    let diff = job.library_diff.as_ref();
+    m.insert("libraries_added".to_string(), diff.and_then(|d| serde_json::to_string(&d.added).ok()).into());
+    m.insert("libraries_removed".to_string(), diff.and_then(|d| serde_json::to_string(&d.removed).ok()).into());
+    m.insert("libraries_upgraded".to_string(), diff.and_then(|d| serde_json::to_string(&d.upgraded).ok()).into());
+    m.insert("keep".to_string(), (JOB_HISTORY as i64).into());
+    m
+}
+
+/// Store the finished job and prune the repository's job history
+async fn record_job(
+    graph_db: &neo4rs::Graph,
+    job_id: &str,
+    repo_id: &str,
+    mode: &str,
+    job: &JobRecord,
+    metadata: &NodeMetadata,
+) -> Result<()> {
+    let mut params = job_node_params(job_id, repo_id, mode, job, Utc::now());
+    params.insert("metadata".to_string(), metadata.to_bolt());
+    retry_query!(graph_db, {
+        query(record_job_cypher()).params(params.clone())
+    }).context("Failed to record job node")?;
+    info!("   Recorded {} Job node: {}", mode, job_id);
+    Ok(())
+}
+
+// ============================================================================
+// Batch Node Inserts
+// ============================================================================
+
+async fn batch_insert_file_nodes(
+    graph_db: &neo4rs::Graph,
+    job_id: &str,
     repo_id: &str,
     parsed_files: &[ParsedFile],
     git_contributions: Option<&RepoContributions>,
-    batch_size: usize,
+    codeowners: Option<&CodeOwners>,
+    writer: &BatchWriter,
 ) -> Result<()> {
     let nodes: Vec<HashMap<String, neo4rs::BoltType>> = parsed_files
         .iter()
@@ -368,8 +1449,19 @@ async fn batch_insert_file_nodes(
             m.insert("id".to_string(), f.path.clone().into());
             m.insert("path".to_string(), f.path.clone().into());
             m.insert("language".to_string(), f.language.clone().into());
+            m.insert("line_count".to_string(), (f.line_count as i64).into());
+            m.insert("is_test".to_string(), f.is_test.into());
+            m.insert("generated".to_string(), f.generated.into());
+            m.insert("parse_failed".to_string(), f.parse_failed.into());
+            m.insert("abstract".to_string(), metrics::is_abstract_file(f).into());
+            m.insert("public_api_count".to_string(), (f.public_api_count() as i64).into());
             m.insert("job_id".to_string(), job_id.to_string().into());
             m.insert("repo_id".to_string(), repo_id.to_string().into());
+            let owners = codeowners.map(|c| c.owners(&f.path).to_vec()).unwrap_or_default();
+            m.insert("owners".to_string(), owners.into());
+            let functions = f.functions.len() + f.classes.iter().map(|c| c.methods.len()).sum::<usize>();
+            let commit_count = metrics::file_commit_count(&f.path, git_contributions);
+            m.insert("hotspot_score".to_string(), metrics::hotspot_score(commit_count, functions).into());
             
             // Add git metrics if available
             if let Some(contributions) = git_contributions {
@@ -394,26 +1486,32 @@ async fn batch_insert_file_nodes(
         })
         .collect();
 
-    for chunk in nodes.chunks(batch_size) {
-        retry_query!(graph_db, {
-
-            query(
+    let nodes = writer.stamp(nodes);
+    writer.write(graph_db, "file nodes", &nodes, |chunk| {
+        query(
             "UNWIND $nodes AS node
              MERGE (f:File {id: node.id})
+             SET f += node.metadata
              SET f.path = node.path,
                  f.language = node.language,
+                 f.line_count = node.line_count,
+                 f.is_test = node.is_test,
+                 f.generated = node.generated,
+                 f.parse_failed = node.parse_failed,
+                 f.abstract = node.abstract,
+                 f.public_api_count = node.public_api_count,
                  f.job_id = node.job_id,
                  f.repo_id = node.repo_id,
+                 f.owners = node.owners,
                  f.commit_count = COALESCE(node.commit_count, 0),
                  f.last_commit_date = COALESCE(node.last_commit_date, ''),
                  f.primary_author = COALESCE(node.primary_author, ''),
                  f.lines_changed_total = COALESCE(node.lines_changed_total, 0),
-                 f.contributors = COALESCE(node.contributors, [])"
+                 f.contributors = COALESCE(node.contributors, []),
+                 f.hotspot_score = node.hotspot_score"
         )
         .param("nodes", chunk.to_vec())
-
-        }).context("Failed to batch insert file nodes")?;
-    }
+    }).await?;
     
     info!("   Inserted {} File nodes", nodes.len());
     Ok(())
@@ -424,33 +1522,35 @@ async fn batch_insert_class_nodes(
     job_id: &str,
     repo_id: &str,
     parsed_files: &[ParsedFile],
-    batch_size: usize,
+    writer: &BatchWriter,
 ) -> Result<()> {
     let mut nodes: Vec<HashMap<String, neo4rs::BoltType>> = Vec::new();
     
     for file in parsed_files {
         for class in &file.classes {
-            nodes.push(class_node_to_map(&class.name, &file.path, class.start_line, class.end_line, job_id, repo_id));
+            nodes.push(class_node_to_map(class, &file.path, job_id, repo_id));
         }
     }
 
-    for chunk in nodes.chunks(batch_size) {
-        retry_query!(graph_db, {
-
-            query(
+    let nodes = writer.stamp(nodes);
+    writer.write(graph_db, "class nodes", &nodes, |chunk| {
+        query(
             "UNWIND $nodes AS node
              MERGE (c:Class {id: node.id})
+             SET c += node.metadata
              SET c.name = node.name,
+                 c.kind = node.kind,
                  c.file = node.file,
                  c.start_line = node.start_line,
                  c.end_line = node.end_line,
+                 c.body_hash = node.body_hash,
+                 c.decorators = node.decorators,
+                 c.visibility = node.visibility,
                  c.job_id = node.job_id,
                  c.repo_id = node.repo_id"
         )
         .param("nodes", chunk.to_vec())
-
-        }).context("Failed to batch insert class nodes")?;
-    }
+    }).await?;
     
     info!("   Inserted {} Class nodes", nodes.len());
     Ok(())
@@ -461,43 +1561,45 @@ async fn batch_insert_function_nodes(
     job_id: &str,
     repo_id: &str,
     parsed_files: &[ParsedFile],
-    batch_size: usize,
+    writer: &BatchWriter,
 ) -> Result<()> {
     let mut nodes: Vec<HashMap<String, neo4rs::BoltType>> = Vec::new();
     
     for file in parsed_files {
         // Top-level functions
         for func in &file.functions {
-            nodes.push(function_node_to_map(func, &file.path, job_id, repo_id));
+            nodes.push(function_node_to_map(func, None, &file.path, job_id, repo_id));
         }
         
         // Class methods
         for class in &file.classes {
             for method in &class.methods {
-                nodes.push(function_node_to_map(method, &file.path, job_id, repo_id));
+                nodes.push(function_node_to_map(method, Some(&class.name), &file.path, job_id, repo_id));
             }
         }
     }
 
-    for chunk in nodes.chunks(batch_size) {
-        retry_query!(graph_db, {
-
-            query(
+    let nodes = writer.stamp(nodes);
+    writer.write(graph_db, "function nodes", &nodes, |chunk| {
+        query(
             "UNWIND $nodes AS node
              MERGE (fn:Function {id: node.id})
+             SET fn += node.metadata
              SET fn.name = node.name,
                  fn.file = node.file,
                  fn.start_line = node.start_line,
                  fn.end_line = node.end_line,
                  fn.params = node.params,
                  fn.return_type = node.return_type,
+                 fn.complexity = node.complexity,
+                 fn.body_hash = node.body_hash,
+                 fn.decorators = node.decorators,
+                 fn.visibility = node.visibility,
                  fn.job_id = node.job_id,
                  fn.repo_id = node.repo_id"
         )
         .param("nodes", chunk.to_vec())
-
-        }).context("Failed to batch insert function nodes")?;
-    }
+    }).await?;
     
     info!("   Inserted {} Function nodes", nodes.len());
     Ok(())
@@ -508,7 +1610,7 @@ async fn batch_insert_module_nodes(
     job_id: &str,
     repo_id: &str,
     dep_graph: &DependencyGraph,
-    batch_size: usize,
+    writer: &BatchWriter,
 ) -> Result<()> {
     let nodes: Vec<BoltMap> = dep_graph
         .nodes
@@ -522,78 +1624,102 @@ async fn batch_insert_module_nodes(
         })
         .collect();
 
-    for chunk in nodes.chunks(batch_size) {
-        retry_query!(graph_db, {
-
-            query(
+    let nodes = writer.stamp(nodes);
+    writer.write(graph_db, "module nodes", &nodes, |chunk| {
+        query(
             "UNWIND $nodes AS node
              MERGE (m:Module {name: node.name})
+             SET m += node.metadata
              SET m.job_id = node.job_id,
                  m.repo_id = node.repo_id"
         )
         .param("nodes", chunk.to_vec())
-
-        }).context("Failed to batch insert module nodes")?;
-    }
+    }).await?;
     
     info!("   Inserted {} Module nodes", nodes.len());
     Ok(())
 }
 
-async fn batch_insert_library_nodes(
-    graph_db: &neo4rs::Graph,
+/// Library node properties. Vulnerability properties are only set for
+/// libraries checked against OSV, so unchecked runs keep the previous ones.
+fn library_node_maps(
+    library_dependencies: &[LibraryDependency],
     job_id: &str,
     repo_id: &str,
-    library_dependencies: &[LibraryDependency],
-    batch_size: usize,
-) -> Result<()> {
-    let mut nodes: Vec<BoltMap> = Vec::new();
+) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    let mut nodes = Vec::new();
 
     for dep in library_dependencies {
-        let mut m = HashMap::new();
-        m.insert("name".to_string(), dep.name.clone());
-        m.insert("version".to_string(), dep.version.clone().unwrap_or_default());
-        m.insert("source_file".to_string(), dep.source_file.clone());
-        m.insert("job_id".to_string(), job_id.to_string());
-        m.insert("repo_id".to_string(), repo_id.to_string());
+        let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+        m.insert("name".to_string(), dep.name.clone().into());
+        m.insert("version".to_string(), dep.version.clone().unwrap_or_default().into());
+        m.insert("source_file".to_string(), dep.source_file.clone().into());
+        m.insert("job_id".to_string(), job_id.to_string().into());
+        m.insert("repo_id".to_string(), repo_id.to_string().into());
+        if let Some(ids) = &dep.vulnerability_ids {
+            m.insert("vulnerability_count".to_string(), (ids.len() as i64).into());
+            m.insert("vulnerability_ids".to_string(), ids.clone().into());
+        }
+        if let Some(usage) = &dep.usage {
+            m.insert("used_by_count".to_string(), (usage.used_by_count as i64).into());
+            m.insert("unused".to_string(), usage.unused.into());
+            m.insert("usage_unknown".to_string(), usage.usage_unknown.into());
+        }
         nodes.push(m);
     }
 
-    for chunk in nodes.chunks(batch_size) {
-        retry_query!(graph_db, {
+    nodes
+}
 
-            query(
+async fn batch_insert_library_nodes(
+    graph_db: &neo4rs::Graph,
+    job_id: &str,
+    repo_id: &str,
+    library_dependencies: &[LibraryDependency],
+    writer: &BatchWriter,
+) -> Result<()> {
+    let nodes = writer.stamp(library_node_maps(library_dependencies, job_id, repo_id));
+
+    writer.write(graph_db, "library nodes", &nodes, |chunk| {
+        query(
             "UNWIND $nodes AS node
              MERGE (l:Library {name: node.name, repo_id: node.repo_id})
+             SET l += node.metadata
              SET l.version = CASE WHEN node.version <> '' THEN node.version ELSE l.version END,
                  l.source_file = node.source_file,
-                 l.job_id = node.job_id"
+                 l.job_id = node.job_id,
+                 l.vulnerability_count = coalesce(node.vulnerability_count, l.vulnerability_count),
+                 l.vulnerability_ids = coalesce(node.vulnerability_ids, l.vulnerability_ids),
+                 l.used_by_count = coalesce(node.used_by_count, l.used_by_count),
+                 l.unused = coalesce(node.unused, l.unused),
+                 l.usage_unknown = coalesce(node.usage_unknown, l.usage_unknown)"
         )
         .param("nodes", chunk.to_vec())
-
-        }).context("Failed to batch insert library nodes")?;
-    }
+    }).await?;
 
     info!("   Inserted {} Library nodes", nodes.len());
     Ok(())
 }
 
-fn normalize_import_to_library(import_path: &str) -> Option<String> {
-    let trimmed = import_path.trim().trim_matches('"').trim_matches('`');
-    if trimmed.starts_with('.') || trimmed.starts_with('/') {
-        return None;
-    }
-
-    let parts: Vec<&str> = trimmed.split('/').collect();
-    if parts.is_empty() {
-        return None;
-    }
-
-    if trimmed.starts_with('@') && parts.len() >= 2 {
-        return Some(format!("{}/{}", parts[0], parts[1]));
+/// Delete the Library nodes of libraries no manifest declares anymore, with
+/// their DEPENDS_ON edges
+async fn delete_removed_libraries(graph_db: &neo4rs::Graph, repo_id: &str, diff: Option<&LibraryDiff>) -> Result<()> {
+    let names: Vec<String> = diff.map(|diff| diff.removed.iter().map(|lib| lib.name.clone()).collect()).unwrap_or_default();
+    if names.is_empty() {
+        return Ok(());
     }
-
-    Some(parts[0].to_string())
+    retry_query!(graph_db, {
+        query(
+            "UNWIND $names AS name
+             MATCH (l:Library {name: name, repo_id: $repo_id})
+             DETACH DELETE l"
+        )
+        .param("names", names.clone())
+        .param("repo_id", repo_id)
+    })
+    .context("Failed to delete removed library nodes")?;
+    info!("   Deleted {} removed Library nodes", names.len());
+    Ok(())
 }
 
 async fn batch_insert_library_edges(
@@ -601,47 +1727,45 @@ async fn batch_insert_library_edges(
     repo_id: &str,
     parsed_files: &[ParsedFile],
     library_dependencies: &[LibraryDependency],
-    batch_size: usize,
+    writer: &BatchWriter,
 ) -> Result<()> {
     let mut library_versions = HashMap::new();
     for dep in library_dependencies {
-        library_versions.insert(dep.name.clone(), dep.version.clone().unwrap_or_default());
+        library_versions.insert(dep.name.as_str(), dep.version.clone().unwrap_or_default());
     }
 
+    let matcher = LibraryMatcher::new(library_dependencies);
     let mut edges: Vec<BoltMap> = Vec::new();
     for file in parsed_files {
         for import in &file.imports {
-            if let Some(lib_name) = normalize_import_to_library(import) {
-                if library_versions.contains_key(&lib_name) {
-                    let mut m = HashMap::new();
-                    m.insert("file_path".to_string(), file.path.clone());
-                    m.insert("library_name".to_string(), lib_name.clone());
-                    m.insert(
-                        "library_version".to_string(),
-                        library_versions.get(&lib_name).cloned().unwrap_or_default(),
-                    );
-                    m.insert("repo_id".to_string(), repo_id.to_string());
-                    edges.push(m);
-                }
+            if let Some(lib_name) = matcher.library_for_import(import) {
+                let mut m = HashMap::new();
+                m.insert("id".to_string(), edge_id("DEPENDS_ON", &file.path, lib_name));
+                m.insert("file_path".to_string(), file.path.clone());
+                m.insert("library_name".to_string(), lib_name.to_string());
+                m.insert(
+                    "library_version".to_string(),
+                    library_versions.get(lib_name).cloned().unwrap_or_default(),
+                );
+                m.insert("repo_id".to_string(), repo_id.to_string());
+                edges.push(m);
             }
         }
     }
 
-    for chunk in edges.chunks(batch_size) {
-        retry_query!(graph_db, {
-
-            query(
+    writer.write(graph_db, "library edges", &edges, |chunk| {
+        query(
             "UNWIND $edges AS edge
              MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
              MATCH (l:Library {name: edge.library_name, repo_id: edge.repo_id})
              MERGE (f)-[r:DEPENDS_ON]->(l)
-             SET r.type = 'library',
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id,
+                 r.type = 'library',
                  r.version = edge.library_version"
         )
         .param("edges", chunk.to_vec())
-
-        }).context("Failed to batch insert library edges")?;
-    }
+    }).await?;
 
     info!("   Created {} Library DEPENDS_ON edges", edges.len());
     Ok(())
@@ -651,7 +1775,7 @@ async fn batch_insert_table_nodes(
     graph_db: &neo4rs::Graph,
     repo_id: &str,
     parsed_files: &[ParsedFile],
-    batch_size: usize,
+    writer: &BatchWriter,
 ) -> Result<()> {
     let mut nodes: Vec<BoltMap> = Vec::new();
     let mut seen = std::collections::HashSet::new();
@@ -667,17 +1791,15 @@ async fn batch_insert_table_nodes(
         }
     }
 
-    for chunk in nodes.chunks(batch_size) {
-        retry_query!(graph_db, {
-
-            query(
+    let nodes = writer.stamp(nodes);
+    writer.write(graph_db, "table nodes", &nodes, |chunk| {
+        query(
             "UNWIND $nodes AS node
-             MERGE (t:Table {name: node.name, repo_id: node.repo_id})"
+             MERGE (t:Table {name: node.name, repo_id: node.repo_id})
+             SET t += node.metadata"
         )
         .param("nodes", chunk.to_vec())
-
-        }).context("Failed to batch insert table nodes")?;
-    }
+    }).await?;
 
     info!("   Inserted {} Table nodes", nodes.len());
     Ok(())
@@ -687,12 +1809,13 @@ async fn batch_insert_table_edges(
     graph_db: &neo4rs::Graph,
     repo_id: &str,
     parsed_files: &[ParsedFile],
-    batch_size: usize,
+    writer: &BatchWriter,
 ) -> Result<()> {
     let mut edges: Vec<BoltMap> = Vec::new();
     for file in parsed_files {
         for table in &file.data_tables {
             let mut m = HashMap::new();
+            m.insert("id".to_string(), edge_id("USES_TABLE", &file.path, table));
             m.insert("file_path".to_string(), file.path.clone());
             m.insert("table_name".to_string(), table.clone());
             m.insert("repo_id".to_string(), repo_id.to_string());
@@ -700,887 +1823,2773 @@ async fn batch_insert_table_edges(
         }
     }
 
-    for chunk in edges.chunks(batch_size) {
-        retry_query!(graph_db, {
-
-            query(
+    writer.write(graph_db, "table edges", &edges, |chunk| {
+        query(
             "UNWIND $edges AS edge
              MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
              MATCH (t:Table {name: edge.table_name, repo_id: edge.repo_id})
-             MERGE (f)-[:USES_TABLE]->(t)"
+             MERGE (f)-[r:USES_TABLE]->(t)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
         )
         .param("edges", chunk.to_vec())
-
-        }).context("Failed to batch insert table edges")?;
-    }
+    }).await?;
 
     info!("   Created {} USES_TABLE edges", edges.len());
     Ok(())
 }
 
-async fn batch_insert_service_nodes(
-    graph_db: &neo4rs::Graph,
-    repo_id: &str,
-    parsed_files: &[ParsedFile],
-    batch_size: usize,
-) -> Result<()> {
-    let mut nodes: Vec<BoltMap> = Vec::new();
-    let mut seen = std::collections::HashSet::new();
+fn ddl_table_node_maps(tables: &[DdlTable], repo_id: &str) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    tables
+        .iter()
+        .map(|table| {
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("name".to_string(), table.name.clone().into());
+            m.insert("repo_id".to_string(), repo_id.to_string().into());
+            m.insert(
+                "columns".to_string(),
+                table.columns.iter().map(|c| c.name.clone()).collect::<Vec<_>>().into(),
+            );
+            m.insert(
+                "column_types".to_string(),
+                table.columns.iter().map(|c| c.data_type.clone()).collect::<Vec<_>>().into(),
+            );
+            m
+        })
+        .collect()
+}
 
-    for file in parsed_files {
-        for service in &file.service_calls {
-            let key = format!("{}::{}", service.protocol, service.target);
-            if seen.insert(key) {
+/// One DEFINES_TABLE edge per file that creates or alters a table
+fn defines_table_edge_maps(tables: &[DdlTable], repo_id: &str) -> Vec<BoltMap> {
+    tables
+        .iter()
+        .flat_map(|table| {
+            table.files.iter().map(|file| {
                 let mut m = HashMap::new();
-                m.insert("name".to_string(), service.target.clone());
-                m.insert("protocol".to_string(), service.protocol.clone());
+                m.insert("id".to_string(), edge_id("DEFINES_TABLE", file, &table.name));
+                m.insert("file_path".to_string(), file.clone());
+                m.insert("table_name".to_string(), table.name.clone());
                 m.insert("repo_id".to_string(), repo_id.to_string());
-                nodes.push(m);
-            }
-        }
-    }
-
-    for chunk in nodes.chunks(batch_size) {
-        retry_query!(graph_db, {
+                m
+            })
+        })
+        .collect()
+}
 
-            query(
+/// Tables defined by schema and migration files. Migration files aren't
+/// parsed, so their File nodes are created here when missing.
+async fn batch_insert_ddl_tables(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    tables: &[DdlTable],
+    writer: &BatchWriter,
+) -> Result<()> {
+    let nodes = writer.stamp(ddl_table_node_maps(tables, repo_id));
+    writer.write(graph_db, "DDL table nodes", &nodes, |chunk| {
+        query(
             "UNWIND $nodes AS node
-             MERGE (s:Service {name: node.name, protocol: node.protocol, repo_id: node.repo_id})"
+             MERGE (t:Table {name: node.name, repo_id: node.repo_id})
+             SET t += node.metadata
+             SET t.columns = node.columns,
+                 t.column_types = node.column_types,
+                 t.source = 'ddl'"
         )
         .param("nodes", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert service nodes")?;
-    }
+    let edges = writer.stamp(defines_table_edge_maps(tables, repo_id));
+    writer.write(graph_db, "DEFINES_TABLE edges", &edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MERGE (f:File {id: edge.file_path})
+             ON CREATE SET f += edge.metadata,
+                           f.path = edge.file_path,
+                           f.repo_id = edge.repo_id,
+                           f.language = 'sql'
+             WITH f, edge
+             MATCH (t:Table {name: edge.table_name, repo_id: edge.repo_id})
+             MERGE (f)-[r:DEFINES_TABLE]->(t)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
 
-    info!("   Inserted {} Service nodes", nodes.len());
+    info!("   Inserted {} DDL Table nodes, {} DEFINES_TABLE edges", nodes.len(), edges.len());
     Ok(())
 }
 
-async fn batch_insert_service_edges(
-    graph_db: &neo4rs::Graph,
-    repo_id: &str,
-    parsed_files: &[ParsedFile],
-    batch_size: usize,
-) -> Result<()> {
-    let mut edges: Vec<BoltMap> = Vec::new();
-    for file in parsed_files {
-        for service in &file.service_calls {
+fn package_node_maps(packages: &[Package], repo_id: &str) -> Vec<BoltMap> {
+    packages
+        .iter()
+        .map(|package| {
             let mut m = HashMap::new();
-            m.insert("file_path".to_string(), file.path.clone());
-            m.insert("service_name".to_string(), service.target.clone());
-            m.insert("service_protocol".to_string(), service.protocol.clone());
+            m.insert("path".to_string(), package.path.clone());
+            m.insert("name".to_string(), package.name.clone());
+            m.insert("parent".to_string(), package.parent.clone().unwrap_or_default());
+            if let Some(parent) = &package.parent {
+                m.insert("edge_id".to_string(), edge_id("SUBPACKAGE_OF", &package.path, parent));
+            }
             m.insert("repo_id".to_string(), repo_id.to_string());
-            edges.push(m);
-        }
-    }
-
-    for chunk in edges.chunks(batch_size) {
-        retry_query!(graph_db, {
-
-            query(
-            "UNWIND $edges AS edge
-             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
-             MATCH (s:Service {name: edge.service_name, protocol: edge.service_protocol, repo_id: edge.repo_id})
-             MERGE (f)-[:CALLS_SERVICE]->(s)"
-        )
-        .param("edges", chunk.to_vec())
-
-        }).context("Failed to batch insert service edges")?;
-    }
+            m
+        })
+        .collect()
+}
 
-    info!("   Created {} CALLS_SERVICE edges", edges.len());
-    Ok(())
+/// One CONTAINS_FILE edge per file a package holds directly
+fn contains_file_edge_maps(packages: &[Package], repo_id: &str) -> Vec<BoltMap> {
+    packages
+        .iter()
+        .flat_map(|package| {
+            package.files.iter().map(|file| {
+                let mut m = HashMap::new();
+                m.insert("id".to_string(), edge_id("CONTAINS_FILE", &package.path, file));
+                m.insert("package_path".to_string(), package.path.clone());
+                m.insert("file_path".to_string(), file.clone());
+                m.insert("repo_id".to_string(), repo_id.to_string());
+                m
+            })
+        })
+        .collect()
 }
 
-async fn batch_insert_endpoint_nodes(
+/// Package nodes, nested with SUBPACKAGE_OF and linked to their files with
+/// CONTAINS_FILE
+async fn batch_insert_packages(
     graph_db: &neo4rs::Graph,
     repo_id: &str,
-    communication_analysis: &CommunicationAnalysis,
-    batch_size: usize,
+    packages: &[Package],
+    writer: &BatchWriter,
 ) -> Result<()> {
-    let mut nodes: Vec<BoltMap> = Vec::new();
-    let mut seen = std::collections::HashSet::new();
-
-    for endpoint in &communication_analysis.endpoints {
-        let key = format!("{}::{}", endpoint.method, endpoint.url);
-        if seen.insert(key) {
-            let mut m = HashMap::new();
-            m.insert("url".to_string(), endpoint.url.clone());
-            m.insert("method".to_string(), endpoint.method.clone());
-            m.insert("host".to_string(), endpoint.host.clone().unwrap_or_default());
-            m.insert("repo_id".to_string(), repo_id.to_string());
-            nodes.push(m);
-        }
-    }
-
-    for chunk in nodes.chunks(batch_size) {
-        retry_query!(graph_db, {
+    let nodes = package_node_maps(packages, repo_id);
+    writer.write(graph_db, "package nodes", &writer.stamp(nodes.clone()), |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (p:Package {path: node.path, repo_id: node.repo_id})
+             SET p += node.metadata
+             SET p.name = node.name"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
 
-            query(
+    let nested: Vec<BoltMap> = nodes.into_iter().filter(|node| !node["parent"].is_empty()).collect();
+    writer.write(graph_db, "SUBPACKAGE_OF edges", &nested, |chunk| {
+        query(
             "UNWIND $nodes AS node
-             MERGE (e:Endpoint {url: node.url, method: node.method, repo_id: node.repo_id})
-             SET e.host = node.host"
+             MATCH (child:Package {path: node.path, repo_id: node.repo_id})
+             MATCH (parent:Package {path: node.parent, repo_id: node.repo_id})
+             MERGE (child)-[r:SUBPACKAGE_OF]->(parent)
+             SET r.id = node.edge_id,
+                 r.repo_id = node.repo_id"
         )
         .param("nodes", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert Endpoint nodes")?;
-    }
+    let edges = contains_file_edge_maps(packages, repo_id);
+    writer.write(graph_db, "CONTAINS_FILE edges", &edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (p:Package {path: edge.package_path, repo_id: edge.repo_id})
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MERGE (p)-[r:CONTAINS_FILE]->(f)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
 
-    info!("   Inserted {} Endpoint nodes", nodes.len());
+    info!(
+        "   Inserted {} Package nodes, {} SUBPACKAGE_OF and {} CONTAINS_FILE edges",
+        packages.len(),
+        nested.len(),
+        edges.len()
+    );
     Ok(())
 }
 
-async fn batch_insert_endpoint_edges(
-    graph_db: &neo4rs::Graph,
-    repo_id: &str,
-    communication_analysis: &CommunicationAnalysis,
-    batch_size: usize,
-) -> Result<()> {
-    let mut edges: Vec<BoltMap> = Vec::new();
+/// Prisma models have no parsed class, so their Class nodes are made here
+fn prisma_model_node_maps(models: &[OrmModel], job_id: &str, repo_id: &str) -> Vec<BoltMap> {
+    models
+        .iter()
+        .filter(|model| model.orm == "prisma")
+        .map(|model| {
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), get_qualified_id(&model.file_path, &model.class_name));
+            m.insert("name".to_string(), model.class_name.clone());
+            m.insert("file".to_string(), model.file_path.clone());
+            m.insert("job_id".to_string(), job_id.to_string());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            m
+        })
+        .collect()
+}
 
-    for endpoint in &communication_analysis.endpoints {
-        let mut m = HashMap::new();
-        m.insert("file_path".to_string(), endpoint.file_path.clone());
-        m.insert("url".to_string(), endpoint.url.clone());
-        m.insert("method".to_string(), endpoint.method.clone());
-        m.insert("repo_id".to_string(), repo_id.to_string());
-        edges.push(m);
-    }
+fn maps_to_edge_maps(models: &[OrmModel], repo_id: &str) -> Vec<BoltMap> {
+    models
+        .iter()
+        .map(|model| {
+            let class_id = get_qualified_id(&model.file_path, &model.class_name);
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), edge_id("MAPS_TO", &class_id, &model.table));
+            m.insert("class_id".to_string(), class_id);
+            m.insert("table_name".to_string(), model.table.clone());
+            m.insert("orm".to_string(), model.orm.clone());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            m
+        })
+        .collect()
+}
 
-    for chunk in edges.chunks(batch_size) {
-        retry_query!(graph_db, {
+async fn batch_insert_orm_edges(
+    graph_db: &neo4rs::Graph,
+    job_id: &str,
+    repo_id: &str,
+    orm_models: &[OrmModel],
+    writer: &BatchWriter,
+) -> Result<()> {
+    let nodes = writer.stamp(prisma_model_node_maps(orm_models, job_id, repo_id));
+    writer.write(graph_db, "prisma model nodes", &nodes, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (c:Class {id: node.id})
+             SET c += node.metadata
+             SET c.name = node.name,
+                 c.file = node.file,
+                 c.job_id = node.job_id,
+                 c.repo_id = node.repo_id"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
 
-            query(
+    let edges = writer.stamp(maps_to_edge_maps(orm_models, repo_id));
+    writer.write(graph_db, "MAPS_TO edges", &edges, |chunk| {
+        query(
             "UNWIND $edges AS edge
-             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
-             MATCH (e:Endpoint {url: edge.url, method: edge.method, repo_id: edge.repo_id})
-             MERGE (f)-[:CALLS_ENDPOINT]->(e)"
+             MATCH (c:Class {id: edge.class_id, repo_id: edge.repo_id})
+             MERGE (t:Table {name: edge.table_name, repo_id: edge.repo_id})
+             SET t += edge.metadata
+             SET c.orm = edge.orm
+             MERGE (c)-[r:MAPS_TO]->(t)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
         )
         .param("edges", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert CALLS_ENDPOINT edges")?;
-    }
-
-    info!("   Created {} CALLS_ENDPOINT edges", edges.len());
+    info!("   Created {} MAPS_TO edges", edges.len());
     Ok(())
 }
 
-async fn batch_insert_rpc_nodes(
+/// Query nodes of `parsed_files`, except the ones in `stored`, which an
+/// earlier chunk of files already wrote
+async fn batch_insert_query_nodes(
     graph_db: &neo4rs::Graph,
+    job_id: &str,
     repo_id: &str,
-    communication_analysis: &CommunicationAnalysis,
-    batch_size: usize,
+    parsed_files: &[ParsedFile],
+    stored: &HashSet<String>,
+    writer: &BatchWriter,
 ) -> Result<()> {
     let mut nodes: Vec<BoltMap> = Vec::new();
-    let mut seen = std::collections::HashSet::new();
+    let mut seen = HashSet::new();
 
-    for rpc in &communication_analysis.rpc_services {
-        if seen.insert(rpc.service_name.clone()) {
-            let mut m = HashMap::new();
-            m.insert("name".to_string(), rpc.service_name.clone());
-            m.insert("repo_id".to_string(), repo_id.to_string());
-            nodes.push(m);
+    for file in parsed_files {
+        for sql in &file.sql_queries {
+            if !stored.contains(&sql.hash) && seen.insert(sql.hash.clone()) {
+                nodes.push(query_node_to_map(sql, job_id, repo_id));
+            }
         }
     }
 
-    for chunk in nodes.chunks(batch_size) {
-        retry_query!(graph_db, {
-
-            query(
+    let nodes = writer.stamp(nodes);
+    writer.write(graph_db, "query nodes", &nodes, |chunk| {
+        query(
             "UNWIND $nodes AS node
-             MERGE (r:RpcService {name: node.name, repo_id: node.repo_id})"
+             MERGE (q:Query {id: node.id, repo_id: node.repo_id})
+             SET q += node.metadata
+             SET q.text = node.text,
+                 q.statement_type = node.statement_type,
+                 q.job_id = node.job_id"
         )
         .param("nodes", chunk.to_vec())
+    }).await?;
+
+    info!("   Inserted {} Query nodes", nodes.len());
+    Ok(())
+}
+
+/// Innermost function or method whose line range contains `line`
+/// Id of the innermost function or method spanning `line`
+fn enclosing_function_id(file: &ParsedFile, line: usize) -> Option<String> {
+    file.functions
+        .iter()
+        .map(|f| (None, f))
+        .chain(file.classes.iter().flat_map(|c| c.methods.iter().map(move |m| (Some(&c.name), m))))
+        .filter(|(_, f)| f.start_line <= line && line <= f.end_line)
+        .min_by_key(|(_, f)| f.end_line - f.start_line)
+        .map(|(class, f)| match class {
+            Some(class) => get_method_id(&file.path, class, &f.name),
+            None => get_qualified_id(&file.path, &f.name),
+        })
+}
+
+#[derive(Default)]
+struct QueryEdgeBatches {
+    file_executes: Vec<BoltMap>,
+    function_executes: Vec<BoltMap>,
+    reads: Vec<BoltMap>,
+    writes: Vec<BoltMap>,
+}
+
+/// EXECUTES_QUERY edges of `parsed_files`, and the table edges of queries
+/// not in `stored`
+fn collect_query_edges(parsed_files: &[ParsedFile], repo_id: &str, stored: &HashSet<String>) -> QueryEdgeBatches {
+    let mut batches = QueryEdgeBatches::default();
+    let mut seen = HashSet::new();
+
+    for file in parsed_files {
+        for sql in &file.sql_queries {
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), edge_id("EXECUTES_QUERY", &file.path, &sql.hash));
+            m.insert("file_path".to_string(), file.path.clone());
+            m.insert("query_id".to_string(), sql.hash.clone());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            batches.file_executes.push(m);
+
+            if let Some(func_id) = enclosing_function_id(file, sql.line) {
+                let mut m = HashMap::new();
+                m.insert("id".to_string(), edge_id("EXECUTES_QUERY", &func_id, &sql.hash));
+                m.insert("func_id".to_string(), func_id);
+                m.insert("query_id".to_string(), sql.hash.clone());
+                m.insert("repo_id".to_string(), repo_id.to_string());
+                batches.function_executes.push(m);
+            }
 
-        }).context("Failed to batch insert RpcService nodes")?;
+            // Table edges belong to the query itself, so only emit them once per hash
+            if stored.contains(&sql.hash) || !seen.insert(sql.hash.clone()) {
+                continue;
+            }
+            for (edge_type, tables, target) in
+                [("READS", &sql.reads, &mut batches.reads), ("WRITES", &sql.writes, &mut batches.writes)]
+            {
+                for table in tables {
+                    let mut m = HashMap::new();
+                    m.insert("id".to_string(), edge_id(edge_type, &sql.hash, table));
+                    m.insert("query_id".to_string(), sql.hash.clone());
+                    m.insert("table_name".to_string(), table.clone());
+                    m.insert("repo_id".to_string(), repo_id.to_string());
+                    target.push(m);
+                }
+            }
+        }
     }
 
-    info!("   Inserted {} RpcService nodes", nodes.len());
-    Ok(())
+    batches
 }
 
-async fn batch_insert_rpc_edges(
+async fn batch_insert_query_edges(
     graph_db: &neo4rs::Graph,
     repo_id: &str,
-    communication_analysis: &CommunicationAnalysis,
-    batch_size: usize,
+    parsed_files: &[ParsedFile],
+    stored: &HashSet<String>,
+    writer: &BatchWriter,
 ) -> Result<()> {
-    let mut edges: Vec<BoltMap> = Vec::new();
+    let batches = collect_query_edges(parsed_files, repo_id, stored);
+    // Tables only queries mention are created here
+    let (reads, writes) = (writer.stamp(batches.reads), writer.stamp(batches.writes));
 
-    for rpc in &communication_analysis.rpc_services {
-        let mut m = HashMap::new();
-        m.insert("file_path".to_string(), rpc.file_path.clone());
-        m.insert("service_name".to_string(), rpc.service_name.clone());
-        m.insert("repo_id".to_string(), repo_id.to_string());
-        edges.push(m);
-    }
+    writer.write(graph_db, "File EXECUTES_QUERY edges", &batches.file_executes, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (q:Query {id: edge.query_id, repo_id: edge.repo_id})
+             MERGE (f)-[r:EXECUTES_QUERY]->(q)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
 
-    for chunk in edges.chunks(batch_size) {
-        retry_query!(graph_db, {
+    writer.write(graph_db, "Function EXECUTES_QUERY edges", &batches.function_executes, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (fn:Function {id: edge.func_id, repo_id: edge.repo_id})
+             MATCH (q:Query {id: edge.query_id, repo_id: edge.repo_id})
+             MERGE (fn)-[r:EXECUTES_QUERY]->(q)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
 
-            query(
+    writer.write(graph_db, "READS edges", &reads, |chunk| {
+        query(
             "UNWIND $edges AS edge
-             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
-             MATCH (r:RpcService {name: edge.service_name, repo_id: edge.repo_id})
-             MERGE (f)-[:CALLS_RPC]->(r)"
+             MATCH (q:Query {id: edge.query_id, repo_id: edge.repo_id})
+             MERGE (t:Table {name: edge.table_name, repo_id: edge.repo_id})
+             SET t += edge.metadata
+             MERGE (q)-[r:READS]->(t)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
         )
         .param("edges", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert CALLS_RPC edges")?;
-    }
+    writer.write(graph_db, "WRITES edges", &writes, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (q:Query {id: edge.query_id, repo_id: edge.repo_id})
+             MERGE (t:Table {name: edge.table_name, repo_id: edge.repo_id})
+             SET t += edge.metadata
+             MERGE (q)-[r:WRITES]->(t)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
 
-    info!("   Created {} CALLS_RPC edges", edges.len());
+    info!(
+        "   Created {} EXECUTES_QUERY, {} READS, {} WRITES edges",
+        batches.file_executes.len() + batches.function_executes.len(),
+        reads.len(),
+        writes.len()
+    );
     Ok(())
 }
 
-async fn batch_insert_queue_nodes(
+async fn batch_insert_service_nodes(
     graph_db: &neo4rs::Graph,
     repo_id: &str,
-    communication_analysis: &CommunicationAnalysis,
-    batch_size: usize,
+    parsed_files: &[ParsedFile],
+    writer: &BatchWriter,
 ) -> Result<()> {
     let mut nodes: Vec<BoltMap> = Vec::new();
     let mut seen = std::collections::HashSet::new();
 
-    for queue in &communication_analysis.queues {
-        if seen.insert(queue.topic.clone()) {
-            let mut m = HashMap::new();
-            m.insert("topic".to_string(), queue.topic.clone());
-            m.insert("repo_id".to_string(), repo_id.to_string());
-            nodes.push(m);
+    for file in parsed_files {
+        for service in &file.service_calls {
+            let key = format!("{}::{}", service.protocol, service.target);
+            if seen.insert(key) {
+                let mut m = HashMap::new();
+                m.insert("name".to_string(), service.target.clone());
+                m.insert("protocol".to_string(), service.protocol.clone());
+                if let Some(port) = service.port {
+                    m.insert("port".to_string(), port.to_string());
+                }
+                if let Some(domain) = &service.domain {
+                    m.insert("domain".to_string(), domain.clone());
+                }
+                m.insert("internal".to_string(), service.internal.to_string());
+                m.insert("repo_id".to_string(), repo_id.to_string());
+                nodes.push(m);
+            }
         }
     }
 
-    for chunk in nodes.chunks(batch_size) {
-        retry_query!(graph_db, {
-
-            query(
+    let nodes = writer.stamp(nodes);
+    writer.write(graph_db, "service nodes", &nodes, |chunk| {
+        query(
             "UNWIND $nodes AS node
-             MERGE (q:MessageQueue {topic: node.topic, repo_id: node.repo_id})"
+             MERGE (s:Service {name: node.name, protocol: node.protocol, repo_id: node.repo_id})
+             SET s += node.metadata
+             SET s.port = toInteger(node.port),
+                 s.domain = node.domain,
+                 s.internal = toBoolean(node.internal)"
         )
         .param("nodes", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert MessageQueue nodes")?;
-    }
-
-    info!("   Inserted {} MessageQueue nodes", nodes.len());
+    info!("   Inserted {} Service nodes", nodes.len());
     Ok(())
 }
 
-async fn batch_insert_queue_edges(
+async fn batch_insert_service_edges(
     graph_db: &neo4rs::Graph,
     repo_id: &str,
-    communication_analysis: &CommunicationAnalysis,
-    batch_size: usize,
+    parsed_files: &[ParsedFile],
+    writer: &BatchWriter,
 ) -> Result<()> {
-    let mut publish_edges: Vec<BoltMap> = Vec::new();
-    let mut consume_edges: Vec<BoltMap> = Vec::new();
-
-    for queue in &communication_analysis.queues {
-        let mut m = HashMap::new();
-        m.insert("file_path".to_string(), queue.file_path.clone());
-        m.insert("topic".to_string(), queue.topic.clone());
-        m.insert("repo_id".to_string(), repo_id.to_string());
-        match queue.direction {
-            QueueDirection::Publish => publish_edges.push(m),
-            QueueDirection::Consume => consume_edges.push(m),
+    let mut edges: Vec<BoltMap> = Vec::new();
+    for file in parsed_files {
+        for service in &file.service_calls {
+            let service_key = format!("{}::{}", service.protocol, service.target);
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), edge_id("CALLS_SERVICE", &file.path, &service_key));
+            m.insert("file_path".to_string(), file.path.clone());
+            m.insert("service_name".to_string(), service.target.clone());
+            m.insert("service_protocol".to_string(), service.protocol.clone());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            edges.push(m);
         }
     }
 
-    for chunk in publish_edges.chunks(batch_size) {
-        retry_query!(graph_db, {
-
-            query(
+    writer.write(graph_db, "service edges", &edges, |chunk| {
+        query(
             "UNWIND $edges AS edge
              MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
-             MATCH (q:MessageQueue {topic: edge.topic, repo_id: edge.repo_id})
-             MERGE (f)-[:PUBLISHES_TO]->(q)"
+             MATCH (s:Service {name: edge.service_name, protocol: edge.service_protocol, repo_id: edge.repo_id})
+             MERGE (f)-[r:CALLS_SERVICE]->(s)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
         )
         .param("edges", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert PUBLISHES_TO edges")?;
-    }
-
-    for chunk in consume_edges.chunks(batch_size) {
-        retry_query!(graph_db, {
+    info!("   Created {} CALLS_SERVICE edges", edges.len());
+    Ok(())
+}
 
-            query(
-            "UNWIND $edges AS edge
-             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
-             MATCH (q:MessageQueue {topic: edge.topic, repo_id: edge.repo_id})
-             MERGE (f)-[:CONSUMES_FROM]->(q)"
-        )
-        .param("edges", chunk.to_vec())
+/// One map per distinct (method, normalized url); the first raw URL seen is
+/// kept as `example_url`
+fn endpoint_node_maps(communication_analysis: &CommunicationAnalysis, repo_id: &str) -> Vec<BoltMap> {
+    let mut nodes: Vec<BoltMap> = Vec::new();
+    let mut seen = std::collections::HashSet::new();
 
-        }).context("Failed to batch insert CONSUMES_FROM edges")?;
+    for endpoint in &communication_analysis.endpoints {
+        let key = format!("{}::{}", endpoint.method, endpoint.url);
+        if seen.insert(key) {
+            let mut m = HashMap::new();
+            m.insert("url".to_string(), endpoint.url.clone());
+            m.insert("example_url".to_string(), endpoint.raw_url.clone());
+            m.insert("path".to_string(), endpoint_path(&endpoint.url));
+            m.insert("method".to_string(), endpoint.method.clone());
+            m.insert("host".to_string(), endpoint.host.clone().unwrap_or_default());
+            if let Some(port) = endpoint.port {
+                m.insert("port".to_string(), port.to_string());
+            }
+            if let Some(domain) = &endpoint.domain {
+                m.insert("domain".to_string(), domain.clone());
+            }
+            m.insert("internal".to_string(), endpoint.internal.to_string());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            nodes.push(m);
+        }
     }
-
-    info!(
-        "   Created {} PUBLISHES_TO and {} CONSUMES_FROM edges",
-        publish_edges.len(),
-        consume_edges.len()
-    );
-    Ok(())
+    nodes
 }
 
-async fn batch_insert_compose_service_nodes(
+async fn batch_insert_endpoint_nodes(
     graph_db: &neo4rs::Graph,
     repo_id: &str,
     communication_analysis: &CommunicationAnalysis,
-    batch_size: usize,
+    writer: &BatchWriter,
 ) -> Result<()> {
-    let mut nodes: Vec<HashMap<String, neo4rs::BoltType>> = Vec::new();
-
-    for service in &communication_analysis.compose_services {
-        let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
-        m.insert("name".to_string(), service.name.clone().into());
-        m.insert("ports".to_string(), service.ports.clone().into());
-        m.insert("repo_id".to_string(), repo_id.to_string().into());
-        nodes.push(m);
-    }
-
-    for chunk in nodes.chunks(batch_size) {
-        retry_query!(graph_db, {
+    let nodes = writer.stamp(endpoint_node_maps(communication_analysis, repo_id));
 
-            query(
+    writer.write(graph_db, "Endpoint nodes", &nodes, |chunk| {
+        query(
             "UNWIND $nodes AS node
-             MERGE (s:ComposeService {name: node.name, repo_id: node.repo_id})
-             SET s.ports = node.ports"
+             MERGE (e:Endpoint {url: node.url, method: node.method, repo_id: node.repo_id})
+             SET e += node.metadata
+             SET e.host = node.host,
+                 e.port = toInteger(node.port),
+                 e.path = node.path,
+                 e.domain = node.domain,
+                 e.internal = toBoolean(node.internal),
+                 e.example_url = COALESCE(e.example_url, node.example_url)"
         )
         .param("nodes", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert ComposeService nodes")?;
-    }
-
-    info!("   Inserted {} ComposeService nodes", nodes.len());
+    info!("   Inserted {} Endpoint nodes", nodes.len());
     Ok(())
 }
 
-async fn batch_insert_endpoint_service_edges(
+async fn batch_insert_endpoint_edges(
     graph_db: &neo4rs::Graph,
     repo_id: &str,
     communication_analysis: &CommunicationAnalysis,
-    batch_size: usize,
+    writer: &BatchWriter,
 ) -> Result<()> {
     let mut edges: Vec<BoltMap> = Vec::new();
-    let mut service_names: HashSet<String> = HashSet::new();
-
-    for service in &communication_analysis.compose_services {
-        service_names.insert(service.name.clone());
-    }
 
     for endpoint in &communication_analysis.endpoints {
-        if let Some(host) = endpoint.host.as_ref() {
-            for service_name in &service_names {
-                if host.contains(service_name) {
-                    let mut m = HashMap::new();
-                    m.insert("url".to_string(), endpoint.url.clone());
-                    m.insert("method".to_string(), endpoint.method.clone());
-                    m.insert("service_name".to_string(), service_name.clone());
-                    m.insert("repo_id".to_string(), repo_id.to_string());
-                    edges.push(m);
-                }
-            }
-        }
+        let endpoint_key = format!("{}::{}", endpoint.method, endpoint.url);
+        let mut m = HashMap::new();
+        m.insert("id".to_string(), edge_id("CALLS_ENDPOINT", &endpoint.file_path, &endpoint_key));
+        m.insert("file_path".to_string(), endpoint.file_path.clone());
+        m.insert("url".to_string(), endpoint.url.clone());
+        m.insert("method".to_string(), endpoint.method.clone());
+        m.insert("repo_id".to_string(), repo_id.to_string());
+        edges.push(m);
     }
 
-    for chunk in edges.chunks(batch_size) {
-        retry_query!(graph_db, {
-
-            query(
+    writer.write(graph_db, "CALLS_ENDPOINT edges", &edges, |chunk| {
+        query(
             "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
              MATCH (e:Endpoint {url: edge.url, method: edge.method, repo_id: edge.repo_id})
-             MATCH (s:ComposeService {name: edge.service_name, repo_id: edge.repo_id})
-             MERGE (e)-[:EXPOSED_BY]->(s)"
+             MERGE (f)-[r:CALLS_ENDPOINT]->(e)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
         )
         .param("edges", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert EXPOSED_BY edges")?;
-    }
+    info!("   Created {} CALLS_ENDPOINT edges", edges.len());
+    Ok(())
+}
+
+fn route_node_maps(communication_analysis: &CommunicationAnalysis, repo_id: &str) -> Vec<BoltMap> {
+    let mut nodes: Vec<BoltMap> = Vec::new();
+    let mut seen = HashSet::new();
+
+    for route in &communication_analysis.routes {
+        if seen.insert((&route.method, &route.path)) {
+            let mut m = HashMap::new();
+            m.insert("path".to_string(), route.path.clone());
+            m.insert("method".to_string(), route.method.clone());
+            m.insert("framework".to_string(), route.framework.clone());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            nodes.push(m);
+        }
+    }
+    nodes
+}
+
+async fn batch_insert_route_nodes(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let nodes = writer.stamp(route_node_maps(communication_analysis, repo_id));
+
+    writer.write(graph_db, "Route nodes", &nodes, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (r:Route {path: node.path, method: node.method, repo_id: node.repo_id})
+             SET r += node.metadata
+             SET r.framework = node.framework"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    info!("   Inserted {} Route nodes", nodes.len());
+    Ok(())
+}
+
+async fn batch_insert_route_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let mut edges: Vec<BoltMap> = Vec::new();
+
+    for route in &communication_analysis.routes {
+        let route_key = format!("{}::{}", route.method, route.path);
+        let mut m = HashMap::new();
+        m.insert("id".to_string(), edge_id("DEFINES_ROUTE", &route.file_path, &route_key));
+        m.insert("file_path".to_string(), route.file_path.clone());
+        m.insert("path".to_string(), route.path.clone());
+        m.insert("method".to_string(), route.method.clone());
+        m.insert("repo_id".to_string(), repo_id.to_string());
+        edges.push(m);
+    }
+
+    writer.write(graph_db, "DEFINES_ROUTE edges", &edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (r:Route {path: edge.path, method: edge.method, repo_id: edge.repo_id})
+             MERGE (f)-[rel:DEFINES_ROUTE]->(r)
+             SET rel.id = edge.id,
+                 rel.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    info!("   Created {} DEFINES_ROUTE edges", edges.len());
+    Ok(())
+}
+
+/// Link every Endpoint of the repo to the Routes serving its path. Runs over
+/// the stored graph rather than this job's analysis so incremental updates
+/// also link calls and routes that live in unchanged files.
+async fn link_endpoints_to_routes(graph_db: &neo4rs::Graph, repo_id: &str) -> Result<()> {
+    let cypher = format!(
+        "MATCH (r:Route {{repo_id: $repo_id}})
+         MATCH (e:Endpoint {{repo_id: $repo_id, path: r.path}})
+         WHERE r.method = e.method OR r.method = 'ANY'
+         MERGE (e)-[rel:RESOLVES_TO]->(r)
+         SET rel.id = {},
+             rel.repo_id = $repo_id",
+        edge_id_cypher("RESOLVES_TO", "e.method + '::' + e.url", "r.method + '::' + r.path")
+    );
+    retry_query!(graph_db, {
+
+        query(&cypher)
+    .param("repo_id", repo_id)
+
+    }).context("Failed to link Endpoints to Routes")?;
+
+    Ok(())
+}
+
+/// One map per spec operation, with `route_path` normalized like Route
+/// paths for matching
+fn api_operation_maps(communication_analysis: &CommunicationAnalysis, repo_id: &str) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    communication_analysis
+        .api_operations
+        .iter()
+        .map(|operation| {
+            let language = if operation.file_path.ends_with(".json") { "json" } else { "yaml" };
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("file_path".to_string(), operation.file_path.clone().into());
+            m.insert("language".to_string(), language.into());
+            m.insert("path".to_string(), operation.path.clone().into());
+            m.insert("route_path".to_string(), normalize_route_path(&operation.path).into());
+            m.insert("method".to_string(), operation.method.clone().into());
+            m.insert("operation_id".to_string(), operation.operation_id.clone().into());
+            m.insert("summary".to_string(), operation.summary.clone().into());
+            m.insert(
+                "edge_id".to_string(),
+                edge_id("DEFINES_SPEC", &operation.file_path, &format!("{}::{}", operation.method, operation.path)).into(),
+            );
+            m.insert("repo_id".to_string(), repo_id.into());
+            m
+        })
+        .collect()
+}
+
+/// Replace the repo's ApiOperation nodes with the specs' current operations.
+/// Specs are scanned in full on every run, so operations dropped from a spec
+/// go away even on incremental runs.
+async fn replace_api_operations(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    retry_query!(graph_db, {
+        query("MATCH (a:ApiOperation {repo_id: $repo_id}) DETACH DELETE a").param("repo_id", repo_id)
+    })
+    .context("Failed to delete ApiOperation nodes")?;
+
+    let operations = writer.stamp(api_operation_maps(communication_analysis, repo_id));
+    writer.write(graph_db, "ApiOperation nodes", &operations, |chunk| {
+        query(
+            "UNWIND $operations AS op
+             MERGE (a:ApiOperation {path: op.path, method: op.method, repo_id: op.repo_id})
+             SET a += op.metadata
+             SET a.route_path = op.route_path,
+                 a.operation_id = op.operation_id,
+                 a.summary = op.summary
+             MERGE (f:File {id: op.file_path})
+             ON CREATE SET f += op.metadata,
+                           f.path = op.file_path,
+                           f.repo_id = op.repo_id,
+                           f.language = op.language
+             MERGE (f)-[r:DEFINES_SPEC]->(a)
+             SET r.id = op.edge_id,
+                 r.repo_id = op.repo_id"
+        )
+        .param("operations", chunk.to_vec())
+    }).await?;
+
+    info!("   Inserted {} ApiOperation nodes", operations.len());
+    Ok(())
+}
+
+/// Link spec operations to the Routes and Endpoints with the same method and
+/// path. Like `link_endpoints_to_routes`, runs over the stored graph.
+async fn link_api_operations(graph_db: &neo4rs::Graph, repo_id: &str) -> Result<()> {
+    let operation_key = "a.method + '::' + a.path";
+    let cypher = format!(
+        "MATCH (a:ApiOperation {{repo_id: $repo_id}})
+         MATCH (r:Route {{repo_id: $repo_id, path: a.route_path}})
+         WHERE r.method = a.method OR r.method = 'ANY'
+         MERGE (a)-[rel:IMPLEMENTED_BY]->(r)
+         SET rel.id = {},
+             rel.repo_id = $repo_id",
+        edge_id_cypher("IMPLEMENTED_BY", operation_key, "r.method + '::' + r.path")
+    );
+    retry_query!(graph_db, {
+        query(&cypher).param("repo_id", repo_id)
+    })
+    .context("Failed to link ApiOperations to Routes")?;
+
+    let cypher = format!(
+        "MATCH (a:ApiOperation {{repo_id: $repo_id}})
+         MATCH (e:Endpoint {{repo_id: $repo_id, path: a.route_path, method: a.method}})
+         MERGE (a)-[rel:IMPLEMENTED_BY]->(e)
+         SET rel.id = {},
+             rel.repo_id = $repo_id",
+        edge_id_cypher("IMPLEMENTED_BY", operation_key, "e.method + '::' + e.url")
+    );
+    retry_query!(graph_db, {
+        query(&cypher).param("repo_id", repo_id)
+    })
+    .context("Failed to link ApiOperations to Endpoints")?;
+
+    Ok(())
+}
+
+async fn batch_insert_rpc_nodes(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let mut nodes: Vec<BoltMap> = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+
+    for rpc in &communication_analysis.rpc_services {
+        if seen.insert(rpc.service_name.clone()) {
+            let mut m = HashMap::new();
+            m.insert("name".to_string(), rpc.service_name.clone());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            nodes.push(m);
+        }
+    }
+
+    let nodes = writer.stamp(nodes);
+    writer.write(graph_db, "RpcService nodes", &nodes, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (r:RpcService {name: node.name, repo_id: node.repo_id})
+             SET r += node.metadata"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    info!("   Inserted {} RpcService nodes", nodes.len());
+    Ok(())
+}
+
+async fn batch_insert_rpc_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let mut edges: Vec<BoltMap> = Vec::new();
+
+    for rpc in &communication_analysis.rpc_services {
+        let mut m = HashMap::new();
+        m.insert("id".to_string(), edge_id("CALLS_RPC", &rpc.file_path, &rpc.service_name));
+        m.insert("file_path".to_string(), rpc.file_path.clone());
+        m.insert("service_name".to_string(), rpc.service_name.clone());
+        m.insert("repo_id".to_string(), repo_id.to_string());
+        edges.push(m);
+    }
+
+    writer.write(graph_db, "CALLS_RPC edges", &edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (r:RpcService {name: edge.service_name, repo_id: edge.repo_id})
+             MERGE (f)-[rel:CALLS_RPC]->(r)
+             SET rel.id = edge.id,
+                 rel.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    info!("   Created {} CALLS_RPC edges", edges.len());
+    Ok(())
+}
+
+fn rpc_method_node_maps(proto_services: &[ProtoService], repo_id: &str) -> Vec<BoltMap> {
+    let mut nodes: Vec<BoltMap> = Vec::new();
+    let mut seen = HashSet::new();
+    for service in proto_services {
+        for method in &service.methods {
+            if seen.insert((service.name.as_str(), method.as_str())) {
+                let mut m = HashMap::new();
+                m.insert("service".to_string(), service.name.clone());
+                m.insert("name".to_string(), method.clone());
+                m.insert("file_path".to_string(), service.file_path.clone());
+                m.insert(
+                    "edge_id".to_string(),
+                    edge_id("CONTAINS", &service.name, &format!("{}.{}", service.name, method)),
+                );
+                m.insert("repo_id".to_string(), repo_id.to_string());
+                nodes.push(m);
+            }
+        }
+    }
+    nodes
+}
+
+fn rpc_method_call_edge_maps(calls: &[RpcMethodCall], repo_id: &str) -> Vec<BoltMap> {
+    calls
+        .iter()
+        .map(|call| {
+            let method_key = format!("{}.{}", call.service_name, call.method);
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), edge_id("CALLS_RPC_METHOD", &call.file_path, &method_key));
+            m.insert("file_path".to_string(), call.file_path.clone());
+            m.insert("service".to_string(), call.service_name.clone());
+            m.insert("method".to_string(), call.method.clone());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            m
+        })
+        .collect()
+}
+
+/// Store the rpcs of the repo's proto services under their RpcService, and
+/// the files calling them through client stubs. Protos are read on every
+/// run, so rpcs no longer declared are deleted.
+async fn store_rpc_methods(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let nodes = rpc_method_node_maps(&communication_analysis.proto_services, repo_id);
+    let declared: Vec<String> = nodes.iter().map(|node| format!("{}.{}", node["service"], node["name"])).collect();
+    retry_query!(graph_db, {
+        query(
+            "MATCH (m:RpcMethod {repo_id: $repo_id})
+             WHERE NOT m.service + '.' + m.name IN $declared
+             DETACH DELETE m"
+        )
+        .param("repo_id", repo_id)
+        .param("declared", declared.clone())
+    }).context("Failed to delete stale RpcMethod nodes")?;
+
+    let nodes = writer.stamp(nodes);
+    writer.write(graph_db, "RpcMethod nodes", &nodes, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (s:RpcService {name: node.service, repo_id: node.repo_id})
+             MERGE (m:RpcMethod {service: node.service, name: node.name, repo_id: node.repo_id})
+             SET s += node.metadata, m += node.metadata
+             SET m.file_path = node.file_path
+             MERGE (s)-[r:CONTAINS]->(m)
+             SET r.id = node.edge_id,
+                 r.repo_id = node.repo_id"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    let edges = rpc_method_call_edge_maps(&communication_analysis.rpc_method_calls, repo_id);
+    writer.write(graph_db, "CALLS_RPC_METHOD edges", &edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (m:RpcMethod {service: edge.service, name: edge.method, repo_id: edge.repo_id})
+             MERGE (f)-[r:CALLS_RPC_METHOD]->(m)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    info!("   Inserted {} RpcMethod nodes and {} CALLS_RPC_METHOD edges", nodes.len(), edges.len());
+    Ok(())
+}
+
+/// One node per topic; a topic is dynamic only if every usage names it
+/// through an identifier
+fn queue_node_maps(communication_analysis: &CommunicationAnalysis, repo_id: &str) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    let mut topics: Vec<(&str, &str, bool)> = Vec::new();
+    for queue in &communication_analysis.queues {
+        match topics.iter_mut().find(|(topic, _, _)| *topic == queue.topic) {
+            Some((_, _, dynamic)) => *dynamic &= queue.dynamic,
+            None => topics.push((&queue.topic, &queue.technology, queue.dynamic)),
+        }
+    }
+
+    topics
+        .into_iter()
+        .map(|(topic, technology, dynamic)| {
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("topic".to_string(), topic.to_string().into());
+            m.insert("technology".to_string(), technology.to_string().into());
+            m.insert("dynamic".to_string(), dynamic.into());
+            m.insert("repo_id".to_string(), repo_id.to_string().into());
+            m
+        })
+        .collect()
+}
+
+async fn batch_insert_queue_nodes(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let nodes = writer.stamp(queue_node_maps(communication_analysis, repo_id));
+
+    writer.write(graph_db, "MessageQueue nodes", &nodes, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (q:MessageQueue {topic: node.topic, repo_id: node.repo_id})
+             SET q += node.metadata
+             SET q.technology = node.technology,
+                 q.dynamic = node.dynamic"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    info!("   Inserted {} MessageQueue nodes", nodes.len());
+    Ok(())
+}
+
+async fn batch_insert_queue_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let mut publish_edges: Vec<BoltMap> = Vec::new();
+    let mut consume_edges: Vec<BoltMap> = Vec::new();
+
+    for queue in &communication_analysis.queues {
+        let (edge_type, edges) = match queue.direction {
+            QueueDirection::Publish => ("PUBLISHES_TO", &mut publish_edges),
+            QueueDirection::Consume => ("CONSUMES_FROM", &mut consume_edges),
+        };
+        let mut m = HashMap::new();
+        m.insert("id".to_string(), edge_id(edge_type, &queue.file_path, &queue.topic));
+        m.insert("file_path".to_string(), queue.file_path.clone());
+        m.insert("topic".to_string(), queue.topic.clone());
+        m.insert("repo_id".to_string(), repo_id.to_string());
+        edges.push(m);
+    }
+
+    writer.write(graph_db, "PUBLISHES_TO edges", &publish_edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (q:MessageQueue {topic: edge.topic, repo_id: edge.repo_id})
+             MERGE (f)-[r:PUBLISHES_TO]->(q)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    writer.write(graph_db, "CONSUMES_FROM edges", &consume_edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (q:MessageQueue {topic: edge.topic, repo_id: edge.repo_id})
+             MERGE (f)-[r:CONSUMES_FROM]->(q)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    info!(
+        "   Created {} PUBLISHES_TO and {} CONSUMES_FROM edges",
+        publish_edges.len(),
+        consume_edges.len()
+    );
+    Ok(())
+}
+
+/// One node per host; a host is internal as long as any connection says so
+fn websocket_channel_node_maps(communication_analysis: &CommunicationAnalysis, repo_id: &str) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    let mut hosts: Vec<(&str, bool)> = Vec::new();
+    for connection in &communication_analysis.realtime_connections {
+        match hosts.iter_mut().find(|(host, _)| *host == connection.host) {
+            Some((_, internal)) => *internal |= connection.internal,
+            None => hosts.push((&connection.host, connection.internal)),
+        }
+    }
+
+    hosts
+        .into_iter()
+        .map(|(host, internal)| {
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("host".to_string(), host.to_string().into());
+            m.insert("internal".to_string(), internal.into());
+            m.insert("repo_id".to_string(), repo_id.to_string().into());
+            m
+        })
+        .collect()
+}
+
+fn socket_event_node_maps(communication_analysis: &CommunicationAnalysis, repo_id: &str) -> Vec<BoltMap> {
+    let mut names: Vec<&str> = communication_analysis.socket_events.iter().map(|event| event.name.as_str()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|name| {
+            let mut m = HashMap::new();
+            m.insert("name".to_string(), name.to_string());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            m
+        })
+        .collect()
+}
+
+/// CONNECTS_TO edges (with the URL and transport of the first connection
+/// per file and host), then EMITS and LISTENS_TO edges
+fn realtime_edge_maps(communication_analysis: &CommunicationAnalysis, repo_id: &str) -> (Vec<BoltMap>, Vec<BoltMap>, Vec<BoltMap>) {
+    let mut connects: Vec<BoltMap> = Vec::new();
+    for connection in &communication_analysis.realtime_connections {
+        let id = edge_id("CONNECTS_TO", &connection.file_path, &connection.host);
+        if connects.iter().any(|edge| edge["id"] == id) {
+            continue;
+        }
+        let mut m = HashMap::new();
+        m.insert("id".to_string(), id);
+        m.insert("file_path".to_string(), connection.file_path.clone());
+        m.insert("host".to_string(), connection.host.clone());
+        m.insert("url".to_string(), connection.url.clone());
+        m.insert("transport".to_string(), connection.transport.clone());
+        m.insert("repo_id".to_string(), repo_id.to_string());
+        connects.push(m);
+    }
+
+    let mut emits: Vec<BoltMap> = Vec::new();
+    let mut listens: Vec<BoltMap> = Vec::new();
+    for event in &communication_analysis.socket_events {
+        let (edge_type, edges) = match event.direction {
+            SocketEventDirection::Emit => ("EMITS", &mut emits),
+            SocketEventDirection::Listen => ("LISTENS_TO", &mut listens),
+        };
+        let mut m = HashMap::new();
+        m.insert("id".to_string(), edge_id(edge_type, &event.file_path, &event.name));
+        m.insert("file_path".to_string(), event.file_path.clone());
+        m.insert("name".to_string(), event.name.clone());
+        m.insert("repo_id".to_string(), repo_id.to_string());
+        edges.push(m);
+    }
+
+    (connects, emits, listens)
+}
+
+async fn batch_insert_realtime_nodes(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let channels = writer.stamp(websocket_channel_node_maps(communication_analysis, repo_id));
+    writer.write(graph_db, "WebSocketChannel nodes", &channels, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (c:WebSocketChannel {host: node.host, repo_id: node.repo_id})
+             SET c += node.metadata
+             SET c.internal = node.internal"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    let events = writer.stamp(socket_event_node_maps(communication_analysis, repo_id));
+    writer.write(graph_db, "SocketEvent nodes", &events, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (e:SocketEvent {name: node.name, repo_id: node.repo_id})
+             SET e += node.metadata"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    info!("   Inserted {} WebSocketChannel and {} SocketEvent nodes", channels.len(), events.len());
+    Ok(())
+}
+
+async fn batch_insert_realtime_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let (connects, emits, listens) = realtime_edge_maps(communication_analysis, repo_id);
+
+    writer.write(graph_db, "CONNECTS_TO edges", &connects, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (c:WebSocketChannel {host: edge.host, repo_id: edge.repo_id})
+             MERGE (f)-[r:CONNECTS_TO]->(c)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id,
+                 r.url = edge.url,
+                 r.transport = edge.transport"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    writer.write(graph_db, "EMITS edges", &emits, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (e:SocketEvent {name: edge.name, repo_id: edge.repo_id})
+             MERGE (f)-[r:EMITS]->(e)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    writer.write(graph_db, "LISTENS_TO edges", &listens, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (e:SocketEvent {name: edge.name, repo_id: edge.repo_id})
+             MERGE (f)-[r:LISTENS_TO]->(e)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    info!(
+        "   Created {} CONNECTS_TO, {} EMITS and {} LISTENS_TO edges",
+        connects.len(),
+        emits.len(),
+        listens.len()
+    );
+    Ok(())
+}
+
+/// `websocket_server` (the framework) for every analyzed file, null for
+/// the ones that don't accept WebSocket connections so a stale mark goes
+fn websocket_server_maps(outlines: &[ParsedFile], communication_analysis: &CommunicationAnalysis, repo_id: &str) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    outlines
+        .iter()
+        .map(|file| {
+            let framework = communication_analysis
+                .websocket_servers
+                .iter()
+                .find(|server| server.file_path == file.path)
+                .map(|server| server.framework.clone());
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("path".to_string(), file.path.clone().into());
+            m.insert("framework".to_string(), framework.into());
+            m.insert("repo_id".to_string(), repo_id.to_string().into());
+            m
+        })
+        .collect()
+}
+
+async fn mark_websocket_servers(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    outlines: &[ParsedFile],
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let files = websocket_server_maps(outlines, communication_analysis, repo_id);
+    writer.write(graph_db, "websocket servers", &files, |chunk| {
+        query(
+            "UNWIND $files AS file
+             MATCH (f:File {path: file.path, repo_id: file.repo_id})
+             SET f.websocket_server = file.framework"
+        )
+        .param("files", chunk.to_vec())
+    }).await?;
+
+    info!("   Marked {} WebSocket server files", communication_analysis.websocket_servers.len());
+    Ok(())
+}
+
+async fn batch_insert_compose_service_nodes(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let mut nodes: Vec<HashMap<String, neo4rs::BoltType>> = Vec::new();
+
+    for service in &communication_analysis.compose_services {
+        let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+        m.insert("name".to_string(), service.name.clone().into());
+        m.insert("image".to_string(), service.image.clone().unwrap_or_default().into());
+        m.insert("build_context".to_string(), service.build_context.clone().unwrap_or_default().into());
+        m.insert("ports".to_string(), service.ports.clone().into());
+        m.insert("environment".to_string(), service.environment.clone().into());
+        m.insert("networks".to_string(), service.networks.clone().into());
+        m.insert("repo_id".to_string(), repo_id.to_string().into());
+        nodes.push(m);
+    }
+
+    let nodes = writer.stamp(nodes);
+    writer.write(graph_db, "ComposeService nodes", &nodes, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (s:ComposeService {name: node.name, repo_id: node.repo_id})
+             SET s += node.metadata
+             SET s.image = node.image,
+                 s.build_context = node.build_context,
+                 s.ports = node.ports,
+                 s.environment = node.environment,
+                 s.networks = node.networks"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    info!("   Inserted {} ComposeService nodes", nodes.len());
+    Ok(())
+}
+
+/// `depends_on` between compose services; dependencies on services not
+/// defined in the merged compose files are skipped
+async fn batch_insert_compose_dependency_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let defined: HashSet<&str> = communication_analysis.compose_services.iter().map(|s| s.name.as_str()).collect();
+    let mut edges: Vec<BoltMap> = Vec::new();
+
+    for service in &communication_analysis.compose_services {
+        for dependency in service.depends_on.iter().filter(|d| defined.contains(d.as_str())) {
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), edge_id("DEPENDS_ON_SERVICE", &service.name, dependency));
+            m.insert("from".to_string(), service.name.clone());
+            m.insert("to".to_string(), dependency.clone());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            edges.push(m);
+        }
+    }
+
+    writer.write(graph_db, "DEPENDS_ON_SERVICE edges", &edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (a:ComposeService {name: edge.from, repo_id: edge.repo_id})
+             MATCH (b:ComposeService {name: edge.to, repo_id: edge.repo_id})
+             MERGE (a)-[r:DEPENDS_ON_SERVICE]->(b)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    info!("   Created {} DEPENDS_ON_SERVICE edges", edges.len());
+    Ok(())
+}
+
+async fn batch_insert_endpoint_service_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let mut edges: Vec<BoltMap> = Vec::new();
+
+    for (endpoint, service_name) in endpoint_services(communication_analysis) {
+        let endpoint_key = format!("{}::{}", endpoint.method, endpoint.url);
+        let mut m = HashMap::new();
+        m.insert("id".to_string(), edge_id("EXPOSED_BY", &endpoint_key, service_name));
+        m.insert("url".to_string(), endpoint.url.clone());
+        m.insert("method".to_string(), endpoint.method.clone());
+        m.insert("service_name".to_string(), service_name.to_string());
+        m.insert("repo_id".to_string(), repo_id.to_string());
+        edges.push(m);
+    }
+
+    writer.write(graph_db, "EXPOSED_BY edges", &edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (e:Endpoint {url: edge.url, method: edge.method, repo_id: edge.repo_id})
+             MATCH (s:ComposeService {name: edge.service_name, repo_id: edge.repo_id})
+             MERGE (e)-[r:EXPOSED_BY]->(s)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    info!("   Created {} EXPOSED_BY edges", edges.len());
+    Ok(())
+}
+
+/// Key of a K8s node in edge ids, unique within the repo
+fn k8s_key(namespace: &str, name: &str) -> String {
+    format!("{}/{}", namespace, name)
+}
+
+fn container_image_node_maps(images: &[ContainerImage], repo_id: &str) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    images
+        .iter()
+        .map(|image| {
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("path".to_string(), image.dockerfile.clone().into());
+            m.insert("name".to_string(), image.name.clone().into());
+            m.insert("base_image".to_string(), image.base_image.clone().unwrap_or_default().into());
+            m.insert("exposed_ports".to_string(), image.exposed_ports.clone().into());
+            m.insert("copy_sources".to_string(), image.copy_sources.clone().into());
+            m.insert("repo_id".to_string(), repo_id.into());
+            m
+        })
+        .collect()
+}
+
+fn k8s_deployment_node_maps(
+    deployments: &[K8sDeployment],
+    repo_id: &str,
+) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    deployments
+        .iter()
+        .map(|deployment| {
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("namespace".to_string(), deployment.namespace.clone().into());
+            m.insert("name".to_string(), deployment.name.clone().into());
+            m.insert("kind".to_string(), deployment.kind.clone().into());
+            m.insert("file".to_string(), deployment.file.clone().into());
+            // Left out rather than zero when the manifest doesn't say
+            if let Some(replicas) = deployment.replicas {
+                m.insert("replicas".to_string(), replicas.into());
+            }
+            m.insert("images".to_string(), deployment.images.clone().into());
+            let labels: Vec<String> = deployment.pod_labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            m.insert("labels".to_string(), labels.into());
+            m.insert("repo_id".to_string(), repo_id.into());
+            m
+        })
+        .collect()
+}
+
+fn k8s_service_node_maps(services: &[K8sService], repo_id: &str) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    services
+        .iter()
+        .map(|service| {
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("namespace".to_string(), service.namespace.clone().into());
+            m.insert("name".to_string(), service.name.clone().into());
+            m.insert("type".to_string(), service.service_type.clone().into());
+            m.insert("file".to_string(), service.file.clone().into());
+            m.insert("ports".to_string(), service.ports.clone().into());
+            let selector: Vec<String> = service.selector.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            m.insert("selector".to_string(), selector.into());
+            m.insert("ingress_hosts".to_string(), service.ingress_hosts.clone().into());
+            m.insert("repo_id".to_string(), repo_id.into());
+            m
+        })
+        .collect()
+}
+
+/// RUNS_IMAGE, SELECTS and BUILDS edges between the infrastructure nodes
+/// and the compose services
+fn infrastructure_edge_maps(
+    infra: &InfraAnalysis,
+    compose_services: &[ComposeService],
+    repo_id: &str,
+) -> (Vec<BoltMap>, Vec<BoltMap>, Vec<BoltMap>) {
+    let mut runs_image = Vec::new();
+    for (deployment, image) in infra.runs_image() {
+        let key = k8s_key(&deployment.namespace, &deployment.name);
+        let mut m = HashMap::new();
+        m.insert("id".to_string(), edge_id("RUNS_IMAGE", &key, &image.dockerfile));
+        m.insert("namespace".to_string(), deployment.namespace.clone());
+        m.insert("name".to_string(), deployment.name.clone());
+        m.insert("path".to_string(), image.dockerfile.clone());
+        m.insert("repo_id".to_string(), repo_id.to_string());
+        runs_image.push(m);
+    }
+
+    let mut selects = Vec::new();
+    for (service, deployment) in infra.selects() {
+        let source = k8s_key(&service.namespace, &service.name);
+        let target = k8s_key(&deployment.namespace, &deployment.name);
+        let mut m = HashMap::new();
+        m.insert("id".to_string(), edge_id("SELECTS", &source, &target));
+        m.insert("namespace".to_string(), service.namespace.clone());
+        m.insert("service".to_string(), service.name.clone());
+        m.insert("deployment".to_string(), deployment.name.clone());
+        m.insert("repo_id".to_string(), repo_id.to_string());
+        selects.push(m);
+    }
+
+    let mut builds = Vec::new();
+    for (service, image) in infra.compose_builds(compose_services) {
+        let mut m = HashMap::new();
+        m.insert("id".to_string(), edge_id("BUILDS", &service.name, &image.dockerfile));
+        m.insert("service_name".to_string(), service.name.clone());
+        m.insert("path".to_string(), image.dockerfile.clone());
+        m.insert("repo_id".to_string(), repo_id.to_string());
+        builds.push(m);
+    }
+
+    (runs_image, selects, builds)
+}
+
+/// Replace the repo's ContainerImage, K8sDeployment and K8sService nodes
+/// with this run's scan. Images are filed under the deepest logical
+/// Boundary whose directory holds their Dockerfile, the way assets are.
+async fn replace_infrastructure(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    infra: &InfraAnalysis,
+    compose_services: &[ComposeService],
+    writer: &BatchWriter,
+) -> Result<()> {
+    retry_query!(graph_db, {
+        query(
+            "MATCH (n {repo_id: $repo_id})
+             WHERE n:ContainerImage OR n:K8sDeployment OR n:K8sService
+             DETACH DELETE n"
+        )
+        .param("repo_id", repo_id)
+    })
+    .context("Failed to delete infrastructure nodes")?;
+
+    let images = writer.stamp(container_image_node_maps(&infra.container_images, repo_id));
+    writer.write(graph_db, "ContainerImage nodes", &images, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (i:ContainerImage {path: node.path, repo_id: node.repo_id})
+             SET i += node.metadata
+             SET i.name = node.name,
+                 i.base_image = node.base_image,
+                 i.exposed_ports = node.exposed_ports,
+                 i.copy_sources = node.copy_sources"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    let cypher = format!(
+        "UNWIND $nodes AS node
+         MATCH (i:ContainerImage {{path: node.path, repo_id: node.repo_id}})
+         CALL {{
+           WITH i
+           MATCH (b:Boundary {{repo_id: i.repo_id, type: 'logical'}})
+           WHERE i.path STARTS WITH b.path + '/'
+           RETURN b ORDER BY size(b.path) DESC LIMIT 1
+         }}
+         MERGE (i)-[r:BELONGS_TO]->(b)
+         SET r.id = {},
+             r.repo_id = i.repo_id",
+        edge_id_cypher("BELONGS_TO", "i.path", "b.id")
+    );
+    writer.write(graph_db, "ContainerImage BELONGS_TO edges", &images, |chunk| {
+        query(&cypher)
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    let deployments = writer.stamp(k8s_deployment_node_maps(&infra.k8s_deployments, repo_id));
+    writer.write(graph_db, "K8sDeployment nodes", &deployments, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (d:K8sDeployment {namespace: node.namespace, name: node.name, repo_id: node.repo_id})
+             SET d += node.metadata
+             SET d.kind = node.kind,
+                 d.file = node.file,
+                 d.replicas = node.replicas,
+                 d.images = node.images,
+                 d.labels = node.labels"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    let services = writer.stamp(k8s_service_node_maps(&infra.k8s_services, repo_id));
+    writer.write(graph_db, "K8sService nodes", &services, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (s:K8sService {namespace: node.namespace, name: node.name, repo_id: node.repo_id})
+             SET s += node.metadata
+             SET s.type = node.type,
+                 s.file = node.file,
+                 s.ports = node.ports,
+                 s.selector = node.selector,
+                 s.ingress_hosts = node.ingress_hosts"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    let (runs_image, selects, builds) = infrastructure_edge_maps(infra, compose_services, repo_id);
+    writer.write(graph_db, "RUNS_IMAGE edges", &runs_image, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (d:K8sDeployment {namespace: edge.namespace, name: edge.name, repo_id: edge.repo_id})
+             MATCH (i:ContainerImage {path: edge.path, repo_id: edge.repo_id})
+             MERGE (d)-[r:RUNS_IMAGE]->(i)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+    writer.write(graph_db, "SELECTS edges", &selects, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (s:K8sService {namespace: edge.namespace, name: edge.service, repo_id: edge.repo_id})
+             MATCH (d:K8sDeployment {namespace: edge.namespace, name: edge.deployment, repo_id: edge.repo_id})
+             MERGE (s)-[r:SELECTS]->(d)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+    writer.write(graph_db, "BUILDS edges", &builds, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (s:ComposeService {name: edge.service_name, repo_id: edge.repo_id})
+             MATCH (i:ContainerImage {path: edge.path, repo_id: edge.repo_id})
+             MERGE (s)-[r:BUILDS]->(i)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    info!(
+        "   Inserted {} ContainerImage, {} K8sDeployment and {} K8sService nodes; {} RUNS_IMAGE, {} SELECTS and {} BUILDS edges",
+        images.len(),
+        deployments.len(),
+        services.len(),
+        runs_image.len(),
+        selects.len(),
+        builds.len()
+    );
+    Ok(())
+}
+
+/// One EnvVar per name, whether a file reads it or a compose service sets it
+fn env_var_node_maps(
+    parsed_files: &[ParsedFile],
+    communication_analysis: &CommunicationAnalysis,
+    repo_id: &str,
+) -> Vec<BoltMap> {
+    let names: BTreeSet<&str> = parsed_files
+        .iter()
+        .flat_map(|file| file.env_vars.iter())
+        .chain(communication_analysis.compose_services.iter().flat_map(|s| s.environment.iter()))
+        .map(String::as_str)
+        .collect();
+
+    names
+        .into_iter()
+        .map(|name| {
+            let mut m = HashMap::new();
+            m.insert("name".to_string(), name.to_string());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            m
+        })
+        .collect()
+}
+
+/// (File)-[:READS_ENV]->(EnvVar) edges
+fn reads_env_edge_maps(parsed_files: &[ParsedFile], repo_id: &str) -> Vec<BoltMap> {
+    let mut edges = Vec::new();
+    for file in parsed_files {
+        for name in &file.env_vars {
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), edge_id("READS_ENV", &file.path, name));
+            m.insert("file_path".to_string(), file.path.clone());
+            m.insert("name".to_string(), name.clone());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            edges.push(m);
+        }
+    }
+    edges
+}
+
+/// (ComposeService)-[:SETS_ENV]->(EnvVar) edges from each service's
+/// `environment` section
+fn sets_env_edge_maps(communication_analysis: &CommunicationAnalysis, repo_id: &str) -> Vec<BoltMap> {
+    let mut edges = Vec::new();
+    for service in &communication_analysis.compose_services {
+        for name in &service.environment {
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), edge_id("SETS_ENV", &service.name, name));
+            m.insert("service_name".to_string(), service.name.clone());
+            m.insert("name".to_string(), name.clone());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            edges.push(m);
+        }
+    }
+    edges
+}
+
+async fn batch_insert_env_var_nodes(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    parsed_files: &[ParsedFile],
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let nodes = writer.stamp(env_var_node_maps(parsed_files, communication_analysis, repo_id));
+
+    writer.write(graph_db, "EnvVar nodes", &nodes, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (v:EnvVar {name: node.name, repo_id: node.repo_id})
+             SET v += node.metadata"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    info!("   Inserted {} EnvVar nodes", nodes.len());
+    Ok(())
+}
+
+async fn batch_insert_env_var_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    parsed_files: &[ParsedFile],
+    communication_analysis: &CommunicationAnalysis,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let read_edges = reads_env_edge_maps(parsed_files, repo_id);
+    let set_edges = sets_env_edge_maps(communication_analysis, repo_id);
+
+    writer.write(graph_db, "READS_ENV edges", &read_edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (v:EnvVar {name: edge.name, repo_id: edge.repo_id})
+             MERGE (f)-[r:READS_ENV]->(v)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    writer.write(graph_db, "SETS_ENV edges", &set_edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (s:ComposeService {name: edge.service_name, repo_id: edge.repo_id})
+             MATCH (v:EnvVar {name: edge.name, repo_id: edge.repo_id})
+             MERGE (s)-[r:SETS_ENV]->(v)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    info!(
+        "   Created {} READS_ENV and {} SETS_ENV edges",
+        read_edges.len(),
+        set_edges.len()
+    );
+    Ok(())
+}
+
+// ============================================================================
+// Batch Edge Inserts
+// ============================================================================
+
+async fn batch_insert_defines_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    dep_graph: &DependencyGraph,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let mut file_to_class: Vec<BoltMap> = Vec::new();
+    let mut file_to_func: Vec<BoltMap> = Vec::new();
+    
+    for edge in &dep_graph.edges {
+        if edge.edge_type != EdgeType::Defines {
+            continue;
+        }
+        
+        match (&edge.from, &edge.to) {
+            (NodeId::File(file_path), NodeId::Class(class_file, class_name)) => {
+                let class_id = get_qualified_id(class_file, class_name);
+                let mut m = HashMap::new();
+                m.insert("id".to_string(), edge.id());
+                m.insert("file_path".to_string(), file_path.to_string());
+                m.insert("class_id".to_string(), class_id);
+                m.insert("repo_id".to_string(), repo_id.to_string());
+                file_to_class.push(m);
+            }
+            (NodeId::File(file_path), func @ NodeId::Function(_, _)) => {
+                let func_id = func.qualified_id();
+                let mut m = HashMap::new();
+                m.insert("id".to_string(), edge.id());
+                m.insert("file_path".to_string(), file_path.to_string());
+                m.insert("func_id".to_string(), func_id);
+                m.insert("repo_id".to_string(), repo_id.to_string());
+                file_to_func.push(m);
+            }
+            _ => {}
+        }
+    }
+
+    // Batch File->Class DEFINES
+    writer.write(graph_db, "File->Class DEFINES", &file_to_class, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (c:Class {id: edge.class_id, repo_id: edge.repo_id})
+             MERGE (f)-[r:DEFINES]->(c)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    // Batch File->Function DEFINES
+    writer.write(graph_db, "File->Function DEFINES", &file_to_func, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (fn:Function {id: edge.func_id, repo_id: edge.repo_id})
+             MERGE (f)-[r:DEFINES]->(fn)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+    
+    info!("   Created {} DEFINES edges", file_to_class.len() + file_to_func.len());
+    Ok(())
+}
+
+async fn batch_insert_contains_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    dep_graph: &DependencyGraph,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let mut edges: Vec<BoltMap> = Vec::new();
+    
+    for edge in &dep_graph.edges {
+        if edge.edge_type != EdgeType::Contains {
+            continue;
+        }
+        
+        if let (class @ NodeId::Class(_, _), method @ NodeId::Method(_, _, _)) = (&edge.from, &edge.to) {
+            let class_id = class.qualified_id();
+            let func_id = method.qualified_id();
+            
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), edge.id());
+            m.insert("class_id".to_string(), class_id);
+            m.insert("func_id".to_string(), func_id);
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            edges.push(m);
+        }
+    }
+
+    writer.write(graph_db, "CONTAINS edges", &edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (c:Class {id: edge.class_id, repo_id: edge.repo_id})
+             MATCH (fn:Function {id: edge.func_id, repo_id: edge.repo_id})
+             MERGE (c)-[r:CONTAINS]->(fn)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+    
+    info!("   Created {} CONTAINS edges", edges.len());
+    Ok(())
+}
+
+/// CALLS edge rows; `count` is the number of call sites in the caller, and
+/// `resolution` is `ambiguous` when the callee was one of several candidates
+fn calls_edge_maps(dep_graph: &DependencyGraph, repo_id: &str) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    let mut edges = Vec::new();
+
+    for edge in &dep_graph.edges {
+        if edge.edge_type != EdgeType::Calls {
+            continue;
+        }
+
+        if edge.from.node_type() == "Function" && edge.to.node_type() == "Function" {
+            let count: i64 = edge.properties.get("count").and_then(|c| c.parse().ok()).unwrap_or(1);
+
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("id".to_string(), edge.id().into());
+            m.insert("from_id".to_string(), edge.from.qualified_id().into());
+            m.insert("to_id".to_string(), edge.to.qualified_id().into());
+            m.insert("count".to_string(), count.into());
+            m.insert("resolution".to_string(), edge.properties.get("resolution").cloned().into());
+            m.insert("repo_id".to_string(), repo_id.to_string().into());
+            edges.push(m);
+        }
+    }
+    edges
+}
+
+async fn batch_insert_calls_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    dep_graph: &DependencyGraph,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let edges = calls_edge_maps(dep_graph, repo_id);
+
+    writer.write(graph_db, "CALLS edges", &edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (from:Function {id: edge.from_id, repo_id: edge.repo_id})
+             MATCH (to:Function {id: edge.to_id, repo_id: edge.repo_id})
+             MERGE (from)-[r:CALLS]->(to)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id,
+                 r.count = edge.count,
+                 r.resolution = edge.resolution"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+    
+    info!("   Created {} CALLS edges", edges.len());
+    Ok(())
+}
+
+async fn batch_insert_imports_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    dep_graph: &DependencyGraph,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let mut edges: Vec<BoltMap> = Vec::new();
+    
+    for edge in &dep_graph.edges {
+        if edge.edge_type != EdgeType::Imports {
+            continue;
+        }
+        
+        if let (NodeId::File(file_path), NodeId::Module(module_name)) = (&edge.from, &edge.to) {
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), edge.id());
+            m.insert("file_path".to_string(), file_path.to_string());
+            m.insert("module_name".to_string(), module_name.to_string());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            edges.push(m);
+        }
+    }
+
+    writer.write(graph_db, "IMPORTS edges", &edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (m:Module {name: edge.module_name, repo_id: edge.repo_id})
+             MERGE (f)-[r:IMPORTS]->(m)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+    
+    info!("   Created {} IMPORTS edges", edges.len());
+    Ok(())
+}
+
+async fn batch_insert_inherits_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    dep_graph: &DependencyGraph,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let mut class_to_class: Vec<BoltMap> = Vec::new();
+    let mut class_to_module: Vec<BoltMap> = Vec::new();
+    
+    for edge in &dep_graph.edges {
+        if edge.edge_type != EdgeType::Inherits {
+            continue;
+        }
+        
+        let inheritance_type = edge
+            .properties
+            .get("kind")
+            .cloned()
+            .unwrap_or_else(|| "class".to_string());
+
+        match (&edge.from, &edge.to) {
+            (NodeId::Class(from_file, from_name), NodeId::Class(to_file, to_name)) => {
+                let from_id = get_qualified_id(from_file, from_name);
+                let to_id = get_qualified_id(to_file, to_name);
+                
+                let mut m = HashMap::new();
+                m.insert("id".to_string(), edge.id());
+                m.insert("from_id".to_string(), from_id);
+                m.insert("to_id".to_string(), to_id);
+                m.insert("repo_id".to_string(), repo_id.to_string());
+                m.insert("inheritance_type".to_string(), inheritance_type.clone());
+                class_to_class.push(m);
+            }
+            (NodeId::Class(class_file, class_name), NodeId::Module(module_name)) => {
+                let class_id = get_qualified_id(class_file, class_name);
+                
+                let mut m = HashMap::new();
+                m.insert("id".to_string(), edge.id());
+                m.insert("class_id".to_string(), class_id);
+                m.insert("module_name".to_string(), module_name.to_string());
+                m.insert("repo_id".to_string(), repo_id.to_string());
+                m.insert("inheritance_type".to_string(), inheritance_type.clone());
+                class_to_module.push(m);
+            }
+            _ => {}
+        }
+    }
+
+    // Batch Class->Class INHERITS
+    writer.write(graph_db, "Class->Class INHERITS", &class_to_class, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (child:Class {id: edge.from_id, repo_id: edge.repo_id})
+             MATCH (parent:Class {id: edge.to_id, repo_id: edge.repo_id})
+               MERGE (child)-[r:INHERITS]->(parent)
+               SET r.id = edge.id,
+                   r.repo_id = edge.repo_id,
+                   r.type = edge.inheritance_type"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
 
-    info!("   Created {} EXPOSED_BY edges", edges.len());
+    // Batch Class->Module INHERITS (external)
+    writer.write(graph_db, "Class->Module INHERITS", &class_to_module, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (child:Class {id: edge.class_id, repo_id: edge.repo_id})
+             MATCH (parent:Module {name: edge.module_name, repo_id: edge.repo_id})
+               MERGE (child)-[r:INHERITS]->(parent)
+               SET r.id = edge.id,
+                   r.repo_id = edge.repo_id,
+                   r.type = edge.inheritance_type"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+    
+    info!("   Created {} INHERITS edges", class_to_class.len() + class_to_module.len());
     Ok(())
 }
 
 // ============================================================================
-// Batch Edge Inserts
+// Boundary Nodes and Edges
 // ============================================================================
 
-async fn batch_insert_defines_edges(
+/// Lines of code per boundary id, summed over the parsed files it contains
+fn boundary_total_loc<'a>(
+    boundary_result: &'a BoundaryDetectionResult,
+    parsed_files: &[ParsedFile],
+) -> HashMap<&'a str, usize> {
+    let line_counts: HashMap<&str, usize> = parsed_files
+        .iter()
+        .map(|f| (f.path.as_str(), f.line_count))
+        .collect();
+    boundary_result
+        .boundaries
+        .iter()
+        .map(|b| {
+            let total = b.files.iter().filter_map(|f| line_counts.get(f.as_str())).sum();
+            (b.id.as_str(), total)
+        })
+        .collect()
+}
+
+async fn batch_insert_boundary_nodes(
     graph_db: &neo4rs::Graph,
+    job_id: &str,
     repo_id: &str,
-    dep_graph: &DependencyGraph,
-    batch_size: usize,
+    boundary_result: &BoundaryDetectionResult,
+    parsed_files: &[ParsedFile],
+    writer: &BatchWriter,
 ) -> Result<()> {
-    let mut file_to_class: Vec<BoltMap> = Vec::new();
-    let mut file_to_func: Vec<BoltMap> = Vec::new();
-    
-    for edge in &dep_graph.edges {
-        if edge.edge_type != EdgeType::Defines {
-            continue;
-        }
-        
-        match (&edge.from, &edge.to) {
-            (NodeId::File(file_path), NodeId::Class(class_file, class_name)) => {
-                let class_id = get_qualified_id(class_file, class_name);
-                let mut m = HashMap::new();
-                m.insert("file_path".to_string(), file_path.to_string());
-                m.insert("class_id".to_string(), class_id);
-                m.insert("repo_id".to_string(), repo_id.to_string());
-                file_to_class.push(m);
+    let total_loc = boundary_total_loc(boundary_result, parsed_files);
+    let nodes: Vec<HashMap<String, neo4rs::BoltType>> = boundary_result.boundaries
+        .iter()
+        .map(|b| {
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("id".to_string(), b.id.clone().into());
+            m.insert("name".to_string(), b.name.clone().into());
+            m.insert("type".to_string(), b.boundary_type.as_str().to_string().into());
+            m.insert("path".to_string(), b.path.clone().into());
+            m.insert("job_id".to_string(), job_id.to_string().into());
+            m.insert("repo_id".to_string(), repo_id.to_string().into());
+            m.insert("file_count".to_string(), (b.file_count as i64).into());
+            m.insert("total_loc".to_string(), (total_loc[b.id.as_str()] as i64).into());
+            
+            if let Some(layer) = &b.layer {
+                m.insert("layer".to_string(), layer.as_str().to_string().into());
             }
-            (NodeId::File(file_path), NodeId::Function(func_file, func_name)) => {
-                let func_id = get_qualified_id(func_file, func_name);
-                let mut m = HashMap::new();
-                m.insert("file_path".to_string(), file_path.to_string());
-                m.insert("func_id".to_string(), func_id);
-                m.insert("repo_id".to_string(), repo_id.to_string());
-                file_to_func.push(m);
+
+            if let Some(metrics) = &b.metrics {
+                m.insert("cohesion".to_string(), metrics.cohesion.into());
+                m.insert("efferent_coupling".to_string(), (metrics.efferent_coupling as i64).into());
+                m.insert("afferent_coupling".to_string(), (metrics.afferent_coupling as i64).into());
+                m.insert("instability".to_string(), metrics.instability.into());
+                m.insert("abstractness".to_string(), metrics.abstractness.into());
+                m.insert("main_sequence_distance".to_string(), metrics.main_sequence_distance.into());
+                m.insert("health".to_string(), metrics.health.clone().into());
+                m.insert("low_cohesion".to_string(), metrics.low_cohesion.into());
             }
-            _ => {}
+            
+            m
+        })
+        .collect();
+
+    let nodes = writer.stamp(nodes);
+    writer.write(graph_db, "boundary nodes", &nodes, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (b:Boundary {id: node.id})
+             SET b += node.metadata
+             SET b.name = node.name,
+                 b.type = node.type,
+                 b.path = node.path,
+                 b.job_id = node.job_id,
+                 b.repo_id = node.repo_id,
+                 b.file_count = node.file_count,
+                 b.total_loc = node.total_loc,
+                 b.layer = COALESCE(node.layer, ''),
+                 b.cohesion = node.cohesion,
+                 b.efferent_coupling = node.efferent_coupling,
+                 b.afferent_coupling = node.afferent_coupling,
+                 b.instability = node.instability,
+                 b.abstractness = node.abstractness,
+                 b.main_sequence_distance = node.main_sequence_distance,
+                 b.health = node.health,
+                 b.low_cohesion = node.low_cohesion"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+    
+    info!("   Inserted {} Boundary nodes", nodes.len());
+    Ok(())
+}
+
+async fn batch_insert_belongs_to_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    boundary_result: &BoundaryDetectionResult,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let mut edges = Vec::new();
+    
+    for boundary in &boundary_result.boundaries {
+        for file_path in &boundary.files {
+            let mut m = HashMap::new();
+            m.insert("id".to_string(), edge_id("BELONGS_TO", file_path, &boundary.id));
+            m.insert("file_id".to_string(), file_path.clone());
+            m.insert("boundary_id".to_string(), boundary.id.clone());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            edges.push(m);
         }
     }
 
-    // Batch File->Class DEFINES
-    for chunk in file_to_class.chunks(batch_size) {
-        retry_query!(graph_db, {
-
-            query(
+    writer.write(graph_db, "BELONGS_TO edges", &edges, |chunk| {
+        query(
             "UNWIND $edges AS edge
-             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
-             MATCH (c:Class {id: edge.class_id, repo_id: edge.repo_id})
-             MERGE (f)-[:DEFINES]->(c)"
+             MATCH (f:File {id: edge.file_id, repo_id: edge.repo_id})
+             MATCH (b:Boundary {id: edge.boundary_id, repo_id: edge.repo_id})
+             MERGE (f)-[r:BELONGS_TO]->(b)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
         )
         .param("edges", chunk.to_vec())
+    }).await?;
+    
+    info!("   Created {} BELONGS_TO edges", edges.len());
+    Ok(())
+}
+
+/// Boundary ids produced by this run, and for every parsed file the ids of
+/// the boundaries it now belongs to (possibly none)
+fn boundary_reconciliation(
+    boundary_result: &BoundaryDetectionResult,
+    parsed_files: &[ParsedFile],
+    repo_id: &str,
+) -> (Vec<String>, Vec<HashMap<String, neo4rs::BoltType>>) {
+    let ids: Vec<String> = boundary_result.boundaries.iter().map(|b| b.id.clone()).collect();
 
-        }).context("Failed to batch insert File->Class DEFINES")?;
+    let mut memberships: BTreeMap<&str, Vec<String>> =
+        parsed_files.iter().map(|f| (f.path.as_str(), Vec::new())).collect();
+    for boundary in &boundary_result.boundaries {
+        for file in &boundary.files {
+            memberships.entry(file.as_str()).or_default().push(boundary.id.clone());
+        }
     }
 
-    // Batch File->Function DEFINES
-    for chunk in file_to_func.chunks(batch_size) {
+    let assignments = memberships
+        .into_iter()
+        .map(|(file, boundary_ids)| {
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("file_id".to_string(), file.to_string().into());
+            m.insert("boundary_ids".to_string(), boundary_ids.into());
+            m.insert("repo_id".to_string(), repo_id.to_string().into());
+            m
+        })
+        .collect();
+    (ids, assignments)
+}
+
+fn asset_node_maps(assets: &[AssetFile], repo_id: &str) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    assets
+        .iter()
+        .map(|asset| {
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("path".to_string(), asset.path.clone().into());
+            m.insert("extension".to_string(), asset.extension.clone().into());
+            m.insert("size".to_string(), (asset.size_bytes as i64).into());
+            m.insert("repo_id".to_string(), repo_id.into());
+            m
+        })
+        .collect()
+}
+
+/// Replace the repo's Asset nodes with this run's inventory, each filed
+/// under the deepest logical Boundary whose directory holds it. Boundaries
+/// come from the stored graph, so incremental runs place every asset too.
+async fn replace_assets(graph_db: &neo4rs::Graph, repo_id: &str, assets: &[AssetFile], writer: &BatchWriter) -> Result<()> {
+    retry_query!(graph_db, {
+        query("MATCH (a:Asset {repo_id: $repo_id}) DETACH DELETE a").param("repo_id", repo_id)
+    })
+    .context("Failed to delete Asset nodes")?;
+
+    let nodes = asset_node_maps(assets, repo_id);
+    let cypher = format!(
+        "UNWIND $nodes AS node
+         MERGE (a:Asset {{path: node.path, repo_id: node.repo_id}})
+         SET a += node.metadata
+         SET a.extension = node.extension,
+             a.size = node.size
+         WITH a
+         CALL {{
+           WITH a
+           MATCH (b:Boundary {{repo_id: a.repo_id, type: 'logical'}})
+           WHERE a.path STARTS WITH b.path + '/'
+           RETURN b ORDER BY size(b.path) DESC LIMIT 1
+         }}
+         MERGE (a)-[r:BELONGS_TO]->(b)
+         SET r.id = {},
+             r.repo_id = a.repo_id",
+        edge_id_cypher("BELONGS_TO", "a.path", "b.id")
+    );
+    let nodes = writer.stamp(nodes);
+    writer.write(graph_db, "Asset nodes", &nodes, |chunk| {
+        query(&cypher)
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    info!("   Inserted {} Asset nodes", nodes.len());
+    Ok(())
+}
+
+/// Drop BELONGS_TO edges from the parsed files to boundaries they no longer
+/// belong to. A full run also deletes the repo's Boundary nodes it didn't
+/// produce again.
+async fn reconcile_boundaries(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    boundary_result: &BoundaryDetectionResult,
+    parsed_files: &[ParsedFile],
+    full: bool,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let (ids, assignments) = boundary_reconciliation(boundary_result, parsed_files, repo_id);
+
+    if full {
         retry_query!(graph_db, {
 
             query(
-            "UNWIND $edges AS edge
-             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
-             MATCH (fn:Function {id: edge.func_id, repo_id: edge.repo_id})
-             MERGE (f)-[:DEFINES]->(fn)"
+                "MATCH (b:Boundary {repo_id: $repo_id})
+                 WHERE NOT b.id IN $ids
+                 DETACH DELETE b"
+            )
+            .param("repo_id", repo_id)
+            .param("ids", ids.clone())
+
+        }).context("Failed to delete stale Boundary nodes")?;
+    }
+
+    writer.write(graph_db, "stale BELONGS_TO edges", &assignments, |chunk| {
+        query(
+            "UNWIND $assignments AS assignment
+             MATCH (f:File {id: assignment.file_id, repo_id: assignment.repo_id})-[r:BELONGS_TO]->(b:Boundary)
+             WHERE NOT b.id IN assignment.boundary_ids
+             DELETE r"
         )
-        .param("edges", chunk.to_vec())
+        .param("assignments", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert File->Function DEFINES")?;
-    }
-    
-    info!("   Created {} DEFINES edges", file_to_class.len() + file_to_func.len());
+    info!("   Reconciled boundaries: {} current, {} files checked", ids.len(), assignments.len());
     Ok(())
 }
 
-async fn batch_insert_contains_edges(
-    graph_db: &neo4rs::Graph,
-    repo_id: &str,
+/// Create file-to-file DEPENDS_ON edges from the graph's resolved imports
+/// File-to-file edges, split into (DEPENDS_ON, TESTS). A test file's
+/// imports of production files become TESTS edges instead of DEPENDS_ON, and
+/// so do its calls into them; test-to-test imports stay DEPENDS_ON.
+fn file_dependency_edge_maps(
     dep_graph: &DependencyGraph,
-    batch_size: usize,
-) -> Result<()> {
-    let mut edges: Vec<BoltMap> = Vec::new();
-    
-    for edge in &dep_graph.edges {
-        if edge.edge_type != EdgeType::Contains {
-            continue;
+    parsed_files: &[ParsedFile],
+    repo_id: &str,
+) -> (Vec<BoltMap>, Vec<BoltMap>) {
+    let test_files: HashSet<&str> = parsed_files.iter().filter(|f| f.is_test).map(|f| f.path.as_str()).collect();
+    let is_test = |path: &str| test_files.contains(path);
+    let edge = |edge_type: &str, source: &str, target: &str, import_path: Option<&String>| {
+        let mut m = HashMap::new();
+        m.insert("id".to_string(), edge_id(edge_type, source, target));
+        m.insert("source_file".to_string(), source.to_string());
+        m.insert("target_file".to_string(), target.to_string());
+        if let Some(import_path) = import_path {
+            m.insert("import_path".to_string(), import_path.clone());
         }
-        
-        if let (NodeId::Class(class_file, class_name), NodeId::Function(func_file, func_name)) = 
-            (&edge.from, &edge.to) 
-        {
-            let class_id = get_qualified_id(class_file, class_name);
-            let func_id = get_qualified_id(func_file, func_name);
-            
-            let mut m = HashMap::new();
-            m.insert("class_id".to_string(), class_id);
-            m.insert("func_id".to_string(), func_id);
-            m.insert("repo_id".to_string(), repo_id.to_string());
-            edges.push(m);
+        m.insert("repo_id".to_string(), repo_id.to_string());
+        m
+    };
+
+    let mut depends_on = Vec::new();
+    let mut tested: BTreeSet<(&str, &str)> = BTreeSet::new();
+    for e in &dep_graph.edges {
+        match (&e.edge_type, &e.from, &e.to) {
+            (EdgeType::DependsOn, NodeId::File(source), NodeId::File(target)) => {
+                if is_test(source) && !is_test(target) {
+                    tested.insert((source, target));
+                } else {
+                    let import_path = e.properties.get("import_path").cloned().unwrap_or_default();
+                    depends_on.push(edge("DEPENDS_ON", source, target, Some(&import_path)));
+                }
+            }
+            (EdgeType::Calls, from, to) => {
+                if let (Some(source), Some(target)) = (from.file_path(), to.file_path()) {
+                    if is_test(source) && !is_test(target) && source != target {
+                        tested.insert((source, target));
+                    }
+                }
+            }
+            _ => {}
         }
     }
 
-    for chunk in edges.chunks(batch_size) {
-        retry_query!(graph_db, {
+    let tests = tested.into_iter().map(|(source, target)| edge("TESTS", source, target, None)).collect();
+    (depends_on, tests)
+}
 
-            query(
+async fn batch_insert_file_dependencies(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    parsed_files: &[ParsedFile],
+    dep_graph: &DependencyGraph,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let (edges, tests) = file_dependency_edge_maps(dep_graph, parsed_files, repo_id);
+
+    // Batch insert edges
+    writer.write(graph_db, "DEPENDS_ON edges", &edges, |chunk| {
+        query(
             "UNWIND $edges AS edge
-             MATCH (c:Class {id: edge.class_id, repo_id: edge.repo_id})
-             MATCH (fn:Function {id: edge.func_id, repo_id: edge.repo_id})
-             MERGE (c)-[:CONTAINS]->(fn)"
+             MATCH (source:File {path: edge.source_file, repo_id: edge.repo_id})
+             MATCH (target:File {path: edge.target_file, repo_id: edge.repo_id})
+             MERGE (source)-[d:DEPENDS_ON]->(target)
+             ON CREATE SET d.import_path = edge.import_path
+             SET d.id = edge.id,
+                 d.repo_id = edge.repo_id"
         )
         .param("edges", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert CONTAINS edges")?;
-    }
-    
-    info!("   Created {} CONTAINS edges", edges.len());
+    writer.write(graph_db, "TESTS edges", &tests, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (source:File {path: edge.source_file, repo_id: edge.repo_id})
+             MATCH (target:File {path: edge.target_file, repo_id: edge.repo_id})
+             MERGE (source)-[t:TESTS]->(target)
+             SET t.id = edge.id,
+                 t.repo_id = edge.repo_id"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    info!("   Created {} DEPENDS_ON edges, {} TESTS edges", edges.len(), tests.len());
     Ok(())
 }
 
-async fn batch_insert_calls_edges(
+/// File-to-file USES_COMPONENT edges, with the rendered component names
+/// comma-separated
+fn uses_component_edge_maps(dep_graph: &DependencyGraph, repo_id: &str) -> Vec<BoltMap> {
+    dep_graph
+        .edges_of_type(EdgeType::UsesComponent)
+        .into_iter()
+        .filter_map(|edge| match (&edge.from, &edge.to) {
+            (NodeId::File(source), NodeId::File(target)) => {
+                let mut m = HashMap::new();
+                m.insert("id".to_string(), edge.id());
+                m.insert("source_file".to_string(), source.clone());
+                m.insert("target_file".to_string(), target.clone());
+                m.insert("components".to_string(), edge.properties.get("components").cloned().unwrap_or_default());
+                m.insert("repo_id".to_string(), repo_id.to_string());
+                Some(m)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+async fn batch_insert_uses_component_edges(
     graph_db: &neo4rs::Graph,
     repo_id: &str,
     dep_graph: &DependencyGraph,
-    batch_size: usize,
+    writer: &BatchWriter,
 ) -> Result<()> {
-    let mut edges: Vec<BoltMap> = Vec::new();
-    
-    for edge in &dep_graph.edges {
-        if edge.edge_type != EdgeType::Calls {
-            continue;
-        }
-        
-        if let (NodeId::Function(from_file, from_name), NodeId::Function(to_file, to_name)) = 
-            (&edge.from, &edge.to) 
-        {
-            let from_id = get_qualified_id(from_file, from_name);
-            let to_id = get_qualified_id(to_file, to_name);
-            
+    let edges = uses_component_edge_maps(dep_graph, repo_id);
+
+    writer.write(graph_db, "USES_COMPONENT edges", &edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (source:File {path: edge.source_file, repo_id: edge.repo_id})
+             MATCH (target:File {path: edge.target_file, repo_id: edge.repo_id})
+             MERGE (source)-[u:USES_COMPONENT]->(target)
+             SET u.id = edge.id,
+                 u.repo_id = edge.repo_id,
+                 u.components = split(edge.components, ',')"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
+
+    info!("   Created {} USES_COMPONENT edges", edges.len());
+    Ok(())
+}
+
+// ============================================================================
+// Dependency Cycles
+// ============================================================================
+
+/// Cycles are disjoint, so the smallest member path identifies one
+fn cycle_id(files: &[String]) -> String {
+    format!("cycle:{}", files.first().map(String::as_str).unwrap_or_default())
+}
+
+fn cycle_node_maps(cycles: &[Vec<String>], repo_id: &str) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    cycles
+        .iter()
+        .map(|files| {
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("id".to_string(), cycle_id(files).into());
+            m.insert("size".to_string(), (files.len() as i64).into());
+            m.insert("repo_id".to_string(), repo_id.to_string().into());
+            m
+        })
+        .collect()
+}
+
+fn part_of_cycle_edge_maps(cycles: &[Vec<String>], repo_id: &str) -> Vec<BoltMap> {
+    let mut edges = Vec::new();
+    for files in cycles {
+        let id = cycle_id(files);
+        for file_path in files {
             let mut m = HashMap::new();
-            m.insert("from_id".to_string(), from_id);
-            m.insert("to_id".to_string(), to_id);
+            m.insert("id".to_string(), edge_id("PART_OF_CYCLE", file_path, &id));
+            m.insert("file_path".to_string(), file_path.clone());
+            m.insert("cycle_id".to_string(), id.clone());
             m.insert("repo_id".to_string(), repo_id.to_string());
             edges.push(m);
         }
     }
+    edges
+}
+
+/// Replace the repo's DependencyCycle nodes with `cycles`
+async fn replace_dependency_cycles(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    cycles: &[Vec<String>],
+    writer: &BatchWriter,
+) -> Result<()> {
+    retry_query!(graph_db, {
 
-    for chunk in edges.chunks(batch_size) {
-        retry_query!(graph_db, {
+        query("MATCH (c:DependencyCycle {repo_id: $repo_id}) DETACH DELETE c")
+        .param("repo_id", repo_id)
 
-            query(
+    }).context("Failed to delete DependencyCycle nodes")?;
+
+    let nodes = writer.stamp(cycle_node_maps(cycles, repo_id));
+    writer.write(graph_db, "DependencyCycle nodes", &nodes, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (c:DependencyCycle {id: node.id, repo_id: node.repo_id})
+             SET c += node.metadata
+             SET c.size = node.size"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    let edges = part_of_cycle_edge_maps(cycles, repo_id);
+    writer.write(graph_db, "PART_OF_CYCLE edges", &edges, |chunk| {
+        query(
             "UNWIND $edges AS edge
-             MATCH (from:Function {id: edge.from_id, repo_id: edge.repo_id})
-             MATCH (to:Function {id: edge.to_id, repo_id: edge.repo_id})
-             MERGE (from)-[:CALLS]->(to)"
+             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
+             MATCH (c:DependencyCycle {id: edge.cycle_id, repo_id: edge.repo_id})
+             MERGE (f)-[r:PART_OF_CYCLE]->(c)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
         )
         .param("edges", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert CALLS edges")?;
-    }
-    
-    info!("   Created {} CALLS edges", edges.len());
+    info!("   Inserted {} DependencyCycle nodes", nodes.len());
     Ok(())
 }
 
-async fn batch_insert_imports_edges(
+fn boundary_dependency_edge_maps(
+    dependencies: &[metrics::BoundaryDependency],
+    repo_id: &str,
+) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    dependencies
+        .iter()
+        .map(|dependency| {
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert(
+                "id".to_string(),
+                edge_id("DEPENDS_ON_BOUNDARY", &dependency.source, &dependency.target).into(),
+            );
+            m.insert("source".to_string(), dependency.source.clone().into());
+            m.insert("target".to_string(), dependency.target.clone().into());
+            m.insert("weight".to_string(), (dependency.weight as i64).into());
+            m.insert("repo_id".to_string(), repo_id.to_string().into());
+            m
+        })
+        .collect()
+}
+
+/// Replace the repo's DEPENDS_ON_BOUNDARY edges, so pairs that no longer
+/// depend on each other lose theirs
+async fn replace_boundary_dependencies(
     graph_db: &neo4rs::Graph,
     repo_id: &str,
-    dep_graph: &DependencyGraph,
-    batch_size: usize,
+    dependencies: &[metrics::BoundaryDependency],
+    writer: &BatchWriter,
 ) -> Result<()> {
-    let mut edges: Vec<BoltMap> = Vec::new();
-    
-    for edge in &dep_graph.edges {
-        if edge.edge_type != EdgeType::Imports {
-            continue;
-        }
-        
-        if let (NodeId::File(file_path), NodeId::Module(module_name)) = (&edge.from, &edge.to) {
-            let mut m = HashMap::new();
-            m.insert("file_path".to_string(), file_path.to_string());
-            m.insert("module_name".to_string(), module_name.to_string());
-            m.insert("repo_id".to_string(), repo_id.to_string());
-            edges.push(m);
-        }
-    }
-
-    for chunk in edges.chunks(batch_size) {
-        retry_query!(graph_db, {
+    retry_query!(graph_db, {
+        query("MATCH (:Boundary {repo_id: $repo_id})-[r:DEPENDS_ON_BOUNDARY]->() DELETE r")
+            .param("repo_id", repo_id)
+    }).context("Failed to delete DEPENDS_ON_BOUNDARY edges")?;
 
-            query(
+    let edges = boundary_dependency_edge_maps(dependencies, repo_id);
+    writer.write(graph_db, "DEPENDS_ON_BOUNDARY edges", &edges, |chunk| {
+        query(
             "UNWIND $edges AS edge
-             MATCH (f:File {path: edge.file_path, repo_id: edge.repo_id})
-             MATCH (m:Module {name: edge.module_name, repo_id: edge.repo_id})
-             MERGE (f)-[:IMPORTS]->(m)"
+             MATCH (a:Boundary {id: edge.source, repo_id: edge.repo_id})
+             MATCH (b:Boundary {id: edge.target, repo_id: edge.repo_id})
+             MERGE (a)-[r:DEPENDS_ON_BOUNDARY]->(b)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id,
+                 r.weight = edge.weight"
         )
         .param("edges", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert IMPORTS edges")?;
-    }
-    
-    info!("   Created {} IMPORTS edges", edges.len());
+    info!("   Created {} DEPENDS_ON_BOUNDARY edges", edges.len());
     Ok(())
 }
 
-async fn batch_insert_inherits_edges(
+fn layer_violation_node_maps(violations: &[LayerViolation], repo_id: &str) -> Vec<BoltMap> {
+    violations
+        .iter()
+        .map(|violation| {
+            let mut m = HashMap::new();
+            m.insert("from_file".to_string(), violation.from_file.clone());
+            m.insert("to_file".to_string(), violation.to_file.clone());
+            m.insert("from_layer".to_string(), violation.from_layer.to_string());
+            m.insert("to_layer".to_string(), violation.to_layer.to_string());
+            m.insert("repo_id".to_string(), repo_id.to_string());
+            m
+        })
+        .collect()
+}
+
+/// Replace the repo's LayerViolation nodes with `violations`
+async fn replace_layer_violations(
     graph_db: &neo4rs::Graph,
     repo_id: &str,
-    dep_graph: &DependencyGraph,
-    batch_size: usize,
+    violations: &[LayerViolation],
+    writer: &BatchWriter,
 ) -> Result<()> {
-    let mut class_to_class: Vec<BoltMap> = Vec::new();
-    let mut class_to_module: Vec<BoltMap> = Vec::new();
-    
-    for edge in &dep_graph.edges {
-        if edge.edge_type != EdgeType::Inherits {
-            continue;
-        }
-        
-        let inheritance_type = edge
-            .properties
-            .get("kind")
-            .cloned()
-            .unwrap_or_else(|| "class".to_string());
-
-        match (&edge.from, &edge.to) {
-            (NodeId::Class(from_file, from_name), NodeId::Class(to_file, to_name)) => {
-                let from_id = get_qualified_id(from_file, from_name);
-                let to_id = get_qualified_id(to_file, to_name);
-                
-                let mut m = HashMap::new();
-                m.insert("from_id".to_string(), from_id);
-                m.insert("to_id".to_string(), to_id);
-                m.insert("repo_id".to_string(), repo_id.to_string());
-                m.insert("inheritance_type".to_string(), inheritance_type.clone());
-                class_to_class.push(m);
-            }
-            (NodeId::Class(class_file, class_name), NodeId::Module(module_name)) => {
-                let class_id = get_qualified_id(class_file, class_name);
-                
-                let mut m = HashMap::new();
-                m.insert("class_id".to_string(), class_id);
-                m.insert("module_name".to_string(), module_name.to_string());
-                m.insert("repo_id".to_string(), repo_id.to_string());
-                m.insert("inheritance_type".to_string(), inheritance_type.clone());
-                class_to_module.push(m);
-            }
-            _ => {}
-        }
-    }
-
-    // Batch Class->Class INHERITS
-    for chunk in class_to_class.chunks(batch_size) {
-        retry_query!(graph_db, {
+    retry_query!(graph_db, {
+        query("MATCH (v:LayerViolation {repo_id: $repo_id}) DETACH DELETE v")
+            .param("repo_id", repo_id)
+    }).context("Failed to delete LayerViolation nodes")?;
 
-            query(
-            "UNWIND $edges AS edge
-             MATCH (child:Class {id: edge.from_id, repo_id: edge.repo_id})
-             MATCH (parent:Class {id: edge.to_id, repo_id: edge.repo_id})
-               MERGE (child)-[r:INHERITS]->(parent)
-               SET r.type = edge.inheritance_type"
+    let nodes = writer.stamp(layer_violation_node_maps(violations, repo_id));
+    writer.write(graph_db, "LayerViolation nodes", &nodes, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (v:LayerViolation {from_file: node.from_file, to_file: node.to_file, repo_id: node.repo_id})
+             SET v += node.metadata
+             SET v.from_layer = node.from_layer,
+                 v.to_layer = node.to_layer"
         )
-        .param("edges", chunk.to_vec())
+        .param("nodes", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert Class->Class INHERITS")?;
-    }
+    info!("   Inserted {} LayerViolation nodes", nodes.len());
+    Ok(())
+}
 
-    // Batch Class->Module INHERITS (external)
-    for chunk in class_to_module.chunks(batch_size) {
-        retry_query!(graph_db, {
+fn team_ownership_edge_maps(
+    owners: &[codeowners::BoundaryOwner],
+    repo_id: &str,
+) -> Vec<HashMap<String, neo4rs::BoltType>> {
+    owners
+        .iter()
+        .map(|owner| {
+            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+            m.insert("id".to_string(), edge_id("OWNS", &owner.owner, &owner.boundary_id).into());
+            m.insert("team".to_string(), owner.owner.clone().into());
+            m.insert("boundary_id".to_string(), owner.boundary_id.clone().into());
+            m.insert("file_count".to_string(), (owner.file_count as i64).into());
+            m.insert("repo_id".to_string(), repo_id.to_string().into());
+            m
+        })
+        .collect()
+}
 
-            query(
+/// Replace the repo's Team nodes with the owners of most of each
+/// boundary's files, linked by OWNS and set as the boundary's `owner`
+async fn replace_team_ownership(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    boundary_result: &BoundaryDetectionResult,
+    codeowners: Option<&CodeOwners>,
+    writer: &BatchWriter,
+) -> Result<()> {
+    retry_query!(graph_db, {
+        query("MATCH (t:Team {repo_id: $repo_id}) DETACH DELETE t")
+            .param("repo_id", repo_id)
+    }).context("Failed to delete Team nodes")?;
+    retry_query!(graph_db, {
+        query("MATCH (b:Boundary {repo_id: $repo_id}) REMOVE b.owner")
+            .param("repo_id", repo_id)
+    }).context("Failed to clear Boundary owners")?;
+
+    let owners = codeowners.map(|c| codeowners::boundary_owners(boundary_result, c)).unwrap_or_default();
+    let edges = writer.stamp(team_ownership_edge_maps(&owners, repo_id));
+    writer.write(graph_db, "OWNS edges", &edges, |chunk| {
+        query(
             "UNWIND $edges AS edge
-             MATCH (child:Class {id: edge.class_id, repo_id: edge.repo_id})
-             MATCH (parent:Module {name: edge.module_name, repo_id: edge.repo_id})
-               MERGE (child)-[r:INHERITS]->(parent)
-               SET r.type = edge.inheritance_type"
+             MATCH (b:Boundary {id: edge.boundary_id, repo_id: edge.repo_id})
+             MERGE (t:Team {name: edge.team, repo_id: edge.repo_id})
+             SET t += edge.metadata
+             MERGE (t)-[r:OWNS]->(b)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id,
+                 r.file_count = edge.file_count,
+                 b.owner = edge.team"
         )
         .param("edges", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert Class->Module INHERITS")?;
-    }
-    
-    info!("   Created {} INHERITS edges", class_to_class.len() + class_to_module.len());
+    info!("   Created {} OWNS edges", edges.len());
     Ok(())
 }
 
 // ============================================================================
-// Boundary Nodes and Edges
+// Commit History
 // ============================================================================
 
-async fn batch_insert_boundary_nodes(
+async fn batch_insert_commit_nodes(
     graph_db: &neo4rs::Graph,
-    job_id: &str,
     repo_id: &str,
-    boundary_result: &BoundaryDetectionResult,
-    batch_size: usize,
+    commits: &[CommitRecord],
+    writer: &BatchWriter,
 ) -> Result<()> {
-    let nodes: Vec<HashMap<String, neo4rs::BoltType>> = boundary_result.boundaries
-        .iter()
-        .map(|b| {
-            let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
-            m.insert("id".to_string(), b.id.clone().into());
-            m.insert("name".to_string(), b.name.clone().into());
-            m.insert("type".to_string(), b.boundary_type.as_str().to_string().into());
-            m.insert("path".to_string(), b.path.clone().into());
-            m.insert("job_id".to_string(), job_id.to_string().into());
-            m.insert("repo_id".to_string(), repo_id.to_string().into());
-            m.insert("file_count".to_string(), (b.file_count as i64).into());
-            
-            if let Some(layer) = &b.layer {
-                m.insert("layer".to_string(), layer.as_str().to_string().into());
-            }
-            
-            m
-        })
-        .collect();
-
-    for chunk in nodes.chunks(batch_size) {
-        retry_query!(graph_db, {
+    let nodes: Vec<BoltMap> = commits.iter().map(|c| commit_node_to_map(c, repo_id)).collect();
 
-            query(
+    let nodes = writer.stamp(nodes);
+    writer.write(graph_db, "commit nodes", &nodes, |chunk| {
+        query(
             "UNWIND $nodes AS node
-             MERGE (b:Boundary {id: node.id})
-             SET b.name = node.name,
-                 b.type = node.type,
-                 b.path = node.path,
-                 b.job_id = node.job_id,
-                 b.repo_id = node.repo_id,
-                 b.file_count = node.file_count,
-                 b.layer = COALESCE(node.layer, '')"
+             MERGE (c:Commit {sha: node.sha, repo_id: node.repo_id})
+             SET c += node.metadata
+             SET c.author_name = node.author_name,
+                 c.author_email = node.author_email,
+                 c.message = node.message,
+                 c.authored_at = node.authored_at,
+                 c.is_merge = toBoolean(node.is_merge)"
         )
         .param("nodes", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert boundary nodes")?;
-    }
-    
-    info!("   Inserted {} Boundary nodes", nodes.len());
+    info!("   Inserted {} Commit nodes", nodes.len());
     Ok(())
 }
 
-async fn batch_insert_belongs_to_edges(
+async fn batch_insert_touched_edges(
     graph_db: &neo4rs::Graph,
     repo_id: &str,
-    boundary_result: &BoundaryDetectionResult,
-    batch_size: usize,
+    commits: &[CommitRecord],
+    writer: &BatchWriter,
 ) -> Result<()> {
-    let mut edges = Vec::new();
-    
-    for boundary in &boundary_result.boundaries {
-        for file_path in &boundary.files {
-            let mut m = HashMap::new();
-            m.insert("file_id".to_string(), file_path.clone());
-            m.insert("boundary_id".to_string(), boundary.id.clone());
-            m.insert("repo_id".to_string(), repo_id.to_string());
-            edges.push(m);
-        }
-    }
-
-    for chunk in edges.chunks(batch_size) {
-        retry_query!(graph_db, {
+    let edges = touched_edge_maps(commits, repo_id);
 
-            query(
+    writer.write(graph_db, "TOUCHED edges", &edges, |chunk| {
+        query(
             "UNWIND $edges AS edge
-             MATCH (f:File {id: edge.file_id, repo_id: edge.repo_id})
-             MATCH (b:Boundary {id: edge.boundary_id, repo_id: edge.repo_id})
-             MERGE (f)-[:BELONGS_TO]->(b)"
+             MATCH (c:Commit {sha: edge.sha, repo_id: edge.repo_id})
+             MATCH (f:File {id: edge.file, repo_id: edge.repo_id})
+             MERGE (c)-[r:TOUCHED]->(f)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id"
         )
         .param("edges", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert BELONGS_TO edges")?;
-    }
-    
-    info!("   Created {} BELONGS_TO edges", edges.len());
+    info!("   Created up to {} TOUCHED edges", edges.len());
     Ok(())
 }
 
-/// Create file-to-file DEPENDS_ON edges based on import resolution
-async fn batch_insert_file_dependencies(
+async fn batch_insert_contributor_nodes(
     graph_db: &neo4rs::Graph,
     repo_id: &str,
-    parsed_files: &[ParsedFile],
-    batch_size: usize,
+    contributions: &RepoContributions,
+    writer: &BatchWriter,
 ) -> Result<()> {
-    use std::path::Path;
-    use std::collections::HashSet;
-    
-    // Build a map of module names to file paths for resolution
-    let mut module_to_files: HashMap<String, Vec<String>> = HashMap::new();
-    
-    for file in parsed_files {
-        let file_path = Path::new(&file.path);
-        
-        // Extract potential module names from file path
-        // e.g., "src/utils/helper.ts" -> ["utils/helper", "helper"]
-        if let Some(file_stem) = file_path.file_stem() {
-            let stem_str = file_stem.to_string_lossy().to_string();
-            module_to_files.entry(stem_str.clone()).or_default().push(file.path.clone());
-            
-            // Also add parent directory as potential module name
-            if let Some(parent) = file_path.parent() {
-                if let Some(parent_str) = parent.file_name() {
-                    let parent_name = parent_str.to_string_lossy().to_string();
-                    module_to_files.entry(parent_name).or_default().push(file.path.clone());
-                }
-            }
-        }
-    }
-    
-    // Now resolve imports to files
-    let mut edges = Vec::new();
-    let mut resolved_count = 0;
-    
-    for file in parsed_files {
-        for import in &file.imports {
-            // Try to resolve import to a file
-            let mut resolved_files = HashSet::new();
-            
-            // Try exact match
-            if module_to_files.contains_key(import) {
-                resolved_files.extend(module_to_files.get(import).unwrap().clone());
-            }
-            
-            // Try extracting last part of import path (e.g., "./utils/helper" -> "helper",
-            // "./fileA.js" -> "fileA.js" then also "fileA" after extension strip)
-            if let Some(last_part) = import.split('/').last() {
-                let clean_part = last_part.trim_start_matches("./").trim_start_matches("../");
-                // Strip file extension so "fileA.js" matches the stem key "fileA"
-                let stem = clean_part.rfind('.').map(|p| &clean_part[..p]).unwrap_or(clean_part);
-                for key in &[clean_part, stem] {
-                    if module_to_files.contains_key(*key) {
-                        resolved_files.extend(module_to_files.get(*key).unwrap().clone());
-                    }
-                }
-            }
-            
-            // Try partial matches for relative imports
-            if import.starts_with("./") || import.starts_with("../") {
-                let import_parts: Vec<&str> = import.split('/').filter(|p| !p.is_empty() && *p != ".." && *p != ".").collect();
-                if let Some(last_part) = import_parts.last() {
-                    let stem = last_part.rfind('.').map(|p| &last_part[..p]).unwrap_or(last_part);
-                    for key in &[*last_part, stem] {
-                        if module_to_files.contains_key(*key) {
-                            resolved_files.extend(module_to_files.get(*key).unwrap().clone());
-                        }
-                    }
-                }
-            }
-            
-            // Create edges for resolved files (excluding self-imports)
-            for target_file in resolved_files {
-                if target_file != file.path {
-                    let mut m = HashMap::new();
-                    m.insert("source_file".to_string(), file.path.clone());
-                    m.insert("target_file".to_string(), target_file);
-                    m.insert("import_path".to_string(), import.clone());
-                    m.insert("repo_id".to_string(), repo_id.to_string());
-                    edges.push(m);
-                    resolved_count += 1;
-                }
-            }
-        }
-    }
-    
-    // Batch insert edges
-    for chunk in edges.chunks(batch_size) {
-        retry_query!(graph_db, {
+    let nodes = writer.stamp(contributor_node_maps(contributions, repo_id));
+
+    writer.write(graph_db, "contributor nodes", &nodes, |chunk| {
+        query(
+            "UNWIND $nodes AS node
+             MERGE (c:Contributor {email: node.email, repo_id: node.repo_id})
+             SET c += node.metadata
+             SET c.name = node.name"
+        )
+        .param("nodes", chunk.to_vec())
+    }).await?;
+
+    info!("   Inserted {} Contributor nodes", nodes.len());
+    Ok(())
+}
+
+async fn batch_insert_authored_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    contributions: &RepoContributions,
+    writer: &BatchWriter,
+) -> Result<()> {
+    let edges = contributed_to_edge_maps(contributions, repo_id);
+
+    writer.write(graph_db, "CONTRIBUTED_TO edges", &edges, |chunk| {
+        query(
+            "UNWIND $edges AS edge
+             MATCH (c:Contributor {email: edge.email, repo_id: edge.repo_id})
+             MATCH (f:File {id: edge.file, repo_id: edge.repo_id})
+             MERGE (c)-[r:CONTRIBUTED_TO]->(f)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id,
+                 r.commit_count = edge.commit_count,
+                 r.lines_added = edge.lines_added,
+                 r.lines_deleted = edge.lines_deleted"
+        )
+        .param("edges", chunk.to_vec())
+    }).await?;
 
-            query(
+    info!("   Created up to {} CONTRIBUTED_TO edges", edges.len());
+    Ok(())
+}
+
+async fn batch_insert_cochange_edges(
+    graph_db: &neo4rs::Graph,
+    repo_id: &str,
+    pairs: &[CoChangePair],
+    writer: &BatchWriter,
+) -> Result<()> {
+    let edges: Vec<HashMap<String, neo4rs::BoltType>> = pairs
+        .iter()
+        .map(|pair| cochange_edge_to_map(pair, repo_id))
+        .collect();
+
+    writer.write(graph_db, "CO_CHANGES edges", &edges, |chunk| {
+        query(
             "UNWIND $edges AS edge
-             MATCH (source:File {path: edge.source_file, repo_id: edge.repo_id})
-             MATCH (target:File {path: edge.target_file, repo_id: edge.repo_id})
-             MERGE (source)-[d:DEPENDS_ON]->(target)
-             ON CREATE SET d.import_path = edge.import_path"
+             MATCH (a:File {id: edge.from, repo_id: edge.repo_id})
+             MATCH (b:File {id: edge.to, repo_id: edge.repo_id})
+             MERGE (a)-[r:CO_CHANGES]->(b)
+             SET r.id = edge.id,
+                 r.repo_id = edge.repo_id,
+                 r.count = edge.count,
+                 r.confidence = edge.confidence"
         )
         .param("edges", chunk.to_vec())
+    }).await?;
 
-        }).context("Failed to batch insert DEPENDS_ON edges")?;
-    }
-    
-    info!("   Created {} DEPENDS_ON edges ({} imports resolved to files)", edges.len(), resolved_count);
+    info!("   Created up to {} CO_CHANGES edges", edges.len());
     Ok(())
 }
 
@@ -1588,6 +4597,7 @@ async fn batch_insert_file_dependencies(
 mod tests {
     use super::*;
     use crate::parsers::{FunctionInfo, ClassInfo};
+    use crate::parsers::sql_queries::analyze_sql;
 
     #[test]
     fn test_file_node_mapping_includes_repo_id() {
@@ -1634,14 +4644,37 @@ mod tests {
             calls: vec![],
             start_line: 10,
             end_line: 20,
+            complexity: 3,
+            body_hash: String::new(),
+            decorators: Vec::new(),
+            visibility: None,
         };
 
-        let map = function_node_to_map(&func, file, job_id, repo_id);
+        let map = function_node_to_map(&func, None, file, job_id, repo_id);
 
         assert!(map.contains_key("repo_id"));
         assert!(map.contains_key("job_id"));
         assert!(map.contains_key("id"));
         assert!(map.contains_key("name"));
+        assert!(map.contains_key("complexity"));
+        assert_eq!(map["return_type"], neo4rs::BoltType::from("void"));
+    }
+
+    #[test]
+    fn test_function_node_carries_parsed_return_type() {
+        use crate::parsers::{rust_parser::RustParser, LanguageParser};
+
+        let parsed = RustParser::new()
+            .unwrap()
+            .parse_file(&std::path::PathBuf::from("src/lib.rs"), "fn load() -> Result<Config> { todo!() }\nfn run() {}\n")
+            .unwrap();
+        let return_type = |name: &str| {
+            let func = parsed.functions.iter().find(|f| f.name == name).unwrap();
+            function_node_to_map(func, None, &parsed.path, "job-1", "repo-1")["return_type"].clone()
+        };
+
+        assert_eq!(return_type("load"), neo4rs::BoltType::from("Result<Config>"));
+        assert_eq!(return_type("run"), neo4rs::BoltType::from(""));
     }
 
     #[test]
@@ -1649,14 +4682,109 @@ mod tests {
         let job_id = "job-123";
         let repo_id = "repo-456";
         let file = "src/main.rs";
-        let name = "MyClass";
+        let class = ClassInfo {
+            name: "MyClass".to_string(),
+            kind: None,
+            inheritances: vec![],
+            methods: vec![],
+            start_line: 10,
+            end_line: 20,
+            body_hash: "0123456789abcdef".to_string(),
+            decorators: vec!["Entity({ name: \"users\" })".to_string()],
+            visibility: None,
+        };
 
-        let map = class_node_to_map(name, file, 10, 20, job_id, repo_id);
+        let map = class_node_to_map(&class, file, job_id, repo_id);
 
         assert!(map.contains_key("repo_id"));
         assert!(map.contains_key("job_id"));
         assert!(map.contains_key("id"));
+        assert_eq!(map["body_hash"], neo4rs::BoltType::from("0123456789abcdef"));
+        assert_eq!(map["decorators"], neo4rs::BoltType::from(vec!["Entity({ name: \"users\" })".to_string()]));
+        assert!(!map.contains_key("kind"));
+        assert!(!map.contains_key("visibility"));
+
+        let class = ClassInfo { kind: Some("trait".to_string()), visibility: Some("crate".to_string()), ..class };
+        let map = class_node_to_map(&class, file, job_id, repo_id);
+        assert_eq!(map["kind"], neo4rs::BoltType::from("trait"));
+        assert_eq!(map["visibility"], neo4rs::BoltType::from("crate"));
+    }
+    #[test]
+    fn test_job_node_params_record_the_run() {
+        let started_at = DateTime::parse_from_rfc3339("2024-03-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        let finished_at = DateTime::parse_from_rfc3339("2024-03-01T12:02:30Z").unwrap().with_timezone(&Utc);
+        let job = JobRecord {
+            started_at,
+            files_processed: 42,
+            nodes_created: 310,
+            analyzed_commit: Some("4b825dc642cb6eb9a060e54bf8d69288fbee4904".to_string()),
+            library_diff: Some(LibraryDiff {
+                upgraded: vec![crate::dependency_metadata::VersionChange {
+                    name: "react".to_string(),
+                    from: "^17.0.2".to_string(),
+                    to: "^18.2.0".to_string(),
+                }],
+                ..LibraryDiff::default()
+            }),
+        };
+
+        let params = job_node_params("job-1", "repo-1", "incremental", &job, finished_at);
+        assert_eq!(params["id"], neo4rs::BoltType::from("job-1"));
+        assert_eq!(params["repo_id"], neo4rs::BoltType::from("repo-1"));
+        assert_eq!(params["mode"], neo4rs::BoltType::from("incremental"));
+        assert_eq!(params["started_at"], neo4rs::BoltType::from("2024-03-01T12:00:00+00:00"));
+        assert_eq!(params["finished_at"], neo4rs::BoltType::from("2024-03-01T12:02:30+00:00"));
+        assert_eq!(params["files_processed"], neo4rs::BoltType::from(42i64));
+        assert_eq!(params["nodes_created"], neo4rs::BoltType::from(310i64));
+        assert_eq!(params["analyzed_commit"], neo4rs::BoltType::from("4b825dc642cb6eb9a060e54bf8d69288fbee4904"));
+        assert_eq!(params["keep"], neo4rs::BoltType::from(JOB_HISTORY as i64));
+        assert_eq!(params["libraries_added"], neo4rs::BoltType::from("[]"));
+        assert_eq!(
+            params["libraries_upgraded"],
+            neo4rs::BoltType::from(r#"[{"name":"react","from":"^17.0.2","to":"^18.2.0"}]"#)
+        );
+    }
+
+    #[test]
+    fn test_record_job_cypher_links_repository_and_prunes_history() {
+        let cypher = record_job_cypher();
+        // Every parameter the query reads is in the map
+        let params = job_node_params("job-1", "repo-1", "full", &JobRecord {
+            started_at: Utc::now(),
+            files_processed: 0,
+            nodes_created: 0,
+            analyzed_commit: None,
+            library_diff: None,
+        }, Utc::now());
+        for name in [
+            "id",
+            "repo_id",
+            "mode",
+            "started_at",
+            "finished_at",
+            "files_processed",
+            "nodes_created",
+            "analyzed_commit",
+            "libraries_added",
+            "libraries_removed",
+            "libraries_upgraded",
+            "keep",
+            "edge_id",
+        ] {
+            assert!(cypher.contains(&format!("${}", name)), "{} unused", name);
+            assert!(params.contains_key(name), "{} missing", name);
+        }
+        assert_eq!(params.len(), 13);
+        assert_eq!(params["edge_id"], neo4rs::BoltType::from("for_repo:job-1->repo-1"));
+
+        assert!(cypher.contains("MERGE (r:Repository {id: $repo_id})"));
+        assert!(cypher.contains("MERGE (j)-[f:FOR_REPO]->(r)"));
+        // Pruning keeps the newest jobs, the one just stored included
+        let prune = &cypher[cypher.find("MATCH (old:Job").unwrap()..];
+        assert!(prune.contains("ORDER BY coalesce(old.started_at, old.timestamp) DESC SKIP $keep"));
+        assert!(prune.ends_with("DETACH DELETE old"));
     }
+
     #[test]
     fn test_qualified_id_generation() {
         let file = "src/main.rs";
@@ -1666,4 +4794,1357 @@ mod tests {
         
         assert_eq!(get_qualified_id(file, name), expected);
     }
+
+    #[test]
+    fn test_orm_models_map_classes_to_tables() {
+        let models = vec![
+            OrmModel {
+                file_path: "app/models.py".to_string(),
+                class_name: "User".to_string(),
+                table: "users".to_string(),
+                orm: "sqlalchemy".to_string(),
+            },
+            OrmModel {
+                file_path: "prisma/schema.prisma".to_string(),
+                class_name: "Post".to_string(),
+                table: "Post".to_string(),
+                orm: "prisma".to_string(),
+            },
+        ];
+
+        let edges = maps_to_edge_maps(&models, "repo-1");
+        assert_eq!(edges[0]["class_id"], "app/models.py::User");
+        assert_eq!(edges[0]["table_name"], "users");
+        assert_eq!(edges[1]["class_id"], "prisma/schema.prisma::Post");
+
+        let nodes = prisma_model_node_maps(&models, "job-1", "repo-1");
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0]["id"], edges[1]["class_id"]);
+    }
+
+    #[test]
+    fn test_method_ids_do_not_collide_with_functions() {
+        let save = FunctionInfo {
+            name: "save".to_string(),
+            params: vec![],
+            return_type: None,
+            calls: vec![],
+            start_line: 1,
+            end_line: 2,
+            complexity: 1,
+            body_hash: String::new(),
+            decorators: Vec::new(),
+            visibility: None,
+        };
+        let free = function_node_to_map(&save, None, "apps/api.py", "job-1", "repo-1");
+        let method = function_node_to_map(&save, Some("User"), "apps/api.py", "job-1", "repo-1");
+
+        assert_eq!(free["id"], neo4rs::BoltType::from("apps/api.py::save"));
+        assert_eq!(method["id"], neo4rs::BoltType::from("apps/api.py::User::save"));
+        assert_eq!(
+            method["id"],
+            neo4rs::BoltType::from(NodeId::Method("apps/api.py".into(), "User".into(), "save".into()).qualified_id())
+        );
+    }
+
+    fn sql_file() -> ParsedFile {
+        let join = analyze_sql(
+            "SELECT u.name FROM users u JOIN orders o ON o.user_id = u.id WHERE o.total > 10",
+            3,
+        )
+        .unwrap();
+        let insert = analyze_sql("INSERT INTO audit_log (user_id) VALUES (1)", 12).unwrap();
+
+        ParsedFile {
+            path: "src/repo.py".to_string(),
+            language: "python".to_string(),
+            functions: vec![FunctionInfo {
+                name: "load_orders".to_string(),
+                params: vec![],
+                return_type: None,
+                calls: vec![],
+                start_line: 1,
+                end_line: 5,
+                complexity: 1,
+                body_hash: String::new(),
+                decorators: Vec::new(),
+                visibility: None,
+            }],
+            classes: vec![],
+            interfaces: vec![],
+            imports: vec![],
+            data_tables: vec![],
+            service_calls: vec![],
+            sql_queries: vec![join, insert],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: false,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: 0,
+        }
+    }
+
+    #[test]
+    fn test_boundary_total_loc_sums_member_files() {
+        use crate::boundary_detector::{Boundary, BoundaryType};
+
+        let file = |path: &str, line_count: usize| ParsedFile {
+            path: path.to_string(),
+            line_count,
+            ..sql_file()
+        };
+        let boundary = |id: &str, files: &[&str]| Boundary {
+            id: id.to_string(),
+            name: id.to_string(),
+            boundary_type: BoundaryType::Logical,
+            path: id.to_string(),
+            layer: None,
+            file_count: files.len(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+            metrics: None,
+        };
+        let parsed_files = vec![file("api/a.ts", 120), file("api/b.ts", 30), file("db/empty.ts", 0)];
+        let result = BoundaryDetectionResult {
+            boundaries: vec![
+                boundary("api", &["api/a.ts", "api/b.ts"]),
+                // Files that weren't parsed this run add nothing
+                boundary("db", &["db/empty.ts", "db/skipped.ts"]),
+            ],
+            file_to_boundary: HashMap::new(),
+        };
+
+        let totals = boundary_total_loc(&result, &parsed_files);
+        assert_eq!(totals["api"], 150);
+        assert_eq!(totals["db"], 0);
+    }
+
+    #[test]
+    fn test_renamed_node_ids_follow_new_path() {
+        let mut file = sql_file();
+        file.path = "src/orders/repo.py".to_string();
+        file.classes = vec![crate::parsers::ClassInfo {
+            name: "OrderRepo".to_string(),
+            kind: None,
+            inheritances: vec![],
+            methods: vec![FunctionInfo {
+                name: "save".to_string(),
+                params: vec![],
+                return_type: None,
+                calls: vec![],
+                start_line: 8,
+                end_line: 9,
+                complexity: 1,
+                body_hash: String::new(),
+                decorators: Vec::new(),
+                visibility: None,
+            }],
+            start_line: 7,
+            end_line: 9,
+            body_hash: String::new(),
+            decorators: Vec::new(),
+            visibility: None,
+        }];
+        let renames = vec![
+            ("src/repo.py".to_string(), "src/orders/repo.py".to_string()),
+            ("src/gone.py".to_string(), "src/not_parsed.py".to_string()),
+        ];
+
+        let ids: Vec<(String, String)> = renamed_node_ids(&[file], &renames)
+            .into_iter()
+            .map(|m| {
+                assert_eq!(m["file"], "src/orders/repo.py");
+                (m["old_id"].clone(), m["new_id"].clone())
+            })
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                ("src/repo.py::OrderRepo".to_string(), "src/orders/repo.py::OrderRepo".to_string()),
+                ("src/repo.py::load_orders".to_string(), "src/orders/repo.py::load_orders".to_string()),
+                ("src/repo.py::OrderRepo::save".to_string(), "src/orders/repo.py::OrderRepo::save".to_string()),
+            ]
+        );
+
+        let maps = rename_maps(&renames);
+        assert_eq!(maps[0]["from"], "src/repo.py");
+        assert_eq!(maps[0]["to"], "src/orders/repo.py");
+    }
+
+    #[test]
+    fn test_endpoint_nodes_keyed_on_normalized_url() {
+        use crate::communication_detector::EndpointCall;
+
+        let call = |raw: &str| EndpointCall {
+            file_path: "src/client.js".to_string(),
+            url: crate::communication_detector::normalize_endpoint_url(raw),
+            raw_url: raw.to_string(),
+            method: "GET".to_string(),
+            host: Some("api.foo.com".to_string()),
+            port: None,
+            domain: Some("foo.com".to_string()),
+            internal: false,
+        };
+        let analysis = CommunicationAnalysis {
+            endpoints: vec![
+                call("http://api.foo.com/users/1"),
+                call("http://api.foo.com/users/2?full=1"),
+                call("http://api.foo.com/users/"),
+            ],
+            routes: vec![],
+            api_operations: vec![],
+            rpc_services: vec![],
+            proto_services: vec![],
+            rpc_method_calls: vec![],
+            queues: vec![],
+            realtime_connections: vec![],
+            socket_events: vec![],
+            websocket_servers: vec![],
+            compose_services: vec![],
+        };
+
+        let nodes = endpoint_node_maps(&analysis, "repo-456");
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0]["url"], "http://api.foo.com/users/:id");
+        assert_eq!(nodes[0]["example_url"], "http://api.foo.com/users/1");
+        assert_eq!(nodes[0]["path"], "/users/:id");
+        assert_eq!(nodes[0]["domain"], "foo.com");
+        assert_eq!(nodes[0]["internal"], "false");
+        assert_eq!(nodes[1]["url"], "http://api.foo.com/users");
+    }
+
+    #[test]
+    fn test_queue_nodes_carry_technology_and_dynamic_flag() {
+        use crate::communication_detector::QueueUsage;
+
+        let usage = |file: &str, topic: &str, dynamic: bool| QueueUsage {
+            file_path: file.to_string(),
+            topic: topic.to_string(),
+            direction: QueueDirection::Publish,
+            technology: "kafka".to_string(),
+            dynamic,
+        };
+        let analysis = CommunicationAnalysis {
+            endpoints: vec![],
+            routes: vec![],
+            api_operations: vec![],
+            rpc_services: vec![],
+            proto_services: vec![],
+            rpc_method_calls: vec![],
+            queues: vec![
+                usage("a.py", "orders", false),
+                usage("b.py", "orders", true),
+                usage("c.py", "ORDERS_TOPIC", true),
+            ],
+            realtime_connections: vec![],
+            socket_events: vec![],
+            websocket_servers: vec![],
+            compose_services: vec![],
+        };
+
+        let nodes = queue_node_maps(&analysis, "repo-1");
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0]["topic"], neo4rs::BoltType::from("orders"));
+        assert_eq!(nodes[0]["technology"], neo4rs::BoltType::from("kafka"));
+        assert_eq!(nodes[0]["dynamic"], neo4rs::BoltType::from(false));
+        assert_eq!(nodes[1]["dynamic"], neo4rs::BoltType::from(true));
+    }
+
+    #[test]
+    fn test_realtime_nodes_and_edges() {
+        use crate::communication_detector::{RealtimeConnection, SocketEventUsage, WebSocketServer};
+
+        let connection = |file: &str, host: &str, internal: bool, transport: &str| RealtimeConnection {
+            file_path: file.to_string(),
+            url: format!("wss://{}/ws", host),
+            host: host.to_string(),
+            internal,
+            transport: transport.to_string(),
+        };
+        let event = |file: &str, name: &str, direction: SocketEventDirection| SocketEventUsage {
+            file_path: file.to_string(),
+            name: name.to_string(),
+            direction,
+        };
+        let analysis = CommunicationAnalysis {
+            realtime_connections: vec![
+                connection("web/feed.js", "stream.example.com", false, "websocket"),
+                connection("web/feed.js", "stream.example.com", false, "sse"),
+                connection("web/chat.js", "chat", true, "socket.io"),
+            ],
+            socket_events: vec![
+                event("web/chat.js", "message", SocketEventDirection::Emit),
+                event("web/chat.js", "message", SocketEventDirection::Listen),
+                event("server/chat.js", "typing", SocketEventDirection::Emit),
+            ],
+            websocket_servers: vec![WebSocketServer { file_path: "server/chat.js".to_string(), framework: "socket.io".to_string() }],
+            ..Default::default()
+        };
+
+        let channels = websocket_channel_node_maps(&analysis, "repo-1");
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[0]["host"], neo4rs::BoltType::from("stream.example.com"));
+        assert_eq!(channels[1]["internal"], neo4rs::BoltType::from(true));
+
+        let events = socket_event_node_maps(&analysis, "repo-1");
+        let names: Vec<&str> = events.iter().map(|node| node["name"].as_str()).collect();
+        assert_eq!(names, vec!["message", "typing"]);
+
+        let (connects, emits, listens) = realtime_edge_maps(&analysis, "repo-1");
+        assert_eq!(connects.len(), 2);
+        assert_eq!(connects[0]["id"], "connects_to:web/feed.js->stream.example.com");
+        assert_eq!(connects[0]["transport"], "websocket");
+        assert_eq!(emits.len(), 2);
+        assert_eq!(listens[0]["id"], "listens_to:web/chat.js->message");
+
+        let file = |path: &str| ParsedFile { path: path.to_string(), ..sql_file() };
+        let servers = websocket_server_maps(&[file("server/chat.js"), file("web/chat.js")], &analysis, "repo-1");
+        assert_eq!(servers[0]["framework"], neo4rs::BoltType::from("socket.io"));
+        assert_eq!(servers[1]["framework"], neo4rs::BoltType::from(None::<String>));
+    }
+
+    #[test]
+    fn test_connection_errors_are_classified() {
+        let io = anyhow::Error::from(neo4rs::Error::IOError {
+            detail: std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset by peer"),
+        });
+        assert!(is_connection_error(&io.context("Failed to link Endpoints to Routes")));
+        assert!(is_connection_error(&anyhow::Error::from(neo4rs::Error::ConnectionError)));
+        assert!(is_connection_error(&anyhow::Error::from(neo4rs::Error::UnexpectedMessage(
+            "Neo.TransientError.General.DatabaseUnavailable".to_string()
+        ))));
+        assert!(is_connection_error(&anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe))));
+
+        assert!(!is_connection_error(&anyhow::Error::from(neo4rs::Error::UnexpectedMessage(
+            "Neo.ClientError.Statement.SyntaxError".to_string()
+        ))));
+        assert!(!is_connection_error(&anyhow::Error::from(neo4rs::Error::AuthenticationError("bad password".to_string()))));
+        assert!(!is_connection_error(&anyhow::anyhow!(crate::job_control::JobCancelled { stage: WorkerStage::Storing })));
+
+        let skipped = |error: &str| PartialError { label: "file nodes".to_string(), rows: 10, error: error.to_string() };
+        assert!(lost_connection(&Ok(vec![skipped("an IO error occurred: Connection reset by peer")])));
+        assert!(!lost_connection(&Ok(vec![skipped("Neo.ClientError.Schema.ConstraintValidationFailed")])));
+        assert!(!lost_connection(&Ok(vec![])));
+    }
+
+    #[tokio::test]
+    async fn test_storage_is_replayed_on_new_connections() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        // Graphs are connection numbers; the first two connections drop
+        let connections = AtomicU32::new(0);
+        let attempts = std::sync::Mutex::new(Vec::new());
+        let store = |graph: u32| {
+            attempts.lock().unwrap().push(graph);
+            async move {
+                match graph {
+                    0 => Err(anyhow::Error::from(neo4rs::Error::ConnectionError).context("Failed to delete Asset nodes")),
+                    1 => Ok(vec![PartialError {
+                        label: "file nodes".to_string(),
+                        rows: 3,
+                        error: "an IO error occurred: broken pipe".to_string(),
+                    }]),
+                    _ => Ok(vec![]),
+                }
+            }
+        };
+        let reconnect = || async { Ok(connections.fetch_add(1, Ordering::SeqCst) + 1) };
+
+        let (errors, replays) = store_with_replays(0, store, reconnect).await.unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(replays, 2);
+        assert_eq!(*attempts.lock().unwrap(), vec![0, 1, 2]);
+
+        // Replays are capped, and other failures are returned as they are
+        attempts.lock().unwrap().clear();
+        let result = store_with_replays(0, store, || async { Ok(0) }).await;
+        assert!(result.is_err());
+        assert_eq!(attempts.lock().unwrap().len(), 1 + MAX_STORAGE_REPLAYS as usize);
+
+        let rejected = store_with_replays(0, |_| async { Err::<Vec<PartialError>, _>(anyhow::anyhow!("Neo.ClientError.Statement.SyntaxError")) }, || async { Ok(1) }).await;
+        assert!(rejected.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_never_stores() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let options: HashMap<String, String> = [(DRY_RUN_OPTION.to_string(), "true".to_string())].into();
+        assert!(dry_run_requested(Some(&options)));
+        assert!(!dry_run_requested(None));
+        let mode = StorageMode::for_job(true, false, dry_run_requested(Some(&options)));
+        assert_eq!(mode, StorageMode::Skipped);
+        assert_eq!(StorageMode::for_job(false, true, false), StorageMode::Skipped);
+        assert_eq!(StorageMode::for_job(true, false, false), StorageMode::Incremental);
+        assert_eq!(StorageMode::for_job(false, false, false), StorageMode::Full);
+
+        let stores = AtomicU32::new(0);
+        let reconnects = AtomicU32::new(0);
+        let store = |_graph: u32| {
+            stores.fetch_add(1, Ordering::SeqCst);
+            async { Ok(vec![]) }
+        };
+        let reconnect = || {
+            reconnects.fetch_add(1, Ordering::SeqCst);
+            async { Ok(1) }
+        };
+
+        let (errors, replays) = store_for_mode(mode, 0, store, reconnect).await.unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(replays, 0);
+        assert_eq!(stores.load(Ordering::SeqCst), 0);
+        assert_eq!(reconnects.load(Ordering::SeqCst), 0);
+
+        store_for_mode(StorageMode::Full, 0, store, reconnect).await.unwrap();
+        assert_eq!(stores.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_asset_nodes_carry_extension_and_size() {
+        let assets = vec![crate::asset_inventory::AssetFile {
+            path: "web/static/hero.png".to_string(),
+            extension: "png".to_string(),
+            size_bytes: 250_000,
+        }];
+
+        let nodes = asset_node_maps(&assets, "repo-1");
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0]["path"], neo4rs::BoltType::from("web/static/hero.png"));
+        assert_eq!(nodes[0]["extension"], neo4rs::BoltType::from("png"));
+        assert_eq!(nodes[0]["size"], neo4rs::BoltType::from(250_000i64));
+    }
+
+    #[test]
+    fn test_infrastructure_nodes_and_edges() {
+        use crate::infra_scanner::parse_dockerfile;
+
+        let deployment = K8sDeployment {
+            file: "k8s/api.yaml".to_string(),
+            kind: "Deployment".to_string(),
+            name: "api".to_string(),
+            namespace: "shop".to_string(),
+            replicas: None,
+            images: vec!["ghcr.io/acme/api:1.4".to_string()],
+            pod_labels: BTreeMap::from([("app".to_string(), "api".to_string())]),
+        };
+        let service = K8sService {
+            file: "k8s/api.yaml".to_string(),
+            name: "api-http".to_string(),
+            namespace: "shop".to_string(),
+            service_type: "ClusterIP".to_string(),
+            ports: vec!["80:8080".to_string()],
+            selector: deployment.pod_labels.clone(),
+            ingress_hosts: vec![],
+        };
+        let infra = InfraAnalysis {
+            container_images: vec![parse_dockerfile("services/api/Dockerfile", "FROM golang:1.22\nEXPOSE 8080\n")],
+            k8s_deployments: vec![deployment],
+            k8s_services: vec![service],
+        };
+        let compose = vec![ComposeService {
+            name: "api".to_string(),
+            build_context: Some("./services/api".to_string()),
+            ..Default::default()
+        }];
+
+        let images = container_image_node_maps(&infra.container_images, "repo-1");
+        assert_eq!(images[0]["path"], neo4rs::BoltType::from("services/api/Dockerfile"));
+        assert_eq!(images[0]["base_image"], neo4rs::BoltType::from("golang:1.22"));
+        let deployments = k8s_deployment_node_maps(&infra.k8s_deployments, "repo-1");
+        assert!(!deployments[0].contains_key("replicas"));
+        assert_eq!(deployments[0]["labels"], neo4rs::BoltType::from(vec!["app=api".to_string()]));
+        let services = k8s_service_node_maps(&infra.k8s_services, "repo-1");
+        assert_eq!(services[0]["selector"], neo4rs::BoltType::from(vec!["app=api".to_string()]));
+
+        let (runs_image, selects, builds) = infrastructure_edge_maps(&infra, &compose, "repo-1");
+        assert_eq!(runs_image.len(), 1);
+        assert_eq!(runs_image[0]["id"], "runs_image:shop/api->services/api/Dockerfile");
+        assert_eq!(selects[0]["id"], "selects:shop/api-http->shop/api");
+        assert_eq!((selects[0]["service"].as_str(), selects[0]["deployment"].as_str()), ("api-http", "api"));
+        assert_eq!(builds[0]["id"], "builds:api->services/api/Dockerfile");
+    }
+
+    #[test]
+    fn test_api_operations_carry_route_path_and_spec_file() {
+        use crate::communication_detector::ApiOperation;
+
+        let analysis = CommunicationAnalysis {
+            endpoints: vec![],
+            routes: vec![],
+            api_operations: vec![ApiOperation {
+                file_path: "docs/openapi.json".to_string(),
+                method: "GET".to_string(),
+                path: "/invoices/{invoiceId}".to_string(),
+                operation_id: Some("getInvoice".to_string()),
+                summary: None,
+            }],
+            rpc_services: vec![],
+            proto_services: vec![],
+            rpc_method_calls: vec![],
+            queues: vec![],
+            realtime_connections: vec![],
+            socket_events: vec![],
+            websocket_servers: vec![],
+            compose_services: vec![],
+        };
+
+        let operations = api_operation_maps(&analysis, "repo-1");
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0]["path"], neo4rs::BoltType::from("/invoices/{invoiceId}"));
+        assert_eq!(operations[0]["route_path"], neo4rs::BoltType::from("/invoices/:id"));
+        assert_eq!(operations[0]["operation_id"], neo4rs::BoltType::from("getInvoice"));
+        assert_eq!(operations[0]["summary"], neo4rs::BoltType::from(None::<String>));
+        assert_eq!(operations[0]["language"], neo4rs::BoltType::from("json"));
+    }
+
+    #[test]
+    fn test_boundary_reconciliation_lists_current_memberships() {
+        use crate::boundary_detector::{Boundary, BoundaryType};
+
+        let boundary = |id: &str, files: &[&str]| Boundary {
+            id: id.to_string(),
+            name: id.to_string(),
+            boundary_type: BoundaryType::Logical,
+            path: id.to_string(),
+            layer: None,
+            file_count: files.len(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+            metrics: None,
+        };
+        let file = |path: &str| ParsedFile { path: path.to_string(), ..sql_file() };
+        let result = BoundaryDetectionResult {
+            boundaries: vec![
+                boundary("logical_api", &["api/a.ts", "api/b.ts"]),
+                boundary("architectural_presentation", &["api/b.ts"]),
+            ],
+            file_to_boundary: HashMap::new(),
+        };
+
+        let (ids, assignments) =
+            boundary_reconciliation(&result, &[file("api/b.ts"), file("api/a.ts"), file("main.ts")], "repo-456");
+        assert_eq!(ids, vec!["logical_api", "architectural_presentation"]);
+
+        let files: Vec<_> = assignments.iter().map(|a| a["file_id"].clone()).collect();
+        assert_eq!(
+            files,
+            vec![
+                neo4rs::BoltType::from("api/a.ts"),
+                neo4rs::BoltType::from("api/b.ts"),
+                neo4rs::BoltType::from("main.ts"),
+            ]
+        );
+        assert_eq!(assignments[0]["boundary_ids"], neo4rs::BoltType::from(vec!["logical_api".to_string()]));
+        assert_eq!(
+            assignments[1]["boundary_ids"],
+            neo4rs::BoltType::from(vec!["logical_api".to_string(), "architectural_presentation".to_string()])
+        );
+        // A file outside every boundary loses all of its BELONGS_TO edges
+        assert_eq!(assignments[2]["boundary_ids"], neo4rs::BoltType::from(Vec::<String>::new()));
+        assert_eq!(assignments[2]["repo_id"], neo4rs::BoltType::from("repo-456"));
+    }
+
+    #[test]
+    fn test_library_nodes_carry_checked_vulnerabilities() {
+        let dependency = |name: &str, ids: Option<Vec<&str>>| LibraryDependency {
+            name: name.to_string(),
+            version: Some("1.0.0".to_string()),
+            source_file: "package.json".to_string(),
+            vulnerability_ids: ids.map(|ids| ids.into_iter().map(str::to_string).collect()),
+            usage: None,
+        };
+        let nodes = library_node_maps(
+            &[dependency("lodash", Some(vec!["GHSA-1", "GHSA-2"])), dependency("react", None)],
+            "job-123",
+            "repo-456",
+        );
+
+        assert_eq!(nodes[0]["vulnerability_count"], neo4rs::BoltType::from(2i64));
+        assert_eq!(
+            nodes[0]["vulnerability_ids"],
+            neo4rs::BoltType::from(vec!["GHSA-1".to_string(), "GHSA-2".to_string()])
+        );
+        assert!(!nodes[1].contains_key("vulnerability_count"));
+        assert!(!nodes[1].contains_key("vulnerability_ids"));
+        // Usage is only set once counted
+        assert!(!nodes[0].contains_key("used_by_count"));
+
+        let mut unused = dependency("left-pad", None);
+        crate::dependency_metadata::apply_library_usage(std::slice::from_mut(&mut unused), &[]);
+        let nodes = library_node_maps(&[unused], "job-123", "repo-456");
+        assert_eq!(nodes[0]["used_by_count"], neo4rs::BoltType::from(0i64));
+        assert_eq!(nodes[0]["unused"], neo4rs::BoltType::from(true));
+        assert_eq!(nodes[0]["usage_unknown"], neo4rs::BoltType::from(false));
+    }
+
+    #[test]
+    fn test_test_files_get_tests_edges_instead_of_depends_on() {
+        use crate::graph_builder::Edge;
+
+        let file = |path: &str, is_test: bool| ParsedFile { path: path.to_string(), is_test, ..sql_file() };
+        let parsed_files = vec![
+            file("src/cart.ts", false),
+            file("src/price.ts", false),
+            file("src/__tests__/cart.test.ts", true),
+            file("src/__tests__/helpers.ts", true),
+        ];
+        let edge = |from: NodeId, to: NodeId, edge_type: EdgeType| Edge { from, to, edge_type, properties: HashMap::new() };
+        let dep_graph = DependencyGraph {
+            edges: vec![
+                edge(NodeId::File("src/cart.ts".into()), NodeId::File("src/price.ts".into()), EdgeType::DependsOn),
+                edge(NodeId::File("src/__tests__/cart.test.ts".into()), NodeId::File("src/cart.ts".into()), EdgeType::DependsOn),
+                edge(
+                    NodeId::File("src/__tests__/cart.test.ts".into()),
+                    NodeId::File("src/__tests__/helpers.ts".into()),
+                    EdgeType::DependsOn,
+                ),
+                // Called without an import, and called through the import as well
+                edge(
+                    NodeId::Function("src/__tests__/cart.test.ts".into(), "checkTotal".into()),
+                    NodeId::Function("src/price.ts".into(), "price".into()),
+                    EdgeType::Calls,
+                ),
+                edge(
+                    NodeId::Function("src/__tests__/cart.test.ts".into(), "checkTotal".into()),
+                    NodeId::Method("src/cart.ts".into(), "Cart".into(), "total".into()),
+                    EdgeType::Calls,
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let (depends_on, tests) = file_dependency_edge_maps(&dep_graph, &parsed_files, "repo-1");
+        let pairs = |edges: &[BoltMap]| -> Vec<(String, String)> {
+            edges.iter().map(|e| (e["source_file"].clone(), e["target_file"].clone())).collect()
+        };
+        assert_eq!(
+            pairs(&depends_on),
+            vec![
+                ("src/cart.ts".to_string(), "src/price.ts".to_string()),
+                ("src/__tests__/cart.test.ts".to_string(), "src/__tests__/helpers.ts".to_string()),
+            ]
+        );
+        assert_eq!(
+            pairs(&tests),
+            vec![
+                ("src/__tests__/cart.test.ts".to_string(), "src/cart.ts".to_string()),
+                ("src/__tests__/cart.test.ts".to_string(), "src/price.ts".to_string()),
+            ]
+        );
+        assert_eq!(depends_on[0]["id"], "depends_on:src/cart.ts->src/price.ts");
+        assert_eq!(tests[0]["id"], "tests:src/__tests__/cart.test.ts->src/cart.ts");
+    }
+
+    #[test]
+    fn test_uses_component_edges_carry_component_names() {
+        let mut properties = HashMap::new();
+        properties.insert("components".to_string(), "Button,IconButton".to_string());
+        let dep_graph = DependencyGraph {
+            edges: vec![crate::graph_builder::Edge {
+                from: NodeId::File("src/App.tsx".into()),
+                to: NodeId::File("src/components/Button.tsx".into()),
+                edge_type: EdgeType::UsesComponent,
+                properties,
+            }],
+            ..Default::default()
+        };
+
+        let edges = uses_component_edge_maps(&dep_graph, "repo-1");
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0]["source_file"], "src/App.tsx");
+        assert_eq!(edges[0]["target_file"], "src/components/Button.tsx");
+        assert_eq!(edges[0]["components"], "Button,IconButton");
+    }
+
+    #[test]
+    fn test_env_vars_link_files_and_compose_services() {
+        use crate::communication_detector::ComposeService;
+
+        let reader = |path: &str, env_vars: &[&str]| ParsedFile {
+            path: path.to_string(),
+            env_vars: env_vars.iter().map(|v| v.to_string()).collect(),
+            ..sql_file()
+        };
+        let parsed_files = vec![
+            reader("api/db.py", &["DATABASE_URL", "DEBUG"]),
+            reader("api/cache.py", &["DATABASE_URL"]),
+        ];
+        let analysis = CommunicationAnalysis {
+            endpoints: vec![],
+            routes: vec![],
+            api_operations: vec![],
+            rpc_services: vec![],
+            proto_services: vec![],
+            rpc_method_calls: vec![],
+            queues: vec![],
+            realtime_connections: vec![],
+            socket_events: vec![],
+            websocket_servers: vec![],
+            compose_services: vec![ComposeService {
+                name: "api".to_string(),
+                environment: vec!["DATABASE_URL".to_string(), "LOG_LEVEL".to_string()],
+                ..Default::default()
+            }],
+        };
+
+        let nodes = env_var_node_maps(&parsed_files, &analysis, "repo-456");
+        let names: Vec<&str> = nodes.iter().map(|n| n["name"].as_str()).collect();
+        assert_eq!(names, vec!["DATABASE_URL", "DEBUG", "LOG_LEVEL"]);
+
+        let reads = reads_env_edge_maps(&parsed_files, "repo-456");
+        assert_eq!(reads.len(), 3);
+        assert_eq!(reads[2]["file_path"], "api/cache.py");
+        assert_eq!(reads[2]["name"], "DATABASE_URL");
+        assert_eq!(reads[2]["id"], "reads_env:api/cache.py->DATABASE_URL");
+
+        let sets = sets_env_edge_maps(&analysis, "repo-456");
+        assert_eq!(sets.len(), 2);
+        assert!(sets.iter().all(|e| e["service_name"] == "api" && e["repo_id"] == "repo-456"));
+        assert_eq!(sets[0]["name"], "DATABASE_URL");
+        assert_eq!(sets[0]["id"], "sets_env:api->DATABASE_URL");
+    }
+
+    #[test]
+    fn test_ddl_tables_carry_columns_and_defining_files() {
+        use crate::ddl_scanner::DdlColumn;
+
+        let column = |name: &str, data_type: &str| DdlColumn { name: name.to_string(), data_type: data_type.to_string() };
+        let tables = vec![DdlTable {
+            name: "users".to_string(),
+            columns: vec![column("id", "SERIAL"), column("email", "TEXT")],
+            files: vec!["migrations/0001_init.sql".to_string(), "migrations/0002_email.sql".to_string()],
+        }];
+
+        let nodes = ddl_table_node_maps(&tables, "repo-456");
+        assert_eq!(nodes[0]["name"], neo4rs::BoltType::from("users"));
+        assert_eq!(nodes[0]["columns"], neo4rs::BoltType::from(vec!["id".to_string(), "email".to_string()]));
+        assert_eq!(
+            nodes[0]["column_types"],
+            neo4rs::BoltType::from(vec!["SERIAL".to_string(), "TEXT".to_string()])
+        );
+
+        let edges = defines_table_edge_maps(&tables, "repo-456");
+        let files: Vec<&str> = edges.iter().map(|e| e["file_path"].as_str()).collect();
+        assert_eq!(files, vec!["migrations/0001_init.sql", "migrations/0002_email.sql"]);
+        assert!(edges.iter().all(|e| e["table_name"] == "users"));
+    }
+
+    #[test]
+    fn test_package_maps_link_parents_and_files() {
+        let packages = crate::graph_builder::build_package_hierarchy(
+            ["src/lib.rs", "src/auth/mod.rs", "src/auth/token.rs"],
+            |_| false,
+        );
+
+        let nodes = package_node_maps(&packages, "repo-456");
+        let parents: Vec<(&str, &str)> = nodes.iter().map(|n| (n["path"].as_str(), n["parent"].as_str())).collect();
+        assert_eq!(parents, vec![("src", ""), ("src/auth", "src")]);
+        assert_eq!(nodes[1]["name"], "auth");
+
+        let edges = contains_file_edge_maps(&packages, "repo-456");
+        let contained: Vec<(&str, &str)> =
+            edges.iter().map(|e| (e["package_path"].as_str(), e["file_path"].as_str())).collect();
+        assert_eq!(
+            contained,
+            vec![("src", "src/lib.rs"), ("src/auth", "src/auth/mod.rs"), ("src/auth", "src/auth/token.rs")]
+        );
+    }
+
+    #[test]
+    fn test_route_nodes_dedupe_by_method_and_path() {
+        use crate::communication_detector::RouteDefinition;
+
+        let route = |file: &str, method: &str| RouteDefinition {
+            file_path: file.to_string(),
+            method: method.to_string(),
+            path: "/charge/:id".to_string(),
+            framework: "express".to_string(),
+        };
+        let analysis = CommunicationAnalysis {
+            endpoints: vec![],
+            routes: vec![route("src/a.js", "POST"), route("src/b.js", "POST"), route("src/a.js", "GET")],
+            api_operations: vec![],
+            rpc_services: vec![],
+            proto_services: vec![],
+            rpc_method_calls: vec![],
+            queues: vec![],
+            realtime_connections: vec![],
+            socket_events: vec![],
+            websocket_servers: vec![],
+            compose_services: vec![],
+        };
+
+        let nodes = route_node_maps(&analysis, "repo-456");
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0]["method"], "POST");
+        assert_eq!(nodes[1]["method"], "GET");
+        assert_eq!(nodes[1]["path"], "/charge/:id");
+        assert_eq!(nodes[1]["repo_id"], "repo-456");
+    }
+
+    #[test]
+    fn test_query_node_mapping() {
+        let file = sql_file();
+        let map = query_node_to_map(&file.sql_queries[0], "job-123", "repo-456");
+
+        assert_eq!(map.get("id"), Some(&file.sql_queries[0].hash));
+        assert_eq!(map.get("statement_type"), Some(&"SELECT".to_string()));
+        assert_eq!(map.get("repo_id"), Some(&"repo-456".to_string()));
+        assert_eq!(
+            map.get("text"),
+            Some(&"SELECT u.name FROM users u JOIN orders o ON o.user_id = u.id WHERE o.total > ?".to_string())
+        );
+    }
+
+    #[test]
+    fn test_query_edges_classify_reads_and_writes() {
+        let file = sql_file();
+        let batches = collect_query_edges(std::slice::from_ref(&file), "repo-456", &HashSet::new());
+
+        // Every query is linked to its file; only the join sits inside a function
+        assert_eq!(batches.file_executes.len(), 2);
+        assert_eq!(batches.function_executes.len(), 1);
+        assert_eq!(
+            batches.function_executes[0].get("func_id"),
+            Some(&"src/repo.py::load_orders".to_string())
+        );
+
+        let read_tables: Vec<&String> = batches.reads.iter().filter_map(|m| m.get("table_name")).collect();
+        let write_tables: Vec<&String> = batches.writes.iter().filter_map(|m| m.get("table_name")).collect();
+        assert_eq!(read_tables, vec!["users", "orders"]);
+        assert_eq!(write_tables, vec!["audit_log"]);
+        assert_eq!(batches.writes[0].get("query_id"), Some(&file.sql_queries[1].hash));
+    }
+
+    fn commit(sha: &str, changed_files: &[&str]) -> CommitRecord {
+        CommitRecord {
+            sha: sha.to_string(),
+            author_name: "Ada".to_string(),
+            author_email: "ada@example.com".to_string(),
+            message: "Fix login\n".to_string(),
+            authored_at: chrono::DateTime::parse_from_rfc3339("2024-03-01T12:00:00Z")
+                .unwrap()
+                .with_timezone(&chrono::Utc),
+            changed_files: changed_files.iter().map(|f| f.to_string()).collect(),
+            files_changed_count: changed_files.len(),
+            is_merge: false,
+        }
+    }
+
+    #[test]
+    fn test_commit_node_mapping() {
+        let map = commit_node_to_map(&commit("abc123", &["src/auth.py"]), "repo-456");
+
+        assert_eq!(map.get("sha"), Some(&"abc123".to_string()));
+        assert_eq!(map.get("author_email"), Some(&"ada@example.com".to_string()));
+        assert_eq!(map.get("message"), Some(&"Fix login".to_string()));
+        assert_eq!(map.get("authored_at"), Some(&"2024-03-01T12:00:00+00:00".to_string()));
+        assert_eq!(map.get("repo_id"), Some(&"repo-456".to_string()));
+    }
+
+    #[test]
+    fn test_touched_edges_one_per_changed_file() {
+        let commits = vec![
+            commit("abc123", &["src/auth.py", "README.md"]),
+            commit("def456", &["src/auth.py"]),
+        ];
+
+        let edges = touched_edge_maps(&commits, "repo-456");
+
+        let pairs: Vec<(&str, &str)> = edges
+            .iter()
+            .map(|e| (e["sha"].as_str(), e["file"].as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![("abc123", "src/auth.py"), ("abc123", "README.md"), ("def456", "src/auth.py")]
+        );
+        assert!(edges.iter().all(|e| e.get("repo_id") == Some(&"repo-456".to_string())));
+    }
+
+    #[test]
+    fn test_cochange_edge_keys() {
+        let pair = CoChangePair {
+            file_a: "api.py".to_string(),
+            file_b: "models.py".to_string(),
+            count: 3,
+            confidence: 0.75,
+        };
+
+        let map = cochange_edge_to_map(&pair, "repo-456");
+
+        for key in ["id", "from", "to", "count", "confidence", "repo_id"] {
+            assert!(map.contains_key(key), "missing {}", key);
+        }
+        assert_eq!(map["id"], neo4rs::BoltType::from("co_changes:api.py->models.py"));
+    }
+
+    fn contributions() -> RepoContributions {
+        use crate::git_analyzer::{ContributorInfo, FileContribution};
+
+        let author = |email: &str, name: &str, commits: usize, added: usize| ContributorInfo {
+            email: email.to_string(),
+            name: name.to_string(),
+            commit_count: commits,
+            lines_added: added,
+            lines_deleted: 1,
+        };
+        let file = |path: &str, contributors: Vec<ContributorInfo>| FileContribution {
+            file_path: path.to_string(),
+            commit_count: contributors.iter().map(|c| c.commit_count).sum(),
+            last_modified: chrono::Utc::now(),
+            primary_author: contributors[0].email.clone(),
+            lines_added_total: 0,
+            lines_deleted_total: 0,
+            lines_changed_total: 0,
+            contributors,
+        };
+        let mut files = HashMap::new();
+        files.insert("api.py".to_string(), file("api.py", vec![author("ann@x.io", "Ann", 4, 40), author("bob@x.io", "Bob", 1, 3)]));
+        files.insert("db.py".to_string(), file("db.py", vec![author("bob@x.io", "Bob", 2, 9)]));
+        // Renamed-away history can name an author the repo-wide list lacks
+        files.insert("old.py".to_string(), file("old.py", vec![author("cy@x.io", "Cy", 1, 5)]));
+
+        RepoContributions {
+            files,
+            total_commits: 6,
+            total_contributors: 2,
+            contributors: vec![author("ann@x.io", "Ann", 4, 40), author("bob@x.io", "Robert", 3, 12)],
+            commits: vec![],
+            shallow: false,
+            cochange: vec![],
+        }
+    }
+
+    #[test]
+    fn test_contributor_nodes_one_per_author() {
+        let nodes = contributor_node_maps(&contributions(), "repo-1");
+        let authors: Vec<(&str, &str)> = nodes.iter().map(|n| (n["email"].as_str(), n["name"].as_str())).collect();
+        // The repo-wide name wins over a per-file one
+        assert_eq!(authors, vec![("ann@x.io", "Ann"), ("bob@x.io", "Robert"), ("cy@x.io", "Cy")]);
+        assert!(nodes.iter().all(|n| n["repo_id"] == "repo-1"));
+    }
+
+    #[test]
+    fn test_contributed_to_edges_carry_per_file_stats() {
+        let mut edges = contributed_to_edge_maps(&contributions(), "repo-1");
+        assert_eq!(edges.len(), 4);
+        edges.retain(|e| e["email"] == neo4rs::BoltType::from("bob@x.io"));
+        edges.sort_by_key(|e| e["file"] == neo4rs::BoltType::from("db.py"));
+        assert_eq!(edges[0]["file"], neo4rs::BoltType::from("api.py"));
+        assert_eq!(edges[0]["commit_count"], neo4rs::BoltType::from(1_i64));
+        assert_eq!(edges[1]["file"], neo4rs::BoltType::from("db.py"));
+        assert_eq!(edges[1]["lines_added"], neo4rs::BoltType::from(9_i64));
+        for key in ["lines_deleted", "repo_id"] {
+            assert!(edges[1].contains_key(key), "missing {}", key);
+        }
+    }
+
+    #[test]
+    fn test_existing_ids_cypher() {
+        let (nodes, edges) = existing_ids_cypher();
+        assert_eq!(nodes.matches(" UNION ").count(), 2);
+        for owner in ["(n:File {path: path", "(n:Class {file: path", "(n:Function {file: path"] {
+            assert!(nodes.contains(owner), "missing {}", owner);
+            assert!(edges.contains(owner), "missing {}", owner);
+        }
+        assert!(edges.contains("-[r:DEFINES|CONTAINS|CALLS|IMPORTS|INHERITS|DEPENDS_ON|USES_COMPONENT]-(other)"));
+        assert!(edges.contains("toLower(type(r)) + ':' + coalesce(a.id, a.name) + '->' + coalesce(b.id, b.name)"));
+        assert_eq!(nodes.matches("RETURN n.id AS id, n.body_hash AS body_hash").count(), 3);
+    }
+
+    #[test]
+    fn test_surviving_symbols_keep_their_nodes() {
+        let mut file = sql_file();
+        file.classes = vec![ClassInfo {
+            name: "Repo".to_string(),
+            kind: None,
+            inheritances: vec![],
+            methods: file.functions.clone(),
+            start_line: 1,
+            end_line: 9,
+            body_hash: String::new(),
+            decorators: Vec::new(),
+            visibility: None,
+        }];
+        assert_eq!(
+            symbol_ids(&[file]),
+            vec![
+                "src/repo.py::load_orders".to_string(),
+                "src/repo.py::Repo".to_string(),
+                "src/repo.py::Repo::load_orders".to_string()
+            ]
+        );
+
+        let (stale, outgoing) = symbol_cleanup_cypher("Function");
+        assert!(stale.contains("MATCH (n:Function {file: path, repo_id: $repo_id})"));
+        assert!(stale.contains("WHERE NOT n.id IN $keep"));
+        assert!(stale.contains("DETACH DELETE n"));
+        assert!(outgoing.contains("-[r:CALLS|CONTAINS|INHERITS|EXECUTES_QUERY|MAPS_TO]->()"));
+        assert!(!outgoing.contains("DETACH"));
+    }
+
+    #[test]
+    fn test_calls_edges_carry_count() {
+        let caller = NodeId::Function("jobs.py".to_string(), "run".to_string());
+        let callee = NodeId::Method("jobs.py".to_string(), "Store".to_string(), "save".to_string());
+        let mut dep_graph = DependencyGraph::default();
+        let mut properties = HashMap::new();
+        properties.insert("count".to_string(), "4".to_string());
+        properties.insert("resolution".to_string(), "ambiguous".to_string());
+        dep_graph.edges.push(crate::graph_builder::Edge { from: caller, to: callee, edge_type: EdgeType::Calls, properties });
+
+        let edges = calls_edge_maps(&dep_graph, "repo-1");
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0]["from_id"], neo4rs::BoltType::from("jobs.py::run"));
+        assert_eq!(edges[0]["to_id"], neo4rs::BoltType::from("jobs.py::Store::save"));
+        assert_eq!(edges[0]["count"], neo4rs::BoltType::from(4_i64));
+        assert_eq!(edges[0]["resolution"], neo4rs::BoltType::from("ambiguous"));
+        assert_eq!(edges[0]["id"], neo4rs::BoltType::from("calls:jobs.py::run->jobs.py::Store::save"));
+    }
+
+    #[test]
+    fn test_boundary_dependency_edge_maps() {
+        let dependencies = vec![metrics::BoundaryDependency {
+            source: "boundary-api".to_string(),
+            target: "boundary-core".to_string(),
+            weight: 3,
+        }];
+        let edges = boundary_dependency_edge_maps(&dependencies, "repo-1");
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0]["source"], neo4rs::BoltType::from("boundary-api"));
+        assert_eq!(edges[0]["target"], neo4rs::BoltType::from("boundary-core"));
+        assert_eq!(edges[0]["weight"], neo4rs::BoltType::from(3i64));
+        assert_eq!(edges[0]["id"], neo4rs::BoltType::from("depends_on_boundary:boundary-api->boundary-core"));
+        assert_eq!(edges[0]["repo_id"], neo4rs::BoltType::from("repo-1"));
+    }
+
+    #[test]
+    fn test_layer_violation_node_maps() {
+        let violations = vec![LayerViolation {
+            from_file: "db/invoices.ts".to_string(),
+            to_file: "web/page.tsx".to_string(),
+            from_layer: "data_access",
+            to_layer: "presentation",
+        }];
+        let nodes = layer_violation_node_maps(&violations, "repo-1");
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0]["from_file"], "db/invoices.ts");
+        assert_eq!(nodes[0]["to_file"], "web/page.tsx");
+        assert_eq!(nodes[0]["from_layer"], "data_access");
+        assert_eq!(nodes[0]["to_layer"], "presentation");
+        assert_eq!(nodes[0]["repo_id"], "repo-1");
+    }
+
+    #[test]
+    fn test_team_ownership_edge_maps() {
+        let owners = vec![codeowners::BoundaryOwner {
+            boundary_id: "boundary-billing".to_string(),
+            owner: "@acme/billing".to_string(),
+            file_count: 12,
+        }];
+        let edges = team_ownership_edge_maps(&owners, "repo-1");
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0]["team"], neo4rs::BoltType::from("@acme/billing"));
+        assert_eq!(edges[0]["boundary_id"], neo4rs::BoltType::from("boundary-billing"));
+        assert_eq!(edges[0]["id"], neo4rs::BoltType::from("owns:@acme/billing->boundary-billing"));
+        assert_eq!(edges[0]["file_count"], neo4rs::BoltType::from(12i64));
+        assert_eq!(edges[0]["repo_id"], neo4rs::BoltType::from("repo-1"));
+    }
+
+    #[test]
+    fn test_rpc_method_maps() {
+        let services = vec![
+            ProtoService {
+                name: "UserService".to_string(),
+                methods: vec!["GetUser".to_string(), "ListUsers".to_string()],
+                file_path: "proto/user.proto".to_string(),
+            },
+            // The same service declared again, e.g. in a vendored copy
+            ProtoService {
+                name: "UserService".to_string(),
+                methods: vec!["GetUser".to_string()],
+                file_path: "vendor/user.proto".to_string(),
+            },
+        ];
+        let nodes = rpc_method_node_maps(&services, "repo-1");
+        let keys: Vec<(&str, &str, &str)> =
+            nodes.iter().map(|n| (n["service"].as_str(), n["name"].as_str(), n["file_path"].as_str())).collect();
+        assert_eq!(
+            keys,
+            vec![("UserService", "GetUser", "proto/user.proto"), ("UserService", "ListUsers", "proto/user.proto")]
+        );
+
+        let calls = vec![RpcMethodCall {
+            file_path: "cmd/client.go".to_string(),
+            service_name: "UserService".to_string(),
+            method: "GetUser".to_string(),
+        }];
+        let edges = rpc_method_call_edge_maps(&calls, "repo-1");
+        assert_eq!(edges[0]["file_path"], "cmd/client.go");
+        assert_eq!(edges[0]["service"], "UserService");
+        assert_eq!(edges[0]["method"], "GetUser");
+        assert_eq!(edges[0]["repo_id"], "repo-1");
+    }
+
+    #[test]
+    fn test_cycle_nodes_and_edges() {
+        let cycles = vec![
+            vec!["a.ts".to_string(), "b.ts".to_string(), "c.ts".to_string()],
+            vec!["x.ts".to_string(), "y.ts".to_string()],
+        ];
+
+        let nodes = cycle_node_maps(&cycles, "repo-1");
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0]["id"], neo4rs::BoltType::from("cycle:a.ts"));
+        assert_eq!(nodes[0]["size"], neo4rs::BoltType::from(3_i64));
+        assert_eq!(nodes[1]["id"], neo4rs::BoltType::from("cycle:x.ts"));
+
+        let edges = part_of_cycle_edge_maps(&cycles, "repo-1");
+        assert_eq!(edges.len(), 5);
+        assert_eq!(edges[4]["file_path"], "y.ts");
+        assert_eq!(edges[4]["cycle_id"], "cycle:x.ts");
+        assert_eq!(edges[4]["repo_id"], "repo-1");
+    }
+
+    #[test]
+    fn test_adaptive_chunks_split_oversized_batches() {
+        let row = |id: usize, size: usize| {
+            let mut m = BoltMap::new();
+            m.insert("id".to_string(), id.to_string());
+            m.insert("params".to_string(), "x".repeat(size));
+            m
+        };
+
+        // Small rows are only chunked by count
+        let small: Vec<BoltMap> = (0..10).map(|i| row(i, 10)).collect();
+        let sizes: Vec<usize> = adaptive_chunks(&small, 4, 10_000).iter().map(|c| c.len()).collect();
+        assert_eq!(sizes, vec![4, 4, 2]);
+
+        // ~1 KB rows under a 2.5 KB budget: each 8-row chunk halves down to pairs
+        let large: Vec<BoltMap> = (0..12).map(|i| row(i, 1000)).collect();
+        let chunks = adaptive_chunks(&large, 8, 2500);
+        let sizes: Vec<usize> = chunks.iter().map(|c| c.len()).collect();
+        assert_eq!(sizes, vec![2, 2, 2, 2, 2, 2]);
+        let ids: Vec<&str> = chunks.iter().flat_map(|c| c.iter()).map(|m| m["id"].as_str()).collect();
+        let expected: Vec<String> = (0..12).map(|i| i.to_string()).collect();
+        assert_eq!(ids, expected);
+
+        // A row over budget by itself still goes out alone
+        let mixed = vec![row(0, 10), row(1, 50_000), row(2, 10)];
+        let sizes: Vec<usize> = adaptive_chunks(&mixed, 3, 1000).iter().map(|c| c.len()).collect();
+        assert_eq!(sizes, vec![1, 1, 1]);
+
+        assert!(adaptive_chunks::<BoltMap>(&[], 4, 1000).is_empty());
+    }
+
+    /// Keeps every fraction it is told
+    #[derive(Default)]
+    struct RecordingReporter {
+        fractions: std::sync::Mutex<Vec<f64>>,
+    }
+
+    impl ProgressReporter for RecordingReporter {
+        fn report(&self, fraction: f64) {
+            self.fractions.lock().unwrap().push(fraction);
+        }
+    }
+
+    #[test]
+    fn test_writer_reports_progress_per_group() {
+        let reporter = std::sync::Arc::new(RecordingReporter::default());
+        let writer = BatchWriter::new(BatchConfig {
+            progress: Some(reporter.clone()),
+            ..BatchConfig::default()
+        });
+        for _ in 0..STORAGE_GROUPS + 1 {
+            writer.group_done();
+        }
+
+        let fractions = reporter.fractions.lock().unwrap();
+        assert_eq!(fractions.len(), STORAGE_GROUPS + 1);
+        assert!(fractions[0] > 0.0);
+        assert!(fractions[..STORAGE_GROUPS].windows(2).all(|w| w[0] < w[1]), "{:?}", fractions);
+        // An extra group never pushes past done
+        assert_eq!(fractions[STORAGE_GROUPS - 1], 1.0);
+        assert_eq!(fractions[STORAGE_GROUPS], 1.0);
+
+        // Without a reporter, groups are just counted
+        BatchWriter::new(BatchConfig::default()).group_done();
+    }
+
+    #[test]
+    fn test_bolt_row_size_counts_nested_values() {
+        let mut m: HashMap<String, neo4rs::BoltType> = HashMap::new();
+        m.insert("name".to_string(), "a".repeat(100).into());
+        m.insert("params".to_string(), vec!["b".repeat(50), "c".repeat(50)].into());
+        let bytes = m.estimated_bytes();
+        assert!((200..260).contains(&bytes), "estimated {}", bytes);
+    }
+
+    #[test]
+    fn test_schema_statements() {
+        let statements = schema_statements();
+        assert_eq!(statements.len(), MERGE_KEYS.len() + LOOKUP_INDEXES.len());
+        assert!(statements.contains(
+            &"CREATE CONSTRAINT file_id IF NOT EXISTS FOR (n:File) REQUIRE n.id IS UNIQUE".to_string()
+        ));
+        assert!(statements.contains(
+            &"CREATE INDEX library_name_repo_id IF NOT EXISTS FOR (n:Library) ON (n.name, n.repo_id)".to_string()
+        ));
+        assert!(statements.contains(
+            &"CREATE INDEX file_path_repo_id IF NOT EXISTS FOR (n:File) ON (n.path, n.repo_id)".to_string()
+        ));
+    }
+
+    #[test]
+    fn test_schema_covers_every_merge_key() {
+        let source = include_str!("neo4j_storage.rs");
+        // Queries built with format! double their braces
+        let merge = regex::Regex::new(r"MERGE \(\w+:(\w+) \{\{?([^}]*)\}\}?\)").unwrap();
+        let mut seen = 0;
+        for cap in merge.captures_iter(source) {
+            let label = &cap[1];
+            let keys: Vec<&str> = cap[2].split(',').filter_map(|kv| kv.split(':').next()).map(str::trim).collect();
+            assert!(
+                MERGE_KEYS.iter().any(|(l, props)| *l == label && *props == keys.as_slice()),
+                "no schema entry for MERGE on {}{:?}",
+                label,
+                keys
+            );
+            seen += 1;
+        }
+        assert!(seen >= MERGE_KEYS.len());
+    }
+
+    #[test]
+    fn test_every_merged_node_sets_metadata() {
+        let source = include_str!("neo4j_storage.rs");
+        let source = source.split("#[cfg(test)]").next().unwrap();
+        let merge = regex::Regex::new(r"MERGE \((\w+):(\w+) \{").unwrap();
+        let mut seen = 0;
+        for cap in merge.captures_iter(source) {
+            // Cypher strings hold no double quotes, so the query ends at the next one
+            let rest = &source[cap.get(0).unwrap().end()..];
+            let query = &rest[..rest.find('"').unwrap()];
+            assert!(query.contains(&format!("{} += ", &cap[1])), "{} is merged without its metadata", &cap[0]);
+            seen += 1;
+        }
+        assert!(seen > 30, "{}", seen);
+    }
+
+    #[test]
+    fn test_node_maps_carry_node_metadata() {
+        let writer = BatchWriter::new(BatchConfig {
+            node_metadata: NodeMetadata::parse(r#"{"tenant_id": "acme", "org": "payments"}"#).unwrap(),
+            ..BatchConfig::default()
+        });
+        let expected: HashMap<String, String> =
+            [("tenant_id".to_string(), "acme".to_string()), ("org".to_string(), "payments".to_string())].into();
+        let expected = neo4rs::BoltType::from(expected);
+
+        let file = sql_file();
+        let functions = vec![function_node_to_map(&file.functions[0], None, &file.path, "job-1", "repo-1")];
+        let libraries = library_node_maps(
+            &[LibraryDependency {
+                name: "express".to_string(),
+                version: Some("4.18.2".to_string()),
+                source_file: "package.json".to_string(),
+                vulnerability_ids: None,
+                usage: None,
+            }],
+            "job-1",
+            "repo-1",
+        );
+        let modules = vec![module_node_to_map("lodash", "job-1", "repo-1")];
+
+        for (label, nodes) in
+            [("function", writer.stamp(functions)), ("library", writer.stamp(libraries)), ("module", writer.stamp(modules))]
+        {
+            assert!(!nodes.is_empty(), "{}", label);
+            for node in &nodes {
+                assert_eq!(node["metadata"], expected, "{}", label);
+                assert_eq!(node["repo_id"], neo4rs::BoltType::from("repo-1"), "{}", label);
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_merged_relationship_sets_id_and_repo_id() {
+        let source = include_str!("neo4j_storage.rs");
+        let source = source.split("#[cfg(test)]").next().unwrap();
+        let merge = regex::Regex::new(r"MERGE \(\w*\)-\[(\w*):(\w+)\]->\(\w*\)").unwrap();
+        let set_id =
+            regex::Regex::new(r"\A\s*(?:ON CREATE SET [^\n]*\s*)?SET (\w+)\.id = [^\n]*\s*(\w+)\.repo_id = ").unwrap();
+        let mut seen = 0;
+        for cap in merge.captures_iter(source) {
+            let rest = &source[cap.get(0).unwrap().end()..];
+            let set = set_id.captures(rest);
+            assert!(
+                set.as_ref().is_some_and(|set| set[1] == cap[1] && set[2] == cap[1]),
+                "{} is merged without its id and repo_id",
+                &cap[0]
+            );
+            seen += 1;
+        }
+        assert!(seen > 40, "{}", seen);
+    }
+
+    #[test]
+    fn test_orphan_cleanup_removes_unreferenced_modules() {
+        let cypher = orphan_cleanup_cypher();
+
+        // A Module whose last importer changed has no IMPORTS edge left once
+        // the old File node is detached, so it matches the relationship-free
+        // pattern. Every label is matched on its own, so its repo_id index applies.
+        assert!(!cypher.contains("MATCH (n {repo_id: $repo_id})"));
+        assert!(!cypher.contains("labels(n)"));
+        for label in FILE_DERIVED_LABELS {
+            let pattern = format!("MATCH (n:{} {{repo_id: $repo_id}})\n           WHERE NOT (n)--()\n           DELETE n", label);
+            assert!(cypher.contains(&pattern), "missing {}", label);
+        }
+        // Files, boundaries and libraries are managed elsewhere
+        assert!(!cypher.contains("(n:File "));
+        assert!(!cypher.contains("(n:Library "));
+
+        // The pending flag is cleared in the same statement, so it only goes
+        // once the cleanup has committed
+        let flag = cypher.find("REMOVE r.orphan_cleanup_pending").unwrap();
+        assert!(cypher.rfind("DELETE n").unwrap() < flag);
+
+        // Dangling queries, endpoints and routes are removed before the orphan check
+        assert!(cypher.contains("MATCH (q:Route {repo_id: $repo_id})"));
+        assert!(cypher.contains("WHERE NOT ()-[:DEFINES_ROUTE]->(q)"));
+        assert!(cypher.contains("WHERE NOT ()-[:CALLS_ENDPOINT]->(q)"));
+        let query_cleanup = cypher.rfind("DETACH DELETE q").unwrap();
+        let orphan_cleanup = cypher.find("DELETE n").unwrap();
+        assert!(query_cleanup < orphan_cleanup);
+    }
+
+    #[test]
+    fn test_orphan_cleanup_runs_only_after_removals() {
+        // The query is scoped to the repository and needs no other parameter
+        assert!(orphan_cleanup_query("repo-1").has_param_key("repo_id"));
+        let cypher = orphan_cleanup_cypher();
+        let params: HashSet<&str> = regex::Regex::new(r"\$(\w+)")
+            .unwrap()
+            .captures_iter(&cypher)
+            .map(|c| c.get(1).unwrap().as_str())
+            .collect();
+        assert_eq!(params, HashSet::from(["repo_id"]));
+        // The flag an interrupted job leaves is the one the cleanup clears
+        assert!(mark_orphan_cleanup_cypher().contains("r.orphan_cleanup_pending = true"));
+        assert!(cypher.contains("REMOVE r.orphan_cleanup_pending"));
+
+        let paths = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let renamed = vec![("src/old.ts".to_string(), "src/new.ts".to_string())];
+
+        // A rename alone keeps its File node, so there is nothing to clean up
+        assert!(files_to_remove(&paths(&["src/new.ts"]), &[], &renamed).is_empty());
+        assert!(files_to_remove(&[], &[], &[]).is_empty());
+        assert_eq!(
+            files_to_remove(&paths(&["src/b.ts", "src/new.ts", "src/a.ts"]), &paths(&["src/gone.ts", "src/a.ts"]), &renamed),
+            paths(&["src/a.ts", "src/b.ts", "src/gone.ts"])
+        );
+    }
 }
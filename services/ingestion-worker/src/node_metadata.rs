@@ -0,0 +1,153 @@
+//! Node Metadata
+//!
+//! Properties a job asks to have stamped on every node it stores, such as
+//! the tenant, org or project an API gateway filters by. They come from the
+//! `node_metadata` job option, a flat JSON object of strings, and are
+//! checked before the job starts so a bad option fails it before the clone.
+//! Keys the worker itself keys or stamps nodes by are rejected, since a
+//! metadata entry would otherwise overwrite them.
+
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, HashMap};
+
+/// Job option holding the metadata
+pub const NODE_METADATA_OPTION: &str = "node_metadata";
+
+pub const MAX_KEYS: usize = 10;
+
+pub const MAX_KEY_CHARS: usize = 64;
+
+pub const MAX_VALUE_CHARS: usize = 256;
+
+/// Properties nodes are merged on or stamped with by the worker
+pub const RESERVED_KEYS: &[&str] = &[
+    "id", "path", "repo_id", "job_id", "name", "protocol", "url", "method", "topic", "host", "service", "sha",
+    "email", "namespace", "from_file", "to_file", "metadata",
+];
+
+/// Validated metadata of one job; empty when the option isn't set
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeMetadata {
+    entries: BTreeMap<String, String>,
+}
+
+impl NodeMetadata {
+    pub fn from_options(options: Option<&HashMap<String, String>>) -> Result<Self> {
+        match options.and_then(|opts| opts.get(NODE_METADATA_OPTION)) {
+            Some(raw) => Self::parse(raw),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn parse(raw: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(raw).context("node_metadata is not valid JSON")?;
+        let Some(object) = value.as_object() else {
+            bail!("node_metadata must be a JSON object of strings");
+        };
+        if object.len() > MAX_KEYS {
+            bail!("node_metadata has {} keys, at most {} are allowed", object.len(), MAX_KEYS);
+        }
+
+        let mut entries = BTreeMap::new();
+        for (key, value) in object {
+            validate_key(key)?;
+            let Some(value) = value.as_str() else {
+                bail!("node_metadata value of {:?} must be a string", key);
+            };
+            if value.chars().count() > MAX_VALUE_CHARS {
+                bail!("node_metadata value of {:?} is longer than {} characters", key, MAX_VALUE_CHARS);
+            }
+            entries.insert(key.clone(), value.to_string());
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries as one map, for `SET n += $metadata`
+    pub fn to_bolt(&self) -> neo4rs::BoltType {
+        let map: HashMap<String, String> = self.entries.clone().into_iter().collect();
+        map.into()
+    }
+
+    /// `rows` with the entries added under `metadata`, an empty map when
+    /// there are none, for node queries to `SET n += node.metadata`
+    pub fn stamp<T: Into<neo4rs::BoltType>>(&self, rows: Vec<HashMap<String, T>>) -> Vec<HashMap<String, neo4rs::BoltType>> {
+        rows.into_iter()
+            .map(|row| {
+                let mut row: HashMap<String, neo4rs::BoltType> = row.into_iter().map(|(k, v)| (k, v.into())).collect();
+                row.insert("metadata".to_string(), self.to_bolt());
+                row
+            })
+            .collect()
+    }
+}
+
+/// A letter, then letters, digits and underscores, so keys read as plain
+/// Neo4j property names
+fn validate_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.len() > MAX_KEY_CHARS {
+        bail!("node_metadata key {:?} must be 1 to {} characters long", key, MAX_KEY_CHARS);
+    }
+    let mut chars = key.chars();
+    let starts_with_letter = chars.next().is_some_and(|c| c.is_ascii_alphabetic());
+    if !starts_with_letter || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        bail!("node_metadata key {:?} must be a letter followed by letters, digits or underscores", key);
+    }
+    if RESERVED_KEYS.contains(&key) {
+        bail!("node_metadata key {:?} is reserved", key);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_is_validated() {
+        let metadata = NodeMetadata::parse(r#"{"tenant_id": "acme", "project": "billing"}"#).unwrap();
+        assert_eq!(metadata.entries.len(), 2);
+        assert!(NodeMetadata::from_options(None).unwrap().is_empty());
+
+        let too_many = format!(
+            "{{{}}}",
+            (0..=MAX_KEYS).map(|i| format!("\"k{}\": \"v\"", i)).collect::<Vec<_>>().join(",")
+        );
+        let long_value = format!(r#"{{"tenant": "{}"}}"#, "x".repeat(MAX_VALUE_CHARS + 1));
+        let long_key = format!(r#"{{"{}": "x"}}"#, "k".repeat(MAX_KEY_CHARS + 1));
+        let rejected = [
+            ("[]", "JSON object"),
+            ("{not json", "not valid JSON"),
+            (too_many.as_str(), "at most 10"),
+            (r#"{"repo_id": "other"}"#, "reserved"),
+            (r#"{"path": "x"}"#, "reserved"),
+            (r#"{"tenant-id": "acme"}"#, "letters, digits or underscores"),
+            (r#"{"1tenant": "acme"}"#, "letters, digits or underscores"),
+            (r#"{"tenant": {"id": "acme"}}"#, "must be a string"),
+            (r#"{"tenant": 7}"#, "must be a string"),
+            (long_value.as_str(), "longer than"),
+            (long_key.as_str(), "characters long"),
+        ];
+        for (raw, message) in rejected {
+            let error = NodeMetadata::parse(raw).unwrap_err();
+            assert!(format!("{:#}", error).contains(message), "{}: {:#}", raw, error);
+        }
+    }
+
+    #[test]
+    fn test_stamp_adds_metadata_to_every_row() {
+        let metadata = NodeMetadata::parse(r#"{"tenant_id": "acme"}"#).unwrap();
+        let row: HashMap<String, String> = [("name".to_string(), "orders".to_string())].into();
+        let rows = metadata.stamp(vec![row.clone(), row]);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["name"], neo4rs::BoltType::from("orders"));
+        let expected: HashMap<String, String> = [("tenant_id".to_string(), "acme".to_string())].into();
+        assert_eq!(rows[1]["metadata"], neo4rs::BoltType::from(expected));
+
+        let unstamped = NodeMetadata::default().stamp(vec![HashMap::<String, String>::new()]);
+        assert_eq!(unstamped[0]["metadata"], neo4rs::BoltType::from(HashMap::<String, String>::new()));
+    }
+}
@@ -0,0 +1,395 @@
+//! ORM Detector
+//!
+//! Finds ORM model classes and the table each one maps to: SQLAlchemy and
+//! Django models in Python, TypeORM entities in TypeScript/JavaScript, GORM
+//! structs in Go and Prisma schema models. Stored as `(Class)-[:MAPS_TO]->(Table)`
+//! so a table has an owner and not just a list of files that mention it.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::parsers::ParsedFile;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrmModel {
+    pub file_path: String,
+    pub class_name: String,
+    pub table: String,
+    /// sqlalchemy | django | typeorm | gorm | prisma
+    pub orm: String,
+}
+
+pub struct OrmDetector;
+
+impl OrmDetector {
+    pub fn detect(repo_path: &PathBuf, parsed_files: &[ParsedFile]) -> Result<Vec<OrmModel>> {
+        let mut models = Vec::new();
+
+        for file in parsed_files {
+            let extract: fn(&str, &str) -> Vec<OrmModel> = match file.language.as_str() {
+                "python" => extract_python_models,
+                "typescript" | "javascript" => extract_typeorm_entities,
+                "go" => extract_gorm_models,
+                _ => continue,
+            };
+            let content = match fs::read_to_string(repo_path.join(Path::new(&file.path))) {
+                Ok(data) => data,
+                Err(_) => continue,
+            };
+            models.extend(extract(&file.path, &content));
+        }
+
+        let mut schema_files = Vec::new();
+        collect_prisma_files(repo_path, &mut schema_files)?;
+        for schema in schema_files {
+            let Ok(content) = fs::read_to_string(&schema) else {
+                continue;
+            };
+            let relative = schema.strip_prefix(repo_path).unwrap_or(&schema).to_string_lossy().replace('\\', "/");
+            models.extend(extract_prisma_models(&relative, &content));
+        }
+
+        Ok(models)
+    }
+}
+
+/// `OrderItem` -> `order_item`, `HTTPRequest` -> `http_request`
+pub fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_is_lower) {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+/// English plural the way GORM's default naming strategy forms it, for the
+/// common cases
+fn pluralize(word: &str) -> String {
+    if let Some(stem) = word.strip_suffix('y') {
+        if !stem.ends_with(['a', 'e', 'i', 'o', 'u']) {
+            return format!("{}ies", stem);
+        }
+    }
+    if ["s", "x", "z", "ch", "sh"].iter().any(|suffix| word.ends_with(suffix)) {
+        return format!("{}es", word);
+    }
+    format!("{}s", word)
+}
+
+fn model(file_path: &str, class_name: &str, table: String, orm: &str) -> OrmModel {
+    OrmModel {
+        file_path: file_path.to_string(),
+        class_name: class_name.to_string(),
+        table,
+        orm: orm.to_string(),
+    }
+}
+
+fn first_capture(re: &Regex, text: &str) -> Option<String> {
+    re.captures(text).and_then(|cap| cap.get(1)).map(|m| m.as_str().to_string())
+}
+
+/// SQLAlchemy models (`__tablename__`, or Flask-SQLAlchemy's `db.Model`) and
+/// Django models (`models.Model`, with `Meta.db_table` when set)
+fn extract_python_models(file_path: &str, content: &str) -> Vec<OrmModel> {
+    let class_re = Regex::new(r"^(\s*)class\s+(\w+)\s*(?:\(([^)]*)\))?\s*:").unwrap();
+    let tablename_re = Regex::new(r#"__tablename__\s*=\s*['"]([^'"]+)['"]"#).unwrap();
+    let db_table_re = Regex::new(r#"db_table\s*=\s*['"]([^'"]+)['"]"#).unwrap();
+    let abstract_re = Regex::new(r"(?:__abstract__|abstract)\s*=\s*True").unwrap();
+
+    let lines: Vec<&str> = content.lines().collect();
+    let mut models = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let Some(cap) = class_re.captures(line) else {
+            continue;
+        };
+        let indent = cap[1].len();
+        let name = &cap[2];
+        let bases = cap.get(3).map(|m| m.as_str()).unwrap_or_default();
+        let body = lines[i + 1..]
+            .iter()
+            .take_while(|l| l.trim().is_empty() || l.len() - l.trim_start().len() > indent)
+            .copied()
+            .collect::<Vec<_>>()
+            .join("\n");
+        if abstract_re.is_match(&body) {
+            continue;
+        }
+
+        let base_names: Vec<&str> = bases.split(',').map(str::trim).collect();
+        if base_names.contains(&"models.Model") {
+            let table = first_capture(&db_table_re, &body).unwrap_or_else(|| django_table_name(file_path, name));
+            models.push(model(file_path, name, table, "django"));
+        } else if let Some(table) = first_capture(&tablename_re, &body) {
+            models.push(model(file_path, name, table, "sqlalchemy"));
+        } else if base_names.contains(&"db.Model") {
+            models.push(model(file_path, name, snake_case(name), "sqlalchemy"));
+        }
+    }
+    models
+}
+
+/// Django's default: `<app_label>_<model name lowercased>`, the app being the
+/// package that holds `models.py` (or the `models/` package)
+fn django_table_name(file_path: &str, class_name: &str) -> String {
+    let mut dirs: Vec<&str> = file_path.split('/').collect();
+    dirs.pop();
+    if dirs.last() == Some(&"models") {
+        dirs.pop();
+    }
+    match dirs.last() {
+        Some(app) => format!("{}_{}", app, class_name.to_lowercase()),
+        None => class_name.to_lowercase(),
+    }
+}
+
+/// TypeORM `@Entity()` classes; the table defaults to the snake_cased class name
+fn extract_typeorm_entities(file_path: &str, content: &str) -> Vec<OrmModel> {
+    let entity_re = Regex::new(
+        r#"@Entity\(\s*(?:['"]([^'"]+)['"]|\{[^}]*?\bname\s*:\s*['"]([^'"]+)['"][^}]*\})?[^)]*\)\s*(?:@\w+(?:\([^)]*\))?\s*)*(?:export\s+)?(?:default\s+)?(?:abstract\s+)?class\s+(\w+)"#,
+    )
+    .unwrap();
+
+    entity_re
+        .captures_iter(content)
+        .map(|cap| {
+            let name = &cap[3];
+            let table = cap
+                .get(1)
+                .or_else(|| cap.get(2))
+                .map(|m| m.as_str().to_string())
+                .unwrap_or_else(|| snake_case(name));
+            model(file_path, name, table, "typeorm")
+        })
+        .collect()
+}
+
+/// Structs embedding `gorm.Model` or carrying `gorm:"..."` tags. The table
+/// comes from a `TableName()` method, else GORM's pluralized snake_case.
+fn extract_gorm_models(file_path: &str, content: &str) -> Vec<OrmModel> {
+    let struct_re = Regex::new(r"(?s)type\s+(\w+)\s+struct\s*\{(.*?)\n\}").unwrap();
+    let table_name_re =
+        Regex::new(r#"func\s*\(\s*(?:\w+\s+)?\*?(\w+)\s*\)\s*TableName\(\)\s*string\s*\{\s*return\s+"([^"]+)""#).unwrap();
+
+    let explicit: Vec<(String, String)> = table_name_re
+        .captures_iter(content)
+        .map(|cap| (cap[1].to_string(), cap[2].to_string()))
+        .collect();
+
+    struct_re
+        .captures_iter(content)
+        .filter(|cap| cap[2].contains("gorm.Model") || cap[2].contains("gorm:\""))
+        .map(|cap| {
+            let name = &cap[1];
+            let table = explicit
+                .iter()
+                .find(|(receiver, _)| receiver == name)
+                .map(|(_, table)| table.clone())
+                .unwrap_or_else(|| pluralize(&snake_case(name)));
+            model(file_path, name, table, "gorm")
+        })
+        .collect()
+}
+
+/// Prisma `model` blocks; `@@map("...")` renames the table
+fn extract_prisma_models(file_path: &str, content: &str) -> Vec<OrmModel> {
+    let model_re = Regex::new(r"(?ms)^model\s+(\w+)\s*\{(.*?)^\}").unwrap();
+    let map_re = Regex::new(r#"@@map\(\s*(?:name\s*:\s*)?"([^"]+)"\s*\)"#).unwrap();
+
+    model_re
+        .captures_iter(content)
+        .map(|cap| {
+            let name = &cap[1];
+            let table = first_capture(&map_re, &cap[2]).unwrap_or_else(|| name.to_string());
+            model(file_path, name, table, "prisma")
+        })
+        .collect()
+}
+
+fn collect_prisma_files(current_dir: &PathBuf, results: &mut Vec<PathBuf>) -> Result<()> {
+    if !current_dir.is_dir() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(current_dir).context("Failed to read directory")? {
+        let entry = entry.context("Failed to read directory entry")?;
+        let path = entry.path();
+
+        if let Some(name) = path.file_name() {
+            let name_str = name.to_string_lossy();
+            if name_str.starts_with('.')
+                || name_str == "node_modules"
+                || name_str == "target"
+                || name_str == "dist"
+                || name_str == "build" {
+                continue;
+            }
+        }
+
+        if path.is_dir() {
+            collect_prisma_files(&path, results)?;
+        } else if path.extension().is_some_and(|ext| ext.to_string_lossy().to_lowercase() == "prisma") {
+            results.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tables(models: &[OrmModel]) -> Vec<(&str, &str)> {
+        models.iter().map(|m| (m.class_name.as_str(), m.table.as_str())).collect()
+    }
+
+    #[test]
+    fn test_snake_case_and_pluralize() {
+        assert_eq!(snake_case("OrderItem"), "order_item");
+        assert_eq!(snake_case("HTTPRequest"), "http_request");
+        assert_eq!(snake_case("User2Fa"), "user2_fa");
+        assert_eq!(pluralize("order_item"), "order_items");
+        assert_eq!(pluralize("category"), "categories");
+        assert_eq!(pluralize("address"), "addresses");
+        assert_eq!(pluralize("day"), "days");
+    }
+
+    #[test]
+    fn test_sqlalchemy_models() {
+        let content = r#"
+class Base(DeclarativeBase):
+    pass
+
+class User(Base):
+    __tablename__ = "users"
+    id = mapped_column(Integer, primary_key=True)
+
+class OrderItem(db.Model):
+    id = db.Column(db.Integer, primary_key=True)
+
+class Timestamped(db.Model):
+    __abstract__ = True
+
+def helper():
+    __tablename__ = "not_a_model"
+"#;
+        let models = extract_python_models("app/models.py", content);
+        assert_eq!(tables(&models), vec![("User", "users"), ("OrderItem", "order_item")]);
+        assert!(models.iter().all(|m| m.orm == "sqlalchemy"));
+    }
+
+    #[test]
+    fn test_django_models() {
+        let content = r#"
+from django.db import models
+
+class Customer(models.Model):
+    name = models.CharField(max_length=100)
+
+    class Meta:
+        db_table = "crm_customers"
+
+class OrderLine(models.Model):
+    qty = models.IntegerField()
+
+class Base(models.Model):
+    class Meta:
+        abstract = True
+"#;
+        let models = extract_python_models("shop/models.py", content);
+        assert_eq!(tables(&models), vec![("Customer", "crm_customers"), ("OrderLine", "shop_orderline")]);
+        assert!(models.iter().all(|m| m.orm == "django"));
+
+        assert_eq!(django_table_name("shop/models/order.py", "Order"), "shop_order");
+        assert_eq!(django_table_name("models.py", "Order"), "order");
+    }
+
+    #[test]
+    fn test_typeorm_entities() {
+        let content = r#"
+@Entity("users")
+export class User {
+  @PrimaryGeneratedColumn() id: number;
+}
+
+@Entity({ name: "orders", schema: "shop" })
+export class Order {}
+
+@Entity()
+@Index(["sku"])
+export class ProductVariant {}
+
+export class NotAnEntity {}
+"#;
+        let models = extract_typeorm_entities("src/entities.ts", content);
+        assert_eq!(
+            tables(&models),
+            vec![("User", "users"), ("Order", "orders"), ("ProductVariant", "product_variant")]
+        );
+    }
+
+    #[test]
+    fn test_gorm_models() {
+        let content = r#"
+type User struct {
+	gorm.Model
+	Name string
+}
+
+type Category struct {
+	ID   uint   `gorm:"primaryKey"`
+	Name string `gorm:"size:64"`
+}
+
+type AuditLog struct {
+	ID uint `gorm:"primaryKey"`
+}
+
+func (AuditLog) TableName() string {
+	return "audit_events"
+}
+
+type Config struct {
+	Port int `json:"port"`
+}
+"#;
+        let models = extract_gorm_models("internal/models.go", content);
+        assert_eq!(
+            tables(&models),
+            vec![("User", "users"), ("Category", "categories"), ("AuditLog", "audit_events")]
+        );
+    }
+
+    #[test]
+    fn test_prisma_models() {
+        let content = r#"
+datasource db {
+  provider = "postgresql"
+}
+
+model User {
+  id    Int    @id
+  posts Post[]
+  @@map("app_users")
+}
+
+model Post {
+  id Int @id
+}
+"#;
+        let models = extract_prisma_models("prisma/schema.prisma", content);
+        assert_eq!(tables(&models), vec![("User", "app_users"), ("Post", "Post")]);
+        assert!(models.iter().all(|m| m.file_path == "prisma/schema.prisma"));
+    }
+}
@@ -0,0 +1,275 @@
+//! OSV Client
+//!
+//! Looks up known vulnerabilities for collected library dependencies through
+//! the OSV.dev `POST /v1/querybatch` API. Only dependencies with a concrete
+//! version are queried; a version range is checked at the lowest version it
+//! allows. Failed batches are logged and skipped, so an unreachable OSV leaves
+//! the libraries unchecked instead of failing the job.
+
+use crate::dependency_metadata::LibraryDependency;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+pub const DEFAULT_OSV_API_URL: &str = "https://api.osv.dev";
+pub const DEFAULT_OSV_CONCURRENCY: usize = 4;
+
+/// Queries per request; OSV accepts up to 1000
+const QUERY_BATCH_SIZE: usize = 100;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct OsvQuery {
+    package: OsvPackage,
+    version: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct OsvPackage {
+    name: String,
+    ecosystem: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvBatchResponse {
+    #[serde(default)]
+    results: Vec<OsvResult>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct OsvResult {
+    #[serde(default)]
+    vulns: Vec<OsvVuln>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvVuln {
+    id: String,
+}
+
+#[derive(Clone)]
+pub struct OsvClient {
+    client: reqwest::Client,
+    base_url: String,
+    concurrency: usize,
+}
+
+impl OsvClient {
+    pub fn new(base_url: String, concurrency: usize) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .context("Failed to build OSV HTTP client")?;
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            concurrency: concurrency.max(1),
+        })
+    }
+
+    /// Fill in `vulnerability_ids` for every dependency OSV could be asked
+    /// about. Returns how many dependencies were checked.
+    pub async fn enrich(&self, dependencies: &mut [LibraryDependency]) -> usize {
+        let queries: Vec<(usize, OsvQuery)> = dependencies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, dep)| osv_query(dep).map(|q| (i, q)))
+            .collect();
+
+        let semaphore = Arc::new(Semaphore::new(self.concurrency));
+        let mut tasks = tokio::task::JoinSet::new();
+        for batch in queries.chunks(QUERY_BATCH_SIZE) {
+            let (indexes, batch): (Vec<usize>, Vec<OsvQuery>) = batch.iter().cloned().unzip();
+            let (client, semaphore) = (self.clone(), semaphore.clone());
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                (indexes, client.query_batch(&batch).await)
+            });
+        }
+
+        let mut checked = 0;
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((indexes, Ok(results))) => {
+                    for (i, ids) in indexes.into_iter().zip(results) {
+                        dependencies[i].vulnerability_ids = Some(ids);
+                        checked += 1;
+                    }
+                }
+                Ok((indexes, Err(e))) => {
+                    warn!("⚠️  Skipping vulnerability check for {} libraries: {:?}", indexes.len(), e);
+                }
+                Err(e) => warn!("⚠️  Vulnerability check task failed: {:?}", e),
+            }
+        }
+        checked
+    }
+
+    /// Vulnerability ids per query, in query order
+    async fn query_batch(&self, queries: &[OsvQuery]) -> Result<Vec<Vec<String>>> {
+        let url = format!("{}/v1/querybatch", self.base_url);
+        let response = self
+            .client
+            .post(&url)
+            .json(&serde_json::json!({ "queries": queries }))
+            .send()
+            .await
+            .context("Failed to send OSV query")?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("OSV returned {}", response.status());
+        }
+
+        let body: OsvBatchResponse = response.json().await.context("Failed to parse OSV response")?;
+        if body.results.len() != queries.len() {
+            anyhow::bail!("OSV returned {} results for {} queries", body.results.len(), queries.len());
+        }
+
+        Ok(body
+            .results
+            .into_iter()
+            .map(|result| {
+                let mut ids: Vec<String> = result.vulns.into_iter().map(|v| v.id).collect();
+                ids.sort();
+                ids.dedup();
+                ids
+            })
+            .collect())
+    }
+}
+
+/// OSV ecosystem of the manifest a dependency came from
+fn ecosystem(source_file: &str) -> Option<&'static str> {
+    match source_file.rsplit('/').next()? {
+        "package.json" => Some("npm"),
+        "Cargo.toml" => Some("crates.io"),
+        "requirements.txt" | "pyproject.toml" | "Pipfile" => Some("PyPI"),
+        "go.mod" => Some("Go"),
+        "pom.xml" | "build.gradle" | "build.gradle.kts" => Some("Maven"),
+        _ => None,
+    }
+}
+
+/// The concrete version to query for a manifest version spec: the spec
+/// itself for exact pins, the lower bound for `^`/`~`/`>=` ranges. `None`
+/// for wildcards, unions and anything else without a usable version.
+fn query_version(spec: &str) -> Option<String> {
+    let version = spec
+        .trim()
+        .trim_start_matches(|c: char| matches!(c, '^' | '~' | '=' | '>' | 'v') || c.is_whitespace())
+        .trim_end_matches("+incompatible");
+
+    let usable = version.starts_with(|c: char| c.is_ascii_digit())
+        && !version.contains(|c: char| c.is_whitespace() || matches!(c, '*' | '<' | '|' | ','))
+        && !version.split('.').any(|part| part == "x" || part == "X");
+    usable.then(|| version.to_string())
+}
+
+fn osv_query(dependency: &LibraryDependency) -> Option<OsvQuery> {
+    let ecosystem = ecosystem(&dependency.source_file)?;
+    let version = query_version(dependency.version.as_deref()?)?;
+    Some(OsvQuery {
+        package: OsvPackage { name: dependency.name.clone(), ecosystem },
+        version,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn dependency(name: &str, version: Option<&str>, source_file: &str) -> LibraryDependency {
+        LibraryDependency {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+            source_file: source_file.to_string(),
+            vulnerability_ids: None,
+            usage: None,
+        }
+    }
+
+    #[test]
+    fn test_osv_query_versions() {
+        assert_eq!(query_version("^4.17.1").as_deref(), Some("4.17.1"));
+        assert_eq!(query_version("==2.31.0").as_deref(), Some("2.31.0"));
+        assert_eq!(query_version(">=1.0").as_deref(), Some("1.0"));
+        assert_eq!(query_version("v1.9.1").as_deref(), Some("1.9.1"));
+        assert_eq!(query_version("v2.0.0+incompatible").as_deref(), Some("2.0.0"));
+        assert_eq!(query_version("*"), None);
+        assert_eq!(query_version("1.x"), None);
+        assert_eq!(query_version(">=1.0,<2.0"), None);
+        assert_eq!(query_version("latest"), None);
+
+        let query = osv_query(&dependency("serde", Some("1.0"), "services/api/Cargo.toml")).unwrap();
+        assert_eq!(query.package.ecosystem, "crates.io");
+        assert!(osv_query(&dependency("left-pad", None, "package.json")).is_none());
+        assert!(osv_query(&dependency("rails", Some("7.0.0"), "Gemfile")).is_none());
+        let query = osv_query(&dependency("org.yaml:snakeyaml", Some("1.33"), "build.gradle.kts")).unwrap();
+        assert_eq!(query.package.ecosystem, "Maven");
+        assert_eq!(query.package.name, "org.yaml:snakeyaml");
+    }
+
+    #[tokio::test]
+    async fn test_enrich_records_vulnerability_ids() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/querybatch")
+            .match_body(mockito::Matcher::Json(json!({
+                "queries": [
+                    {"package": {"name": "lodash", "ecosystem": "npm"}, "version": "4.17.15"},
+                    {"package": {"name": "github.com/gin-gonic/gin", "ecosystem": "Go"}, "version": "1.9.1"}
+                ]
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"results": [
+                    {"vulns": [{"id": "GHSA-p6mc-m468-83gw", "modified": "2024-01-01T00:00:00Z"},
+                               {"id": "GHSA-35jh-r3h4-6jhm", "modified": "2024-01-01T00:00:00Z"}]},
+                    {}
+                ]}"#,
+            )
+            .create_async()
+            .await;
+
+        let mut dependencies = vec![
+            dependency("lodash", Some("^4.17.15"), "web/package.json"),
+            dependency("react", Some("*"), "web/package.json"),
+            dependency("github.com/gin-gonic/gin", Some("v1.9.1"), "go.mod"),
+        ];
+        let client = OsvClient::new(server.url(), 2).unwrap();
+        assert_eq!(client.enrich(&mut dependencies).await, 2);
+        mock.assert_async().await;
+
+        assert_eq!(
+            dependencies[0].vulnerability_ids,
+            Some(vec!["GHSA-35jh-r3h4-6jhm".to_string(), "GHSA-p6mc-m468-83gw".to_string()])
+        );
+        assert_eq!(dependencies[1].vulnerability_ids, None);
+        assert_eq!(dependencies[2].vulnerability_ids, Some(vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_enrich_leaves_libraries_unchecked_on_failure() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("POST", "/v1/querybatch")
+            .with_status(503)
+            .create_async()
+            .await;
+
+        let mut dependencies = vec![dependency("requests", Some("==2.19.0"), "requirements.txt")];
+        let client = OsvClient::new(server.url(), 1).unwrap();
+        assert_eq!(client.enrich(&mut dependencies).await, 0);
+        assert_eq!(dependencies[0].vulnerability_ids, None);
+
+        // Nothing listening at all
+        let client = OsvClient::new("http://127.0.0.1:9".to_string(), 1).unwrap();
+        assert_eq!(client.enrich(&mut dependencies).await, 0);
+        assert_eq!(dependencies[0].vulnerability_ids, None);
+    }
+}
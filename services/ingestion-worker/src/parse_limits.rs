@@ -0,0 +1,149 @@
+//! Parse Limits
+//!
+//! Guards that keep one pathological repository (a minified bundle, a
+//! generated tree of a million files, binaries with a source extension,
+//! symlinks out of the checkout) from exhausting the worker. Whatever is
+//! skipped is counted in `ParseStats` and reported in the job result summary.
+
+use serde::Serialize;
+use tracing::warn;
+
+pub const DEFAULT_MAX_FILE_SIZE_BYTES: u64 = 1024 * 1024;
+pub const DEFAULT_MAX_FILES: usize = 50_000;
+
+/// Bytes inspected for a NUL byte before a file is treated as binary
+const BINARY_SNIFF_BYTES: usize = 8000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileLimits {
+    /// Larger files are skipped without being read
+    pub max_file_size_bytes: u64,
+    /// Files past this many (in path order) are not parsed
+    pub max_files: usize,
+    /// Generated files are skipped instead of parsed and marked, see
+    /// `generated_code`
+    pub skip_generated: bool,
+}
+
+impl Default for FileLimits {
+    fn default() -> Self {
+        Self {
+            max_file_size_bytes: DEFAULT_MAX_FILE_SIZE_BYTES,
+            max_files: DEFAULT_MAX_FILES,
+            skip_generated: false,
+        }
+    }
+}
+
+/// A file left out of the parse, with a path relative to the repository root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+/// A file the parser failed on, by error or panic. It is kept as a File
+/// node with `parse_failed` set and no symbols.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ParseError {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ParseStats {
+    pub skipped_large_files: usize,
+    pub skipped_binary_files: usize,
+    pub skipped_generated_files: usize,
+    /// Whether `max_files` cut the file list short
+    pub truncated: bool,
+    /// Every skipped file and why, including symlinks, which aren't followed
+    pub skipped_files: Vec<SkippedFile>,
+    /// Source files left out by the job's include/exclude paths
+    pub excluded_files: usize,
+    pub parse_errors: Vec<ParseError>,
+}
+
+impl ParseStats {
+    pub fn skip(&mut self, path: impl Into<String>, reason: impl Into<String>) {
+        self.skipped_files.push(SkippedFile {
+            path: path.into(),
+            reason: reason.into(),
+        });
+    }
+
+    pub fn log(&self) {
+        if !self.parse_errors.is_empty() {
+            warn!(parse_failed = self.parse_errors.len(), "⚠️  Some files failed to parse");
+        }
+        if !self.skipped_files.is_empty() || self.truncated {
+            warn!(
+                skipped_large_files = self.skipped_large_files,
+                skipped_binary_files = self.skipped_binary_files,
+                skipped_generated_files = self.skipped_generated_files,
+                skipped_files = self.skipped_files.len(),
+                truncated = self.truncated,
+                "⚠️  Some files were not parsed"
+            );
+        }
+    }
+}
+
+/// Sort candidate paths and keep the first `max_files`, flagging truncation
+pub fn apply_file_limit<T: Ord>(paths: &mut Vec<T>, limits: &FileLimits, stats: &mut ParseStats) {
+    paths.sort();
+    paths.dedup();
+    if paths.len() > limits.max_files {
+        warn!("⚠️  {} files found, parsing only the first {}", paths.len(), limits.max_files);
+        paths.truncate(limits.max_files);
+        stats.truncated = true;
+    }
+}
+
+/// Source text of a file, or None if it looks binary (a NUL byte near the
+/// start). Invalid UTF-8, such as Latin-1 comments in older code, is replaced
+/// with U+FFFD; the flag says whether that happened.
+pub fn decode_source(bytes: Vec<u8>) -> Option<(String, bool)> {
+    if bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+        return None;
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => Some((text, false)),
+        Err(e) => Some((String::from_utf8_lossy(e.as_bytes()).into_owned(), true)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_limit_truncates_in_path_order() {
+        let limits = FileLimits {
+            max_file_size_bytes: DEFAULT_MAX_FILE_SIZE_BYTES,
+            max_files: 2,
+            ..Default::default()
+        };
+        let mut stats = ParseStats::default();
+        let mut paths = vec!["c.rs", "a.rs", "b.rs", "a.rs"];
+        apply_file_limit(&mut paths, &limits, &mut stats);
+        assert_eq!(paths, vec!["a.rs", "b.rs"]);
+        assert!(stats.truncated);
+
+        let mut stats = ParseStats::default();
+        let mut paths = vec!["a.rs"];
+        apply_file_limit(&mut paths, &limits, &mut stats);
+        assert!(!stats.truncated);
+    }
+
+    #[test]
+    fn test_decode_source_rejects_binary() {
+        assert_eq!(decode_source(b"fn main() {}\n".to_vec()), Some(("fn main() {}\n".to_string(), false)));
+        assert_eq!(decode_source(vec![0x7f, b'E', b'L', b'F', 0, 0, 1]), None);
+        // Latin-1 "é" in a comment
+        assert_eq!(
+            decode_source(b"# caf\xe9\nx = 1\n".to_vec()),
+            Some(("# caf\u{FFFD}\nx = 1\n".to_string(), true))
+        );
+    }
+}
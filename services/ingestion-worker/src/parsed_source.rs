@@ -0,0 +1,222 @@
+//! Parsed File Source
+//!
+//! Where a job keeps its parsed files between parsing and storage. Most
+//! repositories keep them in a `Vec`. Above `STREAM_FILE_THRESHOLD` files
+//! the parser writes every file as one JSON line to a spill file instead,
+//! and the phases that need whole files (the symbol table, the graph's
+//! CALLS edges, File and Query nodes) read it back `CHUNK_FILES` at a time.
+//! What every other phase needs of each file stays in memory as its
+//! outline, see `ParsedFile::into_outline`, so a streamed run never holds
+//! the call lists and SQL queries of all files at once.
+//!
+//! The spill file lives in its own `archmind-spill-*` temp directory, which
+//! is removed with the source or, after a crash, at the next startup.
+
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::PathBuf;
+
+use crate::parsers::ParsedFile;
+
+/// Files above which a full run streams its parse results
+pub const DEFAULT_STREAM_FILE_THRESHOLD: usize = 20_000;
+
+/// Files parsed and read back from the spill file at a time
+pub const CHUNK_FILES: usize = 500;
+
+/// The parsed files of a run, sorted by path
+pub enum ParsedFileSource {
+    Memory(Vec<ParsedFile>),
+    Spilled { spill: SpillFile, outlines: Vec<ParsedFile> },
+}
+
+impl ParsedFileSource {
+    pub fn len(&self) -> usize {
+        self.outlines().len()
+    }
+
+    pub fn is_streamed(&self) -> bool {
+        matches!(self, ParsedFileSource::Spilled { .. })
+    }
+
+    /// Every file, without call lists and SQL queries when streamed
+    pub fn outlines(&self) -> &[ParsedFile] {
+        match self {
+            ParsedFileSource::Memory(files) => files,
+            ParsedFileSource::Spilled { outlines, .. } => outlines,
+        }
+    }
+
+    /// The complete files: all at once from memory, `CHUNK_FILES` at a
+    /// time from the spill file
+    pub fn chunks(&self) -> Result<FileChunks<'_>> {
+        match self {
+            ParsedFileSource::Memory(files) => Ok(FileChunks::Memory(Some(files))),
+            ParsedFileSource::Spilled { spill, .. } => spill.chunks(),
+        }
+    }
+
+    /// Apply `update` to every file, rewriting the spill file. It sees the
+    /// files a chunk at a time and the outlines separately, so it must
+    /// treat each file on its own.
+    pub fn update(&mut self, mut update: impl FnMut(&mut [ParsedFile])) -> Result<()> {
+        match self {
+            ParsedFileSource::Memory(files) => update(files),
+            ParsedFileSource::Spilled { spill, outlines } => {
+                let mut writer = SpillWriter::create(spill.chunk_files)?;
+                for chunk in spill.chunks()? {
+                    let mut chunk = chunk?.into_owned();
+                    update(&mut chunk);
+                    for file in &chunk {
+                        writer.push(file)?;
+                    }
+                }
+                *spill = writer.finish()?;
+                update(outlines);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parsed files written one JSON object per line, removed on drop
+pub struct SpillFile {
+    dir: PathBuf,
+    path: PathBuf,
+    chunk_files: usize,
+}
+
+impl SpillFile {
+    fn chunks(&self) -> Result<FileChunks<'_>> {
+        let file = File::open(&self.path).with_context(|| format!("Failed to open spill file {:?}", self.path))?;
+        Ok(FileChunks::Spilled { lines: BufReader::new(file).lines(), size: self.chunk_files.max(1) })
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Appends parsed files to a new spill file
+pub struct SpillWriter {
+    writer: BufWriter<File>,
+    spill: SpillFile,
+}
+
+impl SpillWriter {
+    /// A spill file read back `chunk_files` files at a time
+    pub fn create(chunk_files: usize) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("archmind-spill-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create spill directory {:?}", dir))?;
+        let path = dir.join("parsed_files.ndjson");
+        let spill = SpillFile { dir, path, chunk_files };
+        let file = File::create(&spill.path).with_context(|| format!("Failed to create spill file {:?}", spill.path))?;
+        Ok(Self { writer: BufWriter::new(file), spill })
+    }
+
+    pub fn push(&mut self, file: &ParsedFile) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, file).context("Failed to serialize parsed file")?;
+        self.writer.write_all(b"\n").context("Failed to write spill file")
+    }
+
+    pub fn finish(mut self) -> Result<SpillFile> {
+        self.writer.flush().context("Failed to write spill file")?;
+        Ok(self.spill)
+    }
+}
+
+/// Complete parsed files, a chunk at a time
+pub enum FileChunks<'a> {
+    Memory(Option<&'a [ParsedFile]>),
+    Spilled { lines: Lines<BufReader<File>>, size: usize },
+}
+
+impl<'a> Iterator for FileChunks<'a> {
+    type Item = Result<Cow<'a, [ParsedFile]>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            FileChunks::Memory(files) => files.take().map(|files| Ok(Cow::Borrowed(files))),
+            FileChunks::Spilled { lines, size } => {
+                let mut chunk = Vec::new();
+                for line in lines.by_ref().take(*size) {
+                    let parsed = line
+                        .context("Failed to read spill file")
+                        .and_then(|line| serde_json::from_str(&line).context("Failed to read parsed file from spill"));
+                    match parsed {
+                        Ok(file) => chunk.push(file),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                (!chunk.is_empty()).then_some(Ok(Cow::Owned(chunk)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{python_parser::PythonParser, LanguageParser};
+
+    fn parse(path: &str, content: &str) -> ParsedFile {
+        PythonParser::new().unwrap().parse_file(&PathBuf::from(path), content).unwrap()
+    }
+
+    fn spilled(files: &[ParsedFile], chunk_files: usize) -> ParsedFileSource {
+        let mut writer = SpillWriter::create(chunk_files).unwrap();
+        for file in files {
+            writer.push(file).unwrap();
+        }
+        let outlines = files.iter().cloned().map(ParsedFile::into_outline).collect();
+        ParsedFileSource::Spilled { spill: writer.finish().unwrap(), outlines }
+    }
+
+    fn json(files: &[ParsedFile]) -> serde_json::Value {
+        serde_json::to_value(files).unwrap()
+    }
+
+    #[test]
+    fn test_spilled_files_read_back_in_chunks() {
+        let files: Vec<ParsedFile> = (0..5)
+            .map(|i| parse(&format!("app/m{}.py", i), "def load(db):\n    return db.execute(\"SELECT * FROM users\")\n"))
+            .collect();
+        let source = spilled(&files, 2);
+        assert!(source.is_streamed());
+        assert_eq!(source.len(), 5);
+
+        let chunks: Vec<Vec<ParsedFile>> = source.chunks().unwrap().map(|chunk| chunk.unwrap().into_owned()).collect();
+        assert_eq!(chunks.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 2, 1]);
+        assert_eq!(json(&chunks.concat()), json(&files));
+
+        // Outlines keep the symbols but not what only whole files are read for
+        let outline = &source.outlines()[0];
+        assert_eq!(outline.functions[0].name, "load");
+        assert!(outline.functions[0].calls.is_empty() && outline.sql_queries.is_empty());
+        assert!(!files[0].functions[0].calls.is_empty() && !files[0].sql_queries.is_empty());
+    }
+
+    #[test]
+    fn test_update_rewrites_spill_and_outlines() {
+        let files = vec![parse("a.py", "def f():\n    pass\n"), parse("b.py", "def g():\n    pass\n")];
+        let mut source = spilled(&files, 1);
+        let dir = match &source {
+            ParsedFileSource::Spilled { spill, .. } => spill.dir.clone(),
+            ParsedFileSource::Memory(_) => unreachable!(),
+        };
+        source.update(|files| files.iter_mut().for_each(|file| file.is_test = true)).unwrap();
+
+        let chunks: Vec<ParsedFile> = source.chunks().unwrap().flat_map(|chunk| chunk.unwrap().into_owned()).collect();
+        assert!(chunks.iter().chain(source.outlines()).all(|file| file.is_test));
+        assert!(!dir.exists(), "replaced spill file is removed");
+
+        let mut memory = ParsedFileSource::Memory(files);
+        memory.update(|files| files[0].is_test = true).unwrap();
+        assert_eq!(memory.chunks().unwrap().count(), 1);
+        assert!(memory.outlines()[0].is_test);
+    }
+}
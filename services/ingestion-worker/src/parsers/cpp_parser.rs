@@ -0,0 +1,554 @@
+use super::{ClassInfo, FunctionInfo, InheritanceInfo, LanguageParser, ParsedFile, ServiceCall};
+use super::{body_hash, count_lines, cyclomatic_complexity};
+use super::env_vars::{extract_env_vars, CPP_PATTERNS};
+use super::test_files::{is_test_file, CPP_SIGNALS};
+use super::sql_extractor::extract_data_tables;
+use super::sql_queries::extract_sql_queries;
+use crate::url_utils::canonical_host;
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tree_sitter::{Language, Node, Parser, Tree};
+
+/// Node kinds of string literals, searched for SQL
+const STRING_KINDS: &[&str] = &["string_literal"];
+
+/// Node kinds that add a decision point to a function's complexity
+const BRANCH_KINDS: &[&str] = &[
+    "if_statement", "for_statement", "for_range_loop", "while_statement", "do_statement",
+    "case", "catch_clause", "conditional_expression", "&&", "||",
+];
+
+/// Parses C and C++. `.c` files use the C grammar; everything else uses the
+/// C++ grammar, except headers that only parse cleanly as C.
+pub struct CppParser;
+
+impl CppParser {
+    pub fn new() -> Result<Self> {
+        Ok(CppParser)
+    }
+
+    fn parse_tree(&self, language: Language, content: &str) -> Result<Tree> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(language)
+            .context("Failed to set C/C++ language")?;
+        parser
+            .parse(content, None)
+            .context("Failed to parse C/C++ file")
+    }
+
+    fn extract_service_calls(&self, content: &str) -> Vec<ServiceCall> {
+        let mut services = HashSet::new();
+        let url_pattern = r#"(?i)\b(https?|grpc)://[^\s'"]+"#;
+
+        if let Ok(re) = Regex::new(url_pattern) {
+            for cap in re.captures_iter(content) {
+                let full = cap.get(0).map(|m| m.as_str()).unwrap_or_default();
+                let protocol = cap.get(1).map(|m| m.as_str()).unwrap_or("http");
+                if let Some(host) = canonical_host(full) {
+                    services.insert((host, protocol.to_string()));
+                }
+            }
+        }
+
+        services
+            .into_iter()
+            .map(|(host, protocol)| ServiceCall::new(host, protocol))
+            .collect()
+    }
+
+    fn extract_params(&self, params: Node, content: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut cursor = params.walk();
+        for param in params.named_children(&mut cursor) {
+            // `void f(int)` and `f(void)` have no declarator to name
+            if let Some(name) = param
+                .child_by_field_name("declarator")
+                .and_then(|d| declarator_identifier(d, content))
+            {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    fn extract_calls(&self, body: Node, content: &str) -> Vec<String> {
+        let mut calls = Vec::new();
+        let mut stack = vec![body];
+        while let Some(node) = stack.pop() {
+            let callee = match node.kind() {
+                "call_expression" => node.child_by_field_name("function").and_then(|f| match f.kind() {
+                    "identifier" => Some(content[f.byte_range()].to_string()),
+                    "field_expression" => f
+                        .child_by_field_name("field")
+                        .map(|field| content[field.byte_range()].to_string()),
+                    "qualified_identifier" | "template_function" => Some(split_declarator_name(f, content).1),
+                    _ => None,
+                }),
+                "new_expression" => node
+                    .child_by_field_name("type")
+                    .map(|t| strip_type_arguments(&content[t.byte_range()])),
+                _ => None,
+            };
+            if let Some(callee) = callee.filter(|c| !c.is_empty()) {
+                calls.push(callee);
+            }
+
+            let mut cursor = node.walk();
+            stack.extend(node.named_children(&mut cursor));
+        }
+        calls
+    }
+
+    /// Build a function from a `function_definition`, or from a prototype
+    /// (`field_declaration`/`declaration` with a function declarator), which
+    /// has no body and so no calls. Also returns the enclosing class for
+    /// out-of-line definitions such as `void Foo::bar() {}`.
+    fn extract_function(&self, node: Node, content: &str) -> Option<(Option<String>, FunctionInfo)> {
+        let declarator = find_function_declarator(node.child_by_field_name("declarator")?)?;
+        let (scope, name) = split_declarator_name(declarator.child_by_field_name("declarator")?, content);
+        if name.is_empty() {
+            return None;
+        }
+
+        let params = declarator
+            .child_by_field_name("parameters")
+            .map(|p| self.extract_params(p, content))
+            .unwrap_or_default();
+        let return_type = node
+            .child_by_field_name("type")
+            .map(|t| content[t.byte_range()].to_string());
+        let calls = node
+            .child_by_field_name("body")
+            .map(|body| self.extract_calls(body, content))
+            .unwrap_or_default();
+
+        Some((
+            scope,
+            FunctionInfo {
+                name,
+                params,
+                return_type,
+                calls,
+                start_line: node.start_position().row + 1,
+                end_line: node.end_position().row + 1,
+                complexity: cyclomatic_complexity(node, BRANCH_KINDS),
+                body_hash: body_hash(node, content),
+                decorators: Vec::new(),
+                visibility: None,
+            },
+        ))
+    }
+
+    fn extract_inheritances(&self, node: Node, content: &str) -> Vec<InheritanceInfo> {
+        let mut inheritances = Vec::new();
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.kind() != "base_class_clause" {
+                continue;
+            }
+            let mut base_cursor = child.walk();
+            for base in child.named_children(&mut base_cursor) {
+                if matches!(base.kind(), "type_identifier" | "qualified_identifier" | "template_type") {
+                    inheritances.push(InheritanceInfo {
+                        name: strip_type_arguments(&content[base.byte_range()]),
+                        kind: "class".to_string(),
+                    });
+                }
+            }
+        }
+        inheritances
+    }
+
+    /// Methods declared or defined directly in a class body
+    fn extract_methods(&self, body: Node, content: &str) -> Vec<FunctionInfo> {
+        let mut methods: Vec<FunctionInfo> = Vec::new();
+        let mut cursor = body.walk();
+        for member in body.named_children(&mut cursor) {
+            // Member templates keep the member itself as their last named child
+            let member = if member.kind() == "template_declaration" {
+                match member.named_child(member.named_child_count().saturating_sub(1)) {
+                    Some(inner) => inner,
+                    None => continue,
+                }
+            } else {
+                member
+            };
+            if !matches!(member.kind(), "function_definition" | "field_declaration" | "declaration") {
+                continue;
+            }
+            if let Some((_, method)) = self.extract_function(member, content) {
+                // An inline definition replaces an earlier prototype
+                match methods.iter_mut().find(|m| m.name == method.name && m.calls.is_empty()) {
+                    Some(existing) if member.kind() == "function_definition" => *existing = method,
+                    _ => methods.push(method),
+                }
+            }
+        }
+        methods
+    }
+
+    /// Name of a class/struct specifier, falling back to the typedef name for
+    /// `typedef struct { ... } Name;`
+    fn class_name(&self, node: Node, content: &str) -> Option<String> {
+        if let Some(name) = node.child_by_field_name("name") {
+            return Some(strip_type_arguments(&content[name.byte_range()]));
+        }
+        let parent = node.parent()?;
+        if parent.kind() != "type_definition" {
+            return None;
+        }
+        let declarator = parent.child_by_field_name("declarator")?;
+        (declarator.kind() == "type_identifier").then(|| content[declarator.byte_range()].to_string())
+    }
+}
+
+impl LanguageParser for CppParser {
+    fn parse_file(&self, path: &PathBuf, content: &str) -> Result<ParsedFile> {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        let (tree, language) = if extension == "c" {
+            (self.parse_tree(tree_sitter_c::language(), content)?, "c")
+        } else {
+            let tree = self.parse_tree(tree_sitter_cpp::language(), content)?;
+            if extension == "h" && tree.root_node().has_error() {
+                // C headers may use C++ keywords such as `new` or `class` as names
+                let c_tree = self.parse_tree(tree_sitter_c::language(), content)?;
+                if c_tree.root_node().has_error() {
+                    (tree, "cpp")
+                } else {
+                    (c_tree, "c")
+                }
+            } else {
+                (tree, "cpp")
+            }
+        };
+
+        let root_node = tree.root_node();
+        let mut functions = Vec::new();
+        let mut classes: Vec<ClassInfo> = Vec::new();
+        let mut imports = Vec::new();
+        let mut out_of_line = Vec::new();
+
+        // Walk everything except function bodies: declarations can sit inside
+        // namespaces, `extern "C"` blocks, templates and include guards
+        let mut stack = vec![root_node];
+        while let Some(node) = stack.pop() {
+            match node.kind() {
+                "preproc_include" => {
+                    if let Some(include) = node.child_by_field_name("path") {
+                        let header = content[include.byte_range()]
+                            .trim_matches(|c| c == '"' || c == '<' || c == '>')
+                            .trim();
+                        if !header.is_empty() {
+                            imports.push(header.to_string());
+                        }
+                    }
+                    continue;
+                }
+                "function_definition" => {
+                    if !is_class_member(node) {
+                        if let Some((scope, function)) = self.extract_function(node, content) {
+                            match scope {
+                                Some(scope) => out_of_line.push((scope, function)),
+                                None => functions.push(function),
+                            }
+                        }
+                    }
+                    continue;
+                }
+                "class_specifier" | "struct_specifier" => {
+                    if let (Some(body), Some(name)) = (node.child_by_field_name("body"), self.class_name(node, content)) {
+                        classes.push(ClassInfo {
+                            name,
+                            kind: None,
+                            inheritances: self.extract_inheritances(node, content),
+                            methods: self.extract_methods(body, content),
+                            start_line: node.start_position().row + 1,
+                            end_line: node.end_position().row + 1,
+                            body_hash: body_hash(node, content),
+                            decorators: Vec::new(),
+                            visibility: None,
+                        });
+                    }
+                }
+                _ => {}
+            }
+
+            let mut cursor = node.walk();
+            let children: Vec<Node> = node.named_children(&mut cursor).collect();
+            stack.extend(children.into_iter().rev());
+        }
+
+        // `void Foo::bar() {}` belongs to Foo when Foo is declared in this file;
+        // otherwise it is kept as a free function named after the method
+        for (scope, function) in out_of_line {
+            match classes.iter_mut().find(|c| c.name == scope) {
+                Some(class) => match class.methods.iter_mut().find(|m| m.name == function.name) {
+                    Some(existing) => *existing = function,
+                    None => class.methods.push(function),
+                },
+                None => functions.push(function),
+            }
+        }
+
+        let data_tables = extract_data_tables(root_node, content, STRING_KINDS, &imports);
+        let service_calls = self.extract_service_calls(content);
+        let sql_queries = extract_sql_queries(root_node, content, STRING_KINDS);
+
+        Ok(ParsedFile {
+            path: path.to_string_lossy().to_string(),
+            language: language.to_string(),
+            functions,
+            classes,
+            interfaces: Vec::new(),
+            imports,
+            data_tables,
+            service_calls,
+            sql_queries,
+            env_vars: extract_env_vars(content, CPP_PATTERNS),
+            is_test: is_test_file(&path.to_string_lossy(), content, CPP_SIGNALS),
+            generated: false,
+            parse_failed: false,
+            rendered_components: Vec::new(),
+            reexports: Vec::new(),
+            line_count: count_lines(content),
+        })
+    }
+}
+
+/// Whether a definition sits in a class body, possibly as a member template
+fn is_class_member(node: Node) -> bool {
+    let mut parent = node.parent();
+    while let Some(p) = parent.filter(|p| p.kind() == "template_declaration") {
+        parent = p.parent();
+    }
+    parent.is_some_and(|p| p.kind() == "field_declaration_list")
+}
+
+/// The `function_declarator` under pointer/reference wrappers such as
+/// `Foo* make()` or `Foo& get()`
+fn find_function_declarator(node: Node) -> Option<Node> {
+    match node.kind() {
+        "function_declarator" => Some(node),
+        "pointer_declarator" | "reference_declarator" | "parenthesized_declarator" | "attributed_declarator" => {
+            let inner = node.child_by_field_name("declarator").or_else(|| node.named_child(0))?;
+            find_function_declarator(inner)
+        }
+        _ => None,
+    }
+}
+
+/// Split a declarator name into its innermost scope and unqualified name:
+/// `Foo::bar` -> (Some("Foo"), "bar"), `ns::Foo<T>::~Foo` -> (Some("Foo"), "~Foo")
+fn split_declarator_name(node: Node, content: &str) -> (Option<String>, String) {
+    match node.kind() {
+        "qualified_identifier" => {
+            let scope = node
+                .child_by_field_name("scope")
+                .map(|s| strip_type_arguments(&content[s.byte_range()]));
+            match node.child_by_field_name("name") {
+                Some(name) => {
+                    let (inner_scope, name) = split_declarator_name(name, content);
+                    (inner_scope.or(scope), name)
+                }
+                None => (scope, String::new()),
+            }
+        }
+        "template_function" => {
+            let name = node
+                .child_by_field_name("name")
+                .map(|n| content[n.byte_range()].to_string())
+                .unwrap_or_default();
+            (None, name)
+        }
+        _ => (None, content[node.byte_range()].to_string()),
+    }
+}
+
+/// The identifier a parameter declarator introduces, e.g. `buf` in `char *buf[]`
+fn declarator_identifier(node: Node, content: &str) -> Option<String> {
+    if node.kind() == "identifier" {
+        return Some(content[node.byte_range()].to_string());
+    }
+    let inner = node.child_by_field_name("declarator").or_else(|| node.named_child(0))?;
+    declarator_identifier(inner, content)
+}
+
+fn strip_type_arguments(type_name: &str) -> String {
+    type_name
+        .split('<')
+        .next()
+        .unwrap_or(type_name)
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpp_classes_and_includes() {
+        let parser = CppParser::new().unwrap();
+        let content = r#"
+            #ifndef SHAPES_H
+            #define SHAPES_H
+
+            #include <vector>
+            #include "geometry/point.h"
+
+            namespace shapes {
+
+            class Shape {
+            public:
+                virtual ~Shape() = default;
+                virtual double area() const = 0;
+            };
+
+            class Circle : public Shape, private util::Named<Circle> {
+            public:
+                Circle(double radius);
+                double area() const override {
+                    return compute(radius_) * scale();
+                }
+                void draw();
+            private:
+                double radius_;
+            };
+
+            struct Point {
+                int x;
+                int y;
+            };
+
+            void Circle::draw() {
+                Renderer* r = new Renderer();
+                r->submit(this);
+                log_draw();
+            }
+
+            int count_shapes(const std::vector<Shape*>& items, int limit) {
+                if (items.empty() || limit == 0) {
+                    return 0;
+                }
+                return items.size();
+            }
+
+            }  // namespace shapes
+
+            #endif
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("include/shapes.hpp"), content).unwrap();
+        assert_eq!(result.language, "cpp");
+        assert_eq!(result.imports, vec!["vector", "geometry/point.h"]);
+
+        let circle = result.classes.iter().find(|c| c.name == "Circle").expect("Circle not found");
+        let bases: Vec<&str> = circle.inheritances.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(bases, vec!["Shape", "util::Named"]);
+        assert!(circle.methods.iter().any(|m| m.name == "Circle"));
+
+        let area = circle.methods.iter().find(|m| m.name == "area").expect("area not found");
+        assert!(area.calls.contains(&"compute".to_string()));
+        assert!(area.calls.contains(&"scale".to_string()));
+
+        // The out-of-line definition replaces the prototype
+        let draw = circle.methods.iter().find(|m| m.name == "draw").expect("draw not found");
+        assert_eq!(circle.methods.iter().filter(|m| m.name == "draw").count(), 1);
+        for call in ["Renderer", "submit", "log_draw"] {
+            assert!(draw.calls.contains(&call.to_string()), "missing call {}", call);
+        }
+
+        let shape = result.classes.iter().find(|c| c.name == "Shape").expect("Shape not found");
+        assert!(shape.methods.iter().any(|m| m.name == "~Shape"));
+        assert!(shape.methods.iter().any(|m| m.name == "area"));
+        assert!(result.classes.iter().any(|c| c.name == "Point" && c.methods.is_empty()));
+
+        assert_eq!(result.functions.len(), 1);
+        let count = &result.functions[0];
+        assert_eq!(count.name, "count_shapes");
+        assert_eq!(count.params, vec!["items", "limit"]);
+        assert_eq!(count.return_type.as_deref(), Some("int"));
+        assert_eq!(count.complexity, 3);
+    }
+
+    #[test]
+    fn test_templates_are_ignored_gracefully() {
+        let parser = CppParser::new().unwrap();
+        let content = r#"
+            template <typename T>
+            class Box : public Container<T> {
+            public:
+                template <typename U>
+                U convert() const { return U(value_); }
+                T get() const { return value_; }
+            private:
+                T value_;
+            };
+
+            template <typename T>
+            T max_of(T a, T b) {
+                return a > b ? a : b;
+            }
+
+            template <>
+            int max_of<int>(int a, int b) {
+                return std::max(a, b);
+            }
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("box.hpp"), content).unwrap();
+
+        let boxed = result.classes.iter().find(|c| c.name == "Box").expect("Box not found");
+        assert_eq!(boxed.inheritances[0].name, "Container");
+        let methods: Vec<&str> = boxed.methods.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(methods, vec!["convert", "get"]);
+
+        let names: Vec<&str> = result.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["max_of", "max_of"]);
+        assert_eq!(result.functions[0].params, vec!["a", "b"]);
+        assert!(result.functions[1].calls.contains(&"max".to_string()));
+    }
+
+    #[test]
+    fn test_parse_c_file() {
+        let parser = CppParser::new().unwrap();
+        let content = r#"
+            #include <stdio.h>
+            #include "db.h"
+
+            typedef struct {
+                int id;
+                char *name;
+            } user_t;
+
+            static int load_user(sqlite3 *db, int id, user_t *out) {
+                const char *sql = "SELECT id, name FROM users WHERE id = ?";
+                int class = prepare(db, sql);
+                while (step(class) == 0) {
+                    fill(out);
+                }
+                return 0;
+            }
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("src/users.c"), content).unwrap();
+        assert_eq!(result.language, "c");
+        assert_eq!(result.imports, vec!["stdio.h", "db.h"]);
+        assert!(result.classes.iter().any(|c| c.name == "user_t"));
+
+        let load = &result.functions[0];
+        assert_eq!(load.name, "load_user");
+        assert_eq!(load.params, vec!["db", "id", "out"]);
+        assert!(load.calls.contains(&"prepare".to_string()));
+        assert_eq!(load.complexity, 2);
+        assert_eq!(result.sql_queries.len(), 1);
+        assert!(result.data_tables.contains(&"users".to_string()));
+    }
+}
@@ -0,0 +1,164 @@
+//! Environment Variable Extraction
+//!
+//! Finds the environment variables a file reads so they can be stored as
+//! `EnvVar` nodes. Reads are matched with regexes over the source text; only
+//! literal names are recorded, so `os.getenv(name)` is skipped.
+
+use regex::Regex;
+use std::collections::BTreeSet;
+
+/// `process.env.X`, `process.env["X"]` and Vite's `import.meta.env.X`
+pub const JAVASCRIPT_PATTERNS: &[&str] = &[
+    r"\bprocess\.env\.([A-Za-z_][A-Za-z0-9_]*)",
+    r#"\bprocess\.env\[\s*['"`]([A-Za-z_][A-Za-z0-9_]*)['"`]\s*\]"#,
+    r"\bimport\.meta\.env\.([A-Za-z_][A-Za-z0-9_]*)",
+];
+
+/// `os.environ["X"]`, `os.environ.get("X")` and `os.getenv("X")`
+pub const PYTHON_PATTERNS: &[&str] = &[
+    r#"\bos\.environ\[\s*['"]([A-Za-z_][A-Za-z0-9_]*)['"]\s*\]"#,
+    r#"\bos\.environ\.get\(\s*['"]([A-Za-z_][A-Za-z0-9_]*)['"]"#,
+    r#"\bos\.getenv\(\s*['"]([A-Za-z_][A-Za-z0-9_]*)['"]"#,
+];
+
+/// `env::var("X")`, `env::var_os("X")` and the `env!`/`option_env!` macros
+pub const RUST_PATTERNS: &[&str] = &[
+    r#"\benv::var(?:_os)?\(\s*"([A-Za-z_][A-Za-z0-9_]*)""#,
+    r#"\b(?:option_)?env!\(\s*"([A-Za-z_][A-Za-z0-9_]*)""#,
+];
+
+/// `os.Getenv("X")` and `os.LookupEnv("X")`
+pub const GO_PATTERNS: &[&str] = &[r#"\bos\.(?:Getenv|LookupEnv)\(\s*"([A-Za-z_][A-Za-z0-9_]*)""#];
+
+/// `System.getenv("X")`, shared by Java and Kotlin
+pub const JVM_PATTERNS: &[&str] = &[r#"\bSystem\.getenv\(\s*"([A-Za-z_][A-Za-z0-9_]*)""#];
+
+/// `ProcessInfo.processInfo.environment["X"]`
+pub const SWIFT_PATTERNS: &[&str] = &[r#"\benvironment\[\s*"([A-Za-z_][A-Za-z0-9_]*)"\s*\]"#];
+
+/// `ENV["X"]` and `ENV.fetch("X")`
+pub const RUBY_PATTERNS: &[&str] = &[
+    r#"\bENV\[\s*['"]([A-Za-z_][A-Za-z0-9_]*)['"]\s*\]"#,
+    r#"\bENV\.fetch\(\s*['"]([A-Za-z_][A-Za-z0-9_]*)['"]"#,
+];
+
+/// Laravel's `env('X')`, `getenv('X')` and `$_ENV['X']`; `$app->env()`
+/// and `Config::env()` are not environment reads
+pub const PHP_PATTERNS: &[&str] = &[
+    r#"(?m)(?:^|[^\w>:$])(?:env|getenv)\(\s*['"]([A-Za-z_][A-Za-z0-9_]*)['"]"#,
+    r#"\$_ENV\[\s*['"]([A-Za-z_][A-Za-z0-9_]*)['"]\s*\]"#,
+];
+
+/// `getenv("X")`, `std::getenv("X")` and `secure_getenv("X")`
+pub const CPP_PATTERNS: &[&str] = &[r#"\b(?:secure_)?getenv\(\s*"([A-Za-z_][A-Za-z0-9_]*)""#];
+
+/// Names of the environment variables `content` reads, sorted and
+/// deduplicated. Each pattern captures the name in its first group.
+pub fn extract_env_vars(content: &str, patterns: &[&str]) -> Vec<String> {
+    let mut names = BTreeSet::new();
+
+    for pattern in patterns {
+        let re = Regex::new(pattern).unwrap();
+        for cap in re.captures_iter(content) {
+            names.insert(cap[1].to_string());
+        }
+    }
+
+    names.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_javascript_env_reads() {
+        let content = r#"
+            const url = process.env.DATABASE_URL;
+            const key = process.env['API_KEY'] || process.env.DATABASE_URL;
+            const mode = import.meta.env.VITE_MODE;
+            const dynamic = process.env[name];
+        "#;
+        assert_eq!(
+            extract_env_vars(content, JAVASCRIPT_PATTERNS),
+            vec!["API_KEY", "DATABASE_URL", "VITE_MODE"]
+        );
+    }
+
+    #[test]
+    fn test_python_env_reads() {
+        let content = r#"
+url = os.environ["DATABASE_URL"]
+debug = os.environ.get('DEBUG', "0")
+secret = os.getenv("SECRET_KEY")
+other = os.getenv(name)
+"#;
+        assert_eq!(
+            extract_env_vars(content, PYTHON_PATTERNS),
+            vec!["DATABASE_URL", "DEBUG", "SECRET_KEY"]
+        );
+    }
+
+    #[test]
+    fn test_rust_env_reads() {
+        let content = r#"
+            let url = env::var("DATABASE_URL")?;
+            let home = std::env::var_os("HOME");
+            const VERSION: &str = env!("CARGO_PKG_VERSION");
+            let tag = option_env!("BUILD_TAG");
+        "#;
+        assert_eq!(
+            extract_env_vars(content, RUST_PATTERNS),
+            vec!["BUILD_TAG", "CARGO_PKG_VERSION", "DATABASE_URL", "HOME"]
+        );
+    }
+
+    #[test]
+    fn test_go_env_reads() {
+        let content = r#"
+            port := os.Getenv("PORT")
+            if v, ok := os.LookupEnv("REDIS_URL"); ok {}
+        "#;
+        assert_eq!(extract_env_vars(content, GO_PATTERNS), vec!["PORT", "REDIS_URL"]);
+    }
+
+    #[test]
+    fn test_jvm_swift_and_cpp_env_reads() {
+        assert_eq!(
+            extract_env_vars(r#"String url = System.getenv("DATABASE_URL");"#, JVM_PATTERNS),
+            vec!["DATABASE_URL"]
+        );
+        assert_eq!(
+            extract_env_vars(
+                r#"let token = ProcessInfo.processInfo.environment["API_TOKEN"]"#,
+                SWIFT_PATTERNS
+            ),
+            vec!["API_TOKEN"]
+        );
+        assert_eq!(
+            extract_env_vars(r#"const char* home = std::getenv("HOME");"#, CPP_PATTERNS),
+            vec!["HOME"]
+        );
+    }
+
+    #[test]
+    fn test_ruby_env_reads() {
+        let content = r#"url = ENV["DATABASE_URL"]
+key = ENV.fetch('SECRET_KEY_BASE', nil)
+ENV[name]
+"#;
+        assert_eq!(extract_env_vars(content, RUBY_PATTERNS), vec!["DATABASE_URL", "SECRET_KEY_BASE"]);
+    }
+
+    #[test]
+    fn test_php_env_reads() {
+        let content = r#"<?php
+$key = env('APP_KEY');
+$level = getenv("LOG_LEVEL");
+$url = $_ENV['DATABASE_URL'];
+$local = $app->env('local');
+env($name);
+"#;
+        assert_eq!(extract_env_vars(content, PHP_PATTERNS), vec!["APP_KEY", "DATABASE_URL", "LOG_LEVEL"]);
+    }
+}
@@ -0,0 +1,329 @@
+//! JavaScript and TypeScript Exports
+//!
+//! What a module exports, for the visibility of its functions and classes,
+//! and what it re-exports from other modules. A module-level declaration is
+//! public when it sits in an `export` statement (`export function f`,
+//! `export default class C`, `export const f = () => ...`) or is named in an
+//! export clause without a source (`export { f, C as D }`,
+//! `export default f`), and private otherwise. Class members are public
+//! unless a TypeScript accessibility modifier says otherwise.
+//!
+//! Clauses with a source (`export { Button } from './button'`,
+//! `export * from './icons'`) are kept as the file's `reexports`. A symbol
+//! re-exported by name is marked public in the file defining it once every
+//! file is parsed, see `reexported_symbols`. Names imported from a barrel
+//! file (an `index.ts` re-exporting other modules) are followed to the
+//! module defining them, see `Barrels`.
+
+use std::collections::{HashMap, HashSet};
+use tree_sitter::Node;
+
+use super::{is_public, ParsedFile, ReExport};
+use crate::import_resolver::ImportResolver;
+
+/// Longest chain of re-exports followed from a barrel to a definition
+pub const MAX_REEXPORT_DEPTH: usize = 8;
+
+/// The export statements of one module
+#[derive(Debug, Default)]
+pub struct ModuleExports {
+    /// Local names exported by a clause without a source
+    local: HashSet<String>,
+    pub reexports: Vec<ReExport>,
+}
+
+/// Read the module-level export statements under `root`
+pub fn module_exports(root: Node, content: &str) -> ModuleExports {
+    let text = |node: Node| content[node.byte_range()].to_string();
+    let mut exports = ModuleExports::default();
+
+    let mut cursor = root.walk();
+    for statement in root.named_children(&mut cursor).filter(|n| n.kind() == "export_statement") {
+        let source = statement
+            .child_by_field_name("source")
+            .map(|source| text(source).trim_matches(|c| c == '"' || c == '\'' || c == '`').to_string());
+
+        // `export default f`
+        if let Some(value) = statement.child_by_field_name("value").filter(|v| v.kind() == "identifier") {
+            exports.local.insert(text(value));
+        }
+
+        let mut statement_cursor = statement.walk();
+        let children: Vec<Node> = statement.named_children(&mut statement_cursor).collect();
+        match children.iter().find(|child| child.kind() == "export_clause") {
+            Some(clause) => {
+                let mut clause_cursor = clause.walk();
+                for specifier in clause.named_children(&mut clause_cursor).filter(|n| n.kind() == "export_specifier") {
+                    let Some(name) = specifier.child_by_field_name("name").or_else(|| specifier.named_child(0)) else {
+                        continue;
+                    };
+                    let alias = specifier.child_by_field_name("alias").map(text);
+                    match &source {
+                        Some(source) => exports.reexports.push(ReExport {
+                            source: source.clone(),
+                            name: Some(text(name)),
+                            alias,
+                        }),
+                        None => {
+                            exports.local.insert(text(name));
+                        }
+                    }
+                }
+            }
+            // `export * from './x'` and `export * as ns from './x'`
+            None => {
+                if let Some(source) = source {
+                    let alias = children
+                        .iter()
+                        .find(|child| child.kind() == "namespace_export")
+                        .and_then(|namespace| namespace.named_child(0))
+                        .map(text);
+                    exports.reexports.push(ReExport { source, name: None, alias });
+                }
+            }
+        }
+    }
+
+    exports
+}
+
+impl ModuleExports {
+    /// Visibility of a function or class declaration; for a function held
+    /// in a variable, `declaration` is its `variable_declarator`
+    pub fn visibility(&self, declaration: Node, name: &str) -> String {
+        let statement = match declaration.kind() {
+            "variable_declarator" => declaration.parent().unwrap_or(declaration),
+            _ => declaration,
+        };
+        let exported = match statement.parent().map(|parent| parent.kind()) {
+            Some("export_statement") => true,
+            Some("program") => self.local.contains(name),
+            _ => false,
+        };
+        if exported { "public" } else { "private" }.to_string()
+    }
+}
+
+/// Visibility of a class member: its accessibility modifier, or public
+pub fn member_visibility(member: Node, content: &str) -> String {
+    let mut cursor = member.walk();
+    let modifier = member.children(&mut cursor).find(|child| child.kind() == "accessibility_modifier");
+    modifier.map_or("public", |modifier| &content[modifier.byte_range()]).to_string()
+}
+
+/// The functions and classes other files re-export by name, as `(defining
+/// file, name)`, when the defining file is among `parsed_files`. Wildcard
+/// re-exports only pass on what their module already exports.
+pub fn reexported_symbols(parsed_files: &[ParsedFile], import_resolver: &ImportResolver) -> Vec<(String, String)> {
+    let file_paths: HashSet<&str> = parsed_files.iter().map(|file| file.path.as_str()).collect();
+    let mut reexported: Vec<(String, String)> = Vec::new();
+    for file in parsed_files.iter() {
+        for reexport in &file.reexports {
+            let Some(name) = &reexport.name else {
+                continue;
+            };
+            if let Some(target) = import_resolver.resolve_ts_module(&reexport.source, &file.path, &file_paths) {
+                reexported.push((target, name.clone()));
+            }
+        }
+    }
+
+    reexported
+}
+
+/// Mark the `reexported_symbols` defined in `parsed_files` public. Files
+/// are independent, so streamed files can be marked a chunk at a time.
+pub fn mark_reexported_public(parsed_files: &mut [ParsedFile], reexported: &[(String, String)]) {
+    let mut names: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for (target, name) in reexported {
+        names.entry(target.as_str()).or_default().insert(name.as_str());
+    }
+    for file in parsed_files.iter_mut() {
+        let Some(names) = names.get(file.path.as_str()) else {
+            continue;
+        };
+        let public = Some("public".to_string());
+        for function in file.functions.iter_mut().filter(|function| names.contains(function.name.as_str())) {
+            function.visibility = public.clone();
+        }
+        for class in file.classes.iter_mut().filter(|class| names.contains(class.name.as_str())) {
+            class.visibility = public.clone();
+        }
+    }
+}
+
+/// The functions and classes each JavaScript and TypeScript module defines
+/// and the modules it re-exports, for following a name imported from a
+/// barrel to the module defining it
+#[derive(Debug, Default)]
+pub struct Barrels {
+    modules: HashMap<String, BarrelModule>,
+}
+
+#[derive(Debug, Default)]
+struct BarrelModule {
+    /// Top-level functions and classes, and whether each is public
+    defined: HashMap<String, bool>,
+    /// Re-exports whose module is among the parsed files, as `(module,
+    /// name, alias)`
+    reexports: Vec<(String, Option<String>, Option<String>)>,
+}
+
+impl Barrels {
+    /// Index the JavaScript and TypeScript files among `parsed_files`, which
+    /// needn't hold call lists
+    pub fn from_files(parsed_files: &[ParsedFile], import_resolver: &ImportResolver) -> Self {
+        let scripts: Vec<&ParsedFile> = parsed_files
+            .iter()
+            .filter(|file| matches!(file.language.as_str(), "typescript" | "javascript"))
+            .collect();
+        let file_paths: HashSet<&str> = scripts.iter().map(|file| file.path.as_str()).collect();
+
+        let mut modules = HashMap::new();
+        for file in scripts {
+            let functions = file.functions.iter().map(|function| (&function.name, &function.visibility));
+            let classes = file.classes.iter().map(|class| (&class.name, &class.visibility));
+            let defined = functions
+                .chain(classes)
+                .map(|(name, visibility)| (name.clone(), is_public(visibility.as_deref())))
+                .collect();
+            let reexports = file
+                .reexports
+                .iter()
+                .filter_map(|reexport| {
+                    let module = import_resolver.resolve_ts_module(&reexport.source, &file.path, &file_paths)?;
+                    Some((module, reexport.name.clone(), reexport.alias.clone()))
+                })
+                .collect();
+            modules.insert(file.path.clone(), BarrelModule { defined, reexports });
+        }
+        Self { modules }
+    }
+
+    /// Whether `path` re-exports other modules
+    pub fn is_barrel(&self, path: &str) -> bool {
+        self.modules.get(path).is_some_and(|module| !module.reexports.is_empty())
+    }
+
+    /// The module defining what `module` exports as `name`, and the name it
+    /// has there. Named re-exports are tried before wildcard ones, which
+    /// only pass on public symbols; `export * as ns` is not followed.
+    /// Chains stop after `MAX_REEXPORT_DEPTH` modules or when they come
+    /// back to a module they passed through.
+    pub fn resolve(&self, module: &str, name: &str) -> Option<(String, String)> {
+        self.find(module, name, false, 0, &mut HashSet::new())
+    }
+
+    fn find(
+        &self,
+        path: &str,
+        name: &str,
+        wildcard: bool,
+        depth: usize,
+        visiting: &mut HashSet<String>,
+    ) -> Option<(String, String)> {
+        let module = self.modules.get(path)?;
+        if let Some(public) = module.defined.get(name) {
+            return (*public || !wildcard).then(|| (path.to_string(), name.to_string()));
+        }
+        if depth == MAX_REEXPORT_DEPTH || !visiting.insert(path.to_string()) {
+            return None;
+        }
+
+        let named = module.reexports.iter().find_map(|(target, original, alias)| {
+            let original = original.as_deref()?;
+            (alias.as_deref().unwrap_or(original) == name)
+                .then(|| self.find(target, original, false, depth + 1, visiting))
+                .flatten()
+        });
+        let found = named.or_else(|| {
+            module
+                .reexports
+                .iter()
+                .filter(|(_, original, alias)| original.is_none() && alias.is_none())
+                .find_map(|(target, _, _)| self.find(target, name, true, depth + 1, visiting))
+        });
+        visiting.remove(path);
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{typescript::TypeScriptParser, LanguageParser};
+    use std::path::PathBuf;
+
+    fn parse(path: &str, content: &str) -> ParsedFile {
+        TypeScriptParser::new().unwrap().parse_file(&PathBuf::from(path), content).unwrap()
+    }
+
+    fn visibility<'a>(file: &'a ParsedFile, name: &str) -> Option<&'a str> {
+        let function = file.functions.iter().find(|f| f.name == name).map(|f| &f.visibility);
+        let class = file.classes.iter().find(|c| c.name == name).map(|c| &c.visibility);
+        function.or(class).unwrap().as_deref()
+    }
+
+    #[test]
+    fn test_reexports_are_recorded() {
+        let content = r#"
+            export { Button, Size as ButtonSize } from './button';
+            export * from './icons';
+            export * as forms from "./forms";
+            export { helper };
+            function helper() {}
+        "#;
+        let file = parse("src/ui/index.ts", content);
+        let reexport = |source: &str, name: Option<&str>, alias: Option<&str>| ReExport {
+            source: source.to_string(),
+            name: name.map(str::to_string),
+            alias: alias.map(str::to_string),
+        };
+        assert_eq!(
+            file.reexports,
+            vec![
+                reexport("./button", Some("Button"), None),
+                reexport("./button", Some("Size"), Some("ButtonSize")),
+                reexport("./icons", None, None),
+                reexport("./forms", None, Some("forms")),
+            ]
+        );
+        assert_eq!(visibility(&file, "helper"), Some("public"));
+    }
+
+    #[test]
+    fn test_reexported_symbols_become_public() {
+        // `Button` is only public through the barrel; `Internal` isn't re-exported
+        let mut files = vec![
+            parse("src/ui/index.ts", "export { Button } from './button';\nexport { Nope } from './missing';\n"),
+            parse("src/ui/button.ts", "function Button() {}\nclass Internal {}\n"),
+        ];
+        assert_eq!(visibility(&files[1], "Button"), Some("private"));
+
+        let reexported = reexported_symbols(&files, &ImportResolver::default());
+        assert_eq!(reexported, vec![("src/ui/button.ts".to_string(), "Button".to_string())]);
+        mark_reexported_public(&mut files, &reexported);
+        assert_eq!(visibility(&files[1], "Button"), Some("public"));
+        assert_eq!(visibility(&files[1], "Internal"), Some("private"));
+    }
+
+    #[test]
+    fn test_barrels_follow_reexport_chains() {
+        let files = vec![
+            parse("src/a/index.ts", "export * from '../b';\nexport { Card as Tile } from '../card';\n"),
+            parse("src/b/index.ts", "export * from '../a';\nexport * from './impl';\n"),
+            parse("src/b/impl.ts", "export function run() {}\nfunction hidden() {}\n"),
+            parse("src/card.ts", "class Card {}\n"),
+        ];
+        let barrels = Barrels::from_files(&files, &ImportResolver::default());
+        let resolve = |name: &str| barrels.resolve("src/a/index.ts", name);
+
+        assert!(barrels.is_barrel("src/a/index.ts") && !barrels.is_barrel("src/card.ts"));
+        assert_eq!(resolve("run"), Some(("src/b/impl.ts".to_string(), "run".to_string())));
+        // Named re-exports pass on private symbols, wildcards don't
+        assert_eq!(resolve("Tile"), Some(("src/card.ts".to_string(), "Card".to_string())));
+        assert_eq!(resolve("hidden"), None);
+        // The circular barrels give up instead of looping
+        assert_eq!(resolve("missing"), None);
+    }
+}
@@ -1,10 +1,24 @@
-use super::{ClassInfo, FunctionInfo, LanguageParser, ParsedFile, ServiceCall};
+use super::{ClassInfo, FunctionInfo, InheritanceInfo, InterfaceInfo, LanguageParser, MethodSignature, ParsedFile, ServiceCall};
+use super::{body_hash, count_lines, cyclomatic_complexity};
+use super::env_vars::{extract_env_vars, GO_PATTERNS};
+use super::test_files::{is_test_file, GO_SIGNALS};
+use super::sql_extractor::extract_data_tables;
+use super::sql_queries::extract_sql_queries;
+use crate::url_utils::canonical_host;
 use anyhow::{Context, Result};
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use tree_sitter::{Node, Parser, Query, QueryCursor};
 
+/// Node kinds of string literals, searched for SQL
+const STRING_KINDS: &[&str] = &["interpreted_string_literal", "raw_string_literal"];
+
+/// Node kinds that add a decision point to a function's complexity
+const BRANCH_KINDS: &[&str] = &[
+    "if_statement", "for_statement", "expression_case", "type_case", "communication_case", "&&", "||",
+];
+
 pub struct GoParser;
 
 impl GoParser {
@@ -12,30 +26,6 @@ impl GoParser {
         Ok(GoParser)
     }
 
-    fn extract_data_tables(&self, content: &str) -> Vec<String> {
-        let mut tables = HashSet::new();
-        let patterns = [
-            r"(?i)\bfrom\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bjoin\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\binto\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bupdate\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bdelete\s+from\s+([a-zA-Z0-9_.]+)",
-            r#"(?i)\btable\(\s*['"]([a-zA-Z0-9_.]+)['"]"#,
-        ];
-
-        for pattern in patterns {
-            if let Ok(re) = Regex::new(pattern) {
-                for cap in re.captures_iter(content) {
-                    if let Some(m) = cap.get(1) {
-                        tables.insert(m.as_str().to_string());
-                    }
-                }
-            }
-        }
-
-        tables.into_iter().collect()
-    }
-
     fn extract_service_calls(&self, content: &str) -> Vec<ServiceCall> {
         let mut services = HashSet::new();
         let url_pattern = r#"(?i)\b(https?|grpc)://[^\s'"`]+"#;
@@ -44,36 +34,69 @@ impl GoParser {
             for cap in re.captures_iter(content) {
                 let full = cap.get(0).map(|m| m.as_str()).unwrap_or_default();
                 let protocol = cap.get(1).map(|m| m.as_str()).unwrap_or("http");
-                if let Some(target) = extract_service_target(full) {
-                    services.insert((target, protocol.to_string()));
+                if let Some(host) = canonical_host(full) {
+                    services.insert((host, protocol.to_string()));
                 }
             }
         }
 
         services
             .into_iter()
-            .map(|(target, protocol)| ServiceCall { target, protocol })
+            .map(|(host, protocol)| ServiceCall::new(host, protocol))
             .collect()
     }
 
+    /// Parameter names; unnamed parameters are recorded as `_`, so the
+    /// length is always the parameter count
     fn extract_params(&self, node: Node, content: &str) -> Vec<String> {
         let mut params = Vec::new();
         let mut cursor = node.walk();
         for child in node.children(&mut cursor) {
-             if child.kind() == "parameter_declaration" {
-                 let mut param_cursor = child.walk();
-                 for pc in child.children(&mut param_cursor) {
-                     if pc.kind() == "identifier" {
-                         params.push(content[pc.byte_range()].to_string());
-                     }
+             if child.kind() == "parameter_declaration" || child.kind() == "variadic_parameter_declaration" {
+                 let mut name_cursor = child.walk();
+                 let names: Vec<String> = child
+                     .children_by_field_name("name", &mut name_cursor)
+                     .map(|name| content[name.byte_range()].to_string())
+                     .collect();
+                 if names.is_empty() {
+                     params.push("_".to_string());
+                 } else {
+                     params.extend(names);
                  }
              }
         }
         params
     }
 
+    /// Method set and embedded types of an `interface { ... }` body. Type
+    /// set elements such as `~int | ~string` are kept as embeds too, so the
+    /// method set never looks complete when it isn't.
+    fn extract_interface(&self, name: String, body: Node, content: &str) -> InterfaceInfo {
+        let mut methods = Vec::new();
+        let mut embeds = Vec::new();
+        let mut cursor = body.walk();
+        for child in body.named_children(&mut cursor) {
+            match child.kind() {
+                "method_spec" => {
+                    if let (Some(method), Some(params)) =
+                        (child.child_by_field_name("name"), child.child_by_field_name("parameters"))
+                    {
+                        methods.push(MethodSignature {
+                            name: content[method.byte_range()].to_string(),
+                            arity: self.extract_params(params, content).len(),
+                        });
+                    }
+                }
+                "comment" => {}
+                _ => embeds.push(content[child.byte_range()].trim().to_string()),
+            }
+        }
+        InterfaceInfo { name, methods, embeds }
+    }
+
     fn extract_calls(&self, node: Node, content: &str, query: &Query) -> Vec<String> {
-        let mut calls = HashSet::new();
+        let mut calls = Vec::new();
+        let mut call_sites = HashSet::new();
         let mut query_cursor = QueryCursor::new();
         let matches = query_cursor.matches(query, node, content.as_bytes());
         for m in matches {
@@ -81,11 +104,13 @@ impl GoParser {
                  let capture_name = &query.capture_names()[capture.index as usize];
                  if capture_name == "call.name" {
                      let call_name = content[capture.node.byte_range()].to_string();
-                     calls.insert(call_name);
+                     if call_sites.insert(capture.node.id()) {
+                         calls.push(call_name);
+                     }
                  }
             }
         }
-        calls.into_iter().collect()
+        calls
     }
 }
 
@@ -135,6 +160,18 @@ impl LanguageParser for GoParser {
             "#,
         )?;
 
+        let interface_query = Query::new(
+            tree_sitter_go::language(),
+            r#"
+            (type_declaration
+              (type_spec
+                name: (type_identifier) @name
+                type: (interface_type) @body
+              )
+            ) @def
+            "#,
+        )?;
+
         let call_query = Query::new(
              tree_sitter_go::language(),
              r#"
@@ -150,7 +187,7 @@ impl LanguageParser for GoParser {
         let import_query = Query::new(
             tree_sitter_go::language(),
             r#"
-            (import_spec path: (string_literal) @import.source)
+            (import_spec path: (interpreted_string_literal) @import.source)
             "#,
         )?;
 
@@ -171,15 +208,62 @@ impl LanguageParser for GoParser {
             }
             if !name.is_empty() {
                 class_map.insert(name.clone(), ClassInfo {
+                    visibility: Some(visibility(&name)),
                     name,
+                    kind: None,
                     inheritances: Vec::new(),
                     methods: Vec::new(),
                     start_line: node.start_position().row + 1,
                     end_line: node.end_position().row + 1,
+                    body_hash: body_hash(node, content),
+                    decorators: Vec::new(),
                 });
             }
         }
 
+        // 1b. Extract Interfaces. Embedded interfaces are their parents.
+        let mut interfaces = Vec::new();
+        let iface_matches = query_cursor.matches(&interface_query, root_node, content.as_bytes());
+        for m in iface_matches {
+            let mut name = String::new();
+            let mut node = root_node;
+            let mut body_node = root_node;
+            for c in m.captures {
+                let cn = &interface_query.capture_names()[c.index as usize];
+                if cn == "name" {
+                    name = content[c.node.byte_range()].to_string();
+                } else if cn == "def" {
+                    node = c.node;
+                } else if cn == "body" {
+                    body_node = c.node;
+                }
+            }
+            if !name.is_empty() {
+                let interface = self.extract_interface(name.clone(), body_node, content);
+                let inheritances = interface
+                    .embeds
+                    .iter()
+                    .filter(|embed| embed.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.'))
+                    .map(|embed| InheritanceInfo {
+                        name: embed.clone(),
+                        kind: "interface".to_string(),
+                    })
+                    .collect();
+                class_map.insert(name.clone(), ClassInfo {
+                    visibility: Some(visibility(&name)),
+                    name,
+                    kind: None,
+                    inheritances,
+                    methods: Vec::new(),
+                    start_line: node.start_position().row + 1,
+                    end_line: node.end_position().row + 1,
+                    body_hash: body_hash(node, content),
+                    decorators: Vec::new(),
+                });
+                interfaces.push(interface);
+            }
+        }
+
         // 2. Extract Functions and Methods
         let func_matches = query_cursor.matches(&func_query, root_node, content.as_bytes());
         for m in func_matches {
@@ -210,12 +294,18 @@ impl LanguageParser for GoParser {
                 let calls = self.extract_calls(node, content, &call_query);
                 
                 let func_info = FunctionInfo {
+                    visibility: Some(visibility(&name)),
                     name: name.clone(),
                     params,
-                    return_type: None,
+                    return_type: node
+                        .child_by_field_name("result")
+                        .map(|t| content[t.byte_range()].to_string()),
                     calls,
                     start_line: node.start_position().row + 1,
                     end_line: node.end_position().row + 1,
+                    complexity: cyclomatic_complexity(node, BRANCH_KINDS),
+                    body_hash: body_hash(node, content),
+                    decorators: Vec::new(),
                 };
 
                 if let Some(rn) = receiver_node {
@@ -234,11 +324,15 @@ impl LanguageParser for GoParser {
 
                     if !receiver_type_name.is_empty() {
                          let entry = class_map.entry(receiver_type_name.clone()).or_insert(ClassInfo {
+                             visibility: Some(visibility(&receiver_type_name)),
                              name: receiver_type_name,
+                             kind: None,
                              inheritances: Vec::new(),
                              methods: Vec::new(),
                              start_line: 0,
                              end_line: 0,
+                             body_hash: String::new(),
+                             decorators: Vec::new(),
                          });
                          entry.methods.push(func_info);
                     } else {
@@ -262,32 +356,35 @@ impl LanguageParser for GoParser {
              }
         }
 
-        let data_tables = self.extract_data_tables(content);
+        let data_tables = extract_data_tables(root_node, content, STRING_KINDS, &imports);
         let service_calls = self.extract_service_calls(content);
+        let sql_queries = extract_sql_queries(root_node, content, STRING_KINDS);
 
         Ok(ParsedFile {
             path: path.to_string_lossy().to_string(),
             language: "go".to_string(),
             functions,
             classes: class_map.into_values().collect(),
+            interfaces,
             imports,
             data_tables,
             service_calls,
+            sql_queries,
+            env_vars: extract_env_vars(content, GO_PATTERNS),
+            is_test: is_test_file(&path.to_string_lossy(), content, GO_SIGNALS),
+            generated: false,
+            parse_failed: false,
+            rendered_components: Vec::new(),
+            reexports: Vec::new(),
+            line_count: count_lines(content),
         })
     }
 }
 
-fn extract_service_target(url: &str) -> Option<String> {
-    let parts: Vec<&str> = url.split("//").collect();
-    let host_part = parts.get(1).copied().unwrap_or("");
-    let host = host_part.split('/').next().unwrap_or("");
-    let host = host.split('?').next().unwrap_or("");
-    let host = host.split('#').next().unwrap_or("");
-    if host.is_empty() {
-        None
-    } else {
-        Some(host.to_string())
-    }
+/// Exported identifiers start with an upper-case letter
+fn visibility(name: &str) -> String {
+    let exported = name.chars().next().is_some_and(char::is_uppercase);
+    if exported { "public" } else { "private" }.to_string()
 }
 
 #[cfg(test)]
@@ -314,6 +411,14 @@ mod tests {
                 http.ListenAndServe(":", nil)
             }
 
+            func (s *Server) Port() int {
+                return s.port
+            }
+
+            func dial(addr string) (*Server, error) {
+                return nil, nil
+            }
+
             func main() {
                 server := Server{port: 8080}
                 server.Start()
@@ -328,10 +433,61 @@ mod tests {
         
         // Structs
         let server = result.classes.iter().find(|c| c.name == "Server").expect("Server struct not found");
-        assert!(server.methods.iter().any(|m| m.name == "Start"));
+        let start = server.methods.iter().find(|m| m.name == "Start").unwrap();
+        assert_eq!(start.return_type, None);
+        let port = server.methods.iter().find(|m| m.name == "Port").unwrap();
+        assert_eq!(port.return_type.as_deref(), Some("int"));
         
         // Functions
         let main = result.functions.iter().find(|f| f.name == "main").expect("main not found");
         assert!(main.calls.contains(&"Start".to_string()));
+        let dial = result.functions.iter().find(|f| f.name == "dial").unwrap();
+        assert_eq!(dial.return_type.as_deref(), Some("(*Server, error)"));
+
+        // Visibility follows the identifier's case
+        assert_eq!(server.visibility.as_deref(), Some("public"));
+        assert_eq!(start.visibility.as_deref(), Some("public"));
+        assert_eq!(dial.visibility.as_deref(), Some("private"));
+        assert_eq!(main.visibility.as_deref(), Some("private"));
+    }
+
+    #[test]
+    fn test_parse_go_interfaces() {
+        let parser = GoParser::new().unwrap();
+        let content = r#"
+            package cache
+
+            type Cache interface {
+                // Get returns the cached value
+                Get(key string) ([]byte, bool)
+                Set(string, []byte, time.Duration)
+                Tags(keys ...string) []string
+                io.Closer
+            }
+
+            type Number interface {
+                ~int | ~float64
+            }
+
+            func Join(sep string, parts ...string) string { return "" }
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("cache.go"), content).unwrap();
+        let cache = result.interfaces.iter().find(|i| i.name == "Cache").expect("Cache not found");
+        let methods: Vec<(&str, usize)> = cache.methods.iter().map(|m| (m.name.as_str(), m.arity)).collect();
+        assert_eq!(methods, vec![("Get", 1), ("Set", 3), ("Tags", 1)]);
+        assert_eq!(cache.embeds, vec!["io.Closer"]);
+
+        // Interfaces are classes too, with embeds as parents
+        let class = result.classes.iter().find(|c| c.name == "Cache").unwrap();
+        assert!(class.methods.is_empty());
+        assert!(class.inheritances.iter().any(|i| i.name == "io.Closer" && i.kind == "interface"));
+
+        let number = result.interfaces.iter().find(|i| i.name == "Number").unwrap();
+        assert!(number.methods.is_empty() && number.embeds.len() == 1);
+        assert!(result.classes.iter().find(|c| c.name == "Number").unwrap().inheritances.is_empty());
+
+        let join = result.functions.iter().find(|f| f.name == "Join").unwrap();
+        assert_eq!(join.params, vec!["sep", "parts"]);
     }
 }
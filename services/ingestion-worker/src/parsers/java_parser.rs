@@ -0,0 +1,419 @@
+use super::{ClassInfo, FunctionInfo, InheritanceInfo, LanguageParser, ParsedFile, ServiceCall};
+use super::{body_hash, decorators, count_lines, cyclomatic_complexity};
+use super::env_vars::{extract_env_vars, JVM_PATTERNS};
+use super::test_files::{is_test_file, JVM_SIGNALS};
+use super::sql_extractor::extract_data_tables;
+use super::sql_queries::extract_sql_queries;
+use crate::url_utils::canonical_host;
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tree_sitter::{Node, Parser, Query, QueryCursor};
+
+/// Node kinds of string literals, searched for SQL
+const STRING_KINDS: &[&str] = &["string_literal"];
+
+/// Node kinds that add a decision point to a function's complexity
+const BRANCH_KINDS: &[&str] = &[
+    "if_statement", "for_statement", "enhanced_for_statement", "while_statement", "do_statement",
+    "case", "catch_clause", "ternary_expression", "&&", "||",
+];
+
+pub struct JavaParser;
+
+impl JavaParser {
+    pub fn new() -> Result<Self> {
+        Ok(JavaParser)
+    }
+
+    fn extract_service_calls(&self, content: &str) -> Vec<ServiceCall> {
+        let mut services = HashSet::new();
+        let url_pattern = r#"(?i)\b(https?|grpc)://[^\s'"`]+"#;
+
+        if let Ok(re) = Regex::new(url_pattern) {
+            for cap in re.captures_iter(content) {
+                let full = cap.get(0).map(|m| m.as_str()).unwrap_or_default();
+                let protocol = cap.get(1).map(|m| m.as_str()).unwrap_or("http");
+                if let Some(host) = canonical_host(full) {
+                    services.insert((host, protocol.to_string()));
+                }
+            }
+        }
+
+        services
+            .into_iter()
+            .map(|(host, protocol)| ServiceCall::new(host, protocol))
+            .collect()
+    }
+
+    fn extract_params(&self, node: Node, content: &str) -> Vec<String> {
+        let mut params = Vec::new();
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            match child.kind() {
+                "formal_parameter" => {
+                    if let Some(name) = child.child_by_field_name("name") {
+                        params.push(content[name.byte_range()].to_string());
+                    }
+                }
+                "spread_parameter" => {
+                    // `String... args` keeps its name inside a variable_declarator
+                    let mut inner = child.walk();
+                    for part in child.children(&mut inner) {
+                        if part.kind() == "variable_declarator" {
+                            if let Some(name) = part.child_by_field_name("name") {
+                                params.push(content[name.byte_range()].to_string());
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        params
+    }
+
+    fn extract_calls(&self, node: Node, content: &str, query: &Query) -> Vec<String> {
+        let mut calls = Vec::new();
+        let mut call_sites = HashSet::new();
+        let mut query_cursor = QueryCursor::new();
+        let matches = query_cursor.matches(query, node, content.as_bytes());
+        for m in matches {
+            for capture in m.captures {
+                let capture_name = &query.capture_names()[capture.index as usize];
+                if capture_name == "call.name" {
+                    let call_name = content[capture.node.byte_range()].to_string();
+                    if call_sites.insert(capture.node.id()) {
+                        calls.push(call_name);
+                    }
+                }
+            }
+        }
+        calls
+    }
+
+    fn extract_inheritances(&self, node: Node, content: &str) -> Vec<InheritanceInfo> {
+        let mut inheritances = Vec::new();
+
+        if let Some(superclass) = node.child_by_field_name("superclass") {
+            let mut cursor = superclass.walk();
+            for child in superclass.named_children(&mut cursor) {
+                inheritances.push(InheritanceInfo {
+                    name: strip_type_arguments(&content[child.byte_range()]),
+                    kind: "class".to_string(),
+                });
+            }
+        }
+
+        // Classes, enums and records use `interfaces: (super_interfaces)`, while
+        // interfaces list their parents in an unnamed `extends_interfaces` child.
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.kind() != "super_interfaces" && child.kind() != "extends_interfaces" {
+                continue;
+            }
+            let mut list_cursor = child.walk();
+            for type_list in child.named_children(&mut list_cursor) {
+                let mut type_cursor = type_list.walk();
+                for ty in type_list.named_children(&mut type_cursor) {
+                    inheritances.push(InheritanceInfo {
+                        name: strip_type_arguments(&content[ty.byte_range()]),
+                        kind: "interface".to_string(),
+                    });
+                }
+            }
+        }
+
+        inheritances
+    }
+
+    fn extract_methods(&self, body: Node, content: &str, call_query: &Query) -> Vec<FunctionInfo> {
+        let mut methods = Vec::new();
+        let mut cursor = body.walk();
+        for child in body.named_children(&mut cursor) {
+            if child.kind() != "method_declaration" && child.kind() != "constructor_declaration" {
+                continue;
+            }
+            let name = match child.child_by_field_name("name") {
+                Some(n) => content[n.byte_range()].to_string(),
+                None => continue,
+            };
+            let params = child
+                .child_by_field_name("parameters")
+                .map(|p| self.extract_params(p, content))
+                .unwrap_or_default();
+            let return_type = child
+                .child_by_field_name("type")
+                .map(|t| content[t.byte_range()].to_string());
+            let calls = self.extract_calls(child, content, call_query);
+
+            methods.push(FunctionInfo {
+                name,
+                params,
+                return_type,
+                calls,
+                start_line: child.start_position().row + 1,
+                end_line: child.end_position().row + 1,
+                complexity: cyclomatic_complexity(child, BRANCH_KINDS),
+                body_hash: body_hash(child, content),
+                decorators: decorators(child, content, &["marker_annotation", "annotation"]),
+                visibility: None,
+            });
+        }
+        methods
+    }
+}
+
+impl LanguageParser for JavaParser {
+    fn parse_file(&self, path: &PathBuf, content: &str) -> Result<ParsedFile> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(tree_sitter_java::language())
+            .context("Failed to set Java language")?;
+        let tree = parser
+            .parse(content, None)
+            .context("Failed to parse Java file")?;
+
+        let root_node = tree.root_node();
+        let mut classes = Vec::new();
+        let mut imports = Vec::new();
+
+        // Queries
+        let class_query = Query::new(
+            tree_sitter_java::language(),
+            r#"
+            (class_declaration name: (identifier) @class.name body: (class_body) @class.body) @class.def
+            (interface_declaration name: (identifier) @class.name body: (interface_body) @class.body) @class.def
+            (enum_declaration name: (identifier) @class.name body: (enum_body) @class.body) @class.def
+            (record_declaration name: (identifier) @class.name body: (class_body) @class.body) @class.def
+            "#,
+        )?;
+
+        let call_query = Query::new(
+            tree_sitter_java::language(),
+            r#"
+            (method_invocation name: (identifier) @call.name)
+            (object_creation_expression type: (type_identifier) @call.name)
+            "#,
+        )?;
+
+        let import_query = Query::new(
+            tree_sitter_java::language(),
+            r#"
+            (import_declaration) @import.source
+            "#,
+        )?;
+
+        let mut query_cursor = QueryCursor::new();
+
+        // 1. Extract Classes / Interfaces / Enums / Records
+        let class_matches = query_cursor.matches(&class_query, root_node, content.as_bytes());
+        for m in class_matches {
+            let mut name = String::new();
+            let mut node = root_node;
+            let mut body_node = None;
+
+            for c in m.captures {
+                let cn = &class_query.capture_names()[c.index as usize];
+                if cn == "class.name" {
+                    name = content[c.node.byte_range()].to_string();
+                } else if cn == "class.def" {
+                    node = c.node;
+                } else if cn == "class.body" {
+                    body_node = Some(c.node);
+                }
+            }
+
+            if name.is_empty() {
+                continue;
+            }
+
+            // Enum methods live in an enum_body_declarations node after the constants
+            let methods = match body_node {
+                Some(body) if body.kind() == "enum_body" => {
+                    let mut cursor = body.walk();
+                    let decls = body
+                        .named_children(&mut cursor)
+                        .find(|c| c.kind() == "enum_body_declarations");
+                    decls
+                        .map(|d| self.extract_methods(d, content, &call_query))
+                        .unwrap_or_default()
+                }
+                Some(body) => self.extract_methods(body, content, &call_query),
+                None => Vec::new(),
+            };
+
+            classes.push(ClassInfo {
+                name,
+                kind: None,
+                inheritances: self.extract_inheritances(node, content),
+                methods,
+                start_line: node.start_position().row + 1,
+                end_line: node.end_position().row + 1,
+                body_hash: body_hash(node, content),
+                decorators: decorators(node, content, &["marker_annotation", "annotation"]),
+                visibility: None,
+            });
+        }
+
+        // 2. Imports
+        let import_matches = query_cursor.matches(&import_query, root_node, content.as_bytes());
+        for m in import_matches {
+            for c in m.captures {
+                let cn = &import_query.capture_names()[c.index as usize];
+                if cn == "import.source" {
+                    let text = content[c.node.byte_range()]
+                        .trim_start_matches("import")
+                        .trim()
+                        .trim_end_matches(';')
+                        .trim();
+                    let text = text.strip_prefix("static ").unwrap_or(text).trim();
+                    if !text.is_empty() {
+                        imports.push(text.to_string());
+                    }
+                }
+            }
+        }
+
+        let data_tables = extract_data_tables(root_node, content, STRING_KINDS, &imports);
+        let service_calls = self.extract_service_calls(content);
+        let sql_queries = extract_sql_queries(root_node, content, STRING_KINDS);
+
+        Ok(ParsedFile {
+            path: path.to_string_lossy().to_string(),
+            language: "java".to_string(),
+            // Java has no free functions; everything is attached to a class
+            functions: Vec::new(),
+            classes,
+            interfaces: Vec::new(),
+            imports,
+            data_tables,
+            service_calls,
+            sql_queries,
+            env_vars: extract_env_vars(content, JVM_PATTERNS),
+            is_test: is_test_file(&path.to_string_lossy(), content, JVM_SIGNALS),
+            generated: false,
+            parse_failed: false,
+            rendered_components: Vec::new(),
+            reexports: Vec::new(),
+            line_count: count_lines(content),
+        })
+    }
+}
+
+fn strip_type_arguments(type_name: &str) -> String {
+    type_name
+        .split('<')
+        .next()
+        .unwrap_or(type_name)
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_java_full() {
+        let parser = JavaParser::new().unwrap();
+        let content = r#"
+            package com.example.users;
+
+            import java.util.List;
+            import static java.util.Collections.emptyList;
+            import com.example.base.*;
+
+            public class UserService extends BaseService<User> implements Auditable, Closeable {
+                private final UserRepository repo;
+
+                public UserService(UserRepository repo) {
+                    this.repo = repo;
+                }
+
+                public List<User> findAll(int limit, String... tags) {
+                    List<User> users = repo.findAll();
+                    audit("findAll");
+                    return users;
+                }
+
+                class Cache {
+                    void clear() {}
+                }
+            }
+
+            interface Auditable extends Named {
+                void audit(String action);
+            }
+
+            enum Role {
+                ADMIN, USER;
+
+                boolean isAdmin() {
+                    return this == ADMIN;
+                }
+            }
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("UserService.java"), content).unwrap();
+
+        assert_eq!(result.language, "java");
+        assert!(result.functions.is_empty());
+
+        // Imports
+        assert!(result.imports.contains(&"java.util.List".to_string()));
+        assert!(result.imports.contains(&"java.util.Collections.emptyList".to_string()));
+        assert!(result.imports.contains(&"com.example.base.*".to_string()));
+
+        // Classes
+        let service = result.classes.iter().find(|c| c.name == "UserService").expect("UserService not found");
+        assert!(service.inheritances.iter().any(|i| i.name == "BaseService" && i.kind == "class"));
+        assert!(service.inheritances.iter().any(|i| i.name == "Auditable" && i.kind == "interface"));
+        assert!(service.inheritances.iter().any(|i| i.name == "Closeable" && i.kind == "interface"));
+
+        // Methods (constructor included, nested class methods excluded)
+        assert!(service.methods.iter().any(|m| m.name == "UserService"));
+        assert!(!service.methods.iter().any(|m| m.name == "clear"));
+        let find_all = service.methods.iter().find(|m| m.name == "findAll").expect("findAll not found");
+        assert_eq!(find_all.params, vec!["limit", "tags"]);
+        assert_eq!(find_all.return_type.as_deref(), Some("List<User>"));
+        assert!(find_all.calls.contains(&"findAll".to_string()));
+        assert!(find_all.calls.contains(&"audit".to_string()));
+
+        // Nested class, interface and enum
+        let cache = result.classes.iter().find(|c| c.name == "Cache").expect("Cache not found");
+        assert!(cache.methods.iter().any(|m| m.name == "clear"));
+
+        let auditable = result.classes.iter().find(|c| c.name == "Auditable").expect("Auditable not found");
+        assert!(auditable.inheritances.iter().any(|i| i.name == "Named" && i.kind == "interface"));
+        assert!(auditable.methods.iter().any(|m| m.name == "audit"));
+
+        let role = result.classes.iter().find(|c| c.name == "Role").expect("Role not found");
+        assert!(role.methods.iter().any(|m| m.name == "isAdmin"));
+    }
+
+    #[test]
+    fn test_annotations_are_captured_with_arguments() {
+        let parser = JavaParser::new().unwrap();
+        let content = r#"
+            @Service
+            @RequestMapping("/users")
+            public class UserService {
+                @Transactional(readOnly = true)
+                public User find(long id) {
+                    return repo.find(id);
+                }
+
+                public void clear() {}
+            }
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("UserService.java"), content).unwrap();
+        let service = &result.classes[0];
+        assert_eq!(service.decorators, vec!["Service", "RequestMapping(\"/users\")"]);
+        let method = |name: &str| service.methods.iter().find(|m| m.name == name).unwrap();
+        assert_eq!(method("find").decorators, vec!["Transactional(readOnly = true)"]);
+        assert!(method("clear").decorators.is_empty());
+    }
+}
@@ -1,10 +1,27 @@
 use super::{ClassInfo, FunctionInfo, InheritanceInfo, LanguageParser, ParsedFile, ServiceCall};
+use super::{body_hash, decorators, count_lines, cyclomatic_complexity};
+use super::env_vars::{extract_env_vars, JAVASCRIPT_PATTERNS};
+use super::test_files::{is_test_file, JAVASCRIPT_SIGNALS};
+use super::sql_extractor::extract_data_tables;
+use super::sql_queries::extract_sql_queries;
+use super::jsx::extract_rendered_components;
+use super::exports::{member_visibility, module_exports};
+use crate::url_utils::canonical_host;
 use anyhow::{Context, Result};
 use regex::Regex;
 use std::collections::HashSet;
 use std::path::PathBuf;
 use tree_sitter::{Node, Parser, Query, QueryCursor};
 
+/// Node kinds of string literals, searched for SQL
+const STRING_KINDS: &[&str] = &["string", "template_string"];
+
+/// Node kinds that add a decision point to a function's complexity
+const BRANCH_KINDS: &[&str] = &[
+    "if_statement", "for_statement", "for_in_statement", "while_statement", "do_statement",
+    "switch_case", "catch_clause", "ternary_expression", "&&", "||", "??",
+];
+
 pub struct JavaScriptParser;
 
 impl JavaScriptParser {
@@ -12,30 +29,6 @@ impl JavaScriptParser {
         Ok(JavaScriptParser)
     }
 
-    fn extract_data_tables(&self, content: &str) -> Vec<String> {
-        let mut tables = HashSet::new();
-        let patterns = [
-            r"(?i)\bfrom\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bjoin\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\binto\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bupdate\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bdelete\s+from\s+([a-zA-Z0-9_.]+)",
-            r#"(?i)\btable\(\s*['"]([a-zA-Z0-9_.]+)['"]"#,
-        ];
-
-        for pattern in patterns {
-            if let Ok(re) = Regex::new(pattern) {
-                for cap in re.captures_iter(content) {
-                    if let Some(m) = cap.get(1) {
-                        tables.insert(m.as_str().to_string());
-                    }
-                }
-            }
-        }
-
-        tables.into_iter().collect()
-    }
-
     fn extract_service_calls(&self, content: &str) -> Vec<ServiceCall> {
         let mut services = HashSet::new();
         let url_pattern = r#"(?i)\b(https?|grpc)://[^\s'"`]+"#;
@@ -44,15 +37,15 @@ impl JavaScriptParser {
             for cap in re.captures_iter(content) {
                 let full = cap.get(0).map(|m| m.as_str()).unwrap_or_default();
                 let protocol = cap.get(1).map(|m| m.as_str()).unwrap_or("http");
-                if let Some(target) = extract_service_target(full) {
-                    services.insert((target, protocol.to_string()));
+                if let Some(host) = canonical_host(full) {
+                    services.insert((host, protocol.to_string()));
                 }
             }
         }
 
         services
             .into_iter()
-            .map(|(target, protocol)| ServiceCall { target, protocol })
+            .map(|(host, protocol)| ServiceCall::new(host, protocol))
             .collect()
     }
 
@@ -78,7 +71,8 @@ impl JavaScriptParser {
     }
 
     fn extract_calls(&self, node: Node, content: &str, query: &Query) -> Vec<String> {
-        let mut calls = HashSet::new();
+        let mut calls = Vec::new();
+        let mut call_sites = HashSet::new();
         let mut query_cursor = QueryCursor::new();
         let matches = query_cursor.matches(query, node, content.as_bytes());
         
@@ -87,11 +81,13 @@ impl JavaScriptParser {
                  let capture_name = &query.capture_names()[capture.index as usize];
                  if capture_name == "call.name" {
                      let call_name = content[capture.node.byte_range()].to_string();
-                     calls.insert(call_name);
+                     if call_sites.insert(capture.node.id()) {
+                         calls.push(call_name);
+                     }
                  }
             }
         }
-        calls.into_iter().collect()
+        calls
     }
 }
 
@@ -184,6 +180,7 @@ impl LanguageParser for JavaScriptParser {
         ).context("Failed to create import query")?;
 
         let mut query_cursor = QueryCursor::new();
+        let exports = module_exports(root_node, content);
 
         let process_function = |node: Node, name: String, _params_node: Option<Node>| -> FunctionInfo {
              let start_line = node.start_position().row + 1;
@@ -192,6 +189,12 @@ impl LanguageParser for JavaScriptParser {
              let params = self.extract_params(node, content);
              let calls = self.extract_calls(node, content, &call_query);
 
+             let visibility = if node.kind() == "method_definition" {
+                 member_visibility(node, content)
+             } else {
+                 exports.visibility(node, &name)
+             };
+
              FunctionInfo {
                  name,
                  params,
@@ -199,6 +202,10 @@ impl LanguageParser for JavaScriptParser {
                  calls,
                  start_line,
                  end_line,
+                 complexity: cyclomatic_complexity(node, BRANCH_KINDS),
+                 body_hash: body_hash(node, content),
+                 decorators: decorators(node, content, &["decorator"]),
+                 visibility: Some(visibility),
              }
         };
 
@@ -283,11 +290,15 @@ impl LanguageParser for JavaScriptParser {
                  }
 
                  classes.push(ClassInfo {
+                     visibility: Some(exports.visibility(class_node, &class_name)),
                      name: class_name,
+                     kind: None,
                      inheritances,
                      methods,
                      start_line,
                      end_line,
+                     body_hash: body_hash(class_node, content),
+                     decorators: decorators(class_node, content, &["decorator"]),
                  });
              }
         }
@@ -308,34 +319,31 @@ impl LanguageParser for JavaScriptParser {
             }
         }
 
-        let data_tables = self.extract_data_tables(content);
+        let data_tables = extract_data_tables(root_node, content, STRING_KINDS, &imports);
         let service_calls = self.extract_service_calls(content);
+        let sql_queries = extract_sql_queries(root_node, content, STRING_KINDS);
 
         Ok(ParsedFile {
             path: path.to_string_lossy().to_string(),
             language: "javascript".to_string(),
             functions,
             classes,
+            interfaces: Vec::new(),
             imports,
             data_tables,
             service_calls,
+            sql_queries,
+            env_vars: extract_env_vars(content, JAVASCRIPT_PATTERNS),
+            is_test: is_test_file(&path.to_string_lossy(), content, JAVASCRIPT_SIGNALS),
+            generated: false,
+            parse_failed: false,
+            rendered_components: extract_rendered_components(root_node, content),
+            reexports: exports.reexports,
+            line_count: count_lines(content),
         })
     }
 }
 
-fn extract_service_target(url: &str) -> Option<String> {
-    let parts: Vec<&str> = url.split("//").collect();
-    let host_part = parts.get(1).copied().unwrap_or("");
-    let host = host_part.split('/').next().unwrap_or("");
-    let host = host.split('?').next().unwrap_or("");
-    let host = host.split('#').next().unwrap_or("");
-    if host.is_empty() {
-        None
-    } else {
-        Some(host.to_string())
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -385,5 +393,10 @@ mod tests {
         let mult_method = calc_class.methods.iter().find(|m| m.name == "multiply").expect("multiply not found");
         assert_eq!(mult_method.params, vec!["a", "b"]);
         assert!(mult_method.calls.iter().any(|c| c == "log")); // this.log -> log in simplified extract
+
+        // Nothing is exported, so everything is module-private
+        assert_eq!(add_fn.visibility.as_deref(), Some("private"));
+        assert_eq!(calc_class.visibility.as_deref(), Some("private"));
+        assert_eq!(mult_method.visibility.as_deref(), Some("public"));
     }
 }
@@ -0,0 +1,65 @@
+//! JSX Component Extraction
+//!
+//! Finds the components a JavaScript or TypeScript file renders, for the
+//! graph's USES_COMPONENT edges. React treats a capitalized element name as
+//! a component and a lowercase one as a DOM element, so only `<Button>` and
+//! `<Button/>` count, not `<div>`. Member names like `<Icons.Close/>` name
+//! no single symbol and are skipped.
+
+use tree_sitter::Node;
+
+/// Distinct component names of the JSX elements under `root`, in order of
+/// first use
+pub fn extract_rendered_components(root: Node, content: &str) -> Vec<String> {
+    let mut components: Vec<String> = Vec::new();
+    let mut stack = vec![root];
+
+    while let Some(node) = stack.pop() {
+        if matches!(node.kind(), "jsx_opening_element" | "jsx_self_closing_element") {
+            if let Some(name) = node.child_by_field_name("name").filter(|name| name.kind() == "identifier") {
+                let name = &content[name.byte_range()];
+                if name.starts_with(|c: char| c.is_ascii_uppercase()) && !components.iter().any(|c| c == name) {
+                    components.push(name.to_string());
+                }
+            }
+        }
+
+        // Pushed in reverse so siblings are visited in source order
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev());
+    }
+
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tree_sitter::Parser;
+
+    #[test]
+    fn test_capitalized_elements_are_components() {
+        let content = r#"
+            export function App({ items }: Props) {
+                return (
+                    <Layout title="Home">
+                        <div className="list">
+                            {items.map((item) => <Card key={item.id} item={item} />)}
+                        </div>
+                        <Icons.Close />
+                        <>
+                            <Button onClick={() => save()} />
+                            <Card />
+                        </>
+                    </Layout>
+                );
+            }
+        "#;
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_typescript::language_tsx()).unwrap();
+        let tree = parser.parse(content, None).unwrap();
+
+        assert_eq!(extract_rendered_components(tree.root_node(), content), vec!["Layout", "Card", "Button"]);
+    }
+}
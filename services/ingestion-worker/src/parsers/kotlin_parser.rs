@@ -0,0 +1,415 @@
+use super::{ClassInfo, FunctionInfo, InheritanceInfo, LanguageParser, ParsedFile, ServiceCall};
+use super::{body_hash, count_lines, cyclomatic_complexity};
+use super::env_vars::{extract_env_vars, JVM_PATTERNS};
+use super::test_files::{is_test_file, JVM_SIGNALS};
+use super::sql_extractor::extract_data_tables;
+use super::sql_queries::extract_sql_queries;
+use crate::url_utils::canonical_host;
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tree_sitter::{Node, Parser, Query, QueryCursor};
+
+/// Node kinds of string literals, searched for SQL
+const STRING_KINDS: &[&str] = &["string_literal"];
+
+/// Node kinds that add a decision point to a function's complexity
+const BRANCH_KINDS: &[&str] = &[
+    "if_expression", "for_statement", "while_statement", "do_while_statement",
+    "when_entry", "catch_block", "elvis_expression", "&&", "||",
+];
+
+pub struct KotlinParser;
+
+impl KotlinParser {
+    pub fn new() -> Result<Self> {
+        Ok(KotlinParser)
+    }
+
+    fn extract_service_calls(&self, content: &str) -> Vec<ServiceCall> {
+        let mut services = HashSet::new();
+        let url_pattern = r#"(?i)\b(https?|grpc)://[^\s'"`]+"#;
+
+        if let Ok(re) = Regex::new(url_pattern) {
+            for cap in re.captures_iter(content) {
+                let full = cap.get(0).map(|m| m.as_str()).unwrap_or_default();
+                let protocol = cap.get(1).map(|m| m.as_str()).unwrap_or("http");
+                if let Some(host) = canonical_host(full) {
+                    services.insert((host, protocol.to_string()));
+                }
+            }
+        }
+
+        services
+            .into_iter()
+            .map(|(host, protocol)| ServiceCall::new(host, protocol))
+            .collect()
+    }
+
+    fn extract_params(&self, node: Node, content: &str) -> Vec<String> {
+        let mut params = Vec::new();
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            if child.kind() != "parameter" {
+                continue;
+            }
+            let mut param_cursor = child.walk();
+            let name = child
+                .named_children(&mut param_cursor)
+                .find(|part| part.kind() == "simple_identifier");
+            if let Some(name) = name {
+                params.push(content[name.byte_range()].to_string());
+            }
+        }
+        params
+    }
+
+    fn extract_calls(&self, node: Node, content: &str, query: &Query) -> Vec<String> {
+        let mut calls = Vec::new();
+        let mut call_sites = HashSet::new();
+        let mut query_cursor = QueryCursor::new();
+        let matches = query_cursor.matches(query, node, content.as_bytes());
+        for m in matches {
+            for capture in m.captures {
+                let capture_name = &query.capture_names()[capture.index as usize];
+                if capture_name == "call.name" {
+                    let call_name = content[capture.node.byte_range()].to_string();
+                    if call_sites.insert(capture.node.id()) {
+                        calls.push(call_name);
+                    }
+                }
+            }
+        }
+        calls
+    }
+
+    fn extract_function(&self, node: Node, content: &str, call_query: &Query) -> Option<FunctionInfo> {
+        let mut name = None;
+        let mut params = Vec::new();
+        let mut return_type = None;
+        let mut after_params = false;
+
+        // The grammar has no field names: an extension function's receiver
+        // type comes before the name, and the return type follows the
+        // parameter list
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            match child.kind() {
+                "simple_identifier" if name.is_none() => name = Some(content[child.byte_range()].to_string()),
+                "function_value_parameters" => {
+                    params = self.extract_params(child, content);
+                    after_params = true;
+                }
+                kind if after_params && return_type.is_none() && kind.ends_with("_type") => {
+                    return_type = Some(content[child.byte_range()].to_string());
+                }
+                _ => {}
+            }
+        }
+
+        Some(FunctionInfo {
+            name: name?,
+            params,
+            return_type,
+            calls: self.extract_calls(node, content, call_query),
+            start_line: node.start_position().row + 1,
+            end_line: node.end_position().row + 1,
+            complexity: cyclomatic_complexity(node, BRANCH_KINDS),
+            body_hash: body_hash(node, content),
+            decorators: Vec::new(),
+            visibility: None,
+        })
+    }
+
+    /// Functions declared in a class body. Companion object members are
+    /// called through the class name, so they count as the class's methods.
+    fn extract_methods(&self, body: Node, content: &str, call_query: &Query) -> Vec<FunctionInfo> {
+        let mut methods = Vec::new();
+        let mut cursor = body.walk();
+        for child in body.named_children(&mut cursor) {
+            match child.kind() {
+                "function_declaration" => methods.extend(self.extract_function(child, content, call_query)),
+                "companion_object" => {
+                    let mut companion_cursor = child.walk();
+                    let companion_body = child
+                        .named_children(&mut companion_cursor)
+                        .find(|part| part.kind() == "class_body");
+                    if let Some(companion_body) = companion_body {
+                        methods.extend(self.extract_methods(companion_body, content, call_query));
+                    }
+                }
+                _ => {}
+            }
+        }
+        methods
+    }
+
+    /// A superclass is written as a constructor call (`Base()`); anything
+    /// else in the list is an interface, as is every parent of an interface
+    fn extract_inheritance(&self, specifier: Node, content: &str) -> Option<InheritanceInfo> {
+        let mut cursor = specifier.walk();
+        let target = specifier.named_children(&mut cursor).next()?;
+        let (kind, ty) = match target.kind() {
+            "constructor_invocation" => ("class", target.named_child(0)?),
+            "explicit_delegation" => ("interface", target.named_child(0)?),
+            _ => ("interface", target),
+        };
+        Some(InheritanceInfo {
+            name: strip_type_arguments(&content[ty.byte_range()]),
+            kind: kind.to_string(),
+        })
+    }
+
+    /// Classes, interfaces and objects declared in `container`, nested
+    /// declarations included
+    fn extract_classes(&self, container: Node, content: &str, call_query: &Query, classes: &mut Vec<ClassInfo>) {
+        let mut cursor = container.walk();
+        for node in container.named_children(&mut cursor) {
+            if node.kind() != "class_declaration" && node.kind() != "object_declaration" {
+                continue;
+            }
+
+            let mut name = None;
+            let mut is_interface = false;
+            let mut inheritances = Vec::new();
+            let mut body = None;
+            let mut child_cursor = node.walk();
+            for child in node.children(&mut child_cursor) {
+                match child.kind() {
+                    "interface" => is_interface = true,
+                    "type_identifier" if name.is_none() => name = Some(content[child.byte_range()].to_string()),
+                    "delegation_specifier" => inheritances.extend(self.extract_inheritance(child, content)),
+                    "class_body" | "enum_class_body" => body = Some(child),
+                    _ => {}
+                }
+            }
+            let Some(name) = name else { continue };
+            if is_interface {
+                for inheritance in &mut inheritances {
+                    inheritance.kind = "interface".to_string();
+                }
+            }
+
+            classes.push(ClassInfo {
+                name,
+                kind: None,
+                inheritances,
+                methods: body
+                    .map(|body| self.extract_methods(body, content, call_query))
+                    .unwrap_or_default(),
+                start_line: node.start_position().row + 1,
+                end_line: node.end_position().row + 1,
+                body_hash: body_hash(node, content),
+                decorators: Vec::new(),
+                visibility: None,
+            });
+            if let Some(body) = body {
+                self.extract_classes(body, content, call_query, classes);
+            }
+        }
+    }
+}
+
+impl LanguageParser for KotlinParser {
+    fn parse_file(&self, path: &PathBuf, content: &str) -> Result<ParsedFile> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(tree_sitter_kotlin::language())
+            .context("Failed to set Kotlin language")?;
+        let tree = parser
+            .parse(content, None)
+            .context("Failed to parse Kotlin file")?;
+
+        let root_node = tree.root_node();
+        let mut functions = Vec::new();
+        let mut classes = Vec::new();
+        let mut imports = Vec::new();
+
+        let call_query = Query::new(
+            tree_sitter_kotlin::language(),
+            r#"
+            (call_expression (simple_identifier) @call.name)
+            (call_expression (navigation_expression (navigation_suffix (simple_identifier) @call.name)))
+            "#,
+        )?;
+
+        // 1. Classes / Interfaces / Objects
+        self.extract_classes(root_node, content, &call_query, &mut classes);
+
+        // 2. Top level functions and imports
+        let mut cursor = root_node.walk();
+        for node in root_node.named_children(&mut cursor) {
+            match node.kind() {
+                "function_declaration" => functions.extend(self.extract_function(node, content, &call_query)),
+                "import_list" => {
+                    let mut import_cursor = node.walk();
+                    for header in node.named_children(&mut import_cursor) {
+                        if let Some(import) = import_path(header, content) {
+                            imports.push(import);
+                        }
+                    }
+                }
+                "import_header" => imports.extend(import_path(node, content)),
+                _ => {}
+            }
+        }
+
+        let data_tables = extract_data_tables(root_node, content, STRING_KINDS, &imports);
+        let service_calls = self.extract_service_calls(content);
+        let sql_queries = extract_sql_queries(root_node, content, STRING_KINDS);
+
+        Ok(ParsedFile {
+            path: path.to_string_lossy().to_string(),
+            language: "kotlin".to_string(),
+            functions,
+            classes,
+            interfaces: Vec::new(),
+            imports,
+            data_tables,
+            service_calls,
+            sql_queries,
+            env_vars: extract_env_vars(content, JVM_PATTERNS),
+            is_test: is_test_file(&path.to_string_lossy(), content, JVM_SIGNALS),
+            generated: false,
+            parse_failed: false,
+            rendered_components: Vec::new(),
+            reexports: Vec::new(),
+            line_count: count_lines(content),
+        })
+    }
+}
+
+/// Imported name of an `import_header`, with `.*` kept for wildcard imports
+/// and any `as` alias dropped
+fn import_path(header: Node, content: &str) -> Option<String> {
+    let mut cursor = header.walk();
+    let mut path = None;
+    let mut wildcard = false;
+    for child in header.children(&mut cursor) {
+        match child.kind() {
+            "identifier" => path = Some(content[child.byte_range()].to_string()),
+            ".*" => wildcard = true,
+            _ => {}
+        }
+    }
+    path.map(|path| if wildcard { format!("{}.*", path) } else { path })
+}
+
+fn strip_type_arguments(type_name: &str) -> String {
+    type_name
+        .split('<')
+        .next()
+        .unwrap_or(type_name)
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kotlin_full() {
+        let parser = KotlinParser::new().unwrap();
+        let content = r#"
+            package com.example.users
+
+            import kotlinx.coroutines.flow.Flow
+            import com.example.base.*
+            import java.util.List as JList
+
+            class UserRepository(private val db: Database) : BaseRepository<User>(), Closeable, Auditable {
+                fun findAll(limit: Int, vararg tags: String): List<User> {
+                    val users = db.query("SELECT id, name FROM users")
+                    audit("findAll")
+                    if (limit > 0 && users.isNotEmpty()) {
+                        return users.take(limit)
+                    }
+                    return users
+                }
+
+                companion object {
+                    fun create(): UserRepository = UserRepository(Database())
+                }
+
+                class Cache {
+                    fun clear() {}
+                }
+            }
+
+            interface Auditable : Named {
+                fun audit(action: String)
+            }
+
+            object Registry {
+                fun register(repo: UserRepository) {
+                    println(repo)
+                }
+            }
+
+            enum class Role {
+                ADMIN, USER;
+
+                fun isAdmin() = this == ADMIN
+            }
+
+            fun main() {
+                val repo = UserRepository.create()
+                repo.findAll(10)
+                Registry.register(repo)
+            }
+
+            fun String.shout(): String = uppercase()
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("UserRepository.kt"), content).unwrap();
+
+        assert_eq!(result.language, "kotlin");
+
+        // Imports
+        assert_eq!(result.imports, vec!["kotlinx.coroutines.flow.Flow", "com.example.base.*", "java.util.List"]);
+
+        // Classes
+        let repo = result.classes.iter().find(|c| c.name == "UserRepository").expect("UserRepository not found");
+        assert!(repo.inheritances.iter().any(|i| i.name == "BaseRepository" && i.kind == "class"));
+        assert!(repo.inheritances.iter().any(|i| i.name == "Closeable" && i.kind == "interface"));
+        assert!(repo.inheritances.iter().any(|i| i.name == "Auditable" && i.kind == "interface"));
+
+        // Methods (companion members included, nested class methods excluded)
+        assert!(repo.methods.iter().any(|m| m.name == "create"));
+        assert!(!repo.methods.iter().any(|m| m.name == "clear"));
+        let find_all = repo.methods.iter().find(|m| m.name == "findAll").expect("findAll not found");
+        assert_eq!(find_all.params, vec!["limit", "tags"]);
+        assert_eq!(find_all.return_type.as_deref(), Some("List<User>"));
+        assert!(find_all.calls.contains(&"query".to_string()));
+        assert!(find_all.calls.contains(&"audit".to_string()));
+        assert!(find_all.calls.contains(&"take".to_string()));
+        // if, &&
+        assert_eq!(find_all.complexity, 3);
+
+        // Nested class, interface, object and enum
+        let cache = result.classes.iter().find(|c| c.name == "Cache").expect("Cache not found");
+        assert!(cache.methods.iter().any(|m| m.name == "clear"));
+
+        let auditable = result.classes.iter().find(|c| c.name == "Auditable").expect("Auditable not found");
+        assert!(auditable.inheritances.iter().any(|i| i.name == "Named" && i.kind == "interface"));
+        assert!(auditable.methods.iter().any(|m| m.name == "audit"));
+
+        let registry = result.classes.iter().find(|c| c.name == "Registry").expect("Registry not found");
+        assert!(registry.methods.iter().any(|m| m.name == "register"));
+
+        let role = result.classes.iter().find(|c| c.name == "Role").expect("Role not found");
+        assert!(role.methods.iter().any(|m| m.name == "isAdmin"));
+
+        // Functions
+        let main = result.functions.iter().find(|f| f.name == "main").expect("main not found");
+        assert_eq!(main.calls, vec!["create", "findAll", "register"]);
+        let shout = result.functions.iter().find(|f| f.name == "shout").expect("shout not found");
+        assert_eq!(shout.return_type.as_deref(), Some("String"));
+
+        // SQL
+        assert_eq!(result.sql_queries.len(), 1);
+        assert_eq!(result.sql_queries[0].reads, vec!["users"]);
+    }
+}
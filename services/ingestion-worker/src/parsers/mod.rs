@@ -3,53 +3,440 @@ pub mod typescript;
 pub mod rust_parser;
 pub mod go_parser;
 pub mod python_parser;
+pub mod java_parser;
+pub mod cpp_parser;
+pub mod kotlin_parser;
+pub mod swift_parser;
+pub mod php_parser;
+pub mod ruby_parser;
+pub mod sql_queries;
+pub mod sql_extractor;
+pub mod env_vars;
+pub mod test_files;
+pub mod jsx;
+pub mod exports;
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::path::PathBuf;
+use crate::url_utils::ServiceHost;
 
-#[derive(Debug, Clone)]
+use cpp_parser::CppParser;
+use go_parser::GoParser;
+use java_parser::JavaParser;
+use javascript::JavaScriptParser;
+use kotlin_parser::KotlinParser;
+use php_parser::PhpParser;
+use python_parser::PythonParser;
+use ruby_parser::RubyParser;
+use rust_parser::RustParser;
+use swift_parser::SwiftParser;
+use typescript::TypeScriptParser;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParsedFile {
     pub path: String,
     pub language: String,
     pub functions: Vec<FunctionInfo>,
     pub classes: Vec<ClassInfo>,
+    /// Interface method sets, for languages where types implement
+    /// interfaces implicitly (Go); see `DependencyGraph` for the matching
+    pub interfaces: Vec<InterfaceInfo>,
     pub imports: Vec<String>,
     pub data_tables: Vec<String>,
     pub service_calls: Vec<ServiceCall>,
+    pub sql_queries: Vec<SqlQuery>,
+    /// Environment variables the file reads, see `env_vars`
+    pub env_vars: Vec<String>,
+    /// Test code rather than production code, see `test_files`
+    pub is_test: bool,
+    /// Produced by a tool, see `generated_code`; set by the walker
+    pub generated: bool,
+    /// The parser failed on the file, so it has no symbols; the error is in
+    /// the job's `parse_errors`
+    pub parse_failed: bool,
+    /// Components the file renders, capitalized JSX element names; see `jsx`
+    pub rendered_components: Vec<String>,
+    /// Symbols the file exports from other modules, see `ReExport`
+    pub reexports: Vec<ReExport>,
+    /// Lines in the file; a final line without a newline still counts
+    pub line_count: usize,
+}
+
+impl ParsedFile {
+    /// Stand-in for a file the parser failed on, so it still gets a File node
+    pub fn failed(path: String, language: &str, content: &str) -> Self {
+        ParsedFile {
+            path,
+            language: language.to_string(),
+            functions: vec![],
+            classes: vec![],
+            interfaces: vec![],
+            imports: vec![],
+            data_tables: vec![],
+            service_calls: vec![],
+            sql_queries: vec![],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: true,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: count_lines(content),
+        }
+    }
+
+    /// The file without its call lists and SQL queries, which only the
+    /// dependency graph's CALLS edges, abstract-file detection and the Query
+    /// nodes read. A streamed run keeps this much of every file in memory,
+    /// see `parsed_source`.
+    pub fn into_outline(mut self) -> Self {
+        let methods = self.classes.iter_mut().flat_map(|class| &mut class.methods);
+        for function in self.functions.iter_mut().chain(methods) {
+            function.calls = Vec::new();
+        }
+        self.sql_queries = Vec::new();
+        self
+    }
+
+    /// Public functions, public classes and their public methods: the size
+    /// of the file's public surface
+    pub fn public_api_count(&self) -> usize {
+        let functions = self.functions.iter().filter(|f| is_public(f.visibility.as_deref())).count();
+        let classes: usize = self
+            .classes
+            .iter()
+            .filter(|c| is_public(c.visibility.as_deref()))
+            .map(|c| 1 + c.methods.iter().filter(|m| is_public(m.visibility.as_deref())).count())
+            .sum();
+        functions + classes
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionInfo {
     pub name: String,
     pub params: Vec<String>,
     pub return_type: Option<String>,
+    /// Callee names, one entry per call site, so a function called three
+    /// times appears three times
     pub calls: Vec<String>,
     pub start_line: usize,
     pub end_line: usize,
+    /// Approximate cyclomatic complexity, see `cyclomatic_complexity`
+    pub complexity: usize,
+    /// Hash of the function's source text, see `body_hash`
+    pub body_hash: String,
+    /// Decorators, annotations or attributes on the function, as written
+    /// without their `@` or `#[...]`, arguments included
+    pub decorators: Vec<String>,
+    /// public | private | protected | crate, from the language's modifiers
+    /// or naming convention; None where the parser doesn't tell
+    pub visibility: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+impl FunctionInfo {
+    /// Number of call sites per callee, in order of first call
+    pub fn call_counts(&self) -> Vec<(&str, usize)> {
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for call in &self.calls {
+            match counts.iter_mut().find(|(name, _)| name == call) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((call, 1)),
+            }
+        }
+        counts
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClassInfo {
     pub name: String,
+    /// Declaration kind where a language has several that map to a class
+    /// (`struct`, `enum`, `trait` in Rust); None otherwise
+    pub kind: Option<String>,
     pub inheritances: Vec<InheritanceInfo>,
     pub methods: Vec<FunctionInfo>,
     pub start_line: usize,
     pub end_line: usize,
+    /// Hash of the class's source text; empty for types only seen through
+    /// `impl` blocks, Swift extensions or method receivers
+    pub body_hash: String,
+    /// Decorators, annotations or attributes on the declaration, see
+    /// `FunctionInfo::decorators`
+    pub decorators: Vec<String>,
+    /// See `FunctionInfo::visibility`
+    pub visibility: Option<String>,
+}
+
+/// Whether a `visibility` value makes the symbol part of the public API
+pub fn is_public(visibility: Option<&str>) -> bool {
+    visibility == Some("public")
+}
+
+/// A JavaScript or TypeScript re-export, `export { a as b } from './y'` or
+/// `export * from './y'`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReExport {
+    /// Module specifier as written
+    pub source: String,
+    /// The symbol's name in `source`; None for `export *`
+    pub name: Option<String>,
+    /// Name it's exported under when renamed with `as`
+    pub alias: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InheritanceInfo {
     pub name: String,
     /// class | interface | trait
     pub kind: String,
 }
 
-#[derive(Debug, Clone)]
+/// An interface declaration's method set. The interface also appears in
+/// `classes`, so it has a Class node to point INHERITS edges at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterfaceInfo {
+    pub name: String,
+    pub methods: Vec<MethodSignature>,
+    /// Embedded interfaces, whose methods belong to the set too
+    pub embeds: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MethodSignature {
+    pub name: String,
+    /// Number of parameters
+    pub arity: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceCall {
+    /// `host[:port]` of the URL, canonicalized and without credentials, see
+    /// `url_utils`
     pub target: String,
+    pub port: Option<u16>,
     pub protocol: String,
+    /// Registrable domain of an external host
+    pub domain: Option<String>,
+    /// Host on the caller's own network, kept as written
+    pub internal: bool,
+}
+
+impl ServiceCall {
+    pub fn new(host: ServiceHost, protocol: String) -> Self {
+        ServiceCall {
+            target: host.host.authority(),
+            port: host.host.port,
+            protocol,
+            domain: host.domain,
+            internal: host.internal,
+        }
+    }
+}
+
+/// A SQL statement found in a string literal, normalized for storage as a Query node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SqlQuery {
+    /// Stable key derived from the normalized text
+    pub hash: String,
+    pub normalized: String,
+    /// SELECT | INSERT | UPDATE | DELETE | CREATE
+    pub statement_type: String,
+    pub reads: Vec<String>,
+    pub writes: Vec<String>,
+    pub line: usize,
+}
+
+/// Lines of `content`, counting an unterminated last line; 0 when empty
+pub fn count_lines(content: &str) -> usize {
+    content.lines().count()
+}
+
+/// Short hash of a symbol's source text. Incremental runs compare it with
+/// the stored one to tell modified symbols from ones that only moved.
+pub fn body_hash(node: tree_sitter::Node, content: &str) -> String {
+    let digest = Sha256::digest(content[node.byte_range()].as_bytes());
+    format!("{:x}", digest)[..16].to_string()
+}
+
+/// Decorators of a function or class: nodes of one of `kinds` among its
+/// children or its `modifiers`, right before it among its siblings (with
+/// comments in between), or on a wrapping `decorated_definition` or
+/// `export_statement`. Each is its source text without the leading `@` or
+/// the `#[...]` around it, in source order.
+pub fn decorators(node: tree_sitter::Node, content: &str, kinds: &[&str]) -> Vec<String> {
+    fn children(parent: tree_sitter::Node) -> Vec<tree_sitter::Node> {
+        let mut cursor = parent.walk();
+        parent.named_children(&mut cursor).collect()
+    }
+
+    let mut found = Vec::new();
+    if let Some(wrapper) = node.parent().filter(|p| matches!(p.kind(), "decorated_definition" | "export_statement")) {
+        found.extend(children(wrapper).into_iter().filter(|c| kinds.contains(&c.kind())));
+    }
+    let mut preceding = Vec::new();
+    let mut sibling = node.prev_named_sibling();
+    while let Some(prev) = sibling {
+        if kinds.contains(&prev.kind()) {
+            preceding.push(prev);
+        } else if !prev.kind().contains("comment") {
+            break;
+        }
+        sibling = prev.prev_named_sibling();
+    }
+    found.extend(preceding.into_iter().rev());
+    for child in children(node) {
+        if kinds.contains(&child.kind()) {
+            found.push(child);
+        } else if child.kind() == "modifiers" {
+            found.extend(children(child).into_iter().filter(|c| kinds.contains(&c.kind())));
+        }
+    }
+
+    // Python's decorators are both on the wrapper and before the definition
+    let mut seen = std::collections::HashSet::new();
+    found
+        .into_iter()
+        .filter(|decorator| seen.insert(decorator.id()))
+        .map(|decorator| {
+            let text = content[decorator.byte_range()].trim();
+            let text = text
+                .strip_prefix('@')
+                .or_else(|| text.strip_prefix("#[").and_then(|t| t.strip_suffix(']')))
+                .unwrap_or(text);
+            text.trim().to_string()
+        })
+        .collect()
+}
+
+/// Approximate cyclomatic complexity of a function: one plus every node in
+/// its subtree whose kind is in `branch_kinds`. Operator tokens such as `&&`
+/// are anonymous nodes and can be listed alongside named ones.
+pub fn cyclomatic_complexity(node: tree_sitter::Node, branch_kinds: &[&str]) -> usize {
+    let mut complexity = 1;
+    let mut cursor = node.walk();
+    loop {
+        if branch_kinds.contains(&cursor.node().kind()) {
+            complexity += 1;
+        }
+        if cursor.goto_first_child() {
+            continue;
+        }
+        loop {
+            if cursor.node() == node {
+                return complexity;
+            }
+            if cursor.goto_next_sibling() {
+                break;
+            }
+            if !cursor.goto_parent() {
+                return complexity;
+            }
+        }
+    }
 }
 
 pub trait LanguageParser {
     fn parse_file(&self, path: &PathBuf, content: &str) -> Result<ParsedFile>;
 }
+
+/// Run `parser` on one file, turning an error or a panic inside it into a
+/// message. Tree-sitter queries can panic on syntax a grammar doesn't
+/// expect; that must cost the file, not the job or the worker. Parsers keep
+/// no state between files, so one that panicked is still fine to reuse.
+pub fn parse_isolated(
+    parser: &dyn LanguageParser,
+    path: &PathBuf,
+    content: &str,
+) -> std::result::Result<ParsedFile, String> {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| parser.parse_file(path, content))) {
+        Ok(Ok(parsed_file)) => Ok(parsed_file),
+        Ok(Err(e)) => Err(format!("{:#}", e)),
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Err(format!("parser panicked: {}", message))
+        }
+    }
+}
+
+/// One instance of every language parser, selected by file extension
+pub struct ParserSet {
+    javascript: JavaScriptParser,
+    typescript: TypeScriptParser,
+    rust: RustParser,
+    go: GoParser,
+    python: PythonParser,
+    java: JavaParser,
+    cpp: CppParser,
+    kotlin: KotlinParser,
+    swift: SwiftParser,
+    php: PhpParser,
+    ruby: RubyParser,
+}
+
+impl ParserSet {
+    pub fn new() -> Result<Self> {
+        Ok(ParserSet {
+            javascript: JavaScriptParser::new()?,
+            typescript: TypeScriptParser::new()?,
+            rust: RustParser::new()?,
+            go: GoParser::new()?,
+            python: PythonParser::new()?,
+            java: JavaParser::new()?,
+            cpp: CppParser::new()?,
+            kotlin: KotlinParser::new()?,
+            swift: SwiftParser::new()?,
+            php: PhpParser::new()?,
+            ruby: RubyParser::new()?,
+        })
+    }
+
+    /// Whether a lowercase file extension has a parser
+    pub fn supports(ext: &str) -> bool {
+        Self::language(ext).is_some()
+    }
+
+    /// Language a lowercase file extension is parsed as, or None if it is
+    /// unsupported. Headers count as C++, which the C/C++ parser tries first.
+    pub fn language(ext: &str) -> Option<&'static str> {
+        match ext {
+            "js" | "jsx" | "mjs" => Some("javascript"),
+            "ts" | "tsx" => Some("typescript"),
+            "rs" => Some("rust"),
+            "go" => Some("go"),
+            "py" => Some("python"),
+            "java" => Some("java"),
+            "c" => Some("c"),
+            "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => Some("cpp"),
+            "kt" | "kts" => Some("kotlin"),
+            "swift" => Some("swift"),
+            "php" => Some("php"),
+            "rb" | "rake" => Some("ruby"),
+            _ => None,
+        }
+    }
+
+    /// Parser for a lowercase file extension, or None if the language is unsupported
+    pub fn for_extension(&self, ext: &str) -> Option<&dyn LanguageParser> {
+        match ext {
+            "js" | "jsx" | "mjs" => Some(&self.javascript),
+            "ts" | "tsx" => Some(&self.typescript),
+            "rs" => Some(&self.rust),
+            "go" => Some(&self.go),
+            "py" => Some(&self.python),
+            "java" => Some(&self.java),
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => Some(&self.cpp),
+            "kt" | "kts" => Some(&self.kotlin),
+            "swift" => Some(&self.swift),
+            "php" => Some(&self.php),
+            "rb" | "rake" => Some(&self.ruby),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,517 @@
+use super::{ClassInfo, FunctionInfo, InheritanceInfo, LanguageParser, ParsedFile, ServiceCall};
+use super::{body_hash, decorators, count_lines, cyclomatic_complexity};
+use super::env_vars::{extract_env_vars, PHP_PATTERNS};
+use super::test_files::{is_test_file, PHP_SIGNALS};
+use super::sql_extractor::extract_data_tables;
+use super::sql_queries::extract_sql_queries;
+use crate::url_utils::canonical_host;
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tree_sitter::{Node, Parser, Query, QueryCursor};
+
+/// Node kinds of string literals, searched for SQL. Heredocs and nowdocs
+/// are searched through their bodies, which leave out the `<<<TAG` lines.
+const STRING_KINDS: &[&str] = &["string", "encapsed_string", "heredoc_body", "nowdoc_body"];
+
+/// Node kinds that add a decision point to a function's complexity
+const BRANCH_KINDS: &[&str] = &[
+    "if_statement", "else_if_clause", "for_statement", "foreach_statement", "while_statement",
+    "do_statement", "case_statement", "catch_clause", "conditional_expression",
+    "match_conditional_expression", "&&", "||", "and", "or", "??",
+];
+
+/// Node kinds of PHP 8 attributes, `#[Route('/x')]`
+const ATTRIBUTE_KINDS: &[&str] = &["attribute_list"];
+
+/// Expressions that load another file
+const INCLUDE_KINDS: &[&str] = &[
+    "include_expression", "include_once_expression", "require_expression", "require_once_expression",
+];
+
+pub struct PhpParser;
+
+impl PhpParser {
+    pub fn new() -> Result<Self> {
+        Ok(PhpParser)
+    }
+
+    fn extract_service_calls(&self, content: &str) -> Vec<ServiceCall> {
+        let mut services = HashSet::new();
+        let url_pattern = r#"(?i)\b(https?|grpc)://[^\s'"`]+"#;
+
+        if let Ok(re) = Regex::new(url_pattern) {
+            for cap in re.captures_iter(content) {
+                let full = cap.get(0).map(|m| m.as_str()).unwrap_or_default();
+                let protocol = cap.get(1).map(|m| m.as_str()).unwrap_or("http");
+                if let Some(host) = canonical_host(full) {
+                    services.insert((host, protocol.to_string()));
+                }
+            }
+        }
+
+        services
+            .into_iter()
+            .map(|(host, protocol)| ServiceCall::new(host, protocol))
+            .collect()
+    }
+
+    /// Parameter names without their `$`
+    fn extract_params(&self, node: Node, content: &str) -> Vec<String> {
+        let mut params = Vec::new();
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            if !matches!(child.kind(), "simple_parameter" | "variadic_parameter" | "property_promotion_parameter") {
+                continue;
+            }
+            if let Some(name) = child.child_by_field_name("name") {
+                params.push(content[name.byte_range()].trim_start_matches('$').to_string());
+            }
+        }
+        params
+    }
+
+    fn extract_calls(&self, node: Node, content: &str, query: &Query) -> Vec<String> {
+        let mut calls = Vec::new();
+        let mut call_sites = HashSet::new();
+        let mut query_cursor = QueryCursor::new();
+        let matches = query_cursor.matches(query, node, content.as_bytes());
+        for m in matches {
+            for capture in m.captures {
+                let capture_name = &query.capture_names()[capture.index as usize];
+                if capture_name == "call.name" {
+                    let call_name = content[capture.node.byte_range()].to_string();
+                    if call_sites.insert(capture.node.id()) {
+                        calls.push(call_name);
+                    }
+                }
+            }
+        }
+        calls
+    }
+
+    fn extract_function(&self, node: Node, content: &str, call_query: &Query, visibility: Option<String>) -> Option<FunctionInfo> {
+        let name = node.child_by_field_name("name")?;
+        Some(FunctionInfo {
+            name: content[name.byte_range()].to_string(),
+            params: node
+                .child_by_field_name("parameters")
+                .map(|params| self.extract_params(params, content))
+                .unwrap_or_default(),
+            return_type: node
+                .child_by_field_name("return_type")
+                .map(|t| content[t.byte_range()].to_string()),
+            calls: self.extract_calls(node, content, call_query),
+            start_line: node.start_position().row + 1,
+            end_line: node.end_position().row + 1,
+            complexity: cyclomatic_complexity(node, BRANCH_KINDS),
+            body_hash: body_hash(node, content),
+            decorators: decorators(node, content, ATTRIBUTE_KINDS),
+            visibility,
+        })
+    }
+
+    /// Parents from `extends` and `implements` clauses, and the traits a
+    /// class body `use`s. An interface's `extends` lists interfaces.
+    fn extract_inheritances(&self, node: Node, content: &str) -> Vec<InheritanceInfo> {
+        let extends_kind = if node.kind() == "interface_declaration" { "interface" } else { "class" };
+        let mut inheritances = Vec::new();
+        let mut clauses = vec![];
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            match child.kind() {
+                "base_clause" => clauses.push((child, extends_kind)),
+                "class_interface_clause" => clauses.push((child, "interface")),
+                _ => {}
+            }
+        }
+        if let Some(body) = node.child_by_field_name("body") {
+            let mut body_cursor = body.walk();
+            clauses.extend(
+                body.named_children(&mut body_cursor)
+                    .filter(|member| member.kind() == "use_declaration")
+                    .map(|member| (member, "trait")),
+            );
+        }
+
+        for (clause, kind) in clauses {
+            let mut clause_cursor = clause.walk();
+            for parent in clause.named_children(&mut clause_cursor) {
+                if is_type_name(parent.kind()) {
+                    inheritances.push(InheritanceInfo {
+                        name: type_name(parent, content),
+                        kind: kind.to_string(),
+                    });
+                }
+            }
+        }
+        inheritances
+    }
+
+    /// Methods of a class, interface, trait or enum body, public unless a
+    /// visibility modifier says otherwise
+    fn extract_methods(&self, body: Node, content: &str, call_query: &Query) -> Vec<FunctionInfo> {
+        let mut methods = Vec::new();
+        let mut cursor = body.walk();
+        for member in body.named_children(&mut cursor) {
+            if member.kind() != "method_declaration" {
+                continue;
+            }
+            let mut modifier_cursor = member.walk();
+            let visibility = member
+                .named_children(&mut modifier_cursor)
+                .find(|child| child.kind() == "visibility_modifier")
+                .map(|modifier| content[modifier.byte_range()].to_lowercase())
+                .unwrap_or_else(|| "public".to_string());
+            methods.extend(self.extract_function(member, content, call_query, Some(visibility)));
+        }
+        methods
+    }
+
+    /// Declarations under `container`, which is the file or a statement in
+    /// it: namespace blocks and `if (!function_exists(...))` guards are
+    /// searched, function and class bodies are not
+    fn extract_declarations(
+        &self,
+        container: Node,
+        content: &str,
+        call_query: &Query,
+        functions: &mut Vec<FunctionInfo>,
+        classes: &mut Vec<ClassInfo>,
+        imports: &mut Vec<String>,
+    ) {
+        let mut cursor = container.walk();
+        for node in container.named_children(&mut cursor) {
+            match node.kind() {
+                "function_definition" => functions.extend(self.extract_function(node, content, call_query, None)),
+                "class_declaration" | "interface_declaration" | "trait_declaration" | "enum_declaration" => {
+                    let Some(name) = node.child_by_field_name("name") else { continue };
+                    classes.push(ClassInfo {
+                        name: content[name.byte_range()].to_string(),
+                        kind: match node.kind() {
+                            "interface_declaration" => Some("interface".to_string()),
+                            "trait_declaration" => Some("trait".to_string()),
+                            "enum_declaration" => Some("enum".to_string()),
+                            _ => None,
+                        },
+                        inheritances: self.extract_inheritances(node, content),
+                        methods: node
+                            .child_by_field_name("body")
+                            .map(|body| self.extract_methods(body, content, call_query))
+                            .unwrap_or_default(),
+                        start_line: node.start_position().row + 1,
+                        end_line: node.end_position().row + 1,
+                        body_hash: body_hash(node, content),
+                        decorators: decorators(node, content, ATTRIBUTE_KINDS),
+                        visibility: None,
+                    });
+                }
+                "namespace_use_declaration" => imports.extend(used_names(node, content)),
+                kind if INCLUDE_KINDS.contains(&kind) => imports.extend(included_path(node, content)),
+                _ => self.extract_declarations(node, content, call_query, functions, classes, imports),
+            }
+        }
+    }
+}
+
+impl LanguageParser for PhpParser {
+    fn parse_file(&self, path: &PathBuf, content: &str) -> Result<ParsedFile> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(tree_sitter_php::language_php())
+            .context("Failed to set PHP language")?;
+        let tree = parser
+            .parse(content, None)
+            .context("Failed to parse PHP file")?;
+
+        let root_node = tree.root_node();
+        let mut functions = Vec::new();
+        let mut classes = Vec::new();
+        let mut imports = Vec::new();
+
+        // Namespaced calls (`\App\Support\money()`, `Str::slug()`) keep only
+        // the function's own name; `new Foo` counts as a call to `Foo`
+        let call_query = Query::new(
+            tree_sitter_php::language_php(),
+            r#"
+            (function_call_expression function: (name) @call.name)
+            (function_call_expression function: (qualified_name (name) @call.name))
+            (member_call_expression name: (name) @call.name)
+            (nullsafe_member_call_expression name: (name) @call.name)
+            (scoped_call_expression name: (name) @call.name)
+            (object_creation_expression (name) @call.name)
+            (object_creation_expression (qualified_name (name) @call.name))
+            "#,
+        )?;
+
+        // 1. Classes / Interfaces / Traits / Enums, functions and imports
+        self.extract_declarations(root_node, content, &call_query, &mut functions, &mut classes, &mut imports);
+
+        let data_tables = extract_data_tables(root_node, content, STRING_KINDS, &imports);
+        let service_calls = self.extract_service_calls(content);
+        let sql_queries = extract_sql_queries(root_node, content, STRING_KINDS);
+
+        Ok(ParsedFile {
+            path: path.to_string_lossy().to_string(),
+            language: "php".to_string(),
+            functions,
+            classes,
+            interfaces: Vec::new(),
+            imports,
+            data_tables,
+            service_calls,
+            sql_queries,
+            env_vars: extract_env_vars(content, PHP_PATTERNS),
+            is_test: is_test_file(&path.to_string_lossy(), content, PHP_SIGNALS),
+            generated: false,
+            parse_failed: false,
+            rendered_components: Vec::new(),
+            reexports: Vec::new(),
+            line_count: count_lines(content),
+        })
+    }
+}
+
+/// Names a `use` statement imports, as written without a leading `\`.
+/// Group uses (`use App\{A, B}`) give one name per member; aliases are
+/// dropped.
+fn used_names(node: Node, content: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut prefix = None;
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        match child.kind() {
+            "namespace_use_clause" => {
+                let mut clause_cursor = child.walk();
+                names.extend(
+                    child
+                        .named_children(&mut clause_cursor)
+                        .find(|part| is_type_name(part.kind()))
+                        .map(|part| type_name(part, content)),
+                );
+            }
+            "namespace_name" => prefix = Some(type_name(child, content)),
+            "namespace_use_group" => {
+                let mut group_cursor = child.walk();
+                for clause in child.named_children(&mut group_cursor) {
+                    let mut clause_cursor = clause.walk();
+                    let Some(member) = clause.named_children(&mut clause_cursor).find(|part| part.kind() == "namespace_name") else {
+                        continue;
+                    };
+                    let member = type_name(member, content);
+                    names.push(match &prefix {
+                        Some(prefix) => format!("{}\\{}", prefix, member),
+                        None => member,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Path an `include`/`require` loads, when it is a literal or a literal
+/// appended to `__DIR__`; the latter is relative to the file, so it gets a
+/// leading `.`
+fn included_path(node: Node, content: &str) -> Option<String> {
+    let argument = node.named_child(0)?;
+    match argument.kind() {
+        "string" | "encapsed_string" => string_value(argument, content),
+        "binary_expression" => {
+            let left = argument.child_by_field_name("left")?;
+            let right = argument.child_by_field_name("right")?;
+            if &content[left.byte_range()] != "__DIR__" || !matches!(right.kind(), "string" | "encapsed_string") {
+                return None;
+            }
+            string_value(right, content).map(|path| format!(".{}", path))
+        }
+        _ => None,
+    }
+}
+
+/// Contents of a string literal without interpolation
+fn string_value(node: Node, content: &str) -> Option<String> {
+    let mut cursor = node.walk();
+    let parts: Vec<Node> = node.named_children(&mut cursor).collect();
+    let [part] = parts.as_slice() else { return None };
+    (part.kind() == "string_value").then(|| content[part.byte_range()].to_string())
+}
+
+fn is_type_name(kind: &str) -> bool {
+    matches!(kind, "name" | "qualified_name" | "namespace_name")
+}
+
+/// A class or namespace name as written, without a leading `\`
+fn type_name(node: Node, content: &str) -> String {
+    content[node.byte_range()].trim_start_matches('\\').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_laravel_controller() {
+        let parser = PhpParser::new().unwrap();
+        let content = r#"<?php
+
+namespace App\Http\Controllers;
+
+use App\Models\Invoice;
+use Illuminate\Http\Request;
+use Illuminate\Support\Facades\{DB, Log as Logger};
+
+require_once __DIR__ . '/../helpers.php';
+
+#[Middleware('auth')]
+class InvoiceController extends Controller implements HasMiddleware, \Countable
+{
+    use AuthorizesRequests, Dispatchable;
+
+    public function __construct(private InvoiceService $service, ?int $limit = 10) {}
+
+    public function index(Request $request, string ...$tags): JsonResponse
+    {
+        $rows = DB::select("SELECT id, total FROM invoices WHERE customer_id = ?", [$request->user()->id]);
+        if ($rows && count($rows) > 0) {
+            Logger::info('listing');
+        }
+        $total = \App\Support\format_money($this->service?->sum($rows));
+        return response()->json(new InvoiceResource($rows));
+    }
+
+    protected static function audit($entry)
+    {
+        return $entry ? strtoupper($entry) : env('AUDIT_CHANNEL');
+    }
+
+    private function client()
+    {
+        return Http::baseUrl('https://billing.example.com/api');
+    }
+}
+
+interface Billable extends \JsonSerializable
+{
+    public function bill(): void;
+}
+
+if (!function_exists('money')) {
+    function money($amount, $currency = 'EUR')
+    {
+        return sprintf('%s %s', $amount, $currency);
+    }
+}
+"#;
+
+        let result = parser.parse_file(&PathBuf::from("app/Http/Controllers/InvoiceController.php"), content).unwrap();
+
+        assert_eq!(result.language, "php");
+        assert!(!result.is_test);
+
+        // Imports
+        assert_eq!(
+            result.imports,
+            vec![
+                "App\\Models\\Invoice",
+                "Illuminate\\Http\\Request",
+                "Illuminate\\Support\\Facades\\DB",
+                "Illuminate\\Support\\Facades\\Log",
+                "./../helpers.php",
+            ]
+        );
+
+        // Classes
+        let controller = result.classes.iter().find(|c| c.name == "InvoiceController").expect("InvoiceController not found");
+        assert_eq!(controller.kind, None);
+        assert_eq!(controller.decorators, vec!["Middleware('auth')"]);
+        let parents: Vec<(&str, &str)> = controller.inheritances.iter().map(|i| (i.name.as_str(), i.kind.as_str())).collect();
+        assert_eq!(
+            parents,
+            vec![
+                ("Controller", "class"),
+                ("HasMiddleware", "interface"),
+                ("Countable", "interface"),
+                ("AuthorizesRequests", "trait"),
+                ("Dispatchable", "trait"),
+            ]
+        );
+
+        // Methods, with visibility
+        let methods: Vec<(&str, Option<&str>)> = controller.methods.iter().map(|m| (m.name.as_str(), m.visibility.as_deref())).collect();
+        assert_eq!(
+            methods,
+            vec![("__construct", Some("public")), ("index", Some("public")), ("audit", Some("protected")), ("client", Some("private"))]
+        );
+
+        let constructor = &controller.methods[0];
+        assert_eq!(constructor.params, vec!["service", "limit"]);
+
+        let index = controller.methods.iter().find(|m| m.name == "index").unwrap();
+        assert_eq!(index.params, vec!["request", "tags"]);
+        assert_eq!(index.return_type.as_deref(), Some("JsonResponse"));
+        // The namespaced call keeps its simple name
+        assert_eq!(index.calls, vec!["select", "user", "count", "info", "format_money", "sum", "response", "json", "InvoiceResource"]);
+        // if, &&
+        assert_eq!(index.complexity, 3);
+
+        let billable = result.classes.iter().find(|c| c.name == "Billable").expect("Billable not found");
+        assert_eq!(billable.kind.as_deref(), Some("interface"));
+        assert_eq!(billable.inheritances[0].name, "JsonSerializable");
+        assert_eq!(billable.inheritances[0].kind, "interface");
+        assert_eq!(billable.methods[0].name, "bill");
+
+        // Functions behind a function_exists guard
+        let money = result.functions.iter().find(|f| f.name == "money").expect("money not found");
+        assert_eq!(money.params, vec!["amount", "currency"]);
+        assert_eq!(money.visibility, None);
+        assert_eq!(money.calls, vec!["sprintf"]);
+
+        // SQL, services and environment
+        assert_eq!(result.sql_queries.len(), 1);
+        assert_eq!(result.sql_queries[0].reads, vec!["invoices"]);
+        assert_eq!(result.data_tables, vec!["invoices"]);
+        assert_eq!(result.service_calls.len(), 1);
+        assert_eq!(result.env_vars, vec!["AUDIT_CHANNEL"]);
+    }
+
+    #[test]
+    fn test_parse_laravel_routes_and_tests() {
+        let parser = PhpParser::new().unwrap();
+        let routes = r#"<?php
+
+use App\Http\Controllers\InvoiceController;
+use Illuminate\Support\Facades\Route;
+
+Route::get('/invoices', [InvoiceController::class, 'index']);
+Route::middleware('auth')->group(function () {
+    Route::post('/invoices/{invoice}/pay', [InvoiceController::class, 'pay']);
+});
+"#;
+        let result = parser.parse_file(&PathBuf::from("routes/web.php"), routes).unwrap();
+        assert_eq!(result.imports, vec!["App\\Http\\Controllers\\InvoiceController", "Illuminate\\Support\\Facades\\Route"]);
+        assert!(result.functions.is_empty());
+        assert!(result.classes.is_empty());
+        assert!(!result.is_test);
+
+        let feature_test = r#"<?php
+
+namespace Tests\Feature;
+
+use PHPUnit\Framework\TestCase;
+
+class InvoiceChecks extends TestCase
+{
+    public function test_index_lists_invoices(): void
+    {
+        $this->get('/invoices')->assertOk();
+    }
+}
+"#;
+        let result = parser.parse_file(&PathBuf::from("app/Checks/InvoiceChecks.php"), feature_test).unwrap();
+        assert!(result.is_test);
+        assert_eq!(result.classes[0].methods[0].calls, vec!["get", "assertOk"]);
+    }
+}
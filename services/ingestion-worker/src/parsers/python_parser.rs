@@ -1,10 +1,25 @@
 use super::{ClassInfo, FunctionInfo, InheritanceInfo, LanguageParser, ParsedFile, ServiceCall};
+use super::{body_hash, decorators, count_lines, cyclomatic_complexity};
+use super::env_vars::{extract_env_vars, PYTHON_PATTERNS};
+use super::test_files::{is_test_file, PYTHON_SIGNALS};
+use super::sql_extractor::extract_data_tables;
+use super::sql_queries::extract_sql_queries;
+use crate::url_utils::canonical_host;
 use anyhow::{Context, Result};
 use regex::Regex;
 use std::collections::HashSet;
 use std::path::PathBuf;
 use tree_sitter::{Node, Parser, Query, QueryCursor};
 
+/// Node kinds of string literals, searched for SQL
+const STRING_KINDS: &[&str] = &["string"];
+
+/// Node kinds that add a decision point to a function's complexity
+const BRANCH_KINDS: &[&str] = &[
+    "if_statement", "elif_clause", "for_statement", "while_statement", "except_clause",
+    "case_clause", "conditional_expression", "for_in_clause", "if_clause", "and", "or",
+];
+
 pub struct PythonParser;
 
 impl PythonParser {
@@ -12,30 +27,6 @@ impl PythonParser {
         Ok(PythonParser)
     }
 
-    fn extract_data_tables(&self, content: &str) -> Vec<String> {
-        let mut tables = HashSet::new();
-        let patterns = [
-            r"(?i)\bfrom\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bjoin\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\binto\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bupdate\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bdelete\s+from\s+([a-zA-Z0-9_.]+)",
-            r#"(?i)\btable\(\s*['"]([a-zA-Z0-9_.]+)['"]"#,
-        ];
-
-        for pattern in patterns {
-            if let Ok(re) = Regex::new(pattern) {
-                for cap in re.captures_iter(content) {
-                    if let Some(m) = cap.get(1) {
-                        tables.insert(m.as_str().to_string());
-                    }
-                }
-            }
-        }
-
-        tables.into_iter().collect()
-    }
-
     fn extract_service_calls(&self, content: &str) -> Vec<ServiceCall> {
         let mut services = HashSet::new();
         let url_pattern = r#"(?i)\b(https?|grpc)://[^\s'"`]+"#;
@@ -44,15 +35,15 @@ impl PythonParser {
             for cap in re.captures_iter(content) {
                 let full = cap.get(0).map(|m| m.as_str()).unwrap_or_default();
                 let protocol = cap.get(1).map(|m| m.as_str()).unwrap_or("http");
-                if let Some(target) = extract_service_target(full) {
-                    services.insert((target, protocol.to_string()));
+                if let Some(host) = canonical_host(full) {
+                    services.insert((host, protocol.to_string()));
                 }
             }
         }
 
         services
             .into_iter()
-            .map(|(target, protocol)| ServiceCall { target, protocol })
+            .map(|(host, protocol)| ServiceCall::new(host, protocol))
             .collect()
     }
 
@@ -63,17 +54,23 @@ impl PythonParser {
         for child in node.children(&mut cursor) {
              if child.kind() == "identifier" {
                  params.push(content[child.byte_range()].to_string());
-             } else if child.kind() == "typed_parameter" || child.kind() == "default_parameter" || child.kind() == "typed_default_parameter"{
+             } else if child.kind() == "default_parameter" || child.kind() == "typed_default_parameter" {
                  if let Some(name) = child.child_by_field_name("name") {
                       params.push(content[name.byte_range()].to_string());
                  }
+             } else if child.kind() == "typed_parameter" {
+                 // typed_parameter has no `name` field; the identifier is its first child
+                 if let Some(name) = child.named_child(0) {
+                      params.push(content[name.byte_range()].to_string());
+                 }
              }
         }
         params
     }
 
     fn extract_calls(&self, node: Node, content: &str, query: &Query) -> Vec<String> {
-        let mut calls = HashSet::new();
+        let mut calls = Vec::new();
+        let mut call_sites = HashSet::new();
         let mut query_cursor = QueryCursor::new();
         let matches = query_cursor.matches(query, node, content.as_bytes());
         for m in matches {
@@ -81,11 +78,13 @@ impl PythonParser {
                  let capture_name = &query.capture_names()[capture.index as usize];
                  if capture_name == "call.name" {
                      let call_name = content[capture.node.byte_range()].to_string();
-                     calls.insert(call_name);
+                     if call_sites.insert(capture.node.id()) {
+                         calls.push(call_name);
+                     }
                  }
             }
         }
-        calls.into_iter().collect()
+        calls
     }
 }
 
@@ -169,14 +168,21 @@ impl LanguageParser for PythonParser {
                  params = self.extract_params(params_node, content);
              }
              let calls = self.extract_calls(node, content, &call_query);
+             let return_type = node
+                 .child_by_field_name("return_type")
+                 .map(|t| content[t.byte_range()].to_string());
 
              FunctionInfo {
+                 visibility: Some(visibility(&name)),
                  name,
                  params,
-                 return_type: None,
+                 return_type,
                  calls,
                  start_line,
                  end_line,
+                 complexity: cyclomatic_complexity(node, BRANCH_KINDS),
+                 body_hash: body_hash(node, content),
+                 decorators: decorators(node, content, &["decorator"]),
              }
         };
 
@@ -235,11 +241,15 @@ impl LanguageParser for PythonParser {
                  }
                  
                  classes.push(ClassInfo {
+                     visibility: Some(visibility(&name)),
                      name,
+                     kind: None,
                      inheritances,
                      methods,
                      start_line,
                      end_line,
+                     body_hash: body_hash(node, content),
+                     decorators: decorators(node, content, &["decorator"]),
                  });
             }
         }
@@ -259,7 +269,9 @@ impl LanguageParser for PythonParser {
             
             if !name.is_empty() {
                 let mut is_method = false;
-                if let Some(parent) = node.parent() { 
+                // A decorated method sits in a decorated_definition
+                let definition = node.parent().filter(|p| p.kind() == "decorated_definition").unwrap_or(node);
+                if let Some(parent) = definition.parent() { 
                      if let Some(grandparent) = parent.parent() {
                          if grandparent.kind() == "class_definition" {
                              is_method = true;
@@ -284,32 +296,36 @@ impl LanguageParser for PythonParser {
              }
         }
 
-        let data_tables = self.extract_data_tables(content);
+        let data_tables = extract_data_tables(root_node, content, STRING_KINDS, &imports);
         let service_calls = self.extract_service_calls(content);
+        let sql_queries = extract_sql_queries(root_node, content, STRING_KINDS);
 
         Ok(ParsedFile {
             path: path.to_string_lossy().to_string(),
             language: "python".to_string(),
             functions,
             classes,
+            interfaces: Vec::new(),
             imports,
             data_tables,
             service_calls,
+            sql_queries,
+            env_vars: extract_env_vars(content, PYTHON_PATTERNS),
+            is_test: is_test_file(&path.to_string_lossy(), content, PYTHON_SIGNALS),
+            generated: false,
+            parse_failed: false,
+            rendered_components: Vec::new(),
+            reexports: Vec::new(),
+            line_count: count_lines(content),
         })
     }
 }
 
-fn extract_service_target(url: &str) -> Option<String> {
-    let parts: Vec<&str> = url.split("//").collect();
-    let host_part = parts.get(1).copied().unwrap_or("");
-    let host = host_part.split('/').next().unwrap_or("");
-    let host = host.split('?').next().unwrap_or("");
-    let host = host.split('#').next().unwrap_or("");
-    if host.is_empty() {
-        None
-    } else {
-        Some(host.to_string())
-    }
+/// A leading underscore marks a name private by convention; dunder names
+/// like `__init__` are public
+fn visibility(name: &str) -> String {
+    let dunder = name.len() > 4 && name.starts_with("__") && name.ends_with("__");
+    if name.starts_with('_') && !dunder { "private" } else { "public" }.to_string()
 }
 
 #[cfg(test)]
@@ -324,7 +340,7 @@ mod tests {
             from typing import List
 
             class Processor(BaseProcessor):
-                def process(self, data: List[str]):
+                def process(self, data: List[str]) -> List[str]:
                     self.clean(data)
                     return data
 
@@ -334,6 +350,7 @@ mod tests {
             def main():
                 p = Processor()
                 p.process(["foo"])
+                p.process(["bar"])
         "#;
         
         let result = parser.parse_file(&PathBuf::from("test.py"), content).unwrap();
@@ -352,9 +369,105 @@ mod tests {
         let process = processor.methods.iter().find(|m| m.name == "process").expect("process not found");
         assert_eq!(process.params, vec!["self", "data"]);
         assert!(process.calls.contains(&"clean".to_string())); // self.clean -> clean
+        assert_eq!(process.return_type.as_deref(), Some("List[str]"));
         
         // Functions
         let main = result.functions.iter().find(|f| f.name == "main").expect("main not found");
-        assert!(main.calls.contains(&"process".to_string()));
+        assert_eq!(main.call_counts(), vec![("Processor", 1), ("process", 2)]);
+        assert_eq!(main.return_type, None);
+    }
+
+    #[test]
+    fn test_visibility_from_leading_underscore() {
+        let parser = PythonParser::new().unwrap();
+        let content = "class _Cache:\n    pass\n\nclass Store:\n    def __init__(self):\n        pass\n\n    def _load(self):\n        pass\n\n    def __secret(self):\n        pass\n\n    def save(self):\n        pass\n\ndef _helper():\n    pass\n\ndef run():\n    pass\n";
+
+        let result = parser.parse_file(&PathBuf::from("store.py"), content).unwrap();
+        let class = |name: &str| result.classes.iter().find(|c| c.name == name).unwrap();
+        assert_eq!(class("_Cache").visibility.as_deref(), Some("private"));
+        assert_eq!(class("Store").visibility.as_deref(), Some("public"));
+        let methods: Vec<(&str, Option<&str>)> =
+            class("Store").methods.iter().map(|m| (m.name.as_str(), m.visibility.as_deref())).collect();
+        assert_eq!(
+            methods,
+            vec![("__init__", Some("public")), ("_load", Some("private")), ("__secret", Some("private")), ("save", Some("public"))]
+        );
+
+        let function = |name: &str| result.functions.iter().find(|f| f.name == name).unwrap().visibility.as_deref();
+        assert_eq!(function("_helper"), Some("private"));
+        assert_eq!(function("run"), Some("public"));
+    }
+
+    #[test]
+    fn test_decorators_are_captured_with_arguments() {
+        let parser = PythonParser::new().unwrap();
+        let content = r#"
+@dataclass(frozen=True)
+class Settings:
+    @property
+    def url(self):
+        return self.host
+
+    @app.route("/users/<id>", methods=["GET"])
+    @login_required
+    def show(self, id):
+        return id
+
+@app.get("/health")
+def health():
+    return "ok"
+
+def plain():
+    pass
+"#;
+
+        let result = parser.parse_file(&PathBuf::from("app.py"), content).unwrap();
+        let settings = &result.classes[0];
+        assert_eq!(settings.decorators, vec!["dataclass(frozen=True)"]);
+        let method = |name: &str| settings.methods.iter().find(|m| m.name == name).unwrap();
+        assert_eq!(method("url").decorators, vec!["property"]);
+        assert_eq!(method("show").decorators, vec![r#"app.route("/users/<id>", methods=["GET"])"#, "login_required"]);
+
+        // Decorated methods aren't mistaken for top-level functions
+        let names: Vec<&str> = result.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["health", "plain"]);
+        assert_eq!(result.functions[0].decorators, vec![r#"app.get("/health")"#]);
+        assert!(result.functions[1].decorators.is_empty());
+    }
+
+    #[test]
+    fn test_function_complexity() {
+        let parser = PythonParser::new().unwrap();
+        let content = r#"
+def simple():
+    return 1
+
+def load(path, strict):
+    try:
+        data = open(path).read()
+    except IOError:
+        return None
+    except ValueError:
+        return None
+    if not data or strict:
+        return None
+    elif len(data) > 10:
+        return data[:10]
+    return [line for line in data if line]
+
+class Loader:
+    def ready(self):
+        return self.path is not None and self.data
+"#;
+
+        let result = parser.parse_file(&PathBuf::from("test.py"), content).unwrap();
+        let complexity = |name: &str| result.functions.iter().find(|f| f.name == name).unwrap().complexity;
+
+        assert_eq!(complexity("simple"), 1);
+        // two excepts, if, or, elif, comprehension for and if
+        assert_eq!(complexity("load"), 8);
+
+        let ready = &result.classes[0].methods[0];
+        assert_eq!(ready.complexity, 2);
     }
 }
@@ -0,0 +1,514 @@
+use super::{ClassInfo, FunctionInfo, InheritanceInfo, LanguageParser, ParsedFile, ServiceCall};
+use super::{body_hash, count_lines};
+use super::env_vars::{extract_env_vars, RUBY_PATTERNS};
+use super::test_files::{is_test_file, RUBY_SIGNALS};
+use super::sql_extractor::extract_data_tables;
+use super::sql_queries::extract_sql_queries;
+use crate::url_utils::canonical_host;
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tree_sitter::{Node, Parser};
+
+/// Node kinds of string literals, searched for SQL
+const STRING_KINDS: &[&str] = &["string", "heredoc_body"];
+
+/// Named node kinds that add a decision point to a function's complexity.
+/// `if`, `while` and the like share their kind with their keyword token, so
+/// these are matched on named nodes only, see `complexity`.
+const BRANCH_KINDS: &[&str] = &[
+    "if", "elsif", "unless", "while", "until", "for", "when", "rescue", "conditional",
+    "if_modifier", "unless_modifier", "while_modifier", "until_modifier", "rescue_modifier",
+];
+
+/// Operator tokens that add a decision point
+const BRANCH_OPERATORS: &[&str] = &["&&", "||", "and", "or"];
+
+/// Calls that mix a module into a class
+const MIXIN_CALLS: &[&str] = &["include", "extend", "prepend"];
+
+/// Calls that load another file
+const REQUIRE_CALLS: &[&str] = &["require", "require_relative"];
+
+/// Parameter kinds whose `name` field is the parameter's name
+const NAMED_PARAMETER_KINDS: &[&str] = &[
+    "optional_parameter", "splat_parameter", "hash_splat_parameter", "block_parameter", "keyword_parameter",
+];
+
+pub struct RubyParser;
+
+impl RubyParser {
+    pub fn new() -> Result<Self> {
+        Ok(RubyParser)
+    }
+
+    fn extract_service_calls(&self, content: &str) -> Vec<ServiceCall> {
+        let mut services = HashSet::new();
+        let url_pattern = r#"(?i)\b(https?|grpc)://[^\s'"`]+"#;
+
+        if let Ok(re) = Regex::new(url_pattern) {
+            for cap in re.captures_iter(content) {
+                let full = cap.get(0).map(|m| m.as_str()).unwrap_or_default();
+                let protocol = cap.get(1).map(|m| m.as_str()).unwrap_or("http");
+                if let Some(host) = canonical_host(full) {
+                    services.insert((host, protocol.to_string()));
+                }
+            }
+        }
+
+        services
+            .into_iter()
+            .map(|(host, protocol)| ServiceCall::new(host, protocol))
+            .collect()
+    }
+
+    fn extract_params(&self, node: Node, content: &str) -> Vec<String> {
+        let mut params = Vec::new();
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            let name = match child.kind() {
+                "identifier" => Some(child),
+                kind if NAMED_PARAMETER_KINDS.contains(&kind) => child.child_by_field_name("name"),
+                _ => None,
+            };
+            if let Some(name) = name {
+                params.push(content[name.byte_range()].to_string());
+            }
+        }
+        params
+    }
+
+    /// Callee names in source order. A bare identifier that isn't one of the
+    /// method's local variables is a call on `self`, as Ruby reads it
+    /// (`save!`, an association like `lines`); receivers and namespaces are
+    /// dropped, so `self.recalc` and `Billing::Tax.rate` give `recalc` and
+    /// `rate`.
+    fn extract_calls(&self, node: Node, content: &str) -> Vec<String> {
+        let locals = local_variables(node, content);
+        let mut calls = Vec::new();
+        let mut stack = vec![node];
+        while let Some(current) = stack.pop() {
+            if current.kind() == "identifier" && current != node.child_by_field_name("name").unwrap_or(node) {
+                let name = &content[current.byte_range()];
+                let is_call = match current.parent() {
+                    Some(parent) if parent.kind() == "call" && parent.child_by_field_name("method") == Some(current) => true,
+                    Some(parent) if is_parameter_list(parent.kind()) || NAMED_PARAMETER_KINDS.contains(&parent.kind()) => false,
+                    _ => !locals.contains(name),
+                };
+                if is_call {
+                    calls.push(name.to_string());
+                }
+            }
+            // Nested definitions are symbols of their own
+            if current != node && matches!(current.kind(), "method" | "singleton_method" | "class" | "module") {
+                continue;
+            }
+            let mut cursor = current.walk();
+            let children: Vec<Node> = current.named_children(&mut cursor).collect();
+            stack.extend(children.into_iter().rev());
+        }
+        calls
+    }
+
+    fn extract_function(&self, node: Node, content: &str, visibility: Option<String>) -> Option<FunctionInfo> {
+        let name = node.child_by_field_name("name")?;
+        Some(FunctionInfo {
+            name: content[name.byte_range()].to_string(),
+            params: node
+                .child_by_field_name("parameters")
+                .map(|params| self.extract_params(params, content))
+                .unwrap_or_default(),
+            return_type: None,
+            calls: self.extract_calls(node, content),
+            start_line: node.start_position().row + 1,
+            end_line: node.end_position().row + 1,
+            complexity: complexity(node),
+            body_hash: body_hash(node, content),
+            decorators: Vec::new(),
+            visibility,
+        })
+    }
+
+    /// Methods defined in a class or module body, `self.` and `class << self`
+    /// ones included, with the visibility a bare `private`/`protected`/`public`
+    /// above them or a `private def ...` gives them. Mixins the body
+    /// `include`s, `extend`s or `prepend`s are added to `inheritances`.
+    fn extract_members(
+        &self,
+        body: Node,
+        content: &str,
+        methods: &mut Vec<FunctionInfo>,
+        inheritances: &mut Vec<InheritanceInfo>,
+    ) {
+        let mut visibility = "public";
+        let mut cursor = body.walk();
+        for child in body.named_children(&mut cursor) {
+            match child.kind() {
+                "method" => methods.extend(self.extract_function(child, content, Some(visibility.to_string()))),
+                // Class methods stay public under a bare `private`
+                "singleton_method" => methods.extend(self.extract_function(child, content, Some("public".to_string()))),
+                "singleton_class" => {
+                    if let Some(body) = body_statement(child) {
+                        self.extract_members(body, content, methods, inheritances);
+                    }
+                }
+                "identifier" => {
+                    if let Some(modifier) = visibility_modifier(&content[child.byte_range()]) {
+                        visibility = modifier;
+                    }
+                }
+                "call" => {
+                    let Some(method) = child.child_by_field_name("method") else { continue };
+                    let method_name = &content[method.byte_range()];
+                    let arguments = call_arguments(child);
+                    if let Some(modifier) = visibility_modifier(method_name) {
+                        for argument in arguments.iter().filter(|a| a.kind() == "method") {
+                            methods.extend(self.extract_function(*argument, content, Some(modifier.to_string())));
+                        }
+                    } else if MIXIN_CALLS.contains(&method_name) && child.child_by_field_name("receiver").is_none() {
+                        inheritances.extend(arguments.iter().filter(|a| is_constant(a.kind())).map(|module| InheritanceInfo {
+                            name: content[module.byte_range()].to_string(),
+                            kind: "trait".to_string(),
+                        }));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Classes and modules declared in `container`, nested ones included.
+    /// Names are kept as written, `Admin::UsersController` included.
+    fn extract_classes(&self, container: Node, content: &str, classes: &mut Vec<ClassInfo>) {
+        let mut cursor = container.walk();
+        for node in container.named_children(&mut cursor) {
+            if node.kind() != "class" && node.kind() != "module" {
+                continue;
+            }
+            let Some(name) = node.child_by_field_name("name") else { continue };
+
+            let mut inheritances = Vec::new();
+            if let Some(superclass) = node.child_by_field_name("superclass").and_then(|s| s.named_child(0)) {
+                if is_constant(superclass.kind()) {
+                    inheritances.push(InheritanceInfo {
+                        name: content[superclass.byte_range()].to_string(),
+                        kind: "class".to_string(),
+                    });
+                }
+            }
+            let mut methods = Vec::new();
+            let body = body_statement(node);
+            if let Some(body) = body {
+                self.extract_members(body, content, &mut methods, &mut inheritances);
+            }
+
+            classes.push(ClassInfo {
+                name: content[name.byte_range()].to_string(),
+                kind: (node.kind() == "module").then(|| "module".to_string()),
+                inheritances,
+                methods,
+                start_line: node.start_position().row + 1,
+                end_line: node.end_position().row + 1,
+                body_hash: body_hash(node, content),
+                decorators: Vec::new(),
+                visibility: None,
+            });
+            if let Some(body) = body {
+                self.extract_classes(body, content, classes);
+            }
+        }
+    }
+}
+
+impl LanguageParser for RubyParser {
+    fn parse_file(&self, path: &PathBuf, content: &str) -> Result<ParsedFile> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(tree_sitter_ruby::language())
+            .context("Failed to set Ruby language")?;
+        let tree = parser
+            .parse(content, None)
+            .context("Failed to parse Ruby file")?;
+
+        let root_node = tree.root_node();
+        let mut functions = Vec::new();
+        let mut classes = Vec::new();
+        let mut imports = Vec::new();
+
+        // 1. Classes / Modules
+        self.extract_classes(root_node, content, &mut classes);
+
+        // 2. Top level methods and requires
+        let mut cursor = root_node.walk();
+        for node in root_node.named_children(&mut cursor) {
+            match node.kind() {
+                "method" => functions.extend(self.extract_function(node, content, None)),
+                "call" => imports.extend(required_path(node, content)),
+                _ => {}
+            }
+        }
+
+        let data_tables = extract_data_tables(root_node, content, STRING_KINDS, &imports);
+        let service_calls = self.extract_service_calls(content);
+        let sql_queries = extract_sql_queries(root_node, content, STRING_KINDS);
+
+        Ok(ParsedFile {
+            path: path.to_string_lossy().to_string(),
+            language: "ruby".to_string(),
+            functions,
+            classes,
+            interfaces: Vec::new(),
+            imports,
+            data_tables,
+            service_calls,
+            sql_queries,
+            env_vars: extract_env_vars(content, RUBY_PATTERNS),
+            is_test: is_test_file(&path.to_string_lossy(), content, RUBY_SIGNALS),
+            generated: false,
+            parse_failed: false,
+            rendered_components: Vec::new(),
+            reexports: Vec::new(),
+            line_count: count_lines(content),
+        })
+    }
+}
+
+/// Path loaded by a top-level `require "x"` or `require_relative "x"`.
+/// `require_relative` paths are relative to the file, so they get a leading
+/// `./` when they don't start with `.` already.
+fn required_path(call: Node, content: &str) -> Option<String> {
+    if call.child_by_field_name("receiver").is_some() {
+        return None;
+    }
+    let method = &content[call.child_by_field_name("method")?.byte_range()];
+    if !REQUIRE_CALLS.contains(&method) {
+        return None;
+    }
+    let argument = call_arguments(call).into_iter().next().filter(|a| a.kind() == "string")?;
+    // Interpolated paths can't be resolved
+    let mut cursor = argument.walk();
+    let parts: Vec<Node> = argument.named_children(&mut cursor).collect();
+    let [part] = parts.as_slice() else { return None };
+    if part.kind() != "string_content" {
+        return None;
+    }
+    let path = content[part.byte_range()].to_string();
+    if method == "require_relative" && !path.starts_with('.') {
+        return Some(format!("./{}", path));
+    }
+    Some(path)
+}
+
+fn call_arguments(call: Node) -> Vec<Node> {
+    match call.child_by_field_name("arguments") {
+        Some(arguments) => {
+            let mut cursor = arguments.walk();
+            arguments.named_children(&mut cursor).collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+/// Local variables of a method: its parameters, block parameters, rescued
+/// exceptions and assignment targets, wherever they appear in it
+fn local_variables<'a>(node: Node, content: &'a str) -> HashSet<&'a str> {
+    let mut locals = HashSet::new();
+    let mut stack = vec![node];
+    while let Some(current) = stack.pop() {
+        let targets: Vec<Node> = match current.kind() {
+            kind if is_parameter_list(kind) || kind == "exception_variable" => {
+                let mut cursor = current.walk();
+                current.named_children(&mut cursor).collect()
+            }
+            "assignment" | "operator_assignment" => current.child_by_field_name("left").into_iter().collect(),
+            "left_assignment_list" | "destructured_left_assignment" => {
+                let mut cursor = current.walk();
+                current.named_children(&mut cursor).collect()
+            }
+            _ => Vec::new(),
+        };
+        for target in targets {
+            let name = if NAMED_PARAMETER_KINDS.contains(&target.kind()) {
+                target.child_by_field_name("name")
+            } else {
+                Some(target)
+            };
+            if let Some(name) = name.filter(|n| n.kind() == "identifier") {
+                locals.insert(&content[name.byte_range()]);
+            }
+        }
+        let mut cursor = current.walk();
+        stack.extend(current.named_children(&mut cursor));
+    }
+    locals
+}
+
+/// Like `cyclomatic_complexity`, one plus every decision point under `node`
+fn complexity(node: Node) -> usize {
+    let mut complexity = 1;
+    let mut stack = vec![node];
+    while let Some(current) = stack.pop() {
+        let kinds = if current.is_named() { BRANCH_KINDS } else { BRANCH_OPERATORS };
+        if kinds.contains(&current.kind()) {
+            complexity += 1;
+        }
+        let mut cursor = current.walk();
+        stack.extend(current.children(&mut cursor));
+    }
+    complexity
+}
+
+/// The statements of a class, module or `class << self` block; empty
+/// bodies have none
+fn body_statement(node: Node) -> Option<Node> {
+    let mut cursor = node.walk();
+    let body = node.named_children(&mut cursor).find(|child| child.kind() == "body_statement");
+    body
+}
+
+fn is_parameter_list(kind: &str) -> bool {
+    matches!(kind, "method_parameters" | "block_parameters" | "lambda_parameters")
+}
+
+fn is_constant(kind: &str) -> bool {
+    matches!(kind, "constant" | "scope_resolution")
+}
+
+fn visibility_modifier(name: &str) -> Option<&'static str> {
+    match name {
+        "private" => Some("private"),
+        "protected" => Some("protected"),
+        "public" => Some("public"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rails_model() {
+        let parser = RubyParser::new().unwrap();
+        let content = r#"
+require "json"
+require_relative "concerns/auditable"
+require_relative "../lib/tax"
+
+module Billing
+  class Invoice < ApplicationRecord
+    include Auditable
+    extend Searchable
+    belongs_to :customer
+
+    def self.overdue(days = 30)
+      where("due_at < ?", days).find_each { |invoice| notify(invoice) }
+    end
+
+    def total(currency, *rest, rounding: 2, **options, &block)
+      subtotal = lines.sum(&:amount)
+      tax = Billing::Tax.rate(subtotal)
+      if currency && tax > 0
+        subtotal = convert(subtotal, currency)
+      end
+      recalculate!
+      self.touch
+      subtotal + tax
+    end
+
+    private
+
+    def recalculate!
+      rows = ActiveRecord::Base.connection.execute("SELECT amount FROM invoice_lines WHERE invoice_id = 1")
+      update(total: rows.sum)
+    end
+
+    class << self
+      def archive_all
+        purge
+      end
+    end
+  end
+end
+
+class Admin::InvoicesController < ActionController::Base
+  def index
+    render json: Billing::Invoice.overdue
+  end
+end
+
+def report(invoice)
+  puts invoice.total("EUR")
+  ENV.fetch("REPORT_BUCKET")
+end
+"#;
+
+        let result = parser.parse_file(&PathBuf::from("app/models/billing/invoice.rb"), content).unwrap();
+
+        assert_eq!(result.language, "ruby");
+        assert!(!result.is_test);
+
+        // Imports
+        assert_eq!(result.imports, vec!["json", "./concerns/auditable", "../lib/tax"]);
+
+        // Classes and modules
+        let billing = result.classes.iter().find(|c| c.name == "Billing").expect("Billing not found");
+        assert_eq!(billing.kind.as_deref(), Some("module"));
+
+        let invoice = result.classes.iter().find(|c| c.name == "Invoice").expect("Invoice not found");
+        assert_eq!(invoice.kind, None);
+        let parents: Vec<(&str, &str)> = invoice.inheritances.iter().map(|i| (i.name.as_str(), i.kind.as_str())).collect();
+        assert_eq!(parents, vec![("ApplicationRecord", "class"), ("Auditable", "trait"), ("Searchable", "trait")]);
+
+        // Methods, with visibility
+        let methods: Vec<(&str, Option<&str>)> = invoice.methods.iter().map(|m| (m.name.as_str(), m.visibility.as_deref())).collect();
+        assert_eq!(
+            methods,
+            vec![("overdue", Some("public")), ("total", Some("public")), ("recalculate!", Some("private")), ("archive_all", Some("public"))]
+        );
+
+        let total = invoice.methods.iter().find(|m| m.name == "total").unwrap();
+        assert_eq!(total.params, vec!["currency", "rest", "rounding", "options", "block"]);
+        // `lines` and `recalculate!` are implicit self-calls, `subtotal` and `tax` locals
+        assert_eq!(total.calls, vec!["lines", "sum", "rate", "convert", "recalculate!", "touch"]);
+        // if, &&
+        assert_eq!(total.complexity, 3);
+
+        let overdue = invoice.methods.iter().find(|m| m.name == "overdue").unwrap();
+        assert_eq!(overdue.params, vec!["days"]);
+        assert_eq!(overdue.calls, vec!["where", "find_each", "notify"]);
+
+        let controller = result.classes.iter().find(|c| c.name == "Admin::InvoicesController").expect("controller not found");
+        assert_eq!(controller.inheritances[0].name, "ActionController::Base");
+        assert_eq!(controller.methods[0].calls, vec!["render", "overdue"]);
+
+        // Top level functions
+        let report = result.functions.iter().find(|f| f.name == "report").expect("report not found");
+        assert_eq!(report.visibility, None);
+        assert_eq!(report.calls, vec!["puts", "total", "fetch"]);
+
+        // SQL and environment
+        assert_eq!(result.sql_queries.len(), 1);
+        assert_eq!(result.sql_queries[0].reads, vec!["invoice_lines"]);
+        assert_eq!(result.env_vars, vec!["REPORT_BUCKET"]);
+    }
+
+    #[test]
+    fn test_parse_spec_file() {
+        let parser = RubyParser::new().unwrap();
+        let content = r#"
+require "rails_helper"
+
+RSpec.describe Billing::Invoice do
+  it "totals its lines" do
+    expect(build(:invoice).total).to eq(0)
+  end
+end
+"#;
+        let result = parser.parse_file(&PathBuf::from("lib/invoice_checks.rb"), content).unwrap();
+        assert!(result.is_test);
+        assert_eq!(result.imports, vec!["rails_helper"]);
+        assert!(result.classes.is_empty());
+    }
+}
@@ -1,10 +1,24 @@
 use super::{ClassInfo, FunctionInfo, InheritanceInfo, LanguageParser, ParsedFile, ServiceCall};
+use super::{body_hash, decorators, count_lines, cyclomatic_complexity};
+use super::env_vars::{extract_env_vars, RUST_PATTERNS};
+use super::test_files::{is_test_file, RUST_SIGNALS};
+use super::sql_extractor::extract_data_tables;
+use super::sql_queries::extract_sql_queries;
+use crate::url_utils::canonical_host;
 use anyhow::{Context, Result};
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use tree_sitter::{Node, Parser, Query, QueryCursor};
 
+/// Node kinds of string literals, searched for SQL
+const STRING_KINDS: &[&str] = &["string_literal", "raw_string_literal"];
+
+/// Node kinds that add a decision point to a function's complexity
+const BRANCH_KINDS: &[&str] = &[
+    "if_expression", "while_expression", "for_expression", "match_arm", "&&", "||",
+];
+
 pub struct RustParser;
 
 impl RustParser {
@@ -12,30 +26,6 @@ impl RustParser {
         Ok(RustParser)
     }
 
-    fn extract_data_tables(&self, content: &str) -> Vec<String> {
-        let mut tables = HashSet::new();
-        let patterns = [
-            r"(?i)\bfrom\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bjoin\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\binto\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bupdate\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bdelete\s+from\s+([a-zA-Z0-9_.]+)",
-            r#"(?i)\btable\(\s*['"]([a-zA-Z0-9_.]+)['"]"#,
-        ];
-
-        for pattern in patterns {
-            if let Ok(re) = Regex::new(pattern) {
-                for cap in re.captures_iter(content) {
-                    if let Some(m) = cap.get(1) {
-                        tables.insert(m.as_str().to_string());
-                    }
-                }
-            }
-        }
-
-        tables.into_iter().collect()
-    }
-
     fn extract_service_calls(&self, content: &str) -> Vec<ServiceCall> {
         let mut services = HashSet::new();
         let url_pattern = r#"(?i)\b(https?|grpc)://[^\s'"`]+"#;
@@ -44,15 +34,15 @@ impl RustParser {
             for cap in re.captures_iter(content) {
                 let full = cap.get(0).map(|m| m.as_str()).unwrap_or_default();
                 let protocol = cap.get(1).map(|m| m.as_str()).unwrap_or("http");
-                if let Some(target) = extract_service_target(full) {
-                    services.insert((target, protocol.to_string()));
+                if let Some(host) = canonical_host(full) {
+                    services.insert((host, protocol.to_string()));
                 }
             }
         }
 
         services
             .into_iter()
-            .map(|(target, protocol)| ServiceCall { target, protocol })
+            .map(|(host, protocol)| ServiceCall::new(host, protocol))
             .collect()
     }
 
@@ -72,19 +62,71 @@ impl RustParser {
     }
 
     fn extract_calls(&self, node: Node, content: &str, query: &Query) -> Vec<String> {
-        let mut calls = HashSet::new();
+        let mut calls = Vec::new();
+        let mut call_sites = HashSet::new();
         let mut query_cursor = QueryCursor::new();
         let matches = query_cursor.matches(query, node, content.as_bytes());
         for m in matches {
             for capture in m.captures {
                  let capture_name = &query.capture_names()[capture.index as usize];
                  if capture_name == "call.name" {
-                     let call_name = content[capture.node.byte_range()].to_string();
-                     calls.insert(call_name);
+                     let mut call_name = content[capture.node.byte_range()].to_string();
+                     if capture.node.parent().map(|p| p.kind()) == Some("macro_invocation") {
+                         call_name.push('!');
+                     }
+                     if call_sites.insert(capture.node.id()) {
+                         calls.push(call_name);
+                     }
                  }
             }
         }
-        calls.into_iter().collect()
+        calls
+    }
+
+    /// Methods with a body in an `impl` or `trait` declaration list
+    fn extract_methods(&self, body: Node, content: &str, function_query: &Query, call_query: &Query) -> Vec<FunctionInfo> {
+        let mut methods = Vec::new();
+        let mut method_cursor = QueryCursor::new();
+        let method_matches = method_cursor.matches(function_query, body, content.as_bytes());
+        for mm in method_matches {
+            let mut m_name = String::new();
+            let mut m_node = body;
+            let mut m_params_node = None;
+
+            for c in mm.captures {
+                let cn = &function_query.capture_names()[c.index as usize];
+                if cn == "func.name" {
+                    m_name = content[c.node.byte_range()].to_string();
+                } else if cn == "func.def" {
+                    m_node = c.node;
+                } else if cn == "func.params" {
+                    m_params_node = Some(c.node);
+                }
+            }
+
+            if !m_name.is_empty() {
+                let params = if let Some(pn) = m_params_node {
+                    self.extract_params(pn, content)
+                } else {
+                    Vec::new()
+                };
+                let calls = self.extract_calls(m_node, content, call_query);
+
+                methods.push(FunctionInfo {
+                    name: m_name,
+                    params,
+                    return_type: return_type(m_node, content),
+                    calls,
+                    start_line: m_node.start_position().row + 1,
+                    end_line: m_node.end_position().row + 1,
+                    complexity: cyclomatic_complexity(m_node, BRANCH_KINDS),
+                    body_hash: body_hash(m_node, content),
+                    decorators: decorators(m_node, content, &["attribute_item"]),
+                    visibility: Some(visibility(m_node, content)),
+                });
+            }
+        }
+        methods
     }
 }
 
@@ -123,25 +165,46 @@ impl LanguageParser for RustParser {
             "#,
         )?;
 
-                let impl_query = Query::new(
+        let trait_query = Query::new(
+            tree_sitter_rust::language(),
+            r#"
+            (trait_item
+              name: (type_identifier) @name
+              body: (declaration_list) @body
+            ) @def
+            "#,
+        )?;
+
+        // `impl<T> Thing<T>` targets are matched by the generic type's name
+        let impl_query = Query::new(
             tree_sitter_rust::language(),
             r#"
             (impl_item
-              type: (type_identifier) @target
+              type: [
+                (type_identifier) @target
+                (generic_type type: (type_identifier) @target)
+              ]
               body: (declaration_list) @body
             ) @impl
             "#,
         )?;
 
-                let trait_impl_query = Query::new(
-                        tree_sitter_rust::language(),
-                        r#"
-                        (impl_item
-                            trait: (type_identifier) @trait
-                            type: (type_identifier) @target
-                        ) @impl_trait
-                        "#,
-                )?;
+        let trait_impl_query = Query::new(
+            tree_sitter_rust::language(),
+            r#"
+            (impl_item
+              trait: [
+                (type_identifier) @trait
+                (scoped_type_identifier name: (type_identifier) @trait)
+                (generic_type type: (type_identifier) @trait)
+              ]
+              type: [
+                (type_identifier) @target
+                (generic_type type: (type_identifier) @target)
+              ]
+            ) @impl_trait
+            "#,
+        )?;
 
         let call_query = Query::new(
             tree_sitter_rust::language(),
@@ -152,6 +215,7 @@ impl LanguageParser for RustParser {
                 (field_expression field: (field_identifier) @call.name)
                 (scoped_identifier name: (identifier) @call.name)
               ])
+            (macro_invocation macro: (identifier) @call.name)
             "#,
         )?;
 
@@ -180,17 +244,53 @@ impl LanguageParser for RustParser {
                 }
             }
             if !name.is_empty() {
+                 let kind = node.kind().trim_end_matches("_item").to_string();
                  class_map.insert(name.clone(), ClassInfo {
                      name,
+                     kind: Some(kind),
                      inheritances: Vec::new(),
                      methods: Vec::new(),
                      start_line: node.start_position().row + 1,
                      end_line: node.end_position().row + 1,
+                     body_hash: body_hash(node, content),
+                     decorators: decorators(node, content, &["attribute_item"]),
+                     visibility: Some(visibility(node, content)),
                  });
             }
         }
 
-        // 1b. Extract Trait Implementations
+        // 1b. Extract Traits, with their default methods
+        let trait_matches = query_cursor.matches(&trait_query, root_node, content.as_bytes());
+        for m in trait_matches {
+            let mut name = String::new();
+            let mut node = root_node;
+            let mut body_node = root_node;
+            for c in m.captures {
+                let cn = &trait_query.capture_names()[c.index as usize];
+                if cn == "name" {
+                    name = content[c.node.byte_range()].to_string();
+                } else if cn == "def" {
+                    node = c.node;
+                } else if cn == "body" {
+                    body_node = c.node;
+                }
+            }
+            if !name.is_empty() {
+                class_map.insert(name.clone(), ClassInfo {
+                    name,
+                    kind: Some("trait".to_string()),
+                    inheritances: supertraits(node, content),
+                    methods: self.extract_methods(body_node, content, &function_query, &call_query),
+                    start_line: node.start_position().row + 1,
+                    end_line: node.end_position().row + 1,
+                    body_hash: body_hash(node, content),
+                    decorators: decorators(node, content, &["attribute_item"]),
+                    visibility: Some(visibility(node, content)),
+                });
+            }
+        }
+
+        // 1c. Extract Trait Implementations
         let trait_impl_matches = query_cursor.matches(&trait_impl_query, root_node, content.as_bytes());
         for m in trait_impl_matches {
             let mut trait_name = String::new();
@@ -208,10 +308,14 @@ impl LanguageParser for RustParser {
             if !trait_name.is_empty() && !target_name.is_empty() {
                 let entry = class_map.entry(target_name.clone()).or_insert(ClassInfo {
                     name: target_name,
+                    kind: None,
                     inheritances: Vec::new(),
                     methods: Vec::new(),
                     start_line: 0,
                     end_line: 0,
+                    body_hash: String::new(),
+                    decorators: Vec::new(),
+                    visibility: None,
                 });
                 entry.inheritances.push(InheritanceInfo {
                     name: trait_name,
@@ -238,49 +342,19 @@ impl LanguageParser for RustParser {
             if !target_name.is_empty() {
                  let mut class_info = class_map.remove(&target_name).unwrap_or(ClassInfo {
                      name: target_name.clone(),
+                     kind: None,
                      inheritances: Vec::new(),
                      methods: Vec::new(),
                      start_line: 0,
                      end_line: 0,
+                     body_hash: String::new(),
+                     decorators: Vec::new(),
+                     visibility: None,
                  });
-                 
-                 let mut method_cursor = QueryCursor::new();
-                 let method_matches = method_cursor.matches(&function_query, body_node, content.as_bytes());
-                 for mm in method_matches {
-                     let mut m_name = String::new();
-                     let mut m_node = root_node;
-                     let mut m_params_node = None;
-                     
-                     for c in mm.captures {
-                         let cn = &function_query.capture_names()[c.index as usize];
-                         if cn == "func.name" {
-                             m_name = content[c.node.byte_range()].to_string();
-                         } else if cn == "func.def" {
-                             m_node = c.node;
-                         } else if cn == "func.params" {
-                             m_params_node = Some(c.node);
-                         }
-                     }
-                     
-                     if !m_name.is_empty() {
-                         let params = if let Some(pn) = m_params_node {
-                             self.extract_params(pn, content)
-                         } else {
-                             Vec::new()
-                         };
-                         let calls = self.extract_calls(m_node, content, &call_query);
-                         
-                         class_info.methods.push(FunctionInfo {
-                             name: m_name,
-                             params,
-                             return_type: None, 
-                             calls,
-                             start_line: m_node.start_position().row + 1,
-                             end_line: m_node.end_position().row + 1,
-                         });
-                     }
-                 }
-                 
+
+                 class_info
+                     .methods
+                     .extend(self.extract_methods(body_node, content, &function_query, &call_query));
                  class_map.insert(target_name, class_info);
             }
         }
@@ -303,24 +377,29 @@ impl LanguageParser for RustParser {
                 }
              }
 
-             if let Some(parent) = node.parent() {
-                 if parent.kind() == "source_file" || parent.kind() == "mod_item" {
-                     let params = if let Some(pn) = params_node {
-                         self.extract_params(pn, content)
-                     } else {
-                         Vec::new()
-                     };
-                     let calls = self.extract_calls(node, content, &call_query);
-                     
-                     functions.push(FunctionInfo {
-                         name,
-                         params,
-                         return_type: None,
-                         calls,
-                         start_line: node.start_position().row + 1,
-                         end_line: node.end_position().row + 1,
-                     });
-                 }
+             if is_module_level(node) {
+                 let params = if let Some(pn) = params_node {
+                     self.extract_params(pn, content)
+                 } else {
+                     Vec::new()
+                 };
+                 let calls = self.extract_calls(node, content, &call_query);
+
+                 // Functions of nested modules are named by their path, `foo::bar::baz`
+                 let mut path = module_path(node, content);
+                 path.push(name);
+                 functions.push(FunctionInfo {
+                     name: path.join("::"),
+                     params,
+                     return_type: return_type(node, content),
+                     calls,
+                     start_line: node.start_position().row + 1,
+                     end_line: node.end_position().row + 1,
+                     complexity: cyclomatic_complexity(node, BRANCH_KINDS),
+                     body_hash: body_hash(node, content),
+                     decorators: decorators(node, content, &["attribute_item"]),
+                     visibility: Some(visibility(node, content)),
+                 });
              }
         }
 
@@ -335,32 +414,113 @@ impl LanguageParser for RustParser {
             }
         }
 
-        let data_tables = self.extract_data_tables(content);
+        let data_tables = extract_data_tables(root_node, content, STRING_KINDS, &imports);
         let service_calls = self.extract_service_calls(content);
+        let sql_queries = extract_sql_queries(root_node, content, STRING_KINDS);
 
         Ok(ParsedFile {
             path: path.to_string_lossy().to_string(),
             language: "rust".to_string(),
             functions,
             classes: class_map.into_values().collect(),
+            interfaces: Vec::new(),
             imports,
             data_tables,
             service_calls,
+            sql_queries,
+            env_vars: extract_env_vars(content, RUST_PATTERNS),
+            is_test: is_test_file(&path.to_string_lossy(), content, RUST_SIGNALS),
+            generated: false,
+            parse_failed: false,
+            rendered_components: Vec::new(),
+            reexports: Vec::new(),
+            line_count: count_lines(content),
         })
     }
 }
 
-fn extract_service_target(url: &str) -> Option<String> {
-    let parts: Vec<&str> = url.split("//").collect();
-    let host_part = parts.get(1).copied().unwrap_or("");
-    let host = host_part.split('/').next().unwrap_or("");
-    let host = host.split('?').next().unwrap_or("");
-    let host = host.split('#').next().unwrap_or("");
-    if host.is_empty() {
-        None
-    } else {
-        Some(host.to_string())
+/// Whether `function` is declared directly in the file or in a `mod` block,
+/// however deeply nested, rather than in an `impl`, a trait or a function body
+fn is_module_level(function: Node) -> bool {
+    let mut current = function.parent();
+    while let Some(parent) = current {
+        match parent.kind() {
+            "source_file" => return true,
+            "declaration_list" if parent.parent().is_some_and(|p| p.kind() == "mod_item") => {
+                current = parent.parent().and_then(|module| module.parent());
+            }
+            _ => return false,
+        }
     }
+    false
+}
+
+/// Visibility from a declaration's modifier: `pub` is public, `pub(crate)`,
+/// `pub(super)` and `pub(in path)` are crate, `pub(self)` or none private.
+/// Methods of traits and trait impls can't have one and count as public.
+fn visibility(node: Node, content: &str) -> String {
+    let mut cursor = node.walk();
+    let modifier = node
+        .children(&mut cursor)
+        .find(|child| child.kind() == "visibility_modifier")
+        .map(|modifier| content[modifier.byte_range()].split_whitespace().collect::<String>());
+    let in_trait = node
+        .parent()
+        .filter(|list| list.kind() == "declaration_list")
+        .and_then(|list| list.parent())
+        .is_some_and(|item| item.kind() == "trait_item" || item.child_by_field_name("trait").is_some());
+    let visibility = match modifier.as_deref() {
+        Some("pub") => "public",
+        Some("pub(self)") => "private",
+        Some(_) => "crate",
+        None if in_trait => "public",
+        None => "private",
+    };
+    visibility.to_string()
+}
+
+/// Names of the `mod` blocks enclosing `node`, outermost first
+fn module_path(node: Node, content: &str) -> Vec<String> {
+    let mut path = Vec::new();
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        if parent.kind() == "mod_item" {
+            if let Some(name) = parent.child_by_field_name("name") {
+                path.push(content[name.byte_range()].to_string());
+            }
+        }
+        current = parent.parent();
+    }
+    path.reverse();
+    path
+}
+
+/// Supertraits of a `trait_item`, `trait Store: Reader + Send`
+fn supertraits(trait_item: Node, content: &str) -> Vec<InheritanceInfo> {
+    let Some(bounds) = trait_item.child_by_field_name("bounds") else {
+        return Vec::new();
+    };
+    let mut cursor = bounds.walk();
+    bounds
+        .named_children(&mut cursor)
+        .filter_map(|bound| match bound.kind() {
+            "type_identifier" => Some(bound),
+            "scoped_type_identifier" => bound.child_by_field_name("name"),
+            "generic_type" => bound.child_by_field_name("type"),
+            _ => None,
+        })
+        .map(|name| InheritanceInfo {
+            name: content[name.byte_range()].to_string(),
+            kind: "trait".to_string(),
+        })
+        .collect()
+}
+
+/// Declared return type of a `function_item`, as written; None for `()`
+fn return_type(function: Node, content: &str) -> Option<String> {
+    function
+        .child_by_field_name("return_type")
+        .map(|t| content[t.byte_range()].to_string())
 }
 
 #[cfg(test)]
@@ -403,14 +563,213 @@ mod tests {
         
         // Structs
         let user = result.classes.iter().find(|c| c.name == "User").expect("User struct not found");
-        assert!(user.methods.iter().any(|m| m.name == "new"));
+        let new = user.methods.iter().find(|m| m.name == "new").unwrap();
+        assert_eq!(new.return_type.as_deref(), Some("Self"));
         let grow = user.methods.iter().find(|m| m.name == "grow").unwrap();
         assert_eq!(grow.params, vec!["self"]);
+        assert_eq!(grow.return_type, None);
         assert!(grow.calls.contains(&"println!".to_string())); // Note: println! might be identifier
         
         // Functions
         let main = result.functions.iter().find(|f| f.name == "main").expect("main not found");
+        assert_eq!(main.return_type, None);
         assert!(main.calls.contains(&"new".to_string()));
         assert!(main.calls.contains(&"grow".to_string()));
     }
+
+    #[test]
+    fn test_nested_modules_generic_impls_and_traits() {
+        let parser = RustParser::new().unwrap();
+        let content = r#"
+            fn top() {}
+
+            mod outer {
+                pub fn first() {}
+
+                mod inner {
+                    fn second() {
+                        helper();
+                    }
+
+                    struct Local;
+                    impl Local {
+                        fn method(&self) {}
+                    }
+                }
+            }
+
+            struct Wrapper<T> {
+                inner: T,
+            }
+
+            impl<T: Clone> Wrapper<T> {
+                fn get(&self) -> T {
+                    self.inner.clone()
+                }
+            }
+
+            impl<T> fmt::Display for Wrapper<T> {
+                fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    Ok(())
+                }
+            }
+
+            trait Greeter: Named + Send {
+                fn name(&self) -> String;
+
+                fn greet(&self) -> String {
+                    let name = self.name();
+                    format!("Hello, {}", name)
+                }
+            }
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("lib.rs"), content).unwrap();
+
+        let mut functions: Vec<&str> = result.functions.iter().map(|f| f.name.as_str()).collect();
+        functions.sort();
+        assert_eq!(functions, vec!["outer::first", "outer::inner::second", "top"]);
+        let second = result.functions.iter().find(|f| f.name == "outer::inner::second").unwrap();
+        assert_eq!(second.calls, vec!["helper"]);
+
+        let class = |name: &str| result.classes.iter().find(|c| c.name == name).unwrap();
+        assert_eq!(class("Local").methods[0].name, "method");
+
+        let wrapper = class("Wrapper");
+        assert_eq!(wrapper.kind.as_deref(), Some("struct"));
+        let mut methods: Vec<&str> = wrapper.methods.iter().map(|m| m.name.as_str()).collect();
+        methods.sort();
+        assert_eq!(methods, vec!["fmt", "get"]);
+        assert_eq!(wrapper.inheritances.len(), 1);
+        assert_eq!(wrapper.inheritances[0].name, "Display");
+
+        // Only methods with a default body are methods of the trait
+        let greeter = class("Greeter");
+        assert_eq!(greeter.kind.as_deref(), Some("trait"));
+        assert_eq!(greeter.methods.len(), 1);
+        assert_eq!(greeter.methods[0].name, "greet");
+        assert_eq!(greeter.methods[0].calls, vec!["name", "format!"]);
+        let supertraits: Vec<&str> = greeter.inheritances.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(supertraits, vec!["Named", "Send"]);
+    }
+
+    #[test]
+    fn test_function_complexity() {
+        let parser = RustParser::new().unwrap();
+        let content = r#"
+            fn simple() -> u32 {
+                42
+            }
+
+            fn classify(n: i32, flag: bool) -> &'static str {
+                if n < 0 && flag {
+                    return "negative";
+                }
+                for _ in 0..n {
+                    while flag || n > 10 {
+                        break;
+                    }
+                }
+                match n {
+                    0 => "zero",
+                    1 => "one",
+                    _ => "many",
+                }
+            }
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("test.rs"), content).unwrap();
+        let complexity = |name: &str| result.functions.iter().find(|f| f.name == name).unwrap().complexity;
+
+        assert_eq!(complexity("simple"), 1);
+        let classify = result.functions.iter().find(|f| f.name == "classify").unwrap();
+        assert_eq!(classify.return_type.as_deref(), Some("&'static str"));
+        // if, &&, for, while, ||, three match arms
+        assert_eq!(complexity("classify"), 9);
+    }
+
+    #[test]
+    fn test_attributes_are_captured_with_arguments() {
+        let parser = RustParser::new().unwrap();
+        let content = r#"
+            /// Entry point
+            #[tokio::main(flavor = "current_thread")]
+            async fn main() {}
+
+            fn plain() {}
+
+            #[derive(Debug, Clone)]
+            #[serde(rename_all = "camelCase")]
+            struct Config;
+
+            impl Config {
+                #[inline]
+                // hot path
+                fn load(&self) {}
+            }
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("main.rs"), content).unwrap();
+        let function = |name: &str| result.functions.iter().find(|f| f.name == name).unwrap();
+        assert_eq!(function("main").decorators, vec![r#"tokio::main(flavor = "current_thread")"#]);
+        assert!(function("plain").decorators.is_empty());
+
+        let config = result.classes.iter().find(|c| c.name == "Config").unwrap();
+        assert_eq!(config.decorators, vec!["derive(Debug, Clone)", r#"serde(rename_all = "camelCase")"#]);
+        assert_eq!(config.methods[0].decorators, vec!["inline"]);
+    }
+
+    #[test]
+    fn test_visibility_from_modifiers() {
+        let parser = RustParser::new().unwrap();
+        let content = r#"
+            pub struct Store;
+            pub(crate) enum Mode { Fast }
+            struct Cache;
+            pub trait Load { fn load(&self) {} }
+
+            impl Store {
+                pub fn open() -> Self { Store }
+                pub(super) fn reset(&self) {}
+                pub(self) fn flush(&self) {}
+                fn compact(&self) {}
+            }
+
+            impl Load for Cache {
+                fn load(&self) {}
+            }
+
+            pub fn run() {}
+            pub(in crate::app) fn helper() {}
+            fn private() {}
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("store.rs"), content).unwrap();
+        let class = |name: &str| result.classes.iter().find(|c| c.name == name).unwrap();
+        let visibility = |name: &str| {
+            let function = result.functions.iter().find(|f| f.name == name).unwrap();
+            function.visibility.as_deref()
+        };
+        assert_eq!(class("Store").visibility.as_deref(), Some("public"));
+        assert_eq!(class("Mode").visibility.as_deref(), Some("crate"));
+        assert_eq!(class("Cache").visibility.as_deref(), Some("private"));
+        assert_eq!(class("Load").visibility.as_deref(), Some("public"));
+
+        let methods: Vec<(&str, Option<&str>)> = class("Store")
+            .methods
+            .iter()
+            .map(|m| (m.name.as_str(), m.visibility.as_deref()))
+            .collect();
+        assert_eq!(
+            methods,
+            vec![("open", Some("public")), ("reset", Some("crate")), ("flush", Some("private")), ("compact", Some("private"))]
+        );
+        // Trait methods and trait impl methods have no modifier of their own
+        assert_eq!(class("Load").methods[0].visibility.as_deref(), Some("public"));
+        assert_eq!(class("Cache").methods[0].visibility.as_deref(), Some("public"));
+
+        assert_eq!(visibility("run"), Some("public"));
+        assert_eq!(visibility("helper"), Some("crate"));
+        assert_eq!(visibility("private"), Some("private"));
+    }
 }
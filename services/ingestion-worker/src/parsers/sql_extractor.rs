@@ -0,0 +1,174 @@
+//! Table Reference Extraction
+//!
+//! Finds the tables a file mentions, stored as its `data_tables`. Unlike
+//! `sql_queries`, which only takes literals that start like a statement,
+//! this also reads SQL embedded further into a literal, so it keeps prose
+//! out another way: clauses are only searched in string literals, never in
+//! comments or import statements, and each clause counts only when the same
+//! literal holds the keyword it belongs with (`FROM` next to `SELECT` or
+//! `DELETE`, `INTO` next to `INSERT` and `VALUES`, ...). A bare
+//! `" JOIN payments p ON ..."` fragment therefore names no table.
+//! Query-builder calls like `knex.table('users')` are matched in the code
+//! itself.
+//!
+//! Names are lowercased with their quoting removed, names the file imports
+//! are dropped, and at most `MAX_TABLES_PER_FILE` are kept.
+
+use regex::Regex;
+use std::collections::BTreeSet;
+use tree_sitter::Node;
+
+use super::sql_queries::string_literals;
+
+/// Tables kept per file, in name order
+pub const MAX_TABLES_PER_FILE: usize = 50;
+
+/// A clause naming a table, and the keyword the literal must also hold for
+/// it to count; None when the clause is specific enough on its own
+const CLAUSES: &[(&str, Option<&str>)] = &[
+    (r"(?i)\bfrom\s+([a-z0-9_.`\x22\[\]]+)", Some(r"(?i)\b(?:select|delete)\b")),
+    (r"(?i)\bjoin\s+([a-z0-9_.`\x22\[\]]+)", Some(r"(?i)\b(?:select|update|delete)\b")),
+    (r"(?i)\binto\s+([a-z0-9_.`\x22\[\]]+)", Some(r"(?i)\b(?:insert|replace|merge)\b[\s\S]*\b(?:values|select|set)\b")),
+    (r"(?i)\bupdate\s+([a-z0-9_.`\x22\[\]]+)\s+set\b", None),
+    (r"(?i)\bdelete\s+from\s+([a-z0-9_.`\x22\[\]]+)", None),
+];
+
+/// Query-builder calls naming a table, matched in the code
+const BUILDER_CALL: &str = r#"(?i)\btable\(\s*['"]([a-zA-Z0-9_.]+)['"]"#;
+
+/// Words the clauses can capture that are never tables
+const NOT_TABLES: &[&str] = &["select", "where", "set", "values", "lateral", "unnest", "dual", "only"];
+
+/// Tables referenced in `content`, sorted. `string_kinds` lists the
+/// tree-sitter node kinds of the language's string literals, `imports` the
+/// file's import targets.
+pub fn extract_data_tables(root: Node, content: &str, string_kinds: &[&str], imports: &[String]) -> Vec<String> {
+    let clauses: Vec<(Regex, Option<Regex>)> = CLAUSES
+        .iter()
+        .map(|(clause, context)| (Regex::new(clause).unwrap(), context.map(|c| Regex::new(c).unwrap())))
+        .collect();
+
+    let mut candidates = Vec::new();
+    for (literal, _) in string_literals(root, content, string_kinds) {
+        for (clause, context) in &clauses {
+            if context.as_ref().is_some_and(|context| !context.is_match(literal)) {
+                continue;
+            }
+            candidates.extend(clause.captures_iter(literal).map(|cap| cap[1].to_string()));
+        }
+    }
+    let builder_call = Regex::new(BUILDER_CALL).unwrap();
+    candidates.extend(builder_call.captures_iter(content).map(|cap| cap[1].to_string()));
+
+    let imported = imported_names(imports);
+    let tables: BTreeSet<String> = candidates
+        .iter()
+        .filter_map(|candidate| normalize_table_name(candidate))
+        .filter(|name| !imported.contains(name))
+        .collect();
+    tables.into_iter().take(MAX_TABLES_PER_FILE).collect()
+}
+
+/// Lowercased name without quotes or brackets; None for what can't be a
+/// table name
+fn normalize_table_name(raw: &str) -> Option<String> {
+    let name: String = raw
+        .chars()
+        .filter(|c| !matches!(c, '"' | '`' | '[' | ']'))
+        .collect::<String>()
+        .trim_matches('.')
+        .to_lowercase();
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && !name.contains("..")
+        && !NOT_TABLES.contains(&name.as_str());
+    valid.then_some(name)
+}
+
+/// Imports and their last segments, lowercased: `app.models` also drops
+/// `models`, `./db/users` also drops `users`
+fn imported_names(imports: &[String]) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    for import in imports {
+        let import = import.to_lowercase();
+        if let Some(last) = import.rsplit(['.', '/', ':']).find(|segment| !segment.is_empty()) {
+            names.insert(last.to_string());
+        }
+        names.insert(import);
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn python_tables(content: &str, imports: &[&str]) -> Vec<String> {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_python::language()).unwrap();
+        let tree = parser.parse(content, None).unwrap();
+        let imports: Vec<String> = imports.iter().map(|i| i.to_string()).collect();
+        extract_data_tables(tree.root_node(), content, &["string"], &imports)
+    }
+
+    #[test]
+    fn test_imports_and_comments_are_not_tables() {
+        let content = r#"
+from app.models import User
+from . import views
+import orders
+
+# Load the rows from the user's perspective, then join them into a report
+def report(items):
+    """Build the report from scratch and update totals."""
+    print("Choose a file from disk to upload")
+    return [item for item in items]
+"#;
+        assert!(python_tables(content, &["app.models", ".", "orders"]).is_empty());
+    }
+
+    #[test]
+    fn test_sql_strings_still_name_tables() {
+        let content = r#"
+def load(db, user_id):
+    db.execute('SELECT * FROM Users u JOIN public."Orders" o ON o.user_id = u.id WHERE u.id = %s', user_id)
+    db.execute("INSERT INTO audit_log (action) VALUES ('load')")
+    db.execute("UPDATE accounts SET seen = now()")
+    db.execute("DELETE FROM sessions WHERE expired")
+    fragment = " LEFT JOIN payments p ON p.order_id = o.id"
+    query = "SELECT id " + fragment
+"#;
+        assert_eq!(
+            python_tables(content, &[]),
+            vec!["accounts", "audit_log", "public.orders", "sessions", "users"]
+        );
+    }
+
+    #[test]
+    fn test_matches_equal_to_imports_are_dropped() {
+        let content = "import reports\n\nSQL = \"SELECT * FROM reports\"\nOTHER = \"SELECT * FROM invoices\"\n";
+        assert_eq!(python_tables(content, &["reports"]), vec!["invoices"]);
+        assert_eq!(python_tables(content, &["app.reports"]), vec!["invoices"]);
+    }
+
+    #[test]
+    fn test_builder_calls_and_per_file_cap() {
+        let content = "rows = db.table('Customers').where(active=True)\n";
+        assert_eq!(python_tables(content, &[]), vec!["customers"]);
+
+        let many: String = (0..MAX_TABLES_PER_FILE + 10)
+            .map(|i| format!("q{} = \"SELECT * FROM table_{:03}\"\n", i, i))
+            .collect();
+        let tables = python_tables(&many, &[]);
+        assert_eq!(tables.len(), MAX_TABLES_PER_FILE);
+        assert_eq!(tables[0], "table_000");
+    }
+
+    #[test]
+    fn test_normalize_table_name() {
+        assert_eq!(normalize_table_name("`Orders`").as_deref(), Some("orders"));
+        assert_eq!(normalize_table_name("[dbo].[Users]").as_deref(), Some("dbo.users"));
+        assert_eq!(normalize_table_name("users.").as_deref(), Some("users"));
+        assert_eq!(normalize_table_name("SELECT"), None);
+        assert_eq!(normalize_table_name("42"), None);
+    }
+}
@@ -0,0 +1,313 @@
+//! SQL Query Extraction
+//!
+//! Finds SQL statements embedded in string literals so they can be stored as
+//! `Query` nodes. Literals that parse cleanly with sqlparser get exact
+//! read/write table classification; anything else (string interpolation,
+//! vendor syntax) falls back to keyword regexes.
+
+use super::SqlQuery;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use sqlparser::ast::{visit_relations, Statement};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser as SqlParser;
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use tree_sitter::Node;
+
+/// Normalized query text is cut off after this many characters
+const MAX_QUERY_LENGTH: usize = 500;
+
+/// Collect SQL statements from every string literal node under `root`.
+/// `string_kinds` lists the tree-sitter node kinds the language uses for
+/// string literals.
+pub fn extract_sql_queries(root: Node, content: &str, string_kinds: &[&str]) -> Vec<SqlQuery> {
+    let mut queries: Vec<SqlQuery> = string_literals(root, content, string_kinds)
+        .into_iter()
+        .filter_map(|(literal, line)| analyze_sql(literal, line))
+        .collect();
+    queries.sort_by_key(|q| q.line);
+    queries
+}
+
+/// Contents of every string literal node under `root`, with the line each
+/// starts on
+pub fn string_literals<'a>(root: Node, content: &'a str, string_kinds: &[&str]) -> Vec<(&'a str, usize)> {
+    let mut literals = Vec::new();
+    let mut stack = vec![root];
+
+    while let Some(node) = stack.pop() {
+        if string_kinds.contains(&node.kind()) {
+            literals.push((strip_string_delimiters(&content[node.byte_range()]), node.start_position().row + 1));
+            // Nested nodes are interpolations/escapes of this literal
+            continue;
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            stack.push(child);
+        }
+    }
+
+    literals
+}
+
+/// Build a `SqlQuery` from raw literal text, or `None` if it isn't SQL
+pub fn analyze_sql(text: &str, line: usize) -> Option<SqlQuery> {
+    let statement_type = sql_statement_type(text)?;
+    let normalized = normalize_sql(text);
+
+    let (reads, writes) = tables_from_sqlparser(text)
+        .unwrap_or_else(|| tables_from_regex(text, &statement_type));
+
+    Some(SqlQuery {
+        hash: query_hash(&normalized),
+        normalized,
+        statement_type,
+        reads,
+        writes,
+        line,
+    })
+}
+
+/// Return the statement keyword if the text looks like a SQL statement:
+/// it must start with a DML/DDL keyword and contain the clause that keyword
+/// requires, which keeps prose like "Select a file to upload" out.
+fn sql_statement_type(text: &str) -> Option<String> {
+    let upper = text.trim_start().to_uppercase();
+    let keyword = upper.split_whitespace().next()?;
+
+    let structure = match keyword {
+        "SELECT" => r"^SELECT\s+[\s\S]+?\s+FROM\s+[A-Z0-9_.`\x22\[]",
+        "INSERT" => r"^INSERT\s+(OR\s+\w+\s+)?INTO\s+\S+",
+        "UPDATE" => r"^UPDATE\s+\S+\s+SET\s+",
+        "DELETE" => r"^DELETE\s+FROM\s+\S+",
+        "CREATE" => r"^CREATE\s+(OR\s+REPLACE\s+)?(UNIQUE\s+)?(TEMP(ORARY)?\s+)?(TABLE|INDEX|VIEW)\s+",
+        _ => return None,
+    };
+
+    let re = Regex::new(structure).ok()?;
+    if re.is_match(&upper) {
+        Some(keyword.to_string())
+    } else {
+        None
+    }
+}
+
+/// Collapse whitespace, replace literal values with `?` and truncate
+pub fn normalize_sql(text: &str) -> String {
+    let mut normalized = text.trim().to_string();
+
+    let replacements = [
+        (r"'(?:[^'\\]|\\.|'')*'", "?"),   // 'string' literals
+        (r"\$\{[^}]*\}", "?"),            // ${js} template substitutions
+        (r"\{[a-zA-Z_][a-zA-Z0-9_]*\}", "?"), // {python} format fields
+        (r"\b\d+(?:\.\d+)?\b", "?"),      // numeric literals
+        (r"\s+", " "),
+    ];
+    for (pattern, replacement) in replacements {
+        if let Ok(re) = Regex::new(pattern) {
+            normalized = re.replace_all(&normalized, replacement).to_string();
+        }
+    }
+
+    if normalized.chars().count() > MAX_QUERY_LENGTH {
+        normalized = normalized.chars().take(MAX_QUERY_LENGTH).collect();
+        normalized.push_str("...");
+    }
+
+    normalized
+}
+
+fn query_hash(normalized: &str) -> String {
+    let digest = Sha256::digest(normalized.as_bytes());
+    format!("{:x}", digest)[..16].to_string()
+}
+
+/// Exact table classification from the parsed AST. The first relation of a
+/// write statement is its target (INSERT INTO t, UPDATE t, DELETE FROM t,
+/// CREATE TABLE t); every other relation is read.
+fn tables_from_sqlparser(text: &str) -> Option<(Vec<String>, Vec<String>)> {
+    let statements = SqlParser::parse_sql(&GenericDialect {}, text).ok()?;
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+
+    for statement in &statements {
+        let is_write = matches!(
+            statement,
+            Statement::Insert { .. }
+                | Statement::Update { .. }
+                | Statement::Delete { .. }
+                | Statement::CreateTable { .. }
+        );
+
+        let mut relations = Vec::new();
+        let _ = visit_relations(statement, |relation| {
+            relations.push(relation.to_string());
+            ControlFlow::<()>::Continue(())
+        });
+
+        for (i, relation) in relations.into_iter().enumerate() {
+            let target = if is_write && i == 0 { &mut writes } else { &mut reads };
+            if !target.contains(&relation) {
+                target.push(relation);
+            }
+        }
+    }
+
+    if reads.is_empty() && writes.is_empty() {
+        return None;
+    }
+    Some((reads, writes))
+}
+
+/// Regex fallback mirroring the parsers' table patterns
+fn tables_from_regex(text: &str, statement_type: &str) -> (Vec<String>, Vec<String>) {
+    let mut writes = Vec::new();
+    let write_pattern = match statement_type {
+        "INSERT" => Some(r"(?i)\binto\s+([a-zA-Z0-9_.]+)"),
+        "UPDATE" => Some(r"(?i)^\s*update\s+([a-zA-Z0-9_.]+)"),
+        "DELETE" => Some(r"(?i)\bdelete\s+from\s+([a-zA-Z0-9_.]+)"),
+        "CREATE" => Some(r"(?i)\b(?:table|index|view)\s+(?:if\s+not\s+exists\s+)?([a-zA-Z0-9_.]+)"),
+        _ => None,
+    };
+    if let Some(re) = write_pattern.and_then(|p| Regex::new(p).ok()) {
+        if let Some(m) = re.captures(text).and_then(|c| c.get(1)) {
+            writes.push(m.as_str().to_string());
+        }
+    }
+
+    let mut seen: HashSet<String> = writes.iter().cloned().collect();
+    let mut reads = Vec::new();
+    for pattern in [r"(?i)\bfrom\s+([a-zA-Z0-9_.]+)", r"(?i)\bjoin\s+([a-zA-Z0-9_.]+)"] {
+        if let Ok(re) = Regex::new(pattern) {
+            for cap in re.captures_iter(text) {
+                if let Some(m) = cap.get(1) {
+                    let table = m.as_str().to_string();
+                    if seen.insert(table.clone()) {
+                        reads.push(table);
+                    }
+                }
+            }
+        }
+    }
+
+    (reads, writes)
+}
+
+/// Strip prefixes (`r`, `f`, `b`...), raw-string hashes and quotes from a
+/// literal's source text, leaving only its contents.
+fn strip_string_delimiters(raw: &str) -> &str {
+    let body = raw.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+    let hashes = body.len() - body.trim_start_matches('#').len();
+    let body = &body[hashes..];
+
+    let quote = match body.chars().next() {
+        Some(q @ ('"' | '\'' | '`')) => q,
+        _ => return raw,
+    };
+    let triple = quote.to_string().repeat(3);
+    let delimiter = if body.starts_with(&triple) { triple } else { quote.to_string() };
+    let closing = format!("{}{}", delimiter, "#".repeat(hashes));
+
+    let inner = body.strip_prefix(delimiter.as_str()).unwrap_or(body);
+    inner.strip_suffix(closing.as_str()).unwrap_or(inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_with_join_reads_all_tables() {
+        let query = analyze_sql(
+            "SELECT u.name, o.total\n    FROM users u\n    JOIN orders o ON o.user_id = u.id\n    WHERE o.status = 'paid' AND o.total > 100",
+            7,
+        )
+        .expect("query not detected");
+
+        assert_eq!(query.statement_type, "SELECT");
+        assert_eq!(query.reads, vec!["users", "orders"]);
+        assert!(query.writes.is_empty());
+        assert_eq!(query.line, 7);
+        assert_eq!(
+            query.normalized,
+            "SELECT u.name, o.total FROM users u JOIN orders o ON o.user_id = u.id WHERE o.status = ? AND o.total > ?"
+        );
+    }
+
+    #[test]
+    fn test_insert_select_classifies_reads_and_writes() {
+        let query = analyze_sql(
+            "INSERT INTO audit_log (user_id, action) SELECT id, 'login' FROM users WHERE id = 42",
+            1,
+        )
+        .expect("query not detected");
+
+        assert_eq!(query.statement_type, "INSERT");
+        assert_eq!(query.writes, vec!["audit_log"]);
+        assert_eq!(query.reads, vec!["users"]);
+        assert_eq!(
+            query.normalized,
+            "INSERT INTO audit_log (user_id, action) SELECT id, ? FROM users WHERE id = ?"
+        );
+    }
+
+    #[test]
+    fn test_unparseable_sql_falls_back_to_regex() {
+        let query = analyze_sql("UPDATE accounts SET balance = ${amount} WHERE id IN (SELECT account_id FROM holds)", 3)
+            .expect("query not detected");
+
+        assert_eq!(query.statement_type, "UPDATE");
+        assert_eq!(query.writes, vec!["accounts"]);
+        assert_eq!(query.reads, vec!["holds"]);
+        assert_eq!(query.normalized, "UPDATE accounts SET balance = ? WHERE id IN (SELECT account_id FROM holds)");
+    }
+
+    #[test]
+    fn test_identical_queries_share_hash() {
+        let a = analyze_sql("SELECT * FROM users WHERE id = 1", 1).unwrap();
+        let b = analyze_sql("SELECT *   FROM users\nWHERE id = 2", 9).unwrap();
+        assert_eq!(a.hash, b.hash);
+        assert_eq!(a.hash.len(), 16);
+    }
+
+    #[test]
+    fn test_non_sql_strings_are_ignored() {
+        assert!(analyze_sql("Select an option", 1).is_none());
+        assert!(analyze_sql("Update available, restart to apply", 1).is_none());
+        assert!(analyze_sql("https://example.com/users", 1).is_none());
+    }
+
+    #[test]
+    fn test_long_queries_are_truncated() {
+        let columns: Vec<String> = (0..200).map(|i| format!("column_name_{}", i)).collect();
+        let text = format!("SELECT {} FROM wide_table", columns.join(", "));
+        let query = analyze_sql(&text, 1).unwrap();
+        assert_eq!(query.normalized.chars().count(), MAX_QUERY_LENGTH + 3);
+        assert!(query.normalized.ends_with("..."));
+        assert_eq!(query.reads, vec!["wide_table"]);
+    }
+
+    #[test]
+    fn test_extract_from_python_literals() {
+        let content = "def load(db):\n    db.execute(\"\"\"\n        SELECT * FROM orders\n    \"\"\")\n    log('select nothing')\n";
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_python::language()).unwrap();
+        let tree = parser.parse(content, None).unwrap();
+
+        let queries = extract_sql_queries(tree.root_node(), content, &["string"]);
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].normalized, "SELECT * FROM orders");
+        assert_eq!(queries[0].line, 2);
+    }
+
+    #[test]
+    fn test_strip_string_delimiters() {
+        assert_eq!(strip_string_delimiters("\"SELECT 1\""), "SELECT 1");
+        assert_eq!(strip_string_delimiters("r#\"SELECT 1\"#"), "SELECT 1");
+        assert_eq!(strip_string_delimiters("f'''SELECT 1'''"), "SELECT 1");
+        assert_eq!(strip_string_delimiters("`SELECT 1`"), "SELECT 1");
+        assert_eq!(strip_string_delimiters("\"a = 'x'\""), "a = 'x'");
+    }
+}
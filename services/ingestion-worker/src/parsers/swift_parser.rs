@@ -0,0 +1,448 @@
+use super::{ClassInfo, FunctionInfo, InheritanceInfo, LanguageParser, ParsedFile, ServiceCall};
+use super::{body_hash, count_lines, cyclomatic_complexity};
+use super::env_vars::{extract_env_vars, SWIFT_PATTERNS};
+use super::test_files::{is_test_file, SWIFT_SIGNALS};
+use super::sql_extractor::extract_data_tables;
+use super::sql_queries::extract_sql_queries;
+use crate::url_utils::canonical_host;
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tree_sitter::{Node, Parser, Query, QueryCursor};
+
+/// Node kinds of string literals, searched for SQL
+const STRING_KINDS: &[&str] = &["line_string_literal", "multi_line_string_literal", "raw_string_literal"];
+
+/// Node kinds that add a decision point to a function's complexity
+const BRANCH_KINDS: &[&str] = &[
+    "if_statement", "guard_statement", "for_statement", "while_statement", "repeat_while_statement",
+    "switch_entry", "catch_block", "ternary_expression", "nil_coalescing_expression", "&&", "||",
+];
+
+/// Declarations that can contain methods
+const TYPE_BODY_KINDS: &[&str] = &["class_body", "enum_class_body", "protocol_body"];
+
+pub struct SwiftParser;
+
+/// A type declaration or extension, before extensions are folded into the
+/// types they extend
+struct SwiftType {
+    info: ClassInfo,
+    is_extension: bool,
+    is_protocol: bool,
+}
+
+impl SwiftParser {
+    pub fn new() -> Result<Self> {
+        Ok(SwiftParser)
+    }
+
+    fn extract_service_calls(&self, content: &str) -> Vec<ServiceCall> {
+        let mut services = HashSet::new();
+        let url_pattern = r#"(?i)\b(https?|grpc)://[^\s'"`]+"#;
+
+        if let Ok(re) = Regex::new(url_pattern) {
+            for cap in re.captures_iter(content) {
+                let full = cap.get(0).map(|m| m.as_str()).unwrap_or_default();
+                let protocol = cap.get(1).map(|m| m.as_str()).unwrap_or("http");
+                if let Some(host) = canonical_host(full) {
+                    services.insert((host, protocol.to_string()));
+                }
+            }
+        }
+
+        services
+            .into_iter()
+            .map(|(host, protocol)| ServiceCall::new(host, protocol))
+            .collect()
+    }
+
+    /// Local parameter name; `_ tags: String` and `with tags: String` both
+    /// give `tags`
+    fn extract_param(&self, node: Node, content: &str) -> Option<String> {
+        let mut cursor = node.walk();
+        let name = node
+            .named_children(&mut cursor)
+            .filter(|part| part.kind() == "simple_identifier")
+            .last()?;
+        Some(content[name.byte_range()].to_string())
+    }
+
+    fn extract_calls(&self, node: Node, content: &str, query: &Query) -> Vec<String> {
+        let mut calls = Vec::new();
+        let mut call_sites = HashSet::new();
+        let mut query_cursor = QueryCursor::new();
+        let matches = query_cursor.matches(query, node, content.as_bytes());
+        for m in matches {
+            for capture in m.captures {
+                let capture_name = &query.capture_names()[capture.index as usize];
+                if capture_name == "call.name" {
+                    let call_name = content[capture.node.byte_range()].to_string();
+                    if call_sites.insert(capture.node.id()) {
+                        calls.push(call_name);
+                    }
+                }
+            }
+        }
+        calls
+    }
+
+    /// A `func`, `init` or protocol requirement. Field names aren't reliable
+    /// in this grammar, so the parts are found by kind: the first identifier
+    /// is the name and the type after `->` the return type.
+    fn extract_function(&self, node: Node, content: &str, call_query: &Query) -> Option<FunctionInfo> {
+        let mut name = (node.kind() == "init_declaration").then(|| "init".to_string());
+        let mut params = Vec::new();
+        let mut return_type = None;
+        let mut after_arrow = false;
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            match child.kind() {
+                "simple_identifier" if name.is_none() => name = Some(content[child.byte_range()].to_string()),
+                "parameter" => params.extend(self.extract_param(child, content)),
+                "->" => after_arrow = true,
+                "function_body" => break,
+                _ if after_arrow && return_type.is_none() && child.is_named() => {
+                    return_type = Some(content[child.byte_range()].to_string());
+                }
+                _ => {}
+            }
+        }
+
+        Some(FunctionInfo {
+            name: name?,
+            params,
+            return_type,
+            calls: self.extract_calls(node, content, call_query),
+            start_line: node.start_position().row + 1,
+            end_line: node.end_position().row + 1,
+            complexity: cyclomatic_complexity(node, BRANCH_KINDS),
+            body_hash: body_hash(node, content),
+            decorators: Vec::new(),
+            visibility: None,
+        })
+    }
+
+    fn extract_methods(&self, body: Node, content: &str, call_query: &Query) -> Vec<FunctionInfo> {
+        let mut methods = Vec::new();
+        let mut cursor = body.walk();
+        for child in body.named_children(&mut cursor) {
+            if matches!(child.kind(), "function_declaration" | "init_declaration" | "protocol_function_declaration") {
+                methods.extend(self.extract_function(child, content, call_query));
+            }
+        }
+        methods
+    }
+
+    /// Classes, structs, enums, actors, protocols and extensions declared in
+    /// `container`, nested declarations included
+    fn extract_types(&self, container: Node, content: &str, call_query: &Query, types: &mut Vec<SwiftType>) {
+        let mut cursor = container.walk();
+        for node in container.named_children(&mut cursor) {
+            if node.kind() != "class_declaration" && node.kind() != "protocol_declaration" {
+                continue;
+            }
+
+            let mut name = None;
+            let mut keyword = "";
+            let mut inheritances = Vec::new();
+            let mut body = None;
+            let mut child_cursor = node.walk();
+            for child in node.children(&mut child_cursor) {
+                match child.kind() {
+                    "class" | "struct" | "enum" | "actor" | "extension" | "protocol" => keyword = child.kind(),
+                    // Extensions name the extended type as a user_type
+                    "type_identifier" | "user_type" if name.is_none() => {
+                        name = Some(strip_type_arguments(&content[child.byte_range()]));
+                    }
+                    "inheritance_specifier" => {
+                        if let Some(parent) = child.named_child(0) {
+                            inheritances.push(InheritanceInfo {
+                                name: strip_type_arguments(&content[parent.byte_range()]),
+                                kind: "interface".to_string(),
+                            });
+                        }
+                    }
+                    kind if TYPE_BODY_KINDS.contains(&kind) => body = Some(child),
+                    _ => {}
+                }
+            }
+            let Some(name) = name else { continue };
+
+            // Only a class can have a superclass, and it has to come first
+            // in the list; anything else is a protocol
+            if keyword == "class" {
+                if let Some(first) = inheritances.first_mut() {
+                    first.kind = "class".to_string();
+                }
+            }
+
+            types.push(SwiftType {
+                info: ClassInfo {
+                    name,
+                    kind: None,
+                    inheritances,
+                    methods: body
+                        .map(|body| self.extract_methods(body, content, call_query))
+                        .unwrap_or_default(),
+                    start_line: node.start_position().row + 1,
+                    end_line: node.end_position().row + 1,
+                    body_hash: body_hash(node, content),
+                    decorators: Vec::new(),
+                    visibility: None,
+                },
+                is_extension: keyword == "extension",
+                is_protocol: keyword == "protocol",
+            });
+            if let Some(body) = body {
+                self.extract_types(body, content, call_query, types);
+            }
+        }
+    }
+}
+
+/// Fold extensions into the types they extend. A type only seen through
+/// extensions gets a placeholder, like a Rust type only seen through `impl`
+/// blocks. A "superclass" that is a protocol declared in the file is
+/// corrected to an interface.
+fn merge_extensions(types: Vec<SwiftType>) -> Vec<ClassInfo> {
+    let protocols: HashSet<String> = types
+        .iter()
+        .filter(|t| t.is_protocol)
+        .map(|t| t.info.name.clone())
+        .collect();
+
+    let (extensions, declarations): (Vec<SwiftType>, Vec<SwiftType>) = types.into_iter().partition(|t| t.is_extension);
+    let mut classes: Vec<ClassInfo> = declarations.into_iter().map(|t| t.info).collect();
+    for extension in extensions {
+        let extension = extension.info;
+        match classes.iter_mut().find(|c| c.name == extension.name) {
+            Some(class) => {
+                class.inheritances.extend(extension.inheritances);
+                class.methods.extend(extension.methods);
+            }
+            None => classes.push(ClassInfo {
+                kind: None,
+                start_line: 0,
+                end_line: 0,
+                body_hash: String::new(),
+                decorators: Vec::new(),
+                visibility: None,
+                ..extension
+            }),
+        }
+    }
+
+    for inheritance in classes.iter_mut().flat_map(|c| c.inheritances.iter_mut()) {
+        if inheritance.kind == "class" && protocols.contains(&inheritance.name) {
+            inheritance.kind = "interface".to_string();
+        }
+    }
+    classes
+}
+
+impl LanguageParser for SwiftParser {
+    fn parse_file(&self, path: &PathBuf, content: &str) -> Result<ParsedFile> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(tree_sitter_swift::language())
+            .context("Failed to set Swift language")?;
+        let tree = parser
+            .parse(content, None)
+            .context("Failed to parse Swift file")?;
+
+        let root_node = tree.root_node();
+        let mut functions = Vec::new();
+        let mut types = Vec::new();
+        let mut imports = Vec::new();
+
+        let call_query = Query::new(
+            tree_sitter_swift::language(),
+            r#"
+            (call_expression (simple_identifier) @call.name)
+            (call_expression (navigation_expression (navigation_suffix (simple_identifier) @call.name)))
+            "#,
+        )?;
+
+        // 1. Types and extensions
+        self.extract_types(root_node, content, &call_query, &mut types);
+
+        // 2. Top level functions and imports
+        let mut cursor = root_node.walk();
+        for node in root_node.named_children(&mut cursor) {
+            match node.kind() {
+                "function_declaration" => functions.extend(self.extract_function(node, content, &call_query)),
+                "import_declaration" => {
+                    // `import struct Combine.AnyPublisher` imports one symbol
+                    let mut import_cursor = node.walk();
+                    let module = node
+                        .named_children(&mut import_cursor)
+                        .find(|part| part.kind() == "identifier");
+                    if let Some(module) = module {
+                        imports.push(content[module.byte_range()].to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let data_tables = extract_data_tables(root_node, content, STRING_KINDS, &imports);
+        let service_calls = self.extract_service_calls(content);
+        let sql_queries = extract_sql_queries(root_node, content, STRING_KINDS);
+
+        Ok(ParsedFile {
+            path: path.to_string_lossy().to_string(),
+            language: "swift".to_string(),
+            functions,
+            classes: merge_extensions(types),
+            interfaces: Vec::new(),
+            imports,
+            data_tables,
+            service_calls,
+            sql_queries,
+            env_vars: extract_env_vars(content, SWIFT_PATTERNS),
+            is_test: is_test_file(&path.to_string_lossy(), content, SWIFT_SIGNALS),
+            generated: false,
+            parse_failed: false,
+            rendered_components: Vec::new(),
+            reexports: Vec::new(),
+            line_count: count_lines(content),
+        })
+    }
+}
+
+fn strip_type_arguments(type_name: &str) -> String {
+    type_name
+        .split('<')
+        .next()
+        .unwrap_or(type_name)
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_swift_full() {
+        let parser = SwiftParser::new().unwrap();
+        let content = r#"
+            import Foundation
+            import struct Combine.AnyPublisher
+
+            class UserService: BaseService, Auditable, Codable {
+                let repo: UserRepository
+
+                init(repo: UserRepository) {
+                    self.repo = repo
+                }
+
+                func findAll(limit: Int, _ tags: String...) -> [User] {
+                    let users = repo.query("SELECT id, name FROM users")
+                    audit(action: "findAll")
+                    if limit > 0 && !users.isEmpty {
+                        return Array(users.prefix(limit))
+                    }
+                    return users
+                }
+
+                static func make() -> UserService {
+                    UserService(repo: UserRepository())
+                }
+
+                struct Cache {
+                    func clear() {}
+                }
+            }
+
+            protocol Auditable: Named {
+                func audit(action: String)
+            }
+
+            class AuditLog: Auditable {
+                func audit(action: String) {}
+            }
+
+            enum Role: String {
+                case admin, user
+
+                func isAdmin() -> Bool {
+                    self == .admin
+                }
+            }
+
+            extension UserService: CustomStringConvertible {
+                var description: String { "users" }
+
+                func reset() {}
+            }
+
+            extension Date {
+                func iso() -> String { ISO8601DateFormatter().string(from: self) }
+            }
+
+            func main() {
+                let service = UserService.make()
+                service.findAll(limit: 10)
+            }
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("UserService.swift"), content).unwrap();
+
+        assert_eq!(result.language, "swift");
+
+        // Imports
+        assert_eq!(result.imports, vec!["Foundation", "Combine.AnyPublisher"]);
+
+        // Classes, with the extension's conformance and methods folded in
+        let service = result.classes.iter().find(|c| c.name == "UserService").expect("UserService not found");
+        assert!(service.inheritances.iter().any(|i| i.name == "BaseService" && i.kind == "class"));
+        assert!(service.inheritances.iter().any(|i| i.name == "Auditable" && i.kind == "interface"));
+        assert!(service.inheritances.iter().any(|i| i.name == "CustomStringConvertible" && i.kind == "interface"));
+        assert!(service.methods.iter().any(|m| m.name == "init"));
+        assert!(service.methods.iter().any(|m| m.name == "make"));
+        assert!(service.methods.iter().any(|m| m.name == "reset"));
+        assert!(!service.methods.iter().any(|m| m.name == "clear"));
+        assert!(!service.body_hash.is_empty());
+
+        let find_all = service.methods.iter().find(|m| m.name == "findAll").expect("findAll not found");
+        assert_eq!(find_all.params, vec!["limit", "tags"]);
+        assert_eq!(find_all.return_type.as_deref(), Some("[User]"));
+        assert!(find_all.calls.contains(&"query".to_string()));
+        assert!(find_all.calls.contains(&"audit".to_string()));
+        assert!(find_all.calls.contains(&"prefix".to_string()));
+        // if, &&
+        assert_eq!(find_all.complexity, 3);
+
+        // Nested struct, protocol, enum
+        let cache = result.classes.iter().find(|c| c.name == "Cache").expect("Cache not found");
+        assert!(cache.methods.iter().any(|m| m.name == "clear"));
+
+        let auditable = result.classes.iter().find(|c| c.name == "Auditable").expect("Auditable not found");
+        assert!(auditable.inheritances.iter().any(|i| i.name == "Named" && i.kind == "interface"));
+        assert!(auditable.methods.iter().any(|m| m.name == "audit"));
+
+        // A protocol declared in the file is never a superclass
+        let audit_log = result.classes.iter().find(|c| c.name == "AuditLog").expect("AuditLog not found");
+        assert_eq!(audit_log.inheritances[0].kind, "interface");
+
+        let role = result.classes.iter().find(|c| c.name == "Role").expect("Role not found");
+        assert_eq!(role.methods[0].return_type.as_deref(), Some("Bool"));
+
+        // Extension of a type declared elsewhere
+        let date = result.classes.iter().find(|c| c.name == "Date").expect("Date not found");
+        assert!(date.body_hash.is_empty());
+        assert!(date.methods.iter().any(|m| m.name == "iso"));
+
+        // Functions
+        let main = result.functions.iter().find(|f| f.name == "main").expect("main not found");
+        assert_eq!(main.calls, vec!["make", "findAll"]);
+
+        // SQL
+        assert_eq!(result.sql_queries.len(), 1);
+        assert_eq!(result.sql_queries[0].reads, vec!["users"]);
+    }
+}
@@ -0,0 +1,155 @@
+//! Test File Classification
+//!
+//! Decides whether a file is test code so the graph can tell tests apart
+//! from the code they exercise. Path conventions (`__tests__/`, `tests/`,
+//! `*_test.go`, `*.spec.ts`, `test_*.py`, `FooTest.java`, ...) apply to every
+//! language; each parser adds regexes for the content signals of its own
+//! language, such as a pytest import or a JUnit import.
+
+use regex::Regex;
+
+/// Files made of tests only: a leading `#[cfg(test)]` or `#![cfg(test)]`.
+/// A production file with an inline `mod tests` is not a test file.
+pub const RUST_SIGNALS: &[&str] = &[r"\A(?:\s|//[^\n]*)*#!?\[cfg\(test\)\]"];
+
+/// Top-level `describe("...")` blocks and test framework imports
+pub const JAVASCRIPT_SIGNALS: &[&str] = &[
+    r#"(?m)^describe(?:\.(?:each\([^)]*\)|only|skip))?\(\s*['"`]"#,
+    r#"\bfrom\s+['"](?:vitest|@jest/globals|mocha|@testing-library/[a-z-]+)['"]"#,
+];
+
+/// pytest imports and `unittest.TestCase` subclasses
+pub const PYTHON_SIGNALS: &[&str] = &[r"(?m)^\s*(?:import|from)\s+pytest\b", r"\bunittest\.TestCase\b"];
+
+/// Go only runs tests from `_test.go` files, so the path says it all
+pub const GO_SIGNALS: &[&str] = &[];
+
+/// JUnit, TestNG and kotlin.test imports, shared by Java and Kotlin
+pub const JVM_SIGNALS: &[&str] = &[r"(?m)^import\s+(?:static\s+)?(?:org\.junit|org\.testng|kotlin\.test)\b"];
+
+/// XCTest imports and `@testable import`
+pub const SWIFT_SIGNALS: &[&str] = &[r"(?m)^\s*import\s+XCTest\b", r"(?m)^\s*@testable\s+import\b"];
+
+/// RSpec and Minitest: spec helpers, `RSpec.describe` and test case classes
+pub const RUBY_SIGNALS: &[&str] = &[
+    r#"(?m)^\s*require\s+['"](?:rails_helper|spec_helper|test_helper|minitest/autorun)['"]"#,
+    r"(?m)^\s*RSpec\.describe\b",
+    r"<\s*(?:Minitest::Test|ActiveSupport::TestCase|ActionDispatch::IntegrationTest)\b",
+];
+
+/// PHPUnit imports and Pest's top-level `it(...)`/`test(...)`
+pub const PHP_SIGNALS: &[&str] = &[r"(?m)^\s*use\s+PHPUnit\\", r#"(?m)^(?:it|test)\(\s*['"]"#];
+
+/// GoogleTest, Catch2, doctest and Boost.Test headers
+pub const CPP_SIGNALS: &[&str] = &[r#"(?m)^\s*#\s*include\s*[<"](?:gtest|gmock|catch2?|doctest|boost/test)/"#];
+
+/// Directories whose files are all tests
+const TEST_DIRS: &[&str] = &["__tests__", "tests", "test", "spec"];
+
+/// Whether `path` is a test file by name or location, or `content` matches
+/// one of the language's `signals`
+pub fn is_test_file(path: &str, content: &str, signals: &[&str]) -> bool {
+    is_test_path(path) || signals.iter().any(|pattern| Regex::new(pattern).unwrap().is_match(content))
+}
+
+fn is_test_path(path: &str) -> bool {
+    let mut segments: Vec<&str> = path.split(['/', '\\']).collect();
+    let file_name = segments.pop().unwrap_or_default();
+    if segments.iter().any(|dir| TEST_DIRS.contains(dir)) {
+        return true;
+    }
+
+    let lower = file_name.to_lowercase();
+    if lower.contains(".test.") || lower.contains(".spec.") || lower == "conftest.py" {
+        return true;
+    }
+
+    let stem = file_name.split('.').next().unwrap_or_default();
+    let lower_stem = stem.to_lowercase();
+    lower_stem.starts_with("test_")
+        || lower_stem.ends_with("_test")
+        || lower_stem.ends_with("_tests")
+        || lower_stem.ends_with("_spec")
+        || lower_stem.ends_with("_unittest")
+        // FooTest.java, FooTests.swift; "Latest" or "Contest" don't count
+        || ((stem.ends_with("Test") || stem.ends_with("Tests")) && stem.len() > 5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_conventions() {
+        for path in [
+            "web/src/__tests__/App.tsx",
+            "tests/integration.rs",
+            "src/test/java/com/acme/UserService.java",
+            "spec/models/user.rb",
+            "pkg/server/handler_test.go",
+            "web/src/button.spec.ts",
+            "web/src/button.test.jsx",
+            "api/test_views.py",
+            "api/conftest.py",
+            "app/src/main/java/com/acme/UserServiceTest.java",
+            "Sources/AppTests/LoginTests.swift",
+            "src/codec_unittest.cc",
+        ] {
+            assert!(is_test_file(path, "", &[]), "{} should be a test file", path);
+        }
+
+        for path in [
+            "src/main.rs",
+            "pkg/server/latest.go",
+            "src/testing_utils.py",
+            "src/Contest.java",
+            "src/Test.java",
+            "web/src/contest.ts",
+            "api/attestation.py",
+        ] {
+            assert!(!is_test_file(path, "", &[]), "{} should not be a test file", path);
+        }
+    }
+
+    #[test]
+    fn test_rust_content_signals() {
+        assert!(is_test_file("src/fixtures.rs", "//! Shared fixtures\n\n#![cfg(test)]\nuse super::*;\n", RUST_SIGNALS));
+        assert!(is_test_file("src/checks.rs", "#[cfg(test)]\nmod checks {}\n", RUST_SIGNALS));
+        // Inline test modules don't make a production file a test file
+        assert!(!is_test_file(
+            "src/lib.rs",
+            "pub fn add() {}\n\n#[cfg(test)]\nmod tests {}\n",
+            RUST_SIGNALS
+        ));
+    }
+
+    #[test]
+    fn test_javascript_and_python_content_signals() {
+        assert!(is_test_file("src/cart.ts", "import { cart } from './cart';\n\ndescribe('cart', () => {});\n", JAVASCRIPT_SIGNALS));
+        assert!(is_test_file("src/cart.ts", "import { it, expect } from 'vitest';\n", JAVASCRIPT_SIGNALS));
+        assert!(!is_test_file("src/docs.ts", "export function describe(thing) {}\n", JAVASCRIPT_SIGNALS));
+
+        assert!(is_test_file("api/checks.py", "import pytest\n\ndef check_login(): pass\n", PYTHON_SIGNALS));
+        assert!(is_test_file("api/checks.py", "class LoginCase(unittest.TestCase):\n    pass\n", PYTHON_SIGNALS));
+        assert!(!is_test_file("api/views.py", "import requests\n", PYTHON_SIGNALS));
+    }
+
+    #[test]
+    fn test_jvm_swift_cpp_ruby_and_php_content_signals() {
+        assert!(is_test_file("src/Checks.java", "import org.junit.jupiter.api.Test;\n", JVM_SIGNALS));
+        assert!(is_test_file("src/Checks.kt", "import kotlin.test.assertEquals\n", JVM_SIGNALS));
+        assert!(!is_test_file("src/User.java", "import java.util.List;\n", JVM_SIGNALS));
+
+        assert!(is_test_file("Sources/Checks.swift", "import XCTest\n@testable import App\n", SWIFT_SIGNALS));
+        assert!(is_test_file("src/checks.cc", "#include <gtest/gtest.h>\n", CPP_SIGNALS));
+        assert!(!is_test_file("src/main.cc", "#include <vector>\n", CPP_SIGNALS));
+
+        assert!(is_test_file("lib/checks.rb", "require \"rails_helper\"\n", RUBY_SIGNALS));
+        assert!(is_test_file("lib/checks.rb", "class InvoiceCheck < ActiveSupport::TestCase\nend\n", RUBY_SIGNALS));
+        assert!(!is_test_file("app/models/invoice.rb", "class Invoice < ApplicationRecord\nend\n", RUBY_SIGNALS));
+
+        assert!(is_test_file("app/Checks.php", "<?php\nuse PHPUnit\\Framework\\TestCase;\n", PHP_SIGNALS));
+        assert!(is_test_file("app/Checks.php", "<?php\n\nit('lists invoices', function () {});\n", PHP_SIGNALS));
+        assert!(!is_test_file("app/Models/Invoice.php", "<?php\nuse Illuminate\\Database\\Eloquent\\Model;\n", PHP_SIGNALS));
+    }
+}
@@ -1,11 +1,28 @@
 use super::{ClassInfo, FunctionInfo, LanguageParser, ParsedFile};
+use super::{body_hash, decorators, count_lines, cyclomatic_complexity};
+use super::env_vars::{extract_env_vars, JAVASCRIPT_PATTERNS};
+use super::test_files::{is_test_file, JAVASCRIPT_SIGNALS};
+use super::sql_extractor::extract_data_tables;
+use super::sql_queries::extract_sql_queries;
+use super::jsx::extract_rendered_components;
+use super::exports::{member_visibility, module_exports};
 use super::{InheritanceInfo, ServiceCall};
+use crate::url_utils::canonical_host;
 use anyhow::{Context, Result};
 use regex::Regex;
 use std::collections::HashSet;
 use std::path::PathBuf;
 use tree_sitter::{Node, Parser, Query, QueryCursor};
 
+/// Node kinds of string literals, searched for SQL
+const STRING_KINDS: &[&str] = &["string", "template_string"];
+
+/// Node kinds that add a decision point to a function's complexity
+const BRANCH_KINDS: &[&str] = &[
+    "if_statement", "for_statement", "for_in_statement", "while_statement", "do_statement",
+    "switch_case", "catch_clause", "ternary_expression", "&&", "||", "??",
+];
+
 pub struct TypeScriptParser;
 
 impl TypeScriptParser {
@@ -13,30 +30,6 @@ impl TypeScriptParser {
         Ok(TypeScriptParser)
     }
 
-    fn extract_data_tables(&self, content: &str) -> Vec<String> {
-        let mut tables = HashSet::new();
-        let patterns = [
-            r"(?i)\bfrom\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bjoin\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\binto\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bupdate\s+([a-zA-Z0-9_.]+)",
-            r"(?i)\bdelete\s+from\s+([a-zA-Z0-9_.]+)",
-            r#"(?i)\btable\(\s*['"]([a-zA-Z0-9_.]+)['"]"#,
-        ];
-
-        for pattern in patterns {
-            if let Ok(re) = Regex::new(pattern) {
-                for cap in re.captures_iter(content) {
-                    if let Some(m) = cap.get(1) {
-                        tables.insert(m.as_str().to_string());
-                    }
-                }
-            }
-        }
-
-        tables.into_iter().collect()
-    }
-
     fn extract_service_calls(&self, content: &str) -> Vec<ServiceCall> {
         let mut services = HashSet::new();
         let url_pattern = r#"(?i)\b(https?|grpc)://[^\s'"`]+"#;
@@ -45,15 +38,15 @@ impl TypeScriptParser {
             for cap in re.captures_iter(content) {
                 let full = cap.get(0).map(|m| m.as_str()).unwrap_or_default();
                 let protocol = cap.get(1).map(|m| m.as_str()).unwrap_or("http");
-                if let Some(target) = extract_service_target(full) {
-                    services.insert((target, protocol.to_string()));
+                if let Some(host) = canonical_host(full) {
+                    services.insert((host, protocol.to_string()));
                 }
             }
         }
 
         services
             .into_iter()
-            .map(|(target, protocol)| ServiceCall { target, protocol })
+            .map(|(host, protocol)| ServiceCall::new(host, protocol))
             .collect()
     }
 
@@ -85,7 +78,8 @@ impl TypeScriptParser {
     }
 
     fn extract_calls(&self, node: Node, content: &str, query: &Query) -> Vec<String> {
-        let mut calls = HashSet::new();
+        let mut calls = Vec::new();
+        let mut call_sites = HashSet::new();
         let mut query_cursor = QueryCursor::new();
         let matches = query_cursor.matches(query, node, content.as_bytes());
         
@@ -94,19 +88,28 @@ impl TypeScriptParser {
                  let capture_name = &query.capture_names()[capture.index as usize];
                  if capture_name == "call.name" {
                      let call_name = content[capture.node.byte_range()].to_string();
-                     calls.insert(call_name);
+                     if call_sites.insert(capture.node.id()) {
+                         calls.push(call_name);
+                     }
                  }
             }
         }
-        calls.into_iter().collect()
+        calls
     }
 }
 
 impl LanguageParser for TypeScriptParser {
     fn parse_file(&self, path: &PathBuf, content: &str) -> Result<ParsedFile> {
+        // JSX only parses with the TSX grammar, and type assertions like
+        // `<T>value` only without it
+        let language = if path.extension().is_some_and(|ext| ext == "tsx") {
+            tree_sitter_typescript::language_tsx()
+        } else {
+            tree_sitter_typescript::language_typescript()
+        };
         let mut parser = Parser::new();
         parser
-            .set_language(tree_sitter_typescript::language_typescript())
+            .set_language(language)
             .context("Failed to set TypeScript language")?;
         let tree = parser
             .parse(content, None)
@@ -119,7 +122,7 @@ impl LanguageParser for TypeScriptParser {
 
         // queries
         let function_query = Query::new(
-            tree_sitter_typescript::language_typescript(),
+            language,
             r#"
             (function_declaration
               name: (identifier) @func.name
@@ -148,7 +151,7 @@ impl LanguageParser for TypeScriptParser {
         ).context("Failed to create function query")?;
 
         let class_query = Query::new(
-            tree_sitter_typescript::language_typescript(),
+            language,
             r#"
             (class_declaration
                 name: (type_identifier) @class.name
@@ -158,17 +161,15 @@ impl LanguageParser for TypeScriptParser {
         ).context("Failed to create class query")?;
         
         let inheritance_query = Query::new(
-            tree_sitter_typescript::language_typescript(),
+            language,
              r#"
-             (class_heritage
-                (extends_clause value: (identifier) @parent.extends)
-                (implements_clause (type_identifier) @parent.implements)
-             )
+             (class_heritage (extends_clause value: (identifier) @parent.extends))
+             (class_heritage (implements_clause (type_identifier) @parent.implements))
              "#
         ).context("Failed to create inheritance query")?;
 
         let call_query = Query::new(
-            tree_sitter_typescript::language_typescript(),
+            language,
             r#"
             (call_expression
               function: [
@@ -180,7 +181,7 @@ impl LanguageParser for TypeScriptParser {
         ).context("Failed to create call query")?;
 
         let import_query = Query::new(
-            tree_sitter_typescript::language_typescript(),
+            language,
             r#"
             (import_statement
               source: (string) @import.source)
@@ -188,6 +189,7 @@ impl LanguageParser for TypeScriptParser {
         ).context("Failed to create import query")?;
         
         let mut query_cursor = QueryCursor::new();
+        let exports = module_exports(root_node, content);
 
         let process_function = |node: Node, name: String| -> FunctionInfo {
              let start_line = node.start_position().row + 1;
@@ -196,13 +198,23 @@ impl LanguageParser for TypeScriptParser {
              let params = self.extract_params(node, content); 
              let calls = self.extract_calls(node, content, &call_query);
 
+             let visibility = if node.kind() == "method_definition" {
+                 member_visibility(node, content)
+             } else {
+                 exports.visibility(node, &name)
+             };
+
              FunctionInfo {
                  name,
                  params,
-                 return_type: None, 
+                 return_type: return_type(node, content),
                  calls,
                  start_line,
                  end_line,
+                 complexity: cyclomatic_complexity(node, BRANCH_KINDS),
+                 body_hash: body_hash(node, content),
+                 decorators: decorators(node, content, &["decorator"]),
+                 visibility: Some(visibility),
              }
         };
 
@@ -295,11 +307,15 @@ impl LanguageParser for TypeScriptParser {
                  }
 
                  classes.push(ClassInfo {
+                     visibility: Some(exports.visibility(class_node, &class_name)),
                      name: class_name,
+                     kind: None,
                      inheritances,
                      methods,
                      start_line,
                      end_line,
+                     body_hash: body_hash(class_node, content),
+                     decorators: decorators(class_node, content, &["decorator"]),
                  });
              }
         }
@@ -320,32 +336,42 @@ impl LanguageParser for TypeScriptParser {
             }
         }
 
-        let data_tables = self.extract_data_tables(content);
+        let data_tables = extract_data_tables(root_node, content, STRING_KINDS, &imports);
         let service_calls = self.extract_service_calls(content);
+        let sql_queries = extract_sql_queries(root_node, content, STRING_KINDS);
 
         Ok(ParsedFile {
             path: path.to_string_lossy().to_string(),
             language: "typescript".to_string(),
             functions,
             classes,
+            interfaces: Vec::new(),
             imports,
             data_tables,
             service_calls,
+            sql_queries,
+            env_vars: extract_env_vars(content, JAVASCRIPT_PATTERNS),
+            is_test: is_test_file(&path.to_string_lossy(), content, JAVASCRIPT_SIGNALS),
+            generated: false,
+            parse_failed: false,
+            rendered_components: extract_rendered_components(root_node, content),
+            reexports: exports.reexports,
+            line_count: count_lines(content),
         })
     }
 }
 
-fn extract_service_target(url: &str) -> Option<String> {
-    let parts: Vec<&str> = url.split("//").collect();
-    let host_part = parts.get(1).copied().unwrap_or("");
-    let host = host_part.split('/').next().unwrap_or("");
-    let host = host.split('?').next().unwrap_or("");
-    let host = host.split('#').next().unwrap_or("");
-    if host.is_empty() {
-        None
+/// Return type annotation of a function or method (or of the function a
+/// variable is initialized with), without the leading colon
+fn return_type(node: Node, content: &str) -> Option<String> {
+    let function = if node.kind() == "variable_declarator" {
+        node.child_by_field_name("value")?
     } else {
-        Some(host.to_string())
-    }
+        node
+    };
+    let annotation = function.child_by_field_name("return_type")?;
+    let text = content[annotation.byte_range()].trim_start_matches(':').trim();
+    (!text.is_empty()).then(|| text.to_string())
 }
 
 #[cfg(test)]
@@ -361,10 +387,21 @@ mod tests {
             function process(data: string, options?: any) {
                 return validate(data);
             }
+
+            const load = async (id: string): Promise<User | null> => {
+                return fetchUser(id);
+            };
             
             class User extends Person {
                 update(id: number, name: string) {
                     this.save(id, name);
+                    if (!name) {
+                        this.save(id, this.name);
+                    }
+                }
+
+                toJSON(): Record<string, unknown> {
+                    return {};
                 }
             }
         "#;
@@ -378,6 +415,9 @@ mod tests {
         let proc = result.functions.iter().find(|f| f.name == "process").expect("process not found");
         assert_eq!(proc.params, vec!["data", "options"]);
         assert!(proc.calls.contains(&"validate".to_string()));
+        assert_eq!(proc.return_type, None);
+        let load = result.functions.iter().find(|f| f.name == "load").expect("load not found");
+        assert_eq!(load.return_type.as_deref(), Some("Promise<User | null>"));
         
         // Classes
         let user = result.classes.iter().find(|c| c.name == "User").expect("User not found");
@@ -388,5 +428,143 @@ mod tests {
         
         let update = user.methods.iter().find(|m| m.name == "update").expect("update not found");
         assert_eq!(update.params, vec!["id", "name"]);
+        assert_eq!(update.call_counts(), vec![("save", 2)]);
+        let to_json = user.methods.iter().find(|m| m.name == "toJSON").expect("toJSON not found");
+        assert_eq!(to_json.return_type.as_deref(), Some("Record<string, unknown>"));
+    }
+
+    #[test]
+    fn test_decorators_are_captured_with_arguments() {
+        let parser = TypeScriptParser::new().unwrap();
+        let content = r#"
+            @Injectable()
+            export class UsersController {
+                @Get(':id')
+                @UseGuards(AuthGuard, RolesGuard)
+                find(id: string) {
+                    return this.users.find(id);
+                }
+
+                list() {
+                    return [];
+                }
+            }
+
+            @Component({ selector: 'app-root' })
+            class AppComponent {}
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("users.controller.ts"), content).unwrap();
+        let class = |name: &str| result.classes.iter().find(|c| c.name == name).unwrap();
+        let controller = class("UsersController");
+        assert_eq!(controller.decorators, vec!["Injectable()"]);
+        assert_eq!(controller.methods[0].decorators, vec!["Get(':id')", "UseGuards(AuthGuard, RolesGuard)"]);
+        assert!(controller.methods[1].decorators.is_empty());
+        assert_eq!(class("AppComponent").decorators, vec!["Component({ selector: 'app-root' })"]);
+    }
+
+    #[test]
+    fn test_function_complexity() {
+        let parser = TypeScriptParser::new().unwrap();
+        let content = r#"
+            function simple(): number {
+                return 1;
+            }
+
+            function route(kind: string, user?: User): string {
+                switch (kind) {
+                    case "a":
+                        return "A";
+                    case "b":
+                        return "B";
+                    default:
+                        break;
+                }
+                try {
+                    const name = user?.name ?? "anon";
+                    return user && user.admin ? name : "guest";
+                } catch (e) {
+                    return "error";
+                }
+            }
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("test.ts"), content).unwrap();
+        let complexity = |name: &str| result.functions.iter().find(|f| f.name == name).unwrap().complexity;
+
+        assert_eq!(complexity("simple"), 1);
+        // two cases (default doesn't count), ??, &&, ternary, catch
+        assert_eq!(complexity("route"), 7);
+    }
+
+    #[test]
+    fn test_visibility_from_exports_and_member_modifiers() {
+        let parser = TypeScriptParser::new().unwrap();
+        let content = r#"
+            export function load(): void {}
+            export const save = async (): Promise<void> => {};
+            function format(): string { return ""; }
+            const parse = () => 1;
+            function listed() {}
+            export { listed };
+
+            export default class Store {
+                public open() {}
+                private reset() {}
+                protected flush() {}
+                close() {
+                    const inner = () => 2;
+                }
+            }
+            class Cache {}
+        "#;
+
+        let result = parser.parse_file(&PathBuf::from("store.ts"), content).unwrap();
+        let function = |name: &str| result.functions.iter().find(|f| f.name == name).unwrap().visibility.as_deref();
+        assert_eq!(function("load"), Some("public"));
+        assert_eq!(function("save"), Some("public"));
+        assert_eq!(function("format"), Some("private"));
+        assert_eq!(function("parse"), Some("private"));
+        assert_eq!(function("listed"), Some("public"));
+        assert_eq!(function("inner"), Some("private"));
+
+        let class = |name: &str| result.classes.iter().find(|c| c.name == name).unwrap();
+        assert_eq!(class("Store").visibility.as_deref(), Some("public"));
+        assert_eq!(class("Cache").visibility.as_deref(), Some("private"));
+        let methods: Vec<(&str, Option<&str>)> =
+            class("Store").methods.iter().map(|m| (m.name.as_str(), m.visibility.as_deref())).collect();
+        assert_eq!(
+            methods,
+            vec![("open", Some("public")), ("reset", Some("private")), ("flush", Some("protected")), ("close", Some("public"))]
+        );
+        // Public functions, the public class and its public methods
+        assert_eq!(result.public_api_count(), 3 + 1 + 2);
+    }
+
+    #[test]
+    fn test_service_calls_merge_spellings_of_one_host() {
+        let parser = TypeScriptParser::new().unwrap();
+        let content = r#"
+            await fetch("https://api.stripe.com/v1/charges");
+            await fetch("https://API.Stripe.com:443/v1/refunds");
+            await fetch("http://www.api.stripe.com/v1");
+            await fetch("http://payments:8080/charge");
+        "#;
+        let result = parser.parse_file(&PathBuf::from("billing.ts"), content).unwrap();
+
+        let mut calls: Vec<(String, String, Option<String>, bool)> = result
+            .service_calls
+            .iter()
+            .map(|call| (call.target.clone(), call.protocol.clone(), call.domain.clone(), call.internal))
+            .collect();
+        calls.sort();
+        assert_eq!(
+            calls,
+            vec![
+                ("api.stripe.com".to_string(), "http".to_string(), Some("stripe.com".to_string()), false),
+                ("api.stripe.com".to_string(), "https".to_string(), Some("stripe.com".to_string()), false),
+                ("payments:8080".to_string(), "http".to_string(), None, true),
+            ]
+        );
     }
 }
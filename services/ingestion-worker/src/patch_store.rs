@@ -0,0 +1,135 @@
+//! Patch Store
+//!
+//! Graph patches go into the job result summary so the frontend can apply
+//! them without refetching the graph. A full-repository patch can run to tens
+//! of megabytes, so anything over the configured size is parked in Redis as
+//! `graph_patch:{job_id}` (with a TTL) and the summary only carries a
+//! `graph_patch_ref` pointing at it.
+
+use anyhow::{Context, Result};
+use redis::AsyncCommands;
+use serde::Serialize;
+
+pub const DEFAULT_GRAPH_PATCH_MAX_BYTES: usize = 1024 * 1024;
+pub const DEFAULT_GRAPH_PATCH_TTL_SECS: u64 = 24 * 60 * 60;
+
+pub fn graph_patch_key(job_id: &str) -> String {
+    format!("graph_patch:{}", job_id)
+}
+
+/// Summary stand-in for a patch that was stored in Redis
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GraphPatchRef {
+    pub key: String,
+    pub node_count: usize,
+    pub edge_count: usize,
+    pub size_bytes: usize,
+}
+
+/// Where a serialized patch should go
+#[derive(Debug, PartialEq)]
+pub enum PatchPlacement {
+    Inline(String),
+    Redis { payload: String, reference: GraphPatchRef },
+}
+
+/// Keep patches up to `max_bytes` inline; larger ones go to Redis
+pub fn place_patch(
+    job_id: &str,
+    payload: String,
+    node_count: usize,
+    edge_count: usize,
+    max_bytes: usize,
+) -> PatchPlacement {
+    if payload.len() <= max_bytes {
+        return PatchPlacement::Inline(payload);
+    }
+    let reference = GraphPatchRef {
+        key: graph_patch_key(job_id),
+        node_count,
+        edge_count,
+        size_bytes: payload.len(),
+    };
+    PatchPlacement::Redis { payload, reference }
+}
+
+/// Write the serialized patch under `key`, expiring after `ttl_secs`
+pub async fn store_graph_patch<C>(conn: &mut C, key: &str, payload: &str, ttl_secs: u64) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    conn.set_ex::<_, _, ()>(key, payload, ttl_secs.max(1))
+        .await
+        .with_context(|| format!("Failed to store graph patch under {}", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis::{Arg, Cmd, Pipeline, RedisFuture, Value};
+
+    /// Records the arguments of every command it is sent
+    #[derive(Default)]
+    struct RecordingConnection {
+        commands: Vec<Vec<String>>,
+    }
+
+    impl redis::aio::ConnectionLike for RecordingConnection {
+        fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+            let args = cmd
+                .args_iter()
+                .filter_map(|arg| match arg {
+                    Arg::Simple(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+                    Arg::Cursor => None,
+                })
+                .collect();
+            self.commands.push(args);
+            Box::pin(async { Ok(Value::Okay) })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _cmd: &'a Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> RedisFuture<'a, Vec<Value>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    #[test]
+    fn test_place_patch_respects_threshold() {
+        let payload = r#"{"nodes":[],"edges":[]}"#.to_string();
+        assert_eq!(
+            place_patch("job-1", payload.clone(), 0, 0, payload.len()),
+            PatchPlacement::Inline(payload.clone())
+        );
+
+        match place_patch("job-1", payload.clone(), 3, 2, payload.len() - 1) {
+            PatchPlacement::Redis { payload: stored, reference } => {
+                assert_eq!(stored, payload);
+                assert_eq!(
+                    reference,
+                    GraphPatchRef {
+                        key: "graph_patch:job-1".to_string(),
+                        node_count: 3,
+                        edge_count: 2,
+                        size_bytes: payload.len(),
+                    }
+                );
+            }
+            other => panic!("expected Redis placement, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store_graph_patch_sets_key_with_ttl() {
+        let mut conn = RecordingConnection::default();
+        store_graph_patch(&mut conn, "graph_patch:job-1", "{}", 3600).await.unwrap();
+        assert_eq!(conn.commands, vec![vec!["SETEX", "graph_patch:job-1", "3600", "{}"]]);
+    }
+}
@@ -0,0 +1,177 @@
+//! Path Filter
+//!
+//! Per-job scoping from the `include_paths` and `exclude_paths` options,
+//! both JSON arrays of glob patterns matched against repo-relative paths.
+//!
+//! - `*` and `?` match within one path segment, `**` any number of segments
+//! - a pattern without `/` matches at any depth (`*.spec.ts`, `fixtures`)
+//! - a pattern matching a directory matches everything below it (`docs/`)
+//!
+//! Excludes win over includes, and no includes means everything is included.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PathFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl PathFilter {
+    pub fn from_options(options: &Option<HashMap<String, String>>) -> Result<Self> {
+        let patterns = |key: &str| -> Result<Vec<String>> {
+            match options.as_ref().and_then(|opts| opts.get(key)) {
+                Some(raw) => serde_json::from_str::<Vec<String>>(raw)
+                    .with_context(|| format!("Invalid {} option, expected a JSON array of globs", key)),
+                None => Ok(Vec::new()),
+            }
+        };
+
+        Ok(Self {
+            include: patterns("include_paths")?,
+            exclude: patterns("exclude_paths")?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether a repo-relative path is in scope
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.replace('\\', "/");
+        let path = path.trim_start_matches("./");
+        if self.exclude.iter().any(|pattern| glob_match(pattern, path)) {
+            return false;
+        }
+        self.include.is_empty() || self.include.iter().any(|pattern| glob_match(pattern, path))
+    }
+}
+
+/// Whether `pattern` matches `path` or one of its parent directories
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim().trim_start_matches("./").trim_matches('/');
+    if pattern.is_empty() {
+        return false;
+    }
+    let mut pattern_segments: Vec<&str> = pattern.split('/').collect();
+    if pattern_segments.len() == 1 && pattern_segments[0] != "**" {
+        pattern_segments.insert(0, "**");
+    }
+
+    let path_segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    (1..=path_segments.len()).any(|len| segments_match(&pattern_segments, &path_segments[..len]))
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| segments_match(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path_rest)) => wildcard_match(segment, name) && segments_match(rest, path_rest),
+            None => false,
+        },
+    }
+}
+
+/// Shell-style match of a single path segment
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> PathFilter {
+        PathFilter {
+            include: include.iter().map(|p| p.to_string()).collect(),
+            exclude: exclude.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_glob_semantics() {
+        assert!(glob_match("**/test/**", "test/unit.py"));
+        assert!(glob_match("**/test/**", "pkg/api/test/fixtures/data.py"));
+        assert!(!glob_match("**/test/**", "pkg/testing/data.py"));
+
+        assert!(glob_match("*.spec.ts", "app.spec.ts"));
+        assert!(glob_match("*.spec.ts", "src/app/user.spec.ts"));
+        assert!(!glob_match("*.spec.ts", "src/app/user.ts"));
+
+        assert!(glob_match("docs/", "docs/guide/intro.md"));
+        assert!(glob_match("./services", "services/billing/main.go"));
+        assert!(!glob_match("services", "libs/services.go"));
+        assert!(glob_match("fixtures", "tests/fixtures/repo/a.js"));
+
+        assert!(glob_match("src/*.ts", "src/index.ts"));
+        assert!(!glob_match("src/*.ts", "src/lib/index.ts"));
+        assert!(glob_match("src/**/*.ts", "src/index.ts"));
+        assert!(glob_match("src/**/*.ts", "src/lib/deep/index.ts"));
+        assert!(glob_match("services/api-?/**", "services/api-2/main.go"));
+        assert!(!glob_match("", "anything.ts"));
+    }
+
+    #[test]
+    fn test_excludes_take_precedence_over_includes() {
+        let scope = filter(&["services/**"], &["**/test/**", "*.spec.ts", "examples"]);
+        assert!(scope.matches("services/api/handler.ts"));
+        assert!(!scope.matches("services/api/handler.spec.ts"));
+        assert!(!scope.matches("services/api/test/helpers.ts"));
+        assert!(!scope.matches("services/examples/demo.ts"));
+        assert!(!scope.matches("docs/index.ts"));
+
+        let everything = PathFilter::default();
+        assert!(everything.is_empty());
+        assert!(everything.matches("docs/index.ts"));
+        assert!(filter(&[], &["docs/"]).matches(".\\src\\main.rs"));
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", "ui"));
+        assert!(wildcard_match("svc-*", "svc-billing"));
+        assert!(wildcard_match("a?c*", "abcdef"));
+        assert!(!wildcard_match("svc-*", "lib-billing"));
+        assert!(!wildcard_match("a?c", "ac"));
+    }
+
+    #[test]
+    fn test_from_options() {
+        let mut options = HashMap::new();
+        options.insert("include_paths".to_string(), r#"["services/"]"#.to_string());
+        options.insert("exclude_paths".to_string(), r#"["docs/**", "**/*.test.js"]"#.to_string());
+        assert_eq!(
+            PathFilter::from_options(&Some(options.clone())).unwrap(),
+            filter(&["services/"], &["docs/**", "**/*.test.js"])
+        );
+        assert_eq!(PathFilter::from_options(&None).unwrap(), PathFilter::default());
+
+        options.insert("exclude_paths".to_string(), "docs/**".to_string());
+        let err = PathFilter::from_options(&Some(options)).unwrap_err();
+        assert!(err.to_string().contains("exclude_paths"));
+    }
+}
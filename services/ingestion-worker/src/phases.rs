@@ -0,0 +1,210 @@
+//! Analysis Phases
+//!
+//! The `phases` job option picks the stages a job runs, as a comma-separated
+//! list or a JSON array: `parse`, `git`, `boundaries`, `communication` and
+//! `dependencies`. Skipped stages hand storage nothing, and storage leaves
+//! what earlier jobs stored for them alone. Parsing always runs, since every
+//! other stage reads the parsed files. Job progress is spread over the
+//! stages that run, so skipping some doesn't leave the bar stalling.
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+
+/// Job option listing the phases to run
+pub const PHASES_OPTION: &str = "phases";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Parse,
+    Git,
+    Boundaries,
+    Communication,
+    Dependencies,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [Phase::Parse, Phase::Git, Phase::Boundaries, Phase::Communication, Phase::Dependencies];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Parse => "parse",
+            Phase::Git => "git",
+            Phase::Boundaries => "boundaries",
+            Phase::Communication => "communication",
+            Phase::Dependencies => "dependencies",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Phase::ALL.into_iter().find(|phase| phase.as_str() == name)
+    }
+
+    /// Share of the job's progress after cloning, relative to the other stages
+    fn weight(&self) -> u32 {
+        match self {
+            Phase::Parse => 25,
+            Phase::Git => 4,
+            Phase::Boundaries => 3,
+            Phase::Communication => 15,
+            Phase::Dependencies => 3,
+        }
+    }
+}
+
+/// Progress share of storing the graph, which always runs
+const STORAGE_WEIGHT: u32 = 15;
+
+/// Progress share of the summary, patch and export after storage
+const FINISH_WEIGHT: u32 = 10;
+
+/// The phases a job runs, in pipeline order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Phases {
+    active: Vec<Phase>,
+}
+
+impl Default for Phases {
+    fn default() -> Self {
+        Self { active: Phase::ALL.to_vec() }
+    }
+}
+
+impl Phases {
+    /// Every phase, unless the `phases` option lists some
+    pub fn from_options(options: &Option<HashMap<String, String>>) -> Result<Self> {
+        let Some(raw) = options.as_ref().and_then(|opts| opts.get(PHASES_OPTION)) else {
+            return Ok(Self::default());
+        };
+        let names: Vec<String> = if raw.trim_start().starts_with('[') {
+            serde_json::from_str(raw)
+                .with_context(|| format!("Invalid {} option, expected a JSON array of phase names", PHASES_OPTION))?
+        } else {
+            raw.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect()
+        };
+
+        let mut active = vec![Phase::Parse];
+        for name in &names {
+            let Some(phase) = Phase::from_name(&name.trim().to_lowercase()) else {
+                bail!(
+                    "Invalid {} option, unknown phase {:?} (expected parse, git, boundaries, communication or dependencies)",
+                    PHASES_OPTION,
+                    name
+                );
+            };
+            active.push(phase);
+        }
+        active.sort();
+        active.dedup();
+        Ok(Self { active })
+    }
+
+    pub fn runs(&self, phase: Phase) -> bool {
+        self.active.contains(&phase)
+    }
+
+    /// The same phases but `phase`; parsing always runs
+    pub fn without(mut self, phase: Phase) -> Self {
+        if phase != Phase::Parse {
+            self.active.retain(|active| *active != phase);
+        }
+        self
+    }
+
+    /// Names of the phases run, for the result summary
+    pub fn names(&self) -> Vec<&'static str> {
+        self.active.iter().map(Phase::as_str).collect()
+    }
+
+    /// Progress milestones with the skipped phases' shares spread over the
+    /// rest. Cloning keeps the first `clone_end` percent.
+    pub fn progress_plan(&self, clone_end: i32) -> ProgressPlan {
+        let weight = |phases: &[Phase]| -> u32 { phases.iter().filter(|p| self.runs(**p)).map(Phase::weight).sum() };
+        let total = weight(&Phase::ALL) + STORAGE_WEIGHT + FINISH_WEIGHT;
+        let at = |done: u32| clone_end + ((100 - clone_end) as f64 * done as f64 / total as f64).round() as i32;
+
+        let parsed = weight(&[Phase::Parse]);
+        let analyzed = parsed + weight(&[Phase::Git, Phase::Boundaries, Phase::Dependencies]);
+        let storage_start = analyzed + weight(&[Phase::Communication]);
+        ProgressPlan {
+            parsed: at(parsed),
+            analyzed: at(analyzed),
+            storage_start: at(storage_start),
+            storage_end: at(storage_start + STORAGE_WEIGHT),
+        }
+    }
+}
+
+/// Job progress reported as each stage finishes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgressPlan {
+    /// Parsing done
+    pub parsed: i32,
+    /// Git history, boundaries and library dependencies done
+    pub analyzed: i32,
+    /// Communication detection and the dependency graph done
+    pub storage_start: i32,
+    pub storage_end: i32,
+}
+
+impl ProgressPlan {
+    /// Job progress for a fraction of storage done
+    pub fn storage(&self, fraction: f64) -> i32 {
+        self.storage_start + (fraction.clamp(0.0, 1.0) * (self.storage_end - self.storage_start) as f64).round() as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(raw: &str) -> Option<HashMap<String, String>> {
+        Some(HashMap::from([(PHASES_OPTION.to_string(), raw.to_string())]))
+    }
+
+    #[test]
+    fn test_phase_option_forms() {
+        assert_eq!(Phases::from_options(&None).unwrap(), Phases::default());
+        assert_eq!(
+            Phases::from_options(&options("parse,git,boundaries,communication,dependencies")).unwrap(),
+            Phases::default()
+        );
+
+        let listed = Phases::from_options(&options(" communication , Git,")).unwrap();
+        assert_eq!(listed.names(), vec!["parse", "git", "communication"]);
+        assert!(listed.runs(Phase::Parse));
+        assert!(!listed.runs(Phase::Boundaries));
+        assert!(!listed.runs(Phase::Dependencies));
+
+        let json = Phases::from_options(&options(r#"["boundaries", "parse", "boundaries"]"#)).unwrap();
+        assert_eq!(json.names(), vec!["parse", "boundaries"]);
+
+        // Parsing alone
+        assert_eq!(Phases::from_options(&options("")).unwrap().names(), vec!["parse"]);
+
+        let without_git = Phases::default().without(Phase::Git).without(Phase::Parse);
+        assert_eq!(without_git.names(), vec!["parse", "boundaries", "communication", "dependencies"]);
+
+        let err = Phases::from_options(&options("parse,history")).unwrap_err();
+        assert!(err.to_string().contains("\"history\""), "{}", err);
+        assert!(Phases::from_options(&options("[\"git\"")).is_err());
+    }
+
+    #[test]
+    fn test_progress_is_spread_over_active_phases() {
+        let all = Phases::default().progress_plan(25);
+        assert_eq!(all, ProgressPlan { parsed: 50, analyzed: 60, storage_start: 75, storage_end: 90 });
+        assert_eq!(all.storage(0.5), 83);
+        assert_eq!(all.storage(2.0), 90);
+
+        let parse_only = Phases::from_options(&options("parse")).unwrap().progress_plan(25);
+        assert_eq!(parse_only, ProgressPlan { parsed: 63, analyzed: 63, storage_start: 63, storage_end: 85 });
+
+        // Milestones never go back, and finishing always has room left
+        for raw in ["git", "communication", "boundaries,dependencies", "git,communication"] {
+            let plan = Phases::from_options(&options(raw)).unwrap().progress_plan(25);
+            assert!(25 < plan.parsed, "{}: {:?}", raw, plan);
+            assert!(plan.parsed <= plan.analyzed && plan.analyzed <= plan.storage_start, "{}: {:?}", raw, plan);
+            assert!(plan.storage_start < plan.storage_end && plan.storage_end < 100, "{}: {:?}", raw, plan);
+        }
+    }
+}
@@ -0,0 +1,537 @@
+//! Analysis Pipeline
+//!
+//! Everything a job derives from the parsed files before storing it: git
+//! history, module boundaries, library dependencies, communication, tables,
+//! assets, ownership, the dependency graph and the metrics on top of it.
+//! Queued jobs run it between cloning and storage; the `analyze` command
+//! runs it on a local checkout without Redis, Neo4j or the API.
+//!
+//! It runs in two halves so a job can look up vulnerabilities and report
+//! progress in between: `analyze_sources` reads the repository's history
+//! and manifests, `build_analysis` everything else. Both run on the blocking
+//! pool, so the job's heartbeat and progress updates keep going meanwhile.
+//!
+//! Most phases read the files' outlines; the symbol table, the dependency
+//! graph and abstract-file detection read streamed files a chunk at a time,
+//! see `parsed_source`.
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::asset_inventory::{self, AssetInventory};
+use crate::boundary_detector::{BoundaryDetectionResult, BoundaryDetector};
+use crate::codeowners::CodeOwners;
+use crate::communication_detector::{CommunicationAnalysis, CommunicationDetector};
+use crate::ddl_scanner::{self, DdlTable};
+use crate::dependency_metadata::{self, LibraryDependency};
+use crate::git_analyzer::{GitAnalyzer, RepoContributions};
+use crate::graph_builder::{self, DependencyGraph, FileDependencies, GraphStats, Package, SymbolTable};
+use crate::import_resolver::ImportResolver;
+use crate::infra_scanner::{self, InfraAnalysis};
+use crate::job_warnings::{self, JobWarnings};
+use crate::layering::{self, LayerRules, LayerViolation};
+use crate::metrics;
+use crate::orm_detector::{OrmDetector, OrmModel};
+use crate::parse_limits::ParseStats;
+use crate::parsed_source::ParsedFileSource;
+use crate::parsers::{exports, ParsedFile};
+use crate::path_filter::PathFilter;
+use crate::phases::{Phase, Phases};
+use crate::stage_timings::{timed_blocking, StageTimings};
+
+/// How a run analyzes, from the job's options or the command line
+#[derive(Debug, Clone)]
+pub struct PipelineSettings {
+    pub phases: Phases,
+    pub path_filter: PathFilter,
+    pub layer_rules: LayerRules,
+    /// Only the changed files were parsed, so whole-repository results
+    /// (library usage, layer violations) can't be judged
+    pub incremental: bool,
+    pub git_max_commits: usize,
+    pub git_since: Option<chrono::DateTime<chrono::Utc>>,
+    pub git_max_walk: Option<usize>,
+    pub cohesion_threshold: f64,
+    pub inventory_assets: bool,
+}
+
+/// The first half: what the repository's history and manifests say
+pub struct SourceAnalysis {
+    pub git_contributions: Option<RepoContributions>,
+    pub boundary_result: BoundaryDetectionResult,
+    pub library_dependencies: Vec<LibraryDependency>,
+}
+
+/// Everything the pipeline found. Results of skipped phases are empty.
+pub struct RepoAnalysis {
+    pub git_contributions: Option<RepoContributions>,
+    pub boundary_result: BoundaryDetectionResult,
+    pub library_dependencies: Vec<LibraryDependency>,
+    pub communication_analysis: CommunicationAnalysis,
+    /// None when the communication phase didn't run
+    pub infrastructure: Option<InfraAnalysis>,
+    pub orm_models: Vec<OrmModel>,
+    pub ddl_tables: Vec<DdlTable>,
+    /// None unless the run asked for an asset inventory
+    pub asset_inventory: Option<AssetInventory>,
+    pub codeowners: Option<CodeOwners>,
+    pub dep_graph: DependencyGraph,
+    pub stats: GraphStats,
+    pub packages: Vec<Package>,
+    pub layer_violations: Vec<LayerViolation>,
+}
+
+/// Both halves, for runs that need nothing in between
+pub async fn analyze(
+    repo_path: &Path,
+    parsed_files: &mut ParsedFileSource,
+    settings: &PipelineSettings,
+    timings: &mut StageTimings,
+    warnings: &JobWarnings,
+) -> Result<RepoAnalysis> {
+    let sources = analyze_sources(repo_path, parsed_files, settings, timings, warnings).await?;
+    build_analysis(repo_path, parsed_files, sources, settings, timings, warnings).await
+}
+
+/// Run `stage` on the blocking pool. The parsed files and the timings are
+/// moved there and back, since the pool only takes owned values.
+async fn on_blocking_pool<T, F>(parsed_files: &mut ParsedFileSource, timings: &mut StageTimings, stage: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut ParsedFileSource, &mut StageTimings) -> Result<T> + Send + 'static,
+{
+    let mut files = std::mem::replace(parsed_files, ParsedFileSource::Memory(Vec::new()));
+    let mut stage_timings = timings.clone();
+    let (files, stage_timings, result) = crate::run_blocking(move || {
+        let result = stage(&mut files, &mut stage_timings);
+        Ok((files, stage_timings, result))
+    })
+    .await?;
+    *parsed_files = files;
+    *timings = stage_timings;
+    result
+}
+
+/// Git history, module boundaries and library dependencies
+pub async fn analyze_sources(
+    repo_path: &Path,
+    parsed_files: &mut ParsedFileSource,
+    settings: &PipelineSettings,
+    timings: &mut StageTimings,
+    warnings: &JobWarnings,
+) -> Result<SourceAnalysis> {
+    let (repo_path, settings, warnings) = (repo_path.to_path_buf(), settings.clone(), warnings.clone());
+    on_blocking_pool(parsed_files, timings, move |files, timings| {
+        read_sources(&repo_path, files.outlines(), &settings, timings, &warnings)
+    })
+    .await
+}
+
+fn read_sources(
+    repo_path: &Path,
+    parsed_files: &[ParsedFile],
+    settings: &PipelineSettings,
+    timings: &mut StageTimings,
+    warnings: &JobWarnings,
+) -> Result<SourceAnalysis> {
+    let phases = &settings.phases;
+
+    // Git commit history; a checkout without one is analyzed without it
+    let git_contributions = if !phases.runs(Phase::Git) {
+        None
+    } else {
+        let max_commits = settings.git_max_commits;
+        let (since, max_walk) = (settings.git_since, settings.git_max_walk);
+        timed_blocking(&mut timings.git_analysis_ms, || {
+            let analyzer = GitAnalyzer::new(repo_path)
+                .map(|analyzer| analyzer.with_since(since).with_max_walk(max_walk));
+            match analyzer {
+                Ok(analyzer) => {
+                    match analyzer.analyze_contributions_with_limit(max_commits) {
+                        Ok(contributions) => {
+                            info!("📊 Analyzed git history: {} files with {} total commits",
+                                  contributions.files.len(),
+                                  contributions.total_commits);
+                            Some(contributions)
+                        }
+                        Err(e) => {
+                            warn!("⚠️  Failed to analyze git history: {}. Continuing without git metrics.", e);
+                            let message = format!("Failed to analyze git history, no git metrics: {}", e);
+                            warnings.record(job_warnings::GIT_HISTORY_FAILED, message, None);
+                            None
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!("⚠️  Failed to open git repository: {}. Continuing without git metrics.", e);
+                    let message = format!("Failed to open git repository, no git metrics: {}", e);
+                    warnings.record(job_warnings::GIT_OPEN_FAILED, message, None);
+                    None
+                }
+            }
+        })
+    };
+
+    // Module boundaries
+    let boundary_result = if phases.runs(Phase::Boundaries) {
+        let boundary_result = timed_blocking(&mut timings.boundary_detection_ms, || {
+            BoundaryDetector::detect_boundaries(parsed_files, repo_path)
+        })?;
+        info!("🗺️  Detected {} module boundaries", boundary_result.boundaries.len());
+        boundary_result
+    } else {
+        BoundaryDetectionResult::default()
+    };
+
+    // Library dependencies from manifests
+    let library_dependencies = if phases.runs(Phase::Dependencies) {
+        let mut library_dependencies = timed_blocking(&mut timings.dependency_collection_ms, || {
+            crate::collect_library_dependencies(&repo_path.to_path_buf(), &settings.path_filter)
+        })?;
+        info!("📦 Detected {} library dependencies", library_dependencies.len());
+        // Only a full parse sees every import of a library
+        if !settings.incremental {
+            dependency_metadata::apply_library_usage(&mut library_dependencies, parsed_files);
+        }
+        library_dependencies
+    } else {
+        Vec::new()
+    };
+
+    Ok(SourceAnalysis { git_contributions, boundary_result, library_dependencies })
+}
+
+/// Communication, tables, assets, ownership, the dependency graph and the
+/// metrics read from it. Table references in `parsed_files` are rewritten
+/// to the schema's names.
+pub async fn build_analysis(
+    repo_path: &Path,
+    parsed_files: &mut ParsedFileSource,
+    sources: SourceAnalysis,
+    settings: &PipelineSettings,
+    timings: &mut StageTimings,
+    warnings: &JobWarnings,
+) -> Result<RepoAnalysis> {
+    let (repo_path, settings, warnings) = (repo_path.to_path_buf(), settings.clone(), warnings.clone());
+    on_blocking_pool(parsed_files, timings, move |files, timings| {
+        build(&repo_path, files, sources, &settings, timings, &warnings)
+    })
+    .await
+}
+
+fn build(
+    repo_path: &Path,
+    parsed_files: &mut ParsedFileSource,
+    sources: SourceAnalysis,
+    settings: &PipelineSettings,
+    timings: &mut StageTimings,
+    warnings: &JobWarnings,
+) -> Result<RepoAnalysis> {
+    let SourceAnalysis { git_contributions, mut boundary_result, library_dependencies } = sources;
+    let (phases, path_filter) = (&settings.phases, &settings.path_filter);
+    // The detectors below take the path as a PathBuf
+    let repo_dir = repo_path.to_path_buf();
+
+    // Communication patterns, with the Dockerfiles and Kubernetes manifests
+    let communication_analysis = if phases.runs(Phase::Communication) {
+        let communication_analysis = timed_blocking(&mut timings.communication_detection_ms, || {
+            CommunicationDetector::detect(&repo_dir, parsed_files.outlines(), path_filter, warnings)
+        })?;
+        info!(
+            "Detected communication artifacts: {} endpoints, {} routes, {} API spec operations, {} rpc services, {} queue usages, {} realtime connections, {} socket events, {} compose services",
+            communication_analysis.endpoints.len(),
+            communication_analysis.routes.len(),
+            communication_analysis.api_operations.len(),
+            communication_analysis.rpc_services.len(),
+            communication_analysis.queues.len(),
+            communication_analysis.realtime_connections.len(),
+            communication_analysis.socket_events.len(),
+            communication_analysis.compose_services.len()
+        );
+        communication_analysis
+    } else {
+        CommunicationAnalysis::default()
+    };
+    let infrastructure = if phases.runs(Phase::Communication) {
+        let infrastructure = infra_scanner::scan_infrastructure(repo_path, path_filter)?;
+        info!(
+            "🐳 Found {} container images, {} Kubernetes workloads and {} Kubernetes services",
+            infrastructure.container_images.len(),
+            infrastructure.k8s_deployments.len(),
+            infrastructure.k8s_services.len()
+        );
+        Some(infrastructure)
+    } else {
+        None
+    };
+    let mut orm_models = OrmDetector::detect(&repo_dir, parsed_files.outlines())?;
+    info!("🗃️  Detected {} ORM models", orm_models.len());
+    let ddl_tables = ddl_scanner::scan_migrations(repo_path, path_filter)?;
+    if !ddl_tables.is_empty() {
+        parsed_files.update(|files| ddl_scanner::canonicalize_table_references(files, &ddl_tables))?;
+        ddl_scanner::canonicalize_orm_tables(&mut orm_models, &ddl_tables);
+    }
+    info!("🗃️  Found {} tables in schema and migration files", ddl_tables.len());
+
+    // Non-code assets when the run asks for them
+    let asset_inventory = if settings.inventory_assets {
+        let inventory = asset_inventory::collect_assets(repo_path, path_filter, asset_inventory::MAX_ASSETS)?;
+        info!("🗂️  Listed {} assets", inventory.assets.len());
+        Some(inventory)
+    } else {
+        None
+    };
+
+    // Team ownership from the CODEOWNERS file, if the repo has one
+    let codeowners = CodeOwners::load(repo_path)?;
+    if let Some(codeowners) = &codeowners {
+        info!("👥 Read {} ownership rules from {}", codeowners.rules.len(), codeowners.source);
+    }
+
+    // Symbols re-exported by other modules are public where they're defined
+    let import_resolver = ImportResolver::from_repo(repo_path);
+    let reexported = exports::reexported_symbols(parsed_files.outlines(), &import_resolver);
+    if !reexported.is_empty() {
+        parsed_files.update(|files| exports::mark_reexported_public(files, &reexported))?;
+    }
+
+    // Symbol table for cross-file resolution, then the dependency graph
+    let symbol_table = timed_blocking(&mut timings.symbol_table_ms, || {
+        match &*parsed_files {
+            ParsedFileSource::Memory(files) => Ok(SymbolTable::from_parsed_files(files)),
+            ParsedFileSource::Spilled { .. } => {
+                let mut symbol_table = SymbolTable::default();
+                for chunk in parsed_files.chunks()? {
+                    symbol_table.add_files(&chunk?);
+                }
+                Ok::<_, anyhow::Error>(symbol_table)
+            }
+        }
+    })?;
+    info!("📚 Built symbol table: {} functions, {} classes",
+          symbol_table.functions.len(),
+          symbol_table.classes.len());
+    let dep_graph = timed_blocking(&mut timings.graph_build_ms, || {
+        match &*parsed_files {
+            ParsedFileSource::Memory(files) => {
+                Ok(DependencyGraph::from_parsed_files_with_resolver(files, &symbol_table, &import_resolver))
+            }
+            // Each file's own edges need its call lists, the edges between
+            // files only its outline
+            ParsedFileSource::Spilled { outlines, .. } => {
+                let mut dependencies = FileDependencies::resolve(outlines, &import_resolver);
+                let mut dep_graph = DependencyGraph::default();
+                for chunk in parsed_files.chunks()? {
+                    let chunk = chunk?;
+                    dependencies.follow_barrels(&chunk);
+                    dep_graph.add_files(&chunk, &dependencies, &symbol_table);
+                }
+                dep_graph.link_files(outlines, dependencies, &symbol_table);
+                Ok::<_, anyhow::Error>(dep_graph)
+            }
+        }
+    })?;
+    let stats = dep_graph.stats();
+    info!("🔗 Built dependency graph: {} nodes, {} edges",
+          dep_graph.nodes.len(),
+          dep_graph.edges.len());
+    let packages = graph_builder::build_package_hierarchy(
+        parsed_files.outlines().iter().map(|file| file.path.as_str()),
+        |path| repo_path.join(path).exists(),
+    );
+    info!("📁 Found {} Rust modules and Python packages", packages.len());
+
+    // Score boundaries against the resolved file dependencies; telling an
+    // abstract file needs its call lists
+    let mut abstract_files = HashSet::new();
+    for chunk in parsed_files.chunks()? {
+        let chunk = chunk?;
+        let abstract_paths = chunk.iter().filter(|file| metrics::is_abstract_file(file)).map(|file| file.path.clone());
+        abstract_files.extend(abstract_paths);
+    }
+    metrics::apply_boundary_metrics(&mut boundary_result, &dep_graph, &abstract_files, settings.cohesion_threshold);
+
+    // Check file dependencies against the layer rules; an incremental graph
+    // only holds the changed files' imports
+    let layer_violations = if settings.incremental || !phases.runs(Phase::Boundaries) {
+        Vec::new()
+    } else {
+        layering::layer_violations(&dep_graph, &settings.layer_rules)
+    };
+    if !layer_violations.is_empty() {
+        info!("🧱 Found {} layer violations", layer_violations.len());
+    }
+
+    Ok(RepoAnalysis {
+        git_contributions,
+        boundary_result,
+        library_dependencies,
+        communication_analysis,
+        infrastructure,
+        orm_models,
+        ddl_tables,
+        asset_inventory,
+        codeowners,
+        dep_graph,
+        stats,
+        packages,
+        layer_violations,
+    })
+}
+
+/// The result summary's account of the analysis. Jobs add what storing
+/// and publishing it produced.
+pub fn analysis_summary(
+    parsed_files: &[ParsedFile],
+    parse_stats: &ParseStats,
+    analysis: &RepoAnalysis,
+    settings: &PipelineSettings,
+    analyzed_commit: Option<&str>,
+) -> Result<serde_json::Value> {
+    let RepoAnalysis {
+        git_contributions,
+        boundary_result,
+        library_dependencies,
+        infrastructure,
+        orm_models,
+        ddl_tables,
+        asset_inventory,
+        codeowners,
+        dep_graph,
+        stats,
+        packages,
+        layer_violations,
+        ..
+    } = analysis;
+    let (phases, path_filter, incremental) = (&settings.phases, &settings.path_filter, settings.incremental);
+
+    let mut summary = serde_json::json!({
+        "total_files": parsed_files.len(),
+        "total_functions": stats.functions,
+        "total_classes": stats.classes,
+        "dependencies": stats.imports_edges,
+        "complexity_score": crate::complexity_score(parsed_files),
+        "function_complexity": crate::function_complexity(parsed_files),
+        "languages": crate::language_breakdown(parsed_files),
+        "total_loc": parsed_files.iter().map(|f| f.line_count).sum::<usize>(),
+        "loc_by_language": crate::loc_by_language(parsed_files),
+        "test_files": parsed_files.iter().filter(|f| f.is_test).count(),
+        "generated_files": parsed_files.iter().filter(|f| f.generated).count(),
+        "test_to_code_ratio": crate::test_to_code_ratio(parsed_files),
+        "skipped_large_files": parse_stats.skipped_large_files,
+        "skipped_binary_files": parse_stats.skipped_binary_files,
+        "skipped_generated_files": parse_stats.skipped_generated_files,
+        "truncated": parse_stats.truncated,
+        "skipped_files": parse_stats.skipped_files,
+        "parse_errors": parse_stats.parse_errors,
+        "orm_models": orm_models.len(),
+        "ddl_tables": ddl_tables.len(),
+        "packages": packages.len(),
+        "public_api": public_api_summary(parsed_files),
+        "codeowners": codeowners.as_ref().map(|c| c.source.as_str()),
+        "analyzed_commit": analyzed_commit,
+        "path_filter": {
+            "include": path_filter.include,
+            "exclude": path_filter.exclude,
+            "excluded_files": parse_stats.excluded_files,
+        },
+        "phases": phases.names()
+    });
+
+    if let Some(inventory) = asset_inventory {
+        summary["assets"] = serde_json::json!({
+            "total": inventory.assets.len(),
+            "by_extension": inventory.counts_by_extension(),
+            "truncated": inventory.truncated(),
+        });
+    }
+
+    if let Some(infrastructure) = infrastructure {
+        summary["infrastructure"] = serde_json::json!({
+            "container_images": infrastructure.container_images.len(),
+            "k8s_deployments": infrastructure.k8s_deployments.len(),
+            "k8s_services": infrastructure.k8s_services.len(),
+        });
+    }
+
+    summary["least_cohesive_boundaries"] = serde_json::to_value(
+        metrics::least_cohesive_boundaries(&boundary_result.boundaries, 5)
+            .into_iter()
+            .map(|b| serde_json::json!({
+                "id": b.id,
+                "name": b.name,
+                "type": b.boundary_type.as_str(),
+                "metrics": b.metrics,
+            }))
+            .collect::<Vec<_>>()
+    )?;
+    summary["low_cohesion_boundaries"] = serde_json::json!(
+        boundary_result.boundaries.iter().filter(|b| b.metrics.as_ref().is_some_and(|m| m.low_cohesion)).count()
+    );
+
+    // An incremental graph only holds the changed files' imports
+    if !incremental {
+        let cycles = dep_graph.find_cycles();
+        info!("🔁 Found {} dependency cycles", cycles.len());
+        summary["dependency_cycles"] = serde_json::json!(cycles.len());
+        summary["largest_dependency_cycles"] = serde_json::to_value(&cycles[..cycles.len().min(3)])?;
+    }
+
+    if !incremental && phases.runs(Phase::Boundaries) {
+        let mut boundary_dependencies = metrics::boundary_dependencies(boundary_result, dep_graph);
+        summary["boundary_dependencies_total"] = serde_json::json!(boundary_dependencies.len());
+        boundary_dependencies.truncate(10);
+        summary["boundary_dependencies"] = serde_json::to_value(&boundary_dependencies)?;
+
+        summary["layer_violations_total"] = serde_json::json!(layer_violations.len());
+        summary["layer_violations"] =
+            serde_json::to_value(&layer_violations[..layer_violations.len().min(layering::SUMMARY_EXAMPLES)])?;
+    }
+
+    if !incremental && phases.runs(Phase::Dependencies) {
+        let mut unused: Vec<&str> = library_dependencies
+            .iter()
+            .filter(|dep| dep.usage.as_ref().is_some_and(|usage| usage.unused))
+            .map(|dep| dep.name.as_str())
+            .collect();
+        unused.sort_unstable();
+        unused.dedup();
+        summary["unused_libraries"] = serde_json::json!(unused);
+    }
+
+    summary["hotspots"] = serde_json::to_value(metrics::top_hotspots(parsed_files, git_contributions.as_ref(), 20))?;
+
+    if let Some(contributions) = git_contributions.as_ref() {
+        summary["commit_history"] = serde_json::to_value(&contributions.commits)?;
+        summary["commit_history_total"] = serde_json::json!(contributions.total_commits);
+        summary["commit_history_count"] = serde_json::json!(contributions.commits.len());
+        summary["commit_history_truncated"] = serde_json::json!(contributions.commits.len() < contributions.total_commits);
+        summary["commit_history_limit"] = serde_json::json!(settings.git_max_commits);
+        summary["commit_history_shallow"] = serde_json::json!(contributions.shallow);
+        summary["cochange_top"] = serde_json::to_value(&contributions.cochange[..contributions.cochange.len().min(10)])?;
+        summary["contributors_total"] = serde_json::json!(contributions.total_contributors);
+        summary["top_contributors"] = serde_json::to_value(&contributions.contributors[..contributions.contributors.len().min(5)])?;
+    }
+
+    Ok(summary)
+}
+
+/// Size of the repository's public surface, and the files with the most
+/// public symbols
+fn public_api_summary(parsed_files: &[ParsedFile]) -> serde_json::Value {
+    let mut counts: Vec<(&str, usize)> = parsed_files
+        .iter()
+        .map(|file| (file.path.as_str(), file.public_api_count()))
+        .filter(|(_, count)| *count > 0)
+        .collect();
+    let total: usize = counts.iter().map(|(_, count)| count).sum();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let largest: Vec<serde_json::Value> = counts
+        .iter()
+        .take(10)
+        .map(|(path, count)| serde_json::json!({ "path": path, "public_api_count": count }))
+        .collect();
+    serde_json::json!({ "total": total, "largest_files": largest })
+}
@@ -0,0 +1,247 @@
+//! Pull Request Preview
+//!
+//! Reports the architectural impact of a `base_sha..head_sha` commit range so
+//! a PR can be reviewed before merge. The change set comes from our own git
+//! diff rather than webhook payloads, and preview jobs never write to Neo4j.
+
+use crate::boundary_detector::BoundaryDetectionResult;
+use crate::git_analyzer::CommitRangeDiff;
+use crate::parsers::{ParsedFile, ParserSet};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Base and head commits of a preview job, from the `base_sha`/`head_sha` options
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewRange {
+    pub base_sha: String,
+    pub head_sha: String,
+}
+
+/// An import present at head that the file did not have at base
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NewDependency {
+    pub file: String,
+    pub import: String,
+}
+
+/// A boundary containing at least one changed or removed file
+#[derive(Debug, Clone, Serialize)]
+pub struct AffectedBoundary {
+    pub id: String,
+    pub name: String,
+    pub boundary_type: String,
+    pub changed_files: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviewImpact {
+    pub base_sha: String,
+    pub head_sha: String,
+    pub diff: CommitRangeDiff,
+    pub affected_boundaries: Vec<AffectedBoundary>,
+    pub new_dependencies: Vec<NewDependency>,
+}
+
+/// Read the preview range from job options; both SHAs must be present
+pub fn extract_preview_range(options: &Option<HashMap<String, String>>) -> Option<PreviewRange> {
+    let opts = options.as_ref()?;
+    let base_sha = opts.get("base_sha").map(|s| s.trim()).filter(|s| !s.is_empty())?;
+    let head_sha = opts.get("head_sha").map(|s| s.trim()).filter(|s| !s.is_empty())?;
+
+    Some(PreviewRange {
+        base_sha: base_sha.to_string(),
+        head_sha: head_sha.to_string(),
+    })
+}
+
+/// Compute the impact of a diff. `parsed_files` are the changed files parsed
+/// at head; `base_content` returns a file's content at the base commit.
+pub fn compute_impact(
+    range: &PreviewRange,
+    diff: &CommitRangeDiff,
+    parsed_files: &[ParsedFile],
+    boundary_result: &BoundaryDetectionResult,
+    base_content: impl Fn(&str) -> Option<String>,
+) -> PreviewImpact {
+    let touched: HashSet<String> = diff
+        .changed_files()
+        .into_iter()
+        .chain(diff.removed_files())
+        .collect();
+
+    let mut affected_boundaries: Vec<AffectedBoundary> = boundary_result
+        .boundaries
+        .iter()
+        .filter_map(|boundary| {
+            let mut changed_files: Vec<String> = boundary
+                .files
+                .iter()
+                .filter(|file| touched.contains(*file))
+                .cloned()
+                .collect();
+            if changed_files.is_empty() {
+                return None;
+            }
+            changed_files.sort();
+            Some(AffectedBoundary {
+                id: boundary.id.clone(),
+                name: boundary.name.clone(),
+                boundary_type: boundary.boundary_type.as_str().to_string(),
+                changed_files,
+            })
+        })
+        .collect();
+    affected_boundaries.sort_by(|a, b| a.id.cmp(&b.id));
+
+    // Renamed files are compared against their content under the old path
+    let base_paths: HashMap<&str, &str> = diff
+        .renamed
+        .iter()
+        .map(|(old_path, new_path)| (new_path.as_str(), old_path.as_str()))
+        .collect();
+
+    let parsers = ParserSet::new().ok();
+    let mut new_dependencies = Vec::new();
+    for file in parsed_files {
+        let base_path = base_paths.get(file.path.as_str()).copied().unwrap_or(file.path.as_str());
+        let base_imports: HashSet<String> = match (&parsers, base_content(base_path)) {
+            (Some(parsers), Some(content)) => base_imports(parsers, base_path, &content),
+            _ => HashSet::new(),
+        };
+
+        let mut seen = HashSet::new();
+        for import in &file.imports {
+            if !base_imports.contains(import) && seen.insert(import) {
+                new_dependencies.push(NewDependency {
+                    file: file.path.clone(),
+                    import: import.clone(),
+                });
+            }
+        }
+    }
+
+    PreviewImpact {
+        base_sha: range.base_sha.clone(),
+        head_sha: range.head_sha.clone(),
+        diff: diff.clone(),
+        affected_boundaries,
+        new_dependencies,
+    }
+}
+
+fn base_imports(parsers: &ParserSet, path: &str, content: &str) -> HashSet<String> {
+    let ext = Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    parsers
+        .for_extension(&ext)
+        .and_then(|parser| parser.parse_file(&PathBuf::from(path), content).ok())
+        .map(|parsed| parsed.imports.into_iter().collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary_detector::{Boundary, BoundaryType};
+
+    fn parsed(path: &str, imports: &[&str]) -> ParsedFile {
+        ParsedFile {
+            path: path.to_string(),
+            language: "python".to_string(),
+            functions: vec![],
+            classes: vec![],
+            interfaces: vec![],
+            imports: imports.iter().map(|i| i.to_string()).collect(),
+            data_tables: vec![],
+            service_calls: vec![],
+            sql_queries: vec![],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: false,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: 0,
+        }
+    }
+
+    fn boundary(id: &str, files: &[&str]) -> Boundary {
+        Boundary {
+            id: id.to_string(),
+            name: id.to_string(),
+            boundary_type: BoundaryType::Logical,
+            path: id.to_string(),
+            layer: None,
+            file_count: files.len(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+            metrics: None,
+        }
+    }
+
+    #[test]
+    fn test_extract_preview_range_requires_both_shas() {
+        let mut options = HashMap::new();
+        options.insert("base_sha".to_string(), "abc123".to_string());
+        assert_eq!(extract_preview_range(&Some(options.clone())), None);
+
+        options.insert("head_sha".to_string(), " def456 ".to_string());
+        assert_eq!(
+            extract_preview_range(&Some(options)),
+            Some(PreviewRange {
+                base_sha: "abc123".to_string(),
+                head_sha: "def456".to_string(),
+            })
+        );
+        assert_eq!(extract_preview_range(&None), None);
+    }
+
+    #[test]
+    fn test_compute_impact() {
+        let range = PreviewRange {
+            base_sha: "base".to_string(),
+            head_sha: "head".to_string(),
+        };
+        let diff = CommitRangeDiff {
+            added: vec!["api/new.py".to_string()],
+            modified: vec!["api/app.py".to_string()],
+            deleted: vec!["legacy/old.py".to_string()],
+            renamed: vec![("core/utils.py".to_string(), "core/text.py".to_string())],
+        };
+        let parsed_files = vec![
+            parsed("api/new.py", &["requests"]),
+            parsed("api/app.py", &["os", "json"]),
+            parsed("core/text.py", &["re"]),
+        ];
+        let boundary_result = BoundaryDetectionResult {
+            boundaries: vec![
+                boundary("api", &["api/app.py", "api/new.py"]),
+                boundary("core", &["core/text.py"]),
+                boundary("legacy", &["legacy/old.py"]),
+                boundary("untouched", &["web/index.py"]),
+            ],
+            file_to_boundary: HashMap::new(),
+        };
+        let base_content = |path: &str| match path {
+            "api/app.py" => Some("import os\n".to_string()),
+            "core/utils.py" => Some("import re\n".to_string()),
+            _ => None,
+        };
+
+        let impact = compute_impact(&range, &diff, &parsed_files, &boundary_result, base_content);
+
+        let affected: Vec<&str> = impact.affected_boundaries.iter().map(|b| b.id.as_str()).collect();
+        assert_eq!(affected, vec!["api", "core", "legacy"]);
+        assert_eq!(impact.affected_boundaries[0].changed_files, vec!["api/app.py", "api/new.py"]);
+        assert_eq!(
+            impact.new_dependencies,
+            vec![
+                NewDependency { file: "api/new.py".to_string(), import: "requests".to_string() },
+                NewDependency { file: "api/app.py".to_string(), import: "json".to_string() },
+            ]
+        );
+    }
+}
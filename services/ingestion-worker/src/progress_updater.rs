@@ -0,0 +1,239 @@
+//! Progress Updater
+//!
+//! Coalesces a job's updates before they reach the API gateway. Updates are
+//! merged in memory and a background task sends the latest at most once per
+//! interval, so frequent progress reports never wait on a slow gateway.
+//! A terminal status (COMPLETED, FAILED, CANCELLED) is sent at once, along
+//! with anything still pending, and its result awaited.
+
+use anyhow::Result;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::{ApiClient, JobUpdatePayload};
+
+/// Least time between two updates sent for a job, in milliseconds
+pub const DEFAULT_INTERVAL_MS: u64 = 2_000;
+
+const TERMINAL_STATUSES: &[&str] = &["COMPLETED", "FAILED", "CANCELLED"];
+
+struct Shared {
+    api_client: ApiClient,
+    job_id: String,
+    pending: Mutex<Option<JobUpdatePayload>>,
+    /// Held while sending, so a periodic flush can't land after the
+    /// terminal update
+    send_lock: tokio::sync::Mutex<bool>,
+}
+
+/// Handle shared by everything reporting on one job; the background task
+/// stops once every clone is dropped
+#[derive(Clone)]
+pub struct ProgressUpdater {
+    shared: Arc<Shared>,
+}
+
+impl ProgressUpdater {
+    pub fn new(api_client: ApiClient, job_id: &str, interval: Duration) -> Self {
+        let shared = Arc::new(Shared {
+            api_client,
+            job_id: job_id.to_string(),
+            pending: Mutex::new(None),
+            send_lock: tokio::sync::Mutex::new(false),
+        });
+        tokio::spawn(flush_periodically(Arc::downgrade(&shared), interval));
+        Self { shared }
+    }
+
+    pub fn api_client(&self) -> &ApiClient {
+        &self.shared.api_client
+    }
+
+    /// Merge `payload` into the pending update. A terminal status is sent
+    /// right away and its error returned; anything else waits for the next
+    /// flush and always succeeds.
+    pub async fn update(&self, payload: JobUpdatePayload) -> Result<()> {
+        let terminal = payload.status.as_deref().is_some_and(|status| TERMINAL_STATUSES.contains(&status));
+        self.shared.merge_pending(payload);
+        if terminal {
+            self.shared.flush(true).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Report a progress percentage
+    pub async fn progress(&self, progress: i32) {
+        let _ = self.update(JobUpdatePayload {
+            status: None,
+            progress: Some(progress),
+            result_summary: None,
+            error: None,
+        }).await;
+    }
+}
+
+impl Shared {
+    fn merge_pending(&self, payload: JobUpdatePayload) {
+        let mut pending = self.pending.lock().unwrap();
+        *pending = Some(match pending.take() {
+            Some(older) => merge_payloads(older, payload),
+            None => payload,
+        });
+    }
+
+    /// Send the pending update, if any. Updates after a terminal one are
+    /// dropped.
+    async fn flush(&self, terminal: bool) -> Result<()> {
+        let mut finished = self.send_lock.lock().await;
+        if *finished {
+            return Ok(());
+        }
+        let Some(payload) = self.pending.lock().unwrap().take() else {
+            return Ok(());
+        };
+        let retry = (!terminal).then(|| payload.clone());
+        let result = self.api_client.update_job(&self.job_id, payload).await;
+        match (&result, retry) {
+            // Sent again with the next flush, unless something newer replaced it
+            (Err(_), Some(payload)) => {
+                let mut pending = self.pending.lock().unwrap();
+                *pending = Some(match pending.take() {
+                    Some(newer) => merge_payloads(payload, newer),
+                    None => payload,
+                });
+            }
+            (Ok(()), None) => *finished = true,
+            _ => {}
+        }
+        result
+    }
+}
+
+async fn flush_periodically(shared: Weak<Shared>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        if let Err(e) = shared.flush(false).await {
+            warn!("⚠️  Failed to send progress of job {}: {:?}", shared.job_id, e);
+        }
+    }
+}
+
+/// `newer`'s fields, falling back to `older`'s where `newer` leaves them out
+fn merge_payloads(older: JobUpdatePayload, newer: JobUpdatePayload) -> JobUpdatePayload {
+    JobUpdatePayload {
+        status: newer.status.or(older.status),
+        progress: newer.progress.or(older.progress),
+        result_summary: newer.result_summary.or(older.result_summary),
+        error: newer.error.or(older.error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn progress(progress: i32) -> JobUpdatePayload {
+        JobUpdatePayload { status: None, progress: Some(progress), result_summary: None, error: None }
+    }
+
+    #[test]
+    fn test_merge_payloads_keeps_latest_fields() {
+        let older = JobUpdatePayload {
+            status: Some("PROCESSING".to_string()),
+            progress: Some(10),
+            result_summary: None,
+            error: None,
+        };
+        let merged = merge_payloads(older, progress(40));
+        assert_eq!(merged.status.as_deref(), Some("PROCESSING"));
+        assert_eq!(merged.progress, Some(40));
+    }
+
+    #[tokio::test]
+    async fn test_rapid_updates_are_coalesced_and_terminal_state_arrives() {
+        let mut server = mockito::Server::new_async().await;
+        let progress_patches = server
+            .mock("PATCH", "/api/v1/jobs/job-1")
+            .match_body(mockito::Matcher::PartialJson(json!({"status": "PROCESSING"})))
+            .with_status(200)
+            .expect_at_least(1)
+            .expect_at_most(2)
+            .create_async()
+            .await;
+        let completed = server
+            .mock("PATCH", "/api/v1/jobs/job-1")
+            .match_body(mockito::Matcher::Json(json!({
+                "status": "COMPLETED",
+                "progress": 100,
+                "result_summary": {"files": 3}
+            })))
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let updater = ProgressUpdater::new(ApiClient::new(server.url()), "job-1", Duration::from_millis(200));
+        updater
+            .update(JobUpdatePayload {
+                status: Some("PROCESSING".to_string()),
+                progress: Some(0),
+                result_summary: None,
+                error: None,
+            })
+            .await
+            .unwrap();
+        for p in 1..10 {
+            updater.progress(p * 10).await;
+        }
+        // The status of the first update rides along with the last progress
+        tokio::time::sleep(Duration::from_millis(350)).await;
+        updater.progress(95).await;
+        updater
+            .update(JobUpdatePayload {
+                status: Some("COMPLETED".to_string()),
+                progress: Some(100),
+                result_summary: Some(json!({"files": 3})),
+                error: None,
+            })
+            .await
+            .unwrap();
+        // Nothing is sent after the terminal update
+        updater.progress(50).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        progress_patches.assert_async().await;
+        completed.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_terminal_update_failure_is_returned() {
+        let mut server = mockito::Server::new_async().await;
+        let _failing = server
+            .mock("PATCH", "/api/v1/jobs/job-2")
+            .with_status(503)
+            .with_body("unavailable")
+            .create_async()
+            .await;
+
+        let updater = ProgressUpdater::new(ApiClient::new(server.url()), "job-2", Duration::from_secs(60));
+        updater.progress(30).await;
+        let result = updater
+            .update(JobUpdatePayload {
+                status: Some("FAILED".to_string()),
+                progress: None,
+                result_summary: None,
+                error: Some("boom".to_string()),
+            })
+            .await;
+        assert_eq!(result.unwrap_err().to_string(), "API Error: unavailable");
+    }
+}
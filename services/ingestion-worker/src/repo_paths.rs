@@ -0,0 +1,98 @@
+//! Repo Paths
+//!
+//! Every file path that enters the worker, from webhook options or from
+//! walking the checkout, is brought to the form File nodes are keyed by:
+//! relative to the repository root, with single forward slashes and no `.`
+//! segments. `changed_files`, `removed_files` and `renamed_files` come from
+//! outside, so absolute paths and `..` segments are rejected rather than
+//! cleaned up; joined onto the checkout they would point outside it.
+
+use anyhow::{bail, Result};
+
+/// `raw` as a repo-relative path: backslashes become `/`, and leading `./`,
+/// `.` segments and repeated slashes are dropped
+pub fn normalize_repo_path(raw: &str) -> Result<String> {
+    let path = raw.replace('\\', "/");
+    let bytes = path.as_bytes();
+    if path.starts_with('/') || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':') {
+        bail!("Path {:?} is absolute, expected a path relative to the repository root", raw);
+    }
+
+    let mut segments = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => bail!("Path {:?} leaves the repository", raw),
+            segment => segments.push(segment),
+        }
+    }
+    if segments.is_empty() {
+        bail!("Path {:?} names no file", raw);
+    }
+    Ok(segments.join("/"))
+}
+
+/// Each of `paths` normalized, failing on the first that can't be
+pub fn normalize_repo_paths(paths: &[String]) -> Result<Vec<String>> {
+    paths.iter().map(|path| normalize_repo_path(path)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalizes_separators_and_dot_segments() {
+        for (raw, expected) in [
+            ("src/main.rs", "src/main.rs"),
+            ("./src/main.rs", "src/main.rs"),
+            (".\\src\\main.rs", "src/main.rs"),
+            ("src\\web/app.ts", "src/web/app.ts"),
+            ("src//web///app.ts", "src/web/app.ts"),
+            ("src/./web/./app.ts", "src/web/app.ts"),
+            ("././README.md", "README.md"),
+            ("docs/", "docs"),
+            // Dots inside names are not segments
+            ("..hidden/x.py", "..hidden/x.py"),
+            ("config/.env.example", "config/.env.example"),
+            ("a/b..c", "a/b..c"),
+            // Nor is a colon past the first segment a drive
+            ("src/a:b.rs", "src/a:b.rs"),
+        ] {
+            assert_eq!(normalize_repo_path(raw).unwrap(), expected, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_rejects_absolute_paths() {
+        for raw in ["/etc/passwd", "\\etc\\passwd", "C:\\repo\\src\\main.rs", "c:/repo/main.rs", "D:main.rs", "//server/share/x"]
+        {
+            let err = normalize_repo_path(raw).unwrap_err();
+            assert!(err.to_string().contains("absolute"), "{}: {}", raw, err);
+        }
+    }
+
+    #[test]
+    fn test_rejects_traversal() {
+        for raw in ["../secrets.txt", "src/../../etc/passwd", "src\\..\\..\\x", "./..", "a/b/.."] {
+            let err = normalize_repo_path(raw).unwrap_err();
+            assert!(err.to_string().contains("leaves the repository"), "{}: {}", raw, err);
+        }
+    }
+
+    #[test]
+    fn test_rejects_empty_paths() {
+        for raw in ["", ".", "./", "//.//"] {
+            assert!(normalize_repo_path(raw).is_err(), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_normalize_list_fails_on_any_bad_path() {
+        let paths = vec!["./a.rs".to_string(), "b\\c.rs".to_string()];
+        assert_eq!(normalize_repo_paths(&paths).unwrap(), vec!["a.rs", "b/c.rs"]);
+
+        let paths = vec!["a.rs".to_string(), "../b.rs".to_string()];
+        assert!(normalize_repo_paths(&paths).is_err());
+    }
+}
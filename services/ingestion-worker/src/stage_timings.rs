@@ -0,0 +1,126 @@
+//! Stage Timings
+//!
+//! Wall-clock duration of each stage of `analyze_repository`, reported in the
+//! job result summary as `timings` so slow ingestions can be attributed to a
+//! stage.
+
+use serde::Serialize;
+use std::future::Future;
+use std::time::Instant;
+use tracing::info;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StageTimings {
+    pub clone_ms: u64,
+    pub parse_ms: u64,
+    pub symbol_table_ms: u64,
+    pub git_analysis_ms: u64,
+    pub boundary_detection_ms: u64,
+    pub dependency_collection_ms: u64,
+    pub communication_detection_ms: u64,
+    pub graph_build_ms: u64,
+    pub storage_ms: u64,
+    pub total_ms: u64,
+}
+
+impl StageTimings {
+    pub fn log(&self) {
+        info!(
+            clone_ms = self.clone_ms,
+            parse_ms = self.parse_ms,
+            symbol_table_ms = self.symbol_table_ms,
+            git_analysis_ms = self.git_analysis_ms,
+            boundary_detection_ms = self.boundary_detection_ms,
+            dependency_collection_ms = self.dependency_collection_ms,
+            communication_detection_ms = self.communication_detection_ms,
+            graph_build_ms = self.graph_build_ms,
+            storage_ms = self.storage_ms,
+            total_ms = self.total_ms,
+            "⏱️  Stage timings"
+        );
+    }
+
+    /// Each stage's name and duration, without the total
+    pub fn stages(&self) -> [(&'static str, u64); 9] {
+        [
+            ("clone", self.clone_ms),
+            ("parse", self.parse_ms),
+            ("symbol_table", self.symbol_table_ms),
+            ("git_analysis", self.git_analysis_ms),
+            ("boundary_detection", self.boundary_detection_ms),
+            ("dependency_collection", self.dependency_collection_ms),
+            ("communication_detection", self.communication_detection_ms),
+            ("graph_build", self.graph_build_ms),
+            ("storage", self.storage_ms),
+        ]
+    }
+}
+
+/// Await `stage` and record its duration in `slot`
+pub async fn timed<T>(slot: &mut u64, stage: impl Future<Output = T>) -> T {
+    let started = Instant::now();
+    let output = stage.await;
+    *slot = elapsed_ms(started);
+    output
+}
+
+/// Run `stage` and record its duration in `slot`, for stages already on the
+/// blocking pool
+pub fn timed_blocking<T>(slot: &mut u64, stage: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let output = stage();
+    *slot = elapsed_ms(started);
+    output
+}
+
+pub fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_timed_stages_fill_summary() {
+        let started = Instant::now();
+        let mut timings = StageTimings::default();
+
+        let files = timed(&mut timings.clone_ms, async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            3
+        })
+        .await;
+        let parsed: anyhow::Result<usize> = timed(&mut timings.parse_ms, async { Ok(files * 2) }).await;
+        let nodes = timed_blocking(&mut timings.graph_build_ms, || {
+            std::thread::sleep(Duration::from_millis(10));
+            files + 1
+        });
+        timings.total_ms = elapsed_ms(started);
+
+        assert_eq!(parsed.unwrap(), 6);
+        assert_eq!(nodes, 4);
+        assert!(timings.clone_ms >= 20);
+        assert!(timings.graph_build_ms >= 10);
+        assert!(timings.total_ms >= timings.clone_ms + timings.parse_ms);
+
+        let value = serde_json::to_value(&timings).unwrap();
+        let keys = [
+            "clone_ms",
+            "parse_ms",
+            "symbol_table_ms",
+            "git_analysis_ms",
+            "boundary_detection_ms",
+            "dependency_collection_ms",
+            "communication_detection_ms",
+            "graph_build_ms",
+            "storage_ms",
+            "total_ms",
+        ];
+        assert_eq!(value.as_object().unwrap().len(), keys.len());
+        for key in keys {
+            assert!(value[key].as_u64().is_some(), "{} missing or negative", key);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Repo Summary
+//!
+//! A compact overview of a repository's last full analysis, kept in Redis as
+//! `repo_summary:{repo_id}` so dashboards can show it without querying
+//! Neo4j. It never expires; each full run that stores its graph overwrites
+//! it, and a job that fails afterwards removes it again if it is still the
+//! one that wrote it.
+
+use anyhow::{Context, Result};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::warn;
+
+use crate::boundary_detector::BoundaryDetectionResult;
+use crate::communication_detector::CommunicationAnalysis;
+use crate::graph_builder::GraphStats;
+use crate::parsers::ParsedFile;
+
+pub fn repo_summary_key(repo_id: &str) -> String {
+    format!("repo_summary:{}", repo_id)
+}
+
+/// Deletes the summary only while its `job_id` is the given one, so a job
+/// failing late never removes a newer job's summary
+const CLEAR_OWN_SUMMARY: &str = r#"
+local raw = redis.call('GET', KEYS[1])
+if raw and cjson.decode(raw).job_id == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoSummary {
+    pub repo_id: String,
+    pub job_id: String,
+    /// RFC 3339 time the summary was built
+    pub analyzed_at: String,
+    /// Graph nodes by type: `files`, `classes`, `functions`, `modules`
+    pub node_counts: BTreeMap<String, usize>,
+    /// Graph edges by lowercased type
+    pub edge_counts: BTreeMap<String, usize>,
+    /// Parsed files per language
+    pub languages: BTreeMap<String, usize>,
+    /// None when the job skipped boundary detection
+    pub boundaries: Option<Vec<BoundarySummary>>,
+    /// None when the job skipped the dependencies phase
+    pub library_count: Option<usize>,
+    /// Distinct outgoing endpoints (method and normalized URL); None when
+    /// the job skipped communication analysis
+    pub endpoint_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoundarySummary {
+    pub name: String,
+    pub path: String,
+    pub file_count: usize,
+}
+
+/// Summary of a full run. Results of phases the job skipped are passed as
+/// None and stay None.
+#[allow(clippy::too_many_arguments)]
+pub fn build_repo_summary(
+    repo_id: &str,
+    job_id: &str,
+    analyzed_at: chrono::DateTime<chrono::Utc>,
+    stats: &GraphStats,
+    parsed_files: &[ParsedFile],
+    boundary_result: Option<&BoundaryDetectionResult>,
+    library_count: Option<usize>,
+    communication_analysis: Option<&CommunicationAnalysis>,
+) -> RepoSummary {
+    let counts = |entries: &[(&str, usize)]| -> BTreeMap<String, usize> {
+        entries.iter().map(|(name, count)| (name.to_string(), *count)).collect()
+    };
+    let node_counts = counts(&[
+        ("files", stats.files),
+        ("classes", stats.classes),
+        ("functions", stats.functions),
+        ("modules", stats.modules),
+    ]);
+    let edge_counts = counts(&[
+        ("defines", stats.defines_edges),
+        ("calls", stats.calls_edges),
+        ("imports", stats.imports_edges),
+        ("inherits", stats.inherits_edges),
+        ("contains", stats.contains_edges),
+        ("depends_on", stats.depends_on_edges),
+        ("uses_component", stats.uses_component_edges),
+    ]);
+
+    let mut languages = BTreeMap::new();
+    for file in parsed_files {
+        *languages.entry(file.language.clone()).or_default() += 1;
+    }
+
+    let boundaries = boundary_result.map(|result| {
+        let mut boundaries: Vec<BoundarySummary> = result
+            .boundaries
+            .iter()
+            .map(|boundary| BoundarySummary {
+                name: boundary.name.clone(),
+                path: boundary.path.clone(),
+                file_count: boundary.file_count,
+            })
+            .collect();
+        boundaries.sort_by(|a, b| a.path.cmp(&b.path).then_with(|| a.name.cmp(&b.name)));
+        boundaries
+    });
+    let endpoint_count = communication_analysis.map(|analysis| {
+        analysis
+            .endpoints
+            .iter()
+            .map(|endpoint| (endpoint.method.as_str(), endpoint.url.as_str()))
+            .collect::<BTreeSet<_>>()
+            .len()
+    });
+
+    RepoSummary {
+        repo_id: repo_id.to_string(),
+        job_id: job_id.to_string(),
+        analyzed_at: analyzed_at.to_rfc3339(),
+        node_counts,
+        edge_counts,
+        languages,
+        boundaries,
+        library_count,
+        endpoint_count,
+    }
+}
+
+/// Overwrite the repository's summary, without expiry
+pub async fn store_repo_summary<C>(conn: &mut C, summary: &RepoSummary) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let key = repo_summary_key(&summary.repo_id);
+    let payload = serde_json::to_string(summary).context("Failed to serialize repo summary")?;
+    conn.set::<_, _, ()>(&key, payload)
+        .await
+        .with_context(|| format!("Failed to store repo summary under {}", key))
+}
+
+/// Remove the repository's summary if `job_id` wrote it
+pub async fn clear_repo_summary(redis_client: &redis::Client, repo_id: &str, job_id: &str) {
+    let key = repo_summary_key(repo_id);
+    let result: Result<()> = async {
+        let mut conn = redis_client.get_async_connection().await?;
+        redis::Script::new(CLEAR_OWN_SUMMARY)
+            .key(&key)
+            .arg(job_id)
+            .invoke_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        warn!("⚠️  Failed to clear {}: {:?}", key, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary_detector::{Boundary, BoundaryType};
+    use crate::communication_detector::EndpointCall;
+    use chrono::TimeZone;
+
+    fn file(path: &str, language: &str) -> ParsedFile {
+        ParsedFile {
+            path: path.to_string(),
+            language: language.to_string(),
+            functions: vec![],
+            classes: vec![],
+            interfaces: vec![],
+            imports: vec![],
+            data_tables: vec![],
+            service_calls: vec![],
+            sql_queries: vec![],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: false,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: 1,
+        }
+    }
+
+    fn boundary(name: &str, path: &str, file_count: usize) -> Boundary {
+        Boundary {
+            id: format!("boundary:{}", path),
+            name: name.to_string(),
+            boundary_type: BoundaryType::Logical,
+            path: path.to_string(),
+            layer: None,
+            file_count,
+            files: vec![],
+            metrics: None,
+        }
+    }
+
+    fn endpoint(method: &str, url: &str) -> EndpointCall {
+        EndpointCall {
+            file_path: "web/api.ts".to_string(),
+            url: url.to_string(),
+            raw_url: url.to_string(),
+            method: method.to_string(),
+            host: None,
+            port: None,
+            domain: None,
+            internal: true,
+        }
+    }
+
+    #[test]
+    fn test_build_repo_summary() {
+        let stats = GraphStats { files: 3, classes: 2, functions: 7, calls_edges: 4, ..Default::default() };
+        let parsed_files = vec![file("web/api.ts", "typescript"), file("web/app.ts", "typescript"), file("db.py", "python")];
+        let boundaries = BoundaryDetectionResult {
+            boundaries: vec![boundary("web", "web", 2), boundary("api", "services/api", 5)],
+            ..Default::default()
+        };
+        let communication = CommunicationAnalysis {
+            endpoints: vec![endpoint("GET", "/users/:id"), endpoint("GET", "/users/:id"), endpoint("POST", "/users/:id")],
+            ..Default::default()
+        };
+        let analyzed_at = chrono::Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+
+        let summary = build_repo_summary(
+            "repo-1",
+            "job-9",
+            analyzed_at,
+            &stats,
+            &parsed_files,
+            Some(&boundaries),
+            Some(4),
+            Some(&communication),
+        );
+        assert_eq!(summary.job_id, "job-9");
+        assert_eq!(summary.analyzed_at, "2026-03-01T12:00:00+00:00");
+        assert_eq!(summary.node_counts["functions"], 7);
+        assert_eq!(summary.edge_counts["calls"], 4);
+        assert_eq!(summary.languages, BTreeMap::from([("python".to_string(), 1), ("typescript".to_string(), 2)]));
+        let boundaries: Vec<(&str, usize)> =
+            summary.boundaries.as_ref().unwrap().iter().map(|b| (b.name.as_str(), b.file_count)).collect();
+        assert_eq!(boundaries, vec![("api", 5), ("web", 2)]);
+        assert_eq!(summary.library_count, Some(4));
+        assert_eq!(summary.endpoint_count, Some(2));
+
+        // Skipped phases leave their parts out rather than reporting zero
+        let summary = build_repo_summary("repo-1", "job-9", analyzed_at, &stats, &parsed_files, None, None, None);
+        assert_eq!((summary.boundaries, summary.library_count, summary.endpoint_count), (None, None, None));
+    }
+}
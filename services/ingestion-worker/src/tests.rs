@@ -1,18 +1,20 @@
 use super::*;
-use mockito::mock;
 use serde_json::json;
 
 #[tokio::test]
 async fn test_api_client_update_job_success() {
     // Start a mock server
-    let _m = mock("PATCH", "/api/v1/jobs/test-job-123")
+    let mut server = mockito::Server::new_async().await;
+    let _m = server
+        .mock("PATCH", "/api/v1/jobs/test-job-123")
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{"status": "success"}"#)
-        .create();
+        .create_async()
+        .await;
 
     // Initialize ApiClient with mock server URL
-    let url = mockito::server_url();
+    let url = server.url();
     let client = ApiClient::new(url);
 
     // Create payload
@@ -30,15 +32,46 @@ async fn test_api_client_update_job_success() {
     assert!(result.is_ok());
 }
 
+#[tokio::test]
+async fn test_api_client_upload_artifact() {
+    let mut server = mockito::Server::new_async().await;
+    let m = server
+        .mock("PUT", "/api/v1/jobs/test-job-123/artifacts/graph.dot")
+        .match_header("content-type", "application/octet-stream")
+        .match_body("digraph dependencies {\n}\n")
+        .with_status(201)
+        .create_async()
+        .await;
+    let _rejected = server
+        .mock("PUT", "/api/v1/jobs/test-job-123/artifacts/graph.graphml")
+        .with_status(413)
+        .with_body("too large")
+        .create_async()
+        .await;
+
+    let client = ApiClient::new(server.url());
+    client
+        .upload_artifact("test-job-123", "graph.dot", b"digraph dependencies {\n}\n".to_vec())
+        .await
+        .expect("upload failed");
+    m.assert_async().await;
+
+    let result = client.upload_artifact("test-job-123", "graph.graphml", Vec::new()).await;
+    assert_eq!(result.unwrap_err().to_string(), "API Error: too large");
+}
+
 #[tokio::test]
 async fn test_api_client_update_job_failure() {
     // Mock a 500 error
-    let _m = mock("PATCH", "/api/v1/jobs/test-job-123")
+    let mut server = mockito::Server::new_async().await;
+    let _m = server
+        .mock("PATCH", "/api/v1/jobs/test-job-123")
         .with_status(500)
         .with_body("Internal Server Error")
-        .create();
+        .create_async()
+        .await;
 
-    let url = mockito::server_url();
+    let url = server.url();
     let client = ApiClient::new(url);
 
     let payload = JobUpdatePayload {
@@ -55,6 +88,30 @@ async fn test_api_client_update_job_failure() {
     assert_eq!(result.unwrap_err().to_string(), "API Error: Internal Server Error");
 }
 
+#[tokio::test]
+async fn test_api_client_includes_worker_id() {
+    let mut server = mockito::Server::new_async().await;
+    let _m = server
+        .mock("PATCH", "/api/v1/jobs/test-job-123")
+        .match_body(mockito::Matcher::Json(json!({
+            "progress": 50,
+            "worker_id": "worker-1"
+        })))
+        .with_status(200)
+        .create_async()
+        .await;
+
+    let client = ApiClient::new(server.url()).with_worker_id("worker-1".to_string());
+    let payload = JobUpdatePayload {
+        status: None,
+        progress: Some(50),
+        result_summary: None,
+        error: None,
+    };
+
+    assert!(client.update_job("test-job-123", payload).await.is_ok());
+}
+
 #[tokio::test]
 async fn test_job_update_payload_serialization() {
     let payload = JobUpdatePayload {
@@ -80,59 +137,72 @@ async fn test_api_client_full_workflow_simulation() {
     
     let job_id = "workflow-job";
     let base_path = format!("/api/v1/jobs/{}", job_id);
+    let mut server = mockito::Server::new_async().await;
 
     // 1. Initial Processing (0%)
-    let _m1 = mock("PATCH", base_path.as_str())
+    let _m1 = server
+        .mock("PATCH", base_path.as_str())
         .match_body(mockito::Matcher::Json(json!({
             "status": "PROCESSING",
             "progress": 0
         })))
         .with_status(200)
-        .create();
+        .create_async()
+        .await;
     
     // 2. Cloning (25%)
-    let _m2 = mock("PATCH", base_path.as_str())
+    let _m2 = server
+        .mock("PATCH", base_path.as_str())
         .match_body(mockito::Matcher::Json(json!({
             "progress": 25
         })))
         .with_status(200)
-        .create();
+        .create_async()
+        .await;
 
     // 3. Parsing (50%)
-    let _m3 = mock("PATCH", base_path.as_str())
+    let _m3 = server
+        .mock("PATCH", base_path.as_str())
         .match_body(mockito::Matcher::Json(json!({
             "progress": 50
         })))
         .with_status(200)
-        .create();
+        .create_async()
+        .await;
 
     // 4. Graph Building (75%)
-    let _m4 = mock("PATCH", base_path.as_str())
+    let _m4 = server
+        .mock("PATCH", base_path.as_str())
         .match_body(mockito::Matcher::Json(json!({
             "progress": 75
         })))
         .with_status(200)
-        .create();
+        .create_async()
+        .await;
 
     // 5. Storage (90%)
-    let _m5 = mock("PATCH", base_path.as_str())
+    let _m5 = server
+        .mock("PATCH", base_path.as_str())
         .match_body(mockito::Matcher::Json(json!({
             "progress": 90
         })))
         .with_status(200)
-        .create();
+        .create_async()
+        .await;
 
     // 6. Completion (100%)
-    let _m6 = mock("PATCH", base_path.as_str())
+    let _m6 = server
+        .mock("PATCH", base_path.as_str())
         .match_body(mockito::Matcher::Json(json!({
             "status": "COMPLETED",
             "progress": 100,
             "result_summary": {"success": true}
         })))
         .with_status(200)
-        .create();
+        .create_async()
+        .await;
 
-    let url = mockito::server_url();
+    let url = server.url();
     let client = ApiClient::new(url);
 
     // Execute sequence
@@ -180,18 +250,10 @@ async fn test_api_client_full_workflow_simulation() {
 }
 
 #[test]
-fn test_walk_directory_relative_paths() {
+fn test_parse_repository_relative_paths() {
     use std::fs::{self, File};
     use std::io::Write;
     use uuid::Uuid;
-    use super::parsers::{
-        javascript::JavaScriptParser,
-        typescript::TypeScriptParser,
-        rust_parser::RustParser,
-        go_parser::GoParser,
-        python_parser::PythonParser,
-        ParsedFile,
-    };
 
     let uuid = Uuid::new_v4();
     let temp_dir = std::env::temp_dir().join(format!("test-repo-{}", uuid));
@@ -204,28 +266,12 @@ fn test_walk_directory_relative_paths() {
     let mut file = File::create(&main_rs).expect("Failed to create main.rs");
     writeln!(file, "fn main() {{}}").expect("Failed to write to main.rs");
 
-    let mut parsed_files: Vec<ParsedFile> = Vec::new();
-    let js_parser = JavaScriptParser::new().unwrap();
-    let ts_parser = TypeScriptParser::new().unwrap();
-    let rust_parser = RustParser::new().unwrap();
-    let go_parser = GoParser::new().unwrap();
-    let py_parser = PythonParser::new().unwrap();
-
-    let result = super::walk_directory(
-        &temp_dir,
-        &temp_dir,
-        &mut parsed_files,
-        &js_parser,
-        &ts_parser,
-        &rust_parser,
-        &go_parser,
-        &py_parser,
-    );
+    let result = super::parse_repository(&temp_dir, 1, &FileLimits::default(), &PathFilter::default());
 
     // Cleanup
     let _ = fs::remove_dir_all(&temp_dir);
 
-    assert!(result.is_ok());
+    let (parsed_files, _) = result.expect("Parse failed");
     assert_eq!(parsed_files.len(), 1);
     
     // Check relative path
@@ -234,15 +280,142 @@ fn test_walk_directory_relative_paths() {
     assert_eq!(parsed_files[0].language, "rust");
 }
 
+/// Panics on `src/bad.ts`, fails on `src/error.ts` and parses anything else
+/// as an empty file
+struct FlakyParser;
+
+impl super::parsers::LanguageParser for FlakyParser {
+    fn parse_file(&self, path: &PathBuf, content: &str) -> Result<ParsedFile> {
+        match path.to_str() {
+            Some("src/bad.ts") => panic!("query capture out of range"),
+            Some("src/error.ts") => anyhow::bail!("unexpected node"),
+            _ => Ok(ParsedFile { parse_failed: false, ..ParsedFile::failed(path.display().to_string(), "typescript", content) }),
+        }
+    }
+}
+
+#[test]
+fn test_parser_panics_fail_only_their_file() {
+    use super::parsers::parse_isolated;
+
+    let parse = |path: &str| parse_isolated(&FlakyParser, &PathBuf::from(path), "export {};\n");
+    assert_eq!(parse("src/bad.ts").unwrap_err(), "parser panicked: query capture out of range");
+    assert_eq!(parse("src/error.ts").unwrap_err(), "unexpected node");
+    // The parser keeps working after a panic
+    assert!(!parse("src/good.ts").unwrap().parse_failed);
+
+    let results: Vec<SourceFile> = ["src/bad.ts", "src/good.ts"]
+        .into_iter()
+        .map(|path| match parse(path) {
+            Ok(parsed_file) => SourceFile::Parsed(Box::new(parsed_file)),
+            Err(error) => SourceFile::Failed(Box::new(ParsedFile::failed(path.to_string(), "typescript", "x\n")), error),
+        })
+        .collect();
+    let mut stats = ParseStats::default();
+    let parsed_files = collect_parsed(results, &mut stats);
+
+    let flags: Vec<(&str, bool)> = parsed_files.iter().map(|f| (f.path.as_str(), f.parse_failed)).collect();
+    assert_eq!(flags, vec![("src/bad.ts", true), ("src/good.ts", false)]);
+    assert_eq!(parsed_files[0].line_count, 1);
+    assert_eq!(
+        stats.parse_errors,
+        vec![ParseError {
+            path: "src/bad.ts".to_string(),
+            error: "parser panicked: query capture out of range".to_string(),
+        }]
+    );
+}
+
+/// Stable summary of a parsed file; tables come out of HashSets so their
+/// order differs between parses, and calls are sorted to match.
+fn parsed_file_fingerprint(file: &ParsedFile) -> String {
+    let mut functions: Vec<String> = file
+        .functions
+        .iter()
+        .map(|f| {
+            let mut calls = f.calls.clone();
+            calls.sort();
+            format!("{}:{}-{}:{:?}", f.name, f.start_line, f.end_line, calls)
+        })
+        .collect();
+    functions.sort();
+    let mut tables = file.data_tables.clone();
+    tables.sort();
+
+    format!(
+        "{}|{}|{:?}|{:?}|{:?}|{}",
+        file.path, file.language, functions, file.imports, tables, file.classes.len()
+    )
+}
+
+#[test]
+fn test_parallel_parse_matches_sequential() {
+    use std::fs;
+    use std::time::Instant;
+    use uuid::Uuid;
+
+    let temp_dir = std::env::temp_dir().join(format!("test-repo-parallel-{}", Uuid::new_v4()));
+    for i in 0..200 {
+        let dir = temp_dir.join(format!("pkg{}", i % 12));
+        fs::create_dir_all(&dir).expect("Failed to create package dir");
+        let (name, content) = match i % 5 {
+            0 => (
+                format!("mod_{}.rs", i),
+                format!("use crate::pkg{}::helper;\nfn run_{}() {{ helper(); println!(\"{}\"); }}\n", i % 7, i, i),
+            ),
+            1 => (
+                format!("svc_{}.go", i),
+                format!("package pkg\nimport \"fmt\"\nfunc Run{}() {{ fmt.Println({}) }}\n", i, i),
+            ),
+            2 => (
+                format!("task_{}.py", i),
+                format!("import os\n\ndef task_{}(path):\n    return os.path.join(path, 'SELECT * FROM t{}')\n", i, i),
+            ),
+            3 => (
+                format!("view_{}.ts", i),
+                format!("import {{ api }} from './api';\nexport function view{}(id: string) {{ return api.get(id); }}\n", i),
+            ),
+            _ => (
+                format!("Handler{}.java", i),
+                format!("import java.util.List;\npublic class Handler{} {{ void handle(List<String> items) {{ items.clear(); }} }}\n", i),
+            ),
+        };
+        fs::write(dir.join(name), content).expect("Failed to write source file");
+    }
+
+    let sequential_start = Instant::now();
+    let (sequential, _) = super::parse_repository(&temp_dir, 1, &FileLimits::default(), &PathFilter::default())
+        .expect("Single-threaded parse failed");
+    let sequential_elapsed = sequential_start.elapsed();
+
+    let parallel_start = Instant::now();
+    let (parallel, _) = super::parse_repository(&temp_dir, 4, &FileLimits::default(), &PathFilter::default()).expect("Parallel parse failed");
+    let parallel_elapsed = parallel_start.elapsed();
+
+    let _ = fs::remove_dir_all(&temp_dir);
+
+    println!(
+        "parsed {} files: 1 thread {:?}, parallel (4 threads) {:?}",
+        parallel.len(),
+        sequential_elapsed,
+        parallel_elapsed
+    );
+
+    assert_eq!(sequential.len(), 200);
+    let sequential: Vec<String> = sequential.iter().map(parsed_file_fingerprint).collect();
+    let parallel: Vec<String> = parallel.iter().map(parsed_file_fingerprint).collect();
+    assert_eq!(sequential, parallel);
+}
+
 #[test]
 fn test_analysis_job_deserialization_with_repo_id() {
     let json = r#"{
-        \"job_id\": \"job-123\",
-        \"repo_id\": \"repo-456\",
-        \"repo_url\": \"https://github.com/test\",
-        \"branch\": \"main\",
-        \"status\": \"QUEUED\",
-        \"created_at\": \"2023-01-01T00:00:00Z\"
+        "job_id": "job-123",
+        "repo_id": "repo-456",
+        "repo_url": "https://github.com/test",
+        "branch": "main",
+        "status": "QUEUED",
+        "created_at": "2023-01-01T00:00:00Z"
     }"#;
 
     // We need to use super::AnalysisJob since it is defined in main.rs
@@ -253,3 +426,1508 @@ fn test_analysis_job_deserialization_with_repo_id() {
     assert_eq!(job.repo_url, "https://github.com/test");
 }
 
+
+#[test]
+fn test_queue_names_from_env() {
+    assert_eq!(queue_names(None), job_queue::DEFAULT_QUEUES);
+    assert_eq!(queue_names(Some(" , ")), job_queue::DEFAULT_QUEUES);
+    assert_eq!(
+        queue_names(Some("jobs:urgent, jobs ,jobs:urgent,,jobs:bulk")),
+        ["jobs:urgent", "jobs", "jobs:bulk"]
+    );
+
+    // Priority is read for logging; the claimed queue is never serialized
+    let job: AnalysisJob = serde_json::from_value(serde_json::json!({
+        "job_id": "job-1", "repo_id": "repo-1", "repo_url": "https://github.com/test", "branch": "main",
+        "status": "QUEUED", "options": null, "created_at": "2023-01-01T00:00:00Z", "priority": "high",
+    }))
+    .unwrap();
+    assert_eq!(job.priority.as_deref(), Some("high"));
+    assert!(serde_json::to_value(&job).unwrap().get("queue").is_none());
+}
+
+#[test]
+fn test_queue_wait_from_created_at() {
+    use chrono::TimeZone;
+
+    let now = chrono::Utc.with_ymd_and_hms(2023, 1, 1, 0, 1, 30).unwrap();
+    assert_eq!(queue_wait("2023-01-01T00:00:00Z", now), Some(std::time::Duration::from_secs(90)));
+    assert_eq!(queue_wait("2023-01-01T01:00:00+01:00", now), Some(std::time::Duration::from_secs(90)));
+    // A clock ahead of the worker's, and a timestamp that doesn't parse
+    assert_eq!(queue_wait("2023-01-01T00:05:00Z", now), None);
+    assert_eq!(queue_wait("yesterday", now), None);
+}
+
+
+fn fake_job(job_id: &str) -> AnalysisJob {
+    AnalysisJob {
+        job_id: job_id.to_string(),
+        repo_id: "repo-456".to_string(),
+        repo_url: "https://github.com/test".to_string(),
+        branch: "main".to_string(),
+        status: "QUEUED".to_string(),
+        options: None,
+        created_at: "2023-01-01T00:00:00Z".to_string(),
+        attempts: 0,
+        priority: None,
+        queue: job_queue::QUEUE_KEY.to_string(),
+        queue_entry: String::new(),
+    }
+}
+
+/// Run two fake jobs through the job loop. Shutdown is requested as soon as
+/// the queue is drained, so every job still has to be finished by the loop
+/// itself. Returns (completed job ids, max jobs running at once).
+async fn run_fake_jobs(max_concurrent: usize) -> (Vec<String>, usize) {
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    let queue = Arc::new(Mutex::new(VecDeque::from(vec![fake_job("job-1"), fake_job("job-2")])));
+    let shutdown = super::CancellationToken::new();
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+    let completed = Arc::new(Mutex::new(Vec::new()));
+
+    super::run_job_loop(
+        max_concurrent,
+        &shutdown,
+        || {
+            let (queue, shutdown) = (queue.clone(), shutdown.clone());
+            async move {
+                let mut queue = queue.lock().unwrap();
+                let job = queue.pop_front();
+                if queue.is_empty() {
+                    shutdown.cancel();
+                }
+                Ok(job)
+            }
+        },
+        |job| {
+            let (running, max_running, completed) = (running.clone(), max_running.clone(), completed.clone());
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                completed.lock().unwrap().push(job.job_id);
+            }
+        },
+    )
+    .await;
+
+    let mut completed = completed.lock().unwrap().clone();
+    completed.sort();
+    (completed, max_running.load(Ordering::SeqCst))
+}
+
+#[tokio::test]
+async fn test_job_loop_runs_jobs_concurrently_and_drains_on_shutdown() {
+    let (completed, max_running) = run_fake_jobs(2).await;
+    assert_eq!(completed, vec!["job-1", "job-2"]);
+    assert_eq!(max_running, 2);
+}
+
+/// Answers queue commands with scripted results, then with an empty queue
+struct FlakyConnection {
+    replies: std::collections::VecDeque<redis::RedisResult<redis::Value>>,
+}
+
+impl redis::aio::ConnectionLike for FlakyConnection {
+    fn req_packed_command<'a>(&'a mut self, _cmd: &'a redis::Cmd) -> redis::RedisFuture<'a, redis::Value> {
+        let reply = self.replies.pop_front().unwrap_or(Ok(redis::Value::Nil));
+        Box::pin(async move { reply })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        _cmd: &'a redis::Pipeline,
+        _offset: usize,
+        _count: usize,
+    ) -> redis::RedisFuture<'a, Vec<redis::Value>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+}
+
+/// Hands out the scripted connections in order; None is a failed attempt
+struct FlakyConnector {
+    connections: std::sync::Mutex<std::collections::VecDeque<Option<FlakyConnection>>>,
+    attempts: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl job_queue::Connector for FlakyConnector {
+    type Connection = FlakyConnection;
+
+    async fn connect(&self) -> Result<FlakyConnection> {
+        self.attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.connections.lock().unwrap().pop_front().flatten().context("Redis is down")
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_job_loop_reconnects_after_redis_drops_the_connection() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    let job = |id: &str| Ok(redis::Value::Data(serde_json::to_vec(&fake_job(id)).unwrap()));
+    let connection = |replies: Vec<redis::RedisResult<redis::Value>>| FlakyConnection { replies: replies.into() };
+    let dropped = redis::RedisError::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+
+    // Redis restarts after the first job and is still down on the first reconnect
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let connector = FlakyConnector {
+        connections: Mutex::new(vec![None, Some(connection(vec![job("job-2")]))].into()),
+        attempts: attempts.clone(),
+    };
+    let first = connection(vec![job("job-1"), Err(dropped)]);
+    let queue = Arc::new(tokio::sync::Mutex::new(job_queue::QueueConnection::new(connector, first)));
+    let selector = Arc::new(tokio::sync::Mutex::new(job_queue::QueueSelector::new(vec![job_queue::QUEUE_KEY.to_string()], 0)));
+    let shutdown = CancellationToken::new();
+    let completed = Arc::new(Mutex::new(Vec::new()));
+
+    run_job_loop(
+        1,
+        &shutdown,
+        || {
+            let (queue, selector, shutdown, completed) = (queue.clone(), selector.clone(), shutdown.clone(), completed.clone());
+            async move {
+                let job = pop_job_reconnecting(&mut *queue.lock().await, &mut *selector.lock().await, "w1").await;
+                if completed.lock().unwrap().len() == 2 {
+                    shutdown.cancel();
+                }
+                job
+            }
+        },
+        |job| {
+            let completed = completed.clone();
+            async move { completed.lock().unwrap().push(job.job_id) }
+        },
+    )
+    .await;
+
+    assert_eq!(*completed.lock().unwrap(), vec!["job-1", "job-2"]);
+    // Reconnected until it worked, then kept the new connection
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_job_loop_respects_concurrency_limit() {
+    let (completed, max_running) = run_fake_jobs(1).await;
+    assert_eq!(completed, vec!["job-1", "job-2"]);
+    assert_eq!(max_running, 1);
+}
+
+#[test]
+fn test_count_lines_edge_cases() {
+    use super::parsers::count_lines;
+
+    assert_eq!(count_lines(""), 0);
+    assert_eq!(count_lines("fn main() {}"), 1);
+    assert_eq!(count_lines("a\nb\n"), 2);
+    assert_eq!(count_lines("a\r\nb"), 2);
+    assert_eq!(count_lines("\n\n"), 2);
+}
+
+#[test]
+fn test_language_breakdown_and_complexity_score() {
+    use super::parsers::{ClassInfo, FunctionInfo, ParsedFile};
+
+    fn function(name: &str, end_line: usize) -> FunctionInfo {
+        FunctionInfo {
+            name: name.to_string(),
+            params: vec![],
+            return_type: None,
+            calls: vec![],
+            start_line: 1,
+            end_line,
+            complexity: 1,
+            body_hash: String::new(),
+            decorators: Vec::new(),
+            visibility: None,
+        }
+    }
+
+    fn file(path: &str, language: &str, functions: Vec<FunctionInfo>, classes: Vec<ClassInfo>) -> ParsedFile {
+        ParsedFile {
+            path: path.to_string(),
+            language: language.to_string(),
+            functions,
+            classes,
+            interfaces: vec![],
+            imports: vec![],
+            data_tables: vec![],
+            service_calls: vec![],
+            sql_queries: vec![],
+            env_vars: vec![],
+            is_test: false,
+            generated: false,
+            parse_failed: false,
+            rendered_components: vec![],
+            reexports: vec![],
+            line_count: 0,
+        }
+    }
+
+    let mut parsed_files = vec![
+        file("a.ts", "typescript", vec![function("a", 10), function("b", 25)], vec![]),
+        file(
+            "b.ts",
+            "typescript",
+            vec![],
+            vec![ClassInfo {
+                name: "Widget".to_string(),
+                kind: None,
+                inheritances: vec![],
+                methods: vec![function("render", 30)],
+                start_line: 1,
+                end_line: 40,
+                body_hash: String::new(),
+                decorators: Vec::new(),
+                visibility: None,
+            }],
+        ),
+        file("c.py", "python", vec![function("main", 5)], vec![]),
+        file("d.py", "python", vec![], vec![]),
+    ];
+
+    // Lines count whole files, not just up to the last symbol's end
+    parsed_files[0].line_count = 40;
+    parsed_files[1].line_count = 2;
+    parsed_files[2].line_count = 7;
+    let languages = super::language_breakdown(&parsed_files);
+    assert_eq!(
+        languages,
+        json!({
+            "python": {"files": 2, "functions": 1, "classes": 0, "lines": 7, "percentage": 50.0},
+            "typescript": {"files": 2, "functions": 3, "classes": 1, "lines": 42, "percentage": 50.0},
+        })
+    );
+
+    assert_eq!(super::complexity_score(&parsed_files), 1.0);
+    assert_eq!(super::complexity_score(&[]), 0.0);
+    assert_eq!(super::language_breakdown(&[]), json!({}));
+
+    assert_eq!(
+        serde_json::to_value(super::loc_by_language(&parsed_files)).unwrap(),
+        json!({"python": 7, "typescript": 42})
+    );
+    assert!(super::loc_by_language(&[]).is_empty());
+
+    parsed_files[2].functions[0].complexity = 5;
+    assert_eq!(super::function_complexity(&parsed_files), json!({"average": 2.0, "max": 5}));
+    assert_eq!(super::function_complexity(&[]), json!({"average": 0.0, "max": 0}));
+
+    // 7 test lines against 42 production lines
+    assert_eq!(super::test_to_code_ratio(&parsed_files), 0.0);
+    parsed_files[2].is_test = true;
+    assert_eq!(super::test_to_code_ratio(&parsed_files), 0.17);
+    assert_eq!(super::test_to_code_ratio(&[]), 0.0);
+}
+
+/// Local repository with five commits on `main` and a `feature` branch one
+/// commit ahead, for clone tests.
+fn clone_fixture_repo() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("clone-fixture-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let repo = git2::Repository::init(&dir).unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+    let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+
+    let commit = |message: &str, file: &str, content: &str| {
+        std::fs::write(dir.join(file), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(file)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap()
+    };
+
+    for i in 0..5 {
+        commit(&format!("main {}", i), &format!("mod_{}.py", i), &format!("def f{}():\n    pass\n", i));
+    }
+    let main_head = repo.head().unwrap().peel_to_commit().unwrap();
+    repo.branch("feature", &main_head, false).unwrap();
+    repo.set_head("refs/heads/feature").unwrap();
+    commit("feature work", "feature.py", "def feature():\n    pass\n");
+    repo.set_head("refs/heads/main").unwrap();
+
+    dir
+}
+
+#[test]
+fn test_clone_depth_option_overrides_default() {
+    let mut options = HashMap::new();
+    assert_eq!(super::clone_depth(&None, 50), 50);
+    options.insert("clone_depth".to_string(), "0".to_string());
+    assert_eq!(super::clone_depth(&Some(options.clone()), 50), 0);
+    options.insert("clone_depth".to_string(), "5".to_string());
+    assert_eq!(super::clone_depth(&Some(options.clone()), 0), 5);
+    options.insert("clone_depth".to_string(), "deep".to_string());
+    assert_eq!(super::clone_depth(&Some(options), 7), 7);
+}
+
+#[test]
+fn test_git_history_limits_from_options() {
+    let now = chrono::Utc::now();
+    assert_eq!(super::git_history_limits(&None, now), (None, None));
+
+    let options = HashMap::from([
+        ("git_history_days".to_string(), "30".to_string()),
+        ("git_max_commits".to_string(), "500".to_string()),
+    ]);
+    assert_eq!(
+        super::git_history_limits(&Some(options), now),
+        (Some(now - chrono::Duration::days(30)), Some(500))
+    );
+
+    let options = HashMap::from([
+        ("git_history_days".to_string(), "0".to_string()),
+        ("git_max_commits".to_string(), "all".to_string()),
+    ]);
+    assert_eq!(super::git_history_limits(&Some(options), now), (None, None));
+}
+
+#[test]
+fn test_shallow_clone_single_branch() {
+    let fixture = clone_fixture_repo();
+    let url = format!("file://{}", fixture.display());
+
+    // libgit2's local transport ignores the depth, so only the single-branch
+    // fetch is observable here
+    let clone = super::clone_repository(&url, "feature", &None, 2, &Default::default(), None).expect("Shallow clone failed");
+    let repo = git2::Repository::open(&clone.path).unwrap();
+    assert_eq!(repo.head().unwrap().shorthand(), Some("feature"));
+    assert!(clone.path.join("feature.py").exists());
+    assert!(repo.find_branch("origin/feature", git2::BranchType::Remote).is_ok());
+    assert!(repo.find_branch("origin/main", git2::BranchType::Remote).is_err());
+
+    drop(clone);
+    let _ = std::fs::remove_dir_all(&fixture);
+}
+
+#[test]
+fn test_shallow_clone_missing_branch_falls_back_to_main() {
+    let fixture = clone_fixture_repo();
+    let url = format!("file://{}", fixture.display());
+
+    let clone = super::clone_repository(&url, "does-not-exist", &None, 1, &Default::default(), None).expect("Fallback clone failed");
+    let repo = git2::Repository::open(&clone.path).unwrap();
+    assert_eq!(repo.head().unwrap().shorthand(), Some("main"));
+    assert!(clone.path.join("mod_4.py").exists());
+    assert!(!clone.path.join("feature.py").exists());
+    assert_eq!(clone.fallback_branch.as_deref(), Some("main"));
+
+    let full = super::clone_repository(&url, "main", &None, 0, &Default::default(), None).expect("Full clone failed");
+    assert!(!git2::Repository::open(&full.path).unwrap().is_shallow());
+    assert_eq!(full.fallback_branch, None);
+
+    drop(clone);
+    drop(full);
+    let _ = std::fs::remove_dir_all(&fixture);
+}
+
+#[test]
+fn test_commit_sha_checks_out_the_older_commit() {
+    use crate::parsers::python_parser::PythonParser;
+    use crate::parsers::LanguageParser;
+
+    let dir = std::env::temp_dir().join(format!("commit-sha-fixture-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let repo = git2::Repository::init(&dir).unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+    let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+    let commit = |content: &str| {
+        std::fs::write(dir.join("app.py"), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("app.py")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, "update", &tree, &parents).unwrap()
+    };
+    let older = commit("def handle_v1():\n    pass\n");
+    let newer = commit("def handle_v2():\n    pass\n");
+
+    let clone = super::clone_repository(&format!("file://{}", dir.display()), "main", &None, 0, &Default::default(), None).unwrap();
+    assert_eq!(super::checkout_analyzed_commit(&clone.path, None, CommitCheckout::Force).unwrap(), Some(newer.to_string()));
+
+    let analyzed = super::checkout_analyzed_commit(&clone.path, Some(&older.to_string()[..10]), CommitCheckout::Force).unwrap();
+    assert_eq!(analyzed, Some(older.to_string()));
+    let content = std::fs::read_to_string(clone.path.join("app.py")).unwrap();
+    let parsed = PythonParser::new().unwrap().parse_file(&PathBuf::from("app.py"), &content).unwrap();
+    let functions: Vec<&str> = parsed.functions.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(functions, vec!["handle_v1"]);
+
+    // History is walked from the checked out commit
+    let contributions = git_analyzer::GitAnalyzer::new(&clone.path).unwrap().analyze_contributions().unwrap();
+    assert_eq!(contributions.commits.len(), 1);
+
+    let missing = super::checkout_analyzed_commit(&clone.path, Some("0123456789abcdef0123456789abcdef01234567"), CommitCheckout::Force);
+    assert!(missing.unwrap_err().to_string().contains("0123456789abcdef"));
+    let not_a_repo = std::env::temp_dir();
+    assert_eq!(super::checkout_analyzed_commit(&not_a_repo, None, CommitCheckout::Refused).unwrap(), None);
+    assert!(super::checkout_analyzed_commit(&not_a_repo, Some("abc123"), CommitCheckout::Force).is_err());
+
+    drop(clone);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_clone_progress_scale() {
+    assert_eq!(super::clone_progress(0, 0, 0), 0);
+    assert_eq!(super::clone_progress(0, 0, 40), 0);
+    assert_eq!(super::clone_progress(40, 0, 40), 12);
+    assert_eq!(super::clone_progress(40, 40, 40), super::CLONE_PROGRESS_END);
+}
+
+#[test]
+fn test_storage_progress_band_and_throttle() {
+    let plan = super::Phases::default().progress_plan(super::CLONE_PROGRESS_END);
+    assert_eq!(plan.storage(0.0), 75);
+    assert_eq!(plan.storage(0.5), 83);
+    assert_eq!(plan.storage(1.0), 90);
+    assert_eq!(plan.storage(1.5), 90);
+
+    let start = std::time::Instant::now();
+    let later = start + super::STORAGE_PROGRESS_INTERVAL;
+    assert!(super::should_send_progress(None, start, 76));
+    // Too soon after the last update
+    assert!(!super::should_send_progress(Some((start, 76)), start, 80));
+    assert!(super::should_send_progress(Some((start, 76)), later, 80));
+    // Never repeats or goes backwards
+    assert!(!super::should_send_progress(Some((start, 80)), later, 80));
+    assert!(!super::should_send_progress(Some((start, 80)), later, 78));
+}
+
+#[tokio::test]
+async fn test_storage_progress_forwards_throttled_values() {
+    use super::neo4j_storage::ProgressReporter;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(100);
+    let reporter = super::StorageProgress::new(tx, super::Phases::default().progress_plan(super::CLONE_PROGRESS_END));
+    for step in 1..=4 {
+        reporter.report(step as f64 / 4.0);
+    }
+    // Reports in quick succession collapse into the first one
+    assert_eq!(rx.try_recv().unwrap(), 79);
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn test_clone_reports_transfer_progress() {
+    let fixture = clone_fixture_repo();
+    let url = format!("file://{}", fixture.display());
+    let (progress_tx, mut progress_rx) = tokio::sync::mpsc::channel(100);
+
+    let clone = super::clone_repository(&url, "main", &None, 0, &Default::default(), Some(progress_tx)).expect("Clone failed");
+    let mut updates = Vec::new();
+    while let Ok(progress) = progress_rx.try_recv() {
+        updates.push(progress);
+    }
+
+    assert!(!updates.is_empty());
+    assert!(updates.windows(2).all(|w| w[0] < w[1]), "{:?}", updates);
+    assert_eq!(updates.last(), Some(&super::CLONE_PROGRESS_END));
+
+    drop(clone);
+    let _ = std::fs::remove_dir_all(&fixture);
+}
+
+#[test]
+fn test_local_repo_path_option() {
+    let mut options = HashMap::new();
+    options.insert("local_path".to_string(), " /builds/app ".to_string());
+    assert_eq!(
+        super::local_repo_path("https://github.com/org/app", &Some(options)),
+        Some(PathBuf::from("/builds/app"))
+    );
+
+    assert_eq!(
+        super::local_repo_path("file:///workspace/app", &None),
+        Some(PathBuf::from("/workspace/app"))
+    );
+
+    let mut blank = HashMap::new();
+    blank.insert("local_path".to_string(), "".to_string());
+    assert_eq!(super::local_repo_path("https://github.com/org/app", &Some(blank)), None);
+    assert_eq!(super::local_repo_path("https://github.com/org/app", &None), None);
+}
+
+#[test]
+fn test_local_paths_are_confined_to_the_workspace_root() {
+    use std::os::unix::fs::symlink;
+
+    let base = std::env::temp_dir().join(format!("workspace-root-fixture-{}", uuid::Uuid::new_v4()));
+    let root = base.join("builds");
+    std::fs::create_dir_all(root.join("app")).unwrap();
+    std::fs::create_dir_all(base.join("secrets")).unwrap();
+    symlink(base.join("secrets"), root.join("escape")).unwrap();
+    let root_path = root.canonicalize().unwrap();
+
+    assert_eq!(super::confine_local_path(&root.join("app"), Some(&root)).unwrap(), root_path.join("app"));
+    // Relative segments are resolved before the check
+    assert_eq!(super::confine_local_path(&root.join("app/../app"), Some(&root)).unwrap(), root_path.join("app"));
+
+    // Without a root no local path is allowed
+    let disabled = super::confine_local_path(&root.join("app"), None).unwrap_err();
+    assert!(disabled.to_string().contains("set LOCAL_WORKSPACE_ROOT"));
+    // Paths leaving the root, directly, through `..` or through a symlink
+    for outside in [base.join("secrets"), root.join("../secrets"), root.join("escape"), PathBuf::from("/")] {
+        let refused = super::confine_local_path(&outside, Some(&root)).unwrap_err();
+        assert!(refused.to_string().contains("is outside LOCAL_WORKSPACE_ROOT"), "{:?}: {}", outside, refused);
+    }
+    let missing = super::confine_local_path(&root.join("missing"), Some(&root)).unwrap_err();
+    assert!(missing.to_string().contains("does not exist"));
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
+#[test]
+fn test_local_repository_is_left_in_place() {
+    let fixture = clone_fixture_repo();
+
+    let local = super::open_local_repository(&fixture, "feature", &None).expect("Open failed");
+    assert_eq!(local.path, fixture);
+    // Without local_checkout the workspace stays on its current branch
+    let repo = git2::Repository::open(&fixture).unwrap();
+    assert_eq!(repo.head().unwrap().shorthand(), Some("main"));
+    drop(local);
+    assert!(fixture.join("mod_0.py").exists());
+
+    // The fixture leaves feature.py behind in the main worktree
+    std::fs::remove_file(fixture.join("feature.py")).unwrap();
+    let mut options = HashMap::new();
+    options.insert("local_checkout".to_string(), "true".to_string());
+    let local = super::open_local_repository(&fixture, "feature", &Some(options)).expect("Checkout failed");
+    assert_eq!(repo.head().unwrap().shorthand(), Some("feature"));
+    assert!(fixture.join("feature.py").exists());
+    drop(local);
+    assert!(fixture.exists());
+
+    let missing = super::open_local_repository(&fixture.join("missing"), "main", &None);
+    assert!(missing.err().unwrap().to_string().contains("does not exist or is not a directory"));
+    assert!(super::open_local_repository(&fixture.join("mod_0.py"), "main", &None).is_err());
+
+    let _ = std::fs::remove_dir_all(&fixture);
+}
+
+#[test]
+fn test_commit_checkout_leaves_dirty_local_workspace_untouched() {
+    let dir = std::env::temp_dir().join(format!("dirty-workspace-fixture-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let repo = git2::Repository::init(&dir).unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+    let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+    let commit = |content: &str| {
+        std::fs::write(dir.join("app.py"), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("app.py")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, "update", &tree, &parents).unwrap()
+    };
+    let older = commit("def handle_v1():\n    pass\n");
+    let newer = commit("def handle_v2():\n    pass\n");
+    let edited = "def handle_v3():\n    pass\n";
+    std::fs::write(dir.join("app.py"), edited).unwrap();
+    let untouched = || {
+        assert_eq!(std::fs::read_to_string(dir.join("app.py")).unwrap(), edited);
+        let head = repo.head().unwrap();
+        assert_eq!((head.shorthand(), head.target()), (Some("main"), Some(newer)));
+    };
+
+    // Without local_checkout the job's commit is refused
+    let local = super::open_local_repository(&dir, "main", &None).unwrap();
+    let checkout = CommitCheckout::for_repo(&local, &None);
+    assert_eq!(checkout, CommitCheckout::Refused);
+    let refused = super::checkout_analyzed_commit(&local.path, Some(&older.to_string()), checkout);
+    assert!(refused.unwrap_err().to_string().contains("local_checkout"));
+    untouched();
+    // Analyzing HEAD as it is still works
+    assert_eq!(super::checkout_analyzed_commit(&local.path, None, checkout).unwrap(), Some(newer.to_string()));
+
+    // With it, uncommitted changes still win over the checkout
+    let options = Some(HashMap::from([("local_checkout".to_string(), "true".to_string())]));
+    let checkout = CommitCheckout::for_repo(&local, &options);
+    assert_eq!(checkout, CommitCheckout::Safe);
+    assert!(super::checkout_analyzed_commit(&local.path, Some(&older.to_string()), checkout).is_err());
+    untouched();
+    // and a clean workspace is switched
+    std::fs::write(dir.join("app.py"), "def handle_v2():\n    pass\n").unwrap();
+    let analyzed = super::checkout_analyzed_commit(&local.path, Some(&older.to_string()), checkout).unwrap();
+    assert_eq!(analyzed, Some(older.to_string()));
+    assert_eq!(std::fs::read_to_string(dir.join("app.py")).unwrap(), "def handle_v1():\n    pass\n");
+
+    drop(local);
+    assert!(dir.join("app.py").exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_extract_renamed_files() {
+    let mut options = HashMap::new();
+    options.insert(
+        "renamed_files".to_string(),
+        r#"[{"from": "src/a.ts", "to": "src/b.ts"}, {"from": "./lib\\x.py", "to": "lib/y.py"}]"#.to_string(),
+    );
+    assert_eq!(
+        super::extract_renamed_files(&Some(options)).unwrap(),
+        vec![
+            ("src/a.ts".to_string(), "src/b.ts".to_string()),
+            ("lib/x.py".to_string(), "lib/y.py".to_string()),
+        ]
+    );
+
+    let mut malformed = HashMap::new();
+    malformed.insert("renamed_files".to_string(), r#"["src/a.ts"]"#.to_string());
+    assert!(super::extract_renamed_files(&Some(malformed)).unwrap().is_empty());
+    assert!(super::extract_renamed_files(&None).unwrap().is_empty());
+
+    let mut escaping = HashMap::new();
+    escaping.insert("renamed_files".to_string(), r#"[{"from": "src/a.ts", "to": "../../a.ts"}]"#.to_string());
+    assert!(super::extract_renamed_files(&Some(escaping)).is_err());
+}
+
+#[test]
+fn test_webhook_file_lists_are_normalized() {
+    let options = |changed: &str, removed: &str| {
+        Some(HashMap::from([
+            ("changed_files".to_string(), changed.to_string()),
+            ("removed_files".to_string(), removed.to_string()),
+        ]))
+    };
+    let (changed, removed) =
+        super::extract_webhook_changes(&options(r#"["./src/a.py", "src\\web\\b.ts"]"#, r#"["lib//old.go"]"#)).unwrap();
+    assert_eq!(changed, vec!["src/a.py", "src/web/b.ts"]);
+    assert_eq!(removed, vec!["lib/old.go"]);
+
+    // The lists come from outside: a path out of the checkout fails the job
+    let err = super::extract_webhook_changes(&options(r#"["src/../../etc/passwd"]"#, "[]")).unwrap_err();
+    assert!(format!("{:#}", err).contains("changed_files"), "{:#}", err);
+    assert!(super::extract_webhook_changes(&options("[]", r#"["/etc/passwd"]"#)).is_err());
+    assert!(super::extract_webhook_changes(&options("[]", r#"["C:\\repo\\a.py"]"#)).is_err());
+}
+
+#[test]
+fn test_subset_parse_normalizes_and_confines_paths() {
+    use std::fs;
+    use uuid::Uuid;
+
+    let root = std::env::temp_dir().join(format!("test-repo-subset-paths-{}", Uuid::new_v4()));
+    let repo = root.join("repo");
+    fs::create_dir_all(repo.join("src/web")).expect("Failed to create temp dir");
+    fs::write(repo.join("src/a.py"), "def a():\n    pass\n").unwrap();
+    fs::write(repo.join("src/web/b.py"), "def b():\n    pass\n").unwrap();
+    fs::write(root.join("outside.py"), "def secret():\n    pass\n").unwrap();
+
+    let changed = vec!["./src/a.py".to_string(), "src\\web\\b.py".to_string(), "../outside.py".to_string()];
+    let (parsed, stats) =
+        super::parse_repository_subset(&repo, &changed, 1, &FileLimits::default(), &PathFilter::default()).unwrap();
+    let paths: Vec<&str> = parsed.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, vec!["src/a.py", "src/web/b.py"]);
+    assert_eq!(stats.skipped_files.len(), 1);
+    assert_eq!(stats.skipped_files[0].path, "../outside.py");
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_parse_limits_skip_large_and_binary_files() {
+    use std::fs;
+    use uuid::Uuid;
+
+    let temp_dir = std::env::temp_dir().join(format!("test-repo-limits-{}", Uuid::new_v4()));
+    fs::create_dir_all(temp_dir.join("src")).expect("Failed to create temp dir");
+    fs::write(temp_dir.join("src/a.py"), "def a():\n    pass\n").unwrap();
+    fs::write(temp_dir.join("src/b.py"), "def b():\n    pass\n").unwrap();
+    fs::write(temp_dir.join("src/c.py"), "def c():\n    pass\n").unwrap();
+    fs::write(temp_dir.join("src/bundle.js"), format!("var x = '{}';\n", "a".repeat(4096))).unwrap();
+    fs::write(temp_dir.join("src/blob.ts"), [0x7f, b'E', b'L', b'F', 0, 0, 0, 1]).unwrap();
+
+    let limits = FileLimits {
+        max_file_size_bytes: 1024,
+        max_files: 100,
+        ..Default::default()
+    };
+    let (parsed, stats) = super::parse_repository(&temp_dir, 2, &limits, &PathFilter::default()).expect("Parse failed");
+    let paths: Vec<&str> = parsed.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, vec!["src/a.py", "src/b.py", "src/c.py"]);
+    assert_eq!(stats.skipped_large_files, 1);
+    assert_eq!(stats.skipped_binary_files, 1);
+    assert!(!stats.truncated);
+
+    // Candidates are capped in path order, before the binary file is read
+    let capped = FileLimits { max_files: 2, ..limits };
+    let (parsed, stats) = super::parse_repository(&temp_dir, 1, &capped, &PathFilter::default()).expect("Parse failed");
+    let paths: Vec<&str> = parsed.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, vec!["src/a.py", "src/b.py"]);
+    assert_eq!(stats.skipped_binary_files, 0);
+    assert!(stats.truncated);
+
+    let changed = vec!["src/bundle.js".to_string(), "src/a.py".to_string()];
+    let (parsed, stats) = super::parse_repository_subset(&temp_dir, &changed, 1, &limits, &PathFilter::default()).expect("Parse failed");
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(stats.skipped_large_files, 1);
+
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_generated_files_are_marked_or_skipped() {
+    use std::fs;
+    use uuid::Uuid;
+
+    let temp_dir = std::env::temp_dir().join(format!("test-repo-generated-{}", Uuid::new_v4()));
+    fs::create_dir_all(temp_dir.join("api")).expect("Failed to create temp dir");
+    fs::write(temp_dir.join("api/users.py"), "def list_users():\n    pass\n").unwrap();
+    fs::write(temp_dir.join("api/users_pb2.py"), "def _descriptor():\n    pass\n").unwrap();
+    fs::write(
+        temp_dir.join("api/users.go"),
+        "// Code generated by protoc-gen-go. DO NOT EDIT.\npackage api\n\nfunc Register() {}\n",
+    )
+    .unwrap();
+
+    let (parsed, stats) = super::parse_repository(&temp_dir, 2, &FileLimits::default(), &PathFilter::default()).expect("Parse failed");
+    let marked: Vec<(&str, bool)> = parsed.iter().map(|f| (f.path.as_str(), f.generated)).collect();
+    assert_eq!(marked, vec![("api/users.go", true), ("api/users.py", false), ("api/users_pb2.py", true)]);
+    assert_eq!(stats.skipped_generated_files, 0);
+    // Generated functions don't weigh on the complexity summary
+    assert_eq!(super::complexity_score(&parsed), 1.0);
+
+    let limits = FileLimits { skip_generated: true, ..Default::default() };
+    let (parsed, stats) = super::parse_repository(&temp_dir, 2, &limits, &PathFilter::default()).expect("Parse failed");
+    let paths: Vec<&str> = parsed.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, vec!["api/users.py"]);
+    assert_eq!(stats.skipped_generated_files, 2);
+    assert!(stats.skipped_files.iter().all(|skipped| skipped.reason == "generated"));
+
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_walk_handles_non_utf8_and_symlinks() {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use uuid::Uuid;
+
+    let temp_dir = std::env::temp_dir().join(format!("test-repo-walk-{}", Uuid::new_v4()));
+    let outside = std::env::temp_dir().join(format!("test-repo-outside-{}", Uuid::new_v4()));
+    fs::create_dir_all(temp_dir.join("src")).expect("Failed to create temp dir");
+    fs::create_dir_all(&outside).expect("Failed to create outside dir");
+    fs::write(outside.join("secret.py"), "def leaked():\n    pass\n").unwrap();
+    // Latin-1 comment, invalid as UTF-8
+    fs::write(temp_dir.join("src/legacy.py"), b"# caf\xe9\ndef legacy():\n    pass\n").unwrap();
+    symlink(&outside, temp_dir.join("src/linked")).unwrap();
+    symlink(&temp_dir, temp_dir.join("src/loop")).unwrap();
+    symlink(temp_dir.join("src/legacy.py"), temp_dir.join("src/alias.py")).unwrap();
+
+    let (parsed, stats) = super::parse_repository(&temp_dir, 2, &FileLimits::default(), &PathFilter::default()).expect("Parse failed");
+    let paths: Vec<&str> = parsed.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, vec!["src/legacy.py"]);
+    assert_eq!(parsed[0].functions[0].name, "legacy");
+
+    let mut skipped: Vec<(&str, &str)> = stats
+        .skipped_files
+        .iter()
+        .map(|s| (s.path.as_str(), s.reason.as_str()))
+        .collect();
+    skipped.sort();
+    assert_eq!(
+        skipped,
+        vec![("src/alias.py", "symlink"), ("src/linked", "symlink"), ("src/loop", "symlink")]
+    );
+
+    let changed = vec!["src/linked/secret.py".to_string(), "src/alias.py".to_string(), "src/legacy.py".to_string()];
+    let (parsed, stats) = super::parse_repository_subset(&temp_dir, &changed, 1, &FileLimits::default(), &PathFilter::default())
+        .expect("Parse failed");
+    assert_eq!(parsed.len(), 1);
+    let skipped: Vec<(&str, &str)> = stats
+        .skipped_files
+        .iter()
+        .map(|s| (s.path.as_str(), s.reason.as_str()))
+        .collect();
+    assert_eq!(skipped, vec![("src/linked/secret.py", "outside repository"), ("src/alias.py", "symlink")]);
+
+    let _ = fs::remove_dir_all(&temp_dir);
+    let _ = fs::remove_dir_all(&outside);
+}
+
+#[test]
+fn test_graph_patch_lists_removed_node_and_edge_ids() {
+    use super::neo4j_storage::ExistingIds;
+    use super::parsers::{FunctionInfo, ParsedFile};
+
+    let function = |name: &str, calls: Vec<&str>| FunctionInfo {
+        name: name.to_string(),
+        params: vec![],
+        return_type: None,
+        calls: calls.into_iter().map(String::from).collect(),
+        start_line: 1,
+        end_line: 5,
+        complexity: 1,
+        body_hash: String::new(),
+        decorators: Vec::new(),
+        visibility: None,
+    };
+    // api.py used to define `legacy`, called by `run`, and import `requests`
+    let parsed_files = vec![ParsedFile {
+        path: "api.py".to_string(),
+        language: "python".to_string(),
+        functions: vec![function("run", vec!["fetch"]), function("fetch", vec![])],
+        classes: vec![],
+        interfaces: vec![],
+        imports: vec![],
+        data_tables: vec![],
+        service_calls: vec![],
+        sql_queries: vec![],
+        env_vars: vec![],
+        is_test: false,
+        generated: false,
+        parse_failed: false,
+        rendered_components: vec![],
+        reexports: vec![],
+        line_count: 10,
+    }];
+    let symbol_table = graph_builder::SymbolTable::from_parsed_files(&parsed_files);
+    let dep_graph = graph_builder::DependencyGraph::from_parsed_files(&parsed_files, &symbol_table);
+    let previous = ExistingIds {
+        nodes: vec!["api.py".into(), "api.py::legacy".into(), "api.py::run".into(), "old.py".into()],
+        edges: vec![
+            "calls:api.py::run->api.py::legacy".into(),
+            "defines:api.py->api.py::legacy".into(),
+            "defines:api.py->api.py::run".into(),
+            "imports:api.py->requests".into(),
+        ],
+        ..Default::default()
+    };
+
+    let changed = vec!["./api.py".to_string()];
+    let removed = vec![".\\old.py".to_string()];
+    let patch = build_graph_patch(&parsed_files, &dep_graph, &[], &changed, &removed, Some(&previous));
+    // File lists in the form of the node ids
+    assert_eq!((patch.changed_files, patch.removed_files), (vec!["api.py".to_string()], vec!["old.py".to_string()]));
+    assert_eq!(patch.removed_nodes, vec!["api.py::legacy", "old.py"]);
+    assert_eq!(
+        patch.removed_edges,
+        vec!["calls:api.py::run->api.py::legacy", "defines:api.py->api.py::legacy", "imports:api.py->requests"]
+    );
+    assert!(patch.edges.iter().any(|edge| edge.id == "calls:api.py::run->api.py::fetch"));
+
+    // Full runs don't diff
+    let patch = build_graph_patch(&parsed_files, &dep_graph, &[], &changed, &removed, None);
+    assert!(patch.removed_nodes.is_empty() && patch.removed_edges.is_empty());
+}
+
+#[test]
+fn test_unchanged_functions_survive_reingest_by_body_hash() {
+    use super::neo4j_storage::ExistingIds;
+    use super::parsers::ParserSet;
+
+    let parsers = ParserSet::new().unwrap();
+    let parse = |content: &str| {
+        let path = std::path::PathBuf::from("billing.py");
+        vec![parsers.for_extension("py").unwrap().parse_file(&path, content).unwrap()]
+    };
+    let before = parse(
+        "class Invoice:\n    def total(self):\n        return sum(self.lines)\n\n\
+         def charge(invoice):\n    return invoice.total()\n\n\
+         def refund(invoice):\n    return 0\n",
+    );
+    // A comment shifts everything down a line and only `refund` changes
+    let after = parse(
+        "# billing\nclass Invoice:\n    def total(self):\n        return sum(self.lines)\n\n\
+         def charge(invoice):\n    return invoice.total()\n\n\
+         def refund(invoice):\n    return invoice.total()\n",
+    );
+
+    let hashes = |files: &[ParsedFile]| -> HashMap<String, String> {
+        let file = &files[0];
+        let mut hashes: HashMap<String, String> =
+            file.functions.iter().map(|f| (format!("billing.py::{}", f.name), f.body_hash.clone())).collect();
+        for class in &file.classes {
+            hashes.insert(format!("billing.py::{}", class.name), class.body_hash.clone());
+            for method in &class.methods {
+                hashes.insert(format!("billing.py::{}::{}", class.name, method.name), method.body_hash.clone());
+            }
+        }
+        hashes
+    };
+    let (old_hashes, new_hashes) = (hashes(&before), hashes(&after));
+    assert_eq!(old_hashes["billing.py::charge"], new_hashes["billing.py::charge"]);
+    assert_eq!(old_hashes["billing.py::Invoice::total"], new_hashes["billing.py::Invoice::total"]);
+    assert_ne!(old_hashes["billing.py::refund"], new_hashes["billing.py::refund"]);
+    assert_eq!(after[0].functions[0].start_line, before[0].functions[0].start_line + 1);
+
+    // What a first ingest stored, as read back before the second one
+    let graph_of = |files: &[ParsedFile]| {
+        let symbol_table = graph_builder::SymbolTable::from_parsed_files(files);
+        graph_builder::DependencyGraph::from_parsed_files(files, &symbol_table)
+    };
+    let first = build_graph_patch(&before, &graph_of(&before), &[], &[], &[], None);
+    let previous = ExistingIds {
+        nodes: first.nodes.iter().map(|node| node.id.clone()).collect(),
+        edges: first.edges.iter().map(|edge| edge.id.clone()).collect(),
+        body_hashes: old_hashes,
+    };
+
+    let changed = vec!["billing.py".to_string()];
+    let patch = build_graph_patch(&after, &graph_of(&after), &[], &changed, &[], Some(&previous));
+    let ids: Vec<&str> = patch.nodes.iter().map(|node| node.id.as_str()).collect();
+    assert!(ids.contains(&"billing.py"));
+    assert!(ids.contains(&"billing.py::refund"));
+    for unchanged in ["billing.py::charge", "billing.py::Invoice", "billing.py::Invoice::total"] {
+        assert!(!ids.contains(&unchanged), "{} should not be in the patch", unchanged);
+        assert!(!patch.edges.iter().any(|edge| edge.source == unchanged));
+    }
+    assert!(!patch.edges.iter().any(|edge| edge.id == "defines:billing.py->billing.py::charge"));
+    assert!(patch.removed_nodes.is_empty());
+    assert!(patch.edges.iter().any(|edge| edge.id == "calls:billing.py::refund->billing.py::Invoice::total"));
+}
+
+#[test]
+fn test_graph_patch_nests_files_under_packages() {
+    let file = |path: &str| ParsedFile {
+        path: path.to_string(),
+        language: "python".to_string(),
+        functions: vec![],
+        classes: vec![],
+        interfaces: vec![],
+        imports: vec![],
+        data_tables: vec![],
+        service_calls: vec![],
+        sql_queries: vec![],
+        env_vars: vec![],
+        is_test: false,
+        generated: false,
+        parse_failed: false,
+        rendered_components: vec![],
+        reexports: vec![],
+        line_count: 1,
+    };
+    let parsed_files = vec![file("api/__init__.py"), file("api/v1/__init__.py"), file("api/v1/routes.py"), file("manage.py")];
+    let packages = graph_builder::build_package_hierarchy(parsed_files.iter().map(|f| f.path.as_str()), |_| false);
+
+    let patch = build_graph_patch(&parsed_files, &graph_builder::DependencyGraph::default(), &packages, &[], &[], None);
+    let node = |id: &str| patch.nodes.iter().find(|node| node.id == id).unwrap();
+    assert_eq!(node("package:api").parent_id, None);
+    assert_eq!(node("package:api").node_type, "package");
+    assert_eq!(node("package:api/v1").parent_id.as_deref(), Some("package:api"));
+    assert_eq!(node("package:api/v1").label, "v1");
+    assert_eq!(node("api/v1/routes.py").parent_id.as_deref(), Some("package:api/v1"));
+    assert_eq!(node("api/__init__.py").parent_id.as_deref(), Some("package:api"));
+    assert_eq!(node("manage.py").parent_id, None);
+    assert!(node("package:api").depth < node("package:api/v1").depth);
+}
+
+#[test]
+fn test_graph_patch_rolls_files_up_into_folders() {
+    let file = |path: &str| ParsedFile {
+        path: path.to_string(),
+        language: "typescript".to_string(),
+        functions: vec![],
+        classes: vec![],
+        interfaces: vec![],
+        imports: vec![],
+        data_tables: vec![],
+        service_calls: vec![],
+        sql_queries: vec![],
+        env_vars: vec![],
+        is_test: false,
+        generated: false,
+        parse_failed: false,
+        rendered_components: vec![],
+        reexports: vec![],
+        line_count: 1,
+    };
+    let mut nested = file("a/b/c.ts");
+    nested.functions.push(super::parsers::FunctionInfo {
+        name: "render".to_string(),
+        params: vec![],
+        return_type: None,
+        start_line: 1,
+        end_line: 1,
+        calls: vec![],
+        complexity: 1,
+        body_hash: String::new(),
+        decorators: Vec::new(),
+        visibility: None,
+    });
+    let parsed_files = vec![nested, file("a/d.ts"), file("index.ts")];
+
+    let patch = build_graph_patch(&parsed_files, &graph_builder::DependencyGraph::default(), &[], &[], &[], None);
+    let folders: Vec<&str> =
+        patch.nodes.iter().filter(|node| node.node_type == "folder").map(|node| node.id.as_str()).collect();
+    assert_eq!(folders, vec!["a", "a/b"]);
+
+    let node = |id: &str| patch.nodes.iter().find(|node| node.id == id).unwrap();
+    let chain = |id: &str| {
+        let mut chain = vec![id.to_string()];
+        while let Some(parent) = node(chain.last().unwrap()).parent_id.clone() {
+            chain.push(parent);
+        }
+        chain
+    };
+    assert_eq!(chain("a/b/c.ts::render"), vec!["a/b/c.ts::render", "a/b/c.ts", "a/b", "a"]);
+    assert_eq!(chain("a/d.ts"), vec!["a/d.ts", "a"]);
+    assert_eq!(chain("index.ts"), vec!["index.ts"]);
+    assert_eq!(node("a/b").label, "b");
+    assert_eq!(
+        ["a", "a/b", "a/b/c.ts", "a/d.ts", "index.ts"].map(|id| node(id).depth),
+        [0, 1, 2, 1, 0]
+    );
+}
+
+#[test]
+fn test_path_filter_scopes_parsing_and_manifests() {
+    use std::fs;
+    use uuid::Uuid;
+
+    let temp_dir = std::env::temp_dir().join(format!("test-repo-filter-{}", Uuid::new_v4()));
+    for (path, content) in [
+        ("services/api/main.py", "def main():\n    pass\n"),
+        ("services/api/test/test_main.py", "def test_main():\n    pass\n"),
+        ("services/web/app.ts", "export function app() {}\n"),
+        ("services/web/app.spec.ts", "it('works', () => {});\n"),
+        ("services/web/package.json", r#"{"dependencies": {"react": "18.2.0"}}"#),
+        ("examples/demo.py", "def demo():\n    pass\n"),
+        ("examples/package.json", r#"{"dependencies": {"left-pad": "1.0.0"}}"#),
+    ] {
+        let path = temp_dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    let filter = PathFilter {
+        include: vec!["services/".to_string(), "examples/**".to_string()],
+        exclude: vec!["**/test/**".to_string(), "*.spec.ts".to_string(), "examples".to_string()],
+    };
+    let (parsed, stats) = super::parse_repository(&temp_dir, 2, &FileLimits::default(), &filter).expect("Parse failed");
+    let paths: Vec<&str> = parsed.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, vec!["services/api/main.py", "services/web/app.ts"]);
+    assert_eq!(stats.excluded_files, 3);
+
+    let changed = vec!["examples/demo.py".to_string(), "services/api/main.py".to_string()];
+    let (parsed, stats) =
+        super::parse_repository_subset(&temp_dir, &changed, 1, &FileLimits::default(), &filter).expect("Parse failed");
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(stats.excluded_files, 1);
+
+    let libraries = super::collect_library_dependencies(&temp_dir, &filter).expect("Manifest scan failed");
+    let names: Vec<&str> = libraries.iter().map(|lib| lib.name.as_str()).collect();
+    assert_eq!(names, vec!["react"]);
+
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+/// Write `content` to a fresh temp dir under `name` and parse it with `parse`
+fn parse_manifest_fixture(
+    name: &str,
+    content: &str,
+    parse: fn(&PathBuf, &str) -> Result<Vec<LibraryDependency>>,
+) -> Vec<(String, Option<String>)> {
+    let dir = std::env::temp_dir().join(format!("manifest-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, content).unwrap();
+    let deps = parse(&path, name).expect("Manifest parse failed");
+    let _ = fs::remove_dir_all(&dir);
+
+    assert!(deps.iter().all(|dep| dep.source_file == name));
+    deps.into_iter().map(|dep| (dep.name, dep.version)).collect()
+}
+
+fn dep(name: &str, version: Option<&str>) -> (String, Option<String>) {
+    (name.to_string(), version.map(str::to_string))
+}
+
+#[test]
+fn test_parse_pom_xml() {
+    let pom = r#"<project>
+  <properties>
+    <jackson.version>2.15.2</jackson.version>
+  </properties>
+  <dependencies>
+    <dependency>
+      <groupId>org.springframework.boot</groupId>
+      <artifactId>spring-boot-starter-web</artifactId>
+      <version>3.1.0</version>
+    </dependency>
+    <dependency>
+      <groupId>com.fasterxml.jackson.core</groupId>
+      <artifactId>jackson-databind</artifactId>
+      <version>${jackson.version}</version>
+    </dependency>
+    <!-- <dependency><groupId>old</groupId><artifactId>gone</artifactId></dependency> -->
+    <dependency>
+      <groupId>org.projectlombok</groupId>
+      <artifactId>lombok</artifactId>
+      <scope>provided</scope>
+    </dependency>
+  </dependencies>
+</project>"#;
+    assert_eq!(
+        parse_manifest_fixture("pom.xml", pom, super::parse_pom_xml),
+        vec![
+            dep("org.springframework.boot:spring-boot-starter-web", Some("3.1.0")),
+            dep("com.fasterxml.jackson.core:jackson-databind", Some("2.15.2")),
+            dep("org.projectlombok:lombok", None),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_gradle_builds() {
+    let groovy = r#"
+plugins { id 'java' }
+dependencies {
+    implementation 'com.google.guava:guava:32.1.2-jre'
+    testImplementation "org.junit.jupiter:junit-jupiter:5.10.0"
+    api 'org.slf4j:slf4j-api'
+    implementation platform('org.springframework.boot:spring-boot-dependencies:3.1.0')
+    // implementation 'commented:out:1.0'
+}
+"#;
+    assert_eq!(
+        parse_manifest_fixture("build.gradle", groovy, super::parse_gradle_build),
+        vec![
+            dep("com.google.guava:guava", Some("32.1.2-jre")),
+            dep("org.junit.jupiter:junit-jupiter", Some("5.10.0")),
+            dep("org.slf4j:slf4j-api", None),
+        ]
+    );
+
+    let kotlin = r#"
+dependencies {
+    implementation("io.ktor:ktor-server-core:2.3.4")
+    ksp("com.google.dagger:dagger-compiler:2.48")
+}
+"#;
+    assert_eq!(
+        parse_manifest_fixture("build.gradle.kts", kotlin, super::parse_gradle_build),
+        vec![
+            dep("io.ktor:ktor-server-core", Some("2.3.4")),
+            dep("com.google.dagger:dagger-compiler", Some("2.48")),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_pyproject_toml() {
+    let pep621 = r#"
+[project]
+name = "service"
+dependencies = [
+    "requests[socks]>=2.31",
+    'pydantic (>=2,<3)',
+    "uvicorn; python_version >= '3.8'",
+]
+
+[project.optional-dependencies]
+dev = ["pytest"]
+"#;
+    assert_eq!(
+        parse_manifest_fixture("pyproject.toml", pep621, super::parse_pyproject_toml),
+        vec![
+            dep("requests", Some(">=2.31")),
+            dep("pydantic", Some(">=2,<3")),
+            dep("uvicorn", None),
+        ]
+    );
+
+    let poetry = r#"
+[tool.poetry]
+name = "service"
+
+[tool.poetry.dependencies]
+python = "^3.11"
+fastapi = "^0.103.0"
+sqlalchemy = { version = "2.0.20", extras = ["asyncio"] }
+local-lib = { path = "../lib" }
+
+[tool.poetry.group.dev.dependencies]
+pytest = "^7.4"
+"#;
+    assert_eq!(
+        parse_manifest_fixture("pyproject.toml", poetry, super::parse_pyproject_toml),
+        vec![
+            dep("fastapi", Some("^0.103.0")),
+            dep("sqlalchemy", Some("2.0.20")),
+            dep("local-lib", None),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_pipfile() {
+    let pipfile = r#"
+[[source]]
+url = "https://pypi.org/simple"
+
+[packages]
+flask = "==2.3.3"
+requests = "*"
+django = {version = ">=4.2", extras = ["argon2"]}
+
+[dev-packages]
+pytest = "*"
+
+[requires]
+python_version = "3.11"
+"#;
+    assert_eq!(
+        parse_manifest_fixture("Pipfile", pipfile, super::parse_pipfile),
+        vec![
+            dep("flask", Some("==2.3.3")),
+            dep("requests", None),
+            dep("django", Some(">=4.2")),
+            dep("pytest", None),
+        ]
+    );
+}
+
+#[test]
+fn test_clone_without_commits_and_of_annotated_tag() {
+    let empty = std::env::temp_dir().join(format!("empty-repo-fixture-{}", uuid::Uuid::new_v4()));
+    git2::Repository::init(&empty).unwrap();
+    let clone = super::clone_repository(&format!("file://{}", empty.display()), "main", &None, 0, &Default::default(), None).unwrap();
+    assert_eq!(super::checkout_analyzed_commit(&clone.path, None, CommitCheckout::Force).unwrap(), None);
+    let contributions = git_analyzer::GitAnalyzer::new(&clone.path).unwrap().analyze_contributions().unwrap();
+    assert_eq!(contributions.total_commits, 0);
+    drop(clone);
+
+    let dir = std::env::temp_dir().join(format!("tag-repo-fixture-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let repo = git2::Repository::init(&dir).unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+    let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+    let commit = |content: &str| {
+        std::fs::write(dir.join("app.py"), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("app.py")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, "update", &tree, &parents).unwrap()
+    };
+    let released = commit("def handle_v1():\n    pass\n");
+    let target = repo.find_object(released, None).unwrap();
+    repo.tag("v1.0", &target, &signature, "release 1.0", false).unwrap();
+    commit("def handle_v2():\n    pass\n");
+
+    let clone = super::clone_repository(&format!("file://{}", dir.display()), "v1.0", &None, 0, &Default::default(), None).unwrap();
+    assert_eq!(super::checkout_analyzed_commit(&clone.path, None, CommitCheckout::Force).unwrap(), Some(released.to_string()));
+    assert!(git2::Repository::open(&clone.path).unwrap().head_detached().unwrap());
+    assert_eq!(std::fs::read_to_string(clone.path.join("app.py")).unwrap(), "def handle_v1():\n    pass\n");
+
+    drop(clone);
+    let _ = std::fs::remove_dir_all(&empty);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_analyze_command_writes_graph_without_services() {
+    let dir = std::env::temp_dir().join(format!("analyze-fixture-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(dir.join("app")).unwrap();
+    std::fs::write(dir.join("app/models.py"), "class User:\n    def name(self):\n        return 'user'\n").unwrap();
+    std::fs::write(dir.join("app/views.py"), "from app.models import User\n\ndef show():\n    return User().name()\n").unwrap();
+    let output = dir.join("graph.json");
+    let args = |extra: &[&str]| -> Vec<String> {
+        ["--path", dir.to_str().unwrap(), "--output", output.to_str().unwrap()]
+            .iter()
+            .chain(extra)
+            .map(|arg| arg.to_string())
+            .collect()
+    };
+
+    assert_eq!(cli::run(&args(&["--no-git"])).await, cli::EXIT_OK);
+    let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+    assert_eq!(written["summary"]["total_files"], 2);
+    assert_eq!(written["summary"]["public_api"]["total"], 3);
+    assert_eq!(written["summary"]["analyzed_commit"], serde_json::Value::Null);
+    assert!(!written["summary"]["phases"].as_array().unwrap().contains(&json!("git")));
+    let nodes = written["graph_patch"]["nodes"].as_array().unwrap();
+    assert!(nodes.iter().any(|node| node["id"] == "app/models.py"), "{:?}", nodes);
+
+    assert_eq!(cli::run(&args(&["--no-git", "--format", "graphml"])).await, cli::EXIT_OK);
+    assert!(std::fs::read_to_string(&output).unwrap().contains("<graphml"));
+
+    assert_eq!(cli::run(&args(&["--format", "csv"])).await, cli::EXIT_FAILED);
+    assert_eq!(cli::run(&["--path".to_string(), dir.join("missing").display().to_string(), "--output".to_string(), output.display().to_string()]).await, cli::EXIT_FAILED);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_streamed_parse_matches_in_memory_analysis() {
+    let corpus = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("corpus");
+    let settings = pipeline::PipelineSettings {
+        phases: phases::Phases::default().without(phases::Phase::Git),
+        path_filter: PathFilter::default(),
+        layer_rules: layering::LayerRules::default(),
+        incremental: false,
+        git_max_commits: 0,
+        git_since: None,
+        git_max_walk: None,
+        cohesion_threshold: 0.5,
+        inventory_assets: false,
+    };
+    // Nodes and edges sorted by id, so the comparison doesn't depend on set order
+    let patch = |files: &[ParsedFile], analysis: &pipeline::RepoAnalysis| {
+        let paths: Vec<String> = files.iter().map(|file| file.path.clone()).collect();
+        let mut patch = serde_json::to_value(build_graph_patch(files, &analysis.dep_graph, &analysis.packages, &paths, &[], None)).unwrap();
+        for key in ["nodes", "edges"] {
+            patch[key].as_array_mut().unwrap().sort_by_key(|item| item["id"].to_string());
+        }
+        patch
+    };
+    let edges = |analysis: &pipeline::RepoAnalysis| {
+        let mut edges: Vec<String> = analysis.dep_graph.edges.iter().map(|edge| format!("{:?}", edge)).collect();
+        edges.sort();
+        edges
+    };
+
+    for repo in ["go-inventory", "ts-dashboard", "python-shop"] {
+        let repo_path = corpus.join(repo);
+        // Parsers collect service calls through a set, so two parses of a
+        // file can list them in different orders
+        let parse = |threshold| {
+            let (mut source, stats) =
+                parse_repository_source(&repo_path, 2, &FileLimits::default(), &PathFilter::default(), threshold, 2).unwrap();
+            source
+                .update(|files| {
+                    for file in files {
+                        file.service_calls.sort_by(|a, b| (&a.target, &a.protocol).cmp(&(&b.target, &b.protocol)));
+                    }
+                })
+                .unwrap();
+            (source, stats)
+        };
+        let (mut memory, memory_stats) = parse(usize::MAX);
+        let (mut streamed, streamed_stats) = parse(0);
+        assert!(!memory.is_streamed() && streamed.is_streamed(), "{}", repo);
+
+        let whole: Vec<ParsedFile> = streamed.chunks().unwrap().flat_map(|chunk| chunk.unwrap().into_owned()).collect();
+        assert_eq!(serde_json::to_value(&whole).unwrap(), serde_json::to_value(memory.outlines()).unwrap(), "{}", repo);
+
+        let mut timings = stage_timings::StageTimings::default();
+        let from_memory = pipeline::analyze(&repo_path, &mut memory, &settings, &mut timings, &JobWarnings::default()).await.unwrap();
+        let from_stream = pipeline::analyze(&repo_path, &mut streamed, &settings, &mut timings, &JobWarnings::default()).await.unwrap();
+        assert_eq!(edges(&from_stream), edges(&from_memory), "{}", repo);
+        assert_eq!(patch(streamed.outlines(), &from_stream), patch(memory.outlines(), &from_memory), "{}", repo);
+        let summary = |files: &ParsedFileSource, stats, analysis| {
+            pipeline::analysis_summary(files.outlines(), stats, analysis, &settings, None).unwrap()
+        };
+        assert_eq!(summary(&streamed, &streamed_stats, &from_stream), summary(&memory, &memory_stats, &from_memory), "{}", repo);
+    }
+}
+
+#[tokio::test]
+async fn test_dry_run_job_analyzes_without_storing() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    let fixture = clone_fixture_repo();
+    let options = HashMap::from([(neo4j_storage::DRY_RUN_OPTION.to_string(), "true".to_string())]);
+    let dry_run = neo4j_storage::dry_run_requested(Some(&options));
+    let mode = neo4j_storage::StorageMode::for_job(false, false, dry_run);
+
+    let (mut parsed_files, parse_stats) =
+        parse_repository_source(&fixture, 1, &FileLimits::default(), &PathFilter::default(), usize::MAX, 2).unwrap();
+    let settings = pipeline::PipelineSettings {
+        phases: phases::Phases::default(),
+        path_filter: PathFilter::default(),
+        layer_rules: layering::LayerRules::default(),
+        incremental: false,
+        git_max_commits: 10,
+        git_since: None,
+        git_max_walk: None,
+        cohesion_threshold: 0.5,
+        inventory_assets: false,
+    };
+    let mut timings = stage_timings::StageTimings::default();
+    let warnings = JobWarnings::default();
+    let analysis = pipeline::analyze(&fixture, &mut parsed_files, &settings, &mut timings, &warnings).await.unwrap();
+    let _ = std::fs::remove_dir_all(&fixture);
+
+    // Every phase ran, but the store is never called
+    let stores = AtomicU32::new(0);
+    let store = |_graph: ()| {
+        stores.fetch_add(1, Ordering::SeqCst);
+        async { Ok(vec![]) }
+    };
+    let (partial_errors, replays) = neo4j_storage::store_for_mode(mode, (), store, || async { Ok(()) }).await.unwrap();
+    assert_eq!(stores.load(Ordering::SeqCst), 0);
+    assert!(analysis.git_contributions.is_some());
+
+    let mut summary =
+        pipeline::analysis_summary(parsed_files.outlines(), &parse_stats, &analysis, &settings, None).unwrap();
+    super::add_storage_to_summary(&mut summary, &partial_errors, replays, dry_run).unwrap();
+    let paths: Vec<String> = parsed_files.outlines().iter().map(|file| file.path.clone()).collect();
+    let patch = super::build_graph_patch(parsed_files.outlines(), &analysis.dep_graph, &analysis.packages, &paths, &[], None);
+    let payload = serde_json::to_string(&patch).unwrap();
+    let patch_store::PatchPlacement::Inline(payload) =
+        patch_store::place_patch("job-1", payload, patch.nodes.len(), patch.edges.len(), 1 << 20)
+    else {
+        panic!("the fixture's patch should fit inline");
+    };
+    super::add_inline_patch(&mut summary, &patch, &payload).unwrap();
+
+    assert_eq!(summary["dry_run"], true);
+    assert_eq!(summary["storage_replays"], 0);
+    assert_eq!(summary["partial_errors"], json!([]));
+    let node_ids: Vec<&str> = summary["graph_patch"]["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|node| node["id"].as_str())
+        .collect();
+    assert!(node_ids.contains(&"mod_0.py"), "{:?}", node_ids);
+    assert!(node_ids.contains(&"mod_4.py::f4"), "{:?}", node_ids);
+    assert_eq!(summary["changed_nodes"].as_array().unwrap().len(), node_ids.len());
+}
+
+#[tokio::test]
+async fn test_git_failure_is_reported_as_warning() {
+    // A plain directory: the git phase can't open a repository and goes on without it
+    let dir = std::env::temp_dir().join(format!("archmind-test-nogit-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("app.py"), "def main():\n    pass\n").unwrap();
+    let settings = pipeline::PipelineSettings {
+        phases: phases::Phases::default(),
+        path_filter: PathFilter::default(),
+        layer_rules: layering::LayerRules::default(),
+        incremental: false,
+        git_max_commits: 10,
+        git_since: None,
+        git_max_walk: None,
+        cohesion_threshold: 0.5,
+        inventory_assets: false,
+    };
+    let (parsed_files, _) = parse_repository(&dir, 1, &FileLimits::default(), &PathFilter::default()).unwrap();
+    let mut parsed_files = parsed_source::ParsedFileSource::Memory(parsed_files);
+    let warnings = JobWarnings::default();
+    let mut timings = stage_timings::StageTimings::default();
+    let sources = pipeline::analyze_sources(&dir, &mut parsed_files, &settings, &mut timings, &warnings).await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    // The files come back from the blocking pool
+    assert_eq!(parsed_files.len(), 1);
+    assert!(sources.git_contributions.is_none());
+    let recorded = warnings.warnings();
+    assert_eq!(recorded.len(), 1);
+    assert_eq!(recorded[0].code, job_warnings::GIT_OPEN_FAILED);
+
+    let mut summary = json!({});
+    warnings.add_to_summary(&mut summary);
+    assert_eq!(summary["warnings"][0]["code"], "git_open_failed");
+    assert_eq!(summary["warnings_dropped"], 0);
+}
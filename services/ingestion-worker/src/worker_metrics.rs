@@ -0,0 +1,222 @@
+//! Worker Metrics
+//!
+//! Prometheus metrics about the worker itself: jobs finished by status, job
+//! durations, files parsed per job, stage timings, how long jobs waited in
+//! the Redis queue, and Neo4j batch write latency and rows written per
+//! label. When `METRICS_PORT` is set they are served in the Prometheus text
+//! format on `/metrics`.
+
+use anyhow::{Context, Result};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use crate::stage_timings::StageTimings;
+
+/// Buckets for whole jobs and their stages, in seconds
+const DURATION_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0];
+
+/// Buckets for one Neo4j batch write, in seconds
+const BATCH_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+const FILE_COUNT_BUCKETS: &[f64] = &[10.0, 50.0, 100.0, 500.0, 1_000.0, 5_000.0, 10_000.0, 50_000.0];
+
+static METRICS: LazyLock<WorkerMetrics> = LazyLock::new(WorkerMetrics::new);
+
+/// The metrics of this worker process
+pub fn metrics() -> &'static WorkerMetrics {
+    &METRICS
+}
+
+pub struct WorkerMetrics {
+    registry: Registry,
+    jobs_processed: IntCounterVec,
+    job_duration: HistogramVec,
+    files_parsed: Histogram,
+    stage_duration: HistogramVec,
+    queue_wait: Histogram,
+    neo4j_batch_duration: HistogramVec,
+    neo4j_rows_written: IntCounterVec,
+}
+
+impl WorkerMetrics {
+    fn new() -> Self {
+        let histogram = |name: &str, help: &str, buckets: &[f64]| {
+            HistogramOpts::new(name, help).buckets(buckets.to_vec())
+        };
+        let jobs_processed = IntCounterVec::new(
+            Opts::new("ingestion_jobs_processed_total", "Jobs finished, by final status"),
+            &["status"],
+        )
+        .unwrap();
+        let job_duration = HistogramVec::new(
+            histogram("ingestion_job_duration_seconds", "Time from claiming a job to its final status", DURATION_BUCKETS),
+            &["status"],
+        )
+        .unwrap();
+        let files_parsed =
+            Histogram::with_opts(histogram("ingestion_job_files_parsed", "Files parsed per job", FILE_COUNT_BUCKETS))
+                .unwrap();
+        let stage_duration = HistogramVec::new(
+            histogram("ingestion_stage_duration_seconds", "Duration of each analysis stage", DURATION_BUCKETS),
+            &["stage"],
+        )
+        .unwrap();
+        let queue_wait = Histogram::with_opts(histogram(
+            "ingestion_queue_wait_seconds",
+            "Time jobs spent in the Redis queue before a worker claimed them",
+            DURATION_BUCKETS,
+        ))
+        .unwrap();
+        let neo4j_batch_duration = HistogramVec::new(
+            histogram("ingestion_neo4j_batch_write_seconds", "Latency of one Neo4j batch write", BATCH_BUCKETS),
+            &["label"],
+        )
+        .unwrap();
+        let neo4j_rows_written = IntCounterVec::new(
+            Opts::new("ingestion_neo4j_rows_written_total", "Nodes and edges written to Neo4j, by batch label"),
+            &["label"],
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(jobs_processed.clone())).unwrap();
+        registry.register(Box::new(job_duration.clone())).unwrap();
+        registry.register(Box::new(files_parsed.clone())).unwrap();
+        registry.register(Box::new(stage_duration.clone())).unwrap();
+        registry.register(Box::new(queue_wait.clone())).unwrap();
+        registry.register(Box::new(neo4j_batch_duration.clone())).unwrap();
+        registry.register(Box::new(neo4j_rows_written.clone())).unwrap();
+
+        Self {
+            registry,
+            jobs_processed,
+            job_duration,
+            files_parsed,
+            stage_duration,
+            queue_wait,
+            neo4j_batch_duration,
+            neo4j_rows_written,
+        }
+    }
+
+    /// A job reached its final `status` (COMPLETED, FAILED or CANCELLED)
+    pub fn job_finished(&self, status: &str, duration: Duration) {
+        let status = status.to_lowercase();
+        self.jobs_processed.with_label_values(&[&status]).inc();
+        self.job_duration.with_label_values(&[&status]).observe(duration.as_secs_f64());
+    }
+
+    pub fn files_parsed(&self, count: usize) {
+        self.files_parsed.observe(count as f64);
+    }
+
+    /// Stages a job skipped stay at zero and are left out
+    pub fn stage_timings(&self, timings: &StageTimings) {
+        for (stage, ms) in timings.stages() {
+            if ms > 0 {
+                self.stage_duration.with_label_values(&[stage]).observe(ms as f64 / 1000.0);
+            }
+        }
+    }
+
+    pub fn queue_wait(&self, wait: Duration) {
+        self.queue_wait.observe(wait.as_secs_f64());
+    }
+
+    /// One chunk of a batch insert, and the rows it wrote if it succeeded
+    pub fn neo4j_batch(&self, label: &str, duration: Duration, rows_written: usize) {
+        self.neo4j_batch_duration.with_label_values(&[label]).observe(duration.as_secs_f64());
+        self.neo4j_rows_written.with_label_values(&[label]).inc_by(rows_written as u64);
+    }
+
+    /// Every metric in the Prometheus text format
+    pub fn encode(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .context("Failed to encode metrics")?;
+        String::from_utf8(buffer).context("Metrics are not UTF-8")
+    }
+}
+
+async fn handle(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => match metrics().encode() {
+            Ok(text) => Response::builder()
+                .header(hyper::header::CONTENT_TYPE, prometheus::TEXT_FORMAT)
+                .body(Body::from(text)),
+            Err(e) => Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from(format!("{:#}", e))),
+        },
+        _ => Response::builder().status(StatusCode::NOT_FOUND).body(Body::empty()),
+    };
+    Ok(response.expect("static response parts are valid"))
+}
+
+/// Serve `/metrics` on `listener` until `shutdown` completes
+pub async fn serve(listener: std::net::TcpListener, shutdown: impl Future<Output = ()>) -> Result<()> {
+    listener.set_nonblocking(true).context("Failed to configure metrics listener")?;
+    let make_service = make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(handle)) });
+    hyper::Server::from_tcp(listener)
+        .context("Failed to start metrics server")?
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+        .context("Metrics server failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scrape_after_simulated_job() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve(listener, async {
+            let _ = stopped.await;
+        }));
+
+        let metrics = metrics();
+        metrics.queue_wait(Duration::from_secs(3));
+        metrics.files_parsed(42);
+        metrics.stage_timings(&StageTimings { clone_ms: 1_200, parse_ms: 800, total_ms: 2_000, ..Default::default() });
+        metrics.neo4j_batch("file nodes", Duration::from_millis(30), 42);
+        metrics.job_finished("COMPLETED", Duration::from_secs(2));
+        metrics.job_finished("FAILED", Duration::from_secs(1));
+
+        let client = reqwest::Client::new();
+        let response = client.get(format!("http://{}/metrics", addr)).send().await.unwrap();
+        assert!(response.status().is_success());
+        let body = response.text().await.unwrap();
+        for family in [
+            "ingestion_jobs_processed_total",
+            "ingestion_job_duration_seconds",
+            "ingestion_job_files_parsed",
+            "ingestion_stage_duration_seconds",
+            "ingestion_queue_wait_seconds",
+            "ingestion_neo4j_batch_write_seconds",
+            "ingestion_neo4j_rows_written_total",
+        ] {
+            assert!(body.contains(&format!("# TYPE {} ", family)), "missing {}:\n{}", family, body);
+        }
+        assert!(body.contains("ingestion_jobs_processed_total{status=\"failed\"}"), "{}", body);
+        assert!(body.contains("ingestion_stage_duration_seconds_count{stage=\"clone\"}"), "{}", body);
+        assert!(!body.contains("stage=\"total\""), "{}", body);
+
+        let missing = client.get(format!("http://{}/health", addr)).send().await.unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,200 @@
+//! Worker State
+//!
+//! What the worker is currently doing, shared between the job loop and the
+//! heartbeat task that publishes it to Redis as `worker:{worker_id}:heartbeat`.
+
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+pub type SharedWorkerState = Arc<Mutex<WorkerState>>;
+
+/// Pipeline stage of the current job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkerStage {
+    Idle,
+    Cloning,
+    Parsing,
+    Analyzing,
+    Storing,
+}
+
+impl WorkerStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkerStage::Idle => "idle",
+            WorkerStage::Cloning => "cloning",
+            WorkerStage::Parsing => "parsing",
+            WorkerStage::Analyzing => "analyzing",
+            WorkerStage::Storing => "storing",
+        }
+    }
+}
+
+/// A job this worker is running
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActiveJob {
+    pub job_id: String,
+    pub stage: WorkerStage,
+    pub progress: i32,
+}
+
+#[derive(Debug)]
+pub struct WorkerState {
+    worker_id: String,
+    started_at: Instant,
+    /// In start order
+    active_jobs: Vec<ActiveJob>,
+    jobs_processed: u64,
+}
+
+/// Snapshot written to Redis on every heartbeat. `job_id`, `stage` and
+/// `progress` describe the oldest active job; `active_jobs` lists them all.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Heartbeat {
+    pub worker_id: String,
+    pub uptime_secs: u64,
+    pub job_id: Option<String>,
+    pub stage: WorkerStage,
+    pub progress: Option<i32>,
+    pub active_jobs: Vec<ActiveJob>,
+    pub jobs_processed: u64,
+    pub timestamp: String,
+}
+
+impl WorkerState {
+    pub fn new(worker_id: String) -> Self {
+        Self {
+            worker_id,
+            started_at: Instant::now(),
+            active_jobs: Vec::new(),
+            jobs_processed: 0,
+        }
+    }
+
+    pub fn shared(worker_id: String) -> SharedWorkerState {
+        Arc::new(Mutex::new(Self::new(worker_id)))
+    }
+
+    pub fn worker_id(&self) -> &str {
+        &self.worker_id
+    }
+
+    pub fn start_job(&mut self, job_id: &str) {
+        self.active_jobs.push(ActiveJob {
+            job_id: job_id.to_string(),
+            stage: WorkerStage::Cloning,
+            progress: 0,
+        });
+    }
+
+    pub fn set_stage(&mut self, job_id: &str, stage: WorkerStage, progress: i32) {
+        if let Some(job) = self.active_jobs.iter_mut().find(|j| j.job_id == job_id) {
+            job.stage = stage;
+            job.progress = progress;
+        }
+    }
+
+    pub fn set_progress(&mut self, job_id: &str, progress: i32) {
+        if let Some(job) = self.active_jobs.iter_mut().find(|j| j.job_id == job_id) {
+            job.progress = progress;
+        }
+    }
+
+    /// Drop the job from the active list, whether it succeeded or failed
+    pub fn finish_job(&mut self, job_id: &str) {
+        let before = self.active_jobs.len();
+        self.active_jobs.retain(|j| j.job_id != job_id);
+        if self.active_jobs.len() < before {
+            self.jobs_processed += 1;
+        }
+    }
+
+    pub fn heartbeat(&self) -> Heartbeat {
+        let current = self.active_jobs.first();
+        Heartbeat {
+            worker_id: self.worker_id.clone(),
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            job_id: current.map(|j| j.job_id.clone()),
+            stage: current.map(|j| j.stage).unwrap_or(WorkerStage::Idle),
+            progress: current.map(|j| j.progress),
+            active_jobs: self.active_jobs.clone(),
+            jobs_processed: self.jobs_processed,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+/// Update the shared state; a poisoned lock only means a panic elsewhere, so
+/// the state is still used
+pub fn update(state: &SharedWorkerState, f: impl FnOnce(&mut WorkerState)) {
+    let mut guard = state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut guard);
+}
+
+pub fn heartbeat_key(worker_id: &str) -> String {
+    format!("worker:{}:heartbeat", worker_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_transitions() {
+        let mut state = WorkerState::new("worker-1".to_string());
+        assert_eq!(state.heartbeat().stage, WorkerStage::Idle);
+        assert_eq!(state.heartbeat().job_id, None);
+
+        state.start_job("job-42");
+        state.set_stage("job-42", WorkerStage::Parsing, 25);
+        state.set_progress("job-42", 30);
+        let beat = state.heartbeat();
+        assert_eq!(beat.job_id.as_deref(), Some("job-42"));
+        assert_eq!(beat.stage, WorkerStage::Parsing);
+        assert_eq!(beat.progress, Some(30));
+
+        // A second job is listed but the oldest one stays current
+        state.start_job("job-43");
+        let beat = state.heartbeat();
+        assert_eq!(beat.job_id.as_deref(), Some("job-42"));
+        assert_eq!(beat.active_jobs.len(), 2);
+        assert_eq!(beat.active_jobs[1].stage, WorkerStage::Cloning);
+
+        state.finish_job("job-42");
+        assert_eq!(state.heartbeat().job_id.as_deref(), Some("job-43"));
+        state.finish_job("job-43");
+        let beat = state.heartbeat();
+        assert_eq!(beat.stage, WorkerStage::Idle);
+        assert_eq!(beat.job_id, None);
+        assert_eq!(beat.progress, None);
+        assert_eq!(beat.jobs_processed, 2);
+
+        // Finishing an unknown job doesn't count
+        state.finish_job("job-42");
+        assert_eq!(state.heartbeat().jobs_processed, 2);
+    }
+
+    #[test]
+    fn test_heartbeat_serialization() {
+        let state = WorkerState::shared("worker-1".to_string());
+        update(&state, |s| {
+            s.start_job("job-42");
+            s.set_stage("job-42", WorkerStage::Storing, 75);
+        });
+
+        let beat = state.lock().unwrap().heartbeat();
+        let value = serde_json::to_value(&beat).unwrap();
+        assert_eq!(value["worker_id"], "worker-1");
+        assert_eq!(value["job_id"], "job-42");
+        assert_eq!(value["stage"], "storing");
+        assert_eq!(value["progress"], 75);
+        assert_eq!(value["active_jobs"][0]["job_id"], "job-42");
+        assert_eq!(value["jobs_processed"], 0);
+        assert!(value["uptime_secs"].is_u64());
+        assert!(value["timestamp"].is_string());
+
+        assert_eq!(heartbeat_key("worker-1"), "worker:worker-1:heartbeat");
+    }
+}
@@ -0,0 +1,33 @@
+package main
+
+import (
+	"database/sql"
+	"log"
+	"net/http"
+
+	"google.golang.org/grpc"
+
+	"example.com/inventory/internal/store"
+)
+
+func main() {
+	db, err := sql.Open("postgres", "postgres://localhost/inventory")
+	if err != nil {
+		log.Fatal(err)
+	}
+	conn, err := grpc.Dial("pricing:50051")
+	if err != nil {
+		log.Fatal(err)
+	}
+	defer conn.Close()
+	syncCatalog()
+	item, _ := store.LoadItem(db, "sku-1")
+	log.Println(item)
+}
+
+func syncCatalog() {
+	resp, err := http.Get("https://catalog.example.com/v1/items")
+	if err == nil {
+		resp.Body.Close()
+	}
+}
@@ -0,0 +1,24 @@
+package store
+
+import (
+	"database/sql"
+	"fmt"
+)
+
+type Item struct {
+	SKU   string
+	Count int
+}
+
+func LoadItem(db *sql.DB, sku string) (Item, error) {
+	var item Item
+	err := db.QueryRow("SELECT sku, count FROM items WHERE sku = $1", sku).Scan(&item.SKU, &item.Count)
+	return item, wrap(err)
+}
+
+func wrap(err error) error {
+	if err == nil {
+		return nil
+	}
+	return fmt.Errorf("store: %w", err)
+}
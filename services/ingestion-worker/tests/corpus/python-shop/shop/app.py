@@ -0,0 +1,12 @@
+import os
+
+from shop.services.billing import charge
+
+
+def main():
+    db = connect(os.environ["DATABASE_URL"])
+    print(charge(db, 42))
+
+
+def connect(url):
+    return url
@@ -0,0 +1,12 @@
+class Order:
+    def __init__(self, order_id, total):
+        self.order_id = order_id
+        self.total = total
+
+    def is_large(self):
+        return self.total > 1000
+
+
+class Customer:
+    def __init__(self, email):
+        self.email = email
@@ -0,0 +1,10 @@
+from shop.models import Order
+
+
+def load_order(db, order_id):
+    row = db.execute("SELECT id, total FROM orders WHERE id = %s", (order_id,)).fetchone()
+    return Order(row[0], row[1])
+
+
+def save_order(db, order):
+    db.execute("INSERT INTO orders (id, total) VALUES (%s, %s)", (order.order_id, order.total))
@@ -0,0 +1,16 @@
+import requests
+
+from shop.repository import load_order, save_order
+
+
+def charge(db, order_id):
+    order = load_order(db, order_id)
+    if order.is_large():
+        notify_fraud_team(order)
+    response = requests.post("https://payments.example.com/v1/charges", json={"amount": order.total})
+    save_order(db, order)
+    return response.status_code
+
+
+def notify_fraud_team(order):
+    requests.post("https://alerts.example.com/fraud", json={"order": order.order_id})
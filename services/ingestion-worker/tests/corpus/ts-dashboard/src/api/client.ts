@@ -0,0 +1,15 @@
+import axios from 'axios';
+
+export interface Metric {
+  name: string;
+  value: number;
+}
+
+export async function fetchMetrics(): Promise<Metric[]> {
+  const response = await axios.get('https://metrics.example.com/api/metrics');
+  return response.data;
+}
+
+export async function saveDashboard(layout: string): Promise<void> {
+  await fetch('https://dashboards.example.com/api/layouts', { method: 'POST', body: layout });
+}
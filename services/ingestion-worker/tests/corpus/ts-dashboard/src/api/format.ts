@@ -0,0 +1,9 @@
+import { Metric } from './client';
+
+export function formatMetric(metric: Metric): string {
+  return `${metric.name}: ${roundValue(metric.value)}`;
+}
+
+export function roundValue(value: number): number {
+  return Math.round(value * 100) / 100;
+}
@@ -0,0 +1,21 @@
+import { fetchMetrics, saveDashboard } from '../api/client';
+import { formatMetric } from '../api/format';
+
+export class Panel {
+  private lines: string[] = [];
+
+  async refresh(): Promise<void> {
+    const metrics = await fetchMetrics();
+    this.lines = metrics.map(formatMetric);
+  }
+
+  async persist(): Promise<void> {
+    await saveDashboard(this.lines.join('\n'));
+  }
+}
+
+export class ChartPanel extends Panel {
+  render(): string {
+    return 'chart';
+  }
+}
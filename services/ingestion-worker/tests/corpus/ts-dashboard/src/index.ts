@@ -0,0 +1,9 @@
+import { ChartPanel } from '@/components/Panel';
+import { missingHelper } from './utils/missing';
+
+export async function bootstrap(): Promise<string> {
+  const panel = new ChartPanel();
+  await panel.refresh();
+  missingHelper();
+  return panel.render();
+}